@@ -0,0 +1,179 @@
+//! Benchmarks of the insights and query engines on generated datasets of three
+//! shapes: wide (many columns), long (many rows) and high-cardinality (columns of
+//! mostly distinct values). Run with `make bench`; see the README for comparing
+//! against a saved baseline.
+
+use std::fmt::Write as _;
+use std::hint::black_box;
+use std::time::Duration;
+
+use chrono::{Duration as Days, NaiveDate};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use polars::prelude::DataFrame;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use g_data_pipeline::config::{Config, ConfigSources};
+use g_data_pipeline::models::profile::ProfileSettings;
+use g_data_pipeline::models::schema::SchemaOverrides;
+use g_data_pipeline::services::analysis::column_cache::ColumnStatsCache;
+use g_data_pipeline::services::fs_storage::FsStorageService;
+use g_data_pipeline::services::memory_db::MemoryDatabaseService;
+use g_data_pipeline::services::memory_redis::MemoryRedisService;
+use g_data_pipeline::services::query_translator::{ColumnOperation, QueryIntent, QueryTranslator, StructuredQuery};
+use g_data_pipeline::services::DataProcessor;
+
+type Processor = DataProcessor<FsStorageService, MemoryDatabaseService, MemoryRedisService>;
+
+/// Writes the CSV of one dataset shape
+type Generator = fn(&mut StdRng) -> Vec<u8>;
+
+const REGIONS: &[&str] = &["north", "south", "east", "west", "central", "coastal", "highlands", "islands"];
+
+/// A generated dataset: its CSV and the frame processing parses it into
+struct Dataset {
+    name: &'static str,
+    csv: Vec<u8>,
+    df: DataFrame,
+}
+
+/// 2,000 rows of 200 columns, alternating numeric and low-cardinality text
+fn wide(rng: &mut StdRng) -> Vec<u8> {
+    let columns = 200;
+    let mut csv = (0..columns).map(|c| format!("col_{}", c)).collect::<Vec<_>>().join(",");
+    csv.push('\n');
+    for _ in 0..2_000 {
+        for c in 0..columns {
+            if c > 0 {
+                csv.push(',');
+            }
+            match c % 4 {
+                0 => write!(csv, "{}", rng.gen_range(0..1_000)).unwrap(),
+                1 => write!(csv, "{:.3}", rng.gen_range(-50.0..50.0)).unwrap(),
+                2 => csv.push_str(REGIONS[rng.gen_range(0..REGIONS.len())]),
+                _ => write!(csv, "{:.2}", rng.gen_range(0.0..1.0) * c as f64).unwrap(),
+            }
+        }
+        csv.push('\n');
+    }
+    csv.into_bytes()
+}
+
+/// 200,000 rows of dated sales: a date, two categories and two measures
+fn long(rng: &mut StdRng) -> Vec<u8> {
+    let start = NaiveDate::from_ymd_opt(2022, 1, 1).unwrap();
+    let mut csv = String::from("date,region,product,units,revenue\n");
+    for _ in 0..200_000 {
+        let date = start + Days::days(rng.gen_range(0..730));
+        let region = REGIONS[rng.gen_range(0..REGIONS.len())];
+        let product = rng.gen_range(0..50);
+        let units = rng.gen_range(1..100);
+        writeln!(csv, "{},{},product_{},{},{:.2}", date, region, product, units, units as f64 * rng.gen_range(1.0..20.0)).unwrap();
+    }
+    csv.into_bytes()
+}
+
+/// 100,000 rows whose id is unique and whose city and email mostly are
+fn high_cardinality(rng: &mut StdRng) -> Vec<u8> {
+    let mut csv = String::from("customer_id,city,email,amount\n");
+    for i in 0..100_000 {
+        let city = rng.gen_range(0..20_000);
+        let user = rng.gen_range(0..80_000);
+        writeln!(csv, "cust_{:06},city_{},user{}@example.com,{:.2}", i, city, user, rng.gen_range(0.0..500.0)).unwrap();
+    }
+    csv.into_bytes()
+}
+
+fn processor() -> Processor {
+    let config = Config::load(&ConfigSources::default()).expect("Failed to load the configuration");
+    let storage = FsStorageService::new(std::env::temp_dir().join("g-data-pipeline-bench"), false)
+        .expect("Failed to create the benchmark storage directory");
+    DataProcessor::new(storage, MemoryDatabaseService::new(), MemoryRedisService::new(16, 1 << 20), &config, None)
+}
+
+fn datasets(processor: &Processor) -> Vec<Dataset> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let generators: [(&'static str, Generator); 3] = [("wide", wide), ("long", long), ("high_cardinality", high_cardinality)];
+    generators
+        .into_iter()
+        .map(|(name, generate)| {
+            let csv = generate(&mut rng);
+            let df = processor.parse_csv_data(&csv, &SchemaOverrides::new()).expect("Failed to parse a generated dataset");
+            Dataset { name, csv, df }
+        })
+        .collect()
+}
+
+fn query(intent: QueryIntent, operations: Vec<ColumnOperation>) -> StructuredQuery {
+    StructuredQuery { intent, columns: Vec::new(), operations }
+}
+
+/// Queries of the kinds conversations run most, by the dataset they run on
+fn queries() -> Vec<(&'static str, &'static str, StructuredQuery)> {
+    use ColumnOperation::*;
+    vec![
+        ("long", "group_by_sum", query(QueryIntent::Aggregate, vec![GroupBy("region".into()), Sum("revenue".into())])),
+        (
+            "long",
+            "monthly_mean",
+            query(
+                QueryIntent::Aggregate,
+                vec![DateTrunc("date".into(), "month".into()), GroupBy("date".into()), Mean("units".into())],
+            ),
+        ),
+        (
+            "long",
+            "filter_sort_limit",
+            query(
+                QueryIntent::Filter,
+                vec![
+                    Filter("units".into(), ">".into(), "50".into()),
+                    SortBy(vec![("revenue".into(), false)], true),
+                    Limit(100),
+                ],
+            ),
+        ),
+        ("long", "top_n", query(QueryIntent::Sort, vec![TopN("revenue".into(), 10, false)])),
+        ("high_cardinality", "group_by_count", query(QueryIntent::Aggregate, vec![GroupBy("city".into()), Count("amount".into())])),
+        ("high_cardinality", "distinct_count", query(QueryIntent::Aggregate, vec![DistinctCount("email".into())])),
+    ]
+}
+
+fn bench_engines(c: &mut Criterion) {
+    let processor = processor();
+    let datasets = datasets(&processor);
+
+    let mut parse = c.benchmark_group("csv_parse");
+    for dataset in &datasets {
+        parse.throughput(Throughput::Bytes(dataset.csv.len() as u64));
+        parse.bench_with_input(BenchmarkId::from_parameter(dataset.name), &dataset.csv, |b, csv| {
+            b.iter(|| processor.parse_csv_data(black_box(csv), &SchemaOverrides::new()).unwrap())
+        });
+    }
+    parse.finish();
+
+    // Nothing is cached, so every column is computed as on a first run
+    let profile = ProfileSettings::default();
+    let cold = ColumnStatsCache::default();
+    let mut insights = c.benchmark_group("generate_insights");
+    insights.sample_size(10).measurement_time(Duration::from_secs(20));
+    for dataset in &datasets {
+        insights.bench_with_input(BenchmarkId::from_parameter(dataset.name), &dataset.df, |b, df| {
+            b.iter(|| processor.generate_insights(black_box(df), &profile, &cold).unwrap())
+        });
+    }
+    insights.finish();
+
+    let translator = QueryTranslator::new();
+    let mut operations = c.benchmark_group("apply_operations");
+    for (dataset, name, query) in queries() {
+        let df = &datasets.iter().find(|d| d.name == dataset).expect("Unknown benchmark dataset").df;
+        operations.bench_function(BenchmarkId::new(dataset, name), |b| {
+            b.iter(|| translator.apply_operations(black_box(df.clone()), &query).unwrap())
+        });
+    }
+    operations.finish();
+}
+
+criterion_group!(benches, bench_engines);
+criterion_main!(benches);
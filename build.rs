@@ -0,0 +1,16 @@
+// Rebuild when a migration is added or edited, since `sqlx::migrate!` embeds them
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    // The grpc feature generates its server from `proto/`, parsed by protox so
+    // no `protoc` needs to be installed
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto");
+        let descriptors = protox::compile(["proto/pipeline.proto"], ["proto"]).expect("Failed to parse proto/pipeline.proto");
+        tonic_build::configure()
+            .build_client(false)
+            .compile_fds(descriptors)
+            .expect("Failed to generate the gRPC server");
+    }
+}
@@ -0,0 +1,14 @@
+-- Uploaded datasets and their processing status
+CREATE TABLE IF NOT EXISTS jobs (
+    id UUID PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    file_key TEXT NOT NULL,
+    content_hash TEXT,
+    status TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS jobs_user_id_idx ON jobs (user_id);
+CREATE INDEX IF NOT EXISTS jobs_content_hash_idx ON jobs (content_hash);
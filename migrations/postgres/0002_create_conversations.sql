@@ -0,0 +1,11 @@
+-- Conversations about a job's dataset; `context` holds the serialized history
+CREATE TABLE conversations (
+    id TEXT PRIMARY KEY,
+    job_id UUID NOT NULL REFERENCES jobs (id) ON DELETE CASCADE,
+    user_id TEXT,
+    context JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX conversations_job_id_idx ON conversations (job_id);
@@ -0,0 +1,10 @@
+-- Append-only log of what happened to a job (status changes, appends, downloads)
+CREATE TABLE events (
+    id BIGSERIAL PRIMARY KEY,
+    job_id UUID REFERENCES jobs (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    payload JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX events_job_id_created_at_idx ON events (job_id, created_at);
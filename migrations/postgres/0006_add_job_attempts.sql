@@ -0,0 +1,8 @@
+-- Why a job failed, how often it was attempted and when the last attempt ran
+ALTER TABLE jobs
+    ADD COLUMN error_message TEXT,
+    ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN started_at TIMESTAMPTZ,
+    ADD COLUMN finished_at TIMESTAMPTZ;
+
+CREATE INDEX jobs_status_idx ON jobs (status);
@@ -0,0 +1,2 @@
+-- `X-Request-Id` of the request that created each job, to correlate it with the worker's logs
+ALTER TABLE jobs ADD COLUMN request_id TEXT;
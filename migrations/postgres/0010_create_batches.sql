@@ -0,0 +1,11 @@
+-- Uploads of several files at once; each file's job points at its batch
+CREATE TABLE IF NOT EXISTS batches (
+    id UUID PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    tenant_id TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE jobs ADD COLUMN batch_id UUID REFERENCES batches (id);
+
+CREATE INDEX jobs_batch_id_idx ON jobs (batch_id);
@@ -0,0 +1,6 @@
+-- Tenant whose quota an AI call counts against; usage rows are kept apart per tenant
+ALTER TABLE usage ADD COLUMN tenant_id TEXT;
+
+DROP INDEX usage_key_idx;
+CREATE UNIQUE INDEX usage_key_idx ON usage (date, job_id, COALESCE(tenant_id, ''), COALESCE(user_id, ''), COALESCE(conversation_id, ''));
+CREATE INDEX usage_tenant_id_date_idx ON usage (tenant_id, date);
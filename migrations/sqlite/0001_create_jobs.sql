@@ -0,0 +1,14 @@
+-- Uploaded datasets and their processing status; ids are UUID text, timestamps RFC 3339
+CREATE TABLE IF NOT EXISTS jobs (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    file_key TEXT NOT NULL,
+    content_hash TEXT,
+    status TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS jobs_user_id_idx ON jobs (user_id);
+CREATE INDEX IF NOT EXISTS jobs_content_hash_idx ON jobs (content_hash);
@@ -0,0 +1,11 @@
+-- Conversations about a job's dataset; `context` holds the serialized history as JSON
+CREATE TABLE conversations (
+    id TEXT PRIMARY KEY,
+    job_id TEXT NOT NULL REFERENCES jobs (id) ON DELETE CASCADE,
+    user_id TEXT,
+    context TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE INDEX conversations_job_id_idx ON conversations (job_id);
@@ -0,0 +1,10 @@
+-- Append-only log of what happened to a job (status changes, appends, downloads)
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    job_id TEXT REFERENCES jobs (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    payload TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX events_job_id_created_at_idx ON events (job_id, created_at);
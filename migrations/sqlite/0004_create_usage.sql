@@ -0,0 +1,13 @@
+-- AI token usage and estimated cost per day, job, user and conversation
+CREATE TABLE usage (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL,
+    job_id TEXT NOT NULL,
+    user_id TEXT,
+    conversation_id TEXT,
+    prompt_tokens INTEGER NOT NULL DEFAULT 0,
+    completion_tokens INTEGER NOT NULL DEFAULT 0,
+    estimated_cost_usd REAL NOT NULL DEFAULT 0
+);
+
+CREATE UNIQUE INDEX usage_key_idx ON usage (date, job_id, COALESCE(user_id, ''), COALESCE(conversation_id, ''));
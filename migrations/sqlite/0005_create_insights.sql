@@ -0,0 +1,7 @@
+-- Computed insights per job as JSON, so they outlive the cache
+CREATE TABLE insights (
+    job_id TEXT PRIMARY KEY REFERENCES jobs (id) ON DELETE CASCADE,
+    insights TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,7 @@
+-- Why a job failed, how often it was attempted and when the last attempt ran
+ALTER TABLE jobs ADD COLUMN error_message TEXT;
+ALTER TABLE jobs ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE jobs ADD COLUMN started_at TEXT;
+ALTER TABLE jobs ADD COLUMN finished_at TEXT;
+
+CREATE INDEX jobs_status_idx ON jobs (status);
@@ -0,0 +1,4 @@
+-- Tenant each job belongs to; jobs from before tenants existed belong to the default tenant
+ALTER TABLE jobs ADD COLUMN tenant_id TEXT NOT NULL DEFAULT 'default';
+
+CREATE INDEX jobs_tenant_id_created_at_idx ON jobs (tenant_id, created_at);
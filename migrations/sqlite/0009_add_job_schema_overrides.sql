@@ -0,0 +1,2 @@
+-- Types columns are read as instead of the inferred ones, as a JSON object by column name
+ALTER TABLE jobs ADD COLUMN schema_overrides TEXT;
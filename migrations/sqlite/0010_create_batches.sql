@@ -0,0 +1,11 @@
+-- Uploads of several files at once; each file's job points at its batch
+CREATE TABLE IF NOT EXISTS batches (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    tenant_id TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+ALTER TABLE jobs ADD COLUMN batch_id TEXT;
+
+CREATE INDEX jobs_batch_id_idx ON jobs (batch_id);
@@ -0,0 +1,2 @@
+-- Whether the last processing ran in low-memory mode, reading the file in chunks
+ALTER TABLE jobs ADD COLUMN low_memory BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Columns, types and row count of the job's dataset, as a JSON object, recorded when it is processed
+ALTER TABLE jobs ADD COLUMN dataset_metadata TEXT;
@@ -0,0 +1,199 @@
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::config::{parse_override, Config, ConfigSources};
+use crate::models::cache::CacheBackendKind;
+use crate::models::database::DatabaseBackendKind;
+use crate::models::job::NewJob;
+use crate::models::storage::{content_hash, StorageBackendKind, DEFAULT_TENANT};
+use crate::services::ai::AIService;
+use crate::services::job_store::JobStore;
+use crate::services::registry::ServiceRegistry;
+use crate::services::{DataProcessor, DatabaseServiceTrait, S3ServiceTrait};
+
+/// Data processing API and insights engine
+#[derive(Debug, Parser)]
+#[command(name = "g-data-pipeline", version)]
+pub struct Cli {
+    /// TOML or YAML settings file; CONFIG_FILE when unset
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Override a setting, named like its environment variable (repeatable)
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE", value_parser = parse_override)]
+    pub overrides: Vec<(String, String)>,
+    /// Same as the `migrate` command, kept for existing deploy scripts
+    #[arg(long, hide = true)]
+    pub migrate: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP API (the default)
+    Serve,
+    /// Run the insights pipeline on a local CSV file and print the insights as JSON
+    Process {
+        /// CSV file to analyze
+        file: PathBuf,
+        /// Skip the AI summary even when an LLM provider is configured
+        #[arg(long)]
+        no_ai: bool,
+        /// Print the JSON on a single line
+        #[arg(long)]
+        compact: bool,
+    },
+    /// Apply pending job database migrations and exit
+    Migrate,
+    /// Validate the configuration and print the selected backends
+    CheckConfig,
+}
+
+impl Cli {
+    /// The command to run; `serve` when none is given
+    pub fn command(&self) -> &Command {
+        match &self.command {
+            Some(command) => command,
+            None if self.migrate => &Command::Migrate,
+            None => &Command::Serve,
+        }
+    }
+
+    pub fn config_sources(&self) -> ConfigSources {
+        ConfigSources {
+            file: self.config.clone(),
+            overrides: self.overrides.clone(),
+        }
+    }
+}
+
+/// Apply the job database migrations
+pub async fn migrate(config: &Config) -> Result<()> {
+    let db_service = JobStore::from_config(config).await?;
+    db_service.migrate().await?;
+    log::info!("📜 Job database schema is up to date");
+    Ok(())
+}
+
+/// Print the settings that decide which services run, after checking the
+/// TLS certificate loads
+pub fn check_config(config: &Config, sources: &ConfigSources) -> Result<()> {
+    crate::config::tls::server_config(config)?;
+    let file = sources
+        .file
+        .clone()
+        .or_else(|| std::env::var("CONFIG_FILE").ok().filter(|v| !v.trim().is_empty()).map(PathBuf::from));
+    println!("Configuration is valid");
+    match file {
+        Some(path) => println!("  config file:     {}", path.display()),
+        None => println!("  config file:     none (environment only)"),
+    }
+    println!("  job database:    {}", config.database_backend);
+    println!("  cache:           {}", config.cache_backend);
+    println!("  storage:         {}", config.storage_backend);
+    match &config.auth_jwks_url {
+        Some(jwks_url) => println!("  authentication:  bearer tokens verified against {}", jwks_url),
+        None => println!("  authentication:  off"),
+    }
+    match &config.admin_token {
+        Some(_) => println!("  admin api:       on (ADMIN_TOKEN)"),
+        None => println!("  admin api:       off"),
+    }
+    match config.processing_memory_budget_bytes {
+        0 => println!("  memory budget:   none"),
+        budget => println!("  memory budget:   {} bytes, spilling to {}", budget, config.spill_dir.display()),
+    }
+    match config.max_upload_bytes {
+        0 => println!("  max upload:      none"),
+        max => println!("  max upload:      {} bytes", max),
+    }
+    println!(
+        "  batch uploads:   up to {} files, {} bytes per fetched URL",
+        config.batch_max_files, config.batch_fetch_max_bytes
+    );
+    println!(
+        "  tenant quotas:   {} storage bytes, {} jobs/day, {} AI tokens/day (0 = unlimited)",
+        config.tenant_max_storage_bytes, config.tenant_max_jobs_per_day, config.tenant_max_ai_tokens_per_day
+    );
+    println!("  llm provider:    {}", config.llm_provider);
+    println!("  insight profile: {}", config.insight_profile);
+    println!("  server address:  {}:{}", config.server_host, config.server_port);
+    match &config.tls_cert_path {
+        Some(cert_path) => println!("  tls certificate: {}", cert_path),
+        None => println!("  tls certificate: none (plain HTTP)"),
+    }
+    match config.grpc_port {
+        Some(port) if cfg!(feature = "grpc") => println!("  grpc address:    {}:{}", config.server_host, port),
+        Some(_) => println!("  grpc address:    off (GRPC_PORT needs a build with the grpc feature)"),
+        None => println!("  grpc address:    off"),
+    }
+    Ok(())
+}
+
+/// Run a CSV file through the same pipeline as an upload, using in-process
+/// services and a scratch storage directory, and return the insights JSON
+pub async fn process(mut config: Config, file: &Path, no_ai: bool, compact: bool) -> Result<String> {
+    let data = tokio::fs::read(file)
+        .await
+        .with_context(|| format!("Failed to read {}", file.display()))?;
+    if data.is_empty() {
+        bail!("{} is empty", file.display());
+    }
+    if no_ai {
+        config.ai_analysis_enabled = false;
+    }
+
+    let scratch = std::env::temp_dir().join(format!("g-data-pipeline-{}", Uuid::new_v4()));
+    let result = process_in(&config, &scratch, data).await;
+    if let Err(e) = tokio::fs::remove_dir_all(&scratch).await {
+        log::warn!("⚠️ Failed to remove scratch directory {}: {}", scratch.display(), e);
+    }
+    let insights: serde_json::Value = serde_json::from_str(&result?)?;
+    Ok(if compact {
+        serde_json::to_string(&insights)?
+    } else {
+        serde_json::to_string_pretty(&insights)?
+    })
+}
+
+async fn process_in(config: &Config, scratch: &Path, data: Vec<u8>) -> Result<String> {
+    // Keep everything in this process, with files under the scratch directory
+    let mut config = config.clone();
+    config.storage_backend = StorageBackendKind::Fs;
+    config.storage_root = scratch.display().to_string();
+    config.database_backend = DatabaseBackendKind::Memory;
+    config.cache_backend = CacheBackendKind::Memory;
+    config.memory_cache_sweep_interval_secs = 0;
+    let services = ServiceRegistry::builder(&config).migrate(false).build().await?;
+    let s3_service = services.storage.clone();
+    let db_service = services.jobs.clone();
+
+    let ai_service = match config.ai_analysis_enabled {
+        true => AIService::new(&config)?,
+        false => None,
+    };
+    let processor = DataProcessor::new(services.storage, services.jobs, services.cache, &config, ai_service);
+
+    let hash = content_hash(&data);
+    let file_key = format!("uploads/{}.csv", Uuid::new_v4());
+    s3_service.upload_file(&file_key, data.into()).await?;
+    let job_id = db_service
+        .create_job(NewJob {
+            user_id: "cli".to_string(),
+            tenant_id: DEFAULT_TENANT.to_string(),
+            request_id: None,
+            bucket: processor.bucket().to_string(),
+            file_key,
+            content_hash: hash,
+            batch_id: None,
+        })
+        .await?;
+
+    processor.process_job(job_id).await?;
+    match processor.insights(job_id).await? {
+        Some(insights) => Ok(insights),
+        None => bail!("Processing finished without insights"),
+    }
+}
@@ -1,36 +1,335 @@
+mod sources;
+pub mod tls;
+
+use anyhow::Result;
 use dotenv::dotenv;
-use std::env;
+use std::path::PathBuf;
+
+pub use sources::{parse_override, ConfigSources};
+use sources::Settings;
+
+use crate::models::cache::CacheBackendKind;
+use crate::models::database::DatabaseBackendKind;
+use crate::models::llm::{LlmProviderKind, ModelSettings, RetrySettings};
+use crate::models::profile::InsightProfile;
+use crate::models::storage::StorageBackendKind;
+
+/// Jobs that can wait in the processing queue before new ones are turned away
+pub const JOB_QUEUE_CAPACITY: usize = 32;
 
 #[derive(Debug, Clone)]
 pub struct Config {
+    // Only read by the Postgres backend, compiled with the external-services feature
+    #[allow(dead_code)]
     pub database_url: String,
+    /// Where jobs are tracked
+    pub database_backend: DatabaseBackendKind,
+    /// Connections the Postgres or SQLite pool opens at most
+    #[allow(dead_code)]
+    pub database_max_connections: u32,
+    /// Apply pending schema migrations at startup
+    pub database_auto_migrate: bool,
+    /// File the SQLite backend keeps jobs in
+    #[allow(dead_code)]
+    pub sqlite_path: String,
+    /// Where insights, aggregate state and AI replies are cached
+    pub cache_backend: CacheBackendKind,
+    /// Entries the memory cache holds before evicting the least recently used
+    pub memory_cache_max_entries: usize,
+    /// Bytes of keys and values the memory cache holds before evicting the least recently used
+    pub memory_cache_max_bytes: usize,
+    /// Seconds between sweeps of expired memory cache entries; 0 disables them
+    pub memory_cache_sweep_interval_secs: u64,
+    // Only read by the Redis backend, compiled with the external-services feature
+    #[allow(dead_code)]
     pub redis_url: String,
+    /// Connections the Redis pool opens at most
+    #[allow(dead_code)]
+    pub redis_pool_size: usize,
+    /// Where datasets are stored
+    pub storage_backend: StorageBackendKind,
+    /// Directory the filesystem backend stores objects under
+    pub storage_root: String,
+    /// Bytes of objects the memory backend keeps in memory before evicting the least recently used
+    pub memory_storage_max_bytes: usize,
+    /// Whether the fs and memory backends fsync each object and its directory before a write returns
+    pub storage_fsync: bool,
+    // Only read by the S3 backend, compiled with the external-services feature
+    #[allow(dead_code)]
     pub aws_region: String,
     pub s3_bucket: String,
+    /// Endpoint of an S3-compatible store such as MinIO or R2, instead of AWS
+    #[allow(dead_code)]
+    pub s3_endpoint: Option<String>,
+    /// Static credentials for the S3 backend; the AWS credential chain is used when unset
+    #[allow(dead_code)]
+    pub s3_access_key_id: Option<String>,
+    #[allow(dead_code)]
+    pub s3_secret_access_key: Option<String>,
+    /// Seconds a presigned dataset download URL stays valid
+    pub download_url_expiry_secs: u64,
+    /// Base64 256-bit master key; stored datasets are encrypted when set
+    pub storage_encryption_key: Option<String>,
+    /// Store identical uploads once, shared by every job that uploaded them
+    pub storage_dedup: bool,
+    /// Seconds between checks for jobs whose files are missing from storage; 0 disables them
+    pub reconcile_interval_secs: u64,
+    /// Seconds a job's processing lock is held before another instance may take it over
+    pub job_lock_ttl_secs: u64,
+    /// Seconds clients are told to wait before retrying when the job queue is full
+    pub job_queue_retry_after_secs: u64,
+    /// Most files one batch upload may hold; at most the job queue's capacity, since a
+    /// batch is only accepted when every file can be queued at once
+    pub batch_max_files: usize,
+    /// Largest file a batch upload fetches from a URL
+    pub batch_fetch_max_bytes: u64,
+    /// Largest file `/upload` and appends accept; 0 for no limit
+    pub max_upload_bytes: u64,
+    /// Bytes a job's parsed dataset may take before it is processed in chunks; 0 for no limit
+    pub processing_memory_budget_bytes: u64,
+    /// Directory uploads are written to while processed in chunks
+    pub spill_dir: PathBuf,
+    /// Address the HTTP server binds to
+    pub server_host: String,
     pub server_port: u16,
+    /// PEM certificate chain and private key; the server speaks HTTPS when both are set
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    /// Port the gRPC server listens on at `server_host`; only served by builds with the grpc feature
+    #[allow(dead_code)]
+    pub grpc_port: Option<u16>,
+    /// Largest file a gRPC upload may stream, across all of its chunks
+    #[allow(dead_code)]
+    pub grpc_max_upload_bytes: u64,
+    /// JWKS of the identity provider; requests need a Bearer JWT signed by one of its keys when set
+    pub auth_jwks_url: Option<String>,
+    /// Required `iss` claim of tokens
+    pub auth_issuer: Option<String>,
+    /// Required `aud` claim of tokens
+    pub auth_audience: Option<String>,
+    /// Seconds the JWKS is cached before it is fetched again
+    pub auth_jwks_cache_secs: u64,
+    /// Token claim naming the caller's tenant; tokens without it make the user their own tenant
+    pub auth_tenant_claim: String,
+    /// Bearer token of the `/admin` endpoints, which answer 401 while it is unset
+    pub admin_token: Option<String>,
+    /// Bytes of objects a tenant may store; 0 for no limit
+    pub tenant_max_storage_bytes: u64,
+    /// Jobs a tenant may create per UTC day; 0 for no limit
+    pub tenant_max_jobs_per_day: u64,
+    /// AI tokens a tenant may spend per UTC day before AI features fall back to rules; 0 for no limit
+    pub tenant_max_ai_tokens_per_day: u64,
     pub open_ai_key: Option<String>,
+    /// LLM backend for the AI features
+    pub llm_provider: LlmProviderKind,
+    /// Model name, or the provider's default when unset
+    pub llm_model: Option<String>,
+    /// API root for Azure (the resource endpoint) and local servers, or a proxy in front of OpenAI/Anthropic
+    pub llm_base_url: Option<String>,
+    /// Key for Azure, Anthropic and local servers; OpenAI and Azure fall back to `open_ai_key`
+    pub llm_api_key: Option<String>,
+    /// Azure OpenAI deployment the requests are sent to
+    pub azure_openai_deployment: Option<String>,
+    pub azure_openai_api_version: String,
+    /// Idle connections to the LLM provider kept open for reuse
+    pub llm_pool_max_idle_per_host: usize,
+    /// Seconds an idle provider connection stays in the pool
+    pub llm_pool_idle_timeout_secs: u64,
+    /// Settings for dataset summaries and answer narration
+    pub ai_summary: ModelSettings,
+    /// Settings for query translation, conversation summaries and suggested questions
+    pub ai_translation: ModelSettings,
+    /// Retries and circuit breaking around AI calls
+    pub ai_retry: RetrySettings,
+    /// AI calls in flight at once, across all jobs and conversations
+    pub ai_max_concurrent_calls: usize,
+    /// AI calls started per minute; 0 for no limit
+    pub ai_max_calls_per_minute: usize,
+    /// Seconds identical AI requests are answered from the cache; 0 disables it
+    pub ai_cache_ttl_secs: u64,
+    /// Run the AI summary as part of job processing (needs a configured LLM provider)
+    pub ai_analysis_enabled: bool,
+    /// Datasets with more rows than this get their statistics computed on a sample
+    pub sample_threshold_rows: usize,
+    /// Number of rows drawn when sampling
+    pub sample_size: usize,
+    /// Default insight profile for uploads that do not request one
+    pub insight_profile: InsightProfile,
+    /// Seconds a query may run once its dataset is loaded
+    pub query_timeout_secs: u64,
+    /// Largest in-memory dataset, in bytes, a query may run over
+    pub query_max_dataset_bytes: usize,
+    /// Largest result, in rows, a query may produce
+    pub query_max_result_rows: usize,
+    /// Conversation turns sent verbatim to the translator before older ones are summarized
+    pub history_max_turns: usize,
+    /// Estimated tokens of verbatim turns sent to the translator before older ones are summarized
+    pub history_max_tokens: usize,
+    /// Result rows stored with each conversation turn; 0 keeps only the chart
+    pub snapshot_max_rows: usize,
 }
 
 impl Config {
-    pub fn from_env() -> Self {
+    /// Settings from a config file, environment variables (after `.env`) and
+    /// command-line overrides, each taking precedence over the one before
+    pub fn load(sources: &ConfigSources) -> Result<Self> {
         dotenv().ok();
+        let mut settings = Settings::load(sources)?;
+
+        let database_backend: DatabaseBackendKind = settings.parse("DATABASE_BACKEND").unwrap_or_default();
+        let cache_backend: CacheBackendKind = settings.parse("CACHE_BACKEND").unwrap_or_default();
+        let storage_backend: StorageBackendKind = settings.parse("STORAGE_BACKEND").unwrap_or_default();
 
-        // Get OpenAI API key as optional
-        let open_ai_key = match env::var("OPEN_AI_KEY") {
-            Ok(key) if !key.trim().is_empty() => Some(key),
-            _ => None,
+        // Connection settings are only required by the backends that use them
+        let database_url = match database_backend {
+            DatabaseBackendKind::Postgres => settings.require("DATABASE_URL", "for the postgres job database"),
+            _ => settings.string("DATABASE_URL").unwrap_or_default(),
+        };
+        let redis_url = match cache_backend {
+            CacheBackendKind::Redis => settings.require("REDIS_URL", "for the redis cache"),
+            CacheBackendKind::Memory => settings.string("REDIS_URL").unwrap_or_default(),
+        };
+        let s3_bucket = match storage_backend {
+            StorageBackendKind::S3 => settings.require("S3_BUCKET", "for the s3 storage backend"),
+            _ => settings.string("S3_BUCKET").unwrap_or_else(|| "default-bucket".to_string()),
         };
 
-        Self {
-            database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
-            redis_url: env::var("REDIS_URL").expect("REDIS_URL must be set"),
-            aws_region: env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
-            s3_bucket: env::var("S3_BUCKET").expect("S3_BUCKET must be set"),
-            server_port: env::var("SERVER_PORT")
-                .unwrap_or_else(|_| "8080".to_string())
-                .parse()
-                .expect("SERVER_PORT must be a valid port number"),
-            open_ai_key,
+        // TLS needs both halves of the key pair
+        let tls_cert_path = settings.string("TLS_CERT_PATH");
+        let tls_key_path = settings.string("TLS_KEY_PATH");
+        if tls_cert_path.is_some() && tls_key_path.is_none() {
+            settings.require("TLS_KEY_PATH", "with TLS_CERT_PATH");
         }
+        if tls_key_path.is_some() && tls_cert_path.is_none() {
+            settings.require("TLS_CERT_PATH", "with TLS_KEY_PATH");
+        }
+
+        let config = Self {
+            database_url,
+            database_backend,
+            database_max_connections: settings
+                .parse("DATABASE_MAX_CONNECTIONS")
+                .filter(|n| *n > 0)
+                .unwrap_or(10),
+            database_auto_migrate: settings.flag("DATABASE_AUTO_MIGRATE").unwrap_or(true),
+            sqlite_path: settings.string("SQLITE_PATH").unwrap_or_else(|| "./data/jobs.db".to_string()),
+            cache_backend,
+            memory_cache_max_entries: settings.parse("MEMORY_CACHE_MAX_ENTRIES").unwrap_or(10_000),
+            memory_cache_max_bytes: settings.parse("MEMORY_CACHE_MAX_BYTES").unwrap_or(64 * 1024 * 1024),
+            memory_cache_sweep_interval_secs: settings.parse("MEMORY_CACHE_SWEEP_INTERVAL_SECS").unwrap_or(60),
+            redis_url,
+            redis_pool_size: settings.parse("REDIS_POOL_SIZE").filter(|n| *n > 0).unwrap_or(16),
+            storage_backend,
+            storage_root: settings.string("STORAGE_ROOT").unwrap_or_else(|| "./storage".to_string()),
+            memory_storage_max_bytes: settings.parse("MEMORY_STORAGE_MAX_BYTES").unwrap_or(256 * 1024 * 1024),
+            storage_fsync: settings.flag("STORAGE_FSYNC").unwrap_or(true),
+            aws_region: settings.string("AWS_REGION").unwrap_or_else(|| "us-east-1".to_string()),
+            s3_bucket,
+            s3_endpoint: settings.string("S3_ENDPOINT"),
+            s3_access_key_id: settings.string("S3_ACCESS_KEY_ID"),
+            s3_secret_access_key: settings.string("S3_SECRET_ACCESS_KEY"),
+            download_url_expiry_secs: settings
+                .parse("DOWNLOAD_URL_EXPIRY_SECS")
+                .filter(|secs| *secs > 0)
+                .unwrap_or(900),
+            storage_encryption_key: settings.string("STORAGE_ENCRYPTION_KEY"),
+            storage_dedup: settings.flag("STORAGE_DEDUP").unwrap_or(false),
+            reconcile_interval_secs: settings.parse("RECONCILE_INTERVAL_SECS").unwrap_or(3600),
+            job_lock_ttl_secs: settings.parse("JOB_LOCK_TTL_SECS").filter(|secs| *secs > 0).unwrap_or(1800),
+            job_queue_retry_after_secs: settings.parse("JOB_QUEUE_RETRY_AFTER_SECS").filter(|secs| *secs > 0).unwrap_or(5),
+            batch_max_files: settings
+                .parse("BATCH_MAX_FILES")
+                .filter(|files| *files > 0)
+                .map_or(JOB_QUEUE_CAPACITY, |files: usize| files.min(JOB_QUEUE_CAPACITY)),
+            batch_fetch_max_bytes: settings.parse("BATCH_FETCH_MAX_BYTES").unwrap_or(100 * 1024 * 1024),
+            max_upload_bytes: settings.parse("MAX_UPLOAD_BYTES").unwrap_or(100 * 1024 * 1024),
+            processing_memory_budget_bytes: settings
+                .parse("PROCESSING_MEMORY_BUDGET_BYTES")
+                .unwrap_or(2 * 1024 * 1024 * 1024),
+            spill_dir: settings.string("SPILL_DIR").map(PathBuf::from).unwrap_or_else(std::env::temp_dir),
+            server_host: settings.string("SERVER_HOST").unwrap_or_else(|| "127.0.0.1".to_string()),
+            server_port: settings.parse("SERVER_PORT").unwrap_or(8080),
+            tls_cert_path,
+            tls_key_path,
+            grpc_port: settings.parse("GRPC_PORT"),
+            grpc_max_upload_bytes: settings.parse("GRPC_MAX_UPLOAD_BYTES").unwrap_or(100 * 1024 * 1024),
+            auth_jwks_url: settings.string("AUTH_JWKS_URL"),
+            auth_issuer: settings.string("AUTH_ISSUER"),
+            auth_audience: settings.string("AUTH_AUDIENCE"),
+            auth_jwks_cache_secs: settings.parse("AUTH_JWKS_CACHE_SECS").unwrap_or(3600),
+            auth_tenant_claim: settings.string("AUTH_TENANT_CLAIM").unwrap_or_else(|| "tenant_id".to_string()),
+            admin_token: settings.string("ADMIN_TOKEN"),
+            tenant_max_storage_bytes: settings.parse("TENANT_MAX_STORAGE_BYTES").unwrap_or(0),
+            tenant_max_jobs_per_day: settings.parse("TENANT_MAX_JOBS_PER_DAY").unwrap_or(0),
+            tenant_max_ai_tokens_per_day: settings.parse("TENANT_MAX_AI_TOKENS_PER_DAY").unwrap_or(0),
+            open_ai_key: settings.string("OPEN_AI_KEY"),
+            llm_provider: settings.parse("LLM_PROVIDER").unwrap_or_default(),
+            llm_model: settings.string("LLM_MODEL"),
+            llm_base_url: settings.string("LLM_BASE_URL"),
+            llm_api_key: settings.string("LLM_API_KEY"),
+            azure_openai_deployment: settings.string("AZURE_OPENAI_DEPLOYMENT"),
+            azure_openai_api_version: settings
+                .string("AZURE_OPENAI_API_VERSION")
+                .unwrap_or_else(|| "2024-06-01".to_string()),
+            llm_pool_max_idle_per_host: settings.parse("LLM_POOL_MAX_IDLE_PER_HOST").unwrap_or(16),
+            llm_pool_idle_timeout_secs: settings.parse("LLM_POOL_IDLE_TIMEOUT_SECS").unwrap_or(90),
+            ai_summary: model_settings(&mut settings, "AI_SUMMARY", 30),
+            ai_translation: model_settings(&mut settings, "AI_TRANSLATION", 15),
+            ai_retry: retry_settings(&mut settings),
+            ai_max_concurrent_calls: settings
+                .parse("AI_MAX_CONCURRENT_CALLS")
+                .filter(|calls| *calls > 0)
+                .unwrap_or(8),
+            ai_max_calls_per_minute: settings.parse("AI_MAX_CALLS_PER_MINUTE").unwrap_or(300),
+            ai_cache_ttl_secs: settings.parse("AI_CACHE_TTL_SECS").unwrap_or(24 * 3600),
+            ai_analysis_enabled: settings.flag("AI_ANALYSIS_ENABLED").unwrap_or(true),
+            sample_threshold_rows: settings.parse("INSIGHTS_SAMPLE_THRESHOLD_ROWS").unwrap_or(1_000_000),
+            sample_size: settings.parse("INSIGHTS_SAMPLE_SIZE").unwrap_or(100_000),
+            insight_profile: settings.parse("INSIGHT_PROFILE").unwrap_or_default(),
+            query_timeout_secs: settings.parse("QUERY_TIMEOUT_SECS").unwrap_or(30),
+            query_max_dataset_bytes: settings
+                .parse("QUERY_MAX_DATASET_BYTES")
+                .unwrap_or(4 * 1024 * 1024 * 1024),
+            query_max_result_rows: settings.parse("QUERY_MAX_RESULT_ROWS").unwrap_or(1_000_000),
+            history_max_turns: settings.parse("HISTORY_MAX_TURNS").filter(|turns| *turns > 0).unwrap_or(10),
+            history_max_tokens: settings.parse("HISTORY_MAX_TOKENS").unwrap_or(2_000),
+            snapshot_max_rows: settings.parse("SNAPSHOT_MAX_ROWS").unwrap_or(50),
+        };
+
+        settings.finish()?;
+        Ok(config)
+    }
+}
+
+/// Model settings read from `{prefix}_MODEL`, `{prefix}_TEMPERATURE`,
+/// `{prefix}_MAX_TOKENS` and `{prefix}_TIMEOUT_SECS`
+fn model_settings(settings: &mut Settings, prefix: &str, default_timeout_secs: u64) -> ModelSettings {
+    ModelSettings {
+        model: settings.string(&format!("{}_MODEL", prefix)),
+        temperature: settings
+            .parse(&format!("{}_TEMPERATURE", prefix))
+            .filter(|t: &f64| (0.0..=2.0).contains(t)),
+        max_tokens: settings
+            .parse(&format!("{}_MAX_TOKENS", prefix))
+            .filter(|tokens| *tokens > 0),
+        timeout_secs: settings
+            .parse(&format!("{}_TIMEOUT_SECS", prefix))
+            .filter(|secs| *secs > 0)
+            .unwrap_or(default_timeout_secs),
+    }
+}
+
+/// Retry settings read from the `AI_RETRY_*` and `AI_CIRCUIT_*` settings
+fn retry_settings(settings: &mut Settings) -> RetrySettings {
+    let defaults = RetrySettings::default();
+    let mut var = |name: &str| settings.parse::<u64>(name);
+    RetrySettings {
+        max_retries: var("AI_MAX_RETRIES").map_or(defaults.max_retries, |n| n.min(10) as u32),
+        base_delay_ms: var("AI_RETRY_BASE_DELAY_MS").unwrap_or(defaults.base_delay_ms),
+        max_delay_ms: var("AI_RETRY_MAX_DELAY_MS").unwrap_or(defaults.max_delay_ms),
+        circuit_failure_threshold: var("AI_CIRCUIT_FAILURE_THRESHOLD")
+            .filter(|n| *n > 0)
+            .map_or(defaults.circuit_failure_threshold, |n| n as u32),
+        circuit_cooldown_secs: var("AI_CIRCUIT_COOLDOWN_SECS").unwrap_or(defaults.circuit_cooldown_secs),
     }
 }
@@ -0,0 +1,156 @@
+use anyhow::{bail, Context, Result};
+use figment::providers::{Format, Serialized, Toml, Yaml};
+use figment::value::{Dict, Value};
+use figment::Figment;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Where settings are read from, lowest precedence first: a TOML or YAML file,
+/// environment variables, then overrides given on the command line
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    /// Settings file, from `--config` or `CONFIG_FILE`
+    pub file: Option<PathBuf>,
+    /// `KEY=VALUE` pairs from `--set`, named like the environment variables
+    pub overrides: Vec<(String, String)>,
+}
+
+/// A `KEY=VALUE` override given with `--set`
+pub fn parse_override(pair: &str) -> Result<(String, String)> {
+    match pair.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => bail!("Invalid override '{}' (expected KEY=VALUE)", pair),
+    }
+}
+
+/// Settings merged from every source, looked up by their environment variable
+/// names. Values that fail to parse are collected so they can all be reported
+/// at once instead of stopping at the first.
+pub(super) struct Settings {
+    values: HashMap<String, String>,
+    problems: Vec<String>,
+}
+
+impl Settings {
+    pub fn load(sources: &ConfigSources) -> Result<Self> {
+        let mut figment = Figment::new();
+
+        let file = sources
+            .file
+            .clone()
+            .or_else(|| env::var("CONFIG_FILE").ok().filter(|v| !v.trim().is_empty()).map(PathBuf::from));
+        if let Some(path) = file {
+            if !path.is_file() {
+                bail!("Config file {} does not exist", path.display());
+            }
+            figment = match extension(&path).as_deref() {
+                Some("toml") => figment.merge(Toml::file_exact(&path)),
+                Some("yaml") | Some("yml") => figment.merge(Yaml::file_exact(&path)),
+                _ => bail!("Config file {} must end in .toml, .yaml or .yml", path.display()),
+            };
+        }
+
+        // Environment variables and overrides are merged as plain strings, so
+        // values such as keys are never reinterpreted as numbers or lists
+        let environment: HashMap<String, String> = env::vars().map(|(k, v)| (k.to_lowercase(), v)).collect();
+        let overrides: HashMap<String, String> =
+            sources.overrides.iter().map(|(k, v)| (k.to_lowercase(), v.clone())).collect();
+        figment = figment.merge(Serialized::defaults(environment)).merge(Serialized::defaults(overrides));
+
+        let merged: Dict = figment.extract().context("Failed to read the configuration")?;
+        let mut values = HashMap::new();
+        flatten("", merged, &mut values)?;
+        Ok(Self { values, problems: Vec::new() })
+    }
+
+    /// A setting that is set to something other than whitespace
+    pub fn string(&self, key: &str) -> Option<String> {
+        self.values
+            .get(&key.to_lowercase())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// A parsed setting; unparsable values are recorded as problems
+    pub fn parse<T>(&mut self, key: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let raw = self.string(key)?;
+        match raw.parse() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.problems.push(format!("{}: {} (got '{}')", key, e, raw));
+                None
+            }
+        }
+    }
+
+    /// A true/false setting, also accepting 1/0, yes/no and on/off
+    pub fn flag(&mut self, key: &str) -> Option<bool> {
+        let raw = self.string(key)?;
+        match raw.to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Some(true),
+            "false" | "0" | "no" | "off" => Some(false),
+            _ => {
+                self.problems.push(format!("{}: expected true or false (got '{}')", key, raw));
+                None
+            }
+        }
+    }
+
+    /// A setting that must be present, because of `reason`
+    pub fn require(&mut self, key: &str, reason: &str) -> String {
+        self.string(key).unwrap_or_else(|| {
+            self.problems.push(format!("{} must be set {}", key, reason));
+            String::new()
+        })
+    }
+
+    /// Fails with every problem found while reading the settings
+    pub fn finish(self) -> Result<()> {
+        if self.problems.is_empty() {
+            return Ok(());
+        }
+        bail!("Invalid configuration:\n  - {}", self.problems.join("\n  - "))
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase())
+}
+
+/// Flattens nested tables into variable names, so `[database] url = ...` in a
+/// file sets `DATABASE_URL`
+fn flatten(prefix: &str, dict: Dict, values: &mut HashMap<String, String>) -> Result<()> {
+    for (key, value) in dict {
+        let name = if prefix.is_empty() {
+            key.to_lowercase()
+        } else {
+            format!("{}_{}", prefix, key.to_lowercase())
+        };
+        let scalar = match value {
+            Value::Dict(_, nested) => {
+                flatten(&name, nested, values)?;
+                continue;
+            }
+            Value::String(_, s) => s,
+            Value::Char(_, c) => c.to_string(),
+            Value::Bool(_, b) => b.to_string(),
+            Value::Num(_, n) => n
+                .to_u128()
+                .map(|v| v.to_string())
+                .or_else(|| n.to_i128().map(|v| v.to_string()))
+                .or_else(|| n.to_f64().map(|v| v.to_string()))
+                .unwrap_or_default(),
+            Value::Empty(..) => String::new(),
+            Value::Array(..) => bail!("{}: expected a single value, not a list", name.to_uppercase()),
+        };
+        values.insert(name, scalar);
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use anyhow::{bail, Context, Result};
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use rustls_pemfile::Item;
+use std::fs::File;
+use std::io::BufReader;
+
+use super::Config;
+
+/// The rustls server settings for `TLS_CERT_PATH` and `TLS_KEY_PATH`, or `None`
+/// when TLS is not configured
+pub fn server_config(config: &Config) -> Result<Option<ServerConfig>> {
+    let (Some(cert_path), Some(key_path)) = (&config.tls_cert_path, &config.tls_key_path) else {
+        return Ok(None);
+    };
+
+    let certs: Vec<Certificate> = read_pem(cert_path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect();
+    if certs.is_empty() {
+        bail!("TLS_CERT_PATH {} holds no PEM certificates", cert_path);
+    }
+
+    let key = read_pem(key_path)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(der) | Item::RSAKey(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .with_context(|| format!("TLS_KEY_PATH {} holds no PEM private key", key_path))?;
+
+    let server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("TLS certificate and key do not form a usable pair")?;
+    Ok(Some(server_config))
+}
+
+fn read_pem(path: &str) -> Result<Vec<Item>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file)).with_context(|| format!("Failed to read PEM from {}", path))
+}
@@ -0,0 +1,67 @@
+mod query;
+
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{EmptyMutation, EmptySubscription, Schema};
+use std::sync::Arc;
+
+use crate::i18n::Locale;
+use crate::models::auth::Identity;
+use crate::services::conversation::ConversationService;
+use crate::services::{DatabaseServiceTrait, DataProcessor, RedisServiceTrait, S3ServiceTrait};
+
+pub use query::QueryRoot;
+
+/// Deepest selection a request may nest; the schema's own fields are at most 5 levels
+/// down, and the introspection query GraphiQL sends nests 13
+const MAX_DEPTH: usize = 15;
+
+/// Most work a request may ask for, counting 1 per field, 25 per job's `insights` and
+/// 50 per `query`, times `first` for the fields selected under `jobs`
+const MAX_COMPLEXITY: usize = 500;
+
+/// The GraphQL schema: queries only, answered from the same services as the REST API
+pub type PipelineSchema<S, D, R> = Schema<QueryRoot<S, D, R>, EmptyMutation, EmptySubscription>;
+
+/// Build the schema over the processor and conversation service the routes share
+pub fn schema<S, D, R>(
+    processor: DataProcessor<S, D, R>,
+    conversation_service: Arc<ConversationService<S, D, R>>,
+) -> PipelineSchema<S, D, R>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    Schema::build(QueryRoot::default(), EmptyMutation, EmptySubscription)
+        .data(processor)
+        .data(conversation_service)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Execute a GraphQL request as the caller. Errors are reported in the response's
+/// `errors` with the REST error `code`, `status_code` and `request_id` as extensions.
+pub async fn graphql_endpoint<S, D, R>(
+    schema: web::Data<PipelineSchema<S, D, R>>,
+    identity: Identity,
+    request: web::Json<async_graphql::Request>,
+    req: HttpRequest,
+) -> HttpResponse
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let request = request.into_inner().data(identity).data(Locale::from_request(&req));
+    HttpResponse::Ok().json(schema.execute(request).await)
+}
+
+/// GraphiQL, to explore the schema from a browser
+pub async fn graphiql() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/html; charset=utf-8"))
+        .body(GraphiQLSource::build().endpoint("/graphql").finish())
+}
@@ -0,0 +1,222 @@
+use async_graphql::{Context, ErrorExtensions, Json, Object, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use crate::handlers::error::ApiError;
+use crate::handlers::insights::{job_insights, JobInsights};
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::error::ErrorCode;
+use crate::models::job::Job;
+use crate::models::query::StructuredQueryRequest;
+use crate::models::schema::DatasetSchema;
+use crate::services::conversation::ConversationService;
+use crate::services::{DatabaseServiceTrait, DataProcessor, RedisServiceTrait, S3ServiceTrait};
+
+/// Most jobs `jobs` returns at once
+const MAX_JOBS: usize = 100;
+
+/// Complexity of reading a job's insights, which may recompute them from the dataset
+const INSIGHTS_COMPLEXITY: usize = 25;
+
+/// Complexity of running a query, which reads and scans the whole dataset
+const QUERY_COMPLEXITY: usize = 50;
+
+pub struct QueryRoot<S, D, R>(PhantomData<(S, D, R)>);
+
+impl<S, D, R> Default for QueryRoot<S, D, R> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[Object(name = "Query")]
+impl<S, D, R> QueryRoot<S, D, R>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    /// A job the caller may see, or null
+    async fn job(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<JobNode<S, D, R>>> {
+        let (identity, locale) = caller(ctx)?;
+        match processor::<S, D, R>(ctx)?.get_db_service().get_job(id).await {
+            Ok(Some(job)) if identity.can_access_job(&job) => Ok(Some(JobNode::new(job))),
+            Ok(_) => Ok(None),
+            Err(e) => Err(database_error(&e, locale)),
+        }
+    }
+
+    /// The caller's jobs ordered by ID, up to `first` (at most 100) after the job `after`
+    #[graphql(complexity = "first.min(MAX_JOBS) * child_complexity")]
+    async fn jobs(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] first: usize,
+        after: Option<Uuid>,
+    ) -> Result<Vec<JobNode<S, D, R>>> {
+        let (identity, locale) = caller(ctx)?;
+        let db_service = processor::<S, D, R>(ctx)?.get_db_service();
+        let jobs = db_service
+            .list_jobs(&identity.job_filter(), after, first.min(MAX_JOBS))
+            .await
+            .map_err(|e| database_error(&e, locale))?;
+        Ok(jobs.into_iter().map(JobNode::new).collect())
+    }
+}
+
+/// A job with its insights, schema and queries over its dataset
+pub struct JobNode<S, D, R> {
+    job: Job,
+    services: PhantomData<(S, D, R)>,
+}
+
+impl<S, D, R> JobNode<S, D, R> {
+    fn new(job: Job) -> Self {
+        Self { job, services: PhantomData }
+    }
+}
+
+#[Object(name = "Job")]
+impl<S, D, R> JobNode<S, D, R>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    async fn id(&self) -> Uuid {
+        self.job.id
+    }
+
+    /// `queued`, `processing`, `completed`, `failed` or `cancelled`
+    async fn status(&self) -> &str {
+        &self.job.status
+    }
+
+    async fn tenant_id(&self) -> &str {
+        &self.job.tenant_id
+    }
+
+    async fn user_id(&self) -> &str {
+        &self.job.user_id
+    }
+
+    /// Hex SHA-256 of the uploaded file
+    async fn content_hash(&self) -> Option<&str> {
+        self.job.content_hash.as_deref()
+    }
+
+    /// Why the last attempt failed
+    async fn error_message(&self) -> Option<&str> {
+        self.job.error_message.as_deref()
+    }
+
+    /// Times processing has started
+    async fn attempts(&self) -> i32 {
+        self.job.attempts
+    }
+
+    /// Whether the last processing read the file in chunks to stay within the memory budget
+    async fn low_memory(&self) -> bool {
+        self.job.low_memory
+    }
+
+    /// RFC 3339 timestamps
+    async fn created_at(&self) -> Option<String> {
+        self.job.created_at.map(timestamp)
+    }
+
+    async fn updated_at(&self) -> Option<String> {
+        self.job.updated_at.map(timestamp)
+    }
+
+    /// The insights of a completed job, as `/insights/{job_id}` returns them; null
+    /// while the job or a recompute is in progress
+    #[graphql(complexity = "INSIGHTS_COMPLEXITY")]
+    async fn insights(&self, ctx: &Context<'_>) -> Result<Option<Json<Value>>> {
+        let (identity, locale) = caller(ctx)?;
+        let processor = processor::<S, D, R>(ctx)?;
+        match job_insights(identity, processor.get_db_service(), processor, self.job.id, locale).await {
+            Ok(JobInsights::Ready(response)) => Ok(response
+                .insights
+                .map(|insights| serde_json::to_value(insights).map(Json))
+                .transpose()?),
+            Ok(JobInsights::InProgress(_)) => Ok(None),
+            Err(e) => Err(graphql_error(e)),
+        }
+    }
+
+    /// Columns as processing reads them, with suggested type fixes
+    async fn schema(&self, ctx: &Context<'_>) -> Result<DatasetSchema> {
+        let (_, locale) = caller(ctx)?;
+        processor::<S, D, R>(ctx)?.dataset_schema(&self.job).await.map_err(|e| {
+            log::error!("Error reading the schema of job {}: {:#}", self.job.id, e);
+            let message = Message::SchemaFailed(&format!("{:#}", e));
+            graphql_error(ApiError::from_error(&e, ErrorCode::StorageError, message, locale))
+        })
+    }
+
+    /// Run a structured query, in the shape `/api/query/structured` accepts, on the
+    /// job's dataset
+    #[graphql(complexity = "QUERY_COMPLEXITY")]
+    async fn query(&self, ctx: &Context<'_>, query: Json<Value>) -> Result<Json<Value>> {
+        let (_, locale) = caller(ctx)?;
+        let conversation_service = ctx.data::<Arc<ConversationService<S, D, R>>>()?;
+        let request = StructuredQueryRequest {
+            job_id: self.job.id.to_string(),
+            query: query.0,
+        };
+        match conversation_service.execute_structured(&request).await {
+            Ok(result) => Ok(Json(serde_json::to_value(result)?)),
+            Err(e) => {
+                log::error!("Structured query failed for job {}: {:#}", self.job.id, e);
+                let message = Message::StructuredQueryFailed(&format!("{:#}", e));
+                Err(graphql_error(ApiError::from_error(&e, ErrorCode::QueryExecutionFailed, message, locale)))
+            }
+        }
+    }
+}
+
+/// The caller and locale the endpoint attached to the request
+fn caller<'a>(ctx: &Context<'a>) -> Result<(&'a Identity, Locale)> {
+    Ok((ctx.data::<Identity>()?, *ctx.data::<Locale>()?))
+}
+
+fn processor<'a, S, D, R>(ctx: &Context<'a>) -> Result<&'a DataProcessor<S, D, R>>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    ctx.data::<DataProcessor<S, D, R>>()
+}
+
+fn timestamp(at: SystemTime) -> String {
+    DateTime::<Utc>::from(at).to_rfc3339()
+}
+
+fn database_error(e: &anyhow::Error, locale: Locale) -> async_graphql::Error {
+    graphql_error(ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&e.to_string()), locale))
+}
+
+/// A GraphQL error carrying what the REST API would have answered: the localized
+/// message, with `code`, `status_code`, `details` and `request_id` as extensions
+fn graphql_error(error: ApiError) -> async_graphql::Error {
+    let body = error.body();
+    async_graphql::Error::new(body.error.clone()).extend_with(|_, extensions| {
+        if let Ok(code) = serde_json::to_value(body.code) {
+            extensions.set("code", async_graphql::Value::from_json(code).unwrap_or_default());
+        }
+        extensions.set("status_code", body.status_code);
+        if let Some(details) = &body.details {
+            extensions.set("details", async_graphql::Value::from_json(details.clone()).unwrap_or_default());
+        }
+        if let Some(request_id) = &body.request_id {
+            extensions.set("request_id", request_id.as_str());
+        }
+    })
+}
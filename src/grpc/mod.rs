@@ -0,0 +1,32 @@
+mod service;
+
+/// Messages and the service trait generated from `proto/pipeline.proto`
+pub mod proto {
+    tonic::include_proto!("pipeline.v1");
+}
+
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use tonic::transport::Server;
+
+use crate::services::{DatabaseServiceTrait, RedisServiceTrait, S3ServiceTrait};
+use proto::pipeline_server::PipelineServer;
+
+pub use service::PipelineService;
+
+/// Largest message the server decodes; uploads stream files in chunks below it
+const MAX_MESSAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Serve the gRPC API on `address` until the process exits
+pub async fn serve<S, D, R>(address: SocketAddr, service: PipelineService<S, D, R>) -> Result<()>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    Server::builder()
+        .add_service(PipelineServer::new(service).max_decoding_message_size(MAX_MESSAGE_BYTES))
+        .serve(address)
+        .await
+        .with_context(|| format!("gRPC server on {} stopped", address))
+}
@@ -0,0 +1,282 @@
+use std::sync::Arc;
+use tonic::codegen::Bytes;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::{Code, Request, Response, Status, Streaming};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use super::proto;
+use super::proto::pipeline_server::Pipeline;
+use crate::handlers::conversation::answer_query;
+use crate::handlers::error::ApiError;
+use crate::handlers::insights::{job_insights, JobInsights};
+use crate::handlers::request_id::{self, REQUEST_ID_HEADER};
+use crate::handlers::upload::{accept_upload, reserve_slot, UploadOptions, UploadedFile};
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::conversation::{QueryRequest, QueryResponse};
+use crate::models::error::ErrorCode;
+use crate::models::response::UploadResponse;
+use crate::services::auth::{AuthService, Unauthorized};
+use crate::services::conversation::ConversationService;
+use crate::services::job_queue::JobQueue;
+use crate::services::{DatabaseServiceTrait, DataProcessor, RedisServiceTrait, S3ServiceTrait};
+
+/// The `Pipeline` gRPC service, answering from the same services as the HTTP
+/// handlers. Callers authenticate with an `authorization` metadata entry holding
+/// a Bearer token, and may send `accept-language` and `x-request-id` as headers.
+pub struct PipelineService<S, D, R>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    processor: DataProcessor<S, D, R>,
+    conversation_service: Arc<ConversationService<S, D, R>>,
+    auth_service: Arc<AuthService>,
+    queue: Arc<JobQueue>,
+    max_upload_bytes: u64,
+}
+
+impl<S, D, R> PipelineService<S, D, R>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    pub fn new(
+        processor: DataProcessor<S, D, R>,
+        conversation_service: Arc<ConversationService<S, D, R>>,
+        auth_service: Arc<AuthService>,
+        queue: Arc<JobQueue>,
+        max_upload_bytes: u64,
+    ) -> Self {
+        Self { processor, conversation_service, auth_service, queue, max_upload_bytes }
+    }
+
+    /// Who made a call, as the `Identity` extractor decides for HTTP requests
+    async fn identify(&self, metadata: &MetadataMap, locale: Locale) -> Result<Identity, ApiError> {
+        let authorization = metadata.get("authorization").and_then(|value| value.to_str().ok());
+        self.auth_service.authenticate(authorization).await.map_err(|e| match e.downcast_ref::<Unauthorized>() {
+            Some(reason) => {
+                log::warn!("🔒 Rejected gRPC call: {}", reason);
+                ApiError::new(ErrorCode::Unauthorized, Message::Unauthorized(&reason.0), locale)
+            }
+            None => {
+                log::error!("❌ Failed to verify a token: {:#}", e);
+                let message = Message::AuthenticationUnavailable(&format!("{:#}", e));
+                ApiError::new(ErrorCode::AuthenticationUnavailable, message, locale)
+            }
+        })
+    }
+
+    async fn upload(&self, metadata: &MetadataMap, mut chunks: Streaming<proto::UploadChunk>, locale: Locale) -> Result<UploadResponse, ApiError> {
+        let identity = self.identify(metadata, locale).await?;
+
+        let mut file_content = Vec::new();
+        let mut upload = None;
+        while let Some(chunk) = chunks.message().await.map_err(|e| invalid_request(&e.to_string(), locale))? {
+            if let Some(chunk_metadata) = chunk.metadata {
+                if upload.is_some() {
+                    return Err(invalid_request("only the first chunk may carry metadata", locale));
+                }
+                upload = Some(chunk_metadata);
+            } else if upload.is_none() {
+                return Err(invalid_request("the first chunk must carry the upload metadata", locale));
+            }
+            // Refuse the file once it passes the limit, without buffering the rest
+            if (file_content.len() + chunk.data.len()) as u64 > self.max_upload_bytes {
+                return Err(ApiError::new(ErrorCode::FileTooLarge, Message::FileTooLarge(self.max_upload_bytes), locale));
+            }
+            file_content.extend_from_slice(&chunk.data);
+        }
+        let Some(upload) = upload else {
+            return Err(ApiError::new(ErrorCode::NoFileUploaded, Message::NoFileUploaded, locale));
+        };
+
+        let options = UploadOptions {
+            profile: upload.profile,
+            column_profiles: upload.column_profiles,
+            plausibility_columns: upload.plausibility_columns,
+            top_values: upload.top_values.map(|n| n as usize),
+        };
+        let profile_settings = options
+            .profile_settings(self.processor.default_profile())
+            .map_err(|e| ApiError::new(ErrorCode::InvalidRequest, Message::InvalidProfile(&e.to_string()), locale))?;
+
+        let slot = reserve_slot(Some(&self.queue), locale)?;
+        let file = UploadedFile {
+            filename: upload.filename,
+            content: file_content.into(),
+            batch_id: None,
+        };
+        accept_upload(&self.processor, slot, identity, file, profile_settings, locale).await
+    }
+
+    async fn insights(&self, metadata: &MetadataMap, request: proto::GetInsightsRequest, locale: Locale) -> Result<proto::InsightsReply, ApiError> {
+        let identity = self.identify(metadata, locale).await?;
+        // An ID that is not a UUID names no job, as an unknown one
+        let Ok(job_id) = Uuid::parse_str(&request.job_id) else {
+            return Err(ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(&request.job_id), locale));
+        };
+        let db_service = self.processor.get_db_service();
+        Ok(match job_insights(&identity, db_service, &self.processor, job_id, locale).await? {
+            JobInsights::Ready(response) => proto::InsightsReply {
+                job_id: response.job_id.to_string(),
+                status: response.status,
+                message: response.message,
+                insights_json: response.insights.and_then(|insights| serde_json::to_string(&insights).ok()),
+                content_hash: None,
+            },
+            JobInsights::InProgress(status) => proto::InsightsReply {
+                job_id: status.job_id.to_string(),
+                status: status.status,
+                message: status.message,
+                insights_json: None,
+                content_hash: status.content_hash,
+            },
+        })
+    }
+
+    async fn query(&self, metadata: &MetadataMap, request: proto::QueryRequest, locale: Locale) -> Result<QueryResponse, ApiError> {
+        let identity = self.identify(metadata, locale).await?;
+        log::info!("Received gRPC query: {}", request.query);
+        let request = QueryRequest {
+            job_id: request.job_id,
+            query: request.query,
+            conversation_id: request.conversation_id,
+            page: request.page.map(|n| n as usize),
+            page_size: request.page_size.map(|n| n as usize),
+            cursor: request.cursor,
+            explain: request.explain,
+        };
+        let db_service = self.processor.get_db_service();
+        answer_query(&identity, db_service, &self.conversation_service, request, locale).await
+    }
+}
+
+#[tonic::async_trait]
+impl<S, D, R> Pipeline for PipelineService<S, D, R>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    async fn upload(&self, request: Request<Streaming<proto::UploadChunk>>) -> Result<Response<proto::UploadReply>, Status> {
+        let (metadata, _, chunks) = request.into_parts();
+        let (request_id, locale) = call_context(&metadata);
+        let result = request_id::within(request_id.clone(), self.upload(&metadata, chunks, locale))
+            .instrument(tracing::info_span!("request", request_id = %request_id, method = "grpc", path = "Upload"))
+            .await;
+        reply(result.map(proto::UploadReply::from), &request_id)
+    }
+
+    async fn get_insights(&self, request: Request<proto::GetInsightsRequest>) -> Result<Response<proto::InsightsReply>, Status> {
+        let (metadata, _, request) = request.into_parts();
+        let (request_id, locale) = call_context(&metadata);
+        let result = request_id::within(request_id.clone(), self.insights(&metadata, request, locale))
+            .instrument(tracing::info_span!("request", request_id = %request_id, method = "grpc", path = "GetInsights"))
+            .await;
+        reply(result, &request_id)
+    }
+
+    async fn query(&self, request: Request<proto::QueryRequest>) -> Result<Response<proto::QueryReply>, Status> {
+        let (metadata, _, request) = request.into_parts();
+        let (request_id, locale) = call_context(&metadata);
+        let result = request_id::within(request_id.clone(), self.query(&metadata, request, locale))
+            .instrument(tracing::info_span!("request", request_id = %request_id, method = "grpc", path = "Query"))
+            .await;
+        reply(result.map(proto::QueryReply::from), &request_id)
+    }
+}
+
+/// The call's request ID, taken from `x-request-id` as for HTTP requests, and
+/// the locale its `accept-language` asks for
+fn call_context(metadata: &MetadataMap) -> (String, Locale) {
+    let request_id = request_id::accept(metadata.get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok()));
+    let locale = metadata
+        .get("accept-language")
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::from_accept_language)
+        .unwrap_or_default();
+    (request_id, locale)
+}
+
+fn invalid_request(reason: &str, locale: Locale) -> ApiError {
+    ApiError::new(ErrorCode::InvalidRequest, Message::InvalidRequest(reason), locale)
+}
+
+/// The reply to a call, echoing its request ID. Errors keep their HTTP meaning
+/// through the closest gRPC code, with the JSON error body as the status details.
+// `Status` is the error type tonic's generated trait requires
+#[allow(clippy::result_large_err)]
+fn reply<T>(result: Result<T, ApiError>, request_id: &str) -> Result<Response<T>, Status> {
+    let metadata_value = MetadataValue::try_from(request_id).ok();
+    match result {
+        Ok(message) => {
+            let mut response = Response::new(message);
+            if let Some(request_id) = metadata_value {
+                response.metadata_mut().insert(REQUEST_ID_HEADER, request_id);
+            }
+            Ok(response)
+        }
+        Err(error) => {
+            let code = match error.code.status() {
+                400 | 422 => Code::InvalidArgument,
+                401 => Code::Unauthenticated,
+                404 => Code::NotFound,
+                409 => Code::FailedPrecondition,
+                413 | 429 => Code::ResourceExhausted,
+                503 => Code::Unavailable,
+                504 => Code::DeadlineExceeded,
+                _ => Code::Internal,
+            };
+            let mut body = error.body();
+            body.request_id = Some(request_id.to_string());
+            let body = serde_json::to_vec(&body).unwrap_or_default();
+            let mut status = Status::with_details(code, error.message, Bytes::from(body));
+            if let Some(request_id) = metadata_value {
+                status.metadata_mut().insert(REQUEST_ID_HEADER, request_id);
+            }
+            Err(status)
+        }
+    }
+}
+
+impl From<UploadResponse> for proto::UploadReply {
+    fn from(response: UploadResponse) -> Self {
+        Self {
+            job_id: response.job_id.to_string(),
+            status: response.status,
+            content_hash: response.content_hash,
+            message: response.message,
+        }
+    }
+}
+
+impl From<QueryResponse> for proto::QueryReply {
+    fn from(response: QueryResponse) -> Self {
+        Self {
+            conversation_id: response.conversation_id,
+            response: response.response,
+            data_json: response.data.and_then(|data| serde_json::to_string(&data).ok()),
+            visualization_data_json: response.visualization_data.map(|chart| chart.to_string()),
+            pagination: response.pagination.map(|pagination| proto::Pagination {
+                page: pagination.page as u64,
+                page_size: pagination.page_size as u64,
+                total_rows: pagination.total_rows as u64,
+                returned_rows: pagination.returned_rows as u64,
+                next_cursor: pagination.next_cursor,
+            }),
+            result_id: response.result_id,
+            query_plan: response.query_plan.map(|plan| proto::QueryPlan {
+                structured_query_json: plan.structured_query.to_string(),
+                polars_plan: plan.polars_plan,
+            }),
+            grounding: response.grounding.map(|grounding| proto::GroundingCheck {
+                grounded: grounding.grounded,
+                unverified_figures: grounding.unverified_figures,
+            }),
+        }
+    }
+}
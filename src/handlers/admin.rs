@@ -0,0 +1,237 @@
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use log::error;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::handlers::error::ApiError;
+use crate::i18n::{Locale, Message};
+use crate::models::admin::{AdminJobsQuery, QueueStatus, StuckJobsQuery, TenantUsageReport};
+use crate::models::auth::Admin;
+use crate::models::error::ErrorCode;
+use crate::models::job::{InvalidTransition, Job, JobStatus};
+use crate::models::response::UploadResponse;
+use crate::services::admin;
+use crate::services::job_queue::{JobQueue, QueueError};
+use crate::services::{DatabaseServiceTrait, RedisServiceTrait, DataProcessor, S3ServiceTrait};
+
+/// Job IDs waiting in this instance's queue, and the jobs recorded as queued or processing,
+/// optionally only one tenant's
+pub async fn admin_queue<S, D, R>(
+    _admin: Admin,
+    query: web::Query<AdminJobsQuery>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let jobs = admin::all_jobs(processor.get_db_service(), &query.job_filter())
+        .await
+        .map_err(|e| database_error(&e, locale))?;
+    let queue = req.app_data::<web::Data<Arc<JobQueue>>>();
+
+    Ok(HttpResponse::Ok().json(QueueStatus {
+        depth: queue.map_or(0, |queue| queue.depth()),
+        capacity: queue.map_or(0, |queue| queue.capacity()),
+        rejected: queue.map_or(0, |queue| queue.rejected()),
+        queued: admin::jobs_in(&jobs, JobStatus::Queued),
+        in_flight: admin::jobs_in(&jobs, JobStatus::Processing),
+    }))
+}
+
+/// Jobs, storage and today's AI spend of every tenant, or of the one asked for, against its quotas
+pub async fn admin_tenants<S, D, R>(
+    _admin: Admin,
+    query: web::Query<AdminJobsQuery>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let jobs = admin::all_jobs(processor.get_db_service(), &query.job_filter())
+        .await
+        .map_err(|e| database_error(&e, locale))?;
+    match admin::tenant_usage(processor.get_ref(), &jobs).await {
+        Ok(tenants) => Ok(HttpResponse::Ok().json(TenantUsageReport { tenants })),
+        Err(e) => {
+            error!("Error reporting tenant usage: {:#}", e);
+            let message = Message::AdminReportFailed(&format!("{:#}", e));
+            Err(ApiError::new(ErrorCode::InternalError, message, locale).into())
+        }
+    }
+}
+
+/// Entries, size, hit ratio and evictions of the cache
+pub async fn admin_cache<S, D, R>(
+    _admin: Admin,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    match processor.get_redis_service().stats().await {
+        Ok(stats) => Ok(HttpResponse::Ok().json(stats)),
+        Err(e) => {
+            error!("Error reading cache statistics: {:#}", e);
+            let message = Message::CacheError(&format!("{:#}", e));
+            Err(ApiError::new(ErrorCode::CacheError, message, Locale::from_request(&req)).into())
+        }
+    }
+}
+
+/// Queued and processing jobs that have not changed for `older_than_secs`,
+/// by default as long as a processing job holds its lock, optionally only one tenant's
+pub async fn admin_stuck_jobs<S, D, R>(
+    _admin: Admin,
+    query: web::Query<StuckJobsQuery>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let jobs = admin::all_jobs(processor.get_db_service(), &query.job_filter())
+        .await
+        .map_err(|e| database_error(&e, locale))?;
+    let older_than_secs = query.older_than_secs.unwrap_or_else(|| processor.job_lock_ttl().as_secs());
+    Ok(HttpResponse::Ok().json(admin::stuck_jobs(&jobs, older_than_secs)))
+}
+
+/// Mark a queued or processing job failed
+pub async fn admin_fail_job<S, D, R>(
+    _admin: Admin,
+    job_id: web::Path<Uuid>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let job = find_job(processor.get_db_service(), job_id.into_inner(), locale).await?;
+    if let Err(e) = admin::force_fail(processor.get_ref(), &job).await {
+        return Err(action_error(&job, &e, locale).into());
+    }
+    Ok(HttpResponse::Ok().json(UploadResponse {
+        job_id: job.id,
+        status: JobStatus::Failed.to_string(),
+        content_hash: job.content_hash,
+        message: Some(Message::JobMarkedFailed.localize(locale)),
+    }))
+}
+
+/// Cancel a queued or processing job
+pub async fn admin_cancel_job<S, D, R>(
+    _admin: Admin,
+    job_id: web::Path<Uuid>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let job = find_job(processor.get_db_service(), job_id.into_inner(), locale).await?;
+    if let Err(e) = admin::cancel(processor.get_ref(), &job).await {
+        return Err(action_error(&job, &e, locale).into());
+    }
+    Ok(HttpResponse::Ok().json(UploadResponse {
+        job_id: job.id,
+        status: JobStatus::Cancelled.to_string(),
+        content_hash: job.content_hash,
+        message: Some(Message::JobCancelled.localize(locale)),
+    }))
+}
+
+/// Queue a stuck or failed job for the worker again
+pub async fn admin_retry_job<S, D, R>(
+    _admin: Admin,
+    job_id: web::Path<Uuid>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let job = find_job(processor.get_db_service(), job_id.into_inner(), locale).await?;
+    let Some(queue) = req.app_data::<web::Data<Arc<JobQueue>>>() else {
+        return Err(ApiError::new(ErrorCode::JobQueueUnavailable, Message::JobQueueUnavailable, locale).into());
+    };
+    if let Err(e) = admin::retry(processor.get_ref(), &job, queue).await {
+        return Err(action_error(&job, &e, locale).into());
+    }
+    let status = JobStatus::Queued.to_string();
+    Ok(HttpResponse::Accepted().json(UploadResponse {
+        job_id: job.id,
+        status: status.clone(),
+        content_hash: job.content_hash,
+        message: Some(Message::JobQueued(&status).localize(locale)),
+    }))
+}
+
+/// Objects and bytes stored in total, per tenant and per area
+pub async fn admin_storage<S, D, R>(
+    _admin: Admin,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    match admin::storage_report(processor.get_s3_service()).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e) => {
+            error!("Error reporting storage usage: {:#}", e);
+            let message = Message::ListFilesFailed(&format!("{:#}", e));
+            Err(ApiError::new(ErrorCode::StorageError, message, Locale::from_request(&req)).into())
+        }
+    }
+}
+
+/// Any tenant's job, since the admin sees them all
+async fn find_job<D: DatabaseServiceTrait>(db_service: &D, job_id: Uuid, locale: Locale) -> Result<Job, ApiError> {
+    match db_service.get_job(job_id).await {
+        Ok(Some(job)) => Ok(job),
+        Ok(None) => Err(ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(&job_id.to_string()), locale)),
+        Err(e) => Err(database_error(&e, locale)),
+    }
+}
+
+fn database_error(e: &anyhow::Error, locale: Locale) -> ApiError {
+    error!("Error reading jobs for the admin API: {:#}", e);
+    ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&format!("{:#}", e)), locale)
+}
+
+/// A refused status change is a conflict with the job's status; anything else failed
+fn action_error(job: &Job, e: &anyhow::Error, locale: Locale) -> ApiError {
+    if e.downcast_ref::<InvalidTransition>().is_some() {
+        return ApiError::new(ErrorCode::JobInProgress, Message::JobInProgress(&job.status.to_lowercase()), locale);
+    }
+    if let Some(queue_error) = e.downcast_ref::<QueueError>() {
+        return ApiError::from_queue_error(*queue_error, locale);
+    }
+    error!("Admin action on job {} failed: {:#}", job.id, e);
+    ApiError::new(ErrorCode::InternalError, Message::AdminReportFailed(&format!("{:#}", e)), locale)
+}
@@ -0,0 +1,68 @@
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+use log::{error, warn};
+
+use crate::handlers::error::ApiError;
+use crate::i18n::{Locale, Message};
+use crate::models::auth::{Admin, Identity};
+use crate::models::error::ErrorCode;
+use crate::services::auth::{AuthService, Unauthorized};
+
+/// Handlers take an `Identity` to learn who is calling; the request is answered
+/// with 401 before the handler runs when its token is missing or invalid
+impl FromRequest for Identity {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let Some(auth) = req.app_data::<web::Data<AuthService>>() else {
+                return Ok(Identity::anonymous());
+            };
+            let authorization = req
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok());
+            let locale = Locale::from_request(&req);
+
+            auth.authenticate(authorization).await.map_err(|e| {
+                match e.downcast_ref::<Unauthorized>() {
+                    Some(reason) => {
+                        warn!("🔒 Rejected request to {}: {}", req.path(), reason);
+                        ApiError::new(ErrorCode::Unauthorized, Message::Unauthorized(&reason.0), locale).into()
+                    }
+                    None => {
+                        error!("❌ Failed to verify a token: {:#}", e);
+                        let message = Message::AuthenticationUnavailable(&format!("{:#}", e));
+                        ApiError::new(ErrorCode::AuthenticationUnavailable, message, locale).into()
+                    }
+                }
+            })
+        })
+    }
+}
+
+/// Admin handlers take an `Admin`; the request is answered with 401 before the
+/// handler runs unless it carries `ADMIN_TOKEN` as a Bearer token
+impl FromRequest for Admin {
+    type Error = Error;
+    type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let authorization = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        let result = match req.app_data::<web::Data<AuthService>>() {
+            Some(auth) => auth.authenticate_admin(authorization),
+            None => Err(Unauthorized("the admin API is disabled".to_string()).into()),
+        };
+        std::future::ready(result.map(|()| Admin).map_err(|e| {
+            warn!("🔒 Rejected admin request to {}: {}", req.path(), e);
+            ApiError::new(ErrorCode::Unauthorized, Message::Unauthorized(&e.to_string()), Locale::from_request(req)).into()
+        }))
+    }
+}
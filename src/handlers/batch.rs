@@ -0,0 +1,283 @@
+use actix_multipart::Multipart;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use bytes::{Bytes, BytesMut};
+use futures::{stream, StreamExt};
+use log::error;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::handlers::error::{invalid_request, ApiError};
+use crate::handlers::upload::{accept_upload, UploadOptions, UploadedFile};
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::batch::{BatchFile, BatchJob, BatchManifest, BatchStatus, BatchUploadResponse, NewBatch, RejectedFile};
+use crate::models::error::ErrorCode;
+use crate::models::job::{Job, JobStatus};
+use crate::services::fetch::fetch_csv;
+use crate::services::job_queue::JobQueue;
+use crate::services::{DatabaseServiceTrait, RedisServiceTrait, DataProcessor, S3ServiceTrait};
+
+/// Largest JSON manifest accepted
+const MANIFEST_MAX_BYTES: usize = 1024 * 1024;
+
+/// URLs of a manifest fetched at the same time
+const CONCURRENT_FETCHES: usize = 4;
+
+/// A file of the batch as received: its content, or why it couldn't be read
+type Received = (String, Result<Bytes, ApiError>);
+
+/// Upload many CSVs in one request, as multipart `file` parts or as a JSON
+/// manifest of URLs to fetch. Every file becomes its own job in a new batch;
+/// files that can't be stored are listed as rejected without failing the others.
+pub async fn upload_batch<S, D, R>(
+    payload: web::Payload,
+    options: web::Query<UploadOptions>,
+    identity: Identity,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let profile_settings = match options.profile_settings(processor.default_profile()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            return Err(ApiError::new(ErrorCode::InvalidRequest, Message::InvalidProfile(&e.to_string()), locale).into());
+        }
+    };
+
+    let (max_files, fetch_max_bytes) = processor.batch_limits();
+    let is_json = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let files = if is_json {
+        let body = payload.to_bytes_limited(MANIFEST_MAX_BYTES).await.map_err(|_| {
+            invalid_request(format!("the manifest is larger than {} bytes", MANIFEST_MAX_BYTES), &req)
+        })??;
+        let manifest: BatchManifest = serde_json::from_slice(&body).map_err(|e| invalid_request(e, &req))?;
+        if manifest.urls.len() > max_files {
+            return Err(ApiError::new(ErrorCode::InvalidRequest, Message::TooManyFiles(max_files), locale).into());
+        }
+        fetch_all(manifest.urls, fetch_max_bytes, locale).await
+    } else {
+        read_parts(Multipart::new(req.headers(), payload), max_files, fetch_max_bytes, locale).await?
+    };
+    if files.is_empty() {
+        return Err(ApiError::new(ErrorCode::NoFileUploaded, Message::NoFileUploaded, locale).into());
+    }
+
+    // No file to store means no batch: fail with the first file's reason
+    let received = files.iter().filter(|(_, content)| content.is_ok()).count();
+    if received == 0 {
+        let rejected: Vec<RejectedFile> = files
+            .iter()
+            .filter_map(|(source, content)| content.as_ref().err().map(|e| rejected_file(source, e)))
+            .collect();
+        if let Some((_, Err(e))) = files.into_iter().next() {
+            return Err(e.with_details(json!({ "rejected": rejected })).into());
+        }
+        return Err(ApiError::new(ErrorCode::NoFileUploaded, Message::NoFileUploaded, locale).into());
+    }
+
+    // Every received file gets its slot in the worker's queue before any is stored,
+    // so a batch is queued whole or turned away whole when the queue is too full
+    let Some(queue) = req.app_data::<web::Data<Arc<JobQueue>>>() else {
+        return Err(ApiError::new(ErrorCode::JobQueueUnavailable, Message::JobQueueUnavailable, locale).into());
+    };
+    let mut slots = queue.reserve_many(received).map_err(|e| ApiError::from_queue_error(e, locale))?;
+
+    let new_batch = NewBatch {
+        user_id: identity.user_id.clone(),
+        tenant_id: identity.tenant_id.clone(),
+    };
+    let batch_id = match processor.get_db_service().create_batch(new_batch).await {
+        Ok(batch_id) => batch_id,
+        Err(e) => {
+            error!("Error creating a batch: {:#}", e);
+            return Err(ApiError::new(ErrorCode::DatabaseError, Message::JobCreationFailed(&e.to_string()), locale).into());
+        }
+    };
+
+    let mut jobs = Vec::new();
+    let mut rejected = Vec::new();
+    let mut first_error = None;
+    for (source, content) in files {
+        let accepted = match content {
+            Ok(content) => {
+                let file = UploadedFile {
+                    filename: source.clone(),
+                    content,
+                    batch_id: Some(batch_id),
+                };
+                match slots.next() {
+                    Some(slot) => accept_upload(processor.get_ref(), slot, identity.clone(), file, profile_settings.clone(), locale).await,
+                    // Not reached: a slot was reserved for every received file
+                    None => Err(ApiError::new(ErrorCode::JobQueueUnavailable, Message::JobQueueUnavailable, locale)),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        match accepted {
+            Ok(response) => jobs.push(BatchFile {
+                source,
+                job_id: response.job_id,
+                content_hash: response.content_hash,
+            }),
+            Err(e) => {
+                rejected.push(rejected_file(&source, &e));
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    // With nothing accepted the batch failed as a whole, with the first file's
+    // reason, and no job points at its record
+    if jobs.is_empty() {
+        if let Err(e) = processor.get_db_service().delete_batch(batch_id).await {
+            log::warn!("⚠️ Failed to remove batch {} after every file was rejected: {:#}", batch_id, e);
+        }
+        if let Some(e) = first_error {
+            return Err(e.with_details(json!({ "rejected": rejected })).into());
+        }
+    }
+    log::info!("📦 Batch {} queued {} files, rejected {}", batch_id, jobs.len(), rejected.len());
+    Ok(HttpResponse::Ok().json(BatchUploadResponse {
+        batch_id,
+        status: JobStatus::Queued.to_string(),
+        message: Message::BatchQueued(jobs.len()).localize(locale),
+        jobs,
+        rejected,
+    }))
+}
+
+/// The jobs of a batch and their aggregate status
+pub async fn get_batch<S, D, R>(
+    batch_id: web::Path<Uuid>,
+    identity: Identity,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let batch_id = batch_id.into_inner();
+    let locale = Locale::from_request(&req);
+    let db_service = processor.get_db_service();
+
+    let database_error = |e: anyhow::Error| -> Error {
+        error!("Error reading batch {}: {:#}", batch_id, e);
+        ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&e.to_string()), locale).into()
+    };
+    match db_service.get_batch(batch_id).await.map_err(database_error)? {
+        Some(batch) if identity.can_access(Some(&batch.tenant_id), Some(&batch.user_id)) => {}
+        _ => {
+            return Err(ApiError::new(ErrorCode::BatchNotFound, Message::BatchNotFound(&batch_id.to_string()), locale).into());
+        }
+    }
+    let jobs = db_service.list_batch_jobs(batch_id).await.map_err(database_error)?;
+    Ok(HttpResponse::Ok().json(batch_status(batch_id, &jobs)))
+}
+
+/// The `file` parts of a multipart upload, refusing more than `max_files`. A part
+/// over `max_bytes` is rejected on its own; the rest of it is read and dropped.
+async fn read_parts(mut payload: Multipart, max_files: usize, max_bytes: u64, locale: Locale) -> Result<Vec<Received>, Error> {
+    let mut files = Vec::new();
+    while let Some(item) = payload.next().await {
+        let mut field = item?;
+        let content_disposition = field.content_disposition();
+        if content_disposition.get_name() != Some("file") {
+            continue;
+        }
+        if files.len() == max_files {
+            return Err(ApiError::new(ErrorCode::InvalidRequest, Message::TooManyFiles(max_files), locale).into());
+        }
+        let filename = content_disposition.get_filename().unwrap_or_default().to_string();
+        let mut content = Some(BytesMut::new());
+        while let Some(chunk) = field.next().await {
+            let chunk = chunk?;
+            if let Some(buffer) = &mut content {
+                if (buffer.len() + chunk.len()) as u64 > max_bytes {
+                    content = None;
+                } else {
+                    buffer.extend_from_slice(&chunk);
+                }
+            }
+        }
+        let content = content
+            .map(BytesMut::freeze)
+            .ok_or_else(|| ApiError::new(ErrorCode::FileTooLarge, Message::FileTooLarge(max_bytes), locale));
+        files.push((filename, content));
+    }
+    Ok(files)
+}
+
+/// How a file that couldn't be queued is listed in the response
+fn rejected_file(source: &str, e: &ApiError) -> RejectedFile {
+    RejectedFile {
+        source: source.to_string(),
+        code: e.code,
+        error: e.message.clone(),
+    }
+}
+
+/// Fetch a manifest's URLs a few at a time, keeping their order
+async fn fetch_all(urls: Vec<String>, max_bytes: u64, locale: Locale) -> Vec<Received> {
+    stream::iter(urls)
+        .map(|url| async move {
+            match fetch_csv(&url, max_bytes).await {
+                Ok(fetched) => (fetched.filename, Ok(fetched.content.into())),
+                Err(e) => {
+                    log::warn!("⚠️ Failed to fetch {} for a batch: {:#}", url, e);
+                    let message = Message::FetchFailed(&format!("{:#}", e));
+                    (url, Err(ApiError::new(ErrorCode::InvalidRequest, message, locale)))
+                }
+            }
+        })
+        .buffered(CONCURRENT_FETCHES)
+        .collect()
+        .await
+}
+
+/// Counts per status and the status of the batch as a whole
+fn batch_status(batch_id: Uuid, jobs: &[Job]) -> BatchStatus {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for job in jobs {
+        *counts.entry(job.status.clone()).or_insert(0) += 1;
+    }
+    let count = |status: JobStatus| counts.get(&status.to_string()).copied().unwrap_or(0);
+
+    let status = if count(JobStatus::Queued) + count(JobStatus::Processing) > 0 {
+        "processing"
+    } else if count(JobStatus::Completed) == jobs.len() {
+        "completed"
+    } else if count(JobStatus::Completed) == 0 {
+        "failed"
+    } else {
+        "partial"
+    };
+
+    BatchStatus {
+        batch_id,
+        status: status.to_string(),
+        total: jobs.len(),
+        counts,
+        jobs: jobs
+            .iter()
+            .map(|job| BatchJob {
+                job_id: job.id,
+                status: job.status.clone(),
+                error_message: job.error_message.clone(),
+            })
+            .collect(),
+    }
+}
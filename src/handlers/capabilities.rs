@@ -0,0 +1,109 @@
+use actix_web::{web, HttpResponse, Error};
+use std::sync::Arc;
+
+use crate::config::JOB_QUEUE_CAPACITY;
+use crate::i18n::Locale;
+use crate::models::response::{
+    AiCapabilities, BackendCapabilities, Capabilities, LimitCapabilities, QueryCapabilities,
+};
+use crate::services::conversation::ConversationService;
+use crate::services::query_translator::{
+    ColumnOperation, QueryIntent, DATE_TRUNC_UNITS, DESCRIBE_ROW_LIMIT, FILTER_OPERATORS, HAVING_OPERATORS, PIVOT_AGGREGATIONS,
+    SQL_ROW_LIMIT, VISUALIZE_ROW_LIMIT, WINDOW_UNITS,
+};
+use crate::services::{S3ServiceTrait, DatabaseServiceTrait, RedisServiceTrait, DataProcessor};
+
+/// Sections the insights payload may contain
+const INSIGHT_SECTIONS: &[&str] = &[
+    "data_summary",
+    "column_statistics",
+    "correlations",
+    "categorical_associations",
+    "anomalies",
+    "extreme_records",
+    "functional_dependencies",
+    "plausibility",
+    "ai_analysis",
+];
+
+/// Describe the active backends, query engine, AI availability and limits
+pub async fn get_capabilities<S, D, R>(
+    s3_service: web::Data<S>,
+    db_service: web::Data<D>,
+    redis_service: web::Data<R>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let ai_available = conversation_service.ai_available();
+    let ai_service = conversation_service.ai_service();
+    let (sample_threshold_rows, sample_size) = processor.sampling_limits();
+    let query_limits = conversation_service.query_limits();
+
+    let mut features = Vec::new();
+    if cfg!(feature = "memory-services") {
+        features.push("memory-services".to_string());
+    }
+    if cfg!(feature = "external-services") {
+        features.push("external-services".to_string());
+    }
+    if cfg!(feature = "sqlite") {
+        features.push("sqlite".to_string());
+    }
+    if cfg!(feature = "graphql") {
+        features.push("graphql".to_string());
+    }
+    if cfg!(feature = "grpc") {
+        features.push("grpc".to_string());
+    }
+    if cfg!(feature = "debug-endpoints") {
+        features.push("debug-endpoints".to_string());
+    }
+
+    Ok(HttpResponse::Ok().json(Capabilities {
+        backends: BackendCapabilities {
+            storage: s3_service.backend_name().to_string(),
+            database: db_service.backend_name().to_string(),
+            cache: redis_service.backend_name().to_string(),
+        },
+        query: QueryCapabilities {
+            intents: to_strings(QueryIntent::SUPPORTED),
+            operations: to_strings(ColumnOperation::SUPPORTED),
+            filter_operators: to_strings(FILTER_OPERATORS),
+            date_trunc_units: to_strings(DATE_TRUNC_UNITS),
+            pivot_aggregations: to_strings(PIVOT_AGGREGATIONS),
+            having_operators: to_strings(HAVING_OPERATORS),
+            window_units: to_strings(WINDOW_UNITS),
+        },
+        ai: AiCapabilities {
+            available: ai_available,
+            provider: ai_service.map(|ai| ai.provider_name().to_string()),
+            summary_model: ai_service.map(|ai| ai.summary_model().to_string()),
+            translation_model: ai_service.map(|ai| ai.translation_model().to_string()),
+            job_analysis: processor.ai_analysis_enabled(),
+        },
+        limits: LimitCapabilities {
+            max_upload_bytes: processor.max_upload_bytes(),
+            describe_row_limit: DESCRIBE_ROW_LIMIT,
+            visualize_row_limit: VISUALIZE_ROW_LIMIT,
+            sql_row_limit: SQL_ROW_LIMIT,
+            job_queue_capacity: JOB_QUEUE_CAPACITY,
+            insights_sample_threshold_rows: sample_threshold_rows,
+            insights_sample_size: sample_size,
+            query_timeout_secs: query_limits.timeout.as_secs(),
+            query_max_dataset_bytes: query_limits.max_dataset_bytes,
+            query_max_result_rows: query_limits.max_result_rows,
+        },
+        insights: to_strings(INSIGHT_SECTIONS),
+        locales: Locale::ALL.iter().map(|l| l.code().to_string()).collect(),
+        features,
+    }))
+}
+
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
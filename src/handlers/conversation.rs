@@ -1,15 +1,79 @@
-use actix_web::{web, HttpResponse, Error};
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
 use log::{info, error};
+use serde::Deserialize;
+use serde_json::{json, Value};
 use std::sync::Arc;
 
-use crate::models::conversation::QueryRequest;
-use crate::services::conversation::ConversationService;
+use crate::handlers::query::missing_job;
+use crate::handlers::error::ApiError;
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::conversation::{
+    ConversationExportFormat, FeedbackRating, FeedbackRequest, QueryRequest, QueryResponse, DEFAULT_EXPORT_ROWS, MAX_PAGE_SIZE,
+};
+use crate::models::error::ErrorCode;
+use crate::models::query::ExportFormat;
+use crate::services::conversation::{ConversationService, QueryFailed, QueryStage};
+use crate::services::export::conversation_markdown;
 use crate::services::{S3ServiceTrait, DatabaseServiceTrait, RedisServiceTrait};
 
+/// 404 error when `conversation_id` does not name a conversation the caller may
+/// see; unknown IDs pass, for the handler to answer as it does
+fn hidden_conversation<S, D, R>(
+    conversation_service: &ConversationService<S, D, R>,
+    conversation_id: &str,
+    identity: &Identity,
+    locale: Locale,
+) -> Option<ApiError>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    match conversation_service.get_conversation(conversation_id) {
+        Ok(Some(detail)) if !identity.can_access(detail.conversation.tenant_id.as_deref(), detail.conversation.user_id.as_deref()) => {
+            Some(ApiError::new(ErrorCode::ConversationNotFound, Message::ConversationNotFound(conversation_id), locale))
+        }
+        _ => None,
+    }
+}
+
+/// Error for a query turn that produced no answer, coded by the step that failed
+/// and pointing at the conversation it ran in
+pub(crate) fn query_turn_failed(e: &anyhow::Error, locale: Locale) -> ApiError {
+    let Some(failed) = e.downcast_ref::<QueryFailed>() else {
+        let message = Message::QueryProcessingFailed(&format!("{:#}", e));
+        return ApiError::from_error(e, ErrorCode::InternalError, message, locale);
+    };
+    let reason = format!("{:#}", failed.source);
+    let (code, message) = match failed.stage {
+        QueryStage::Pagination => (ErrorCode::InvalidPagination, Message::InvalidPagination(&reason)),
+        QueryStage::Translation => (ErrorCode::QueryTranslationFailed, Message::QueryNotUnderstood(&reason)),
+        QueryStage::Execution => (ErrorCode::QueryExecutionFailed, Message::QueryExecutionFailed(&reason)),
+        QueryStage::Formatting => (ErrorCode::InternalError, Message::ResultFormattingFailed(&reason)),
+    };
+    let mut details = serde_json::Map::new();
+    if let Some(conversation_id) = &failed.conversation_id {
+        details.insert("conversation_id".to_string(), json!(conversation_id));
+    }
+    if let Some(query_plan) = &failed.query_plan {
+        details.insert("query_plan".to_string(), json!(query_plan));
+    }
+    let error = ApiError::from_error(&failed.source, code, message, locale);
+    if details.is_empty() {
+        return error;
+    }
+    error.with_details(Value::Object(details))
+}
+
 /// Handle a natural language query about a dataset
 pub async fn query_endpoint<S, D, R>(
     query_req: web::Json<QueryRequest>,
+    identity: Identity,
+    db_service: web::Data<D>,
     conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error>
 where
     S: S3ServiceTrait + Clone + std::fmt::Debug,
@@ -17,16 +81,337 @@ where
     R: RedisServiceTrait + Clone + std::fmt::Debug,
 {
     info!("Received query: {}", query_req.query);
+    let locale = Locale::from_request(&req);
+    let response = answer_query(&identity, db_service.get_ref(), &conversation_service, query_req.into_inner(), locale).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Answer a natural language query for a caller, whichever API asked it
+pub(crate) async fn answer_query<S, D, R>(
+    identity: &Identity,
+    db_service: &D,
+    conversation_service: &ConversationService<S, D, R>,
+    query_req: QueryRequest,
+    locale: Locale,
+) -> Result<QueryResponse, ApiError>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    // Only the caller's datasets, and only their own conversations about them
+    let (job_id, conversation_id) = conversation_service.query_target(&query_req);
+    if let Some(error) = missing_job(db_service, &job_id, identity, locale).await {
+        return Err(error);
+    }
+    if let Some(conversation_id) = conversation_id {
+        if let Some(error) = hidden_conversation(conversation_service, &conversation_id, identity, locale) {
+            return Err(error);
+        }
+    }
     
     // Process the query
-    match conversation_service.process_query(query_req.into_inner()).await {
+    match conversation_service.process_query(query_req, locale).await {
         Ok(response) => {
             info!("Query processed successfully");
-            Ok(HttpResponse::Ok().json(response))
+            Ok(response)
         },
         Err(e) => {
-            error!("Error processing query: {}", e);
-            Ok(HttpResponse::InternalServerError().json(format!("Error processing query: {}", e)))
+            error!("Error processing query: {:#}", e);
+            Err(query_turn_failed(&e, locale))
+        }
+    }
+}
+
+/// Get a conversation's turn history with per-turn timings and token costs
+pub async fn get_conversation<S, D, R>(
+    conversation_id: web::Path<String>,
+    identity: Identity,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let conversation_id = conversation_id.into_inner();
+    let locale = Locale::from_request(&req);
+
+    match conversation_service.get_conversation(&conversation_id) {
+        Ok(Some(detail)) if identity.can_access(detail.conversation.tenant_id.as_deref(), detail.conversation.user_id.as_deref()) => {
+            Ok(HttpResponse::Ok().json(detail))
+        }
+        Ok(_) => Err(ApiError::new(ErrorCode::ConversationNotFound, Message::ConversationNotFound(&conversation_id), locale).into()),
+        Err(e) => {
+            error!("Error loading conversation {}: {}", conversation_id, e);
+            Err(ApiError::new(ErrorCode::InternalError, Message::QueryProcessingFailed(&e.to_string()), locale).into())
+        }
+    }
+}
+
+/// Rate an answer in a conversation
+pub async fn submit_feedback<S, D, R>(
+    conversation_id: web::Path<String>,
+    feedback: web::Json<FeedbackRequest>,
+    identity: Identity,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let conversation_id = conversation_id.into_inner();
+    let locale = Locale::from_request(&req);
+    if let Some(error) = hidden_conversation(&conversation_service, &conversation_id, &identity, locale) {
+        return Err(error.into());
+    }
+
+    match conversation_service.submit_feedback(&conversation_id, &feedback) {
+        Ok(Some(turn)) => Ok(HttpResponse::Ok().json(turn)),
+        Ok(None) => Err(ApiError::new(ErrorCode::ConversationNotFound, Message::ConversationNotFound(&conversation_id), locale).into()),
+        Err(e) => Err(ApiError::new(ErrorCode::InvalidRequest, Message::FeedbackFailed(&e.to_string()), locale).into()),
+    }
+}
+
+/// Suggest example questions to ask about a dataset
+pub async fn get_suggestions<S, D, R>(
+    job_id: web::Path<String>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let job_id = job_id.into_inner();
+    let locale = Locale::from_request(&req);
+    if let Some(error) = missing_job(db_service.get_ref(), &job_id, &identity, locale).await {
+        return Err(error.into());
+    }
+
+    match conversation_service.suggest_questions(&job_id, locale).await {
+        Ok(suggestions) => Ok(HttpResponse::Ok().json(suggestions)),
+        Err(e) => {
+            error!("Failed to suggest questions for job {}: {:#}", job_id, e);
+            Err(ApiError::new(ErrorCode::ProcessingFailed, Message::SuggestionsFailed(&e.to_string()), locale).into())
+        }
+    }
+}
+
+/// Query parameters accepted by the feedback export endpoint
+#[derive(Debug, Deserialize)]
+pub struct FeedbackExportOptions {
+    /// Only export `up` or `down` ratings
+    pub rating: Option<FeedbackRating>,
+    /// json (default) or jsonl, one record per line as a file attachment
+    pub format: Option<String>,
+}
+
+/// Export rated turns with the question, answer and structured query they rate
+pub async fn export_feedback<S, D, R>(
+    options: web::Query<FeedbackExportOptions>,
+    identity: Identity,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let records = match conversation_service.export_feedback(options.rating) {
+        // Only feedback on the caller's own conversations
+        Ok(records) => records
+            .into_iter()
+            .filter(|record| hidden_conversation(&conversation_service, &record.conversation_id, &identity, locale).is_none())
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            error!("Error exporting feedback: {}", e);
+            return Err(ApiError::new(ErrorCode::ProcessingFailed, Message::ExportFailed(&e.to_string()), locale).into());
+        }
+    };
+
+    match options.format.as_deref().map(|f| f.trim().to_lowercase()).as_deref() {
+        None | Some("json") => Ok(HttpResponse::Ok().json(records)),
+        Some("jsonl") | Some("ndjson") => {
+            let mut body = String::new();
+            for record in &records {
+                body.push_str(&serde_json::to_string(record)?);
+                body.push('\n');
+            }
+            Ok(HttpResponse::Ok()
+                .content_type("application/x-ndjson")
+                .insert_header(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename("feedback.jsonl".to_string())],
+                })
+                .body(body))
+        }
+        Some(other) => {
+            let reason = format!("Unknown export format '{}' (expected json or jsonl)", other);
+            Err(ApiError::new(ErrorCode::InvalidRequest, Message::ExportFailed(&reason), locale).into())
+        }
+    }
+}
+
+/// Conversation latency and AI spend aggregated per tenant
+pub async fn conversation_metrics<S, D, R>(
+    identity: Identity,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    match conversation_service.tenant_metrics() {
+        Ok(metrics) => Ok(HttpResponse::Ok().json(
+            metrics
+                .into_iter()
+                .filter(|tenant| identity.can_access_tenant(&tenant.tenant))
+                .collect::<Vec<_>>(),
+        )),
+        Err(e) => {
+            error!("Error aggregating conversation metrics: {}", e);
+            Err(ApiError::new(ErrorCode::InternalError, Message::QueryProcessingFailed(&e.to_string()), Locale::from_request(&req)).into())
+        }
+    }
+}
+
+/// Query parameters accepted by the result download endpoint
+#[derive(Debug, Deserialize)]
+pub struct DownloadOptions {
+    /// csv (default), xlsx or arrow
+    pub format: Option<String>,
+}
+
+/// Download the full result of a conversational query as a file
+pub async fn download_result<S, D, R>(
+    result_id: web::Path<String>,
+    options: web::Query<DownloadOptions>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let result_id = result_id.into_inner();
+    let locale = Locale::from_request(&req);
+
+    // A result is visible to whoever may see the dataset it came from
+    let visible = match conversation_service.result_job(&result_id) {
+        Ok(Some(job_id)) => missing_job(db_service.get_ref(), &job_id, &identity, locale).await.is_none(),
+        _ => true,
+    };
+    if !visible {
+        return Err(ApiError::new(ErrorCode::ResultNotFound, Message::ResultNotFound(&result_id), locale).into());
+    }
+
+    let format = match options.format.as_deref().map(str::parse::<ExportFormat>).transpose() {
+        Ok(format) => format.unwrap_or_default(),
+        Err(e) => {
+            return Err(ApiError::new(ErrorCode::InvalidRequest, Message::ExportFailed(&e.to_string()), locale).into());
+        }
+    };
+
+    match conversation_service.export_result(&result_id, format).await {
+        Ok(Some(file)) => Ok(HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!("result-{}.{}", result_id, format.extension()))],
+            })
+            .body(file)),
+        Ok(None) => Err(ApiError::new(ErrorCode::ResultNotFound, Message::ResultNotFound(&result_id), locale).into()),
+        Err(e) => {
+            error!("Error exporting result {}: {:#}", result_id, e);
+            Err(ApiError::new(ErrorCode::ProcessingFailed, Message::ExportFailed(&format!("{:#}", e)), locale).into())
+        }
+    }
+}
+
+/// Query parameters accepted by the conversation export endpoint
+#[derive(Debug, Deserialize)]
+pub struct ConversationExportOptions {
+    /// markdown (default) or json
+    pub format: Option<String>,
+    /// Rows shown per data table, `DEFAULT_EXPORT_ROWS` when unset
+    pub rows: Option<usize>,
+}
+
+/// Export a conversation with its answers, data tables and chart configs as a
+/// Markdown or JSON file
+pub async fn export_conversation<S, D, R>(
+    conversation_id: web::Path<String>,
+    options: web::Query<ConversationExportOptions>,
+    identity: Identity,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let conversation_id = conversation_id.into_inner();
+    let locale = Locale::from_request(&req);
+    if let Some(error) = hidden_conversation(&conversation_service, &conversation_id, &identity, locale) {
+        return Err(error.into());
+    }
+
+    let format = match options.format.as_deref().map(str::parse::<ConversationExportFormat>).transpose() {
+        Ok(format) => format.unwrap_or_default(),
+        Err(e) => {
+            return Err(ApiError::new(ErrorCode::InvalidRequest, Message::ExportFailed(&e.to_string()), locale).into());
+        }
+    };
+    let rows = options.rows.unwrap_or(DEFAULT_EXPORT_ROWS);
+    if rows == 0 || rows > MAX_PAGE_SIZE {
+        return Err(ApiError::new(ErrorCode::InvalidRequest, Message::ExportFailed(&format!("rows must be between 1 and {}", MAX_PAGE_SIZE)), locale).into());
+    }
+
+    let export = match conversation_service.export_conversation(&conversation_id, rows).await {
+        Ok(Some(export)) => export,
+        Ok(None) => {
+            return Err(ApiError::new(ErrorCode::ConversationNotFound, Message::ConversationNotFound(&conversation_id), locale).into());
+        }
+        Err(e) => {
+            error!("Error exporting conversation {}: {:#}", conversation_id, e);
+            return Err(ApiError::new(ErrorCode::ProcessingFailed, Message::ExportFailed(&format!("{:#}", e)), locale).into());
+        }
+    };
+    let body = match format {
+        ConversationExportFormat::Markdown => conversation_markdown(&export).map(String::into_bytes),
+        ConversationExportFormat::Json => serde_json::to_vec_pretty(&export).map_err(Into::into),
+    };
+
+    match body {
+        Ok(body) => Ok(HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!(
+                    "conversation-{}.{}",
+                    conversation_id,
+                    format.extension()
+                ))],
+            })
+            .body(body)),
+        Err(e) => {
+            error!("Error rendering conversation {}: {:#}", conversation_id, e);
+            Err(ApiError::new(ErrorCode::ProcessingFailed, Message::ExportFailed(&format!("{:#}", e)), locale).into())
         }
     }
 }
@@ -0,0 +1,157 @@
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use actix_web::http::header::{self, ContentDisposition, DispositionParam, DispositionType};
+use log::error;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::handlers::error::ApiError;
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::error::ErrorCode;
+use crate::models::job::{Job, JobStatus};
+use crate::models::response::UploadResponse;
+use crate::models::schema::SchemaUpdate;
+use crate::services::job_queue::JobQueue;
+use crate::services::{DatabaseServiceTrait, RedisServiceTrait, DataProcessor, S3ServiceTrait};
+
+/// Download the file uploaded for a job. Backends that can sign URLs answer with a
+/// redirect to a presigned URL so the file does not pass through the API; the
+/// others send the stored file as an attachment.
+pub async fn download_dataset<S, D, R>(
+    job_id: web::Path<Uuid>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    s3_service: web::Data<S>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let job_id = job_id.into_inner();
+    let locale = Locale::from_request(&req);
+
+    let job = match db_service.get_job(job_id).await {
+        Ok(Some(job)) if identity.can_access_job(&job) => job,
+        Ok(_) => {
+            return Err(ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(&job_id.to_string()), locale).into());
+        }
+        Err(e) => {
+            return Err(ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&e.to_string()), locale).into());
+        }
+    };
+    let source = job.source_key();
+
+    match s3_service.presigned_url(&source.bucket, &source.key, processor.download_url_expiry()).await {
+        Ok(Some(url)) => {
+            return Ok(HttpResponse::TemporaryRedirect()
+                .insert_header((header::LOCATION, url))
+                .finish());
+        }
+        Ok(None) => {}
+        Err(e) => error!("Failed to sign a download URL for {}, serving the file instead: {:#}", source, e),
+    }
+
+    match s3_service.get_object(&source.bucket, &source.key).await {
+        Ok(data) => Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!("dataset-{}.csv", job_id))],
+            })
+            .body(data)),
+        Err(e) => {
+            if let Ok(None) = s3_service.head_object(&source.key).await {
+                let message = Message::DatasetFileMissing(&job_id.to_string());
+                return Err(ApiError::new(ErrorCode::DatasetNotFound, message, locale).into());
+            }
+            error!("Error downloading dataset {} of job {}: {:#}", source, job_id, e);
+            let message = Message::DatasetDownloadFailed(&format!("{:#}", e));
+            Err(ApiError::new(ErrorCode::StorageError, message, locale).into())
+        }
+    }
+}
+
+/// Describe a job's columns as processing reads them: dtype, null ratio, any
+/// override in effect, and a suggested type for string columns whose values
+/// nearly all parse as a number, date or boolean
+pub async fn get_dataset_schema<S, D, R>(
+    job_id: web::Path<Uuid>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let job = accessible_job(db_service.get_ref(), job_id.into_inner(), &identity, locale).await?;
+
+    match processor.dataset_schema(&job).await {
+        Ok(schema) => Ok(HttpResponse::Ok().json(schema)),
+        Err(e) => {
+            error!("Error reading the schema of job {}: {:#}", job.id, e);
+            let message = Message::SchemaFailed(&format!("{:#}", e));
+            Err(ApiError::from_error(&e, ErrorCode::StorageError, message, locale).into())
+        }
+    }
+}
+
+/// Replace a job's column type overrides and queue it to be processed again
+/// with them. Columns missing from the body go back to their inferred type.
+pub async fn update_dataset_schema<S, D, R>(
+    job_id: web::Path<Uuid>,
+    body: web::Json<SchemaUpdate>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    let job = accessible_job(db_service.get_ref(), job_id.into_inner(), &identity, locale).await?;
+
+    // A job the worker holds would be reprocessed with the old types
+    let status = JobStatus::from_str(&job.status).map_err(|e| ApiError::new(ErrorCode::InternalError, Message::DatabaseError(&e.to_string()), locale))?;
+    if !status.can_transition_to(JobStatus::Queued) {
+        return Err(ApiError::new(ErrorCode::JobInProgress, Message::JobInProgress(&job.status.to_lowercase()), locale).into());
+    }
+    let Some(queue) = req.app_data::<web::Data<Arc<JobQueue>>>() else {
+        return Err(ApiError::new(ErrorCode::JobQueueUnavailable, Message::JobQueueUnavailable, locale).into());
+    };
+    // The job is only marked queued once a slot in the worker's queue is held for it
+    let slot = queue.reserve().map_err(|e| ApiError::from_queue_error(e, locale))?;
+
+    if let Err(e) = processor.update_schema(&job, &body.overrides).await {
+        error!("Error applying schema overrides to job {}: {:#}", job.id, e);
+        let message = Message::SchemaUpdateFailed(&format!("{:#}", e));
+        return Err(ApiError::from_error(&e, ErrorCode::InvalidRequest, message, locale).into());
+    }
+    slot.send(job.id);
+
+    Ok(HttpResponse::Accepted().json(UploadResponse {
+        job_id: job.id,
+        status: JobStatus::Queued.to_string(),
+        content_hash: job.content_hash,
+        message: Some(Message::SchemaQueued.localize(locale)),
+    }))
+}
+
+/// Look up a job the caller may access, answering 404 for jobs of other tenants
+async fn accessible_job<D: DatabaseServiceTrait>(db_service: &D, job_id: Uuid, identity: &Identity, locale: Locale) -> Result<Job, ApiError> {
+    match db_service.get_job(job_id).await {
+        Ok(Some(job)) if identity.can_access_job(&job) => Ok(job),
+        Ok(_) => Err(ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(&job_id.to_string()), locale)),
+        Err(e) => Err(ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&e.to_string()), locale)),
+    }
+}
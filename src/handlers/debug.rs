@@ -0,0 +1,36 @@
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use log::error;
+
+use crate::handlers::error::ApiError;
+use crate::i18n::{Locale, Message};
+use crate::models::admin::{FileListing, FilesQuery};
+use crate::models::auth::Admin;
+use crate::models::error::ErrorCode;
+use crate::services::S3ServiceTrait;
+
+/// Stored objects whose key starts with `prefix`, with their sizes and
+/// modification times. Only built into debug builds and builds with the
+/// `debug-endpoints` feature, and only answered for the admin token.
+pub async fn list_files<S>(
+    _admin: Admin,
+    query: web::Query<FilesQuery>,
+    s3_service: web::Data<S>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+{
+    let prefix = query.into_inner().prefix.unwrap_or_default();
+    match s3_service.list_objects(&prefix).await {
+        Ok(objects) => Ok(HttpResponse::Ok().json(FileListing {
+            total_bytes: objects.iter().map(|object| object.size).sum(),
+            prefix,
+            objects,
+        })),
+        Err(e) => {
+            error!("Error listing stored files under '{}': {:#}", prefix, e);
+            let message = Message::ListFilesFailed(&format!("{:#}", e));
+            Err(ApiError::new(ErrorCode::StorageError, message, Locale::from_request(&req)).into())
+        }
+    }
+}
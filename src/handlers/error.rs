@@ -0,0 +1,130 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use serde_json::{json, Value};
+use std::fmt;
+
+use crate::handlers::request_id;
+use crate::i18n::{Locale, Message};
+use crate::models::error::ErrorCode;
+use crate::models::response::ErrorResponse;
+use crate::services::conversation::QueryTooExpensive;
+use crate::services::job_queue::QueueError;
+use crate::services::llm::TransientLlmError;
+use crate::services::quota::QuotaExceeded;
+
+/// The error every handler answers with: a stable code, the HTTP status it maps
+/// to, a localized message and optional details, sent as an `ErrorResponse`
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub details: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: Message, locale: Locale) -> Self {
+        Self {
+            code,
+            message: message.localize(locale),
+            details: None,
+        }
+    }
+
+    /// Attach details; objects are merged into details the error already has
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = match (self.details.take(), details) {
+            (Some(Value::Object(mut existing)), Value::Object(added)) => {
+                existing.extend(added);
+                Some(Value::Object(existing))
+            }
+            (_, details) => Some(details),
+        };
+        self
+    }
+
+    /// An error from a lower layer. Quotas, query limits and AI provider failures
+    /// keep their own code wherever they surface; anything else is answered with
+    /// `code` and `message`.
+    pub fn from_error(error: &anyhow::Error, code: ErrorCode, message: Message, locale: Locale) -> Self {
+        for cause in error.chain() {
+            if let Some(reason) = cause.downcast_ref::<QuotaExceeded>() {
+                return Self::new(ErrorCode::QuotaExceeded, Message::QuotaExceeded(&reason.0), locale);
+            }
+            if let Some(reason) = cause.downcast_ref::<QueryTooExpensive>() {
+                return Self::new(ErrorCode::QueryTooExpensive, Message::QueryTooExpensive(&reason.0), locale);
+            }
+            if let Some(reason) = cause.downcast_ref::<TransientLlmError>() {
+                if reason.timed_out {
+                    return Self::new(ErrorCode::UpstreamAiTimeout, Message::AiTimeout(&reason.message), locale);
+                }
+                let error = Self::new(ErrorCode::UpstreamAiUnavailable, Message::AiUnavailable(&reason.message), locale);
+                return match reason.retry_after {
+                    Some(wait) => error.with_details(json!({ "retry_after_secs": wait.as_secs().max(1) })),
+                    None => error,
+                };
+            }
+        }
+        Self::new(code, message, locale)
+    }
+
+    /// A job that could not be queued: `JOB_QUEUE_FULL` with the queue's depth and
+    /// a `Retry-After` when every slot is taken, `JOB_QUEUE_UNAVAILABLE` otherwise
+    pub fn from_queue_error(error: QueueError, locale: Locale) -> Self {
+        match error {
+            QueueError::Full { depth, capacity, retry_after } => {
+                let retry_after_secs = retry_after.as_secs().max(1);
+                Self::new(ErrorCode::JobQueueFull, Message::JobQueueFull(retry_after_secs as usize), locale).with_details(json!({
+                    "queue_depth": depth,
+                    "queue_capacity": capacity,
+                    "retry_after_secs": retry_after_secs,
+                }))
+            }
+            QueueError::Closed => Self::new(ErrorCode::JobQueueUnavailable, Message::JobQueueFailed(&error.to_string()), locale),
+        }
+    }
+
+    /// Seconds the client is asked to wait before trying again, from the details
+    fn retry_after_secs(&self) -> Option<u64> {
+        self.details.as_ref()?.get("retry_after_secs")?.as_u64()
+    }
+
+    /// The `ErrorResponse` this error is answered with
+    pub fn body(&self) -> ErrorResponse {
+        ErrorResponse {
+            code: self.code,
+            error: self.message.clone(),
+            status_code: self.code.status(),
+            details: self.details.clone(),
+            request_id: request_id::current(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.code.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if self.code == ErrorCode::Unauthorized {
+            response.insert_header((header::WWW_AUTHENTICATE, "Bearer"));
+        }
+        if let Some(secs) = self.retry_after_secs() {
+            response.insert_header((header::RETRY_AFTER, secs.to_string()));
+        }
+        response.json(self.body())
+    }
+}
+
+/// Answer a JSON body, query string or path that does not parse with `INVALID_REQUEST`
+pub fn invalid_request(error: impl fmt::Display, req: &HttpRequest) -> actix_web::Error {
+    let message = Message::InvalidRequest(&error.to_string());
+    ApiError::new(ErrorCode::InvalidRequest, message, Locale::from_request(req)).into()
+}
@@ -1,105 +1,186 @@
-use actix_web::{web, HttpResponse, Error};
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::models::response::{InsightsResponse, UploadResponse, ErrorResponse};
+use crate::handlers::error::ApiError;
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::error::ErrorCode;
+use crate::models::response::{CacheInvalidationResponse, Insights, InsightsResponse, UploadResponse};
 use crate::models::job::JobStatus;
 use crate::services::{DatabaseServiceTrait, RedisServiceTrait, DataProcessor, S3ServiceTrait};
+use crate::services::processor::InsightsLookup;
 
 /// Get insights for a job
 pub async fn get_insights<S, D, R>(
     job_id: web::Path<Uuid>,
+    identity: Identity,
     db_service: web::Data<D>,
-    redis_service: web::Data<R>,
     processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, Error>
 where
     S: S3ServiceTrait + Clone + std::fmt::Debug + 'static,
     D: DatabaseServiceTrait + Clone + std::fmt::Debug + 'static,
     R: RedisServiceTrait + Clone + std::fmt::Debug + 'static,
 {
-    let job_id = job_id.into_inner();
-    
+    let locale = Locale::from_request(&req);
+    match job_insights(&identity, db_service.get_ref(), processor.get_ref(), job_id.into_inner(), locale).await? {
+        JobInsights::Ready(insights) => Ok(HttpResponse::Ok().json(insights)),
+        JobInsights::InProgress(status) => Ok(HttpResponse::Accepted().json(status)),
+    }
+}
+
+/// A job's insights, or its status while they are not ready yet
+pub(crate) enum JobInsights {
+    Ready(Box<InsightsResponse>),
+    InProgress(UploadResponse),
+}
+
+/// Look up a job's insights for a caller, whichever API asked for them
+pub(crate) async fn job_insights<S, D, R>(
+    identity: &Identity,
+    db_service: &D,
+    processor: &DataProcessor<S, D, R>,
+    job_id: Uuid,
+    locale: Locale,
+) -> Result<JobInsights, ApiError>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
     // Check if job exists
     let job = match db_service.get_job(job_id).await {
-        Ok(Some(job)) => job,
-        Ok(None) => {
-            return Ok(HttpResponse::NotFound().json(ErrorResponse {
-                error: format!("Job with ID {} not found", job_id),
-                status_code: 404,
-            }));
+        Ok(Some(job)) if identity.can_access_job(&job) => job,
+        Ok(_) => {
+            return Err(ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(&job_id.to_string()), locale));
         },
         Err(e) => {
-            return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Database error: {}", e),
-                status_code: 500,
-            }));
+            return Err(ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&e.to_string()), locale));
         }
     };
     
     // If job is not completed, return status
     if job.status != JobStatus::Completed.to_string() {
-        return Ok(HttpResponse::Accepted().json(UploadResponse {
+        return Ok(JobInsights::InProgress(UploadResponse {
             job_id,
             status: job.status.clone(),
-            message: Some(format!("Job is {}", job.status.to_lowercase())),
+            content_hash: job.content_hash.clone(),
+            message: Some(Message::JobInProgress(&job.status.to_lowercase()).localize(locale)),
         }));
     }
     
-    // Try to get insights from Redis cache
-    match redis_service.get_insights(job_id) {
-        Ok(Some(insights)) => {
-            // Return insights
-            Ok(HttpResponse::Ok().json(InsightsResponse {
+    // Read the insights through the cache, falling back to the stored copy; jobs
+    // processed before insights were stored have none and are recomputed
+    match processor.insights_or_recompute(job_id).await {
+        Ok(InsightsLookup::Ready(insights)) => {
+            Ok(JobInsights::Ready(Box::new(InsightsResponse {
                 job_id,
                 status: "completed".to_string(),
-                message: Some("Job completed successfully".to_string()),
-                insights: match serde_json::from_str(&insights) {
-                    Ok(parsed_insights) => Some(parsed_insights),
-                    Err(_) => None,
-                },
+                message: Some(Message::JobCompleted.localize(locale)),
+                insights: serde_json::from_str::<Insights>(&insights).ok().map(|mut insights| {
+                    insights.localize(locale);
+                    insights
+                }),
+            })))
+        },
+        Ok(InsightsLookup::Pending) => {
+            // Another request or instance is already computing them
+            let status = JobStatus::Processing.to_string();
+            Ok(JobInsights::InProgress(UploadResponse {
+                job_id,
+                status: status.clone(),
+                content_hash: job.content_hash.clone(),
+                message: Some(Message::JobInProgress(&status).localize(locale)),
             }))
         },
-        Ok(None) => {
-            // If insights not in cache, trigger processing
-            match processor.process_job(job_id).await {
-                Ok(_) => {
-                    // Try to get insights after processing
-                    match redis_service.get_insights(job_id) {
-                        Ok(Some(insights)) => {
-                            Ok(HttpResponse::Ok().json(InsightsResponse {
-                                job_id,
-                                status: "completed".to_string(),
-                                message: Some("Job completed successfully".to_string()),
-                                insights: match serde_json::from_str(&insights) {
-                                    Ok(parsed_insights) => Some(parsed_insights),
-                                    Err(_) => None,
-                                },
-                            }))
-                        },
-                        _ => {
-                            // If still no insights, return error
-                            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                                error: "Failed to generate insights".to_string(),
-                                status_code: 500,
-                            }))
-                        }
-                    }
-                },
-                Err(e) => {
-                    // Return processing error
-                    Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                        error: format!("Failed to process job: {}", e),
-                        status_code: 500,
-                    }))
-                }
-            }
+        Err(e) => {
+            log::error!("❌ [Job-{}] Failed to load or recompute insights: {:#}", job_id, e);
+            Err(ApiError::new(ErrorCode::ProcessingFailed, Message::JobProcessingFailed(&e.to_string()), locale))
+        }
+    }
+}
+
+/// Drop a job's cached insights so the next read loads the stored copy, for when
+/// the cache holds stale results
+pub async fn invalidate_insights_cache<S, D, R>(
+    job_id: web::Path<Uuid>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug + 'static,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug + 'static,
+    R: RedisServiceTrait + Clone + std::fmt::Debug + 'static,
+{
+    let job_id = job_id.into_inner();
+    let locale = Locale::from_request(&req);
+
+    match db_service.get_job(job_id).await {
+        Ok(Some(job)) if identity.can_access_job(&job) => {}
+        Ok(_) => {
+            return Err(ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(&job_id.to_string()), locale).into());
         },
         Err(e) => {
-            // Return Redis error
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Cache error: {}", e),
-                status_code: 500,
-            }))
+            return Err(ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&e.to_string()), locale).into());
+        }
+    }
+
+    match processor.invalidate_cache(job_id).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(CacheInvalidationResponse {
+            job_id,
+            message: Message::CacheInvalidated.localize(locale),
+        })),
+        Err(e) => {
+            log::error!("❌ [Job-{}] Failed to clear cached insights: {:#}", job_id, e);
+            Err(ApiError::new(ErrorCode::CacheError, Message::CacheError(&e.to_string()), locale).into())
+        }
+    }
+}
+
+/// Query parameters of the drift comparison endpoint
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    pub base: Uuid,
+    pub target: Uuid,
+}
+
+/// Compare two jobs' datasets: schema diff, row-count delta and per-column drift
+pub async fn compare_insights<S, D, R>(
+    query: web::Query<CompareQuery>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug + 'static,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug + 'static,
+    R: RedisServiceTrait + Clone + std::fmt::Debug + 'static,
+{
+    let locale = Locale::from_request(&req);
+
+    let mut jobs = Vec::with_capacity(2);
+    for job_id in [query.base, query.target] {
+        match db_service.get_job(job_id).await {
+            Ok(Some(job)) if identity.can_access_job(&job) => jobs.push(job),
+            Ok(_) => {
+                return Err(ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(&job_id.to_string()), locale).into());
+            },
+            Err(e) => {
+                return Err(ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&e.to_string()), locale).into());
+            }
+        }
+    }
+
+    match processor.compare_jobs(&jobs[0], &jobs[1]).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e) => {
+            log::error!("❌ Failed to compare jobs {} and {}: {}", query.base, query.target, e);
+            Err(ApiError::new(ErrorCode::ProcessingFailed, Message::ComparisonFailed(&e.to_string()), locale).into())
         }
     }
 }
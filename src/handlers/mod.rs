@@ -1,7 +1,28 @@
 pub mod upload;
+pub mod batch;
 pub mod insights;
 pub mod conversation;
+pub mod capabilities;
+pub mod query;
+pub mod usage;
+pub mod datasets;
+pub mod session;
+pub mod admin;
+#[cfg(any(debug_assertions, feature = "debug-endpoints"))]
+pub mod debug;
+pub mod auth;
+pub mod error;
+pub mod request_id;
 
 pub use upload::*;
+pub use batch::*;
 pub use insights::*;
 pub use conversation::*;
+pub use capabilities::*;
+pub use query::*;
+pub use usage::*;
+pub use datasets::*;
+pub use session::*;
+pub use admin::*;
+#[cfg(any(debug_assertions, feature = "debug-endpoints"))]
+pub use debug::*;
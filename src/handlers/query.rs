@@ -0,0 +1,188 @@
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use log::error;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::handlers::error::ApiError;
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::error::ErrorCode;
+use crate::models::query::{RunSavedQueryOptions, SaveQueryRequest, SavedQueryFilter, SqlRequest, StructuredQueryRequest};
+use crate::services::conversation::ConversationService;
+use crate::services::{S3ServiceTrait, DatabaseServiceTrait, RedisServiceTrait};
+
+/// 404 error when `job_id` does not name a known job the caller may see
+pub(crate) async fn missing_job<D: DatabaseServiceTrait>(
+    db_service: &D,
+    job_id: &str,
+    identity: &Identity,
+    locale: Locale,
+) -> Option<ApiError> {
+    let job_exists = match Uuid::parse_str(job_id) {
+        Ok(id) => matches!(db_service.get_job(id).await, Ok(Some(job)) if identity.can_access_job(&job)),
+        Err(_) => false,
+    };
+    (!job_exists).then(|| ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(job_id), locale))
+}
+
+/// Error for a failed query: 422 with a "narrow it down" explanation when the query
+/// limits stopped it, otherwise 400 with the error
+fn query_failed(e: &anyhow::Error, locale: Locale, failed: impl FnOnce(&str) -> Message) -> Error {
+    let reason = format!("{:#}", e);
+    ApiError::from_error(e, ErrorCode::QueryExecutionFailed, failed(&reason), locale).into()
+}
+
+/// Run SQL against an uploaded dataset, which is registered as the table `data`
+pub async fn sql_query<S, D, R>(
+    sql_req: web::Json<SqlRequest>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    if let Some(error) = missing_job(db_service.get_ref(), &sql_req.job_id, &identity, locale).await {
+        return Err(error.into());
+    }
+
+    match conversation_service.execute_sql(&sql_req).await {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(e) => {
+            error!("SQL query failed for job {}: {:#}", sql_req.job_id, e);
+            Err(query_failed(&e, locale, |e| Message::SqlQueryFailed(e)))
+        }
+    }
+}
+
+/// Run a structured query built by a client, bypassing natural language translation
+pub async fn structured_query<S, D, R>(
+    query_req: web::Json<StructuredQueryRequest>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    if let Some(error) = missing_job(db_service.get_ref(), &query_req.job_id, &identity, locale).await {
+        return Err(error.into());
+    }
+
+    match conversation_service.execute_structured(&query_req).await {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(e) => {
+            error!("Structured query failed for job {}: {:#}", query_req.job_id, e);
+            Err(query_failed(&e, locale, |e| Message::StructuredQueryFailed(e)))
+        }
+    }
+}
+
+/// Save a named structured query against a dataset
+pub async fn save_query<S, D, R>(
+    save_req: web::Json<SaveQueryRequest>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+    if let Some(error) = missing_job(db_service.get_ref(), &save_req.job_id, &identity, locale).await {
+        return Err(error.into());
+    }
+
+    match conversation_service.save_query(&save_req).await {
+        Ok(saved) => Ok(HttpResponse::Created().json(saved)),
+        Err(e) => {
+            error!("Saving query '{}' for job {} failed: {:#}", save_req.name, save_req.job_id, e);
+            Err(ApiError::new(ErrorCode::InvalidRequest, Message::SaveQueryFailed(&format!("{:#}", e)), locale).into())
+        }
+    }
+}
+
+/// List saved queries, optionally only those saved against one dataset
+pub async fn list_saved_queries<S, D, R>(
+    filter: web::Query<SavedQueryFilter>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let locale = Locale::from_request(&req);
+
+    match conversation_service.list_saved_queries(filter.job_id.as_deref()) {
+        Ok(queries) => {
+            // Only queries saved against datasets the caller may see
+            let mut visible = Vec::with_capacity(queries.len());
+            for query in queries {
+                if missing_job(db_service.get_ref(), &query.job_id, &identity, locale).await.is_none() {
+                    visible.push(query);
+                }
+            }
+            Ok(HttpResponse::Ok().json(visible))
+        }
+        Err(e) => {
+            error!("Error listing saved queries: {}", e);
+            Err(ApiError::new(ErrorCode::InternalError, Message::QueryProcessingFailed(&e.to_string()), locale).into())
+        }
+    }
+}
+
+/// Re-run a saved query on its dataset, or on the `job_id` given
+pub async fn run_saved_query<S, D, R>(
+    query_id: web::Path<String>,
+    options: web::Query<RunSavedQueryOptions>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let query_id = query_id.into_inner();
+    let locale = Locale::from_request(&req);
+
+    let saved = match conversation_service.get_saved_query(&query_id) {
+        Ok(Some(saved)) if missing_job(db_service.get_ref(), &saved.job_id, &identity, locale).await.is_none() => saved,
+        Ok(_) => {
+            return Err(ApiError::new(ErrorCode::SavedQueryNotFound, Message::SavedQueryNotFound(&query_id), locale).into())
+        }
+        Err(e) => {
+            error!("Error loading saved query {}: {}", query_id, e);
+            return Err(ApiError::new(ErrorCode::InternalError, Message::QueryProcessingFailed(&e.to_string()), locale).into());
+        }
+    };
+    let job_id = options.job_id.as_deref().unwrap_or(&saved.job_id);
+    if let Some(error) = missing_job(db_service.get_ref(), job_id, &identity, locale).await {
+        return Err(error.into());
+    }
+
+    match conversation_service.run_saved_query(&saved, Some(job_id)).await {
+        Ok(result) => Ok(HttpResponse::Ok().json(result)),
+        Err(e) => {
+            error!("Saved query {} failed for job {}: {:#}", query_id, job_id, e);
+            Err(query_failed(&e, locale, |e| Message::StructuredQueryFailed(e)))
+        }
+    }
+}
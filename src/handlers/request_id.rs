@@ -0,0 +1,70 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::Error;
+use std::future::Future;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Header a request ID is read from and echoed in
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request ID taken from a caller; longer ones are replaced by a fresh ID
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// ID of the request being handled on this task, `None` outside a request such as
+/// in the worker
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok().filter(|id| !id.is_empty())
+}
+
+/// Run `future`, typically spawned to keep serving a request such as a WebSocket
+/// session, as part of the current request: its errors and log lines name it too
+pub fn in_current_request<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    within(current().unwrap_or_default(), future).instrument(tracing::Span::current())
+}
+
+/// Run `future` as the request `request_id`, for requests that do not pass
+/// through `propagate` such as gRPC calls
+pub fn within<F: Future>(request_id: String, future: F) -> impl Future<Output = F::Output> {
+    REQUEST_ID.scope(request_id, future)
+}
+
+/// The caller's request ID when it is usable, otherwise a fresh one
+pub fn accept(caller_id: Option<&str>) -> String {
+    caller_id
+        .filter(|id| is_usable(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// A caller's ID is kept when it is short and made of characters safe to log and
+/// echo in a header
+fn is_usable(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// Middleware that takes the caller's `X-Request-Id`, or assigns one, and handles
+/// the request inside a `request` span carrying it, so every log line written
+/// while handling it names the request. The ID is echoed in the response.
+pub async fn propagate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let request_id = accept(req.headers().get(REQUEST_ID_HEADER).and_then(|value| value.to_str().ok()));
+
+    let span = tracing::info_span!("request", request_id = %request_id, method = %req.method(), path = %req.path());
+    let mut response = within(request_id.clone(), next.call(req)).instrument(span).await?;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    Ok(response)
+}
@@ -0,0 +1,227 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_ws::{Closed, Message as Frame, MessageStream, Session};
+use futures::StreamExt;
+use log::{error, info, warn};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::handlers::conversation::query_turn_failed;
+use crate::handlers::error::ApiError;
+use crate::handlers::query::missing_job;
+use crate::handlers::request_id;
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::conversation::QueryRequest;
+use crate::models::error::ErrorCode;
+use crate::models::session::{ClientFrame, ServerFrame, SessionOptions};
+use crate::services::conversation::ConversationService;
+use crate::services::{DatabaseServiceTrait, RedisServiceTrait, S3ServiceTrait};
+
+/// Open an interactive conversation session over WebSocket. A session is bound to
+/// one conversation: `conversation_id` continues it, `job_id` starts one with the
+/// first question. Each `query` frame is answered with typing indicators, partial
+/// results as the answer takes shape, then the response or an error.
+pub async fn conversation_session<S, D, R>(
+    options: web::Query<SessionOptions>,
+    identity: Identity,
+    db_service: web::Data<D>,
+    conversation_service: web::Data<Arc<ConversationService<S, D, R>>>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug + 'static,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug + 'static,
+    R: RedisServiceTrait + Clone + std::fmt::Debug + 'static,
+{
+    let locale = Locale::from_request(&req);
+    let SessionOptions { conversation_id, job_id } = options.into_inner();
+
+    // The session's conversation and dataset must be the caller's, as for HTTP queries
+    let job_id = match (&conversation_id, job_id) {
+        (Some(id), _) => match conversation_service.get_conversation(id) {
+            Ok(Some(detail))
+                if identity.can_access(detail.conversation.tenant_id.as_deref(), detail.conversation.user_id.as_deref()) =>
+            {
+                detail.conversation.job_id
+            }
+            Ok(_) => return Err(ApiError::new(ErrorCode::ConversationNotFound, Message::ConversationNotFound(id), locale).into()),
+            Err(e) => {
+                error!("Error loading conversation {}: {}", id, e);
+                return Err(ApiError::new(ErrorCode::InternalError, Message::QueryProcessingFailed(&e.to_string()), locale).into());
+            }
+        },
+        (None, Some(job_id)) => job_id,
+        (None, None) => {
+            let message = Message::InvalidRequest("a session needs a conversation_id or a job_id");
+            return Err(ApiError::new(ErrorCode::InvalidRequest, message, locale).into());
+        }
+    };
+    if let Some(error) = missing_job(db_service.get_ref(), &job_id, &identity, locale).await {
+        return Err(error.into());
+    }
+
+    let (response, session, frames) = actix_ws::handle(&req, body)?;
+    let service = conversation_service.get_ref().clone();
+    actix_web::rt::spawn(request_id::in_current_request(run_session(
+        service,
+        session,
+        frames,
+        job_id,
+        conversation_id,
+        locale,
+    )));
+    Ok(response)
+}
+
+/// Answer the frames of one session until the client closes it
+async fn run_session<S, D, R>(
+    service: Arc<ConversationService<S, D, R>>,
+    mut session: Session,
+    mut frames: MessageStream,
+    job_id: String,
+    mut conversation_id: Option<String>,
+    locale: Locale,
+) where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    info!("🔌 Opened conversation session for job {}", job_id);
+    let opened = ServerFrame::Session {
+        job_id: job_id.clone(),
+        conversation_id: conversation_id.clone(),
+    };
+    if send(&mut session, &opened).await.is_err() {
+        return;
+    }
+
+    while let Some(frame) = frames.next().await {
+        let text = match frame {
+            Ok(Frame::Text(text)) => text,
+            Ok(Frame::Ping(bytes)) => {
+                if session.pong(&bytes).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            Ok(Frame::Close(reason)) => {
+                info!("🔌 Conversation session for job {} closed by the client", job_id);
+                let _ = session.close(reason).await;
+                return;
+            }
+            Ok(Frame::Binary(_)) => {
+                if invalid_frame(&mut session, "frames must be JSON text", locale).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            Ok(_) => continue,
+            Err(e) => {
+                warn!("Conversation session for job {} failed: {}", job_id, e);
+                break;
+            }
+        };
+
+        let ClientFrame::Query { query, page, page_size, cursor, explain } = match serde_json::from_str(&text) {
+            Ok(frame) => frame,
+            Err(e) => {
+                if invalid_frame(&mut session, &e.to_string(), locale).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        let request = QueryRequest {
+            job_id: job_id.clone(),
+            query,
+            conversation_id: conversation_id.clone(),
+            page,
+            page_size,
+            cursor,
+            explain,
+        };
+
+        // A cursor names its own conversation, which must be this session's
+        let (target_job, target_conversation) = service.query_target(&request);
+        let bound = conversation_id.is_none() || target_conversation == conversation_id;
+        if target_job != job_id || !bound {
+            if invalid_frame(&mut session, "the cursor belongs to another conversation", locale).await.is_err() {
+                return;
+            }
+            continue;
+        }
+
+        if answer(&service, &mut session, request, &job_id, &mut conversation_id, locale).await.is_err() {
+            info!("🔌 Conversation session for job {} went away", job_id);
+            return;
+        }
+    }
+    let _ = session.close(None).await;
+}
+
+/// Answer one question, streaming its progress; fails only when the client is gone
+async fn answer<S, D, R>(
+    service: &ConversationService<S, D, R>,
+    session: &mut Session,
+    request: QueryRequest,
+    job_id: &str,
+    conversation_id: &mut Option<String>,
+    locale: Locale,
+) -> Result<(), Closed>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    send(session, &ServerFrame::Typing { active: true }).await?;
+
+    let (progress, mut steps) = mpsc::unbounded_channel();
+    let turn = service.process_query_with_progress(request, locale, Some(&progress));
+    tokio::pin!(turn);
+    let result = loop {
+        tokio::select! {
+            result = &mut turn => break result,
+            Some(step) = steps.recv() => send(session, &ServerFrame::Partial(step)).await?,
+        }
+    };
+    while let Ok(step) = steps.try_recv() {
+        send(session, &ServerFrame::Partial(step)).await?;
+    }
+
+    match result {
+        Ok(response) => {
+            // The first answer creates the conversation the session is then bound to
+            if conversation_id.as_deref() != Some(response.conversation_id.as_str()) {
+                *conversation_id = Some(response.conversation_id.clone());
+                let bound = ServerFrame::Session {
+                    job_id: job_id.to_string(),
+                    conversation_id: conversation_id.clone(),
+                };
+                send(session, &bound).await?;
+            }
+            send(session, &ServerFrame::Response(response)).await?;
+        }
+        Err(e) => {
+            error!("Error processing query: {:#}", e);
+            send(session, &ServerFrame::Error(query_turn_failed(&e, locale).body())).await?;
+        }
+    }
+    send(session, &ServerFrame::Typing { active: false }).await
+}
+
+/// Tell the client a frame was not understood, keeping the session open
+async fn invalid_frame(session: &mut Session, reason: &str, locale: Locale) -> Result<(), Closed> {
+    let error = ApiError::new(ErrorCode::InvalidRequest, Message::InvalidRequest(reason), locale);
+    send(session, &ServerFrame::Error(error.body())).await
+}
+
+async fn send(session: &mut Session, frame: &ServerFrame) -> Result<(), Closed> {
+    match serde_json::to_string(frame) {
+        Ok(text) => session.text(text).await,
+        Err(e) => {
+            error!("Failed to serialize a session frame: {}", e);
+            Ok(())
+        }
+    }
+}
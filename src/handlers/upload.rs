@@ -1,36 +1,91 @@
 use actix_web::{web, HttpResponse, Error};
 use std::sync::Arc;
-use actix_multipart::Multipart;
+use actix_multipart::{Field, Multipart};
 use futures::StreamExt;
 use uuid::Uuid;
-use std::io::Write;
-use tokio::sync::mpsc;
+use bytes::{Bytes, BytesMut};
 use actix_web::HttpRequest;
+use serde::Deserialize;
+use tokio::sync::mpsc;
 
-use crate::models::response::{UploadResponse, ErrorResponse};
+use crate::handlers::error::ApiError;
+use crate::handlers::request_id;
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::error::ErrorCode;
+use crate::models::response::UploadResponse;
 use crate::models::job::{NewJob, JobStatus};
-use crate::services::{DatabaseServiceTrait, S3ServiceTrait};
+use crate::models::profile::ProfileSettings;
+use crate::models::storage::{blob_key, content_hash, is_blob_key, tenant_key};
+use crate::services::processor::{AppendFault, InvalidAppend, JobLocked};
+use crate::services::job_queue::JobQueue;
+use crate::services::{DatabaseServiceTrait, RedisServiceTrait, S3ServiceTrait, DataProcessor};
+
+/// Optional query parameters of the upload endpoint
+#[derive(Debug, Default, Deserialize)]
+pub struct UploadOptions {
+    /// Insight profile for the job: minimal, basic or full
+    pub profile: Option<String>,
+    /// Per-column overrides written as `column:profile,column:profile`
+    pub column_profiles: Option<String>,
+    /// Comma-separated numeric columns for the Benford/plausibility checks, or `*` for all
+    pub plausibility_columns: Option<String>,
+    /// Frequent values reported per categorical column
+    pub top_values: Option<usize>,
+}
+
+impl UploadOptions {
+    /// Profile settings requested by the upload, if it asked for any
+    pub(crate) fn profile_settings(&self, default: ProfileSettings) -> anyhow::Result<Option<ProfileSettings>> {
+        if self.profile.is_none()
+            && self.column_profiles.is_none()
+            && self.plausibility_columns.is_none()
+            && self.top_values.is_none()
+        {
+            return Ok(None);
+        }
+        let mut settings = default;
+        if let Some(profile) = &self.profile {
+            settings.profile = profile.parse()?;
+        }
+        if let Some(overrides) = &self.column_profiles {
+            settings = settings.with_column_overrides(overrides)?;
+        }
+        if let Some(columns) = &self.plausibility_columns {
+            settings = settings.with_plausibility_columns(columns);
+        }
+        if let Some(top_values) = self.top_values {
+            settings = settings.with_top_values(top_values)?;
+        }
+        Ok(Some(settings))
+    }
+}
 
 /// Handle file upload, store in S3, and create a job
-pub async fn upload_csv<S, D>(
+pub async fn upload_csv<S, D, R>(
     mut payload: Multipart,
-    db_service: web::Data<D>,
-    s3_service: web::Data<S>,
+    options: web::Query<UploadOptions>,
+    identity: Identity,
+    processor: web::Data<DataProcessor<S, D, R>>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error>
 where
-    S: S3ServiceTrait,
-    D: DatabaseServiceTrait,
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
 {
-    // Default user ID (in a real app, this would come from authentication)
-    let user_id = "user123".to_string();
-    
-    // Generate a unique job ID and file key
-    let job_id = Uuid::new_v4();
-    let file_key = format!("uploads/{}.csv", job_id);
-    
+    let locale = Locale::from_request(&req);
+
+    // Validate the requested insight profile before accepting the file
+    let profile_settings = match options.profile_settings(processor.default_profile()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            return Err(ApiError::new(ErrorCode::InvalidRequest, Message::InvalidProfile(&e.to_string()), locale).into());
+        }
+    };
+
     // Process the multipart form data
-    let mut file_content = Vec::new();
+    let mut file_content = BytesMut::new();
     let mut filename = String::new();
     
     while let Some(item) = payload.next().await {
@@ -44,86 +99,253 @@ where
                     filename = fname.to_string();
                 }
                 
-                // Read the file data
-                while let Some(chunk) = field.next().await {
-                    let data = chunk?;
-                    file_content.write_all(&data)?;
-                }
+                file_content = read_file(&mut field, processor.max_upload_bytes(), locale).await?;
             }
         }
     }
     
+    let queue = req.app_data::<web::Data<Arc<JobQueue>>>().map(|queue| queue.as_ref().as_ref());
+    let slot = reserve_slot(queue, locale)?;
+    let file = UploadedFile {
+        filename,
+        content: file_content.freeze(),
+        batch_id: None,
+    };
+    let response = accept_upload(processor.get_ref(), slot, identity, file, profile_settings, locale).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Read a multipart file field, answering `FILE_TOO_LARGE` as soon as it grows past
+/// `max_bytes` rather than buffering the rest of it
+async fn read_file(field: &mut Field, max_bytes: Option<u64>, locale: Locale) -> Result<BytesMut, Error> {
+    let mut content = BytesMut::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk?;
+        if let Some(max) = max_bytes.filter(|max| (content.len() + chunk.len()) as u64 > *max) {
+            return Err(ApiError::new(ErrorCode::FileTooLarge, Message::FileTooLarge(max), locale).into());
+        }
+        content.extend_from_slice(&chunk);
+    }
+    Ok(content)
+}
+
+/// A CSV received through one of the APIs
+pub(crate) struct UploadedFile {
+    pub filename: String,
+    pub content: Bytes,
+    /// Batch upload the file arrived in
+    pub batch_id: Option<Uuid>,
+}
+
+/// Hold a slot in the worker's queue before anything is stored, so a full queue
+/// turns the upload away at once and every created job is sure to be queued
+pub(crate) fn reserve_slot(queue: Option<&JobQueue>, locale: Locale) -> Result<mpsc::Permit<'_, Uuid>, ApiError> {
+    let Some(queue) = queue else {
+        log::error!("❌ Job queue sender is not available");
+        return Err(ApiError::new(ErrorCode::JobQueueUnavailable, Message::JobQueueUnavailable, locale));
+    };
+    queue.reserve().map_err(|e| ApiError::from_queue_error(e, locale))
+}
+
+/// Store an uploaded CSV, create its job and queue it for the worker through the
+/// slot held for it, whichever API the file arrived through
+pub(crate) async fn accept_upload<S, D, R>(
+    processor: &DataProcessor<S, D, R>,
+    slot: mpsc::Permit<'_, Uuid>,
+    identity: Identity,
+    file: UploadedFile,
+    profile_settings: Option<ProfileSettings>,
+    locale: Locale,
+) -> Result<UploadResponse, ApiError>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    // The uploader owns the job, within their tenant
+    let Identity { user_id, tenant_id, .. } = identity;
+    let UploadedFile { filename, content: file_content, batch_id } = file;
+    let s3_service = processor.get_s3_service();
+
     // Validate the file
     if file_content.is_empty() {
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "No file uploaded".to_string(),
-            status_code: 400,
-        }));
+        return Err(ApiError::new(ErrorCode::NoFileUploaded, Message::NoFileUploaded, locale));
     }
     
     if !filename.to_lowercase().ends_with(".csv") {
-        return Ok(HttpResponse::BadRequest().json(ErrorResponse {
-            error: "File must be a CSV".to_string(),
-            status_code: 400,
-        }));
+        return Err(ApiError::new(ErrorCode::UnsupportedFileType, Message::FileMustBeCsv, locale));
     }
     
+    // With dedup on, identical uploads of a tenant share one blob keyed by their hash
+    let hash = content_hash(&file_content);
+    let file_key = if processor.dedup_uploads() {
+        blob_key(&tenant_id, &hash)
+    } else {
+        tenant_key(&tenant_id, &format!("uploads/{}.csv", Uuid::new_v4()))
+    };
+    let already_stored = processor.dedup_uploads()
+        && matches!(s3_service.head_object(&file_key).await, Ok(Some(_)));
+
+    let new_bytes = if already_stored { 0 } else { file_content.len() as u64 };
+    if let Err(e) = processor.reserve_upload(&tenant_id, new_bytes).await {
+        let message = Message::UploadFailed(&format!("{:#}", e));
+        return Err(ApiError::from_error(&e, ErrorCode::StorageError, message, locale));
+    }
+
+    let stored = if already_stored {
+        log::info!("♻️ Upload matches stored blob {}, reusing it", file_key);
+        Ok(())
+    } else {
+        s3_service.upload_file(&file_key, file_content).await
+    };
+
     // Upload file to S3
-    match s3_service.upload_file(&file_key, file_content).await {
+    match stored {
         Ok(_) => {
             // Create job in database
             let new_job = NewJob {
                 user_id: user_id.clone(),
+                tenant_id: tenant_id.clone(),
+                request_id: request_id::current(),
+                bucket: processor.bucket().to_string(),
                 file_key: file_key.clone(),
+                content_hash: hash.clone(),
+                batch_id,
             };
             
-            match db_service.create_job(new_job).await {
+            match processor.get_db_service().create_job(new_job).await {
                 Ok(job_id) => {
-                    // Get the job queue sender
-                    log::info!("🔄 Attempting to queue job: {} for processing", job_id);
-                    if let Some(tx) = req.app_data::<web::Data<Arc<mpsc::Sender<Uuid>>>>() {
-                        // Send job to the worker
-                        match tx.send(job_id).await {
-                            Ok(_) => log::info!("✅ Successfully queued job: {} for processing", job_id),
-                            Err(e) => {
-                                log::error!("❌ Failed to queue job: {} - Error: {}", job_id, e);
-                                return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                                    error: format!("Failed to queue job: {}", e),
-                                    status_code: 500,
-                                }));
-                            }
+                    // Record the requested profile where the worker will look for it
+                    if let Some(settings) = &profile_settings {
+                        if let Err(e) = processor.get_redis_service().cache_profile_settings(job_id, settings).await {
+                            log::warn!("⚠️ Failed to store insight profile for job {}: {}", job_id, e);
                         }
-                    } else {
-                        log::error!("❌ Job queue sender not found in app_data");
-                        return Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                            error: "Job queue unavailable".to_string(),
-                            status_code: 500,
-                        }));
                     }
+
+                    // Announce the job before the worker can pick it up
+                    processor.publish_status(job_id, JobStatus::Queued, None).await;
+
+                    // Send job to the worker through the slot held for it
+                    slot.send(job_id);
+                    log::info!("✅ Successfully queued job: {} for processing", job_id);
                     
                     // Return success response
                     let status = JobStatus::Queued.to_string();
-                    Ok(HttpResponse::Ok().json(UploadResponse {
+                    Ok(UploadResponse {
                         job_id,
                         status: status.clone(),
-                        message: Some(format!("File uploaded and job queued for processing. Status: {}", status)),
-                    }))
+                        content_hash: Some(hash),
+                        message: Some(Message::JobQueued(&status).localize(locale)),
+                    })
                 },
                 Err(e) => {
+                    // No job points at the file, so remove it unless it was already
+                    // stored or is a blob other uploads may share
+                    let mut removed_bytes = 0;
+                    if !already_stored && !is_blob_key(&file_key) {
+                        match s3_service.delete_object(&file_key).await {
+                            Ok(()) => removed_bytes = new_bytes,
+                            Err(delete_error) => {
+                                log::warn!("⚠️ Failed to remove {} after job creation failed: {}", file_key, delete_error);
+                            }
+                        }
+                    }
+                    processor.release_upload(&tenant_id, removed_bytes).await;
                     // Return database error
-                    Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                        error: format!("Failed to create job: {}", e),
-                        status_code: 500,
-                    }))
+                    Err(ApiError::new(ErrorCode::DatabaseError, Message::JobCreationFailed(&e.to_string()), locale))
                 }
             }
         },
         Err(e) => {
+            processor.release_upload(&tenant_id, new_bytes).await;
             // Return S3 upload error
-            Ok(HttpResponse::InternalServerError().json(ErrorResponse {
-                error: format!("Failed to upload file: {}", e),
-                status_code: 500,
+            Err(ApiError::new(ErrorCode::StorageError, Message::UploadFailed(&e.to_string()), locale))
+        }
+    }
+}
+
+/// Append CSV rows to a completed job's dataset and update its insights incrementally
+pub async fn append_csv<S, D, R>(
+    job_id: web::Path<Uuid>,
+    mut payload: Multipart,
+    identity: Identity,
+    db_service: web::Data<D>,
+    processor: web::Data<DataProcessor<S, D, R>>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let job_id = job_id.into_inner();
+    let locale = Locale::from_request(&req);
+
+    let job = match db_service.get_job(job_id).await {
+        Ok(Some(job)) if identity.can_access_job(&job) => job,
+        Ok(_) => {
+            return Err(ApiError::new(ErrorCode::JobNotFound, Message::JobNotFound(&job_id.to_string()), locale).into());
+        },
+        Err(e) => {
+            return Err(ApiError::new(ErrorCode::DatabaseError, Message::DatabaseError(&e.to_string()), locale).into());
+        }
+    };
+
+    // Appends are merged into finished insights, so the first pass must be done
+    if job.status != JobStatus::Completed.to_string() {
+        return Err(ApiError::new(ErrorCode::JobInProgress, Message::JobInProgress(&job.status.to_lowercase()), locale).into());
+    }
+
+    let mut file_content = BytesMut::new();
+    let mut filename = String::new();
+
+    while let Some(item) = payload.next().await {
+        let mut field = item?;
+        let content_disposition = field.content_disposition();
+
+        if content_disposition.get_name() == Some("file") {
+            if let Some(fname) = content_disposition.get_filename() {
+                filename = fname.to_string();
+            }
+            file_content = read_file(&mut field, processor.max_upload_bytes(), locale).await?;
+        }
+    }
+
+    if file_content.is_empty() {
+        return Err(ApiError::new(ErrorCode::NoFileUploaded, Message::NoFileUploaded, locale).into());
+    }
+
+    if !filename.to_lowercase().ends_with(".csv") {
+        return Err(ApiError::new(ErrorCode::UnsupportedFileType, Message::FileMustBeCsv, locale).into());
+    }
+
+    match processor.append_data(job_id, &file_content).await {
+        Ok(rows) => {
+            log::info!("✅ [Job-{}] Appended {} rows", job_id, rows);
+            let content_hash = db_service.get_job(job_id).await.ok().flatten().and_then(|job| job.content_hash);
+            Ok(HttpResponse::Ok().json(UploadResponse {
+                job_id,
+                status: JobStatus::Completed.to_string(),
+                content_hash,
+                message: Some(Message::RowsAppended(rows).localize(locale)),
             }))
+        },
+        // Another append or processing run holds the job
+        Err(e) if e.downcast_ref::<JobLocked>().is_some() => {
+            Err(ApiError::new(ErrorCode::JobInProgress, Message::JobInProgress(&JobStatus::Processing.to_string()), locale).into())
+        },
+        Err(e) => {
+            log::error!("❌ [Job-{}] Failed to append data: {:#}", job_id, e);
+            // Rows the dataset can't take are the caller's to fix; a failing
+            // service is answered as that service's error
+            let code = match e.downcast_ref::<AppendFault>() {
+                Some(AppendFault::Storage) => ErrorCode::StorageError,
+                Some(AppendFault::Database) => ErrorCode::DatabaseError,
+                Some(AppendFault::Cache) => ErrorCode::CacheError,
+                None if e.downcast_ref::<InvalidAppend>().is_some() => ErrorCode::InvalidRequest,
+                None => ErrorCode::InternalError,
+            };
+            Err(ApiError::from_error(&e, code, Message::AppendFailed(&format!("{:#}", e)), locale).into())
         }
     }
 }
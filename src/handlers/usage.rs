@@ -0,0 +1,39 @@
+use actix_web::{web, HttpRequest, HttpResponse, Error};
+use log::error;
+
+use crate::handlers::error::ApiError;
+use crate::i18n::{Locale, Message};
+use crate::models::auth::Identity;
+use crate::models::error::ErrorCode;
+use crate::models::usage::UsageQuery;
+use crate::services::{DatabaseServiceTrait, RedisServiceTrait, DataProcessor, S3ServiceTrait};
+
+/// Tokens and estimated cost of AI calls per job, user and day, optionally
+/// filtered by job, tenant, user, conversation and date range
+pub async fn get_usage<S, D, R>(
+    processor: web::Data<DataProcessor<S, D, R>>,
+    query: web::Query<UsageQuery>,
+    identity: Identity,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    // Signed-in callers only see their own spend
+    let mut query = query.into_inner();
+    if identity.is_authenticated() {
+        query.tenant_id = Some(identity.tenant_id);
+        query.user_id = Some(identity.user_id);
+    }
+
+    match processor.usage_ledger().report(&query).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e) => {
+            error!("Error reporting AI usage: {}", e);
+            let message = Message::UsageReportFailed(&e.to_string());
+            Err(ApiError::new(ErrorCode::InternalError, message, Locale::from_request(&req)).into())
+        }
+    }
+}
@@ -0,0 +1,663 @@
+use actix_web::http::header::ACCEPT_LANGUAGE;
+use actix_web::HttpRequest;
+
+/// Languages the message catalog is translated into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+    Pt,
+}
+
+impl Locale {
+    /// Pick the locale from the request's `Accept-Language` header, falling back to English
+    pub fn from_request(req: &HttpRequest) -> Self {
+        req.headers()
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(Self::from_accept_language)
+            .unwrap_or_default()
+    }
+
+    /// Resolve an `Accept-Language` value such as `fr-CA,fr;q=0.9,en;q=0.8`
+    /// to the supported language with the highest quality weight
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut candidates: Vec<(f32, Locale)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.trim().split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                let primary = tag.split('-').next()?.to_lowercase();
+                let locale = match primary.as_str() {
+                    "en" => Locale::En,
+                    "fr" => Locale::Fr,
+                    "pt" => Locale::Pt,
+                    _ => return None,
+                };
+                (quality > 0.0).then_some((quality, locale))
+            })
+            .collect();
+
+        // Stable sort keeps header order for equal weights
+        candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        candidates.first().map(|(_, locale)| *locale).unwrap_or_default()
+    }
+
+    /// English name of the language, used to steer AI-generated narratives
+    pub fn language_name(&self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::Fr => "French",
+            Locale::Pt => "Portuguese",
+        }
+    }
+
+    /// Every supported locale, default first
+    pub const ALL: [Locale; 3] = [Locale::En, Locale::Fr, Locale::Pt];
+
+    /// ISO 639-1 language code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+            Locale::Pt => "pt",
+        }
+    }
+
+    /// A number rounded to two decimals with the locale's separators:
+    /// `1,234.56`, `1 234,56` or `1.234,56`
+    pub fn format_number(&self, value: f64) -> String {
+        if !value.is_finite() {
+            return value.to_string();
+        }
+        let (thousands, decimal) = match self {
+            Locale::En => (',', '.'),
+            Locale::Fr => (' ', ','),
+            Locale::Pt => ('.', ','),
+        };
+        let rounded = format!("{:.2}", value.abs());
+        let (whole, fraction) = rounded.split_once('.').unwrap_or((&rounded, ""));
+        let fraction = fraction.trim_end_matches('0');
+
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                grouped.push(thousands);
+            }
+            grouped.push(digit);
+        }
+        let sign = if value < 0.0 && rounded.chars().any(|c| c.is_ascii_digit() && c != '0') { "-" } else { "" };
+        if fraction.is_empty() {
+            format!("{}{}", sign, grouped)
+        } else {
+            format!("{}{}{}{}", sign, grouped, decimal, fraction)
+        }
+    }
+}
+
+/// Deterministic user-facing messages. Variants carry the values interpolated
+/// into the text; error details from lower layers are passed through untranslated.
+#[derive(Debug, Clone)]
+pub enum Message<'a> {
+    InvalidRequest(&'a str),
+    NoFileUploaded,
+    FileMustBeCsv,
+    UploadFailed(&'a str),
+    JobCreationFailed(&'a str),
+    JobQueueFailed(&'a str),
+    JobQueueUnavailable,
+    JobQueueFull(usize),
+    JobQueued(&'a str),
+    JobNotFound(&'a str),
+    BatchNotFound(&'a str),
+    TooManyFiles(usize),
+    FileTooLarge(u64),
+    FetchFailed(&'a str),
+    BatchQueued(usize),
+    ConversationNotFound(&'a str),
+    DatabaseError(&'a str),
+    CacheError(&'a str),
+    JobInProgress(&'a str),
+    JobCompleted,
+    CacheInvalidated,
+    JobProcessingFailed(&'a str),
+    QueryProcessingFailed(&'a str),
+    QueryNotUnderstood(&'a str),
+    QueryExecutionFailed(&'a str),
+    ResultFormattingFailed(&'a str),
+    NoDataFound,
+    ResultsReady,
+    AppendFailed(&'a str),
+    RowsAppended(usize),
+    InvalidProfile(&'a str),
+    ComparisonFailed(&'a str),
+    SqlQueryFailed(&'a str),
+    StructuredQueryFailed(&'a str),
+    InvalidPagination(&'a str),
+    ResultNotFound(&'a str),
+    ExportFailed(&'a str),
+    QueryTooExpensive(&'a str),
+    SavedQueryNotFound(&'a str),
+    SaveQueryFailed(&'a str),
+    FeedbackFailed(&'a str),
+    SuggestionsFailed(&'a str),
+    UsageReportFailed(&'a str),
+    DatasetFileMissing(&'a str),
+    DatasetDownloadFailed(&'a str),
+    SchemaFailed(&'a str),
+    SchemaUpdateFailed(&'a str),
+    SchemaQueued,
+    AdminReportFailed(&'a str),
+    JobMarkedFailed,
+    JobCancelled,
+    ListFilesFailed(&'a str),
+    Unauthorized(&'a str),
+    AuthenticationUnavailable(&'a str),
+    QuotaExceeded(&'a str),
+    AiTimeout(&'a str),
+    AiUnavailable(&'a str),
+    /// `(aggregation, column)`, where the aggregation is `mean`, `sum`, `count`,
+    /// `median`, `std`, `min`, `max`, `distinct_count` or a percentile such as `p90`
+    AggregationOf(&'a str, &'a str),
+    AggregateValue(&'a str, &'a str),
+    AcrossRows(usize),
+    /// `(label, highest group, its value, lowest group, its value)`
+    GroupExtremes(&'a str, &'a str, &'a str, &'a str, &'a str),
+    GroupCount(usize, &'a str),
+    MatchingRows(usize, &'a str),
+    And,
+    SortedRows(usize, &'a str, &'a str),
+    FirstRows(usize, usize),
+    PivotTable(&'a str, &'a str, usize, usize),
+    ResultRows(usize),
+    /// `(rows, columns, numeric, categorical, date)`
+    DatasetShape(usize, usize, usize, usize, usize),
+    StatisticsSampled(usize, usize),
+    ProcessedInLowMemory,
+    IdentifierColumns(&'a [String]),
+    ConstantColumns(&'a [String]),
+    HighCardinalityColumns(&'a [String]),
+    PersonalDataColumns(&'a [String]),
+    TypeSuggestions(&'a [String]),
+    /// `(categorical column, percent of variance explained, numeric column)`
+    VarianceExplained(&'a str, f64, &'a str),
+    /// `(count, column of the largest, its period, its z-score)`
+    AnomalousPeriods(usize, &'a str, &'a str, f64),
+    FunctionalDependencies(&'a [String]),
+    ImplausibleColumns(&'a [String]),
+    AiAnalysisFailed(&'a str),
+    NoAiInsights,
+    ReviewDataset,
+    NoAiPatterns,
+}
+
+impl Message<'_> {
+    /// Render the message in the given locale
+    pub fn localize(&self, locale: Locale) -> String {
+        use Locale::*;
+        use Message::*;
+
+        match (self, locale) {
+            (InvalidRequest(e), En) => format!("Invalid request: {}", e),
+            (InvalidRequest(e), Fr) => format!("Requête invalide : {}", e),
+            (InvalidRequest(e), Pt) => format!("Requisição inválida: {}", e),
+
+            (NoFileUploaded, En) => "No file uploaded".to_string(),
+            (NoFileUploaded, Fr) => "Aucun fichier envoyé".to_string(),
+            (NoFileUploaded, Pt) => "Nenhum arquivo enviado".to_string(),
+
+            (FileMustBeCsv, En) => "File must be a CSV".to_string(),
+            (FileMustBeCsv, Fr) => "Le fichier doit être au format CSV".to_string(),
+            (FileMustBeCsv, Pt) => "O arquivo deve estar no formato CSV".to_string(),
+
+            (UploadFailed(e), En) => format!("Failed to upload file: {}", e),
+            (UploadFailed(e), Fr) => format!("Échec de l'envoi du fichier : {}", e),
+            (UploadFailed(e), Pt) => format!("Falha ao enviar o arquivo: {}", e),
+
+            (JobCreationFailed(e), En) => format!("Failed to create job: {}", e),
+            (JobCreationFailed(e), Fr) => format!("Échec de la création de la tâche : {}", e),
+            (JobCreationFailed(e), Pt) => format!("Falha ao criar a tarefa: {}", e),
+
+            (JobQueueFailed(e), En) => format!("Failed to queue job: {}", e),
+            (JobQueueFailed(e), Fr) => format!("Impossible de mettre la tâche en file d'attente : {}", e),
+            (JobQueueFailed(e), Pt) => format!("Falha ao colocar a tarefa na fila: {}", e),
+
+            (JobQueueUnavailable, En) => "Job queue unavailable".to_string(),
+            (JobQueueUnavailable, Fr) => "File d'attente des tâches indisponible".to_string(),
+            (JobQueueUnavailable, Pt) => "Fila de tarefas indisponível".to_string(),
+
+            (JobQueueFull(secs), En) => format!("The job queue is full; try again in {} seconds", secs),
+            (JobQueueFull(secs), Fr) => format!("La file d'attente des tâches est pleine ; réessayez dans {} secondes", secs),
+            (JobQueueFull(secs), Pt) => format!("A fila de tarefas está cheia; tente novamente em {} segundos", secs),
+
+            (JobQueued(status), En) => format!("File uploaded and job queued for processing. Status: {}", status),
+            (JobQueued(status), Fr) => format!("Fichier envoyé et tâche mise en file d'attente. Statut : {}", status),
+            (JobQueued(status), Pt) => format!("Arquivo enviado e tarefa colocada na fila. Status: {}", status),
+
+            (JobNotFound(id), En) => format!("Job with ID {} not found", id),
+            (JobNotFound(id), Fr) => format!("Tâche avec l'ID {} introuvable", id),
+            (JobNotFound(id), Pt) => format!("Tarefa com ID {} não encontrada", id),
+
+            (BatchNotFound(id), En) => format!("Batch with ID {} not found", id),
+            (BatchNotFound(id), Fr) => format!("Lot avec l'ID {} introuvable", id),
+            (BatchNotFound(id), Pt) => format!("Lote com ID {} não encontrado", id),
+
+            (TooManyFiles(max), En) => format!("A batch may hold at most {} files", max),
+            (TooManyFiles(max), Fr) => format!("Un lot peut contenir au plus {} fichiers", max),
+            (TooManyFiles(max), Pt) => format!("Um lote pode conter no máximo {} arquivos", max),
+
+            (FileTooLarge(max), En) => format!("The file is larger than {} bytes", max),
+            (FileTooLarge(max), Fr) => format!("Le fichier dépasse {} octets", max),
+            (FileTooLarge(max), Pt) => format!("O arquivo excede {} bytes", max),
+
+            (FetchFailed(e), En) => format!("Failed to fetch the file: {}", e),
+            (FetchFailed(e), Fr) => format!("Échec de la récupération du fichier : {}", e),
+            (FetchFailed(e), Pt) => format!("Falha ao obter o arquivo: {}", e),
+
+            (BatchQueued(count), En) => format!("{} files queued for processing", count),
+            (BatchQueued(count), Fr) => format!("{} fichiers en file d'attente de traitement", count),
+            (BatchQueued(count), Pt) => format!("{} arquivos na fila para processamento", count),
+
+            (ConversationNotFound(id), En) => format!("Conversation with ID {} not found", id),
+            (ConversationNotFound(id), Fr) => format!("Conversation avec l'ID {} introuvable", id),
+            (ConversationNotFound(id), Pt) => format!("Conversa com ID {} não encontrada", id),
+
+            (DatabaseError(e), En) => format!("Database error: {}", e),
+            (DatabaseError(e), Fr) => format!("Erreur de base de données : {}", e),
+            (DatabaseError(e), Pt) => format!("Erro de banco de dados: {}", e),
+
+            (CacheError(e), En) => format!("Cache error: {}", e),
+            (CacheError(e), Fr) => format!("Erreur de cache : {}", e),
+            (CacheError(e), Pt) => format!("Erro de cache: {}", e),
+
+            (JobInProgress(status), En) => format!("Job is {}", status),
+            (JobInProgress(status), Fr) => format!("La tâche est à l'état : {}", status),
+            (JobInProgress(status), Pt) => format!("A tarefa está no estado: {}", status),
+
+            (JobCompleted, En) => "Job completed successfully".to_string(),
+            (JobCompleted, Fr) => "Tâche terminée avec succès".to_string(),
+            (JobCompleted, Pt) => "Tarefa concluída com sucesso".to_string(),
+
+            (CacheInvalidated, En) => "Cached insights cleared; they will be reloaded from the stored copy".to_string(),
+            (CacheInvalidated, Fr) => "Analyses en cache effacées ; elles seront rechargées depuis la copie enregistrée".to_string(),
+            (CacheInvalidated, Pt) => "Análises em cache removidas; serão recarregadas da cópia armazenada".to_string(),
+
+            (JobProcessingFailed(e), En) => format!("Failed to process job: {}", e),
+            (JobProcessingFailed(e), Fr) => format!("Échec du traitement de la tâche : {}", e),
+            (JobProcessingFailed(e), Pt) => format!("Falha ao processar a tarefa: {}", e),
+
+            (QueryProcessingFailed(e), En) => format!("Error processing query: {}", e),
+            (QueryProcessingFailed(e), Fr) => format!("Erreur lors du traitement de la requête : {}", e),
+            (QueryProcessingFailed(e), Pt) => format!("Erro ao processar a consulta: {}", e),
+
+            (QueryNotUnderstood(e), En) => format!("I couldn't understand your query: {}", e),
+            (QueryNotUnderstood(e), Fr) => format!("Je n'ai pas compris votre question : {}", e),
+            (QueryNotUnderstood(e), Pt) => format!("Não consegui entender sua pergunta: {}", e),
+
+            (QueryExecutionFailed(e), En) => format!("I couldn't execute your query: {}", e),
+            (QueryExecutionFailed(e), Fr) => format!("Je n'ai pas pu exécuter votre requête : {}", e),
+            (QueryExecutionFailed(e), Pt) => format!("Não consegui executar sua consulta: {}", e),
+
+            (ResultFormattingFailed(e), En) => format!("I couldn't format the results: {}", e),
+            (ResultFormattingFailed(e), Fr) => format!("Je n'ai pas pu mettre en forme les résultats : {}", e),
+            (ResultFormattingFailed(e), Pt) => format!("Não consegui formatar os resultados: {}", e),
+
+            (NoDataFound, En) => "No data found for your query.".to_string(),
+            (NoDataFound, Fr) => "Aucune donnée ne correspond à votre requête.".to_string(),
+            (NoDataFound, Pt) => "Nenhum dado encontrado para sua consulta.".to_string(),
+
+            (ResultsReady, En) => "Here are the results for your query.".to_string(),
+            (ResultsReady, Fr) => "Voici les résultats de votre requête.".to_string(),
+            (ResultsReady, Pt) => "Aqui estão os resultados da sua consulta.".to_string(),
+
+            (AppendFailed(e), En) => format!("Failed to append data: {}", e),
+            (AppendFailed(e), Fr) => format!("Échec de l'ajout des données : {}", e),
+            (AppendFailed(e), Pt) => format!("Falha ao anexar os dados: {}", e),
+
+            (RowsAppended(n), En) => format!("{} rows appended and insights updated", n),
+            (RowsAppended(n), Fr) => format!("{} lignes ajoutées et analyses mises à jour", n),
+            (RowsAppended(n), Pt) => format!("{} linhas anexadas e análises atualizadas", n),
+
+            (InvalidProfile(e), En) => format!("Invalid insight profile: {}", e),
+            (InvalidProfile(e), Fr) => format!("Profil d'analyse invalide : {}", e),
+            (InvalidProfile(e), Pt) => format!("Perfil de análise inválido: {}", e),
+
+            (ComparisonFailed(e), En) => format!("Failed to compare datasets: {}", e),
+            (ComparisonFailed(e), Fr) => format!("Échec de la comparaison des jeux de données : {}", e),
+            (ComparisonFailed(e), Pt) => format!("Falha ao comparar os conjuntos de dados: {}", e),
+
+            (SqlQueryFailed(e), En) => format!("SQL query failed: {}", e),
+            (SqlQueryFailed(e), Fr) => format!("Échec de la requête SQL : {}", e),
+            (SqlQueryFailed(e), Pt) => format!("Falha na consulta SQL: {}", e),
+
+            (StructuredQueryFailed(e), En) => format!("Structured query failed: {}", e),
+            (StructuredQueryFailed(e), Fr) => format!("Échec de la requête structurée : {}", e),
+            (StructuredQueryFailed(e), Pt) => format!("Falha na consulta estruturada: {}", e),
+
+            (InvalidPagination(e), En) => format!("Invalid pagination: {}", e),
+            (InvalidPagination(e), Fr) => format!("Pagination invalide : {}", e),
+            (InvalidPagination(e), Pt) => format!("Paginação inválida: {}", e),
+
+            (ResultNotFound(id), En) => format!("Query result with ID {} not found", id),
+            (ResultNotFound(id), Fr) => format!("Résultat de requête avec l'ID {} introuvable", id),
+            (ResultNotFound(id), Pt) => format!("Resultado de consulta com ID {} não encontrado", id),
+
+            (ExportFailed(e), En) => format!("Failed to export query result: {}", e),
+            (ExportFailed(e), Fr) => format!("Échec de l'export du résultat de la requête : {}", e),
+            (ExportFailed(e), Pt) => format!("Falha ao exportar o resultado da consulta: {}", e),
+
+            (QueryTooExpensive(reason), En) => format!(
+                "This query is too expensive to run ({}). Try narrowing it with a filter, a shorter date range or a limit.",
+                reason
+            ),
+            (QueryTooExpensive(reason), Fr) => format!(
+                "Cette requête est trop coûteuse ({}). Essayez de la restreindre avec un filtre, une période plus courte ou une limite.",
+                reason
+            ),
+            (QueryTooExpensive(reason), Pt) => format!(
+                "Esta consulta é cara demais ({}). Tente restringi-la com um filtro, um período menor ou um limite.",
+                reason
+            ),
+
+            (SavedQueryNotFound(id), En) => format!("Saved query with ID {} not found", id),
+            (SavedQueryNotFound(id), Fr) => format!("Requête enregistrée avec l'ID {} introuvable", id),
+            (SavedQueryNotFound(id), Pt) => format!("Consulta salva com ID {} não encontrada", id),
+
+            (SaveQueryFailed(e), En) => format!("Failed to save query: {}", e),
+            (SaveQueryFailed(e), Fr) => format!("Échec de l'enregistrement de la requête : {}", e),
+            (SaveQueryFailed(e), Pt) => format!("Falha ao salvar a consulta: {}", e),
+
+            (FeedbackFailed(e), En) => format!("Failed to record feedback: {}", e),
+            (FeedbackFailed(e), Fr) => format!("Échec de l'enregistrement de l'avis : {}", e),
+            (FeedbackFailed(e), Pt) => format!("Falha ao registrar a avaliação: {}", e),
+            (SuggestionsFailed(e), En) => format!("Failed to suggest questions: {}", e),
+            (SuggestionsFailed(e), Fr) => format!("Échec de la suggestion de questions : {}", e),
+            (SuggestionsFailed(e), Pt) => format!("Falha ao sugerir perguntas: {}", e),
+
+            (UsageReportFailed(e), En) => format!("Failed to report AI usage: {}", e),
+            (UsageReportFailed(e), Fr) => format!("Échec du rapport d'utilisation de l'IA : {}", e),
+            (UsageReportFailed(e), Pt) => format!("Falha ao relatar o uso de IA: {}", e),
+
+            (DatasetFileMissing(id), En) => format!("The uploaded file of job {} is no longer stored", id),
+            (DatasetFileMissing(id), Fr) => format!("Le fichier envoyé pour la tâche {} n'est plus stocké", id),
+            (DatasetFileMissing(id), Pt) => format!("O arquivo enviado da tarefa {} não está mais armazenado", id),
+
+            (DatasetDownloadFailed(e), En) => format!("Failed to download dataset: {}", e),
+            (DatasetDownloadFailed(e), Fr) => format!("Échec du téléchargement du jeu de données : {}", e),
+            (DatasetDownloadFailed(e), Pt) => format!("Falha ao baixar o conjunto de dados: {}", e),
+
+            (SchemaFailed(e), En) => format!("Failed to read the dataset schema: {}", e),
+            (SchemaFailed(e), Fr) => format!("Échec de la lecture du schéma du jeu de données : {}", e),
+            (SchemaFailed(e), Pt) => format!("Falha ao ler o esquema do conjunto de dados: {}", e),
+
+            (SchemaUpdateFailed(e), En) => format!("Failed to apply schema overrides: {}", e),
+            (SchemaUpdateFailed(e), Fr) => format!("Échec de l'application des types de colonnes : {}", e),
+            (SchemaUpdateFailed(e), Pt) => format!("Falha ao aplicar os tipos de coluna: {}", e),
+
+            (SchemaQueued, En) => "Schema overrides saved and job queued for reprocessing".to_string(),
+            (SchemaQueued, Fr) => "Types de colonnes enregistrés et tâche remise en file d'attente".to_string(),
+            (SchemaQueued, Pt) => "Tipos de coluna salvos e tarefa colocada na fila para reprocessamento".to_string(),
+
+            (AdminReportFailed(e), En) => format!("Admin request failed: {}", e),
+            (AdminReportFailed(e), Fr) => format!("Échec de la requête d'administration : {}", e),
+            (AdminReportFailed(e), Pt) => format!("Falha na requisição de administração: {}", e),
+
+            (JobMarkedFailed, En) => "Job marked as failed".to_string(),
+            (JobMarkedFailed, Fr) => "Tâche marquée en échec".to_string(),
+            (JobMarkedFailed, Pt) => "Tarefa marcada como falha".to_string(),
+
+            (JobCancelled, En) => "Job cancelled".to_string(),
+            (JobCancelled, Fr) => "Tâche annulée".to_string(),
+            (JobCancelled, Pt) => "Tarefa cancelada".to_string(),
+
+            (ListFilesFailed(e), En) => format!("Failed to list files: {}", e),
+            (ListFilesFailed(e), Fr) => format!("Impossible de lister les fichiers : {}", e),
+            (ListFilesFailed(e), Pt) => format!("Falha ao listar os arquivos: {}", e),
+
+            (Unauthorized(e), En) => format!("Authentication failed: {}", e),
+            (Unauthorized(e), Fr) => format!("Échec de l'authentification : {}", e),
+            (Unauthorized(e), Pt) => format!("Falha na autenticação: {}", e),
+
+            (AuthenticationUnavailable(e), En) => format!("Authentication is temporarily unavailable: {}", e),
+            (AuthenticationUnavailable(e), Fr) => format!("L'authentification est temporairement indisponible : {}", e),
+            (AuthenticationUnavailable(e), Pt) => format!("A autenticação está temporariamente indisponível: {}", e),
+
+            (QuotaExceeded(e), En) => format!("Quota exceeded: {}", e),
+            (QuotaExceeded(e), Fr) => format!("Quota dépassé : {}", e),
+            (QuotaExceeded(e), Pt) => format!("Cota excedida: {}", e),
+
+            (AiTimeout(e), En) => format!("The AI provider did not answer in time: {}", e),
+            (AiTimeout(e), Fr) => format!("Le fournisseur d'IA n'a pas répondu à temps : {}", e),
+            (AiTimeout(e), Pt) => format!("O provedor de IA não respondeu a tempo: {}", e),
+
+            (AiUnavailable(e), En) => format!("The AI provider is temporarily unavailable: {}", e),
+            (AiUnavailable(e), Fr) => format!("Le fournisseur d'IA est temporairement indisponible : {}", e),
+            (AiUnavailable(e), Pt) => format!("O provedor de IA está temporariamente indisponível: {}", e),
+
+            (AggregationOf(aggregation, column), _) => {
+                let name = match (*aggregation, locale) {
+                    ("mean", En) => "average",
+                    ("mean", Fr) => "moyenne",
+                    ("mean", Pt) => "média",
+                    ("sum", En) => "total",
+                    ("sum", Fr) => "total",
+                    ("sum", Pt) => "total",
+                    ("count", En) => "count",
+                    ("count", Fr) => "nombre de valeurs",
+                    ("count", Pt) => "contagem",
+                    ("median", En) => "median",
+                    ("median", Fr) => "médiane",
+                    ("median", Pt) => "mediana",
+                    ("std", En) => "standard deviation",
+                    ("std", Fr) => "écart type",
+                    ("std", Pt) => "desvio padrão",
+                    ("min", En) => "minimum",
+                    ("min", Fr) => "minimum",
+                    ("min", Pt) => "mínimo",
+                    ("max", En) => "maximum",
+                    ("max", Fr) => "maximum",
+                    ("max", Pt) => "máximo",
+                    ("distinct_count", En) => "number of distinct values",
+                    ("distinct_count", Fr) => "nombre de valeurs distinctes",
+                    ("distinct_count", Pt) => "número de valores distintos",
+                    (percentile, _) => {
+                        let rank = percentile.trim_start_matches('p');
+                        return match locale {
+                            En => format!("{}th percentile of {}", rank, column),
+                            Fr => format!("{}e centile de {}", rank, column),
+                            Pt => format!("percentil {} de {}", rank, column),
+                        };
+                    }
+                };
+                match locale {
+                    En => format!("{} of {}", name, column),
+                    Fr | Pt => format!("{} de {}", name, column),
+                }
+            }
+
+            (AggregateValue(label, value), En) => format!("{} is {}", label, value),
+            (AggregateValue(label, value), Fr) => format!("{} : {}", label, value),
+            (AggregateValue(label, value), Pt) => format!("{}: {}", label, value),
+
+            (AcrossRows(1), En) => " across 1 row".to_string(),
+            (AcrossRows(n), En) => format!(" across {} rows", En.format_number(*n as f64)),
+            (AcrossRows(1), Fr) => " sur 1 ligne".to_string(),
+            (AcrossRows(n), Fr) => format!(" sur {} lignes", Fr.format_number(*n as f64)),
+            (AcrossRows(1), Pt) => " em 1 linha".to_string(),
+            (AcrossRows(n), Pt) => format!(" em {} linhas", Pt.format_number(*n as f64)),
+
+            (GroupExtremes(label, top, top_value, bottom, bottom_value), En) => format!(
+                "{} is highest for {} ({}) and lowest for {} ({})",
+                label, top, top_value, bottom, bottom_value
+            ),
+            (GroupExtremes(label, top, top_value, bottom, bottom_value), Fr) => format!(
+                "{} : valeur la plus élevée pour {} ({}), la plus basse pour {} ({})",
+                label, top, top_value, bottom, bottom_value
+            ),
+            (GroupExtremes(label, top, top_value, bottom, bottom_value), Pt) => format!(
+                "{}: maior valor para {} ({}), menor para {} ({})",
+                label, top, top_value, bottom, bottom_value
+            ),
+
+            (GroupCount(1, column), En) => format!("1 group of {}", column),
+            (GroupCount(n, column), En) => format!("{} groups of {}", En.format_number(*n as f64), column),
+            (GroupCount(1, column), Fr) => format!("1 groupe de {}", column),
+            (GroupCount(n, column), Fr) => format!("{} groupes de {}", Fr.format_number(*n as f64), column),
+            (GroupCount(1, column), Pt) => format!("1 grupo de {}", column),
+            (GroupCount(n, column), Pt) => format!("{} grupos de {}", Pt.format_number(*n as f64), column),
+
+            (MatchingRows(1, conditions), En) => format!("1 row matches {}", conditions),
+            (MatchingRows(n, conditions), En) => format!("{} rows match {}", En.format_number(*n as f64), conditions),
+            (MatchingRows(1, conditions), Fr) => format!("1 ligne correspond à {}", conditions),
+            (MatchingRows(n, conditions), Fr) => format!("{} lignes correspondent à {}", Fr.format_number(*n as f64), conditions),
+            (MatchingRows(1, conditions), Pt) => format!("1 linha corresponde a {}", conditions),
+            (MatchingRows(n, conditions), Pt) => format!("{} linhas correspondem a {}", Pt.format_number(*n as f64), conditions),
+
+            (And, En) => " and ".to_string(),
+            (And, Fr) => " et ".to_string(),
+            (And, Pt) => " e ".to_string(),
+
+            (SortedRows(n, column, first), En) => format!(
+                "{} {} ordered by {}, starting at {}",
+                En.format_number(*n as f64), if *n == 1 { "row" } else { "rows" }, column, first
+            ),
+            (SortedRows(n, column, first), Fr) => format!(
+                "{} {} par {}, en commençant par {}",
+                Fr.format_number(*n as f64), if *n == 1 { "ligne triée" } else { "lignes triées" }, column, first
+            ),
+            (SortedRows(n, column, first), Pt) => format!(
+                "{} {} por {}, começando por {}",
+                Pt.format_number(*n as f64), if *n == 1 { "linha ordenada" } else { "linhas ordenadas" }, column, first
+            ),
+
+            (FirstRows(n, total), En) => format!(
+                "The first {} of {} rows", En.format_number(*n as f64), En.format_number(*total as f64)
+            ),
+            (FirstRows(n, total), Fr) => format!(
+                "Les {} premières lignes sur {}", Fr.format_number(*n as f64), Fr.format_number(*total as f64)
+            ),
+            (FirstRows(n, total), Pt) => format!(
+                "As {} primeiras linhas de {}", Pt.format_number(*n as f64), Pt.format_number(*total as f64)
+            ),
+
+            (PivotTable(index, pivot, rows, columns), En) => format!(
+                "Pivot of {} by {}: {} rows and {} columns", index, pivot, En.format_number(*rows as f64), columns
+            ),
+            (PivotTable(index, pivot, rows, columns), Fr) => format!(
+                "Tableau croisé de {} par {} : {} lignes et {} colonnes", index, pivot, Fr.format_number(*rows as f64), columns
+            ),
+            (PivotTable(index, pivot, rows, columns), Pt) => format!(
+                "Tabela dinâmica de {} por {}: {} linhas e {} colunas", index, pivot, Pt.format_number(*rows as f64), columns
+            ),
+
+            (ResultRows(1), En) => "The query returned 1 row".to_string(),
+            (ResultRows(n), En) => format!("The query returned {} rows", En.format_number(*n as f64)),
+            (ResultRows(1), Fr) => "La requête a renvoyé 1 ligne".to_string(),
+            (ResultRows(n), Fr) => format!("La requête a renvoyé {} lignes", Fr.format_number(*n as f64)),
+            (ResultRows(1), Pt) => "A consulta retornou 1 linha".to_string(),
+            (ResultRows(n), Pt) => format!("A consulta retornou {} linhas", Pt.format_number(*n as f64)),
+
+            (DatasetShape(rows, columns, numeric, categorical, date), En) => format!(
+                "Dataset has {} rows and {} columns ({} numeric, {} categorical, {} date).",
+                En.format_number(*rows as f64), columns, numeric, categorical, date
+            ),
+            (DatasetShape(rows, columns, numeric, categorical, date), Fr) => format!(
+                "Le jeu de données compte {} lignes et {} colonnes ({} numériques, {} catégorielles, {} de date).",
+                Fr.format_number(*rows as f64), columns, numeric, categorical, date
+            ),
+            (DatasetShape(rows, columns, numeric, categorical, date), Pt) => format!(
+                "O conjunto de dados tem {} linhas e {} colunas ({} numéricas, {} categóricas, {} de data).",
+                Pt.format_number(*rows as f64), columns, numeric, categorical, date
+            ),
+
+            (StatisticsSampled(sample, total), En) => format!(
+                "Statistics were computed on a random sample of {} of {} rows.",
+                En.format_number(*sample as f64), En.format_number(*total as f64)
+            ),
+            (StatisticsSampled(sample, total), Fr) => format!(
+                "Les statistiques ont été calculées sur un échantillon aléatoire de {} lignes sur {}.",
+                Fr.format_number(*sample as f64), Fr.format_number(*total as f64)
+            ),
+            (StatisticsSampled(sample, total), Pt) => format!(
+                "As estatísticas foram calculadas sobre uma amostra aleatória de {} de {} linhas.",
+                Pt.format_number(*sample as f64), Pt.format_number(*total as f64)
+            ),
+
+            (ProcessedInLowMemory, En) => "The file was processed in low-memory mode: null counts, min/max, mean, standard deviation and correlations cover every row.".to_string(),
+            (ProcessedInLowMemory, Fr) => "Le fichier a été traité en mode mémoire réduite : valeurs manquantes, min/max, moyenne, écart type et corrélations portent sur toutes les lignes.".to_string(),
+            (ProcessedInLowMemory, Pt) => "O arquivo foi processado no modo de pouca memória: valores nulos, mín./máx., média, desvio padrão e correlações cobrem todas as linhas.".to_string(),
+
+            (IdentifierColumns(columns), En) => format!("Likely identifier columns: {}.", columns.join(", ")),
+            (IdentifierColumns(columns), Fr) => format!("Colonnes probablement identifiantes : {}.", columns.join(", ")),
+            (IdentifierColumns(columns), Pt) => format!("Colunas provavelmente identificadoras: {}.", columns.join(", ")),
+
+            (ConstantColumns(columns), En) => format!("Constant columns: {}.", columns.join(", ")),
+            (ConstantColumns(columns), Fr) => format!("Colonnes constantes : {}.", columns.join(", ")),
+            (ConstantColumns(columns), Pt) => format!("Colunas constantes: {}.", columns.join(", ")),
+
+            (HighCardinalityColumns(columns), En) => format!("High-cardinality categorical columns: {}.", columns.join(", ")),
+            (HighCardinalityColumns(columns), Fr) => format!("Colonnes catégorielles à forte cardinalité : {}.", columns.join(", ")),
+            (HighCardinalityColumns(columns), Pt) => format!("Colunas categóricas de alta cardinalidade: {}.", columns.join(", ")),
+
+            (PersonalDataColumns(columns), En) => format!("Columns that may contain personal data: {}.", columns.join(", ")),
+            (PersonalDataColumns(columns), Fr) => format!("Colonnes pouvant contenir des données personnelles : {}.", columns.join(", ")),
+            (PersonalDataColumns(columns), Pt) => format!("Colunas que podem conter dados pessoais: {}.", columns.join(", ")),
+
+            (TypeSuggestions(columns), En) => format!("Text columns that look like another type: {}.", columns.join(", ")),
+            (TypeSuggestions(columns), Fr) => format!("Colonnes de texte qui semblent d'un autre type : {}.", columns.join(", ")),
+            (TypeSuggestions(columns), Pt) => format!("Colunas de texto que parecem ser de outro tipo: {}.", columns.join(", ")),
+
+            (VarianceExplained(categorical, percent, numeric), En) => format!(
+                "'{}' explains {}% of the variance in '{}'.", categorical, En.format_number(percent.round()), numeric
+            ),
+            (VarianceExplained(categorical, percent, numeric), Fr) => format!(
+                "« {} » explique {} % de la variance de « {} ».", categorical, Fr.format_number(percent.round()), numeric
+            ),
+            (VarianceExplained(categorical, percent, numeric), Pt) => format!(
+                "'{}' explica {}% da variância de '{}'.", categorical, Pt.format_number(percent.round()), numeric
+            ),
+
+            (AnomalousPeriods(count, column, period, z), En) => format!(
+                "Found {} anomalous period(s); the largest is '{}' on {} (z = {}).",
+                count, column, period, En.format_number((z * 10.0).round() / 10.0)
+            ),
+            (AnomalousPeriods(count, column, period, z), Fr) => format!(
+                "{} période(s) anormale(s) ; la plus marquée concerne « {} » le {} (z = {}).",
+                count, column, period, Fr.format_number((z * 10.0).round() / 10.0)
+            ),
+            (AnomalousPeriods(count, column, period, z), Pt) => format!(
+                "{} período(s) anômalo(s); o maior é '{}' em {} (z = {}).",
+                count, column, period, Pt.format_number((z * 10.0).round() / 10.0)
+            ),
+
+            (FunctionalDependencies(dependencies), En) => format!("Functional dependencies: {}.", dependencies.join(", ")),
+            (FunctionalDependencies(dependencies), Fr) => format!("Dépendances fonctionnelles : {}.", dependencies.join(", ")),
+            (FunctionalDependencies(dependencies), Pt) => format!("Dependências funcionais: {}.", dependencies.join(", ")),
+
+            (ImplausibleColumns(columns), En) => format!("Columns failing plausibility checks: {}.", columns.join(", ")),
+            (ImplausibleColumns(columns), Fr) => format!("Colonnes échouant aux contrôles de plausibilité : {}.", columns.join(", ")),
+            (ImplausibleColumns(columns), Pt) => format!("Colunas reprovadas nas verificações de plausibilidade: {}.", columns.join(", ")),
+
+            (AiAnalysisFailed(e), En) => format!("AI analysis could not be generated at this time. Last error: {}", e),
+            (AiAnalysisFailed(e), Fr) => format!("L'analyse par IA n'a pas pu être générée pour le moment. Dernière erreur : {}", e),
+            (AiAnalysisFailed(e), Pt) => format!("Não foi possível gerar a análise por IA no momento. Último erro: {}", e),
+
+            (NoAiInsights, En) => "No insights could be generated from the data.".to_string(),
+            (NoAiInsights, Fr) => "Aucune analyse n'a pu être tirée des données.".to_string(),
+            (NoAiInsights, Pt) => "Nenhuma análise pôde ser gerada a partir dos dados.".to_string(),
+
+            (ReviewDataset, En) => "Review your dataset for completeness and try again.".to_string(),
+            (ReviewDataset, Fr) => "Vérifiez que votre jeu de données est complet et réessayez.".to_string(),
+            (ReviewDataset, Pt) => "Verifique se o seu conjunto de dados está completo e tente novamente.".to_string(),
+
+            (NoAiPatterns, En) => "The AI was unable to extract meaningful patterns or recommendations from the current data.".to_string(),
+            (NoAiPatterns, Fr) => "L'IA n'a pas pu dégager de tendances ou de recommandations pertinentes des données actuelles.".to_string(),
+            (NoAiPatterns, Pt) => "A IA não conseguiu extrair padrões ou recomendações relevantes dos dados atuais.".to_string(),
+        }
+    }
+}
@@ -0,0 +1,13 @@
+//! The data pipeline as a library: the `g-data-pipeline` binary serves it, and the
+//! benchmarks under `benches/` drive its engines directly.
+
+pub mod cli;
+pub mod config;
+pub mod i18n;
+pub mod models;
+pub mod services;
+pub mod handlers;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "graphql")]
+pub mod graphql;
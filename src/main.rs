@@ -1,76 +1,149 @@
-mod config;
-mod models;
-mod services;
-mod handlers;
+use g_data_pipeline::{cli, config, handlers, services};
+#[cfg(feature = "grpc")]
+use g_data_pipeline::grpc;
+#[cfg(feature = "graphql")]
+use g_data_pipeline::graphql;
 
-use actix_web::{web, App, HttpServer, middleware::Logger, HttpResponse};
+use actix_web::{web, App, HttpServer, middleware::{from_fn, Logger}};
 use actix_cors::Cors;
+use std::io::IsTerminal;
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use clap::Parser;
 
-use config::Config;
+use cli::{Cli, Command};
+use config::{Config, JOB_QUEUE_CAPACITY};
+use services::job_queue::JobQueue;
 use services::DataProcessor;
-use services::memory_s3::MemoryS3Service;
-use services::memory_db::MemoryDatabaseService;
-use services::memory_redis::MemoryRedisService;
-use services::conversation::ConversationService;
+use services::processor::JobLocked;
+use services::storage::StorageService;
+use services::{DatabaseServiceTrait, RedisServiceTrait, S3ServiceTrait};
+use services::job_store::JobStore;
+use services::cache::CacheStore;
+use services::registry::ServiceRegistry;
+use services::conversation::{ConversationService, HistoryLimits, QueryLimits};
 use services::ai::AIService;
-use handlers::{upload_csv, get_insights, query_endpoint};
+use services::auth::AuthService;
+use handlers::{upload_csv, append_csv, upload_batch, get_batch, get_insights, invalidate_insights_cache, compare_insights, query_endpoint, get_conversation, conversation_metrics, submit_feedback, export_feedback, get_suggestions, export_conversation, download_result, get_capabilities, sql_query, structured_query, save_query, list_saved_queries, run_saved_query, get_usage, download_dataset, get_dataset_schema, update_dataset_schema, conversation_session, admin_queue, admin_tenants, admin_cache, admin_stuck_jobs, admin_fail_job, admin_cancel_job, admin_retry_job, admin_storage};
+use handlers::error::invalid_request;
+use handlers::request_id;
+use tracing::Instrument;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::prelude::*;
 use uuid::Uuid;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Initialize logging
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    // Initialize logging; `log` records are shown with the span they were written in
+    let filter = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|directives| directives.parse::<Targets>().ok())
+        .unwrap_or_else(|| Targets::new().with_default(LevelFilter::INFO));
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_ansi(std::io::stderr().is_terminal()))
+        .with(filter)
+        .init();
+    let cli = Cli::parse();
+
+    // Load configuration from the config file, environment variables and `--set` overrides
+    let sources = cli.config_sources();
+    let config = match Config::load(&sources) {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("❌ {:#}", e);
+            return Err(std::io::Error::other(format!("{:#}", e)));
+        }
+    };
+
+    let result = match cli.command() {
+        Command::Serve => return serve(config).await,
+        Command::Migrate => cli::migrate(&config).await,
+        Command::CheckConfig => cli::check_config(&config, &sources),
+        Command::Process { file, no_ai, compact } => cli::process(config, file, *no_ai, *compact)
+            .await
+            .map(|insights| println!("{}", insights)),
+    };
+    result.map_err(|e| {
+        log::error!("❌ {:#}", e);
+        std::io::Error::other(format!("{:#}", e))
+    })
+}
+
+/// Run the HTTP API with its background worker
+async fn serve(config: Config) -> std::io::Result<()> {
     log::info!("🚀 Starting Data Processing API");
+
+    // Load the TLS certificate first, so a bad one fails before any backend is touched
+    let tls_config = match config::tls::server_config(&config) {
+        Ok(tls_config) => tls_config,
+        Err(e) => {
+            log::error!("❌ Failed to load the TLS certificate: {:#}", e);
+            return Err(std::io::Error::other(format!("{:#}", e)));
+        }
+    };
     
-    // Load configuration from environment variables
-    let config = Config::from_env();
+    // Verify callers' tokens when an identity provider is configured
+    let auth_service = match AuthService::from_config(&config) {
+        Ok(service) => web::Data::new(service),
+        Err(e) => {
+            log::error!("❌ Failed to initialize authentication: {:#}", e);
+            return Err(std::io::Error::other(format!("{:#}", e)));
+        }
+    };
+    if !auth_service.enabled() {
+        log::warn!("🔓 AUTH_JWKS_URL is not set, requests are not authenticated");
+    }
+
+    // Connect the storage, job database and cache backends the config selects
+    let ServiceRegistry { storage: s3_service, jobs: db_service, cache: redis_service } =
+        match ServiceRegistry::builder(&config).build().await {
+            Ok(services) => services,
+            Err(e) => {
+                log::error!("❌ {:#}", e);
+                return Err(std::io::Error::other(format!("{:#}", e)));
+            }
+        };
     
-    // Initialize in-memory services
-    log::info!("💾 Using in-memory services for local development");
-    let s3_service = MemoryS3Service::new();
-    let db_service = MemoryDatabaseService::new();
-    let redis_service = MemoryRedisService::new();
+    // Initialize the AI service if its LLM provider is configured
+    let ai_service = match AIService::new(&config) {
+        Ok(Some(service)) => {
+            log::info!(
+                "🤖 AI service initialized with the {} provider (summaries: {}, translation: {})",
+                service.provider_name(), service.summary_model(), service.translation_model()
+            );
+            Some(service.with_cache(Arc::new(redis_service.clone())))
+        }
+        Ok(None) => {
+            log::warn!("⚠️ No API key found for the {} LLM provider, AI service will not be available", config.llm_provider);
+            None
+        }
+        Err(e) => {
+            log::error!("❌ Failed to initialize AI service: {}", e);
+            None
+        }
+    };
     
     // Initialize data processor
     let processor = DataProcessor::new(
         s3_service.clone(),
         db_service.clone(),
         redis_service.clone(),
-        config.s3_bucket.clone(),
+        &config,
+        ai_service.clone(),
     );
     
-    // Initialize AI service if API key is available
-    let ai_service = if let Some(api_key) = &config.open_ai_key {
-        if !api_key.is_empty() {
-            log::info!("🤖 AI service initialized with OpenAI API key");
-            match AIService::new(&config) {
-                Ok(service) => service,
-                Err(e) => {
-                    log::error!("❌ Failed to initialize AI service: {}", e);
-                    None
-                }
-            }
-        } else {
-            log::warn!("⚠️ OpenAI API key is empty, AI service will not be available");
-            None
-        }
-    } else {
-        log::warn!("⚠️ No OpenAI API key found, AI service will not be available");
-        None
-    };
-    
     // Initialize conversation service
     let conversation_service = Arc::new(ConversationService::new(
         ai_service,
         processor.clone(),
+        QueryLimits::from_config(&config),
+        HistoryLimits::from_config(&config),
     ));
     log::info!("💬 Conversation service initialized");
     
     // Create a channel for job processing
-    let (tx, mut rx) = mpsc::channel::<Uuid>(32);
-    let tx = Arc::new(tx);
+    let (tx, mut rx) = mpsc::channel::<Uuid>(JOB_QUEUE_CAPACITY);
+    let queue = Arc::new(JobQueue::new(tx, std::time::Duration::from_secs(config.job_queue_retry_after_secs)));
     
     // Start background worker
     let processor_clone = processor.clone();
@@ -90,12 +163,16 @@ async fn main() -> std::io::Result<()> {
             let start_time = std::time::Instant::now();
             log::info!("🚀 [Job-{}] Starting processing at {:?}", job_id, std::time::SystemTime::now());
             
-            match processor_clone.process_job(job_id).await {
+            let span = tracing::info_span!("job", %job_id, request_id = tracing::field::Empty);
+            match processor_clone.process_job(job_id).instrument(span).await {
                 Ok(_) => {
                     let duration = start_time.elapsed();
                     log::info!("✅ [Job-{}] Completed successfully in {:.2?}", job_id, duration);
                     log::info!("📈 [Job-{}] Processing stats: Duration={:.2?}", job_id, duration);
                 },
+                Err(e) if e.downcast_ref::<JobLocked>().is_some() => {
+                    log::info!("🔒 [Job-{}] Skipped, another worker is already processing it", job_id);
+                },
                 Err(e) => {
                     let duration = start_time.elapsed();
                     log::error!("❌ [Job-{}] Failed after {:.2?}: {}", job_id, duration, e);
@@ -106,52 +183,232 @@ async fn main() -> std::io::Result<()> {
         log::warn!("🛑 Background worker shutting down (total jobs processed: {})", job_count);
     });
     
+    // Serve the gRPC API next to the HTTP one when a port is configured for it
+    if let Some(port) = config.grpc_port {
+        #[cfg(feature = "grpc")]
+        {
+            let address = match format!("{}:{}", config.server_host, port).parse() {
+                Ok(address) => address,
+                Err(e) => {
+                    log::error!("❌ Invalid gRPC address {}:{}: {}", config.server_host, port, e);
+                    return Err(std::io::Error::other(e));
+                }
+            };
+            let service = grpc::PipelineService::new(
+                processor.clone(),
+                conversation_service.clone(),
+                auth_service.clone().into_inner(),
+                queue.clone(),
+                config.grpc_max_upload_bytes,
+            );
+            log::info!("📡 Starting gRPC server at {}", address);
+            tokio::spawn(async move {
+                if let Err(e) = grpc::serve(address, service).await {
+                    log::error!("❌ {:#}", e);
+                }
+            });
+        }
+        #[cfg(not(feature = "grpc"))]
+        log::warn!("⚠️ GRPC_PORT is set to {}, but this build lacks the grpc feature", port);
+    }
+
+    // Periodically mark jobs whose files went missing from storage, starting now so
+    // files lost while the service was down are caught on restart
+    if config.reconcile_interval_secs > 0 {
+        let reconciler = processor.clone();
+        let interval = std::time::Duration::from_secs(config.reconcile_interval_secs);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match reconciler.reconcile_missing_files().await {
+                    Ok(0) => log::debug!("🧹 Reconciliation found no jobs with missing files"),
+                    Ok(marked) => log::warn!("🧹 Reconciliation marked {} job(s) with missing files as failed", marked),
+                    Err(e) => log::error!("❌ Reconciliation failed: {:#}", e),
+                }
+            }
+        });
+    }
+    
     // Start HTTP server
-    let server_url = format!("http://127.0.0.1:{}", config.server_port);
-    log::info!("🌐 Starting server at {}", server_url);
+    let bind_address = format!("{}:{}", config.server_host, config.server_port);
+    let scheme = if tls_config.is_some() { "https" } else { "http" };
+    log::info!("🌐 Starting server at {}://{}", scheme, bind_address);
     
-    HttpServer::new(move || {
+    #[cfg(feature = "graphql")]
+    let graphql_schema = web::Data::new(graphql::schema(processor.clone(), conversation_service.clone()));
+
+    let server = HttpServer::new(move || {
         let cors = Cors::default()
                 .allowed_origin("http://localhost:3001")
-                .allowed_methods(vec!["GET", "POST"])
+                .allowed_methods(vec!["GET", "POST", "DELETE"])
                 .allowed_headers(vec![actix_web::http::header::AUTHORIZATION, actix_web::http::header::ACCEPT])
                 .allowed_header(actix_web::http::header::CONTENT_TYPE)
                 .max_age(3600);
 
-        App::new()
+        let app = App::new()
             .wrap(cors)
-            .wrap(Logger::default())
+            .wrap(from_fn(request_id::propagate))
+            // The default format with the request ID set by `request_id::propagate`
+            .wrap(Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{x-request-id}o"#))
             .app_data(web::Data::new(s3_service.clone()))
             .app_data(web::Data::new(db_service.clone()))
             .app_data(web::Data::new(redis_service.clone()))
             .app_data(web::Data::new(processor.clone()))
-            .app_data(web::Data::new(tx.clone()))
+            .app_data(web::Data::new(queue.clone()))
             .app_data(web::Data::new(conversation_service.clone()))
-            .service(
-                web::resource("/upload")
-                    .route(web::post().to(upload_csv::<MemoryS3Service, MemoryDatabaseService>))
-            )
-            .service(
-                web::resource("/insights/{job_id}")
-                    .route(web::get().to(get_insights::<MemoryS3Service, MemoryDatabaseService, MemoryRedisService>))
-            )
-            .service(
-                web::resource("/api/conversation/query")
-                    .route(web::post().to(query_endpoint::<MemoryS3Service, MemoryDatabaseService, MemoryRedisService>))
-            )
-            .service(
-                web::resource("/debug/files")
-                    .route(web::get().to(|s3: web::Data<MemoryS3Service>| async move {
-                        let files = s3.list_files();
-                        HttpResponse::Ok().json(files)
-                    }))
-            )
-    })
-    .bind(format!("127.0.0.1:{}", config.server_port))
-    .map_err(|e| {
-        log::error!("❌ Failed to bind to port {}: {}", config.server_port, e);
-        e
-    })?
-    .run()
-    .await
-}
\ No newline at end of file
+            .app_data(auth_service.clone())
+            .app_data(web::JsonConfig::default().error_handler(invalid_request))
+            .app_data(web::QueryConfig::default().error_handler(invalid_request))
+            .app_data(web::PathConfig::default().error_handler(invalid_request))
+            .configure(routes::<StorageService, JobStore, CacheStore>);
+        #[cfg(feature = "graphql")]
+        let app = app.app_data(graphql_schema.clone());
+        app
+    });
+    let server = match tls_config {
+        Some(tls_config) => server.bind_rustls_021(&bind_address, tls_config),
+        None => server.bind(&bind_address),
+    };
+    server
+        .map_err(|e| {
+            log::error!("❌ Failed to bind to {}: {}", bind_address, e);
+            e
+        })?
+        .run()
+        .await
+}
+
+/// Every route, for whichever storage, job database and cache the registry built
+fn routes<S, D, R>(cfg: &mut web::ServiceConfig)
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    cfg
+        .service(
+            web::resource("/upload")
+                .route(web::post().to(upload_csv::<S, D, R>))
+        )
+        .service(
+            web::resource("/upload/batch")
+                .route(web::post().to(upload_batch::<S, D, R>))
+        )
+        .service(
+            web::resource("/batches/{batch_id}")
+                .route(web::get().to(get_batch::<S, D, R>))
+        )
+        .service(
+            web::resource("/upload/{job_id}/append")
+                .route(web::post().to(append_csv::<S, D, R>))
+        )
+        .service(
+            web::resource("/insights/compare")
+                .route(web::get().to(compare_insights::<S, D, R>))
+        )
+        .service(
+            web::resource("/insights/{job_id}")
+                .route(web::get().to(get_insights::<S, D, R>))
+        )
+        .service(
+            web::resource("/insights/{job_id}/cache")
+                .route(web::delete().to(invalidate_insights_cache::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/conversation/query")
+                .route(web::post().to(query_endpoint::<S, D, R>))
+        )
+        .service(
+            web::resource("/ws/conversation")
+                .route(web::get().to(conversation_session::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/conversation/metrics")
+                .route(web::get().to(conversation_metrics::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/conversation/feedback")
+                .route(web::get().to(export_feedback::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/conversation/{conversation_id}/feedback")
+                .route(web::post().to(submit_feedback::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/conversation/{conversation_id}/export")
+                .route(web::get().to(export_conversation::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/conversation/suggestions/{job_id}")
+                .route(web::get().to(get_suggestions::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/conversation/result/{result_id}/download")
+                .route(web::get().to(download_result::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/conversation/{conversation_id}")
+                .route(web::get().to(get_conversation::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/sql")
+                .route(web::post().to(sql_query::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/query/structured")
+                .route(web::post().to(structured_query::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/queries")
+                .route(web::post().to(save_query::<S, D, R>))
+                .route(web::get().to(list_saved_queries::<S, D, R>))
+        )
+        .service(
+            web::resource("/api/queries/{query_id}/run")
+                .route(web::get().to(run_saved_query::<S, D, R>))
+        )
+        .service(
+            web::resource("/usage")
+                .route(web::get().to(get_usage::<S, D, R>))
+        )
+        .service(
+            web::resource("/datasets/{job_id}/download")
+                .route(web::get().to(download_dataset::<S, D, R>))
+        )
+        .service(
+            web::resource("/datasets/{job_id}/schema")
+                .route(web::get().to(get_dataset_schema::<S, D, R>))
+                .route(web::post().to(update_dataset_schema::<S, D, R>))
+        )
+        .service(
+            web::scope("/admin")
+                .route("/queue", web::get().to(admin_queue::<S, D, R>))
+                .route("/tenants", web::get().to(admin_tenants::<S, D, R>))
+                .route("/cache", web::get().to(admin_cache::<S, D, R>))
+                .route("/storage", web::get().to(admin_storage::<S, D, R>))
+                .route("/jobs/stuck", web::get().to(admin_stuck_jobs::<S, D, R>))
+                .route("/jobs/{job_id}/fail", web::post().to(admin_fail_job::<S, D, R>))
+                .route("/jobs/{job_id}/cancel", web::post().to(admin_cancel_job::<S, D, R>))
+                .route("/jobs/{job_id}/retry", web::post().to(admin_retry_job::<S, D, R>))
+        )
+        .service(
+            web::resource("/capabilities")
+                .route(web::get().to(get_capabilities::<S, D, R>))
+        );
+
+    // One graph over jobs, insights, schemas and structured queries
+    #[cfg(feature = "graphql")]
+    cfg.service(
+        web::resource("/graphql")
+            .route(web::post().to(graphql::graphql_endpoint::<S, D, R>))
+            .route(web::get().to(graphql::graphiql))
+    );
+
+    // Storage introspection, left out of release builds unless asked for
+    #[cfg(any(debug_assertions, feature = "debug-endpoints"))]
+    cfg.service(
+        web::resource("/debug/files")
+            .route(web::get().to(handlers::list_files::<S>))
+    );
+}
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::models::job::JobFilter;
+
+#[cfg(any(debug_assertions, feature = "debug-endpoints"))]
+use crate::models::storage::ObjectInfo;
+
+/// A job as the admin API lists it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobSummary {
+    pub job_id: Uuid,
+    pub tenant_id: String,
+    pub user_id: String,
+    pub status: String,
+    pub attempts: i32,
+    /// Whether the last processing ran in low-memory mode
+    pub low_memory: bool,
+    pub request_id: Option<String>,
+    pub error_message: Option<String>,
+    /// Seconds since the job last changed, from its last update or creation
+    pub idle_secs: Option<u64>,
+}
+
+/// The in-process job queue and the jobs the database records as waiting or running
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueStatus {
+    /// Job IDs sent to this instance's worker and not yet picked up
+    pub depth: usize,
+    pub capacity: usize,
+    /// Uploads and requeues turned away with `503` since startup because the queue was full
+    pub rejected: u64,
+    /// Jobs recorded as queued, including ones sent to other instances or lost in a restart
+    pub queued: Vec<JobSummary>,
+    pub in_flight: Vec<JobSummary>,
+}
+
+/// A tenant's jobs, storage and AI spend against its quotas; a limit of 0 means none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantUsage {
+    pub tenant_id: String,
+    /// Job count per status
+    pub jobs: BTreeMap<String, u64>,
+    pub jobs_today: u64,
+    pub max_jobs_per_day: u64,
+    pub storage_bytes: u64,
+    pub max_storage_bytes: u64,
+    pub ai_tokens_today: u64,
+    pub max_ai_tokens_per_day: u64,
+}
+
+/// Every tenant that has a job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantUsageReport {
+    pub tenants: Vec<TenantUsage>,
+}
+
+/// Query of `GET /admin/queue` and `GET /admin/tenants`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AdminJobsQuery {
+    /// Only report this tenant's jobs
+    pub tenant_id: Option<String>,
+}
+
+impl AdminJobsQuery {
+    pub fn job_filter(&self) -> JobFilter {
+        JobFilter { tenant_id: self.tenant_id.clone(), user_id: None }
+    }
+}
+
+/// Query of `GET /admin/jobs/stuck`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StuckJobsQuery {
+    /// Seconds a queued or processing job must have gone unchanged; defaults to `JOB_LOCK_TTL_SECS`
+    pub older_than_secs: Option<u64>,
+    /// Only report this tenant's jobs
+    pub tenant_id: Option<String>,
+}
+
+impl StuckJobsQuery {
+    pub fn job_filter(&self) -> JobFilter {
+        JobFilter { tenant_id: self.tenant_id.clone(), user_id: None }
+    }
+}
+
+/// Queued or processing jobs that have not changed for `older_than_secs`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StuckJobs {
+    pub older_than_secs: u64,
+    pub jobs: Vec<JobSummary>,
+}
+
+/// Objects and bytes under one grouping of keys
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+/// Stored objects in total, per tenant and per area (`uploads`, `blobs`, `processed`, …)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageReport {
+    pub backend: String,
+    pub total: StorageUsage,
+    pub tenants: BTreeMap<String, StorageUsage>,
+    pub areas: BTreeMap<String, StorageUsage>,
+}
+
+/// Query of `GET /debug/files`
+#[cfg(any(debug_assertions, feature = "debug-endpoints"))]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FilesQuery {
+    /// Only keys starting with this, e.g. `uploads/` or `tenants/acme/`
+    pub prefix: Option<String>,
+}
+
+/// Stored objects under a prefix, sorted by key
+#[cfg(any(debug_assertions, feature = "debug-endpoints"))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileListing {
+    pub prefix: String,
+    pub total_bytes: u64,
+    pub objects: Vec<ObjectInfo>,
+}
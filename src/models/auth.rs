@@ -0,0 +1,141 @@
+use crate::models::job::{Job, JobFilter};
+use crate::models::storage::DEFAULT_TENANT;
+
+/// Who made a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// The token's `sub`, or `anonymous` when authentication is off
+    pub user_id: String,
+    /// The customer the caller belongs to, whose data they are confined to
+    pub tenant_id: String,
+    authenticated: bool,
+}
+
+impl Identity {
+    pub fn anonymous() -> Self {
+        Self {
+            user_id: "anonymous".to_string(),
+            tenant_id: DEFAULT_TENANT.to_string(),
+            authenticated: false,
+        }
+    }
+
+    pub fn authenticated(user_id: String, tenant_id: String) -> Self {
+        Self {
+            user_id,
+            tenant_id,
+            authenticated: true,
+        }
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// Whether this caller may see a job or conversation of `tenant` owned by
+    /// `owner`. Without authentication everything is reachable, as before it existed.
+    pub fn can_access(&self, tenant: Option<&str>, owner: Option<&str>) -> bool {
+        !self.authenticated || (tenant == Some(self.tenant_id.as_str()) && owner == Some(self.user_id.as_str()))
+    }
+
+    /// Whether this caller may see figures aggregated over a whole tenant
+    pub fn can_access_tenant(&self, tenant: &str) -> bool {
+        !self.authenticated || tenant == self.tenant_id
+    }
+
+    /// Whether this caller may see a job
+    pub fn can_access_job(&self, job: &Job) -> bool {
+        self.can_access(Some(&job.tenant_id), Some(&job.user_id))
+    }
+
+    /// The jobs this caller may see, as a listing filter
+    pub fn job_filter(&self) -> JobFilter {
+        if !self.authenticated {
+            return JobFilter::all();
+        }
+        JobFilter { tenant_id: Some(self.tenant_id.clone()), user_id: Some(self.user_id.clone()) }
+    }
+}
+
+/// A request made with the admin token, which may see and act on every tenant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Admin;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn job(tenant_id: &str, user_id: &str) -> Job {
+        Job {
+            id: Uuid::new_v4(),
+            user_id: user_id.to_string(),
+            tenant_id: tenant_id.to_string(),
+            request_id: None,
+            bucket: "datasets".to_string(),
+            file_key: format!("tenants/{}/uploads/data.csv", tenant_id),
+            content_hash: None,
+            batch_id: None,
+            schema_overrides: Default::default(),
+            status: "completed".to_string(),
+            error_message: None,
+            attempts: 1,
+            low_memory: false,
+            dataset_metadata: None,
+            started_at: None,
+            finished_at: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn alice() -> Identity {
+        Identity::authenticated("alice".to_string(), "acme".to_string())
+    }
+
+    #[test]
+    fn reaches_only_own_jobs_in_own_tenant() {
+        let alice = alice();
+        assert!(alice.can_access(Some("acme"), Some("alice")));
+        assert!(alice.can_access_job(&job("acme", "alice")));
+
+        // Another tenant's data, even under the same user ID
+        assert!(!alice.can_access(Some("globex"), Some("alice")));
+        assert!(!alice.can_access_job(&job("globex", "alice")));
+        // A colleague's data in the same tenant
+        assert!(!alice.can_access(Some("acme"), Some("bob")));
+        assert!(!alice.can_access_job(&job("acme", "bob")));
+        // Records missing their tenant or owner
+        assert!(!alice.can_access(None, Some("alice")));
+        assert!(!alice.can_access(Some("acme"), None));
+        assert!(!alice.can_access(None, None));
+    }
+
+    #[test]
+    fn reaches_only_own_tenant_aggregates() {
+        let alice = alice();
+        assert!(alice.can_access_tenant("acme"));
+        assert!(!alice.can_access_tenant("globex"));
+        assert!(!alice.can_access_tenant(DEFAULT_TENANT));
+    }
+
+    #[test]
+    fn anonymous_reaches_everything() {
+        let anonymous = Identity::anonymous();
+        assert!(!anonymous.is_authenticated());
+        assert!(anonymous.can_access(Some("globex"), Some("bob")));
+        assert!(anonymous.can_access(None, None));
+        assert!(anonymous.can_access_tenant("globex"));
+        assert!(anonymous.can_access_job(&job("globex", "bob")));
+        assert_eq!(anonymous.job_filter(), JobFilter::all());
+    }
+
+    #[test]
+    fn job_filter_matches_what_can_access_allows() {
+        let alice = alice();
+        let filter = alice.job_filter();
+        for job in [job("acme", "alice"), job("acme", "bob"), job("globex", "alice"), job("globex", "bob")] {
+            assert_eq!(filter.matches(&job), alice.can_access_job(&job), "{}/{}", job.tenant_id, job.user_id);
+        }
+    }
+}
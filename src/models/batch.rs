@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+use crate::models::error::ErrorCode;
+
+/// Files uploaded together in one request, each processed as its own job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Batch {
+    pub id: Uuid,
+    pub user_id: String,
+    /// Tenant the batch and its jobs belong to
+    pub tenant_id: String,
+    pub created_at: Option<SystemTime>,
+}
+
+/// Represents a new batch to be created
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewBatch {
+    pub user_id: String,
+    pub tenant_id: String,
+}
+
+/// JSON body of `POST /upload/batch`: CSV files to fetch instead of uploading them
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchManifest {
+    pub urls: Vec<String>,
+}
+
+/// A file of the batch that was stored and queued
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchFile {
+    /// The uploaded file's name, or the URL it was fetched from
+    pub source: String,
+    pub job_id: Uuid,
+    pub content_hash: Option<String>,
+}
+
+/// A file of the batch that got no job, and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedFile {
+    pub source: String,
+    pub code: ErrorCode,
+    pub error: String,
+}
+
+/// Response of `POST /upload/batch`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchUploadResponse {
+    pub batch_id: Uuid,
+    pub status: String,
+    pub jobs: Vec<BatchFile>,
+    pub rejected: Vec<RejectedFile>,
+    pub message: String,
+}
+
+/// A job of a batch, as `GET /batches/{batch_id}` lists it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchJob {
+    pub job_id: Uuid,
+    pub status: String,
+    pub error_message: Option<String>,
+}
+
+/// A batch's jobs and how far they got together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchStatus {
+    pub batch_id: Uuid,
+    /// `processing` while any job is queued or processing, then `completed` when
+    /// every job completed, `failed` when none did, and `partial` otherwise
+    pub status: String,
+    pub total: usize,
+    /// Job count per status
+    pub counts: BTreeMap<String, usize>,
+    pub jobs: Vec<BatchJob>,
+}
@@ -0,0 +1,64 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Where insights, aggregate state and AI replies are cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheBackendKind {
+    /// In process, lost on restart and not shared between instances
+    #[default]
+    Memory,
+    /// Redis at `REDIS_URL` (needs the `external-services` feature)
+    Redis,
+}
+
+impl FromStr for CacheBackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "memory" => Ok(CacheBackendKind::Memory),
+            "redis" => Ok(CacheBackendKind::Redis),
+            other => Err(anyhow!("Unknown cache backend '{}' (expected memory or redis)", other)),
+        }
+    }
+}
+
+impl fmt::Display for CacheBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CacheBackendKind::Memory => "memory",
+            CacheBackendKind::Redis => "redis",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Size and effectiveness of the cache. Redis counts hits, misses and evictions
+/// server-wide, including other clients of the same server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub backend: String,
+    pub entries: u64,
+    /// Bytes held: keys and values in memory, `used_memory` on Redis
+    pub bytes: u64,
+    /// Entry and byte caps of the in-memory cache; Redis reports neither
+    pub max_entries: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room
+    pub evictions: u64,
+    /// Share of reads answered from the cache; `None` before the first read
+    pub hit_ratio: Option<f64>,
+}
+
+impl CacheStats {
+    /// Share of `hits` among all reads
+    pub fn ratio(hits: u64, misses: u64) -> Option<f64> {
+        let reads = hits + misses;
+        (reads > 0).then(|| hits as f64 / reads as f64)
+    }
+}
@@ -1,8 +1,22 @@
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
+use std::str::FromStr;
 use uuid::Uuid;
 
+use crate::models::query::{ResultColumn, ResultRows};
+use crate::models::usage::{estimate_tokens, TokenUsage};
+
+/// Result rows returned per page unless the request asks for another size
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Upper bound on the requested page size
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Rows shown per data table in a conversation export unless the request asks for more
+pub const DEFAULT_EXPORT_ROWS: usize = 20;
+
 /// Represents a user query and its response in a conversation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversationTurn {
@@ -12,6 +26,110 @@ pub struct ConversationTurn {
     pub response: String,
     /// When this turn occurred
     pub timestamp: DateTime<Utc>,
+    /// Where the time and tokens for this turn went
+    pub metrics: Option<TurnMetrics>,
+    /// The structured query the question was translated into
+    #[serde(default)]
+    pub structured_query: Option<serde_json::Value>,
+    /// The user's rating of the answer
+    #[serde(default)]
+    pub feedback: Option<TurnFeedback>,
+    /// The start of the result and its chart, so a reopened conversation shows them again
+    #[serde(default)]
+    pub snapshot: Option<TurnSnapshot>,
+}
+
+/// What a turn's answer showed: a capped sample of the result and its chart config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnSnapshot {
+    pub columns: Vec<ResultColumn>,
+    /// The first rows of the result, as row objects
+    pub rows: serde_json::Value,
+    /// Rows in the full result
+    pub total_rows: usize,
+    pub visualization_data: Option<serde_json::Value>,
+}
+
+/// Thumbs up or down on an answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackRating {
+    Up,
+    Down,
+}
+
+/// A user's rating of one conversation turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnFeedback {
+    pub rating: FeedbackRating,
+    pub comment: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+/// Request to rate a conversation turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackRequest {
+    /// 0-based position of the turn in the conversation history
+    pub turn_index: usize,
+    pub rating: FeedbackRating,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// A rated turn with the question, answer and translation it rates, for export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackRecord {
+    pub conversation_id: String,
+    pub job_id: String,
+    pub turn_index: usize,
+    pub query: String,
+    pub response: String,
+    pub structured_query: Option<serde_json::Value>,
+    pub rating: FeedbackRating,
+    pub comment: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+}
+
+/// Example questions for a dataset, for clients to offer before the first query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestedQuestions {
+    pub job_id: String,
+    pub questions: Vec<String>,
+    /// "ai" when generated by the AI service, "rules" when built from the schema alone
+    pub source: String,
+}
+
+impl ConversationTurn {
+    /// Rough number of tokens the turn takes up in a translation prompt
+    pub fn estimated_tokens(&self) -> usize {
+        estimate_tokens(&self.query) + estimate_tokens(&self.response)
+    }
+}
+
+/// Latency breakdown and AI spend for a single conversation turn
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TurnMetrics {
+    /// Time spent translating the query into a structured query
+    pub translation_ms: u64,
+    /// Time spent loading the dataset and running the structured query
+    pub execution_ms: u64,
+    /// Time spent generating the natural language answer
+    pub narration_ms: u64,
+    /// End-to-end time for the turn
+    pub total_ms: u64,
+    /// Tokens used by translation and narration combined
+    pub token_usage: TokenUsage,
+}
+
+impl TurnMetrics {
+    /// Accumulate another turn's metrics into this one
+    pub fn accumulate(&mut self, other: &TurnMetrics) {
+        self.translation_ms += other.translation_ms;
+        self.execution_ms += other.execution_ms;
+        self.narration_ms += other.narration_ms;
+        self.total_ms += other.total_ms;
+        self.token_usage += other.token_usage;
+    }
 }
 
 /// Metadata about the dataset being queried
@@ -25,6 +143,12 @@ pub struct DatasetMetadata {
     pub data_types: HashMap<String, String>,
 }
 
+/// Dataset metadata stored as JSON in a jobs row; `None` for jobs processed before it was recorded
+pub fn metadata_from_json(json: Option<&str>) -> Result<Option<DatasetMetadata>> {
+    json.map(|json| serde_json::from_str(json).context("Invalid dataset metadata in the database"))
+        .transpose()
+}
+
 /// Represents the state of a conversation about a dataset
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversationContext {
@@ -32,8 +156,26 @@ pub struct ConversationContext {
     pub id: String,
     /// The job ID associated with the dataset
     pub job_id: String,
+    /// Owner of the dataset, used to attribute conversation cost
+    pub user_id: Option<String>,
+    /// Tenant of the dataset; only its users can reach the conversation
+    #[serde(default)]
+    pub tenant_id: Option<String>,
     /// History of the conversation
     pub history: Vec<ConversationTurn>,
+    /// Summary of the oldest `summarized_turns` turns, sent to the model in their place
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Number of turns, from the start of `history`, that `summary` covers
+    #[serde(default)]
+    pub summarized_turns: usize,
+    /// The last query that ran, in the shape `/api/query/structured` accepts, so a
+    /// follow-up question can refine it
+    #[serde(default)]
+    pub last_query: Option<serde_json::Value>,
+    /// Columns the last query returned
+    #[serde(default)]
+    pub last_result_columns: Vec<ResultColumn>,
     /// Metadata about the dataset
     pub dataset_metadata: DatasetMetadata,
     /// When the conversation was created
@@ -44,12 +186,18 @@ pub struct ConversationContext {
 
 impl ConversationContext {
     /// Create a new conversation context
-    pub fn new(job_id: String, dataset_metadata: DatasetMetadata) -> Self {
+    pub fn new(job_id: String, user_id: Option<String>, tenant_id: Option<String>, dataset_metadata: DatasetMetadata) -> Self {
         let now = Utc::now();
         Self {
             id: Uuid::new_v4().to_string(),
             job_id,
+            user_id,
+            tenant_id,
             history: Vec::new(),
+            summary: None,
+            summarized_turns: 0,
+            last_query: None,
+            last_result_columns: Vec::new(),
             dataset_metadata,
             created_at: now,
             updated_at: now,
@@ -57,15 +205,37 @@ impl ConversationContext {
     }
 
     /// Add a turn to the conversation
-    pub fn add_turn(&mut self, query: String, response: String) {
+    pub fn add_turn(
+        &mut self,
+        query: String,
+        response: String,
+        structured_query: Option<serde_json::Value>,
+        snapshot: Option<TurnSnapshot>,
+        metrics: Option<TurnMetrics>,
+    ) {
         let turn = ConversationTurn {
             query,
             response,
             timestamp: Utc::now(),
+            metrics,
+            structured_query,
+            feedback: None,
+            snapshot,
         };
         self.history.push(turn);
         self.updated_at = Utc::now();
     }
+
+    /// Turns not covered by the summary, sent to the model verbatim
+    pub fn recent_turns(&self) -> &[ConversationTurn] {
+        &self.history[self.summarized_turns.min(self.history.len())..]
+    }
+
+    /// Replace the summary with one that also covers the next `turns` recent turns
+    pub fn fold_into_summary(&mut self, summary: String, turns: usize) {
+        self.summary = Some(summary);
+        self.summarized_turns = (self.summarized_turns + turns).min(self.history.len());
+    }
 }
 
 /// Request to query a dataset using natural language
@@ -77,17 +247,192 @@ pub struct QueryRequest {
     pub query: String,
     /// Optional conversation ID for follow-up queries
     pub conversation_id: Option<String>,
+    /// 1-based page of the result to return
+    #[serde(default)]
+    pub page: Option<usize>,
+    /// Rows per page, `DEFAULT_PAGE_SIZE` when unset
+    #[serde(default)]
+    pub page_size: Option<usize>,
+    /// `next_cursor` of a previous response; continues that result without
+    /// translating the query again
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Include the optimized Polars plan in `query_plan`
+    #[serde(default)]
+    pub explain: bool,
 }
 
-/// Response to a natural language query
+impl QueryRequest {
+    /// Requested page and page size, validated against `MAX_PAGE_SIZE`
+    pub fn page_window(&self) -> Result<(usize, usize)> {
+        let page = self.page.unwrap_or(1);
+        let page_size = self.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+        if page == 0 {
+            return Err(anyhow!("page starts at 1"));
+        }
+        if page_size == 0 || page_size > MAX_PAGE_SIZE {
+            return Err(anyhow!("page_size must be between 1 and {}", MAX_PAGE_SIZE));
+        }
+        Ok((page, page_size))
+    }
+}
+
+/// Where a page sits in the full query result
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pagination {
+    pub page: usize,
+    pub page_size: usize,
+    /// Rows in the full result
+    pub total_rows: usize,
+    /// Rows in this page
+    pub returned_rows: usize,
+    /// Pass as `cursor` to fetch the following page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// A step of a query turn finished ahead of its `QueryResponse`, so interactive
+/// clients can show progress while the answer is still being written
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum QueryProgress {
+    /// The question was translated; the query is about to run
+    Translated { query_plan: QueryPlan },
+    /// The query ran. `draft` describes the result without AI and is replaced by
+    /// the narrated `response`; it is absent when no narration follows.
+    Executed {
+        data: ResultRows,
+        visualization_data: Option<serde_json::Value>,
+        pagination: Pagination,
+        result_id: String,
+        draft: Option<String>,
+    },
+}
+
+/// Response to a natural language query
+#[derive(Debug, Clone, Serialize)]
 pub struct QueryResponse {
     /// The conversation ID
     pub conversation_id: String,
     /// Natural language response to the query
     pub response: String,
-    /// Any data returned by the query (as JSON)
-    pub data: Option<serde_json::Value>,
+    /// This page of the result, written out as JSON objects from the frame
+    pub data: Option<ResultRows>,
     /// Optional JSON data for visualization (e.g., Chart.js config)
     pub visualization_data: Option<serde_json::Value>,
+    /// Position of `data` in the full result
+    pub pagination: Option<Pagination>,
+    /// Identifies the full result for `/api/conversation/result/{id}/download`
+    pub result_id: Option<String>,
+    /// What was executed, absent when the query could not be translated
+    pub query_plan: Option<QueryPlan>,
+    /// Whether the figures in an AI-written `response` were found in the result
+    pub grounding: Option<GroundingCheck>,
+}
+
+/// Figures stated in an AI narration that could not be matched to the query result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroundingCheck {
+    /// Every figure in the narration matched a value of the result
+    pub grounded: bool,
+    /// Figures as written in the narration, e.g. `12.5k` or `38%`
+    pub unverified_figures: Vec<String>,
+}
+
+/// The structured query a natural language query was translated into and ran as
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryPlan {
+    /// Intent, columns and operations after column resolution, in the shape
+    /// `/api/query/structured` accepts
+    pub structured_query: serde_json::Value,
+    /// Optimized Polars plan, present when the request asks to `explain`
+    pub polars_plan: Option<String>,
+}
+
+/// A conversation with its turn history and summed turn metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationDetail {
+    pub conversation: ConversationContext,
+    /// Sum of the metrics of every turn
+    pub totals: TurnMetrics,
+}
+
+/// Formats a conversation can be exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConversationExportFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+impl ConversationExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ConversationExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ConversationExportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConversationExportFormat::Markdown => "md",
+            ConversationExportFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for ConversationExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "markdown" | "md" => Ok(ConversationExportFormat::Markdown),
+            "json" => Ok(ConversationExportFormat::Json),
+            other => Err(anyhow!("Unknown conversation export format '{}' (expected markdown or json)", other)),
+        }
+    }
+}
+
+/// A conversation rendered for sharing: every question with its answer, the first
+/// rows of its result and its chart config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationExport {
+    pub conversation_id: String,
+    pub job_id: String,
+    pub created_at: DateTime<Utc>,
+    pub exported_at: DateTime<Utc>,
+    /// Summary of turns folded out of the translation history
+    pub summary: Option<String>,
+    pub turns: Vec<ExportedTurn>,
+}
+
+/// One question and answer of an exported conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedTurn {
+    pub query: String,
+    pub response: String,
+    pub timestamp: DateTime<Utc>,
+    pub structured_query: Option<serde_json::Value>,
+    /// Result columns, in order
+    pub columns: Vec<String>,
+    /// The first rows of the result, as row objects
+    pub data: Option<serde_json::Value>,
+    /// Rows in the full result
+    pub total_rows: Option<usize>,
+    pub visualization_data: Option<serde_json::Value>,
+    /// Why the result could not be reproduced, when it could not
+    pub error: Option<String>,
+}
+
+/// Conversation latency and AI spend aggregated for one tenant
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantConversationMetrics {
+    pub tenant: String,
+    pub conversations: usize,
+    pub turns: usize,
+    pub totals: TurnMetrics,
+    pub avg_translation_ms: f64,
+    pub avg_execution_ms: f64,
+    pub avg_narration_ms: f64,
+    pub avg_total_ms: f64,
 }
@@ -0,0 +1,41 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Where jobs are tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatabaseBackendKind {
+    /// In memory, lost on restart; for local development
+    #[default]
+    Memory,
+    /// PostgreSQL at `DATABASE_URL` (needs the `external-services` feature)
+    Postgres,
+    /// A local SQLite file, for single-node deployments (needs the `sqlite` feature)
+    Sqlite,
+}
+
+impl FromStr for DatabaseBackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "memory" => Ok(DatabaseBackendKind::Memory),
+            "postgres" | "postgresql" => Ok(DatabaseBackendKind::Postgres),
+            "sqlite" => Ok(DatabaseBackendKind::Sqlite),
+            other => Err(anyhow!("Unknown database backend '{}' (expected memory, postgres or sqlite)", other)),
+        }
+    }
+}
+
+impl fmt::Display for DatabaseBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DatabaseBackendKind::Memory => "memory",
+            DatabaseBackendKind::Postgres => "postgres",
+            DatabaseBackendKind::Sqlite => "sqlite",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// Stable, machine-readable reason for an error response. Clients branch on the
+/// code; the accompanying message is localized and may change wording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    InvalidRequest,
+    NoFileUploaded,
+    UnsupportedFileType,
+    InvalidPagination,
+    QueryTranslationFailed,
+    QueryExecutionFailed,
+    QueryTooExpensive,
+    Unauthorized,
+    JobNotFound,
+    BatchNotFound,
+    DatasetNotFound,
+    ConversationNotFound,
+    ResultNotFound,
+    SavedQueryNotFound,
+    JobInProgress,
+    FileTooLarge,
+    QuotaExceeded,
+    DatabaseError,
+    CacheError,
+    StorageError,
+    ProcessingFailed,
+    InternalError,
+    JobQueueUnavailable,
+    JobQueueFull,
+    UpstreamAiUnavailable,
+    AuthenticationUnavailable,
+    UpstreamAiTimeout,
+}
+
+impl ErrorCode {
+    /// HTTP status every response with this code is sent with
+    pub fn status(&self) -> u16 {
+        use ErrorCode::*;
+
+        match self {
+            InvalidRequest | NoFileUploaded | UnsupportedFileType | InvalidPagination | QueryExecutionFailed => 400,
+            Unauthorized => 401,
+            JobNotFound | BatchNotFound | DatasetNotFound | ConversationNotFound | ResultNotFound | SavedQueryNotFound => 404,
+            JobInProgress => 409,
+            FileTooLarge => 413,
+            QueryTranslationFailed | QueryTooExpensive => 422,
+            QuotaExceeded => 429,
+            DatabaseError | CacheError | StorageError | ProcessingFailed | InternalError => 500,
+            JobQueueUnavailable | JobQueueFull | UpstreamAiUnavailable | AuthenticationUnavailable => 503,
+            UpstreamAiTimeout => 504,
+        }
+    }
+}
@@ -1,11 +1,16 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "external-services")]
-use sqlx::FromRow;
 use uuid::Uuid;
+use std::fmt;
+use std::str::FromStr;
 use std::time::SystemTime;
 
+use crate::models::conversation::DatasetMetadata;
+use crate::models::schema::SchemaOverrides;
+use crate::models::storage::{is_blob_key, tenant_key, StorageKey};
+
 /// Represents the status of a data processing job
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum JobStatus {
     #[serde(rename = "queued")]
     Queued,
@@ -15,42 +20,307 @@ pub enum JobStatus {
     Completed,
     #[serde(rename = "failed")]
     Failed,
+    #[serde(rename = "cancelled")]
+    Cancelled,
 }
 
-impl ToString for JobStatus {
-    fn to_string(&self) -> String {
+impl JobStatus {
+    /// Statuses a job may move to this one from. Only queued jobs start processing,
+    /// Completed is only reachable from Processing and Cancelled is final, so a
+    /// cancelled job can't later complete and a completed one is never picked up by
+    /// a stray worker run.
+    pub fn allowed_from(&self) -> &'static [JobStatus] {
         match self {
-            JobStatus::Queued => "queued".to_string(),
-            JobStatus::Processing => "processing".to_string(),
-            JobStatus::Completed => "completed".to_string(),
-            JobStatus::Failed => "failed".to_string(),
+            // Failed jobs are queued again by an administrator's retry. Completed jobs
+            // are queued again to be reprocessed: after a schema change, an append
+            // whose rows can't be merged, or insights that were never stored
+            JobStatus::Queued => &[JobStatus::Failed, JobStatus::Completed],
+            JobStatus::Processing => &[JobStatus::Queued],
+            JobStatus::Completed => &[JobStatus::Processing],
+            // Completed jobs fail when reconciliation finds their file missing
+            JobStatus::Failed => &[JobStatus::Queued, JobStatus::Processing, JobStatus::Completed],
+            // Cancelled by an administrator before the job finished
+            JobStatus::Cancelled => &[JobStatus::Queued, JobStatus::Processing],
         }
     }
+
+    /// Whether a job in this status may move to `next`
+    pub fn can_transition_to(&self, next: JobStatus) -> bool {
+        next.allowed_from().contains(self)
+    }
 }
 
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Processing => "processing",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "queued" => Ok(JobStatus::Queued),
+            "processing" => Ok(JobStatus::Processing),
+            "completed" => Ok(JobStatus::Completed),
+            "failed" => Ok(JobStatus::Failed),
+            "cancelled" => Ok(JobStatus::Cancelled),
+            other => Err(anyhow::anyhow!("Unknown job status '{}'", other)),
+        }
+    }
+}
+
+/// A status change the job's current status doesn't allow, e.g. completing a
+/// job that was cancelled while it was processing
+#[derive(Debug)]
+pub struct InvalidTransition {
+    pub job_id: Uuid,
+    /// Status the job was in when the change was attempted
+    pub from: String,
+    pub to: JobStatus,
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Job {} cannot move from {} to {}", self.job_id, self.from, self.to)
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
 /// Represents a data processing job in the database
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[cfg_attr(feature = "external-services", derive(FromRow))]
 pub struct Job {
     pub id: Uuid,
     pub user_id: String,
+    /// Tenant the job belongs to; its files are stored under the tenant's prefix
+    pub tenant_id: String,
+    /// `X-Request-Id` of the request that created the job, to follow it into the worker's logs
+    pub request_id: Option<String>,
+    /// Bucket the uploaded file and its processed copy are stored in
+    pub bucket: String,
     pub file_key: String,
+    /// Hex SHA-256 of the uploaded file; `None` for jobs created before hashing
+    pub content_hash: Option<String>,
+    /// Batch upload the job's file arrived in
+    #[serde(default)]
+    pub batch_id: Option<Uuid>,
+    /// Types columns are read as instead of the inferred ones
+    #[serde(default)]
+    pub schema_overrides: SchemaOverrides,
     pub status: String,
+    /// Why the last attempt failed; cleared when a new attempt starts
+    pub error_message: Option<String>,
+    /// Times processing has started
+    pub attempts: i32,
+    /// Whether the last processing read the file in chunks, its parsed size being over the memory budget
+    #[serde(default)]
+    pub low_memory: bool,
+    /// Columns, types and row count recorded when the job was last processed or
+    /// appended to, so conversations don't parse the dataset to learn its shape
+    #[serde(default)]
+    pub dataset_metadata: Option<DatasetMetadata>,
+    /// When the last attempt started and finished
+    pub started_at: Option<SystemTime>,
+    pub finished_at: Option<SystemTime>,
     pub created_at: Option<SystemTime>,
     pub updated_at: Option<SystemTime>,
 }
 
+impl Job {
+    /// The uploaded file
+    pub fn source_key(&self) -> StorageKey {
+        StorageKey::new(&self.bucket, &self.file_key)
+    }
+
+    /// Whether the uploaded file is a deduplicated blob other jobs may also reference,
+    /// and so must never be rewritten in place
+    pub fn shares_source(&self) -> bool {
+        is_blob_key(&self.file_key)
+    }
+
+    /// The typed Arrow IPC copy of the parsed dataset, written after processing so
+    /// queries skip CSV parsing and type inference
+    pub fn processed_key(&self) -> StorageKey {
+        StorageKey::new(&self.bucket, tenant_key(&self.tenant_id, &format!("processed/{}.arrow", self.id)))
+    }
+
+    /// Fail with `InvalidTransition` unless the job may move to `to` from its current status
+    pub fn check_transition(&self, to: JobStatus) -> anyhow::Result<()> {
+        let allowed = self.status.parse::<JobStatus>().is_ok_and(|from| from.can_transition_to(to));
+        if !allowed {
+            return Err(InvalidTransition { job_id: self.id, from: self.status.clone(), to }.into());
+        }
+        Ok(())
+    }
+}
+
+/// Whose jobs a listing returns; a `None` field doesn't narrow it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JobFilter {
+    pub tenant_id: Option<String>,
+    pub user_id: Option<String>,
+}
+
+impl JobFilter {
+    /// Every tenant's jobs
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// One tenant's jobs
+    pub fn tenant(tenant_id: impl Into<String>) -> Self {
+        Self { tenant_id: Some(tenant_id.into()), user_id: None }
+    }
+
+    pub fn matches(&self, job: &Job) -> bool {
+        self.tenant_id.as_ref().is_none_or(|tenant| *tenant == job.tenant_id)
+            && self.user_id.as_ref().is_none_or(|user| *user == job.user_id)
+    }
+}
+
+/// Channel job status changes are published on
+pub const JOB_EVENTS_CHANNEL: &str = "job_events";
+
+/// A job status change, published so other services can follow jobs without
+/// polling the jobs table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobEvent {
+    pub job_id: Uuid,
+    pub status: JobStatus,
+    /// Why the job failed, for `failed` events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl JobEvent {
+    pub fn new(job_id: Uuid, status: JobStatus, error_message: Option<&str>) -> Self {
+        Self {
+            job_id,
+            status,
+            error_message: error_message.map(str::to_string),
+            at: Utc::now(),
+        }
+    }
+}
+
 /// Represents a new job to be created
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NewJob {
     pub user_id: String,
+    pub tenant_id: String,
+    pub request_id: Option<String>,
+    pub bucket: String,
     pub file_key: String,
+    pub content_hash: String,
+    pub batch_id: Option<Uuid>,
 }
 
-/// Represents job metadata for client responses
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JobMetadata {
-    pub id: Uuid,
-    pub status: String,
-    pub created_at: Option<SystemTime>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [JobStatus; 5] = [
+        JobStatus::Queued,
+        JobStatus::Processing,
+        JobStatus::Completed,
+        JobStatus::Failed,
+        JobStatus::Cancelled,
+    ];
+
+    /// Every (from, to) pair the workflow allows; anything else is refused
+    const ALLOWED: [(JobStatus, JobStatus); 9] = [
+        (JobStatus::Queued, JobStatus::Processing),
+        (JobStatus::Processing, JobStatus::Completed),
+        (JobStatus::Processing, JobStatus::Failed),
+        (JobStatus::Queued, JobStatus::Failed),
+        (JobStatus::Completed, JobStatus::Failed),
+        (JobStatus::Completed, JobStatus::Queued),
+        (JobStatus::Failed, JobStatus::Queued),
+        (JobStatus::Queued, JobStatus::Cancelled),
+        (JobStatus::Processing, JobStatus::Cancelled),
+    ];
+
+    fn job_in(status: JobStatus) -> Job {
+        Job {
+            id: Uuid::new_v4(),
+            user_id: "user".to_string(),
+            tenant_id: "tenant".to_string(),
+            request_id: None,
+            bucket: "bucket".to_string(),
+            file_key: "uploads/data.csv".to_string(),
+            content_hash: None,
+            batch_id: None,
+            schema_overrides: SchemaOverrides::new(),
+            status: status.to_string(),
+            error_message: None,
+            attempts: 0,
+            low_memory: false,
+            dataset_metadata: None,
+            started_at: None,
+            finished_at: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn allowed(from: JobStatus, to: JobStatus) -> bool {
+        ALLOWED.contains(&(from, to))
+    }
+
+    #[test]
+    fn only_the_workflow_transitions_are_allowed() {
+        for from in ALL {
+            for to in ALL {
+                assert_eq!(from.can_transition_to(to), allowed(from, to), "{} -> {}", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn cancellation_is_only_allowed_before_a_job_finishes() {
+        for from in ALL {
+            let expected = matches!(from, JobStatus::Queued | JobStatus::Processing);
+            assert_eq!(from.can_transition_to(JobStatus::Cancelled), expected, "{} -> cancelled", from);
+        }
+        // Cancelled is final
+        assert!(ALL.iter().all(|&to| !JobStatus::Cancelled.can_transition_to(to)));
+    }
+
+    #[test]
+    fn check_transition_refuses_every_other_change() {
+        for from in ALL {
+            for to in ALL {
+                let job = job_in(from);
+                match job.check_transition(to) {
+                    Ok(()) => assert!(allowed(from, to), "{} -> {} was accepted", from, to),
+                    Err(err) => {
+                        assert!(!allowed(from, to), "{} -> {} was refused", from, to);
+                        let invalid = err.downcast_ref::<InvalidTransition>().expect("an InvalidTransition");
+                        assert_eq!(invalid.job_id, job.id);
+                        assert_eq!(invalid.from, from.to_string());
+                        assert_eq!(invalid.to, to);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn an_unknown_status_cannot_move_anywhere() {
+        let mut job = job_in(JobStatus::Queued);
+        job.status = "archived".to_string();
+        for to in ALL {
+            assert!(job.check_transition(to).unwrap_err().is::<InvalidTransition>());
+        }
+    }
 }
@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Which LLM backend serves summaries, query translation and narration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmProviderKind {
+    /// api.openai.com, authenticated with `OPEN_AI_KEY`
+    #[default]
+    OpenAi,
+    /// An Azure OpenAI resource, addressed by deployment
+    Azure,
+    /// The Anthropic Messages API
+    Anthropic,
+    /// Any server speaking the OpenAI chat completions API, such as Ollama or vLLM,
+    /// so prompts and data stay on-prem
+    Local,
+}
+
+impl LlmProviderKind {
+    /// Model used when `LLM_MODEL` is not set; Azure addresses its deployment instead
+    /// and local servers have no sensible default
+    pub fn default_model(self) -> Option<&'static str> {
+        match self {
+            LlmProviderKind::OpenAi => Some("gpt-4o"),
+            LlmProviderKind::Anthropic => Some("claude-sonnet-4-0"),
+            LlmProviderKind::Azure | LlmProviderKind::Local => None,
+        }
+    }
+}
+
+impl FromStr for LlmProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "openai" => Ok(LlmProviderKind::OpenAi),
+            "azure" | "azure_openai" => Ok(LlmProviderKind::Azure),
+            "anthropic" => Ok(LlmProviderKind::Anthropic),
+            "local" | "ollama" | "vllm" | "openai_compatible" => Ok(LlmProviderKind::Local),
+            other => Err(anyhow!(
+                "Unknown LLM provider '{}' (expected openai, azure, anthropic or local)",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LlmProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LlmProviderKind::OpenAi => "openai",
+            LlmProviderKind::Azure => "azure",
+            LlmProviderKind::Anthropic => "anthropic",
+            LlmProviderKind::Local => "local",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Model and sampling settings for one kind of AI call
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelSettings {
+    /// Model (or Azure deployment), the provider's model when unset
+    pub model: Option<String>,
+    /// Sampling temperature, the provider's default when unset
+    pub temperature: Option<f64>,
+    /// Completion tokens the reply may use
+    pub max_tokens: Option<u32>,
+    /// Seconds to wait for the reply
+    pub timeout_secs: u64,
+}
+
+/// How failed AI calls are retried, and when the provider is given a rest
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RetrySettings {
+    /// Retries after the first attempt of a call
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each later one
+    pub base_delay_ms: u64,
+    /// Longest wait between attempts; a Retry-After beyond it ends the call
+    pub max_delay_ms: u64,
+    /// Consecutive failed calls that open the circuit
+    pub circuit_failure_threshold: u32,
+    /// Seconds calls fail fast once the circuit is open
+    pub circuit_cooldown_secs: u64,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 10_000,
+            circuit_failure_threshold: 5,
+            circuit_cooldown_secs: 30,
+        }
+    }
+}
@@ -1,3 +1,16 @@
 pub mod job;
+pub mod batch;
 pub mod response;
 pub mod conversation;
+pub mod usage;
+pub mod profile;
+pub mod query;
+pub mod llm;
+pub mod storage;
+pub mod database;
+pub mod cache;
+pub mod auth;
+pub mod session;
+pub mod schema;
+pub mod admin;
+pub mod error;
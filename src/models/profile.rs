@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Frequent values reported per categorical column unless the upload asks for another depth
+pub const DEFAULT_TOP_VALUES: usize = 10;
+
+/// Upper bound on the requested frequent-values depth
+pub const MAX_TOP_VALUES: usize = 1000;
+
+/// How much work `generate_insights` puts into the statistics it computes.
+/// Counts, flags and PII detection are always computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InsightProfile {
+    /// Counts and min/max only
+    Minimal,
+    /// Adds mean, standard deviation, frequent values, text profiles, correlations
+    /// and extreme records
+    Basic,
+    /// Adds median and quartiles, categorical associations, time-series anomalies
+    /// and functional dependencies
+    #[default]
+    Full,
+}
+
+impl InsightProfile {
+    /// Mean and standard deviation of numeric columns
+    pub fn moments(self) -> bool {
+        self != InsightProfile::Minimal
+    }
+
+    /// Median and 25th/75th percentiles of numeric columns
+    pub fn percentiles(self) -> bool {
+        self == InsightProfile::Full
+    }
+
+    /// Top values of categorical columns
+    pub fn frequent_values(self) -> bool {
+        self != InsightProfile::Minimal
+    }
+
+    /// Lengths, patterns and formatting issues of string columns
+    pub fn text_profile(self) -> bool {
+        self != InsightProfile::Minimal
+    }
+
+    /// Pairwise correlations between numeric columns
+    pub fn correlations(self) -> bool {
+        self != InsightProfile::Minimal
+    }
+
+    /// Top and bottom rows of each numeric column
+    pub fn extremes(self) -> bool {
+        self != InsightProfile::Minimal
+    }
+
+    /// Categorical–numeric associations
+    pub fn associations(self) -> bool {
+        self == InsightProfile::Full
+    }
+
+    /// Functional dependency discovery
+    pub fn dependencies(self) -> bool {
+        self == InsightProfile::Full
+    }
+
+    /// Time-series anomaly detection
+    pub fn anomalies(self) -> bool {
+        self == InsightProfile::Full
+    }
+}
+
+impl FromStr for InsightProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "minimal" => Ok(InsightProfile::Minimal),
+            "basic" => Ok(InsightProfile::Basic),
+            "full" => Ok(InsightProfile::Full),
+            other => Err(anyhow!("Unknown insight profile '{}' (expected minimal, basic or full)", other)),
+        }
+    }
+}
+
+impl fmt::Display for InsightProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InsightProfile::Minimal => "minimal",
+            InsightProfile::Basic => "basic",
+            InsightProfile::Full => "full",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Dataset-wide profile plus per-column overrides for the column statistics
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileSettings {
+    pub profile: InsightProfile,
+    #[serde(default)]
+    pub columns: HashMap<String, InsightProfile>,
+    /// Numeric columns to run the Benford and plausibility checks on; `*` selects all
+    #[serde(default)]
+    pub plausibility_columns: Vec<String>,
+    /// Frequent values reported per categorical column, `DEFAULT_TOP_VALUES` when unset
+    #[serde(default)]
+    pub top_values: Option<usize>,
+}
+
+impl ProfileSettings {
+    /// Parse overrides written as `column:profile` pairs separated by commas,
+    /// e.g. `revenue:full,notes:minimal`
+    pub fn with_column_overrides(mut self, overrides: &str) -> Result<Self> {
+        for pair in overrides.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (column, profile) = pair
+                .rsplit_once(':')
+                .ok_or_else(|| anyhow!("Invalid column profile '{}' (expected column:profile)", pair))?;
+            self.columns.insert(column.trim().to_string(), profile.parse()?);
+        }
+        Ok(self)
+    }
+
+    /// Parse a comma-separated list of columns to run plausibility checks on
+    pub fn with_plausibility_columns(mut self, columns: &str) -> Self {
+        self.plausibility_columns = columns
+            .split(',')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(String::from)
+            .collect();
+        self
+    }
+
+    /// Resolve the plausibility selection against the dataset's numeric columns
+    pub fn plausibility_targets(&self, numeric_columns: &[String]) -> Vec<String> {
+        if self.plausibility_columns.iter().any(|c| c == "*") {
+            return numeric_columns.to_vec();
+        }
+        numeric_columns
+            .iter()
+            .filter(|c| self.plausibility_columns.contains(c))
+            .cloned()
+            .collect()
+    }
+
+    /// Set how many frequent values are reported per categorical column
+    pub fn with_top_values(mut self, top_values: usize) -> Result<Self> {
+        if top_values == 0 || top_values > MAX_TOP_VALUES {
+            return Err(anyhow!("top_values must be between 1 and {}", MAX_TOP_VALUES));
+        }
+        self.top_values = Some(top_values);
+        Ok(self)
+    }
+
+    /// Frequent values reported per categorical column
+    pub fn top_values(&self) -> usize {
+        self.top_values.unwrap_or(DEFAULT_TOP_VALUES)
+    }
+
+    /// Profile that applies to the statistics of `column`
+    pub fn for_column(&self, column: &str) -> InsightProfile {
+        self.columns.get(column).copied().unwrap_or(self.profile)
+    }
+}
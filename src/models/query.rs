@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use polars::prelude::*;
+use serde::ser::{Error as _, SerializeMap, SerializeSeq};
+use serde::{Deserialize, Serialize, Serializer};
+use std::str::FromStr;
+
+/// Request to run SQL against an uploaded dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SqlRequest {
+    /// The job ID associated with the dataset
+    pub job_id: String,
+    /// SQL statement; the dataset is the table `data`
+    pub sql: String,
+}
+
+/// Request to run a structured query without natural language translation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructuredQueryRequest {
+    /// The job ID associated with the dataset
+    pub job_id: String,
+    /// Query in the documented shape: `intent`, `columns` and `operations`,
+    /// e.g. `{"type": "Sum", "column": "revenue"}`
+    pub query: serde_json::Value,
+}
+
+/// Request to save a named structured query for re-running later
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveQueryRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Dataset the query is validated against and runs on by default
+    pub job_id: String,
+    /// Query in the shape `/api/query/structured` accepts
+    pub query: serde_json::Value,
+}
+
+/// A named structured query saved against a dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub job_id: String,
+    /// Query after column resolution, in the shape `/api/query/structured` accepts
+    pub query: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Query parameters of `GET /api/queries`
+#[derive(Debug, Deserialize)]
+pub struct SavedQueryFilter {
+    /// Only list queries saved against this dataset
+    pub job_id: Option<String>,
+}
+
+/// Query parameters of `GET /api/queries/{id}/run`
+#[derive(Debug, Deserialize)]
+pub struct RunSavedQueryOptions {
+    /// Run on another dataset with the same columns, e.g. this week's upload
+    pub job_id: Option<String>,
+}
+
+/// Name and type of a result column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultColumn {
+    pub name: String,
+    pub data_type: String,
+}
+
+/// Rows and schema produced by a query
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryResult {
+    pub columns: Vec<ResultColumn>,
+    /// Rows as JSON objects keyed by column name
+    pub rows: ResultRows,
+    pub row_count: usize,
+    /// Whether the result was cut off at the row limit
+    pub truncated: bool,
+}
+
+/// The rows of a query result, kept as the frame and written out as JSON objects
+/// row by row when the response is serialized, with no intermediate JSON tree
+#[derive(Debug, Clone)]
+pub struct ResultRows(pub DataFrame);
+
+impl Serialize for ResultRows {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        FrameRows(&self.0).serialize(serializer)
+    }
+}
+
+/// A borrowed frame serialized as an array of objects keyed by column name, in
+/// column order. Dates and datetimes are written as text, non-finite floats as null.
+pub struct FrameRows<'a>(pub &'a DataFrame);
+
+impl Serialize for FrameRows<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let columns = self.0.get_columns();
+        let mut rows = serializer.serialize_seq(Some(self.0.height()))?;
+        for index in 0..self.0.height() {
+            rows.serialize_element(&FrameRow { columns, index })?;
+        }
+        rows.end()
+    }
+}
+
+struct FrameRow<'a> {
+    columns: &'a [Series],
+    index: usize,
+}
+
+impl Serialize for FrameRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut row = serializer.serialize_map(Some(self.columns.len()))?;
+        for series in self.columns {
+            let value = series.get(self.index).map_err(S::Error::custom)?;
+            row.serialize_entry(series.name(), &Cell(value))?;
+        }
+        row.end()
+    }
+}
+
+/// One value of a frame, written as `FrameRows` writes it
+pub struct Cell<'a>(pub AnyValue<'a>);
+
+impl Serialize for Cell<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match &self.0 {
+            AnyValue::Null => serializer.serialize_unit(),
+            AnyValue::Boolean(v) => serializer.serialize_bool(*v),
+            AnyValue::Int8(v) => serializer.serialize_i8(*v),
+            AnyValue::Int16(v) => serializer.serialize_i16(*v),
+            AnyValue::Int32(v) => serializer.serialize_i32(*v),
+            AnyValue::Int64(v) => serializer.serialize_i64(*v),
+            AnyValue::UInt8(v) => serializer.serialize_u8(*v),
+            AnyValue::UInt16(v) => serializer.serialize_u16(*v),
+            AnyValue::UInt32(v) => serializer.serialize_u32(*v),
+            AnyValue::UInt64(v) => serializer.serialize_u64(*v),
+            AnyValue::Float32(v) => serializer.serialize_f32(*v),
+            AnyValue::Float64(v) => serializer.serialize_f64(*v),
+            AnyValue::List(values) => {
+                let mut list = serializer.serialize_seq(Some(values.len()))?;
+                for index in 0..values.len() {
+                    list.serialize_element(&Cell(values.get(index).map_err(S::Error::custom)?))?;
+                }
+                list.end()
+            }
+            value => match value.get_str() {
+                Some(text) => serializer.serialize_str(text),
+                // Dates, datetimes and durations as Polars prints them
+                None => serializer.collect_str(value),
+            },
+        }
+    }
+}
+
+/// File formats a query result can be downloaded as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+    /// Arrow IPC (Feather v2), for columnar tools such as pandas and DuckDB
+    Arrow,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            ExportFormat::Arrow => "application/vnd.apache.arrow.file",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Arrow => "arrow",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "xlsx" | "excel" => Ok(ExportFormat::Xlsx),
+            "arrow" | "feather" | "ipc" => Ok(ExportFormat::Arrow),
+            other => Err(anyhow!("Unknown export format '{}' (expected csv, xlsx or arrow)", other)),
+        }
+    }
+}
@@ -1,25 +1,94 @@
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
 use uuid::Uuid;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::i18n::{Locale, Message};
+use crate::models::error::ErrorCode;
+use crate::models::profile::InsightProfile;
 
 /// Response for file upload endpoint
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UploadResponse {
     pub job_id: Uuid,
     pub status: String,
+    /// Hex SHA-256 of the job's file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
 }
 
-/// Represents a category average for insights
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CategoryAverage {
-    pub category: String,
-    pub avg_score: f64,
+/// Cardinality-based classification of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnFlag {
+    /// Nearly every value is distinct, e.g. an order number or UUID
+    LikelyIdentifier,
+    /// The column holds a single distinct value (or only nulls)
+    Constant,
+    /// A categorical column with too many distinct values to group by
+    HighCardinality,
+}
+
+/// Kind of personal data detected in a column's values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiType {
+    Email,
+    Phone,
+    NationalId,
+    CreditCard,
+}
+
+/// Share of a text column's values matching one shape, e.g. `integer` or `date`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TextPattern {
+    pub pattern: String,
+    pub share: f64,
+}
+
+/// Profile of a string column
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TextProfile {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub avg_length: f64,
+    /// Most common shapes first, measured on a sample of the values
+    pub patterns: Vec<TextPattern>,
+    /// Values with leading, trailing or repeated spaces
+    pub whitespace_issues: usize,
+    /// Distinct values spelled with more than one capitalisation
+    pub case_variants: usize,
+    /// Writing system most letters belong to, e.g. `latin` or `cyrillic`
+    pub script: Option<String>,
+    /// `integer`, `float`, `date` or `boolean` when nearly every value parses as one
+    pub suggested_type: Option<String>,
+}
+
+/// A string column whose values look like another type
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TypeSuggestion {
+    pub column: String,
+    pub suggested_type: String,
+}
+
+/// How concentrated a categorical column's values are
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LongTail {
+    pub distinct_values: usize,
+    /// Fewest distinct values that together cover 80% of the non-null rows
+    pub values_for_80_percent: usize,
+    /// Share of non-null rows covered by the values listed in `frequent_values`
+    pub top_values_coverage: f64,
+    /// Shannon entropy of the value distribution in bits
+    pub entropy: f64,
+    /// Entropy divided by its maximum for this many values: 0 is one dominant value, 1 is uniform
+    pub normalized_entropy: f64,
 }
 
 /// Statistics for a single column in the dataset
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ColumnStatistics {
     pub name: String,
     pub data_type: String,
@@ -33,6 +102,17 @@ pub struct ColumnStatistics {
     pub percentile_25: Option<String>,
     pub percentile_75: Option<String>,
     pub frequent_values: Option<HashMap<String, u32>>,
+    /// Concentration of the values beyond `frequent_values`
+    #[serde(default)]
+    pub long_tail: Option<LongTail>,
+    #[serde(default)]
+    pub flags: Vec<ColumnFlag>,
+    /// Personal data found in a sample of the column's values
+    #[serde(default)]
+    pub pii_types: Vec<PiiType>,
+    /// Lengths, patterns and formatting issues of string columns
+    #[serde(default)]
+    pub text_profile: Option<TextProfile>,
 }
 
 /// Summary of the dataset
@@ -43,7 +123,151 @@ pub struct DataSummary {
     pub numeric_columns: Vec<String>,
     pub categorical_columns: Vec<String>,
     pub date_columns: Vec<String>,
+    /// Columns excluded from correlations and visualization recommendations
+    #[serde(default)]
+    pub identifier_columns: Vec<String>,
+    #[serde(default)]
+    pub constant_columns: Vec<String>,
+    #[serde(default)]
+    pub high_cardinality_columns: Vec<String>,
+    /// Columns that appear to contain personal data
+    #[serde(default)]
+    pub pii_columns: Vec<String>,
+    /// String columns that could be cast to a numeric, date or boolean type
+    #[serde(default)]
+    pub type_suggestions: Vec<TypeSuggestion>,
+    /// Dataset-wide insight profile the statistics were computed with
+    #[serde(default)]
+    pub profile: Option<InsightProfile>,
+    /// How rows were sampled for the statistics, absent when every row was used
+    #[serde(default)]
+    pub sampling: Option<SamplingInfo>,
+    /// Rows folded in by appends since the last full computation
+    #[serde(default)]
+    pub incremental: Option<IncrementalInfo>,
+    /// In English as stored; responses render it in the caller's language
     pub summary_text: String,
+    /// The sentences of `summary_text`, which it is rendered from
+    #[serde(default)]
+    pub summary_parts: Vec<SummaryPart>,
+}
+
+impl DataSummary {
+    /// Add a sentence to the summary
+    pub fn push_summary(&mut self, part: SummaryPart) {
+        if !self.summary_text.is_empty() {
+            self.summary_text.push(' ');
+        }
+        self.summary_text.push_str(&self.summary_sentence(&part, Locale::En));
+        self.summary_parts.push(part);
+    }
+
+    /// The summary in `locale`; summaries stored before their parts were kept
+    /// are only available in English
+    pub fn localized_summary(&self, locale: Locale) -> String {
+        if self.summary_parts.is_empty() {
+            return self.summary_text.clone();
+        }
+        self.summary_parts
+            .iter()
+            .map(|part| self.summary_sentence(part, locale))
+            .filter(|sentence| !sentence.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn summary_sentence(&self, part: &SummaryPart, locale: Locale) -> String {
+        let message = match part {
+            SummaryPart::Shape => Message::DatasetShape(
+                self.row_count,
+                self.column_count,
+                self.numeric_columns.len(),
+                self.categorical_columns.len(),
+                self.date_columns.len(),
+            ),
+            SummaryPart::Sampled => match &self.sampling {
+                Some(info) => Message::StatisticsSampled(info.sample_rows, info.total_rows),
+                None => return String::new(),
+            },
+            SummaryPart::LowMemory => Message::ProcessedInLowMemory,
+            SummaryPart::IdentifierColumns => Message::IdentifierColumns(&self.identifier_columns),
+            SummaryPart::ConstantColumns => Message::ConstantColumns(&self.constant_columns),
+            SummaryPart::HighCardinalityColumns => Message::HighCardinalityColumns(&self.high_cardinality_columns),
+            SummaryPart::PersonalDataColumns => Message::PersonalDataColumns(&self.pii_columns),
+            SummaryPart::TypeSuggestions => {
+                let suggestions: Vec<String> = self
+                    .type_suggestions
+                    .iter()
+                    .map(|s| format!("{} ({})", s.column, s.suggested_type))
+                    .collect();
+                return Message::TypeSuggestions(&suggestions).localize(locale);
+            }
+            SummaryPart::VarianceExplained { categorical_column, numeric_column, percent } => {
+                Message::VarianceExplained(categorical_column, *percent, numeric_column)
+            }
+            SummaryPart::Anomalies { count, measure_column, period, z_score } => {
+                Message::AnomalousPeriods(*count, measure_column, period, *z_score)
+            }
+            SummaryPart::FunctionalDependencies { dependencies } => Message::FunctionalDependencies(dependencies),
+            SummaryPart::ImplausibleColumns { columns } => Message::ImplausibleColumns(columns),
+        };
+        message.localize(locale)
+    }
+}
+
+/// One sentence of a dataset summary. Sentences about data the summary already
+/// holds read it from there; the others carry what they report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SummaryPart {
+    /// Row and column counts
+    Shape,
+    /// Statistics were computed on the sample in `sampling`
+    Sampled,
+    /// The file was processed in low-memory mode
+    LowMemory,
+    IdentifierColumns,
+    ConstantColumns,
+    HighCardinalityColumns,
+    PersonalDataColumns,
+    TypeSuggestions,
+    /// The strongest categorical driver of a numeric column
+    VarianceExplained {
+        categorical_column: String,
+        numeric_column: String,
+        percent: f64,
+    },
+    /// How many anomalous periods were found, and the largest
+    Anomalies {
+        count: usize,
+        measure_column: String,
+        period: String,
+        z_score: f64,
+    },
+    /// The first few dependencies, as `determinant → dependent`
+    FunctionalDependencies { dependencies: Vec<String> },
+    /// Columns failing plausibility checks
+    ImplausibleColumns { columns: Vec<String> },
+}
+
+/// Appends merged into the insights without recomputing them from scratch
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct IncrementalInfo {
+    pub appends: u32,
+    pub appended_rows: usize,
+    /// Fields still reflecting the last full computation
+    pub approximate_fields: Vec<String>,
+}
+
+/// Sampling scheme used when a dataset is too large for full-column statistics.
+/// `DataSummary::row_count` always reports the exact number of rows.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SamplingInfo {
+    pub method: String,
+    pub sample_rows: usize,
+    pub total_rows: usize,
+    /// Seed of the random draw, so the sample can be reproduced
+    pub seed: u64,
 }
 
 /// Visualization recommendation from AI analysis
@@ -62,6 +286,33 @@ pub struct AISummary {
     pub key_insights: Vec<String>,
     pub actionable_recommendations: Vec<ActionableRecommendation>,
     pub visualization_recommendations: Vec<VisualizationRecommendation>,
+    /// Why the AI analysis could not be generated, on the placeholder stored instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+}
+
+impl AISummary {
+    /// Placeholder for an AI analysis that could not be generated, saying why
+    pub fn unavailable(error: Option<String>) -> Self {
+        let error = error.unwrap_or_else(|| "Unknown".to_string());
+        let mut summary = Self { failure: Some(error), ..Default::default() };
+        summary.localize(Locale::En);
+        summary
+    }
+
+    /// Render a placeholder in `locale`; generated analyses are left as the model wrote them
+    pub fn localize(&mut self, locale: Locale) {
+        let Some(error) = &self.failure else {
+            return;
+        };
+        self.summary = Message::AiAnalysisFailed(error).localize(locale);
+        self.key_insights = vec![Message::NoAiInsights.localize(locale)];
+        self.actionable_recommendations = vec![ActionableRecommendation {
+            recommendation: Message::ReviewDataset.localize(locale),
+            rationale: Message::NoAiPatterns.localize(locale),
+        }];
+        self.visualization_recommendations = Vec::new();
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -70,15 +321,190 @@ pub struct ActionableRecommendation {
     pub rationale: String,
 }
 
+/// Summary of a numeric column within a single category
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct GroupStatistics {
+    pub group: String,
+    pub count: usize,
+    pub mean: f64,
+    pub std_dev: Option<f64>,
+}
+
+/// Association strength between a categorical and a numeric column
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CategoricalAssociation {
+    pub categorical_column: String,
+    pub numeric_column: String,
+    /// Correlation ratio (eta): 0 means no association, 1 means the category fully determines the value
+    pub correlation_ratio: f64,
+    /// One-way ANOVA F-statistic, absent when every group has zero variance
+    pub f_statistic: Option<f64>,
+    pub group_statistics: Vec<GroupStatistics>,
+}
+
+/// A period whose aggregated value deviates sharply from its trailing window
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct TimeSeriesAnomaly {
+    pub date_column: String,
+    pub measure_column: String,
+    /// Period start, formatted as YYYY-MM-DD
+    pub period: String,
+    pub value: f64,
+    /// Mean of the trailing window the value was compared against
+    pub expected: f64,
+    pub z_score: f64,
+}
+
+/// A row at one end of a numeric column, with a few identifying fields
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct RecordSample {
+    pub value: f64,
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Highest and lowest rows of a numeric column
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ExtremeRecords {
+    pub column: String,
+    pub top: Vec<RecordSample>,
+    pub bottom: Vec<RecordSample>,
+}
+
+/// Near-functional dependency: each `determinant` value (almost) always maps to one `dependent` value
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct FunctionalDependency {
+    pub determinant: String,
+    pub dependent: String,
+    /// Share of rows consistent with the dependency, 1.0 for an exact dependency
+    pub strength: f64,
+    /// Rows that break the dependency
+    pub violations: usize,
+    pub determinant_distinct: usize,
+    pub dependent_distinct: usize,
+}
+
+/// Reason a numeric column looks implausible
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlausibilityFlag {
+    /// First digits deviate from Benford's law
+    BenfordNonconformity,
+    /// A handful of negative values in an otherwise non-negative column
+    StrayNegatives,
+    /// Zeros are unusually common, possibly standing in for missing values
+    ExcessZeros,
+    /// Most values are multiples of 10, suggesting estimates or made-up figures
+    ExcessRoundNumbers,
+}
+
+/// First-digit distribution compared against Benford's law
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct BenfordResult {
+    /// Observed share of first digits 1 through 9
+    pub observed: Vec<f64>,
+    /// Benford share of first digits 1 through 9
+    pub expected: Vec<f64>,
+    /// Chi-square statistic (8 degrees of freedom)
+    pub chi_square: f64,
+    /// Mean absolute deviation between observed and expected shares
+    pub mad: f64,
+    /// "close", "acceptable", "marginal" or "nonconformity"
+    pub conformity: String,
+}
+
+/// Plausibility checks on one numeric column
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct PlausibilityCheck {
+    pub column: String,
+    /// Non-null values checked
+    pub values: usize,
+    pub negative_share: f64,
+    pub zero_share: f64,
+    /// Share of values ≥ 10 that are multiples of 10
+    pub round_share: Option<f64>,
+    /// Absent when the column has too few values or spans too narrow a range
+    pub benford: Option<BenfordResult>,
+    pub flags: Vec<PlausibilityFlag>,
+}
+
 /// Represents insights generated from data analysis
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Insights {
     pub data_summary: DataSummary,
     pub column_statistics: Vec<ColumnStatistics>,
     pub correlations: Option<HashMap<String, f64>>,
+    pub categorical_associations: Option<Vec<CategoricalAssociation>>,
+    pub anomalies: Option<Vec<TimeSeriesAnomaly>>,
+    pub extreme_records: Option<Vec<ExtremeRecords>>,
+    pub functional_dependencies: Option<Vec<FunctionalDependency>>,
+    pub plausibility: Option<Vec<PlausibilityCheck>>,
     pub ai_analysis: Option<AISummary>,
 }
 
+impl Insights {
+    /// Render the summary and an AI analysis placeholder in `locale`
+    pub fn localize(&mut self, locale: Locale) {
+        self.data_summary.summary_text = self.data_summary.localized_summary(locale);
+        if let Some(ai_analysis) = self.ai_analysis.as_mut() {
+            ai_analysis.localize(locale);
+        }
+    }
+}
+
+/// Columns added, removed or retyped between two datasets
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SchemaDiff {
+    pub added_columns: Vec<String>,
+    pub removed_columns: Vec<String>,
+    pub type_changes: Vec<TypeChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct TypeChange {
+    pub column: String,
+    pub base_type: String,
+    pub target_type: String,
+}
+
+/// Distribution shift of one column between two datasets
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ColumnDrift {
+    pub column: String,
+    /// "numeric" or "categorical"
+    pub kind: String,
+    /// Population stability index over decile bins (numeric) or categories
+    pub psi: f64,
+    /// "stable", "moderate" or "significant"
+    pub severity: String,
+    /// Two-sample Kolmogorov–Smirnov statistic, numeric columns only
+    pub ks_statistic: Option<f64>,
+    pub ks_p_value: Option<f64>,
+    pub base_mean: Option<f64>,
+    pub target_mean: Option<f64>,
+    /// Categories that only appear in the target dataset
+    pub new_categories: Vec<String>,
+    /// Categories that no longer appear in the target dataset
+    pub missing_categories: Vec<String>,
+}
+
+/// What changed between two uploaded datasets
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DriftReport {
+    pub base_job_id: Uuid,
+    pub target_job_id: Uuid,
+    pub base_row_count: usize,
+    pub target_row_count: usize,
+    pub row_count_delta: i64,
+    /// Relative row count change in percent, absent when the base is empty
+    pub row_count_change_pct: Option<f64>,
+    pub schema: SchemaDiff,
+    /// Columns present in both datasets, largest shift first
+    pub columns: Vec<ColumnDrift>,
+    /// Sampling applied to the base and target before comparing distributions
+    pub base_sampling: Option<SamplingInfo>,
+    pub target_sampling: Option<SamplingInfo>,
+}
+
 /// Response for insights endpoint
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InsightsResponse {
@@ -89,9 +515,155 @@ pub struct InsightsResponse {
     pub insights: Option<Insights>,
 }
 
+/// Response for clearing a job's cached insights
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheInvalidationResponse {
+    pub job_id: Uuid,
+    pub message: String,
+}
+
 /// Error response for API
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
+    pub code: ErrorCode,
     pub error: String,
     pub status_code: u16,
+    /// Context for the error, such as the conversation a failed query belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    /// `X-Request-Id` of the failed request, to find its log lines
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// What this deployment supports, so clients can adapt instead of probing endpoints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub backends: BackendCapabilities,
+    pub query: QueryCapabilities,
+    pub ai: AiCapabilities,
+    pub limits: LimitCapabilities,
+    /// Sections that may appear in the insights payload
+    pub insights: Vec<String>,
+    /// Languages deterministic responses are localized into (ISO 639-1)
+    pub locales: Vec<String>,
+    /// Cargo features the server was compiled with
+    pub features: Vec<String>,
+}
+
+/// Backends the running server is wired to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackendCapabilities {
+    pub storage: String,
+    pub database: String,
+    pub cache: String,
+}
+
+/// Intents, operations and filter operators the query engine can execute
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryCapabilities {
+    pub intents: Vec<String>,
+    pub operations: Vec<String>,
+    pub filter_operators: Vec<String>,
+    /// Units a date column can be truncated to
+    pub date_trunc_units: Vec<String>,
+    /// Aggregations a pivot table can use
+    pub pivot_aggregations: Vec<String>,
+    /// Comparison operators a `Having` condition can use
+    pub having_operators: Vec<String>,
+    /// Time units a rolling window can span
+    pub window_units: Vec<String>,
+}
+
+/// Whether AI-backed features are available
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AiCapabilities {
+    pub available: bool,
+    /// LLM provider (openai, azure, anthropic or local), absent when AI is unavailable
+    pub provider: Option<String>,
+    /// Model dataset summaries and answers are written with, absent when AI is unavailable
+    pub summary_model: Option<String>,
+    /// Model queries are translated with, absent when AI is unavailable
+    pub translation_model: Option<String>,
+    /// Whether completed jobs include `ai_analysis` in their insights
+    pub job_analysis: bool,
+}
+
+/// Size and row limits enforced by the server
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LimitCapabilities {
+    /// Largest accepted upload, absent when uploads are not capped
+    pub max_upload_bytes: Option<u64>,
+    pub describe_row_limit: usize,
+    pub visualize_row_limit: usize,
+    pub sql_row_limit: usize,
+    pub job_queue_capacity: usize,
+    /// Datasets with more rows than this get statistics computed on a sample
+    pub insights_sample_threshold_rows: usize,
+    pub insights_sample_size: usize,
+    /// Seconds a query may run before it is abandoned
+    pub query_timeout_secs: u64,
+    /// Largest in-memory dataset a query may run over
+    pub query_max_dataset_bytes: usize,
+    /// Largest result a query may produce
+    pub query_max_result_rows: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary() -> DataSummary {
+        let mut summary = DataSummary {
+            row_count: 1200,
+            column_count: 3,
+            numeric_columns: vec!["revenue".to_string()],
+            categorical_columns: vec!["id".to_string(), "region".to_string()],
+            identifier_columns: vec!["id".to_string()],
+            ..Default::default()
+        };
+        summary.push_summary(SummaryPart::Shape);
+        summary.push_summary(SummaryPart::IdentifierColumns);
+        summary.push_summary(SummaryPart::VarianceExplained {
+            categorical_column: "region".to_string(),
+            numeric_column: "revenue".to_string(),
+            percent: 42.4,
+        });
+        summary
+    }
+
+    #[test]
+    fn summaries_are_stored_in_english_and_rendered_per_locale() {
+        let summary = summary();
+        assert_eq!(
+            summary.summary_text,
+            "Dataset has 1,200 rows and 3 columns (1 numeric, 2 categorical, 0 date). \
+             Likely identifier columns: id. 'region' explains 42% of the variance in 'revenue'."
+        );
+        assert_eq!(summary.localized_summary(Locale::En), summary.summary_text);
+        assert_eq!(
+            summary.localized_summary(Locale::Fr),
+            "Le jeu de données compte 1 200 lignes et 3 colonnes (1 numériques, 2 catégorielles, 0 de date). \
+             Colonnes probablement identifiantes : id. « region » explique 42 % de la variance de « revenue »."
+        );
+    }
+
+    #[test]
+    fn summaries_stored_without_parts_keep_their_text() {
+        let summary = DataSummary { summary_text: "Dataset has 3 rows.".to_string(), ..Default::default() };
+        assert_eq!(summary.localized_summary(Locale::Pt), "Dataset has 3 rows.");
+    }
+
+    #[test]
+    fn only_ai_placeholders_are_localized() {
+        let mut placeholder = AISummary::unavailable(Some("timeout".to_string()));
+        assert!(placeholder.summary.ends_with("Last error: timeout"));
+        placeholder.localize(Locale::Pt);
+        assert_eq!(placeholder.summary, "Não foi possível gerar a análise por IA no momento. Último erro: timeout");
+        assert_eq!(placeholder.key_insights, vec!["Nenhuma análise pôde ser gerada a partir dos dados."]);
+
+        let mut generated = AISummary { summary: "Revenue grew.".to_string(), ..Default::default() };
+        generated.localize(Locale::Fr);
+        assert_eq!(generated.summary, "Revenue grew.");
+    }
 }
@@ -0,0 +1,102 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Type a column can be read as, whatever CSV type inference decided
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Date,
+    Boolean,
+    Text,
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Date => "date",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Text => "text",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "integer" => Ok(ColumnType::Integer),
+            "float" => Ok(ColumnType::Float),
+            "date" => Ok(ColumnType::Date),
+            "boolean" => Ok(ColumnType::Boolean),
+            "text" => Ok(ColumnType::Text),
+            other => Err(anyhow::anyhow!(
+                "Unknown column type '{}' (expected integer, float, date, boolean or text)",
+                other
+            )),
+        }
+    }
+}
+
+/// Types a job's columns are read as instead of the inferred ones, by column name
+pub type SchemaOverrides = BTreeMap<String, ColumnType>;
+
+/// Overrides stored as JSON text; jobs without any store none
+pub fn overrides_from_json(json: Option<&str>) -> anyhow::Result<SchemaOverrides> {
+    match json {
+        Some(json) => serde_json::from_str(json).context("Invalid schema overrides in the database"),
+        None => Ok(SchemaOverrides::new()),
+    }
+}
+
+/// A column whose values would be better read as another type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct TypeFix {
+    pub suggested_type: ColumnType,
+    /// Share of the sampled non-null values that parse as `suggested_type`
+    pub parseable_share: f64,
+    /// The suggestion in words, e.g. `revenue is str but 99% parses as float`
+    pub message: String,
+}
+
+/// A column of a job's dataset as it is read after overrides
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct SchemaColumn {
+    pub name: String,
+    /// Polars dtype, e.g. `i64` or `str`
+    pub dtype: String,
+    /// Share of rows where the column is null
+    pub null_ratio: f64,
+    /// Type the column is overridden to, if any
+    #[serde(rename = "override")]
+    #[cfg_attr(feature = "graphql", graphql(name = "override"))]
+    pub override_type: Option<ColumnType>,
+    pub suggestion: Option<TypeFix>,
+}
+
+/// Response of `GET /datasets/{job_id}/schema`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DatasetSchema {
+    pub job_id: Uuid,
+    pub row_count: usize,
+    pub columns: Vec<SchemaColumn>,
+}
+
+/// Body of `POST /datasets/{job_id}/schema`; replaces the job's overrides
+#[derive(Debug, Clone, Deserialize)]
+pub struct SchemaUpdate {
+    pub overrides: SchemaOverrides,
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::conversation::{QueryProgress, QueryResponse};
+use crate::models::response::ErrorResponse;
+
+/// Query parameters of the WebSocket conversation endpoint; a session either
+/// continues `conversation_id` or starts a conversation about `job_id`
+#[derive(Debug, Deserialize)]
+pub struct SessionOptions {
+    pub conversation_id: Option<String>,
+    pub job_id: Option<String>,
+}
+
+/// A text frame sent by the client of a conversation session
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientFrame {
+    /// Ask a question in the session's conversation, with the paging fields of `QueryRequest`
+    Query {
+        query: String,
+        #[serde(default)]
+        page: Option<usize>,
+        #[serde(default)]
+        page_size: Option<usize>,
+        #[serde(default)]
+        cursor: Option<String>,
+        #[serde(default)]
+        explain: bool,
+    },
+}
+
+/// A text frame sent to the client of a conversation session
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerFrame {
+    /// The session opened, or its conversation was created by the first answer
+    Session {
+        job_id: String,
+        conversation_id: Option<String>,
+    },
+    /// Whether an answer is being worked on
+    Typing { active: bool },
+    /// A step of the answer being worked on
+    Partial(QueryProgress),
+    /// The answer, as `/api/conversation/query` returns it
+    Response(QueryResponse),
+    /// The frame could not be answered, as an HTTP error body
+    Error(ErrorResponse),
+}
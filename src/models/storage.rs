@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// Where uploaded datasets and processed copies are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackendKind {
+    /// In memory, mirrored to `./storage` for local development
+    #[default]
+    Memory,
+    /// Files under a local directory, for single-node deployments
+    Fs,
+    /// An AWS S3 bucket (needs the `external-services` feature)
+    S3,
+}
+
+impl FromStr for StorageBackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "memory" => Ok(StorageBackendKind::Memory),
+            "fs" | "filesystem" => Ok(StorageBackendKind::Fs),
+            "s3" => Ok(StorageBackendKind::S3),
+            other => Err(anyhow!("Unknown storage backend '{}' (expected memory, fs or s3)", other)),
+        }
+    }
+}
+
+impl fmt::Display for StorageBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StorageBackendKind::Memory => "memory",
+            StorageBackendKind::Fs => "fs",
+            StorageBackendKind::S3 => "s3",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A stored object's key, size and when it was last written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Where an object lives: the bucket and key recorded with the job that owns it,
+/// so every reader resolves a dataset the same way
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageKey {
+    pub bucket: String,
+    pub key: String,
+}
+
+impl StorageKey {
+    pub fn new(bucket: impl Into<String>, key: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+            key: key.into(),
+        }
+    }
+}
+
+impl fmt::Display for StorageKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.bucket, self.key)
+    }
+}
+
+/// Prefix of deduplicated uploads, stored once per content hash and shared by
+/// every job of the tenant that uploaded the same file
+pub const BLOB_PREFIX: &str = "blobs/";
+
+/// Prefix every tenant's objects are stored under, apart from the default tenant's
+pub const TENANT_PREFIX: &str = "tenants/";
+
+/// Tenant of anonymous requests and of jobs created before tenants existed. Its
+/// objects keep the unprefixed layout, so existing deployments need no move.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Hex SHA-256 of an object's content
+pub fn content_hash(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Prefix of a tenant's objects. Characters other than letters, digits, `-`, `_`
+/// and `.` are percent-encoded, so no tenant ID can reach into another's prefix.
+pub fn tenant_prefix(tenant_id: &str) -> String {
+    if tenant_id == DEFAULT_TENANT {
+        return String::new();
+    }
+    let mut prefix = String::from(TENANT_PREFIX);
+    for byte in tenant_id.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => prefix.push(byte as char),
+            _ => prefix.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    prefix.push('/');
+    prefix
+}
+
+/// Key of `key` within a tenant's objects
+pub fn tenant_key(tenant_id: &str, key: &str) -> String {
+    format!("{}{}", tenant_prefix(tenant_id), key)
+}
+
+/// Key of a tenant's shared copy of an upload with this content hash
+pub fn blob_key(tenant_id: &str, content_hash: &str) -> String {
+    tenant_key(tenant_id, &format!("{}{}.csv", BLOB_PREFIX, content_hash))
+}
+
+/// Whether a key is a deduplicated blob, in any tenant's objects
+pub fn is_blob_key(key: &str) -> bool {
+    let key = key
+        .strip_prefix(TENANT_PREFIX)
+        .and_then(|rest| rest.split_once('/'))
+        .map_or(key, |(_, rest)| rest);
+    key.starts_with(BLOB_PREFIX)
+}
@@ -0,0 +1,132 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ops::AddAssign;
+
+/// Outcome of reserving an amount against a usage counter kept in the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageReservation {
+    /// The amount was added; the counter now holds `used`
+    Reserved { used: u64 },
+    /// Adding the amount would pass the limit; the counter holds `used`
+    Exceeded { used: u64 },
+    /// The counter is not set, never seeded or expired, and must be seeded first
+    Unset,
+}
+
+/// Token counts and estimated spend for one or more LLM calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub estimated_cost_usd: f64,
+}
+
+impl TokenUsage {
+    /// Read the `usage` block of a chat completion response and price it for `model`
+    pub fn from_completion(response: &Value, model: &str) -> Self {
+        let usage = &response["usage"];
+        Self::priced(
+            usage["prompt_tokens"].as_u64().unwrap_or(0),
+            usage["completion_tokens"].as_u64().unwrap_or(0),
+            model,
+        )
+    }
+
+    /// Token counts priced at `model`'s list price
+    pub fn priced(prompt_tokens: u64, completion_tokens: u64, model: &str) -> Self {
+        let (prompt_price, completion_price) = price_per_million_tokens(model);
+
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            estimated_cost_usd: (prompt_tokens as f64 * prompt_price
+                + completion_tokens as f64 * completion_price)
+                / 1_000_000.0,
+        }
+    }
+
+    /// Token counts from a self-hosted model, which costs nothing per token
+    pub fn unpriced(prompt_tokens: u64, completion_tokens: u64) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            estimated_cost_usd: 0.0,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.estimated_cost_usd += other.estimated_cost_usd;
+    }
+}
+
+/// AI spend of one job on one day, by one user and, for queries, in one conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageEntry {
+    pub date: NaiveDate,
+    pub job_id: String,
+    pub tenant_id: Option<String>,
+    pub user_id: Option<String>,
+    /// `None` for dataset summaries and suggested questions
+    pub conversation_id: Option<String>,
+    #[serde(flatten)]
+    pub usage: TokenUsage,
+}
+
+/// AI spend of one user across all their jobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserUsage {
+    pub user_id: Option<String>,
+    #[serde(flatten)]
+    pub usage: TokenUsage,
+}
+
+/// Filters accepted by `GET /usage`; dates are inclusive
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UsageQuery {
+    pub job_id: Option<String>,
+    pub tenant_id: Option<String>,
+    pub user_id: Option<String>,
+    pub conversation_id: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// AI spend matching a `UsageQuery`, in total, per user and per day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub total: TokenUsage,
+    pub by_user: Vec<UserUsage>,
+    pub entries: Vec<UsageEntry>,
+}
+
+/// Rough token count of text sent to a model, at about four characters per token,
+/// for budgeting prompts before they are sent
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Published USD list prices per million (prompt, completion) tokens.
+/// Unknown models are priced as gpt-4o so estimates err on the high side.
+fn price_per_million_tokens(model: &str) -> (f64, f64) {
+    match model {
+        m if m.starts_with("gpt-4o-mini") => (0.15, 0.60),
+        m if m.starts_with("gpt-4o") => (2.50, 10.00),
+        m if m.starts_with("gpt-4-turbo") => (10.00, 30.00),
+        m if m.starts_with("gpt-3.5-turbo") => (0.50, 1.50),
+        m if m.starts_with("claude") && m.contains("haiku") => (0.80, 4.00),
+        m if m.starts_with("claude") && m.contains("opus") => (15.00, 75.00),
+        m if m.starts_with("claude") => (3.00, 15.00),
+        _ => (2.50, 10.00),
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{bail, Result};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::models::admin::{JobSummary, StorageReport, StorageUsage, StuckJobs, TenantUsage};
+use crate::models::job::{InvalidTransition, Job, JobFilter, JobStatus};
+use crate::models::storage::{tenant_prefix, ObjectInfo, DEFAULT_TENANT, TENANT_PREFIX};
+use crate::services::job_queue::JobQueue;
+use crate::services::{DataProcessor, DatabaseServiceTrait, RedisServiceTrait, S3ServiceTrait};
+
+/// Jobs read per page when walking every job
+const PAGE_SIZE: usize = 500;
+
+impl From<&Job> for JobSummary {
+    fn from(job: &Job) -> Self {
+        let last_change = job.updated_at.or(job.created_at);
+        Self {
+            job_id: job.id,
+            tenant_id: job.tenant_id.clone(),
+            user_id: job.user_id.clone(),
+            status: job.status.clone(),
+            attempts: job.attempts,
+            low_memory: job.low_memory,
+            request_id: job.request_id.clone(),
+            error_message: job.error_message.clone(),
+            idle_secs: last_change.and_then(|at| SystemTime::now().duration_since(at).ok()).map(|idle| idle.as_secs()),
+        }
+    }
+}
+
+/// Every job `filter` matches, walked in pages
+pub async fn all_jobs<D: DatabaseServiceTrait>(db_service: &D, filter: &JobFilter) -> Result<Vec<Job>> {
+    let mut jobs = Vec::new();
+    let mut after = None;
+    loop {
+        let page = db_service.list_jobs(filter, after, PAGE_SIZE).await?;
+        let done = page.len() < PAGE_SIZE;
+        after = page.last().map(|job| job.id);
+        jobs.extend(page);
+        if done {
+            return Ok(jobs);
+        }
+    }
+}
+
+/// Summaries of the jobs in `status`
+pub fn jobs_in(jobs: &[Job], status: JobStatus) -> Vec<JobSummary> {
+    let status = status.to_string();
+    jobs.iter().filter(|job| job.status == status).map(JobSummary::from).collect()
+}
+
+/// Queued and processing jobs that have gone `older_than_secs` without changing,
+/// longest idle first
+pub fn stuck_jobs(jobs: &[Job], older_than_secs: u64) -> StuckJobs {
+    let waiting = [JobStatus::Queued.to_string(), JobStatus::Processing.to_string()];
+    let mut stuck: Vec<JobSummary> = jobs
+        .iter()
+        .filter(|job| waiting.contains(&job.status))
+        .map(JobSummary::from)
+        .filter(|job| job.idle_secs.is_some_and(|idle| idle >= older_than_secs))
+        .collect();
+    stuck.sort_by_key(|job| std::cmp::Reverse(job.idle_secs));
+    StuckJobs {
+        older_than_secs,
+        jobs: stuck,
+    }
+}
+
+/// Jobs, storage and today's AI spend of every tenant with a job, with its quotas
+pub async fn tenant_usage<S, D, R>(processor: &DataProcessor<S, D, R>, jobs: &[Job]) -> Result<Vec<TenantUsage>>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let quotas = processor.quotas();
+    let storage = storage_by_tenant(&processor.get_s3_service().list_objects("").await?);
+    let midnight = Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+
+    let mut tenants: BTreeMap<&str, BTreeMap<String, u64>> = BTreeMap::new();
+    for job in jobs {
+        *tenants.entry(&job.tenant_id).or_default().entry(job.status.clone()).or_insert(0) += 1;
+    }
+
+    let mut usage = Vec::with_capacity(tenants.len());
+    for (tenant_id, job_counts) in tenants {
+        usage.push(TenantUsage {
+            tenant_id: tenant_id.to_string(),
+            jobs: job_counts,
+            jobs_today: processor.get_db_service().count_jobs_since(tenant_id, midnight).await?,
+            max_jobs_per_day: quotas.max_jobs_per_day,
+            storage_bytes: storage.get(&tenant_label(&tenant_prefix(tenant_id))).map_or(0, |used| used.bytes),
+            max_storage_bytes: quotas.max_storage_bytes,
+            ai_tokens_today: processor.usage_ledger().tenant_tokens_today(tenant_id).await?,
+            max_ai_tokens_per_day: quotas.max_ai_tokens_per_day,
+        });
+    }
+    Ok(usage)
+}
+
+/// Every stored object, totalled, per tenant prefix and per area: the first key
+/// segment after the tenant prefix, such as `uploads` or `processed`
+pub async fn storage_report<S: S3ServiceTrait>(storage: &S) -> Result<StorageReport> {
+    let objects = storage.list_objects("").await?;
+    let mut total = StorageUsage::default();
+    let mut areas: BTreeMap<String, StorageUsage> = BTreeMap::new();
+    for object in &objects {
+        add(&mut total, object);
+        let area = object_area(&object.key);
+        add(areas.entry(area.to_string()).or_default(), object);
+    }
+    Ok(StorageReport {
+        backend: storage.backend_name().to_string(),
+        total,
+        tenants: storage_by_tenant(&objects),
+        areas,
+    })
+}
+
+/// Mark a queued or processing job failed. The job's own status decides: one that
+/// already finished is refused with `InvalidTransition`.
+pub async fn force_fail<S, D, R>(processor: &DataProcessor<S, D, R>, job: &Job) -> Result<()>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let status = JobStatus::from_str(&job.status)?;
+    if !matches!(status, JobStatus::Queued | JobStatus::Processing) {
+        bail!(InvalidTransition { job_id: job.id, from: job.status.clone(), to: JobStatus::Failed });
+    }
+    processor.fail_job(job.id, "Failed by an administrator").await?;
+    log::warn!("🛠️ [Job-{}] Marked failed by an administrator (was {})", job.id, status);
+    Ok(())
+}
+
+/// Cancel a queued or processing job for good; one that already finished is
+/// refused with `InvalidTransition`. The worker skips a cancelled job it has not
+/// started, and one it is processing stops when it tries to complete it.
+pub async fn cancel<S, D, R>(processor: &DataProcessor<S, D, R>, job: &Job) -> Result<()>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let status = JobStatus::from_str(&job.status)?;
+    if !matches!(status, JobStatus::Queued | JobStatus::Processing) {
+        bail!(InvalidTransition { job_id: job.id, from: job.status.clone(), to: JobStatus::Cancelled });
+    }
+    processor.get_db_service().update_job_status(job.id, JobStatus::Cancelled).await?;
+    processor.publish_status(job.id, JobStatus::Cancelled, None).await;
+    log::warn!("🛠️ [Job-{}] Cancelled by an administrator (was {})", job.id, status);
+    Ok(())
+}
+
+/// Send a queued, processing or failed job to the worker again. A processing job
+/// is failed first so it can be queued; the worker skips it while the stuck
+/// attempt still holds the job's lock.
+pub async fn retry<S, D, R>(processor: &DataProcessor<S, D, R>, job: &Job, queue: &JobQueue) -> Result<()>
+where
+    S: S3ServiceTrait + Clone + std::fmt::Debug,
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    R: RedisServiceTrait + Clone + std::fmt::Debug,
+{
+    let status = JobStatus::from_str(&job.status)?;
+    let slot = queue.reserve()?;
+    match status {
+        JobStatus::Queued => {}
+        JobStatus::Processing | JobStatus::Failed => {
+            if status == JobStatus::Processing {
+                processor.fail_job(job.id, "Retried by an administrator").await?;
+            }
+            processor.get_db_service().update_job_status(job.id, JobStatus::Queued).await?;
+            processor.publish_status(job.id, JobStatus::Queued, None).await;
+        }
+        _ => bail!(InvalidTransition { job_id: job.id, from: job.status.clone(), to: JobStatus::Queued }),
+    }
+    slot.send(job.id);
+    log::warn!("🛠️ [Job-{}] Queued again by an administrator (was {})", job.id, status);
+    Ok(())
+}
+
+fn add(usage: &mut StorageUsage, object: &ObjectInfo) {
+    usage.objects += 1;
+    usage.bytes += object.size;
+}
+
+/// Usage per tenant, keyed as `tenant_label` names them
+fn storage_by_tenant(objects: &[ObjectInfo]) -> BTreeMap<String, StorageUsage> {
+    let mut tenants: BTreeMap<String, StorageUsage> = BTreeMap::new();
+    for object in objects {
+        add(tenants.entry(tenant_label(&object.key)).or_default(), object);
+    }
+    tenants
+}
+
+/// The tenant a key is stored for: its (percent-encoded) segment under
+/// `tenants/`, or the default tenant for unprefixed keys
+fn tenant_label(key: &str) -> String {
+    key.strip_prefix(TENANT_PREFIX)
+        .and_then(|rest| rest.split('/').next())
+        .filter(|tenant| !tenant.is_empty())
+        .unwrap_or(DEFAULT_TENANT)
+        .to_string()
+}
+
+/// First key segment after the tenant prefix
+fn object_area(key: &str) -> &str {
+    let key = match key.strip_prefix(TENANT_PREFIX) {
+        Some(rest) => rest.split_once('/').map_or(rest, |(_, rest)| rest),
+        None => key,
+    };
+    key.split_once('/').map_or("(root)", |(area, _)| area)
+}
@@ -1,51 +1,325 @@
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use anyhow::{Result, anyhow};
-use log::{info, error, debug};
-use reqwest::Client;
+use log::{info, error, warn};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use tokio::sync::{Semaphore, SemaphorePermit};
 use serde_json::{json, Value};
 
+use crate::models::conversation::ConversationTurn;
 use crate::models::response::AISummary;
+use crate::models::usage::TokenUsage;
 use crate::config::Config;
+use crate::models::llm::{ModelSettings, RetrySettings};
+use crate::services::{LlmProviderTrait, RedisServiceTrait};
+use crate::services::llm::{provider_from_config, ChatRequest, ToolSpec, TransientLlmError};
+use crate::services::query_translator::structured_query_schema;
+use crate::services::quota::AiReservation;
 
 /// Service for AI-powered data analysis and insights
 #[derive(Clone, Debug)]
 pub struct AIService {
-    client: Client,
-    api_key: Option<String>,
+    provider: Arc<dyn LlmProviderTrait>,
+    /// Settings for dataset summaries and answer narration
+    summary: ModelSettings,
+    /// Settings for query translation, conversation summaries and suggested questions
+    translation: ModelSettings,
+    retry: RetrySettings,
+    circuit: Arc<Mutex<CircuitState>>,
+    limiter: Arc<CallLimiter>,
+    /// Replies to earlier requests, so identical requests are not paid for twice
+    cache: Option<Arc<dyn RedisServiceTrait>>,
+    cache_ttl_secs: u64,
+    /// Tenant the calls are made for; cached replies are only shared within it
+    tenant_id: Option<String>,
+    /// Tokens of the tenant's AI quota held for this service's calls, charged what they spend
+    reservation: Option<Arc<AiReservation>>,
+}
+
+/// Caps the provider calls in flight and started per minute, shared by every
+/// clone of the service so bursts of conversations stay under the provider's
+/// organization rate limits
+#[derive(Debug)]
+struct CallLimiter {
+    in_flight: Semaphore,
+    per_minute: usize,
+    /// When the calls of the last minute started, oldest first
+    started: Mutex<VecDeque<Instant>>,
+}
+
+impl CallLimiter {
+    fn new(max_concurrent: usize, per_minute: usize) -> Self {
+        Self {
+            in_flight: Semaphore::new(max_concurrent),
+            per_minute,
+            started: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Wait for a free slot under both limits; the call holds it until the permit drops
+    async fn acquire(&self) -> Result<SemaphorePermit<'_>> {
+        let permit = self.in_flight.acquire().await?;
+        if self.per_minute == 0 {
+            return Ok(permit);
+        }
+        loop {
+            let wait = {
+                let mut started = self.started.lock().unwrap();
+                let now = Instant::now();
+                while started.front().is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(60)) {
+                    started.pop_front();
+                }
+                if started.len() < self.per_minute {
+                    started.push_back(now);
+                    return Ok(permit);
+                }
+                // Room opens when the oldest call of the window turns a minute old
+                started[0] + Duration::from_secs(60) - now
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Calls in a row that failed after their retries, and once there are enough of
+/// them, until when further calls fail fast
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// A call is trying the provider after the cooldown; others fail fast until it ends
+    trial_in_flight: bool,
+}
+
+/// How long calls turned away while the trial call runs are asked to wait
+const TRIAL_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// The trial call let through once the cooldown passed. A trial that ends without
+/// closing or reopening the circuit, e.g. on a bad request or because its caller
+/// gave up waiting, lets the next call try instead.
+struct CircuitTrial {
+    circuit: Arc<Mutex<CircuitState>>,
+}
+
+impl Drop for CircuitTrial {
+    fn drop(&mut self) {
+        if let Ok(mut circuit) = self.circuit.lock() {
+            circuit.trial_in_flight = false;
+        }
+    }
 }
 
 impl AIService {
-    /// Create a new AIService using Config
+    /// Create a new AIService for the LLM provider selected in Config; `None` when
+    /// the provider has no API key configured
     pub fn new(config: &Config) -> Result<Option<Self>> {
-        // Check if the OpenAI API key is set in the config
-        match &config.open_ai_key {
-            Some(api_key) if !api_key.trim().is_empty() => {
-                info!("AIService initialized with OpenAI API key");
+        match provider_from_config(config)? {
+            Some(provider) => {
+                info!("AIService initialized with the {} provider, model {}", provider.backend_name(), provider.model());
                 Ok(Some(Self {
-                    client: Client::new(),
-                    api_key: Some(api_key.clone()),
+                    provider,
+                    summary: config.ai_summary.clone(),
+                    translation: config.ai_translation.clone(),
+                    retry: config.ai_retry,
+                    circuit: Arc::new(Mutex::new(CircuitState::default())),
+                    limiter: Arc::new(CallLimiter::new(config.ai_max_concurrent_calls, config.ai_max_calls_per_minute)),
+                    cache: None,
+                    cache_ttl_secs: config.ai_cache_ttl_secs,
+                    tenant_id: None,
+                    reservation: None,
                 }))
             },
-            _ => {
-                info!("OpenAI API key not set in config, AIService not initialized");
+            None => {
+                info!("No API key set for the {} LLM provider, AIService not initialized", config.llm_provider);
                 Ok(None)
             }
         }
     }
-    
-    /// Generate a data summary from insights JSON
-    pub async fn generate_data_summary(&self, insights: &Value) -> Result<AISummary> {
-        // Check if API key is available
-        let api_key = match &self.api_key {
-            Some(key) if !key.trim().is_empty() => key,
-            _ => {
-                error!("OpenAI API key is not available. Cannot generate AI summary.");
-                return Err(anyhow!("OpenAI API key is not available"));
+
+    /// Answer repeated requests from `cache` instead of the provider
+    pub fn with_cache(mut self, cache: Arc<dyn RedisServiceTrait>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// The same service making calls for `tenant_id`, whose cached replies no
+    /// other tenant is answered from, charging what its calls spend to
+    /// `reservation`. Limits and the circuit stay shared.
+    pub fn for_tenant(&self, tenant_id: &str, reservation: Option<AiReservation>) -> Self {
+        Self {
+            tenant_id: Some(tenant_id.to_string()),
+            reservation: reservation.map(Arc::new),
+            ..self.clone()
+        }
+    }
+
+    /// Short name of the LLM provider
+    pub fn provider_name(&self) -> &'static str {
+        self.provider.backend_name()
+    }
+
+    /// Model dataset summaries and answers are written with
+    pub fn summary_model(&self) -> &str {
+        self.summary.model.as_deref().unwrap_or(self.provider.model())
+    }
+
+    /// Model queries are translated with
+    pub fn translation_model(&self) -> &str {
+        self.translation.model.as_deref().unwrap_or(self.provider.model())
+    }
+
+    /// Send a request to the provider, or answer it from the cache when the same
+    /// request was sent recently. Cached replies consume no tokens.
+    async fn chat(&self, request: &ChatRequest) -> Result<(String, TokenUsage)> {
+        let Some(cache) = self.cache.as_ref().filter(|_| self.cache_ttl_secs > 0) else {
+            return self.send_with_retries(request).await;
+        };
+        let key = self.cache_key(request);
+        match cache.get_ai_response(&key).await {
+            Ok(Some(content)) => {
+                info!("Answering {} request from the AI response cache", self.provider_name());
+                return Ok((content, TokenUsage::default()));
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read the AI response cache: {}", e),
+        }
+
+        let (content, usage) = self.send_with_retries(request).await?;
+        if let Err(e) = cache.cache_ai_response(&key, &content, self.cache_ttl_secs).await {
+            warn!("Failed to cache AI response: {}", e);
+        }
+        Ok((content, usage))
+    }
+
+    /// SHA-256 of the tenant and everything that shapes the reply: provider, model, sampling, the
+    /// function asked for and both prompts, which carry the insights, query, schema and recent history
+    fn cache_key(&self, request: &ChatRequest) -> String {
+        let mut hasher = Sha256::new();
+        for part in [
+            self.tenant_id.as_deref().unwrap_or_default(),
+            self.provider_name(),
+            request.settings.model.as_deref().unwrap_or(self.provider.model()),
+            &format!("{:?}", request.settings.temperature),
+            &format!("{:?}", request.settings.max_tokens),
+            &request.json_response.to_string(),
+            &request.tool.as_ref().map(|tool| format!("{}{}", tool.name, tool.parameters)).unwrap_or_default(),
+            &request.system,
+            &request.user,
+        ] {
+            hasher.update(part.as_bytes());
+            // Separate the parts so moving text between them changes the key
+            hasher.update([0]);
+        }
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Send a request to the provider, retrying rate limits, server errors and
+    /// timeouts with jittered exponential backoff. Once enough calls in a row have
+    /// failed, the circuit opens and calls fail fast until the cooldown passes; the
+    /// first call after it is a trial that closes or reopens the circuit, and the
+    /// others keep failing fast while it runs. Every attempt waits for a slot under
+    /// the concurrency and per-minute caps.
+    async fn send_with_retries(&self, request: &ChatRequest) -> Result<(String, TokenUsage)> {
+        let _trial = match self.admit() {
+            Ok(trial) => trial,
+            Err(remaining) => {
+                let message = format!(
+                    "{} API calls suspended after repeated failures; retrying in {} seconds",
+                    self.provider_name(),
+                    remaining.as_secs().max(1)
+                );
+                return Err(TransientLlmError { message, retry_after: Some(remaining), timed_out: false }.into());
             }
         };
-        
-        info!("Using OpenAI API key (first 3 chars): {}...", api_key.chars().take(3).collect::<String>());
 
+        let mut attempt = 0;
+        loop {
+            let permit = self.limiter.acquire().await?;
+            let reply = self.provider.chat(request).await;
+            drop(permit);
+            let error = match reply {
+                Ok(reply) => {
+                    *self.circuit.lock().unwrap() = CircuitState::default();
+                    if let Some(reservation) = &self.reservation {
+                        reservation.spend(reply.1.total_tokens());
+                    }
+                    return Ok(reply);
+                }
+                Err(e) => e,
+            };
+            // Bad requests and auth errors say nothing about the provider's health
+            let Some(transient) = error.downcast_ref::<TransientLlmError>() else {
+                return Err(error);
+            };
+            match self.retry_delay(attempt, transient.retry_after) {
+                Some(delay) if attempt < self.retry.max_retries => {
+                    attempt += 1;
+                    warn!(
+                        "{}; retry {} of {} in {} ms",
+                        transient, attempt, self.retry.max_retries, delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                _ => {
+                    self.record_failure();
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Whether a call may reach the provider, failing with the time left while the
+    /// circuit is open. Once the cooldown passed, the first call is let through as
+    /// the trial and the time to wait for it is given to the others.
+    fn admit(&self) -> std::result::Result<Option<CircuitTrial>, Duration> {
+        let mut circuit = self.circuit.lock().unwrap();
+        let Some(until) = circuit.open_until else {
+            return Ok(None);
+        };
+        if let Some(remaining) = until.checked_duration_since(Instant::now()) {
+            return Err(remaining);
+        }
+        if circuit.trial_in_flight {
+            return Err(TRIAL_RETRY_AFTER);
+        }
+        circuit.trial_in_flight = true;
+        Ok(Some(CircuitTrial { circuit: self.circuit.clone() }))
+    }
+
+    fn record_failure(&self) {
+        let mut circuit = self.circuit.lock().unwrap();
+        circuit.consecutive_failures += 1;
+        if circuit.consecutive_failures >= self.retry.circuit_failure_threshold {
+            let cooldown = Duration::from_secs(self.retry.circuit_cooldown_secs);
+            warn!(
+                "{} API failed {} calls in a row; suspending calls for {} seconds",
+                self.provider_name(), circuit.consecutive_failures, cooldown.as_secs()
+            );
+            circuit.open_until = Some(Instant::now() + cooldown);
+            circuit.trial_in_flight = false;
+        }
+    }
+
+    /// Wait before the next attempt: the provider's Retry-After when it sent one,
+    /// otherwise the doubled base delay with jitter. `None` when the provider asks
+    /// for longer than the longest wait allowed.
+    fn retry_delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        let max_delay = Duration::from_millis(self.retry.max_delay_ms);
+        if let Some(retry_after) = retry_after {
+            return (retry_after <= max_delay).then_some(retry_after);
+        }
+        let backoff = Duration::from_millis(self.retry.base_delay_ms)
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(max_delay);
+        // Spread concurrent retries over the upper half of the window
+        let millis = backoff.as_millis() as u64;
+        Some(Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis)))
+    }
+
+    /// Generate a data summary from insights JSON, along with the tokens it consumed
+    pub async fn generate_data_summary(&self, insights: &Value) -> Result<(AISummary, TokenUsage)> {
         // Log a sample of the insights for debugging
         let insights_sample = insights.to_string()
             .chars()
@@ -64,6 +338,10 @@ Based on this data, please provide:
 3. 3-5 actionable business recommendations and suggestions for improvement, with a brief rationale for each
 4. 3-5 recommended visualization types with titles, descriptions, and relevant columns
 
+Columns listed under "identifier_columns", "constant_columns" or "high_cardinality_columns" carry no analytical signal: do not base insights, correlations or visualization recommendations on them.
+
+When "extreme_records" is present, cite those concrete rows as examples in your insights rather than only quoting aggregates.
+
 IMPORTANT: Do NOT return empty arrays or blank fields. If you cannot find any insights or recommendations, explain why in the summary and provide at least one general suggestion. Your response must always contain non-empty, meaningful content for each field.
 
 Format your response as a JSON object with the following structure:
@@ -89,94 +367,21 @@ Format your response as a JSON object with the following structure:
 }}
 "#, insights);
 
-        info!("Sending request to OpenAI API");
-        
-        // Create a client with a 30-second timeout
-        let client = match Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build() {
-                Ok(client) => client,
-                Err(e) => {
-                    error!("Failed to build HTTP client: {}", e);
-                    return Err(anyhow!("Failed to build HTTP client: {}", e));
-                }
-            };
-            
-        let request_body = json!({
-            "model": "gpt-4o",
-            "messages": [
-                {
-                    "role": "system",
-                    "content": "You are a data analysis assistant that helps interpret data insights and recommend visualizations. Provide concise, business-focused analysis."
-                },
-                {
-                    "role": "user",
-                    "content": prompt
-                }
-            ],
-            "response_format": { "type": "json_object" }
-        });
-        
-        info!("Sending request to OpenAI API with model: gpt-4o");
-        
-        // Send the request with detailed error handling
-        let response = match client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await {
-                Ok(resp) => resp,
-                Err(e) => {
-                    error!("Failed to send request to OpenAI API: {}", e);
-                    if e.is_timeout() {
-                        error!("Request timed out after 30 seconds");
-                        return Err(anyhow!("OpenAI API request timed out after 30 seconds"));
-                    } else if e.is_connect() {
-                        error!("Connection error: {}", e);
-                        return Err(anyhow!("Failed to connect to OpenAI API: {}", e));
-                    } else {
-                        return Err(anyhow!("Failed to send request to OpenAI API: {}", e));
-                    }
-                }
-            };
-
-        let status = response.status();
-        info!("OpenAI API response status: {}", status);
-        
-        if !status.is_success() {
-            let error_text = response.text().await
-                .unwrap_or_else(|_| "Could not read error response".to_string());
-            error!("OpenAI API error: Status {}, Details: {}", status, error_text);
-            return Err(anyhow!("OpenAI API error: Status {}, Details: {}", status, error_text));
-        }
-        
-        // Parse the response with detailed error handling
-        let response_json: Value = match response.json().await {
-            Ok(json) => json,
-            Err(e) => {
-                error!("Failed to parse OpenAI API response as JSON: {}", e);
-                return Err(anyhow!("Failed to parse OpenAI API response: {}", e));
-            }
-        };
-            
-        debug!("OpenAI API response received");
-        
-        // Extract the content from the response
-        let content = match response_json["choices"][0]["message"]["content"].as_str() {
-            Some(content) => content,
-            None => {
-                error!("Could not extract content from OpenAI response: {:?}", response_json);
-                return Err(anyhow!("Could not extract content from OpenAI response"));
-            }
-        };
+        info!("Sending summary request to the {} provider with model: {}", self.provider.backend_name(), self.summary_model());
+        let (content, usage) = self.chat(&ChatRequest {
+            system: "You are a data analysis assistant that helps interpret data insights and recommend visualizations. Provide concise, business-focused analysis.".to_string(),
+            user: prompt,
+            json_response: true,
+            tool: None,
+            settings: self.summary.clone(),
+        }).await?;
+        let content = content.as_str();
 
-        info!("Parsing AI summary from OpenAI response");
+        info!("Parsing AI summary from the model response");
         let ai_summary: AISummary = match serde_json::from_str(content) {
             Ok(summary) => summary,
             Err(e) => {
-                error!("Failed to parse AI summary from OpenAI response: {}", e);
+                error!("Failed to parse AI summary from the model response: {}", e);
                 error!("Raw AI response content: {}", content);
                 // Try to extract JSON substring from the content
                 if let Some(start) = content.find('{') {
@@ -185,7 +390,7 @@ Format your response as a JSON object with the following structure:
                         match serde_json::from_str::<AISummary>(json_str) {
                             Ok(summary) => {
                                 info!("Successfully parsed AISummary from extracted JSON substring");
-                                return Ok(summary);
+                                return Ok((summary, usage));
                             },
                             Err(e2) => {
                                 error!("Failed to parse extracted JSON substring as AISummary: {}", e2);
@@ -195,55 +400,60 @@ Format your response as a JSON object with the following structure:
                     }
                 }
                 error!("Raw content received: {}", content);
-                return Err(anyhow!("Failed to parse AI summary from OpenAI response: {}", e));
+                return Err(anyhow!("Failed to parse AI summary from the model response: {}", e));
             }
         };
 
         info!("Successfully generated AI summary");
-        Ok(ai_summary)
+        Ok((ai_summary, usage))
     }
     
-    /// Generate a structured query from a natural language query
-    pub async fn generate_query_translation(&self, prompt_data: &Value) -> Result<Value> {
-        // Check if API key is available
-        let api_key = match &self.api_key {
-            Some(key) if !key.trim().is_empty() => key,
-            _ => {
-                error!("OpenAI API key is not available. Cannot translate query.");
-                return Err(anyhow!("OpenAI API key is not available"));
-            }
-        };
-        
+    /// Answer a user's question in prose from the result of the query that answered
+    /// it, along with the tokens it consumed
+    pub async fn narrate_result(&self, narration: &Value) -> Result<(String, TokenUsage)> {
+        let system_prompt = "You answer a user's question about their dataset from the result of the query that answered it. \
+Every number you state must appear in `result`, or be `result_row_count`; copy figures as they appear (you may round them or add thousands separators, \
+and write fractions as percentages), and never estimate, extrapolate or invent one. Do not compute new totals, averages, differences or ratios from the rows. \
+When `result_truncated` is true, `result` holds only the first rows, so do not describe the rest. If the result does not answer the question, say so. \
+Reply in `response_language` with two or three plain sentences and no JSON or Markdown.";
+        let (content, usage) = self.chat(&ChatRequest {
+            system: system_prompt.to_string(),
+            user: narration.to_string(),
+            json_response: false,
+            tool: None,
+            settings: self.summary.clone(),
+        }).await?;
+        Ok((content.trim().to_string(), usage))
+    }
+
+    /// Generate a structured query from a natural language query, along with the tokens it consumed
+    pub async fn generate_query_translation(&self, prompt_data: &Value) -> Result<(Value, TokenUsage)> {
         info!("Translating natural language query to structured query");
         
-        // Create a client with a 15-second timeout
-        let client = match Client::builder()
-            .timeout(Duration::from_secs(15))
-            .build() {
-                Ok(client) => client,
-                Err(e) => {
-                    error!("Failed to build HTTP client: {}", e);
-                    return Err(anyhow!("Failed to build HTTP client: {}", e));
-                }
-            };
-            
         // Construct the system prompt
         let system_prompt = r#"You are a data query translator that converts natural language queries into structured queries for data analysis. 
-You analyze the user's query in the context of their dataset and conversation history, then return a structured JSON representation of the query that can be executed by a data processing system.
+You analyze the user's query in the context of their dataset and conversation history (older turns are condensed into conversation_summary), then return a structured JSON representation of the query that can be executed by a data processing system.
 
-Your response must be a valid JSON object with the following structure:
-{
-  "intent": "Aggregate|Filter|Sort|Describe|Visualize",
-  "columns": ["column1", "column2", ...],
-  "operations": [
+Answer by calling the structured_query function. Its intent is one of Aggregate, Filter, Sort, Describe, Visualize or Pivot, columns lists the dataset columns involved, and each operation takes one of these shapes:
     {"type": "Mean", "column": "column_name"},
+    {"type": "Sum", "column": "column_name"},
+    {"type": "Count", "column": "column_name"},
+    {"type": "Median|Std|Min|Max|DistinctCount", "column": "column_name"},
+    {"type": "Quantile", "column": "column_name", "q": 0.9},
     {"type": "GroupBy", "column": "column_name"},
+    {"type": "SortBy", "column": "column_name", "ascending": false},
+    {"type": "SortBy", "by": [{"column": "first_key", "direction": "asc"}, {"column": "second_key", "direction": "desc"}], "nulls": "first|last"},
+    {"type": "TopN", "column": "column_name", "n": 5, "ascending": false},
+    {"type": "Limit", "n": 10},
+    {"type": "DateTrunc", "column": "date_column", "unit": "day|week|month|quarter|year"},
+    {"type": "Pivot", "index": "row_column", "pivot": "column_to_spread", "column": "value_column", "aggregation": "sum|mean|count|min|max|median|first"},
     {"type": "Filter", "column": "column_name", "operator": ">", "value": "10"},
-    ...
-  ]
-}
+    {"type": "Having", "column": "column_name", "aggregation": "sum", "operator": ">", "value": "1000000"},
+    {"type": "RollingMean|RollingSum", "column": "column_name", "window": "7|7d|2w|1mo", "date_column": "date_column"},
+    {"type": "CumSum", "column": "column_name", "date_column": "date_column"},
+    {"type": "Lag|Diff", "column": "column_name", "n": 1, "date_column": "date_column"}
 
-Be precise and only include columns that exist in the dataset. If the query is ambiguous, make a reasonable guess based on the dataset schema and conversation history."#;
+Filter operators are =, !=, >, <, >=, <=, contains, starts_with, ends_with, regex (all text matching is case-insensitive) and in (value is a comma-separated list or a JSON array); filters on date columns take ISO dates such as "2024-01-01"; numeric values may keep currency symbols, thousands separators, k/M/B suffixes or a % sign (15% compares as 0.15). Filters and DateTrunc apply before any aggregation, and Mean/Sum/Count/Median/Quantile/Std/Min/Max/DistinctCount are computed per GroupBy group; Having filters the aggregated groups (aggregation is one of mean, sum, count, median, std, min, max, distinct_count, or omit it and name a result column such as "p90_column_name"); use Quantile with q between 0 and 1 for percentiles (the 90th percentile is q 0.9). RollingMean, RollingSum, CumSum, Lag and Diff add a column to each row (e.g. "rolling_mean_7d_signups") after filtering, or after grouping in Aggregate queries, where they run over the aggregated rows; a window is a row count or a time span (h, d, w, mo, q, y) and rows are ordered by date_column. When previous_query is present and the current query refers back to it ("filter that by region", "now by month", "show the median instead", "only for 2024"), return previous_query with just that change applied (add or replace filters, change the aggregation, grouping or ordering) and keep everything else; translate unrelated questions from scratch. Be precise and only include columns that exist in the dataset. If the query is ambiguous, make a reasonable guess based on the dataset schema and conversation history."#;
         
         // Convert prompt_data to a JSON string for the API
         let prompt_data_str = serde_json::to_string(&prompt_data).map_err(|e| {
@@ -251,87 +461,179 @@ Be precise and only include columns that exist in the dataset. If the query is a
             anyhow!("Failed to serialize prompt_data for AI query")
         })?;
 
-        // Create the request body
-        let request_body = json!({
-            "model": "gpt-4o",
-            "messages": [
-                {
-                    "role": "system",
-                    "content": system_prompt
-                },
-                {
-                    "role": "user",
-                    "content": prompt_data_str // Use the stringified version here
-                }
-            ],
-            "response_format": { "type": "json_object" }
-        });
-        
-        info!("Sending query translation request to OpenAI API");
-        
-        // Send the request with detailed error handling
-        let response = match client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await {
-                Ok(resp) => resp,
-                Err(e) => {
-                    error!("Failed to send request to OpenAI API: {}", e);
-                    if e.is_timeout() {
-                        error!("Request timed out after 15 seconds");
-                        return Err(anyhow!("OpenAI API request timed out after 15 seconds"));
-                    } else if e.is_connect() {
-                        error!("Connection error: {}", e);
-                        return Err(anyhow!("Failed to connect to OpenAI API: {}", e));
-                    } else {
-                        return Err(anyhow!("Failed to send request to OpenAI API: {}", e));
-                    }
-                }
-            };
-
-        let status = response.status();
-        info!("OpenAI API response status: {}", status);
-        
-        if !status.is_success() {
-            let error_text = response.text().await
-                .unwrap_or_else(|_| "Could not read error response".to_string());
-            error!("OpenAI API error: Status {}, Details: {}", status, error_text);
-            return Err(anyhow!("OpenAI API error: Status {}, Details: {}", status, error_text));
-        }
-        
-        // Parse the response with detailed error handling
-        let response_json: Value = match response.json().await {
-            Ok(json) => json,
-            Err(e) => {
-                error!("Failed to parse OpenAI API response as JSON: {}", e);
-                return Err(anyhow!("Failed to parse OpenAI API response: {}", e));
-            }
-        };
-            
-        debug!("OpenAI API response received");
-        
-        // Extract the content from the response
-        let content = match response_json["choices"][0]["message"]["content"].as_str() {
-            Some(content) => content,
-            None => {
-                error!("Could not extract content from OpenAI response: {:?}", response_json);
-                return Err(anyhow!("Could not extract content from OpenAI response"));
-            }
-        };
+        info!("Sending query translation request to the {} provider", self.provider.backend_name());
+        let (content, usage) = self.chat(&ChatRequest {
+            system: system_prompt.to_string(),
+            user: prompt_data_str,
+            json_response: true,
+            tool: Some(ToolSpec {
+                name: "structured_query",
+                description: "Run a structured query against the user's dataset",
+                parameters: structured_query_schema(),
+            }),
+            settings: self.translation.clone(),
+        }).await?;
+        let content = content.as_str();
 
         info!("Successfully translated query");
         
         // Parse the content as JSON
         match serde_json::from_str::<Value>(content) {
-            Ok(parsed) => Ok(parsed),
+            Ok(parsed) => Ok((parsed, usage)),
             Err(e) => {
-                error!("Failed to parse query translation from OpenAI response: {}", e);
+                error!("Failed to parse query translation from the model response: {}", e);
                 error!("Raw content received: {}", content);
-                return Err(anyhow!("Failed to parse query translation: {}", e));
+                Err(anyhow!("Failed to parse query translation: {}", e))
             }
         }
     }
+
+    /// Condense earlier conversation turns, folded into any previous summary, into a
+    /// short summary that stands in for them in later translation prompts
+    pub async fn summarize_conversation(&self, previous_summary: Option<&str>, turns: &[ConversationTurn]) -> Result<(String, TokenUsage)> {
+        info!("Summarizing {} conversation turns", turns.len());
+        let system_prompt = "You summarize a conversation between a user and a data analysis assistant about one dataset. \
+Keep the columns, filters, groupings, time ranges and figures the user asked about or was told, and what they seem to be investigating, \
+so later questions that refer back (\"the same for last year\", \"only those regions\") can be understood. \
+Reply with the summary only, in at most 150 words.";
+        let transcript = json!({
+            "previous_summary": previous_summary,
+            "turns": turns.iter().map(|turn| json!({"query": turn.query, "response": turn.response})).collect::<Vec<_>>(),
+        });
+        self.chat(&ChatRequest {
+            system: system_prompt.to_string(),
+            user: transcript.to_string(),
+            json_response: false,
+            tool: None,
+            settings: self.translation.clone(),
+        }).await
+    }
+
+    /// Suggest questions a user could ask about a dataset, from its schema and a
+    /// digest of its insights
+    pub async fn suggest_questions(&self, dataset: &Value) -> Result<(Vec<String>, TokenUsage)> {
+        info!("Generating suggested questions");
+        let system_prompt = "You help people start exploring a dataset by suggesting questions they can ask a data analysis assistant. \
+Suggest 5 to 8 short, specific questions that can be answered from the dataset alone: averages, totals and counts by a category, \
+top and bottom rankings, trends over a date column, filters on notable values, and the relationships and anomalies the insights point to. \
+Use the column names as given and write the questions in the response_language. \
+Reply with a JSON object: {\"questions\": [\"...\"]}.";
+        let (content, usage) = self.chat(&ChatRequest {
+            system: system_prompt.to_string(),
+            user: dataset.to_string(),
+            json_response: true,
+            tool: None,
+            settings: self.translation.clone(),
+        }).await?;
+        let parsed: Value = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Failed to parse suggested questions: {}", e))?;
+        let questions = parsed["questions"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Suggested questions response has no questions"))?
+            .iter()
+            .filter_map(|q| q.as_str())
+            .map(|q| q.trim().to_string())
+            .filter(|q| !q.is_empty())
+            .collect();
+        Ok((questions, usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Fails every call while `down`, otherwise answers after `delay`
+    #[derive(Debug, Default)]
+    struct FlakyProvider {
+        down: AtomicBool,
+        delay: Duration,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProviderTrait for FlakyProvider {
+        async fn chat(&self, _request: &ChatRequest) -> Result<(String, TokenUsage)> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            if self.down.load(Ordering::SeqCst) {
+                return Err(TransientLlmError { message: "down".to_string(), retry_after: None, timed_out: false }.into());
+            }
+            Ok(("ok".to_string(), TokenUsage::default()))
+        }
+
+        fn backend_name(&self) -> &'static str {
+            "flaky"
+        }
+
+        fn model(&self) -> &str {
+            "flaky"
+        }
+    }
+
+    /// A service whose circuit opens on the first failed call and whose cooldown
+    /// has already passed when the next call comes
+    fn service(provider: Arc<FlakyProvider>) -> AIService {
+        AIService {
+            provider,
+            summary: ModelSettings::default(),
+            translation: ModelSettings::default(),
+            retry: RetrySettings { max_retries: 0, circuit_failure_threshold: 1, circuit_cooldown_secs: 0, ..Default::default() },
+            circuit: Arc::new(Mutex::new(CircuitState::default())),
+            limiter: Arc::new(CallLimiter::new(10, 0)),
+            cache: None,
+            cache_ttl_secs: 0,
+            tenant_id: None,
+            reservation: None,
+        }
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            system: String::new(),
+            user: String::new(),
+            json_response: false,
+            tool: None,
+            settings: ModelSettings::default(),
+        }
+    }
+
+    fn failed_fast(result: Result<(String, TokenUsage)>) -> bool {
+        result.err().is_some_and(|e| e.to_string().contains("suspended"))
+    }
+
+    #[tokio::test]
+    async fn only_one_trial_call_runs_after_the_cooldown() {
+        let provider = Arc::new(FlakyProvider { delay: Duration::from_millis(100), ..Default::default() });
+        provider.down.store(true, Ordering::SeqCst);
+        let ai = service(provider.clone());
+        assert!(ai.send_with_retries(&request()).await.is_err());
+
+        provider.down.store(false, Ordering::SeqCst);
+        let trial = tokio::spawn({
+            let ai = ai.clone();
+            async move { ai.send_with_retries(&request()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(failed_fast(ai.send_with_retries(&request()).await));
+        assert!(trial.await.unwrap().is_ok());
+
+        // The trial closed the circuit
+        assert!(ai.send_with_retries(&request()).await.is_ok());
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn an_abandoned_trial_lets_the_next_call_try() {
+        let provider = Arc::new(FlakyProvider { delay: Duration::from_millis(100), ..Default::default() });
+        provider.down.store(true, Ordering::SeqCst);
+        let ai = service(provider.clone());
+        assert!(ai.send_with_retries(&request()).await.is_err());
+
+        provider.down.store(false, Ordering::SeqCst);
+        let abandoned = tokio::time::timeout(Duration::from_millis(20), ai.send_with_retries(&request())).await;
+        assert!(abandoned.is_err());
+        assert!(ai.send_with_retries(&request()).await.is_ok());
+    }
 }
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDate};
+use polars::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::models::response::TimeSeriesAnomaly;
+
+/// Number of preceding periods each value is compared against
+pub const WINDOW: usize = 14;
+
+/// Absolute rolling z-score at or above which a period is reported
+pub const Z_THRESHOLD: f64 = 3.0;
+
+/// Cap on reported anomalies so a noisy series cannot flood the insights
+pub const MAX_ANOMALIES: usize = 20;
+
+/// Bucket size used when aggregating a measure over time
+#[derive(Debug, Clone, Copy)]
+enum Granularity {
+    Day,
+    Week,
+    Month,
+}
+
+impl Granularity {
+    /// Pick a bucket size that keeps the series reasonably dense for the span covered
+    fn for_span(first: NaiveDate, last: NaiveDate) -> Self {
+        let days = (last - first).num_days();
+        if days <= 180 {
+            Granularity::Day
+        } else if days <= 3 * 365 {
+            Granularity::Week
+        } else {
+            Granularity::Month
+        }
+    }
+
+    /// Start date of the period containing `date`
+    fn period_start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => date,
+            Granularity::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Granularity::Month => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+/// Sum every numeric measure per period of each date column and flag periods
+/// whose total deviates from the trailing `WINDOW` periods by `Z_THRESHOLD`
+/// standard deviations or more. Largest deviations come first.
+pub fn detect_anomalies(
+    df: &DataFrame,
+    date_columns: &[String],
+    numeric_columns: &[String],
+) -> Result<Vec<TimeSeriesAnomaly>> {
+    let mut anomalies = Vec::new();
+
+    for date_name in date_columns {
+        let dates = to_naive_dates(df.column(date_name)?)?;
+        let (first, last) = match (dates.iter().flatten().min(), dates.iter().flatten().max()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => continue,
+        };
+        let granularity = Granularity::for_span(first, last);
+
+        for num_name in numeric_columns {
+            let num_f64 = df.column(num_name)?.cast(&DataType::Float64)?;
+            let values = num_f64.f64()?;
+
+            let mut totals: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+            for (date, value) in dates.iter().zip(values) {
+                if let (Some(date), Some(value)) = (date, value) {
+                    if value.is_finite() {
+                        *totals.entry(granularity.period_start(*date)).or_insert(0.0) += value;
+                    }
+                }
+            }
+
+            anomalies.extend(rolling_anomalies(date_name, num_name, &totals));
+        }
+    }
+
+    anomalies.sort_by(|a, b| {
+        b.z_score
+            .abs()
+            .partial_cmp(&a.z_score.abs())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    anomalies.truncate(MAX_ANOMALIES);
+    Ok(anomalies)
+}
+
+/// Convert a Date or Datetime column into calendar dates
+fn to_naive_dates(series: &Series) -> Result<Vec<Option<NaiveDate>>> {
+    // Date is physically stored as days since the Unix epoch
+    let days = series.cast(&DataType::Date)?.cast(&DataType::Int32)?;
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid epoch");
+    Ok(days
+        .i32()?
+        .into_iter()
+        .map(|d| d.and_then(|d| epoch.checked_add_signed(Duration::days(d as i64))))
+        .collect())
+}
+
+/// Score each period against the mean and standard deviation of the window before it
+fn rolling_anomalies(
+    date_column: &str,
+    measure_column: &str,
+    totals: &BTreeMap<NaiveDate, f64>,
+) -> Vec<TimeSeriesAnomaly> {
+    let series: Vec<(&NaiveDate, &f64)> = totals.iter().collect();
+    if series.len() <= WINDOW {
+        return Vec::new();
+    }
+
+    let mut anomalies = Vec::new();
+    for i in WINDOW..series.len() {
+        let window = &series[i - WINDOW..i];
+        let mean = window.iter().map(|(_, v)| **v).sum::<f64>() / WINDOW as f64;
+        let variance = window.iter().map(|(_, v)| (**v - mean).powi(2)).sum::<f64>() / (WINDOW - 1) as f64;
+        let std_dev = variance.sqrt();
+        // A perfectly flat window gives no scale to measure the deviation against
+        if std_dev <= f64::EPSILON {
+            continue;
+        }
+
+        let (period, value) = series[i];
+        let z_score = (value - mean) / std_dev;
+        if z_score.abs() >= Z_THRESHOLD {
+            anomalies.push(TimeSeriesAnomaly {
+                date_column: date_column.to_string(),
+                measure_column: measure_column.to_string(),
+                period: period.format("%Y-%m-%d").to_string(),
+                value: *value,
+                expected: mean,
+                z_score,
+            });
+        }
+    }
+    anomalies
+}
@@ -0,0 +1,129 @@
+use anyhow::Result;
+use polars::prelude::*;
+use std::collections::HashMap;
+
+use crate::models::response::{CategoricalAssociation, GroupStatistics};
+
+/// Categorical columns with more distinct values than this are skipped,
+/// since per-group statistics stop being meaningful.
+pub const MAX_GROUPS: usize = 50;
+
+/// Running per-group accumulator: (count, sum, sum of squares)
+type GroupAccumulator = (usize, f64, f64);
+
+/// Compute the correlation ratio (eta) and one-way ANOVA F-statistic for every
+/// categorical/numeric column pair, strongest association first.
+pub fn categorical_numeric_associations(
+    df: &DataFrame,
+    categorical_columns: &[String],
+    numeric_columns: &[String],
+) -> Result<Vec<CategoricalAssociation>> {
+    let mut associations = Vec::new();
+
+    for cat_name in categorical_columns {
+        let cat_series = df.column(cat_name)?;
+        let distinct = cat_series.n_unique().unwrap_or(0);
+        if !(2..=MAX_GROUPS).contains(&distinct) {
+            continue;
+        }
+
+        let cat_utf8 = cat_series.cast(&DataType::Utf8)?;
+        let categories = cat_utf8.utf8()?;
+
+        for num_name in numeric_columns {
+            let num_f64 = df.column(num_name)?.cast(&DataType::Float64)?;
+            let values = num_f64.f64()?;
+
+            if let Some(association) = associate(cat_name, categories, num_name, values) {
+                associations.push(association);
+            }
+        }
+    }
+
+    associations.sort_by(|a, b| {
+        b.correlation_ratio
+            .partial_cmp(&a.correlation_ratio)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    Ok(associations)
+}
+
+/// Build the association for a single pair, or `None` if there is not enough
+/// non-null data (or no variance) to say anything.
+fn associate(
+    cat_name: &str,
+    categories: &Utf8Chunked,
+    num_name: &str,
+    values: &Float64Chunked,
+) -> Option<CategoricalAssociation> {
+    let mut groups: HashMap<&str, GroupAccumulator> = HashMap::new();
+    let mut total_count = 0usize;
+    let mut total_sum = 0.0;
+
+    // Only use rows where both the category and the value are present
+    for (category, value) in categories.into_iter().zip(values) {
+        if let (Some(category), Some(value)) = (category, value) {
+            let entry = groups.entry(category).or_insert((0, 0.0, 0.0));
+            entry.0 += 1;
+            entry.1 += value;
+            entry.2 += value * value;
+            total_count += 1;
+            total_sum += value;
+        }
+    }
+
+    let group_count = groups.len();
+    if group_count < 2 || total_count <= group_count {
+        return None;
+    }
+
+    let grand_mean = total_sum / total_count as f64;
+    let mut ss_between = 0.0;
+    let mut ss_within = 0.0;
+    let mut group_statistics = Vec::with_capacity(group_count);
+
+    for (group, (count, sum, sum_sq)) in groups {
+        let mean = sum / count as f64;
+        // Clamp tiny negative values caused by floating point cancellation
+        let group_ss = (sum_sq - count as f64 * mean * mean).max(0.0);
+
+        ss_between += count as f64 * (mean - grand_mean).powi(2);
+        ss_within += group_ss;
+
+        group_statistics.push(GroupStatistics {
+            group: group.to_string(),
+            count,
+            mean,
+            std_dev: if count > 1 {
+                Some((group_ss / (count - 1) as f64).sqrt())
+            } else {
+                None
+            },
+        });
+    }
+
+    let ss_total = ss_between + ss_within;
+    if ss_total < f64::EPSILON {
+        return None;
+    }
+
+    let correlation_ratio = (ss_between / ss_total).sqrt().min(1.0);
+    let f_statistic = if ss_within > f64::EPSILON {
+        let df_between = (group_count - 1) as f64;
+        let df_within = (total_count - group_count) as f64;
+        Some((ss_between / df_between) / (ss_within / df_within))
+    } else {
+        None
+    };
+
+    group_statistics.sort_by(|a, b| b.mean.partial_cmp(&a.mean).unwrap_or(std::cmp::Ordering::Equal));
+
+    Some(CategoricalAssociation {
+        categorical_column: cat_name.to_string(),
+        numeric_column: num_name.to_string(),
+        correlation_ratio,
+        f_statistic,
+        group_statistics,
+    })
+}
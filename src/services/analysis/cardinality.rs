@@ -0,0 +1,116 @@
+use polars::prelude::DataType;
+
+use crate::models::response::{ColumnFlag, LongTail};
+
+/// Share of non-null values that must be distinct for a column to look like an identifier
+const IDENTIFIER_UNIQUE_RATIO: f64 = 0.95;
+/// Name-based hints lower the uniqueness bar for identifier detection
+const NAMED_IDENTIFIER_UNIQUE_RATIO: f64 = 0.9;
+/// Categorical columns with more distinct values than this are high-cardinality
+const HIGH_CARDINALITY_MIN_DISTINCT: usize = 50;
+/// ...and at least this share of non-null values must be distinct
+const HIGH_CARDINALITY_UNIQUE_RATIO: f64 = 0.5;
+
+/// Classify a column by its cardinality.
+///
+/// `unique_count` is Polars' `n_unique`, which counts null as a value.
+pub fn column_flags(
+    name: &str,
+    dtype: &DataType,
+    row_count: usize,
+    null_count: usize,
+    unique_count: usize,
+) -> Vec<ColumnFlag> {
+    let non_null = row_count.saturating_sub(null_count);
+    let distinct = if null_count > 0 { unique_count.saturating_sub(1) } else { unique_count };
+
+    if non_null == 0 || distinct <= 1 {
+        return vec![ColumnFlag::Constant];
+    }
+
+    let unique_ratio = distinct as f64 / non_null as f64;
+    let mut flags = Vec::new();
+
+    // Continuous floats are naturally unique, so only integer and string columns can be identifiers
+    let identifier_candidate = matches!(
+        dtype,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Utf8
+    );
+    let required_ratio = if looks_like_identifier_name(name) {
+        NAMED_IDENTIFIER_UNIQUE_RATIO
+    } else {
+        IDENTIFIER_UNIQUE_RATIO
+    };
+
+    if identifier_candidate && non_null > 1 && unique_ratio >= required_ratio {
+        flags.push(ColumnFlag::LikelyIdentifier);
+    } else if matches!(dtype, DataType::Utf8)
+        && distinct > HIGH_CARDINALITY_MIN_DISTINCT
+        && unique_ratio >= HIGH_CARDINALITY_UNIQUE_RATIO
+    {
+        flags.push(ColumnFlag::HighCardinality);
+    }
+
+    flags
+}
+
+/// Share of rows the long-tail summary counts values up to
+const LONG_TAIL_COVERAGE: f64 = 0.8;
+
+/// Summarise how concentrated a column's values are, given the count of every
+/// distinct non-null value in descending order and how many of them are reported
+/// as frequent values.
+pub fn long_tail(counts: &[u32], reported: usize) -> Option<LongTail> {
+    let total: u64 = counts.iter().map(|c| *c as u64).sum();
+    if total == 0 {
+        return None;
+    }
+    let total = total as f64;
+
+    let mut covered = 0.0;
+    let mut values_for_80_percent = counts.len();
+    for (i, count) in counts.iter().enumerate() {
+        covered += *count as f64;
+        if covered / total >= LONG_TAIL_COVERAGE {
+            values_for_80_percent = i + 1;
+            break;
+        }
+    }
+
+    let entropy: f64 = counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    let normalized_entropy = if counts.len() > 1 { entropy / (counts.len() as f64).log2() } else { 0.0 };
+
+    Some(LongTail {
+        distinct_values: counts.len(),
+        values_for_80_percent,
+        top_values_coverage: counts.iter().take(reported).map(|c| *c as f64).sum::<f64>() / total,
+        entropy,
+        normalized_entropy,
+    })
+}
+
+/// Whether a column name follows common identifier conventions (`id`, `user_id`, `orderId`, `uuid`, ...)
+fn looks_like_identifier_name(name: &str) -> bool {
+    let lower = name.trim().to_lowercase();
+    lower == "id"
+        || lower.ends_with("_id")
+        || lower.ends_with(" id")
+        || lower.ends_with("uuid")
+        || lower.ends_with("guid")
+        || (name.ends_with("Id") && name.len() > 2)
+}
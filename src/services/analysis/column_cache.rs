@@ -0,0 +1,75 @@
+use anyhow::Result;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::models::profile::InsightProfile;
+use crate::models::response::ColumnStatistics;
+
+/// Statistics of a job's columns from its last processing, each kept with the
+/// fingerprint of the content and settings it was computed from. A column whose
+/// fingerprint is unchanged reuses its statistics instead of computing them again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColumnStatsCache {
+    pub columns: HashMap<String, CachedColumn>,
+}
+
+/// The statistics of one column and the fingerprint they belong to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedColumn {
+    pub fingerprint: String,
+    pub stats: ColumnStatistics,
+}
+
+impl ColumnStatsCache {
+    /// Statistics of `column` if they were computed from content with this fingerprint
+    pub fn get(&self, column: &str, fingerprint: &str) -> Option<&ColumnStatistics> {
+        self.columns
+            .get(column)
+            .filter(|cached| cached.fingerprint == fingerprint)
+            .map(|cached| &cached.stats)
+    }
+}
+
+/// Hex SHA-256 of a column's name, type, values and the settings its statistics
+/// depend on. Values are hashed with their nulls, so equal fingerprints mean equal
+/// columns rather than columns that hash alike.
+pub fn fingerprint(s: &Series, profile: InsightProfile, top_values: usize) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(s.name().as_bytes());
+    hasher.update([0]);
+    hasher.update(format!("{:?}|{:?}|{}|{}", s.dtype(), profile, top_values, s.len()).as_bytes());
+
+    // Each value is written as a presence byte followed by its bytes
+    let mut update = |value: Option<&[u8]>| match value {
+        Some(bytes) => {
+            hasher.update([1]);
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        }
+        None => hasher.update([0]),
+    };
+    match s.dtype() {
+        DataType::Utf8 => s.utf8()?.into_iter().for_each(|v| update(v.map(str::as_bytes))),
+        DataType::Boolean => s.bool()?.into_iter().for_each(|v| update(v.map(|b| if b { &[1u8][..] } else { &[0u8][..] }))),
+        DataType::Float32 | DataType::Float64 => s
+            .cast(&DataType::Float64)?
+            .f64()?
+            .into_iter()
+            .for_each(|v| update(v.map(|x| x.to_bits().to_le_bytes()).as_ref().map(|b| &b[..]))),
+        DataType::UInt64 => s.u64()?.into_iter().for_each(|v| update(v.map(u64::to_le_bytes).as_ref().map(|b| &b[..]))),
+        // Other integers, dates and datetimes are stored as integers that fit an i64
+        dtype if dtype.is_numeric() || dtype.is_temporal() => s
+            .to_physical_repr()
+            .cast(&DataType::Int64)?
+            .i64()?
+            .into_iter()
+            .for_each(|v| update(v.map(i64::to_le_bytes).as_ref().map(|b| &b[..]))),
+        _ => s.iter().for_each(|v| match v {
+            AnyValue::Null => update(None),
+            v => update(Some(v.to_string().as_bytes())),
+        }),
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
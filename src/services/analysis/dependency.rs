@@ -0,0 +1,77 @@
+use anyhow::Result;
+use polars::prelude::*;
+use std::collections::HashMap;
+
+use crate::models::response::FunctionalDependency;
+
+/// Share of rows that must agree with the dominant dependent value per determinant value
+pub const MIN_STRENGTH: f64 = 0.95;
+
+/// Candidate columns beyond this count are ignored to bound the pairwise cost
+pub const MAX_COLUMNS: usize = 30;
+
+/// Determinant values must repeat on average at least this often, otherwise
+/// the dependency holds trivially (every near-unique column determines everything)
+const MIN_AVG_GROUP_SIZE: usize = 2;
+
+/// Detect near-functional dependencies `determinant → dependent` between the
+/// given columns, strongest first. Strength is the share of rows whose dependent
+/// value is the most common one for their determinant value.
+pub fn functional_dependencies(df: &DataFrame, columns: &[String]) -> Result<Vec<FunctionalDependency>> {
+    let mut candidates = Vec::new();
+    for name in columns.iter().take(MAX_COLUMNS) {
+        let series = df.column(name)?;
+        let distinct = series.n_unique().unwrap_or(0);
+        if distinct < 2 {
+            continue;
+        }
+        candidates.push((name, series.cast(&DataType::Utf8)?, distinct));
+    }
+
+    let mut dependencies = Vec::new();
+    for (det_name, det_series, det_distinct) in &candidates {
+        let determinant = det_series.utf8()?;
+        for (dep_name, dep_series, dep_distinct) in &candidates {
+            if det_name == dep_name {
+                continue;
+            }
+            let dependent = dep_series.utf8()?;
+
+            // Count dependent values per determinant value over complete rows
+            let mut groups: HashMap<&str, HashMap<&str, usize>> = HashMap::new();
+            let mut rows = 0;
+            for (a, b) in determinant.into_iter().zip(dependent) {
+                if let (Some(a), Some(b)) = (a, b) {
+                    *groups.entry(a).or_default().entry(b).or_insert(0) += 1;
+                    rows += 1;
+                }
+            }
+            if rows == 0 || groups.len() * MIN_AVG_GROUP_SIZE > rows {
+                continue;
+            }
+
+            let agreeing: usize = groups
+                .values()
+                .map(|counts| counts.values().copied().max().unwrap_or(0))
+                .sum();
+            let strength = agreeing as f64 / rows as f64;
+            if strength >= MIN_STRENGTH {
+                dependencies.push(FunctionalDependency {
+                    determinant: (*det_name).clone(),
+                    dependent: (*dep_name).clone(),
+                    strength,
+                    violations: rows - agreeing,
+                    determinant_distinct: *det_distinct,
+                    dependent_distinct: *dep_distinct,
+                });
+            }
+        }
+    }
+
+    dependencies.sort_by(|a, b| {
+        b.strength
+            .partial_cmp(&a.strength)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Ok(dependencies)
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use polars::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::models::response::{ColumnDrift, SchemaDiff, TypeChange};
+
+/// Number of quantile bins numeric PSI is computed over
+pub const PSI_BINS: usize = 10;
+
+/// Categories beyond the most frequent ones in the base dataset are pooled together
+pub const MAX_CATEGORIES: usize = 20;
+
+/// Floor applied to bin shares so empty bins don't blow up the PSI logarithm
+const PSI_EPSILON: f64 = 1e-4;
+
+/// Columns added, removed or retyped between two datasets
+pub fn schema_diff(base: &DataFrame, target: &DataFrame) -> SchemaDiff {
+    let base_names: Vec<&str> = base.get_column_names();
+    let target_names: Vec<&str> = target.get_column_names();
+
+    let added_columns = target_names
+        .iter()
+        .filter(|name| !base_names.contains(name))
+        .map(|name| name.to_string())
+        .collect();
+    let removed_columns = base_names
+        .iter()
+        .filter(|name| !target_names.contains(name))
+        .map(|name| name.to_string())
+        .collect();
+
+    let type_changes = base
+        .get_columns()
+        .iter()
+        .filter_map(|b| {
+            let t = target.column(b.name()).ok()?;
+            (b.dtype() != t.dtype()).then(|| TypeChange {
+                column: b.name().to_string(),
+                base_type: format!("{:?}", b.dtype()),
+                target_type: format!("{:?}", t.dtype()),
+            })
+        })
+        .collect();
+
+    SchemaDiff {
+        added_columns,
+        removed_columns,
+        type_changes,
+    }
+}
+
+/// Distribution shift of every column present in both datasets, largest PSI first
+pub fn column_drift(base: &DataFrame, target: &DataFrame) -> Result<Vec<ColumnDrift>> {
+    let mut drift = Vec::new();
+
+    for b in base.get_columns() {
+        let t = match target.column(b.name()) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+
+        let column = if b.dtype().is_numeric() && t.dtype().is_numeric() {
+            numeric_drift(b, t)?
+        } else {
+            categorical_drift(b, t)?
+        };
+        if let Some(column) = column {
+            drift.push(column);
+        }
+    }
+
+    drift.sort_by(|a, b| b.psi.partial_cmp(&a.psi).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(drift)
+}
+
+fn finite_values(series: &Series) -> Result<Vec<f64>> {
+    let values = series.cast(&DataType::Float64)?;
+    let mut values: Vec<f64> = values.f64()?.into_iter().flatten().filter(|v| v.is_finite()).collect();
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(values)
+}
+
+fn numeric_drift(base: &Series, target: &Series) -> Result<Option<ColumnDrift>> {
+    let base_values = finite_values(base)?;
+    let target_values = finite_values(target)?;
+    if base_values.is_empty() || target_values.is_empty() {
+        return Ok(None);
+    }
+
+    // Bin edges at the base deciles; both datasets are bucketed against them
+    let mut edges: Vec<f64> = (1..PSI_BINS)
+        .map(|i| base_values[(i * base_values.len() / PSI_BINS).min(base_values.len() - 1)])
+        .collect();
+    edges.dedup();
+    let bucket = |values: &[f64]| {
+        let mut counts = vec![0usize; edges.len() + 1];
+        for v in values {
+            counts[edges.partition_point(|edge| edge <= v)] += 1;
+        }
+        counts
+    };
+    let psi = population_stability_index(&bucket(&base_values), &bucket(&target_values));
+
+    let ks_statistic = ks_statistic(&base_values, &target_values);
+    let ks_p_value = ks_p_value(ks_statistic, base_values.len(), target_values.len());
+
+    Ok(Some(ColumnDrift {
+        column: base.name().to_string(),
+        kind: "numeric".to_string(),
+        psi,
+        severity: severity(psi).to_string(),
+        ks_statistic: Some(ks_statistic),
+        ks_p_value: Some(ks_p_value),
+        base_mean: Some(base_values.iter().sum::<f64>() / base_values.len() as f64),
+        target_mean: Some(target_values.iter().sum::<f64>() / target_values.len() as f64),
+        new_categories: Vec::new(),
+        missing_categories: Vec::new(),
+    }))
+}
+
+fn categorical_drift(base: &Series, target: &Series) -> Result<Option<ColumnDrift>> {
+    let count = |series: &Series| -> Result<HashMap<String, usize>> {
+        let utf8 = series.cast(&DataType::Utf8)?;
+        let mut counts = HashMap::new();
+        for value in utf8.utf8()?.into_iter().flatten() {
+            *counts.entry(value.to_string()).or_insert(0) += 1;
+        }
+        Ok(counts)
+    };
+    let base_counts = count(base)?;
+    let target_counts = count(target)?;
+    if base_counts.is_empty() || target_counts.is_empty() {
+        return Ok(None);
+    }
+
+    // Keep the most frequent base categories and pool the rest
+    let mut ranked: Vec<(&String, &usize)> = base_counts.iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let kept: Vec<&String> = ranked.iter().take(MAX_CATEGORIES).map(|(k, _)| *k).collect();
+    let bucket = |counts: &HashMap<String, usize>| {
+        let mut buckets: Vec<usize> = kept.iter().map(|k| counts.get(*k).copied().unwrap_or(0)).collect();
+        let pooled: usize = counts
+            .iter()
+            .filter(|(k, _)| !kept.contains(k))
+            .map(|(_, c)| c)
+            .sum();
+        buckets.push(pooled);
+        buckets
+    };
+    let psi = population_stability_index(&bucket(&base_counts), &bucket(&target_counts));
+
+    let base_keys: HashSet<&String> = base_counts.keys().collect();
+    let target_keys: HashSet<&String> = target_counts.keys().collect();
+    let mut new_categories: Vec<String> = target_keys.difference(&base_keys).map(|k| k.to_string()).collect();
+    let mut missing_categories: Vec<String> = base_keys.difference(&target_keys).map(|k| k.to_string()).collect();
+    new_categories.sort();
+    missing_categories.sort();
+    new_categories.truncate(MAX_CATEGORIES);
+    missing_categories.truncate(MAX_CATEGORIES);
+
+    Ok(Some(ColumnDrift {
+        column: base.name().to_string(),
+        kind: "categorical".to_string(),
+        psi,
+        severity: severity(psi).to_string(),
+        ks_statistic: None,
+        ks_p_value: None,
+        base_mean: None,
+        target_mean: None,
+        new_categories,
+        missing_categories,
+    }))
+}
+
+/// PSI = Σ (t − b) · ln(t / b) over bin shares
+fn population_stability_index(base: &[usize], target: &[usize]) -> f64 {
+    let base_total = base.iter().sum::<usize>().max(1) as f64;
+    let target_total = target.iter().sum::<usize>().max(1) as f64;
+    base.iter()
+        .zip(target)
+        .map(|(b, t)| {
+            let b = (*b as f64 / base_total).max(PSI_EPSILON);
+            let t = (*t as f64 / target_total).max(PSI_EPSILON);
+            (t - b) * (t / b).ln()
+        })
+        .sum()
+}
+
+/// Two-sample Kolmogorov–Smirnov statistic over sorted samples
+fn ks_statistic(base: &[f64], target: &[f64]) -> f64 {
+    let (n, m) = (base.len() as f64, target.len() as f64);
+    let (mut i, mut j, mut d) = (0, 0, 0.0f64);
+    while i < base.len() && j < target.len() {
+        let x = base[i].min(target[j]);
+        while i < base.len() && base[i] <= x {
+            i += 1;
+        }
+        while j < target.len() && target[j] <= x {
+            j += 1;
+        }
+        d = d.max((i as f64 / n - j as f64 / m).abs());
+    }
+    d
+}
+
+/// Asymptotic p-value of the two-sample KS statistic
+fn ks_p_value(d: f64, n: usize, m: usize) -> f64 {
+    let ne = (n * m) as f64 / (n + m) as f64;
+    let lambda = (ne.sqrt() + 0.12 + 0.11 / ne.sqrt()) * d;
+    if lambda < 1e-3 {
+        return 1.0;
+    }
+    let p: f64 = (1..=100)
+        .map(|k| {
+            let k = k as f64;
+            let sign = if k as i64 % 2 == 1 { 1.0 } else { -1.0 };
+            sign * (-2.0 * k * k * lambda * lambda).exp()
+        })
+        .sum::<f64>()
+        * 2.0;
+    p.clamp(0.0, 1.0)
+}
+
+/// Conventional PSI reading: below 0.1 stable, below 0.25 moderate, otherwise significant
+fn severity(psi: f64) -> &'static str {
+    if psi < 0.1 {
+        "stable"
+    } else if psi < 0.25 {
+        "moderate"
+    } else {
+        "significant"
+    }
+}
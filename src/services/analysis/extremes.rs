@@ -0,0 +1,68 @@
+use anyhow::Result;
+use polars::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::models::response::{ExtremeRecords, RecordSample};
+
+/// Rows reported at each end of every numeric column
+pub const TOP_N: usize = 3;
+
+/// Identifying columns shown alongside each extreme value
+pub const MAX_CONTEXT_COLUMNS: usize = 3;
+
+/// Pick the top and bottom `TOP_N` rows of each numeric column, each with the
+/// values of `context_columns` so the rows can be recognised.
+pub fn extreme_records(
+    df: &DataFrame,
+    numeric_columns: &[String],
+    context_columns: &[String],
+) -> Result<Vec<ExtremeRecords>> {
+    let context: Vec<&Series> = context_columns
+        .iter()
+        .take(MAX_CONTEXT_COLUMNS)
+        .filter_map(|name| df.column(name).ok())
+        .collect();
+
+    let mut records = Vec::new();
+    for name in numeric_columns {
+        let values = df.column(name)?.cast(&DataType::Float64)?;
+        let mut ranked: Vec<(usize, f64)> = values
+            .f64()?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, v)| v.filter(|v| v.is_finite()).map(|v| (idx, v)))
+            .collect();
+        if ranked.is_empty() {
+            continue;
+        }
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        let top = ranked.iter().take(TOP_N);
+        // Small columns would otherwise list the same rows at both ends
+        let bottom_count = TOP_N.min(ranked.len().saturating_sub(TOP_N));
+        let bottom = ranked.iter().rev().take(bottom_count);
+
+        records.push(ExtremeRecords {
+            column: name.clone(),
+            top: top.map(|(idx, v)| sample(*idx, *v, &context)).collect(),
+            bottom: bottom.map(|(idx, v)| sample(*idx, *v, &context)).collect(),
+        });
+    }
+    Ok(records)
+}
+
+fn sample(row: usize, value: f64, context: &[&Series]) -> RecordSample {
+    let fields = context
+        .iter()
+        .filter_map(|s| {
+            let rendered = match s.get(row).ok()? {
+                AnyValue::Null => return None,
+                AnyValue::Utf8(v) => v.to_string(),
+                other => other.to_string(),
+            };
+            Some((s.name().to_string(), rendered))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    RecordSample { value, fields }
+}
@@ -0,0 +1,290 @@
+use anyhow::Result;
+use polars::prelude::*;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::Locale;
+use crate::models::response::{ColumnStatistics, IncrementalInfo, Insights};
+
+/// Statistics that cannot be merged from running aggregates and keep the
+/// value of the last full computation after an append
+pub const APPROXIMATE_FIELDS: &[&str] = &[
+    "unique_count",
+    "median",
+    "percentile_25",
+    "percentile_75",
+    "frequent_values",
+    "categorical_associations",
+    "anomalies",
+    "extreme_records",
+    "functional_dependencies",
+    "plausibility",
+];
+
+/// Running aggregates of one numeric column
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColumnAccumulator {
+    pub count: usize,
+    pub sum: f64,
+    pub sum_sq: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl ColumnAccumulator {
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.sum_sq += value * value;
+        self.min = Some(self.min.map_or(value, |m| m.min(value)));
+        self.max = Some(self.max.map_or(value, |m| m.max(value)));
+    }
+
+    fn merge(&mut self, other: &ColumnAccumulator) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.sum_sq += other.sum_sq;
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max = match (self.max, other.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// Sample standard deviation (ddof = 1), matching the full computation
+    fn std_dev(&self) -> Option<f64> {
+        if self.count < 2 {
+            return None;
+        }
+        let n = self.count as f64;
+        let variance = (self.sum_sq - self.sum * self.sum / n) / (n - 1.0);
+        Some(variance.max(0.0).sqrt())
+    }
+}
+
+/// Running co-moments of a numeric column pair over rows where both are present
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PairAccumulator {
+    pub x: String,
+    pub y: String,
+    pub n: usize,
+    pub sum_x: f64,
+    pub sum_y: f64,
+    pub sum_xx: f64,
+    pub sum_yy: f64,
+    pub sum_xy: f64,
+}
+
+impl PairAccumulator {
+    fn merge(&mut self, other: &PairAccumulator) {
+        self.n += other.n;
+        self.sum_x += other.sum_x;
+        self.sum_y += other.sum_y;
+        self.sum_xx += other.sum_xx;
+        self.sum_yy += other.sum_yy;
+        self.sum_xy += other.sum_xy;
+    }
+
+    fn correlation(&self) -> Option<f64> {
+        if self.n < 2 {
+            return None;
+        }
+        let n = self.n as f64;
+        let cov = self.sum_xy - self.sum_x * self.sum_y / n;
+        let var_x = self.sum_xx - self.sum_x * self.sum_x / n;
+        let var_y = self.sum_yy - self.sum_y * self.sum_y / n;
+        if var_x <= f64::EPSILON || var_y <= f64::EPSILON {
+            return None;
+        }
+        Some((cov / (var_x.sqrt() * var_y.sqrt())).clamp(-1.0, 1.0))
+    }
+}
+
+/// Mergeable aggregates kept next to a job's insights so appended rows can be
+/// folded in without re-reading the whole dataset
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AggregateState {
+    pub row_count: usize,
+    /// Null count per column, in dataset column order
+    pub null_counts: Vec<(String, usize)>,
+    pub numeric: Vec<(String, ColumnAccumulator)>,
+    pub pairs: Vec<PairAccumulator>,
+}
+
+impl AggregateState {
+    /// Build the aggregates of `df` for the given numeric columns
+    pub fn from_frame(df: &DataFrame, numeric_columns: &[String]) -> Result<Self> {
+        let null_counts = df
+            .get_columns()
+            .iter()
+            .map(|s| (s.name().to_string(), s.null_count()))
+            .collect();
+
+        let mut values = Vec::with_capacity(numeric_columns.len());
+        for name in numeric_columns {
+            let series = match df.column(name) {
+                Ok(series) => series.cast(&DataType::Float64)?,
+                // Columns missing from an appended chunk contribute nothing
+                Err(_) => Series::full_null(name, df.height(), &DataType::Float64),
+            };
+            values.push(series);
+        }
+
+        let mut numeric = Vec::with_capacity(numeric_columns.len());
+        for (name, series) in numeric_columns.iter().zip(&values) {
+            let mut acc = ColumnAccumulator::default();
+            for value in series.f64()?.into_iter().flatten().filter(|v| v.is_finite()) {
+                acc.push(value);
+            }
+            numeric.push((name.clone(), acc));
+        }
+
+        // Pairs are independent, so each is accumulated on its own thread
+        let indices: Vec<(usize, usize)> = (0..numeric_columns.len())
+            .flat_map(|i| ((i + 1)..numeric_columns.len()).map(move |j| (i, j)))
+            .collect();
+        let pairs = indices
+            .into_par_iter()
+            .map(|(i, j)| {
+                let mut pair = PairAccumulator {
+                    x: numeric_columns[i].clone(),
+                    y: numeric_columns[j].clone(),
+                    ..Default::default()
+                };
+                for (x, y) in values[i].f64()?.into_iter().zip(values[j].f64()?) {
+                    if let (Some(x), Some(y)) = (x, y) {
+                        if x.is_finite() && y.is_finite() {
+                            pair.n += 1;
+                            pair.sum_x += x;
+                            pair.sum_y += y;
+                            pair.sum_xx += x * x;
+                            pair.sum_yy += y * y;
+                            pair.sum_xy += x * y;
+                        }
+                    }
+                }
+                Ok(pair)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            row_count: df.height(),
+            null_counts,
+            numeric,
+            pairs,
+        })
+    }
+
+    /// Fold the aggregates of an appended chunk into this state
+    pub fn merge(&mut self, other: &AggregateState) {
+        self.row_count += other.row_count;
+        for (name, nulls) in &other.null_counts {
+            if let Some((_, total)) = self.null_counts.iter_mut().find(|(n, _)| n == name) {
+                *total += nulls;
+            }
+        }
+        for (name, acc) in &other.numeric {
+            if let Some((_, total)) = self.numeric.iter_mut().find(|(n, _)| n == name) {
+                total.merge(acc);
+            }
+        }
+        for pair in &other.pairs {
+            if let Some(total) = self.pairs.iter_mut().find(|p| p.x == pair.x && p.y == pair.y) {
+                total.merge(pair);
+            }
+        }
+    }
+}
+
+/// Refresh `insights` from the merged `state` after `chunk` was appended.
+/// Counts, min/max, mean, standard deviation and the correlations of pairs the
+/// chunk touched are exact; the fields in `APPROXIMATE_FIELDS` are left as they were.
+pub fn apply_append(insights: &mut Insights, state: &AggregateState, chunk: &AggregateState) {
+    let previous_rows = insights.data_summary.row_count;
+    insights.data_summary.row_count = state.row_count;
+    if insights.data_summary.summary_parts.is_empty() {
+        // Summaries stored before their parts were kept
+        insights.data_summary.summary_text = insights.data_summary.summary_text.replacen(
+            &format!("Dataset has {} rows", previous_rows),
+            &format!("Dataset has {} rows", state.row_count),
+            1,
+        );
+    } else {
+        insights.data_summary.summary_text = insights.data_summary.localized_summary(Locale::En);
+    }
+
+    refresh_columns(insights, state);
+
+    // Only pairs with complete rows in the chunk can have moved
+    if let Some(correlations) = insights.correlations.as_mut() {
+        for touched in chunk.pairs.iter().filter(|p| p.n > 0) {
+            let key = format!("{}-{}", touched.x, touched.y);
+            // Pairs left out of the original analysis (e.g. identifier columns) stay out
+            if !correlations.contains_key(&key) {
+                continue;
+            }
+            if let Some(merged) = state.pairs.iter().find(|p| p.x == touched.x && p.y == touched.y) {
+                match merged.correlation() {
+                    Some(value) => {
+                        correlations.insert(key, value);
+                    }
+                    None => {
+                        correlations.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    let info = insights.data_summary.incremental.get_or_insert_with(|| IncrementalInfo {
+        approximate_fields: APPROXIMATE_FIELDS.iter().map(|f| f.to_string()).collect(),
+        ..Default::default()
+    });
+    info.appends += 1;
+    info.appended_rows += chunk.row_count;
+}
+
+/// Overwrite what `state` holds exactly over the whole dataset — null counts,
+/// min/max, mean, standard deviation and correlations — in insights computed on
+/// a sample of it
+pub fn apply_exact(insights: &mut Insights, state: &AggregateState) {
+    refresh_columns(insights, state);
+    if let Some(correlations) = insights.correlations.as_mut() {
+        for pair in &state.pairs {
+            let key = format!("{}-{}", pair.x, pair.y);
+            if let (Some(value), Some(correlation)) = (pair.correlation(), correlations.get_mut(&key)) {
+                *correlation = value;
+            }
+        }
+    }
+}
+
+fn refresh_columns(insights: &mut Insights, state: &AggregateState) {
+    for stats in insights.column_statistics.iter_mut() {
+        if let Some((_, nulls)) = state.null_counts.iter().find(|(n, _)| *n == stats.name) {
+            stats.null_count = *nulls;
+        }
+        if let Some((_, acc)) = state.numeric.iter().find(|(n, _)| *n == stats.name) {
+            refresh_numeric(stats, acc);
+        }
+    }
+}
+
+/// Refresh the merged statistics, leaving out any the insight profile skipped
+fn refresh_numeric(stats: &mut ColumnStatistics, acc: &ColumnAccumulator) {
+    stats.min = acc.min.map(|v| v.to_string());
+    stats.max = acc.max.map(|v| v.to_string());
+    if stats.mean.is_some() {
+        stats.mean = acc.mean().map(|v| format!("{:.2}", v));
+    }
+    if stats.std_dev.is_some() {
+        stats.std_dev = acc.std_dev().map(|v| format!("{:.2}", v));
+    }
+}
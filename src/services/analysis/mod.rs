@@ -0,0 +1,13 @@
+pub mod anomaly;
+pub mod association;
+pub mod cardinality;
+pub mod column_cache;
+pub mod dependency;
+pub mod drift;
+pub mod extremes;
+pub mod incremental;
+pub mod pii;
+pub mod plausibility;
+pub mod sampling;
+pub mod schema;
+pub mod text;
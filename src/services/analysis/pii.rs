@@ -0,0 +1,133 @@
+use anyhow::Result;
+use polars::prelude::*;
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::models::response::PiiType;
+
+/// Maximum number of non-null values inspected per column
+pub const SAMPLE_SIZE: usize = 1000;
+/// Share of sampled values that must match before a column is flagged
+const MIN_MATCH_RATIO: f64 = 0.3;
+
+struct PiiPatterns {
+    email: Regex,
+    phone: Regex,
+    national_ids: Vec<Regex>,
+    card: Regex,
+    date: Regex,
+}
+
+fn patterns() -> &'static PiiPatterns {
+    static PATTERNS: OnceLock<PiiPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| PiiPatterns {
+        email: Regex::new(r"^[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}$").unwrap(),
+        phone: Regex::new(r"^\+?[0-9(][0-9 ().-]{5,18}[0-9]$").unwrap(),
+        national_ids: vec![
+            // US social security number
+            Regex::new(r"^\d{3}-\d{2}-\d{4}$").unwrap(),
+            // UK national insurance number
+            Regex::new(r"^[A-CEGHJ-PR-TW-Z]{2}\s?\d{2}\s?\d{2}\s?\d{2}\s?[A-D]$").unwrap(),
+            // Brazilian CPF
+            Regex::new(r"^\d{3}\.\d{3}\.\d{3}-\d{2}$").unwrap(),
+            // French INSEE (social security) number
+            Regex::new(r"^[12]\s?\d{2}\s?(0[1-9]|1[0-2])\s?(\d{2}|2[AB])\s?\d{3}\s?\d{3}(\s?\d{2})?$").unwrap(),
+        ],
+        card: Regex::new(r"^[0-9][0-9 -]{11,21}[0-9]$").unwrap(),
+        date: Regex::new(r"^(\d{4}[-/.]\d{1,2}[-/.]\d{1,2}|\d{1,2}[-/.]\d{1,2}[-/.]\d{2,4})$").unwrap(),
+    })
+}
+
+/// Scan a sample of a column's values for personal data.
+///
+/// Only string and integer columns are inspected; a PII type is reported when
+/// at least 30% of the sampled values match it.
+pub fn detect_pii(series: &Series) -> Result<Vec<PiiType>> {
+    let is_scannable = matches!(
+        series.dtype(),
+        DataType::Utf8 | DataType::Int64 | DataType::UInt64 | DataType::Int32 | DataType::UInt32
+    );
+    if !is_scannable {
+        return Ok(Vec::new());
+    }
+
+    let as_utf8 = series.cast(&DataType::Utf8)?;
+    let values = as_utf8.utf8()?;
+    let non_null = values.len() - values.null_count();
+    if non_null == 0 {
+        return Ok(Vec::new());
+    }
+
+    // Evenly spaced sample so values from the whole file are represented
+    let step = (non_null / SAMPLE_SIZE).max(1);
+    let sample: Vec<&str> = values
+        .into_iter()
+        .flatten()
+        .step_by(step)
+        .take(SAMPLE_SIZE)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect();
+    if sample.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut counts = [0usize; 4];
+    for value in &sample {
+        if let Some(pii_type) = classify(value) {
+            counts[pii_type as usize] += 1;
+        }
+    }
+
+    let threshold = (sample.len() as f64 * MIN_MATCH_RATIO).ceil() as usize;
+    Ok([PiiType::Email, PiiType::Phone, PiiType::NationalId, PiiType::CreditCard]
+        .into_iter()
+        .filter(|pii_type| counts[*pii_type as usize] >= threshold.max(1))
+        .collect())
+}
+
+/// Classify a single value, checking the most specific patterns first
+fn classify(value: &str) -> Option<PiiType> {
+    let patterns = patterns();
+
+    if patterns.email.is_match(value) {
+        return Some(PiiType::Email);
+    }
+    if patterns.national_ids.iter().any(|re| re.is_match(value)) {
+        return Some(PiiType::NationalId);
+    }
+
+    let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+    if patterns.card.is_match(value) && (13..=19).contains(&digits.len()) && passes_luhn(&digits) {
+        return Some(PiiType::CreditCard);
+    }
+
+    // Bare digit runs are usually identifiers or amounts, so phone numbers
+    // need a leading '+' or some formatting
+    let has_formatting = value.chars().any(|c| matches!(c, '+' | ' ' | '-' | '.' | '(' | ')'));
+    if has_formatting
+        && !patterns.date.is_match(value)
+        && patterns.phone.is_match(value) && (7..=15).contains(&digits.len()) {
+        return Some(PiiType::Phone);
+    }
+
+    None
+}
+
+/// Luhn checksum used by payment card numbers
+fn passes_luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
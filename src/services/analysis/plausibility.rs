@@ -0,0 +1,121 @@
+use anyhow::Result;
+use polars::prelude::*;
+
+use crate::models::response::{BenfordResult, PlausibilityCheck, PlausibilityFlag};
+
+/// Fewer non-zero values than this make the Benford test meaningless
+pub const MIN_BENFORD_VALUES: usize = 100;
+
+/// Benford's law only describes data spanning several orders of magnitude
+const MIN_ORDERS_OF_MAGNITUDE: f64 = 2.0;
+
+/// Nigrini's first-digit MAD thresholds: close, acceptable and marginal conformity
+const MAD_CLOSE: f64 = 0.006;
+const MAD_ACCEPTABLE: f64 = 0.012;
+const MAD_MARGINAL: f64 = 0.015;
+
+/// Negatives making up less than this share of a column look like data-entry errors
+const STRAY_NEGATIVE_SHARE: f64 = 0.05;
+
+/// Zero share above which zeros look like placeholders for missing values
+const EXCESS_ZERO_SHARE: f64 = 0.2;
+
+/// Share of values ≥ 10 that are multiples of 10 above which numbers look made up
+/// (about 10% is expected for naturally occurring amounts)
+const EXCESS_ROUND_SHARE: f64 = 0.5;
+
+/// Run Benford conformance and negative/zero/rounding checks on each column
+pub fn plausibility_checks(df: &DataFrame, columns: &[String]) -> Result<Vec<PlausibilityCheck>> {
+    let mut checks = Vec::new();
+
+    for name in columns {
+        let values = df.column(name)?.cast(&DataType::Float64)?;
+        let values: Vec<f64> = values.f64()?.into_iter().flatten().filter(|v| v.is_finite()).collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        let total = values.len() as f64;
+        let negatives = values.iter().filter(|v| **v < 0.0).count();
+        let zeros = values.iter().filter(|v| **v == 0.0).count();
+        let large: Vec<f64> = values.iter().map(|v| v.abs()).filter(|v| *v >= 10.0).collect();
+        let round = large.iter().filter(|v| v.fract() == 0.0 && *v % 10.0 == 0.0).count();
+
+        let negative_share = negatives as f64 / total;
+        let zero_share = zeros as f64 / total;
+        let round_share = (!large.is_empty()).then(|| round as f64 / large.len() as f64);
+        let benford = benford(&values);
+
+        let mut flags = Vec::new();
+        if benford.as_ref().is_some_and(|b| b.conformity == "nonconformity") {
+            flags.push(PlausibilityFlag::BenfordNonconformity);
+        }
+        if negatives > 0 && negative_share < STRAY_NEGATIVE_SHARE {
+            flags.push(PlausibilityFlag::StrayNegatives);
+        }
+        if zero_share > EXCESS_ZERO_SHARE {
+            flags.push(PlausibilityFlag::ExcessZeros);
+        }
+        if round_share.is_some_and(|share| share > EXCESS_ROUND_SHARE) {
+            flags.push(PlausibilityFlag::ExcessRoundNumbers);
+        }
+
+        checks.push(PlausibilityCheck {
+            column: name.clone(),
+            values: values.len(),
+            negative_share,
+            zero_share,
+            round_share,
+            benford,
+            flags,
+        });
+    }
+
+    Ok(checks)
+}
+
+/// First-digit Benford test, absent when the column is too small or too narrow
+fn benford(values: &[f64]) -> Option<BenfordResult> {
+    let magnitudes: Vec<f64> = values.iter().map(|v| v.abs()).filter(|v| *v > 0.0).collect();
+    if magnitudes.len() < MIN_BENFORD_VALUES {
+        return None;
+    }
+    let min = magnitudes.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = magnitudes.iter().cloned().fold(0.0, f64::max);
+    if (max / min).log10() < MIN_ORDERS_OF_MAGNITUDE {
+        return None;
+    }
+
+    let mut counts = [0usize; 9];
+    for v in &magnitudes {
+        let digit = (v / 10f64.powf(v.log10().floor())) as usize;
+        counts[digit.clamp(1, 9) - 1] += 1;
+    }
+
+    let n = magnitudes.len() as f64;
+    let expected: Vec<f64> = (1..=9).map(|d| (1.0 + 1.0 / d as f64).log10()).collect();
+    let observed: Vec<f64> = counts.iter().map(|c| *c as f64 / n).collect();
+    let chi_square = observed
+        .iter()
+        .zip(&expected)
+        .map(|(o, e)| n * (o - e).powi(2) / e)
+        .sum();
+    let mad = observed.iter().zip(&expected).map(|(o, e)| (o - e).abs()).sum::<f64>() / 9.0;
+    let conformity = if mad < MAD_CLOSE {
+        "close"
+    } else if mad < MAD_ACCEPTABLE {
+        "acceptable"
+    } else if mad < MAD_MARGINAL {
+        "marginal"
+    } else {
+        "nonconformity"
+    };
+
+    Some(BenfordResult {
+        observed,
+        expected,
+        chi_square,
+        mad,
+        conformity: conformity.to_string(),
+    })
+}
@@ -0,0 +1,31 @@
+use anyhow::Result;
+use polars::prelude::*;
+
+use crate::models::response::SamplingInfo;
+
+/// Fixed seed so repeated runs over the same file report the same statistics
+pub const SAMPLE_SEED: u64 = 42;
+
+/// Draw a uniform random sample of `sample_size` rows when the frame has more
+/// than `threshold_rows` rows; otherwise return the frame unchanged.
+pub fn sample_for_insights(
+    df: &DataFrame,
+    threshold_rows: usize,
+    sample_size: usize,
+) -> Result<(DataFrame, Option<SamplingInfo>)> {
+    let total_rows = df.height();
+    if total_rows <= threshold_rows || sample_size == 0 || sample_size >= total_rows {
+        return Ok((df.clone(), None));
+    }
+
+    let sample = df.sample_n_literal(sample_size, false, false, Some(SAMPLE_SEED))?;
+    Ok((
+        sample,
+        Some(SamplingInfo {
+            method: "random".to_string(),
+            sample_rows: sample_size,
+            total_rows,
+            seed: SAMPLE_SEED,
+        }),
+    ))
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Context, Result};
+use polars::prelude::*;
+use std::collections::HashMap;
+
+use crate::models::conversation::DatasetMetadata;
+use crate::models::schema::{ColumnType, SchemaColumn, SchemaOverrides, TypeFix};
+use crate::services::analysis::text;
+
+/// Characters dropped from formatted numbers such as `$1,234.50` or `38%` before
+/// they are parsed
+const NUMBER_DECORATIONS: [char; 6] = ['$', '€', '£', ',', '%', ' '];
+
+/// Read each overridden column as its override type. Values that don't parse as
+/// it become null rather than failing the dataset.
+pub fn apply_overrides(mut df: DataFrame, overrides: &SchemaOverrides) -> Result<DataFrame> {
+    for (name, column_type) in overrides {
+        let Ok(series) = df.column(name) else {
+            log::warn!("⚠️ Schema override for missing column '{}' ignored", name);
+            continue;
+        };
+        let cast = cast_column(series, *column_type)
+            .with_context(|| format!("Failed to read column '{}' as {}", name, column_type))?;
+        df.replace(name, cast)?;
+    }
+    Ok(df)
+}
+
+fn cast_column(series: &Series, column_type: ColumnType) -> Result<Series> {
+    let is_text = series.dtype() == &DataType::Utf8;
+    let cast = match column_type {
+        ColumnType::Integer if is_text => number_values(series)?.cast(&DataType::Float64)?.cast(&DataType::Int64)?,
+        ColumnType::Integer => series.cast(&DataType::Int64)?,
+        ColumnType::Float if is_text => number_values(series)?.cast(&DataType::Float64)?,
+        ColumnType::Float => series.cast(&DataType::Float64)?,
+        ColumnType::Date if is_text => series.utf8()?.as_date(None, false)?.into_series(),
+        ColumnType::Date => series.cast(&DataType::Date)?,
+        ColumnType::Boolean if is_text => boolean_values(series)?,
+        ColumnType::Boolean => series.cast(&DataType::Boolean)?,
+        ColumnType::Text => series.cast(&DataType::Utf8)?,
+    };
+    Ok(cast)
+}
+
+/// String values with currency symbols, thousands separators and percent signs removed
+fn number_values(series: &Series) -> Result<Series> {
+    let values: Utf8Chunked = series
+        .utf8()?
+        .into_iter()
+        .map(|value| value.map(|v| v.trim().replace(NUMBER_DECORATIONS, "")))
+        .collect();
+    Ok(values.with_name(series.name()).into_series())
+}
+
+/// `true`/`false`, `yes`/`no`, `y`/`n`, `t`/`f` and `1`/`0` in any case; anything else is null
+fn boolean_values(series: &Series) -> Result<Series> {
+    let values: BooleanChunked = series
+        .utf8()?
+        .into_iter()
+        .map(|value| match value?.trim().to_lowercase().as_str() {
+            "true" | "yes" | "y" | "t" | "1" => Some(true),
+            "false" | "no" | "n" | "f" | "0" => Some(false),
+            _ => None,
+        })
+        .collect();
+    Ok(values.with_name(series.name()).into_series())
+}
+
+/// Each column's dtype, null ratio and override, with a suggested type for string
+/// columns whose values nearly all parse as a number, date or boolean
+pub fn describe_columns(df: &DataFrame, overrides: &SchemaOverrides) -> Result<Vec<SchemaColumn>> {
+    let rows = df.height();
+    df.get_columns()
+        .iter()
+        .map(|series| {
+            let name = series.name().to_string();
+            let override_type = overrides.get(&name).copied();
+            let suggestion = match override_type {
+                Some(_) => None,
+                None => type_fix(series)?,
+            };
+            Ok(SchemaColumn {
+                dtype: series.dtype().to_string(),
+                null_ratio: if rows == 0 { 0.0 } else { series.null_count() as f64 / rows as f64 },
+                name,
+                override_type,
+                suggestion,
+            })
+        })
+        .collect()
+}
+
+fn type_fix(series: &Series) -> Result<Option<TypeFix>> {
+    let Some((suggested, share)) = text::suggest_type(series)? else {
+        return Ok(None);
+    };
+    let suggested_type: ColumnType = suggested.parse().map_err(|e| anyhow!("{}", e))?;
+    Ok(Some(TypeFix {
+        suggested_type,
+        parseable_share: share,
+        message: format!(
+            "{} is {} but {:.0}% parses as {}",
+            series.name(),
+            series.dtype(),
+            (share * 100.0).floor(),
+            suggested_type
+        ),
+    }))
+}
+
+/// Column names, simplified data types and row count of a dataset
+pub fn dataset_metadata(df: &DataFrame) -> DatasetMetadata {
+    let mut columns = Vec::new();
+    let mut data_types = HashMap::new();
+
+    for col in df.get_column_names() {
+        columns.push(col.to_string());
+
+        if let Ok(series) = df.column(col) {
+            let dtype = match series.dtype() {
+                DataType::Boolean => "boolean",
+                DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => "unsigned integer",
+                DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => "integer",
+                DataType::Float32 | DataType::Float64 => "float",
+                DataType::Utf8 => "string",
+                DataType::Date => "date",
+                DataType::Datetime(_, _) => "datetime",
+                DataType::Time => "time",
+                _ => "unknown",
+            };
+
+            data_types.insert(col.to_string(), dtype.to_string());
+        }
+    }
+
+    DatasetMetadata {
+        columns,
+        row_count: df.height(),
+        data_types,
+    }
+}
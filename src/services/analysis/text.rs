@@ -0,0 +1,202 @@
+use anyhow::Result;
+use polars::prelude::*;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::models::response::{TextPattern, TextProfile};
+
+/// Maximum number of non-null values classified into patterns per column
+pub const SAMPLE_SIZE: usize = 1000;
+
+/// Share of sampled values that must fit one type before the column is suggested to be cast
+const MIN_TYPE_SHARE: f64 = 0.95;
+
+/// Share of letters that must come from one script for it to be reported
+const MIN_SCRIPT_SHARE: f64 = 0.5;
+
+struct TextPatterns {
+    integer: Regex,
+    decimal: Regex,
+    formatted_number: Regex,
+    date: Regex,
+    boolean: Regex,
+    code: Regex,
+    word: Regex,
+}
+
+fn patterns() -> &'static TextPatterns {
+    static PATTERNS: OnceLock<TextPatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| TextPatterns {
+        integer: Regex::new(r"^[+-]?\d+$").unwrap(),
+        decimal: Regex::new(r"^[+-]?(\d+\.\d*|\.\d+)([eE][+-]?\d+)?$").unwrap(),
+        // Thousands separators, currency symbols and percentages
+        formatted_number: Regex::new(r"^[+-]?[$€£]?\s?(\d{1,3}(,\d{3})+|\d+)(\.\d+)?\s?%?$").unwrap(),
+        date: Regex::new(
+            r"^(\d{4}[-/.]\d{1,2}[-/.]\d{1,2}([T ]\d{1,2}:\d{2}(:\d{2}(\.\d+)?)?(Z|[+-]\d{2}:?\d{2})?)?|\d{1,2}[-/.]\d{1,2}[-/.]\d{2,4})$",
+        )
+        .unwrap(),
+        boolean: Regex::new(r"(?i)^(true|false|yes|no|y|n|t|f)$").unwrap(),
+        code: Regex::new(r"^[A-Za-z0-9_./-]*\d[A-Za-z0-9_./-]*$").unwrap(),
+        word: Regex::new(r"^\p{L}+$").unwrap(),
+    })
+}
+
+/// Pattern names in the order they are tried; the first match wins
+const PATTERN_NAMES: [&str; 8] = [
+    "integer",
+    "decimal",
+    "formatted_number",
+    "date",
+    "boolean",
+    "code",
+    "word",
+    "text",
+];
+
+fn classify(value: &str) -> usize {
+    let p = patterns();
+    [&p.integer, &p.decimal, &p.formatted_number, &p.date, &p.boolean, &p.code, &p.word]
+        .iter()
+        .position(|re| re.is_match(value))
+        .unwrap_or(PATTERN_NAMES.len() - 1)
+}
+
+/// Profile a string column: value lengths, the share of values matching common
+/// shapes, whitespace and casing problems, the dominant script, and the type the
+/// column could be cast to when nearly every value is a number, date or boolean.
+pub fn profile_text(series: &Series) -> Result<Option<TextProfile>> {
+    if series.dtype() != &DataType::Utf8 {
+        return Ok(None);
+    }
+    let values = series.utf8()?;
+    let non_null = values.len() - values.null_count();
+    if non_null == 0 {
+        return Ok(None);
+    }
+
+    // Lengths, whitespace and casing over every value
+    let (mut min_length, mut max_length, mut total_length) = (usize::MAX, 0, 0);
+    let mut whitespace_issues = 0;
+    let mut spellings: HashMap<String, HashSet<&str>> = HashMap::new();
+    for value in values.into_iter().flatten() {
+        let length = value.chars().count();
+        min_length = min_length.min(length);
+        max_length = max_length.max(length);
+        total_length += length;
+
+        let trimmed = value.trim();
+        if trimmed.len() != value.len() || trimmed.contains("  ") {
+            whitespace_issues += 1;
+        }
+        spellings.entry(trimmed.to_lowercase()).or_default().insert(trimmed);
+    }
+    let case_variants = spellings.values().filter(|s| s.len() > 1).count();
+
+    // Patterns and script over an evenly spaced sample
+    let sample = sample_values(values, non_null);
+    let counts = pattern_counts(&sample);
+    let share = |count: usize| count as f64 / sample.len().max(1) as f64;
+    let mut patterns: Vec<TextPattern> = PATTERN_NAMES
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(name, count)| TextPattern {
+            pattern: name.to_string(),
+            share: share(count),
+        })
+        .collect();
+    patterns.sort_by(|a, b| b.share.partial_cmp(&a.share).unwrap_or(std::cmp::Ordering::Equal));
+    let suggested_type = castable_type(&counts, sample.len()).map(|(suggested_type, _)| suggested_type);
+
+    Ok(Some(TextProfile {
+        min_length,
+        max_length,
+        avg_length: total_length as f64 / non_null as f64,
+        patterns,
+        whitespace_issues,
+        case_variants,
+        script: dominant_script(&sample).map(str::to_string),
+        suggested_type: suggested_type.map(str::to_string),
+    }))
+}
+
+/// The type a string column could be cast to, with the share of its sampled
+/// non-null values that parse as it, when nearly every value is a number, date
+/// or boolean
+pub fn suggest_type(series: &Series) -> Result<Option<(&'static str, f64)>> {
+    if series.dtype() != &DataType::Utf8 {
+        return Ok(None);
+    }
+    let values = series.utf8()?;
+    let non_null = values.len() - values.null_count();
+    let sample = sample_values(values, non_null);
+    Ok(castable_type(&pattern_counts(&sample), sample.len()))
+}
+
+/// Up to `SAMPLE_SIZE` evenly spaced, trimmed, non-empty values
+fn sample_values(values: &Utf8Chunked, non_null: usize) -> Vec<&str> {
+    let step = (non_null / SAMPLE_SIZE).max(1);
+    values
+        .into_iter()
+        .flatten()
+        .step_by(step)
+        .take(SAMPLE_SIZE)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Values matching each of `PATTERN_NAMES`
+fn pattern_counts(sample: &[&str]) -> [usize; PATTERN_NAMES.len()] {
+    let mut counts = [0usize; PATTERN_NAMES.len()];
+    for value in sample {
+        counts[classify(value)] += 1;
+    }
+    counts
+}
+
+/// The type at least `MIN_TYPE_SHARE` of `total` sampled values parse as, with that share
+fn castable_type(counts: &[usize; PATTERN_NAMES.len()], total: usize) -> Option<(&'static str, f64)> {
+    if total == 0 {
+        return None;
+    }
+    let share = |count: usize| count as f64 / total as f64;
+    [
+        ("integer", share(counts[0])),
+        ("float", share(counts[0] + counts[1] + counts[2])),
+        ("date", share(counts[3])),
+        ("boolean", share(counts[4])),
+    ]
+    .into_iter()
+    .find(|(_, share)| *share >= MIN_TYPE_SHARE)
+}
+
+/// Writing system most of the letters belong to, a rough hint at the language
+fn dominant_script(sample: &[&str]) -> Option<&'static str> {
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    let mut letters = 0;
+    for c in sample.iter().flat_map(|v| v.chars()).filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        let script = match c as u32 {
+            0x0000..=0x024F | 0x1E00..=0x1EFF => "latin",
+            0x0370..=0x03FF => "greek",
+            0x0400..=0x052F => "cyrillic",
+            0x0590..=0x05FF => "hebrew",
+            0x0600..=0x06FF | 0x0750..=0x077F => "arabic",
+            0x0900..=0x097F => "devanagari",
+            0x0E00..=0x0E7F => "thai",
+            0x3040..=0x30FF => "japanese",
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => "hangul",
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => "han",
+            _ => "other",
+        };
+        *counts.entry(script).or_insert(0) += 1;
+    }
+
+    counts
+        .into_iter()
+        .filter(|(script, count)| *script != "other" && *count as f64 / letters as f64 >= MIN_SCRIPT_SHARE)
+        .max_by_key(|(_, count)| *count)
+        .map(|(script, _)| script)
+}
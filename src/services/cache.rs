@@ -0,0 +1,230 @@
+use anyhow::Result;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::cache::{CacheBackendKind, CacheStats};
+use crate::models::job::JobEvent;
+use crate::models::profile::ProfileSettings;
+use crate::models::response::Insights;
+use crate::models::usage::UsageReservation;
+use crate::services::analysis::column_cache::ColumnStatsCache;
+use crate::services::analysis::incremental::AggregateState;
+use crate::services::memory_redis::MemoryRedisService;
+#[cfg(feature = "external-services")]
+use crate::services::redis::RedisService;
+use crate::services::RedisServiceTrait;
+
+/// The cache selected by `CACHE_BACKEND`, so the routes are built once whichever
+/// cache the deployment uses
+#[derive(Clone, Debug)]
+pub enum CacheStore {
+    Memory(MemoryRedisService),
+    #[cfg(feature = "external-services")]
+    Redis(RedisService),
+}
+
+impl CacheStore {
+    pub async fn from_config(config: &Config) -> Result<Self> {
+        match config.cache_backend {
+            CacheBackendKind::Memory => {
+                let service = MemoryRedisService::new(config.memory_cache_max_entries, config.memory_cache_max_bytes);
+                if config.memory_cache_sweep_interval_secs > 0 {
+                    service.spawn_sweeper(Duration::from_secs(config.memory_cache_sweep_interval_secs));
+                }
+                Ok(CacheStore::Memory(service))
+            }
+            #[cfg(feature = "external-services")]
+            CacheBackendKind::Redis => Ok(CacheStore::Redis(RedisService::connect(config).await?)),
+            #[cfg(not(feature = "external-services"))]
+            CacheBackendKind::Redis => anyhow::bail!("CACHE_BACKEND=redis needs a build with the external-services feature"),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RedisServiceTrait for CacheStore {
+    async fn get_insights(&self, job_id: Uuid) -> Result<Option<String>> {
+        match self {
+            CacheStore::Memory(service) => service.get_insights(job_id).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.get_insights(job_id).await,
+        }
+    }
+
+    async fn cache_insights(&self, job_id: Uuid, insights: &Insights) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.cache_insights(job_id, insights).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.cache_insights(job_id, insights).await,
+        }
+    }
+
+    async fn get_aggregate_state(&self, job_id: Uuid) -> Result<Option<String>> {
+        match self {
+            CacheStore::Memory(service) => service.get_aggregate_state(job_id).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.get_aggregate_state(job_id).await,
+        }
+    }
+
+    async fn cache_aggregate_state(&self, job_id: Uuid, state: &AggregateState) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.cache_aggregate_state(job_id, state).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.cache_aggregate_state(job_id, state).await,
+        }
+    }
+
+    async fn get_column_stats(&self, job_id: Uuid) -> Result<Option<String>> {
+        match self {
+            CacheStore::Memory(service) => service.get_column_stats(job_id).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.get_column_stats(job_id).await,
+        }
+    }
+
+    async fn cache_column_stats(&self, job_id: Uuid, stats: &ColumnStatsCache) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.cache_column_stats(job_id, stats).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.cache_column_stats(job_id, stats).await,
+        }
+    }
+
+    async fn get_profile_settings(&self, job_id: Uuid) -> Result<Option<String>> {
+        match self {
+            CacheStore::Memory(service) => service.get_profile_settings(job_id).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.get_profile_settings(job_id).await,
+        }
+    }
+
+    async fn cache_profile_settings(&self, job_id: Uuid, settings: &ProfileSettings) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.cache_profile_settings(job_id, settings).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.cache_profile_settings(job_id, settings).await,
+        }
+    }
+
+    async fn get_ai_response(&self, key: &str) -> Result<Option<String>> {
+        match self {
+            CacheStore::Memory(service) => service.get_ai_response(key).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.get_ai_response(key).await,
+        }
+    }
+
+    async fn cache_ai_response(&self, key: &str, response: &str, expiry_secs: u64) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.cache_ai_response(key, response, expiry_secs).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.cache_ai_response(key, response, expiry_secs).await,
+        }
+    }
+
+    async fn invalidate_insights(&self, job_id: Uuid) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.invalidate_insights(job_id).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.invalidate_insights(job_id).await,
+        }
+    }
+
+    async fn publish_job_event(&self, event: &JobEvent) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.publish_job_event(event).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.publish_job_event(event).await,
+        }
+    }
+
+    async fn insights_pending(&self, job_id: Uuid) -> Result<bool> {
+        match self {
+            CacheStore::Memory(service) => service.insights_pending(job_id).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.insights_pending(job_id).await,
+        }
+    }
+
+    async fn mark_insights_pending(&self, job_id: Uuid, ttl_secs: u64) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.mark_insights_pending(job_id, ttl_secs).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.mark_insights_pending(job_id, ttl_secs).await,
+        }
+    }
+
+    async fn clear_insights_pending(&self, job_id: Uuid) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.clear_insights_pending(job_id).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.clear_insights_pending(job_id).await,
+        }
+    }
+
+    async fn acquire_lock(&self, name: &str, token: &str, ttl: Duration) -> Result<bool> {
+        match self {
+            CacheStore::Memory(service) => service.acquire_lock(name, token, ttl).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.acquire_lock(name, token, ttl).await,
+        }
+    }
+
+    async fn release_lock(&self, name: &str, token: &str) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.release_lock(name, token).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.release_lock(name, token).await,
+        }
+    }
+
+    async fn renew_lock(&self, name: &str, token: &str, ttl: Duration) -> Result<bool> {
+        match self {
+            CacheStore::Memory(service) => service.renew_lock(name, token, ttl).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.renew_lock(name, token, ttl).await,
+        }
+    }
+
+    async fn reserve_usage(&self, name: &str, amount: u64, limit: u64) -> Result<UsageReservation> {
+        match self {
+            CacheStore::Memory(service) => service.reserve_usage(name, amount, limit).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.reserve_usage(name, amount, limit).await,
+        }
+    }
+
+    async fn seed_usage(&self, name: &str, used: u64, ttl: Duration) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.seed_usage(name, used, ttl).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.seed_usage(name, used, ttl).await,
+        }
+    }
+
+    async fn release_usage(&self, name: &str, amount: u64) -> Result<()> {
+        match self {
+            CacheStore::Memory(service) => service.release_usage(name, amount).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => service.release_usage(name, amount).await,
+        }
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        match self {
+            CacheStore::Memory(service) => RedisServiceTrait::stats(service).await,
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => RedisServiceTrait::stats(service).await,
+        }
+    }
+
+    fn backend_name(&self) -> &'static str {
+        match self {
+            CacheStore::Memory(service) => RedisServiceTrait::backend_name(service),
+            #[cfg(feature = "external-services")]
+            CacheStore::Redis(service) => RedisServiceTrait::backend_name(service),
+        }
+    }
+}
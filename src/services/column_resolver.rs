@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use log::info;
+
+/// Normalized edit similarity a fuzzy match needs before it is used without asking
+pub const MIN_SIMILARITY: f64 = 0.75;
+
+/// How far the best fuzzy match must lead the runner-up to count as unambiguous
+const AMBIGUITY_MARGIN: f64 = 0.1;
+
+/// Columns suggested when a name cannot be resolved confidently
+const MAX_CANDIDATES: usize = 3;
+
+/// Columns less similar than this are not worth suggesting
+const MIN_CANDIDATE_SIMILARITY: f64 = 0.4;
+
+/// Map a column name written by the model or a user to a dataset column. Tried in
+/// order: exact match; match ignoring case, whitespace, underscores and hyphens;
+/// the name being one word of exactly one column (`Revenue` → `revenue_usd`); and
+/// the closest column by Levenshtein similarity. Anything less certain is an error
+/// that lists the likeliest columns so the question can be clarified.
+pub fn resolve_column(name: &str, columns: &[String]) -> Result<String> {
+    if columns.iter().any(|c| c == name) {
+        return Ok(name.to_string());
+    }
+
+    let wanted = normalize(name);
+    if let Some(column) = columns.iter().find(|c| normalize(c) == wanted) {
+        return Ok(column.clone());
+    }
+
+    let word_matches: Vec<&String> = columns
+        .iter()
+        .filter(|c| !wanted.is_empty() && words(c).any(|word| normalize(word) == wanted))
+        .collect();
+    let mut ranked: Vec<(&String, f64)> = columns.iter().map(|c| (c, similarity(&wanted, &normalize(c)))).collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    // A word match is only trusted when no other column is a near miss, e.g.
+    // `unit` could be `unit_price` or a misspelled `units`
+    if let [column] = word_matches.as_slice() {
+        let near_miss = ranked.iter().any(|(c, score)| c != column && *score >= MIN_SIMILARITY);
+        if !near_miss {
+            info!("Resolved column '{}' to '{}' by word match", name, column);
+            return Ok((*column).clone());
+        }
+    }
+
+    if word_matches.is_empty() {
+        if let Some((column, score)) = ranked.first() {
+            let runner_up = ranked.get(1).map_or(0.0, |(_, s)| *s);
+            if *score >= MIN_SIMILARITY && score - runner_up >= AMBIGUITY_MARGIN {
+                info!("Resolved column '{}' to '{}' (similarity {:.2})", name, column, score);
+                return Ok((*column).clone());
+            }
+        }
+    }
+
+    // Ambiguous word matches come first, then the closest names
+    let mut candidates: Vec<&String> = word_matches;
+    for (column, _) in &ranked {
+        if candidates.len() >= MAX_CANDIDATES {
+            break;
+        }
+        if !candidates.contains(column) && ranked_score(&ranked, column) >= MIN_CANDIDATE_SIMILARITY {
+            candidates.push(column);
+        }
+    }
+    candidates.truncate(MAX_CANDIDATES);
+
+    if candidates.is_empty() {
+        return Err(anyhow!(
+            "Query referenced column '{}', which is not in the dataset (available columns: {})",
+            name,
+            columns.join(", ")
+        ));
+    }
+    Err(anyhow!(
+        "Query referenced column '{}', which is not in the dataset. Did you mean {}? (available columns: {})",
+        name,
+        candidates.iter().map(|c| format!("'{}'", c)).collect::<Vec<_>>().join(" or "),
+        columns.join(", ")
+    ))
+}
+
+/// Similarity a column was ranked with
+fn ranked_score(ranked: &[(&String, f64)], column: &String) -> f64 {
+    ranked.iter().find(|(c, _)| *c == column).map_or(0.0, |(_, score)| *score)
+}
+
+/// Lowercase with whitespace, underscores and hyphens removed
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Words of a column name split on whitespace, underscores, hyphens and camelCase
+fn words(name: &str) -> impl Iterator<Item = &str> {
+    let mut bounds = Vec::new();
+    let mut start = 0;
+    let mut prev_lower = false;
+    for (i, c) in name.char_indices() {
+        if c.is_whitespace() || c == '_' || c == '-' {
+            bounds.push((start, i));
+            start = i + c.len_utf8();
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            bounds.push((start, i));
+            start = i;
+        }
+        prev_lower = c.is_lowercase();
+    }
+    bounds.push((start, name.len()));
+    bounds.into_iter().map(move |(a, b)| &name[a..b]).filter(|w| !w.is_empty())
+}
+
+/// 1 − Levenshtein distance / length of the longer name
+fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
@@ -1,24 +1,177 @@
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
 use anyhow::{Result, anyhow, Context};
+use chrono::Utc;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use log::{info, warn, error};
+use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use tokio::sync::mpsc::UnboundedSender;
 use uuid::Uuid;
 use polars::prelude::*;
-use polars::io::json::{JsonWriter, JsonFormat};
 
+use crate::config::Config;
+use crate::i18n::{Locale, Message};
 use crate::models::conversation::{
-    ConversationContext, QueryRequest, QueryResponse, DatasetMetadata
+    ConversationContext, ConversationDetail, ConversationExport, ConversationTurn, DatasetMetadata, ExportedTurn,
+    FeedbackRating, FeedbackRecord, FeedbackRequest, Pagination, QueryPlan, QueryProgress, QueryRequest, QueryResponse,
+    SuggestedQuestions, TenantConversationMetrics, TurnFeedback, TurnMetrics, TurnSnapshot, DEFAULT_PAGE_SIZE,
 };
+use crate::models::query::{
+    Cell, ExportFormat, FrameRows, QueryResult, ResultColumn, ResultRows, SaveQueryRequest, SavedQuery, SqlRequest, StructuredQueryRequest,
+};
+use crate::models::job::Job;
+use crate::models::response::Insights;
+use crate::models::usage::{estimate_tokens, TokenUsage};
 use crate::services::ai::AIService;
+use crate::services::analysis::schema::dataset_metadata;
+use crate::services::export::export_dataframe;
+use crate::services::grounding::check_grounding;
+use crate::services::narration::describe_result;
 use crate::services::processor::DataProcessor;
 use crate::services::{S3ServiceTrait, DatabaseServiceTrait, RedisServiceTrait};
-use crate::services::query_translator::{QueryTranslator, StructuredQuery};
+use crate::services::query_translator::{QueryIntent, QueryTranslator, StructuredQuery};
+use crate::services::suggestions::{rule_based_suggestions, MAX_SUGGESTIONS, MIN_SUGGESTIONS};
 
-/// In-memory store for conversation contexts
+/// Guardrails around query execution, so one expensive question gets a "narrow it
+/// down" answer instead of tying up the server
+#[derive(Debug, Clone, Copy)]
+pub struct QueryLimits {
+    /// Wall-clock budget for running a query once its dataset is loaded
+    pub timeout: Duration,
+    /// Largest in-memory dataset a query may run over
+    pub max_dataset_bytes: usize,
+    /// Largest result a query may produce
+    pub max_result_rows: usize,
+}
+
+impl QueryLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.query_timeout_secs),
+            max_dataset_bytes: config.query_max_dataset_bytes,
+            max_result_rows: config.query_max_result_rows,
+        }
+    }
+}
+
+/// How much conversation history goes into a translation prompt and how much of
+/// each result is kept with it. Beyond either prompt limit, older turns are folded
+/// into a running summary.
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryLimits {
+    /// Turns sent verbatim
+    pub max_turns: usize,
+    /// Estimated tokens of the turns sent verbatim
+    pub max_tokens: usize,
+    /// Result rows stored in each turn's snapshot
+    pub snapshot_rows: usize,
+}
+
+impl HistoryLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_turns: config.history_max_turns,
+            max_tokens: config.history_max_tokens,
+            snapshot_rows: config.snapshot_max_rows,
+        }
+    }
+}
+
+/// Characters of each folded answer kept when summarizing without the AI service
+const FALLBACK_SUMMARY_RESPONSE_CHARS: usize = 160;
+
+/// Most result rows, and estimated tokens of them, given to the AI to narrate
+const NARRATION_MAX_ROWS: usize = 200;
+const NARRATION_MAX_TOKENS: usize = 6_000;
+
+/// A query stopped by one of the `QueryLimits` rather than failing on its own
+#[derive(Debug)]
+pub struct QueryTooExpensive(pub String);
+
+impl fmt::Display for QueryTooExpensive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for QueryTooExpensive {}
+
+/// Step of a query turn that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryStage {
+    Pagination,
+    Translation,
+    Execution,
+    Formatting,
+}
+
+/// A query turn that produced no answer, with the conversation it ran in so the
+/// client can carry on in it
+#[derive(Debug)]
+pub struct QueryFailed {
+    pub stage: QueryStage,
+    pub conversation_id: Option<String>,
+    pub query_plan: Option<QueryPlan>,
+    pub source: anyhow::Error,
+}
+
+impl fmt::Display for QueryFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stage {
+            QueryStage::Pagination => write!(f, "Invalid pagination"),
+            QueryStage::Translation => write!(f, "Failed to translate query"),
+            QueryStage::Execution => write!(f, "Failed to execute query"),
+            QueryStage::Formatting => write!(f, "Failed to format the result"),
+        }
+    }
+}
+
+impl std::error::Error for QueryFailed {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Continuation of a paged query result. Carrying the structured query lets later
+/// pages re-run it deterministically instead of translating the question again.
+#[derive(Debug, Serialize, Deserialize)]
+struct ResultCursor {
+    conversation_id: String,
+    job_id: String,
+    result_id: String,
+    query: StructuredQuery,
+    page: usize,
+    page_size: usize,
+}
+
+impl ResultCursor {
+    fn encode(&self) -> Result<String> {
+        Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?))
+    }
+
+    fn decode(cursor: &str) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).context("Cursor is not valid base64")?;
+        serde_json::from_slice(&bytes).context("Cursor is malformed")
+    }
+}
+
+/// Query behind a conversational answer, kept so the full result can be downloaded
 #[derive(Debug, Clone)]
+struct StoredResult {
+    job_id: String,
+    query: StructuredQuery,
+}
+
+/// In-memory store for conversation contexts
+#[derive(Debug, Clone, Default)]
 pub struct InMemoryStore {
     conversations: Arc<Mutex<HashMap<String, ConversationContext>>>,
+    results: Arc<Mutex<HashMap<String, StoredResult>>>,
+    saved_queries: Arc<Mutex<HashMap<String, SavedQuery>>>,
 }
 
 impl InMemoryStore {
@@ -26,6 +179,8 @@ impl InMemoryStore {
     pub fn new() -> Self {
         Self {
             conversations: Arc::new(Mutex::new(HashMap::new())),
+            results: Arc::new(Mutex::new(HashMap::new())),
+            saved_queries: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -45,6 +200,57 @@ impl InMemoryStore {
         
         Ok(conversations.get(id).cloned())
     }
+
+    /// Get all stored conversation contexts
+    pub fn list(&self) -> Result<Vec<ConversationContext>> {
+        let conversations = self.conversations.lock()
+            .map_err(|_| anyhow!("Failed to acquire lock on conversations"))?;
+
+        Ok(conversations.values().cloned().collect())
+    }
+
+    /// Remember the query behind a result under a new result ID
+    fn store_result(&self, result: StoredResult) -> Result<String> {
+        let mut results = self.results.lock()
+            .map_err(|_| anyhow!("Failed to acquire lock on results"))?;
+
+        let id = Uuid::new_v4().to_string();
+        results.insert(id.clone(), result);
+        Ok(id)
+    }
+
+    /// Get the query behind a result
+    fn get_result(&self, id: &str) -> Result<Option<StoredResult>> {
+        let results = self.results.lock()
+            .map_err(|_| anyhow!("Failed to acquire lock on results"))?;
+
+        Ok(results.get(id).cloned())
+    }
+
+    /// Store a saved query
+    fn store_saved_query(&self, query: SavedQuery) -> Result<()> {
+        let mut saved_queries = self.saved_queries.lock()
+            .map_err(|_| anyhow!("Failed to acquire lock on saved queries"))?;
+
+        saved_queries.insert(query.id.clone(), query);
+        Ok(())
+    }
+
+    /// Get a saved query by ID
+    fn get_saved_query(&self, id: &str) -> Result<Option<SavedQuery>> {
+        let saved_queries = self.saved_queries.lock()
+            .map_err(|_| anyhow!("Failed to acquire lock on saved queries"))?;
+
+        Ok(saved_queries.get(id).cloned())
+    }
+
+    /// Get all saved queries
+    fn list_saved_queries(&self) -> Result<Vec<SavedQuery>> {
+        let saved_queries = self.saved_queries.lock()
+            .map_err(|_| anyhow!("Failed to acquire lock on saved queries"))?;
+
+        Ok(saved_queries.values().cloned().collect())
+    }
 }
 
 /// Service for managing conversational interactions with datasets
@@ -59,6 +265,8 @@ where
     ai_service: Option<AIService>,
     data_processor: DataProcessor<S, D, R>,
     query_translator: QueryTranslator,
+    limits: QueryLimits,
+    history_limits: HistoryLimits,
 }
 
 impl<S, D, R> ConversationService<S, D, R>
@@ -71,25 +279,261 @@ where
     pub fn new(
         ai_service: Option<AIService>,
         data_processor: DataProcessor<S, D, R>,
+        limits: QueryLimits,
+        history_limits: HistoryLimits,
     ) -> Self {
-        // Create a new QueryTranslator with a clone of the AIService if available
-        let query_translator = if let Some(ai) = &ai_service {
-            QueryTranslator::new(Some(ai.clone()))
-        } else {
-            QueryTranslator::new(None)
-        };
-        
         Self {
             store: InMemoryStore::new(),
             ai_service,
             data_processor,
-            query_translator,
+            query_translator: QueryTranslator::new(),
+            limits,
+            history_limits,
+        }
+    }
+
+    /// Whether queries are translated and narrated by the AI service rather than rules alone
+    pub fn ai_available(&self) -> bool {
+        self.ai_service.is_some()
+    }
+
+    /// The AI service, when it is available
+    pub fn ai_service(&self) -> Option<&AIService> {
+        self.ai_service.as_ref()
+    }
+
+    /// Timeout and size limits applied to every query
+    pub fn query_limits(&self) -> QueryLimits {
+        self.limits
+    }
+
+    /// Run a SQL statement against a job's dataset without AI translation
+    pub async fn execute_sql(&self, request: &SqlRequest) -> Result<QueryResult> {
+        info!("Executing SQL for job {}: {}", request.job_id, request.sql);
+        let df = self.load_guarded(&request.job_id).await?;
+        let translator = self.query_translator.clone();
+        let sql = request.sql.clone();
+        let (df, truncated) = self.run_guarded(move || translator.execute_sql(df, &sql)).await?;
+        Ok(query_result(df, truncated))
+    }
+
+    /// Validate a structured query against a job's dataset and run it, skipping
+    /// AI translation
+    pub async fn execute_structured(&self, request: &StructuredQueryRequest) -> Result<QueryResult> {
+        info!("Executing structured query for job {}: {}", request.job_id, request.query);
+        let df = self.load_guarded(&request.job_id).await?;
+
+        let structured_query = self
+            .query_translator
+            .parse_structured_query(request.query.clone(), &dataset_metadata(&df))?;
+        let (result, _) = self.apply_guarded(df, structured_query, false).await?;
+        Ok(query_result(result, false))
+    }
+
+    /// Validate a structured query against its dataset and save it under a name.
+    /// The column-resolved query is saved, so re-runs skip fuzzy matching.
+    pub async fn save_query(&self, request: &SaveQueryRequest) -> Result<SavedQuery> {
+        let name = request.name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("Saved queries need a name"));
+        }
+        let metadata = self.get_dataset_metadata(&request.job_id).await?;
+        let structured_query = self
+            .query_translator
+            .parse_structured_query(request.query.clone(), &metadata)?;
+
+        let saved = SavedQuery {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            description: request.description.clone(),
+            job_id: request.job_id.clone(),
+            query: structured_query.to_json(),
+            created_at: Utc::now(),
+        };
+        self.store.store_saved_query(saved.clone())?;
+        info!("Saved query '{}' ({}) for job {}", saved.name, saved.id, saved.job_id);
+        Ok(saved)
+    }
+
+    /// Get a saved query by ID
+    pub fn get_saved_query(&self, id: &str) -> Result<Option<SavedQuery>> {
+        self.store.get_saved_query(id)
+    }
+
+    /// Saved queries, oldest first, optionally only those saved against one dataset
+    pub fn list_saved_queries(&self, job_id: Option<&str>) -> Result<Vec<SavedQuery>> {
+        let mut queries: Vec<SavedQuery> = self
+            .store
+            .list_saved_queries()?
+            .into_iter()
+            .filter(|query| job_id.is_none_or(|job_id| query.job_id == job_id))
+            .collect();
+        queries.sort_by_key(|query| query.created_at);
+        Ok(queries)
+    }
+
+    /// Run a saved query on its dataset, or on `job_id` when given
+    pub async fn run_saved_query(&self, saved: &SavedQuery, job_id: Option<&str>) -> Result<QueryResult> {
+        let request = StructuredQueryRequest {
+            job_id: job_id.unwrap_or(&saved.job_id).to_string(),
+            query: saved.query.clone(),
+        };
+        self.execute_structured(&request).await
+    }
+
+    /// Load a job's dataset, refusing datasets larger than the query limit allows
+    async fn load_guarded(&self, job_id: &str) -> Result<DataFrame> {
+        let job = self.job(job_id).await?;
+        let s3_service = self.data_processor.get_s3_service();
+        let df = self.query_translator.load_dataset(&job, s3_service).await?;
+        let size = df.estimated_size();
+        if size > self.limits.max_dataset_bytes {
+            return Err(QueryTooExpensive(format!(
+                "the dataset takes {:.1} MB in memory, over the {:.1} MB query limit",
+                size as f64 / (1024.0 * 1024.0),
+                self.limits.max_dataset_bytes as f64 / (1024.0 * 1024.0)
+            ))
+            .into());
+        }
+        Ok(df)
+    }
+
+    /// Run CPU-bound query work on the blocking pool within the query timeout, so a
+    /// slow query never stalls the async workers. Work that times out is abandoned;
+    /// it finishes in the background and its result is dropped.
+    async fn run_guarded<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        match tokio::time::timeout(self.limits.timeout, tokio::task::spawn_blocking(work)).await {
+            Ok(joined) => joined.context("Query execution panicked")?,
+            Err(_) => Err(QueryTooExpensive(format!(
+                "it ran longer than {} seconds",
+                self.limits.timeout.as_secs()
+            ))
+            .into()),
+        }
+    }
+
+    /// Apply a structured query under the timeout and result size limits, with its
+    /// optimized plan when `explain` is set
+    async fn apply_guarded(&self, df: DataFrame, query: StructuredQuery, explain: bool) -> Result<(DataFrame, Option<String>)> {
+        let translator = self.query_translator.clone();
+        let (result, plan) = self
+            .run_guarded(move || {
+                let plan = explain.then(|| translator.explain(&df.schema(), &query)).transpose()?;
+                Ok((translator.apply_operations(df, &query)?, plan))
+            })
+            .await?;
+        if result.height() > self.limits.max_result_rows {
+            return Err(QueryTooExpensive(format!(
+                "it returns {} rows, over the {} row limit",
+                result.height(),
+                self.limits.max_result_rows
+            ))
+            .into());
+        }
+        Ok((result, plan))
+    }
+
+    /// Load a job's dataset and run a structured query on it within the query limits
+    async fn run_query(&self, query: &StructuredQuery, job_id: &str, explain: bool) -> Result<(DataFrame, Option<String>)> {
+        let df = self.load_guarded(job_id).await?;
+        self.apply_guarded(df, query.clone(), explain).await
+    }
+
+    /// Fold the oldest recent turns into the conversation summary once the recent
+    /// turns exceed the history limits, keeping the newest half of the budget
+    /// verbatim. The AI service writes the summary when available; otherwise the
+    /// folded questions are listed with shortened answers. Returns the tokens spent.
+    async fn compact_history(&self, context: &mut ConversationContext, ai_service: Option<&AIService>) -> TokenUsage {
+        let limits = self.history_limits;
+        let recent = context.recent_turns();
+        let tokens: usize = recent.iter().map(|turn| turn.estimated_tokens()).sum();
+        if recent.len() <= limits.max_turns && tokens <= limits.max_tokens {
+            return TokenUsage::default();
+        }
+
+        let (mut kept, mut kept_tokens) = (0, 0);
+        for turn in recent.iter().rev() {
+            if kept > 0 && (kept >= (limits.max_turns / 2).max(1) || kept_tokens + turn.estimated_tokens() > limits.max_tokens / 2) {
+                break;
+            }
+            kept += 1;
+            kept_tokens += turn.estimated_tokens();
+        }
+        let folded = &recent[..recent.len() - kept];
+        if folded.is_empty() {
+            return TokenUsage::default();
+        }
+
+        let ai_summary = match ai_service {
+            Some(ai_service) => match ai_service.summarize_conversation(context.summary.as_deref(), folded).await {
+                Ok(summary) => Some(summary),
+                Err(e) => {
+                    warn!("Failed to summarize conversation {}, listing earlier questions instead: {}", context.id, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let (summary, usage) = ai_summary.unwrap_or_else(|| {
+            (fallback_summary(context.summary.as_deref(), folded, limits.max_tokens / 2), TokenUsage::default())
+        });
+        info!("Summarized {} earlier turns of conversation {}", folded.len(), context.id);
+        let folded = folded.len();
+        context.fold_into_summary(summary, folded);
+        usage
+    }
+
+    /// The job and conversation a query will run against, taking a valid cursor
+    /// over the request's own fields as `process_query` does
+    pub fn query_target(&self, request: &QueryRequest) -> (String, Option<String>) {
+        match request.cursor.as_deref().map(ResultCursor::decode) {
+            Some(Ok(cursor)) => (cursor.job_id, Some(cursor.conversation_id)),
+            _ => (request.job_id.clone(), request.conversation_id.clone()),
         }
     }
 
     /// Process a natural language query
-    pub async fn process_query(&self, request: QueryRequest) -> Result<QueryResponse> {
+    pub async fn process_query(&self, request: QueryRequest, locale: Locale) -> Result<QueryResponse> {
+        self.process_query_with_progress(request, locale, None).await
+    }
+
+    /// Process a natural language query, reporting each finished step on `progress`
+    pub async fn process_query_with_progress(
+        &self,
+        mut request: QueryRequest,
+        locale: Locale,
+        progress: Option<&UnboundedSender<QueryProgress>>,
+    ) -> Result<QueryResponse> {
+        // A client that went away stops listening, not the turn
+        let report = |step: QueryProgress| {
+            if let Some(progress) = progress {
+                let _ = progress.send(step);
+            }
+        };
         info!("Processing query: {}", request.query);
+        let turn_start = Instant::now();
+        let mut metrics = TurnMetrics::default();
+
+        // A cursor continues an earlier result and overrides the page fields
+        let cursor = match request.cursor.as_deref().map(ResultCursor::decode).transpose() {
+            Ok(cursor) => cursor,
+            Err(e) => return Err(invalid_pagination(&request, e)),
+        };
+        let (page, page_size) = match &cursor {
+            Some(cursor) => (cursor.page, cursor.page_size),
+            None => match request.page_window() {
+                Ok(window) => window,
+                Err(e) => return Err(invalid_pagination(&request, e)),
+            },
+        };
+        if let Some(cursor) = &cursor {
+            request.conversation_id = Some(cursor.conversation_id.clone());
+            request.job_id = cursor.job_id.clone();
+        }
         
         // Get or create conversation context
         let mut context = match &request.conversation_id {
@@ -111,32 +555,69 @@ where
             }
         };
         
-        // Translate the query to a structured query
-        let structured_query = match self.query_translator.translate_query(&request.query, &context).await {
-            Ok(query) => query,
+        // A tenant out of AI tokens is answered by rules for the rest of the day
+        let ai_service = self.tenant_ai(context.tenant_id.as_deref()).await;
+
+        // Keep the history sent to the translator within the limits
+        if cursor.is_none() {
+            metrics.token_usage += self.compact_history(&mut context, ai_service.as_ref()).await;
+        }
+
+        // Translate the query to a structured query, unless a cursor already carries it
+        let translation_start = Instant::now();
+        let result_id = cursor.as_ref().map(|cursor| cursor.result_id.clone());
+        let translation = match cursor {
+            Some(cursor) => Ok((cursor.query, Default::default())),
+            None => self.query_translator.translate_query(&request.query, &context, ai_service.as_ref()).await,
+        };
+        metrics.translation_ms = translation_start.elapsed().as_millis() as u64;
+        let structured_query = match translation {
+            Ok((query, usage)) => {
+                metrics.token_usage += usage;
+                query
+            },
             Err(e) => {
                 error!("Failed to translate query: {}", e);
-                return Ok(QueryResponse {
-                    conversation_id: context.id,
-                    response: format!("I couldn't understand your query: {}", e),
-                    data: None,
-                    visualization_data: None,
-                });
+                self.record_usage(&context, metrics.token_usage).await;
+                return Err(QueryFailed {
+                    stage: QueryStage::Translation,
+                    conversation_id: Some(context.id),
+                    query_plan: None,
+                    source: e,
+                }
+                .into());
             }
         };
+        self.record_usage(&context, metrics.token_usage).await;
+        report(QueryProgress::Translated {
+            query_plan: QueryPlan {
+                structured_query: structured_query.to_json(),
+                polars_plan: None,
+            },
+        });
 
         // Execute the structured query
-        let s3_service = self.data_processor.get_s3_service();
-        let df = match self.query_translator.execute_query(&structured_query, &context.job_id, s3_service).await {
-            Ok(df) => df,
+        let execution_start = Instant::now();
+        let execution = self.run_query(&structured_query, &context.job_id, request.explain).await;
+        metrics.execution_ms = execution_start.elapsed().as_millis() as u64;
+        let mut query_plan = QueryPlan {
+            structured_query: structured_query.to_json(),
+            polars_plan: None,
+        };
+        let df = match execution {
+            Ok((df, polars_plan)) => {
+                query_plan.polars_plan = polars_plan;
+                df
+            }
             Err(e) => {
                 error!("Failed to execute query: {}", e);
-                return Ok(QueryResponse {
-                    conversation_id: context.id,
-                    response: format!("I couldn't execute your query: {}", e),
-                    data: None,
-                    visualization_data: None,
-                });
+                return Err(QueryFailed {
+                    stage: QueryStage::Execution,
+                    conversation_id: Some(context.id),
+                    query_plan: Some(query_plan),
+                    source: e,
+                }
+                .into());
             }
         };
 
@@ -144,192 +625,423 @@ where
         if df.height() == 0 {
             return Ok(QueryResponse {
                 conversation_id: context.id,
-                response: "No data found for your query.".to_string(),
-                data: Some(json!({"result": "empty"})),
+                response: Message::NoDataFound.localize(locale),
+                data: Some(ResultRows(df)),
                 visualization_data: None,
+                pagination: None,
+                result_id: None,
+                query_plan: Some(query_plan),
+                grounding: None,
             });
         }
 
-        // Convert the DataFrame to JSON using JsonWriter
-        let json_result = match {
-            let mut buf = Vec::new();
-            let mut df_mut = df.clone();
-            JsonWriter::new(&mut buf)
-                .with_json_format(JsonFormat::Json)
-                .finish(&mut df_mut)
-                .context("Failed to write DataFrame to JSON")?;
-            let json_string = std::str::from_utf8(&buf)
-                .context("Failed to convert JSON bytes to string")?
-                .to_string();
-            serde_json::from_str::<Value>(&json_string)
-                .context("Failed to parse JSON string into Value")
-        } {
-            Ok(json_value) => json_value,
+        let result_id = match result_id {
+            Some(id) => id,
+            None => self.store.store_result(StoredResult {
+                job_id: context.job_id.clone(),
+                query: structured_query.clone(),
+            })?,
+        };
+
+        // The answer is written from the full result, not just this page
+        let full_result = df.clone();
+
+        // Only the requested page is serialized
+        let total_rows = df.height();
+        let offset = (page - 1).saturating_mul(page_size);
+        let df = df.slice(offset.min(total_rows) as i64, page_size);
+        let next_cursor = if offset + df.height() < total_rows {
+            let next = ResultCursor {
+                conversation_id: context.id.clone(),
+                job_id: context.job_id.clone(),
+                result_id: result_id.clone(),
+                query: structured_query.clone(),
+                page: page + 1,
+                page_size,
+            };
+            Some(next.encode()?)
+        } else {
+            None
+        };
+        let pagination = Pagination {
+            page,
+            page_size,
+            total_rows,
+            returned_rows: df.height(),
+            next_cursor,
+        };
+
+        // The page is written out from the frame with the response; only the rows
+        // kept in the conversation's history are converted to JSON here
+        let snapshot_rows = match dataframe_to_json(&df.slice(0, self.history_limits.snapshot_rows)) {
+            Ok(rows) => rows,
             Err(e) => {
                 error!("Failed to convert DataFrame to JSON: {}", e);
-                return Ok(QueryResponse {
-                    conversation_id: context.id,
-                    response: format!("I couldn't format the results: {}", e),
-                    data: None,
-                    visualization_data: None,
-                });
+                return Err(QueryFailed {
+                    stage: QueryStage::Formatting,
+                    conversation_id: Some(context.id),
+                    query_plan: Some(query_plan),
+                    source: e,
+                }
+                .into());
             }
         };
 
-        // Prepare visualization_data if intent is Visualize
-        let mut visualization_data = None;
-        use crate::services::query_translator::QueryIntent;
-        if let QueryIntent::Visualize = structured_query.intent {
-            if let Some(data_array) = json_result.as_array() {
-                if !data_array.is_empty() {
-                    let first_row = &data_array[0];
-                    if let Some(obj) = first_row.as_object() {
-                        // Try numeric columns (for averages, distributions)
-                        let mut numeric_cols: Vec<String> = Vec::new();
-                        for (k, v) in obj.iter() {
-                            if v.is_number() || (v.is_string() && v.as_str().unwrap().trim().parse::<f64>().is_ok()) {
-                                numeric_cols.push(k.clone());
-                            }
-                        }
-                        if !numeric_cols.is_empty() {
-                            // Compute averages for each numeric column
-                            let mut averages = Vec::new();
-                            for col in &numeric_cols {
-                                let mut sum = 0.0;
-                                let mut count = 0.0;
-                                for row in data_array.iter() {
-                                    if let Some(val) = row.get(col) {
-                                        if val.is_number() {
-                                            if let Some(f) = val.as_f64() {
-                                                sum += f;
-                                                count += 1.0;
-                                            }
-                                        } else if val.is_string() {
-                                            if let Ok(f) = val.as_str().unwrap().trim().parse::<f64>() {
-                                                sum += f;
-                                                count += 1.0;
-                                            }
-                                        }
-                                    }
-                                }
-                                if count > 0.0 {
-                                    averages.push(sum / count);
-                                } else {
-                                    averages.push(0.0);
-                                }
-                            }
-                            let chart_json = serde_json::json!({
-                                "type": "bar",
-                                "data": {
-                                    "labels": numeric_cols,
-                                    "datasets": [{
-                                        "label": "Average",
-                                        "data": averages
-                                    }]
-                                },
-                                "options": {}
-                            });
-                            visualization_data = Some(chart_json);
-                        } else {
-                            // Try categorical columns (value counts)
-                            let mut categorical_cols: Vec<String> = Vec::new();
-                            for (k, v) in obj.iter() {
-                                if v.is_string() {
-                                    categorical_cols.push(k.clone());
-                                }
-                            }
-                            if !categorical_cols.is_empty() {
-                                let col = &categorical_cols[0];
-                                let mut counts = std::collections::HashMap::new();
-                                for row in data_array.iter() {
-                                    if let Some(val) = row.get(col) {
-                                        if let Some(s) = val.as_str() {
-                                            *counts.entry(s.to_string()).or_insert(0) += 1;
-                                        }
-                                    }
-                                }
-                                let mut labels = Vec::new();
-                                let mut values = Vec::new();
-                                for (label, value) in counts.iter() {
-                                    labels.push(label.clone());
-                                    values.push(*value);
-                                }
-                                let chart_json = serde_json::json!({
-                                    "type": "bar",
-                                    "data": {
-                                        "labels": labels,
-                                        "datasets": [{
-                                            "label": format!("{} count", col),
-                                            "data": values
-                                        }]
-                                    },
-                                    "options": {}
-                                });
-                                visualization_data = Some(chart_json);
-                            } else {
-                                // Fallback: no suitable columns found, show a table config
-                                let columns: Vec<String> = obj.keys().cloned().collect();
-                                let rows: Vec<Vec<String>> = data_array.iter().map(|row| {
-                                    columns.iter().map(|col| {
-                                        row.get(col).map(|v| v.to_string()).unwrap_or_default()
-                                    }).collect()
-                                }).collect();
-                                let chart_json = serde_json::json!({
-                                    "type": "table",
-                                    "data": {
-                                        "columns": columns,
-                                        "rows": rows
-                                    },
-                                    "options": {}
-                                });
-                                visualization_data = Some(chart_json);
-                            }
+        let visualization_data = visualization_data(&structured_query.intent, &df);
+
+        // Generate a dynamic AI response; later pages of a result were narrated with the first
+        let narration_start = Instant::now();
+        let continuing = request.cursor.is_some();
+        let narrating = ai_service.is_some() && !continuing;
+        report(QueryProgress::Executed {
+            data: ResultRows(df.clone()),
+            visualization_data: visualization_data.clone(),
+            pagination: pagination.clone(),
+            result_id: result_id.clone(),
+            draft: narrating.then(|| {
+                describe_result(&structured_query, &full_result, context.dataset_metadata.row_count, locale)
+            }),
+        });
+        let mut grounding = None;
+        let ai_response = match (&ai_service, continuing) {
+            (Some(ai_service), false) => {
+                let result = narration_rows(&full_result.slice(0, NARRATION_MAX_ROWS));
+                let prompt = json!({
+                    "query": request.query,
+                    "intent": format!("{:?}", structured_query.intent),
+                    "result_columns": df.get_column_names(),
+                    "result_row_count": total_rows,
+                    "result_truncated": result.as_array().map_or(0, Vec::len) < total_rows,
+                    "result": result,
+                    "response_language": locale.language_name(),
+                });
+                match ai_service.narrate_result(&prompt).await {
+                    Ok((narration, usage)) => {
+                        metrics.token_usage += usage;
+                        self.record_usage(&context, usage).await;
+                        let check = check_grounding(&narration, &result, &request.query, &[total_rows as f64]);
+                        if !check.grounded {
+                            warn!(
+                                "Narration for conversation {} states figures not found in the result: {}",
+                                context.id,
+                                check.unverified_figures.join(", ")
+                            );
                         }
+                        grounding = Some(check);
+                        narration
+                    }
+                    Err(e) => {
+                        error!("AIService failed to narrate the result: {}", e);
+                        describe_result(&structured_query, &full_result, context.dataset_metadata.row_count, locale)
                     }
                 }
             }
-        }
+            (None, false) => describe_result(&structured_query, &full_result, context.dataset_metadata.row_count, locale),
+            (_, true) => Message::ResultsReady.localize(locale),
+        };
 
-        // Generate a dynamic AI response
-        let ai_response = if let Some(ai_service) = &self.ai_service {
-            // Compose a prompt with query, intent, and a sample of the data
-            let prompt = json!({
-                "query": request.query,
-                "intent": format!("{:?}", structured_query.intent),
-                "result_sample": json_result.as_array().and_then(|arr| arr.get(0)).cloned().unwrap_or(json!({})),
-                "result_columns": df.get_column_names(),
-                "result_row_count": df.height(),
-            });
-            match ai_service.generate_data_summary(&prompt).await {
-                Ok(summary) => summary.summary,
-                Err(e) => {
-                    error!("AIService failed to generate summary: {}", e);
-                    "Here are the results for your query.".to_string()
+        metrics.narration_ms = narration_start.elapsed().as_millis() as u64;
+        metrics.total_ms = turn_start.elapsed().as_millis() as u64;
+        info!(
+            "Turn timings for conversation {}: translation={}ms execution={}ms narration={}ms total={}ms tokens={}",
+            context.id, metrics.translation_ms, metrics.execution_ms, metrics.narration_ms,
+            metrics.total_ms, metrics.token_usage.total_tokens()
+        );
+
+        // Add the real AI response to the conversation; paging through a result is not a new turn
+        if !continuing {
+            context.last_query = Some(query_plan.structured_query.clone());
+            context.last_result_columns = result_columns(&df);
+            let snapshot = TurnSnapshot {
+                columns: context.last_result_columns.clone(),
+                rows: snapshot_rows,
+                total_rows,
+                visualization_data: visualization_data.clone(),
+            };
+            context.add_turn(
+                request.query.clone(),
+                ai_response.clone(),
+                Some(query_plan.structured_query.clone()),
+                Some(snapshot),
+                Some(metrics),
+            );
+            // Keep feedback given on earlier turns while this one was running
+            if let Some(stored) = self.store.get(&context.id)? {
+                for (turn, stored) in context.history.iter_mut().zip(stored.history) {
+                    turn.feedback = turn.feedback.take().or(stored.feedback);
                 }
             }
-        } else {
-            "Here are the results for your query.".to_string()
-        };
-
-        // Add the real AI response to the conversation
-        context.add_turn(request.query.clone(), ai_response.clone());
-        self.store.store(context.clone())?;
+            self.store.store(context.clone())?;
+        }
 
         Ok(QueryResponse {
             conversation_id: context.id,
             response: ai_response,
-            data: Some(json_result),
+            data: Some(ResultRows(df)),
             visualization_data,
+            pagination: Some(pagination),
+            result_id: Some(result_id),
+            query_plan: Some(query_plan),
+            grounding,
         })
     }
 
+    /// Re-run the query behind a conversational answer and serialize every row of the
+    /// result as a file; `None` when the result ID is unknown
+    pub async fn export_result(&self, result_id: &str, format: ExportFormat) -> Result<Option<Vec<u8>>> {
+        let stored = match self.store.get_result(result_id)? {
+            Some(stored) => stored,
+            None => return Ok(None),
+        };
+
+        info!("Exporting result {} for job {} as {:?}", result_id, stored.job_id, format);
+        let (mut df, _) = self.run_query(&stored.query, &stored.job_id, false).await?;
+        export_dataframe(&mut df, format).map(Some)
+    }
+
+    /// The job a conversational result was computed from; `None` when the result ID is unknown
+    pub fn result_job(&self, result_id: &str) -> Result<Option<String>> {
+        Ok(self.store.get_result(result_id)?.map(|stored| stored.job_id))
+    }
+
+    /// Get a conversation with its turn history and summed turn metrics
+    pub fn get_conversation(&self, conversation_id: &str) -> Result<Option<ConversationDetail>> {
+        Ok(self.store.get(conversation_id)?.map(|conversation| {
+            let mut totals = TurnMetrics::default();
+            for metrics in conversation.history.iter().filter_map(|turn| turn.metrics.as_ref()) {
+                totals.accumulate(metrics);
+            }
+            ConversationDetail { conversation, totals }
+        }))
+    }
+
+    /// Gather every question of a conversation with its answer, the first `rows`
+    /// rows of its result and its chart config for sharing. Turn snapshots are used
+    /// when they hold enough rows; otherwise the question's query is re-run. `None`
+    /// when the conversation is unknown; a turn whose result cannot be reproduced
+    /// keeps its text and records why.
+    pub async fn export_conversation(&self, conversation_id: &str, rows: usize) -> Result<Option<ConversationExport>> {
+        let Some(conversation) = self.store.get(conversation_id)? else {
+            return Ok(None);
+        };
+        info!("Exporting conversation {} ({} turns)", conversation.id, conversation.history.len());
+        // Loaded on the first turn that has to be re-run
+        let dataset = tokio::sync::OnceCell::new();
+
+        let mut turns = Vec::with_capacity(conversation.history.len());
+        for turn in &conversation.history {
+            let mut exported = ExportedTurn {
+                query: turn.query.clone(),
+                response: turn.response.clone(),
+                timestamp: turn.timestamp,
+                structured_query: turn.structured_query.clone(),
+                columns: Vec::new(),
+                data: None,
+                total_rows: None,
+                visualization_data: None,
+                error: None,
+            };
+            let snapshot = turn.snapshot.as_ref().filter(|snapshot| {
+                snapshot.rows.as_array().is_some_and(|kept| kept.len() >= rows.min(snapshot.total_rows))
+            });
+            if let Some(snapshot) = snapshot {
+                let kept = snapshot.rows.as_array().into_iter().flatten().take(rows).cloned().collect();
+                exported.columns = snapshot.columns.iter().map(|c| c.name.clone()).collect();
+                exported.data = Some(Value::Array(kept));
+                exported.total_rows = Some(snapshot.total_rows);
+                exported.visualization_data = snapshot.visualization_data.clone();
+            } else if let Some(query) = &turn.structured_query {
+                let dataset = dataset
+                    .get_or_init(|| async { self.load_guarded(&conversation.job_id).await.map_err(|e| format!("{:#}", e)) })
+                    .await;
+                match self.export_turn_result(dataset, query, &conversation.dataset_metadata).await {
+                    Err(e) => exported.error = Some(format!("{:#}", e)),
+                    Ok((intent, df)) => {
+                        // Charts are drawn from the first page, as in the original answer
+                        exported.visualization_data = visualization_data(&intent, &df.slice(0, DEFAULT_PAGE_SIZE));
+                        exported.data = Some(dataframe_to_json(&df.slice(0, rows))?);
+                        exported.columns = df.get_column_names().iter().map(|c| c.to_string()).collect();
+                        exported.total_rows = Some(df.height());
+                    }
+                }
+            }
+            turns.push(exported);
+        }
+
+        Ok(Some(ConversationExport {
+            conversation_id: conversation.id,
+            job_id: conversation.job_id,
+            created_at: conversation.created_at,
+            exported_at: Utc::now(),
+            summary: conversation.summary,
+            turns,
+        }))
+    }
+
+    /// Run the structured query of one exported turn against the loaded dataset
+    async fn export_turn_result(
+        &self,
+        dataset: &std::result::Result<DataFrame, String>,
+        query: &Value,
+        metadata: &DatasetMetadata,
+    ) -> Result<(QueryIntent, DataFrame)> {
+        let df = dataset.as_ref().map_err(|e| anyhow!("{}", e))?.clone();
+        let query = self.query_translator.parse_structured_query(query.clone(), metadata)?;
+        let intent = query.intent.clone();
+        let (result, _) = self.apply_guarded(df, query, false).await?;
+        Ok((intent, result))
+    }
+
+    /// Rate a turn of a conversation, replacing any earlier rating of it. `None` when
+    /// the conversation is unknown; an error when it has no such turn.
+    pub fn submit_feedback(&self, conversation_id: &str, request: &FeedbackRequest) -> Result<Option<ConversationTurn>> {
+        let Some(mut conversation) = self.store.get(conversation_id)? else {
+            return Ok(None);
+        };
+        let turns = conversation.history.len();
+        let turn = conversation.history.get_mut(request.turn_index).ok_or_else(|| {
+            anyhow!("Conversation has no turn {} (it has {} turns, numbered from 0)", request.turn_index, turns)
+        })?;
+        turn.feedback = Some(TurnFeedback {
+            rating: request.rating,
+            comment: request.comment.as_deref().map(str::trim).filter(|c| !c.is_empty()).map(String::from),
+            submitted_at: Utc::now(),
+        });
+        let turn = turn.clone();
+        self.store.store(conversation)?;
+        info!("Recorded {:?} feedback on turn {} of conversation {}", request.rating, request.turn_index, conversation_id);
+        Ok(Some(turn))
+    }
+
+    /// Every rated turn, oldest rating first, optionally only those with one rating
+    pub fn export_feedback(&self, rating: Option<FeedbackRating>) -> Result<Vec<FeedbackRecord>> {
+        let mut records = Vec::new();
+        for conversation in self.store.list()? {
+            for (turn_index, turn) in conversation.history.iter().enumerate() {
+                let Some(feedback) = &turn.feedback else { continue };
+                if rating.is_some_and(|rating| rating != feedback.rating) {
+                    continue;
+                }
+                records.push(FeedbackRecord {
+                    conversation_id: conversation.id.clone(),
+                    job_id: conversation.job_id.clone(),
+                    turn_index,
+                    query: turn.query.clone(),
+                    response: turn.response.clone(),
+                    structured_query: turn.structured_query.clone(),
+                    rating: feedback.rating,
+                    comment: feedback.comment.clone(),
+                    submitted_at: feedback.submitted_at,
+                });
+            }
+        }
+        records.sort_by_key(|record| record.submitted_at);
+        Ok(records)
+    }
+
+    /// Example questions for a dataset, generated by the AI service from its schema and
+    /// cached insights and topped up from the rule-based suggestions when it returns
+    /// too few or is unavailable
+    pub async fn suggest_questions(&self, job_id: &str, locale: Locale) -> Result<SuggestedQuestions> {
+        let metadata = self.get_dataset_metadata(job_id).await?;
+        let insights = match Uuid::parse_str(job_id) {
+            Ok(id) => self.data_processor.insights(id).await?
+                .and_then(|json| serde_json::from_str::<Insights>(&json).ok()),
+            Err(_) => None,
+        };
+        let rules = rule_based_suggestions(&metadata, insights.as_ref());
+
+        let job = self.job_owner(job_id).await.unwrap_or_default();
+        let tenant_id = job.as_ref().map(|job| job.tenant_id.as_str());
+        let mut questions = Vec::new();
+        if let Some(ai_service) = self.tenant_ai(tenant_id).await {
+            let dataset = json!({
+                "columns": metadata.columns,
+                "data_types": metadata.data_types,
+                "row_count": metadata.row_count,
+                "insights": insights.as_ref().map(insights_digest),
+                "response_language": locale.language_name(),
+            });
+            match ai_service.suggest_questions(&dataset).await {
+                Ok((suggested, usage)) => {
+                    questions = suggested;
+                    let user_id = job.as_ref().map(|job| job.user_id.as_str());
+                    if let Err(e) = self.data_processor.usage_ledger().record(job_id, tenant_id, user_id, None, usage).await {
+                        warn!("Failed to record AI usage for job {}: {}", job_id, e);
+                    }
+                }
+                Err(e) => warn!("Failed to generate suggested questions for job {}, using rules instead: {}", job_id, e),
+            }
+        }
+
+        if questions.is_empty() {
+            return Ok(SuggestedQuestions {
+                job_id: job_id.to_string(),
+                questions: rules,
+                source: "rules".to_string(),
+            });
+        }
+        questions.truncate(MAX_SUGGESTIONS);
+        for question in rules {
+            if questions.len() >= MIN_SUGGESTIONS {
+                break;
+            }
+            if !questions.contains(&question) {
+                questions.push(question);
+            }
+        }
+
+        Ok(SuggestedQuestions {
+            job_id: job_id.to_string(),
+            questions,
+            source: "ai".to_string(),
+        })
+    }
+
+    /// Aggregate turn latency and AI spend across all conversations, per tenant
+    pub fn tenant_metrics(&self) -> Result<Vec<TenantConversationMetrics>> {
+        let mut by_tenant: HashMap<String, TenantConversationMetrics> = HashMap::new();
+
+        for conversation in self.store.list()? {
+            let tenant = conversation.tenant_id.clone().unwrap_or_else(|| "unknown".to_string());
+            let entry = by_tenant.entry(tenant.clone()).or_insert_with(|| TenantConversationMetrics {
+                tenant,
+                ..Default::default()
+            });
+            entry.conversations += 1;
+            for metrics in conversation.history.iter().filter_map(|turn| turn.metrics.as_ref()) {
+                entry.turns += 1;
+                entry.totals.accumulate(metrics);
+            }
+        }
+
+        let mut tenants: Vec<TenantConversationMetrics> = by_tenant.into_values().collect();
+        for tenant in &mut tenants {
+            if tenant.turns > 0 {
+                let turns = tenant.turns as f64;
+                tenant.avg_translation_ms = tenant.totals.translation_ms as f64 / turns;
+                tenant.avg_execution_ms = tenant.totals.execution_ms as f64 / turns;
+                tenant.avg_narration_ms = tenant.totals.narration_ms as f64 / turns;
+                tenant.avg_total_ms = tenant.totals.total_ms as f64 / turns;
+            }
+        }
+        tenants.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        Ok(tenants)
+    }
+
     /// Create a new conversation context for a job
     async fn create_context(&self, job_id: &str) -> Result<ConversationContext> {
         // Get dataset metadata from the data processor
         let metadata = self.get_dataset_metadata(job_id).await?;
         
+        // Attribute the conversation to the dataset owner and their tenant
+        let (user_id, tenant_id) = match self.job_owner(job_id).await? {
+            Some(job) => (Some(job.user_id), Some(job.tenant_id)),
+            None => (None, None),
+        };
+
         // Create a new context
-        let context = ConversationContext::new(job_id.to_string(), metadata);
+        let context = ConversationContext::new(job_id.to_string(), user_id, tenant_id, metadata);
         
         // Store the context
         self.store.store(context.clone())?;
@@ -337,117 +1049,66 @@ where
         Ok(context)
     }
 
-    /// Get metadata about a dataset
+    /// The job a dataset belongs to, which records where its files are stored
+    async fn job(&self, job_id: &str) -> Result<Job> {
+        let uuid = Uuid::parse_str(job_id).map_err(|e| anyhow!("Invalid job ID: {}", e))?;
+        self.data_processor
+            .get_db_service()
+            .get_job(uuid)
+            .await?
+            .ok_or_else(|| anyhow!("Job {} not found", job_id))
+    }
+
+    /// The job behind a dataset, for the user who uploaded it and their tenant
+    async fn job_owner(&self, job_id: &str) -> Result<Option<Job>> {
+        Ok(match Uuid::parse_str(job_id) {
+            Ok(uuid) => self.data_processor.get_db_service().get_job(uuid).await?,
+            Err(_) => None,
+        })
+    }
+
+    /// The AI service for a tenant's requests, or `None` when there is none or the
+    /// tenant spent its AI tokens for today, so rules answer instead
+    async fn tenant_ai(&self, tenant_id: Option<&str>) -> Option<AIService> {
+        match tenant_id {
+            Some(tenant_id) => self.data_processor.tenant_ai(self.ai_service.as_ref(), tenant_id).await,
+            None => self.ai_service.clone(),
+        }
+    }
+
+    /// Attribute the AI spend of a turn to its conversation, tenant and the dataset owner
+    async fn record_usage(&self, context: &ConversationContext, usage: TokenUsage) {
+        let ledger = self.data_processor.usage_ledger();
+        let (tenant_id, user_id) = (context.tenant_id.as_deref(), context.user_id.as_deref());
+        if let Err(e) = ledger.record(&context.job_id, tenant_id, user_id, Some(&context.id), usage).await {
+            warn!("Failed to record AI usage for conversation {}: {}", context.id, e);
+        }
+    }
+
+    /// Get metadata about a dataset: the copy recorded when the job was processed,
+    /// or, for jobs processed before it was recorded, read from the dataset
     async fn get_dataset_metadata(&self, job_id: &str) -> Result<DatasetMetadata> {
-        // Parse the job ID
-        let uuid = match Uuid::parse_str(job_id) {
-            Ok(id) => id,
-            Err(e) => return Err(anyhow!("Invalid job ID: {}", e)),
-        };
-        
-        // Try to get insights from Redis cache first
         info!("Attempting to get dataset metadata for job {}", job_id);
-        let s3_service = self.data_processor.get_s3_service();
-        let file_key = format!("uploads/{}.csv", uuid);
-        let bucket = std::env::var("S3_BUCKET").unwrap_or_else(|_| "data-pipeline-bucket".to_string());
-        
-        // Try multiple approaches to get the file from MemoryS3Service with fallbacks
-        let csv_data = match s3_service.get_object("", &file_key).await {
-            Ok(data) => {
-                info!("Successfully loaded CSV data for metadata with direct key: {} bytes", data.len());
-                data
-            },
-            Err(_) => {
-                // First fallback: Try with default bucket
-                match s3_service.get_object("default-bucket", &file_key).await {
-                    Ok(data) => {
-                        info!("Successfully loaded CSV data for metadata with default bucket: {} bytes", data.len());
-                        data
-                    },
-                    Err(_) => {
-                        // Second fallback: Try with configured bucket
-                        match s3_service.get_object(&bucket, &file_key).await {
-                            Ok(data) => {
-                                info!("Successfully loaded CSV data for metadata with configured bucket: {} bytes", data.len());
-                                data
-                            },
-                            Err(_) => {
-                                // Third fallback: Try with just the UUID
-                                let simple_key = format!("{}.csv", uuid);
-                                match s3_service.get_object("", &simple_key).await {
-                                    Ok(data) => {
-                                        info!("Successfully loaded CSV data for metadata with simple key: {} bytes", data.len());
-                                        data
-                                    },
-                                    Err(e) => {
-                                        // If all attempts fail, return the error
-                                        error!("Failed to load CSV data for metadata after all fallback attempts: {}", e);
-                                        return Err(anyhow!("Failed to load CSV data: {}", e));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        };
-        
-        // Parse the CSV to get column names and data types
-        let df = match CsvReader::new(std::io::Cursor::new(csv_data))
-            .infer_schema(Some(100))
-            .has_header(true)
-            .finish() {
-            Ok(df) => {
-                info!("Successfully parsed CSV data for metadata: {} rows, {} columns", df.height(), df.width());
-                df
-            },
-            Err(e) => {
-                error!("Failed to parse CSV data for metadata: {}", e);
-                return Err(anyhow!("Failed to parse CSV data: {}", e));
-            }
-        };
-        
-        // Extract column names and data types
-        let mut columns = Vec::new();
-        let mut data_types = HashMap::new();
-        
-        for col in df.get_column_names() {
-            columns.push(col.to_string());
-            
-            if let Ok(series) = df.column(col) {
-                let dtype = match series.dtype() {
-                    DataType::Boolean => "boolean",
-                    DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => "unsigned integer",
-                    DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => "integer",
-                    DataType::Float32 | DataType::Float64 => "float",
-                    DataType::Utf8 => "string",
-                    DataType::Date => "date",
-                    DataType::Datetime(_, _) => "datetime",
-                    DataType::Time => "time",
-                    _ => "unknown",
-                };
-                
-                data_types.insert(col.to_string(), dtype.to_string());
-            }
+        let job = self.job(job_id).await?;
+        if let Some(metadata) = job.dataset_metadata {
+            return Ok(metadata);
         }
-        
-        let metadata = DatasetMetadata {
-            columns,
-            row_count: df.height(),
-            data_types,
-        };
-        
+        let s3_service = self.data_processor.get_s3_service();
+        let df = self.query_translator.load_dataset(&job, s3_service).await?;
+        let metadata = dataset_metadata(&df);
+
         info!("Generated metadata for job {}: {} columns, {} rows", job_id, metadata.columns.len(), metadata.row_count);
         Ok(metadata)
     }
 
     /// Execute a natural language query
+    #[allow(dead_code)]
     async fn execute_query(&self, query: &str, context: &ConversationContext) -> Result<(String, Value)> {
         info!("Executing query: {}", query);
         
         // Translate the query to a structured query
-        let structured_query = match self.query_translator.translate_query(query, context).await {
-            Ok(query) => {
+        let structured_query = match self.query_translator.translate_query(query, context, self.ai_service.as_ref()).await {
+            Ok((query, _usage)) => {
                 info!("Translated query: {:?}", query);
                 query
             },
@@ -458,8 +1119,9 @@ where
         };
         
         // Execute the structured query
+        let job = self.job(&context.job_id).await?;
         let s3_service = self.data_processor.get_s3_service();
-        let df = match self.query_translator.execute_query(&structured_query, &context.job_id, s3_service).await {
+        let df = match self.query_translator.execute_query(&structured_query, &job, s3_service).await {
             Ok(df) => {
                 info!("Query executed successfully");
                 df
@@ -475,29 +1137,8 @@ where
             return Ok(("No data found for your query.".to_string(), json!({"result": "empty"})));
         }
         
-        // Convert the DataFrame to JSON using JsonWriter
-        let json_result = match {
-            // Create a buffer
-            let mut buf = Vec::new();
-            
-            // Create a mutable clone of the DataFrame
-            let mut df_mut = df.clone();
-            
-            // Write DataFrame to buffer as JSON
-            JsonWriter::new(&mut buf)
-                .with_json_format(JsonFormat::Json)
-                .finish(&mut df_mut)
-                .context("Failed to write DataFrame to JSON")?;
-            
-            // Convert buffer to UTF-8 string
-            let json_string = std::str::from_utf8(&buf)
-                .context("Failed to convert JSON bytes to string")?
-                .to_string();
-            
-            // Parse string into JSON Value
-            serde_json::from_str::<Value>(&json_string)
-                .context("Failed to parse JSON string into Value")
-        } {
+        // Convert the rows to JSON objects
+        let json_result = match dataframe_to_json(&df) {
             Ok(json_value) => json_value,
             Err(e) => {
                 error!("Failed to convert DataFrame to JSON: {}", e);
@@ -512,6 +1153,7 @@ where
     }
     
     /// Generate a natural language response based on the query and results
+    #[allow(dead_code)]
     fn generate_nl_response(&self, query: &str, structured_query: &StructuredQuery, df: &DataFrame) -> String {
         // In a real implementation, this would use the AI service to generate a natural language response
         // For now, we'll generate a simple response based on the query intent
@@ -533,6 +1175,207 @@ where
             crate::services::query_translator::QueryIntent::Visualize => {
                 format!("Here's a visualization for your query: '{}'", query)
             },
+            crate::services::query_translator::QueryIntent::Pivot => {
+                format!("Here is the pivot table for your query: '{}'", query)
+            },
+        }
+    }
+}
+
+/// Summary built without the AI service: the previous summary followed by each
+/// folded question and the start of its answer, dropping the oldest lines to stay
+/// within `max_tokens`
+/// The parts of the insights worth asking about, small enough for a prompt
+fn insights_digest(insights: &Insights) -> Value {
+    let mut correlations: Vec<(&String, &f64)> = insights.correlations.iter().flatten().collect();
+    correlations.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+    let mut associations: Vec<_> = insights.categorical_associations.iter().flatten().collect();
+    associations.sort_by(|a, b| b.correlation_ratio.total_cmp(&a.correlation_ratio));
+
+    json!({
+        "categorical_columns": insights.data_summary.categorical_columns,
+        "identifier_columns": insights.data_summary.identifier_columns,
+        "pii_columns": insights.data_summary.pii_columns,
+        "top_correlations": correlations.iter().take(3)
+            .map(|(pair, r)| json!({"columns": pair, "correlation": r}))
+            .collect::<Vec<_>>(),
+        "top_associations": associations.iter().take(3)
+            .map(|a| json!({
+                "category": a.categorical_column,
+                "measure": a.numeric_column,
+                "correlation_ratio": a.correlation_ratio,
+            }))
+            .collect::<Vec<_>>(),
+        "anomalies": insights.anomalies.iter().flatten().take(3)
+            .map(|a| json!({"date": a.date_column, "measure": a.measure_column, "period": a.period}))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn fallback_summary(previous: Option<&str>, turns: &[ConversationTurn], max_tokens: usize) -> String {
+    let mut lines: Vec<String> = previous.map(|summary| summary.lines().map(String::from).collect()).unwrap_or_default();
+    for turn in turns {
+        let mut answer: String = turn.response.chars().take(FALLBACK_SUMMARY_RESPONSE_CHARS).collect();
+        if answer.len() < turn.response.len() {
+            answer.push('…');
+        }
+        lines.push(format!("- Asked \"{}\": {}", turn.query.trim(), answer.trim()));
+    }
+    while lines.len() > 1 && estimate_tokens(&lines.join("\n")) > max_tokens {
+        lines.remove(0);
+    }
+    lines.join("\n")
+}
+
+/// Rows of a result for the AI to narrate from, as many as fit the token budget
+fn narration_rows(df: &DataFrame) -> Value {
+    let mut rows = dataframe_to_json(df)
+        .ok()
+        .and_then(|rows| rows.as_array().cloned())
+        .unwrap_or_default();
+    while rows.len() > 1 && estimate_tokens(&Value::Array(rows.clone()).to_string()) > NARRATION_MAX_TOKENS {
+        rows.truncate(rows.len() / 2);
+    }
+    Value::Array(rows)
+}
+
+/// Failure of a request whose page, page size or cursor is unusable
+fn invalid_pagination(request: &QueryRequest, source: anyhow::Error) -> anyhow::Error {
+    QueryFailed {
+        stage: QueryStage::Pagination,
+        conversation_id: request.conversation_id.clone(),
+        query_plan: None,
+        source,
+    }
+    .into()
+}
+
+/// Name and type of each column of a query result
+fn result_columns(df: &DataFrame) -> Vec<ResultColumn> {
+    df.get_columns()
+        .iter()
+        .map(|s| ResultColumn {
+            name: s.name().to_string(),
+            data_type: format!("{:?}", s.dtype()),
+        })
+        .collect()
+}
+
+/// Rows and schema of a query result
+fn query_result(df: DataFrame, truncated: bool) -> QueryResult {
+    QueryResult {
+        columns: result_columns(&df),
+        row_count: df.height(),
+        rows: ResultRows(df),
+        truncated,
+    }
+}
+
+/// Chart config for a result: bars of column averages or category counts for
+/// visualizations, a heatmap for pivot tables. Read from the frame, with cells
+/// written as in the response's rows.
+fn visualization_data(intent: &QueryIntent, df: &DataFrame) -> Option<Value> {
+    match intent {
+        QueryIntent::Visualize if df.height() > 0 => Some(bar_chart(df)),
+        // Pivot tables render naturally as heatmaps
+        QueryIntent::Pivot => heatmap_chart(df),
+        _ => None,
+    }
+}
+
+/// Averages of the numeric columns, else counts of the first text column's
+/// values, else the rows as a table
+fn bar_chart(df: &DataFrame) -> Value {
+    let columns = df.get_columns();
+
+    let numeric: Vec<&Series> = columns.iter().filter(|s| s.dtype().is_numeric()).collect();
+    if !numeric.is_empty() {
+        let labels: Vec<&str> = numeric.iter().map(|s| s.name()).collect();
+        let averages: Vec<f64> = numeric.iter().map(|s| s.mean().unwrap_or(0.0)).collect();
+        return json!({
+            "type": "bar",
+            "data": {
+                "labels": labels,
+                "datasets": [{
+                    "label": "Average",
+                    "data": averages
+                }]
+            },
+            "options": {}
+        });
+    }
+
+    // Strings, dates and datetimes are written as text
+    let categorical = columns
+        .iter()
+        .find(|s| !matches!(s.dtype(), DataType::Boolean | DataType::Null | DataType::List(_)));
+    if let Some(series) = categorical {
+        let mut counts = std::collections::HashMap::new();
+        for index in 0..series.len() {
+            if let Value::String(value) = cell(series, index) {
+                *counts.entry(value).or_insert(0) += 1;
+            }
         }
+        let (labels, values): (Vec<String>, Vec<usize>) = counts.into_iter().unzip();
+        return json!({
+            "type": "bar",
+            "data": {
+                "labels": labels,
+                "datasets": [{
+                    "label": format!("{} count", series.name()),
+                    "data": values
+                }]
+            },
+            "options": {}
+        });
     }
+
+    // Fallback: no suitable columns found, show a table config
+    let rows: Vec<Vec<String>> = (0..df.height())
+        .map(|index| columns.iter().map(|series| cell(series, index).to_string()).collect())
+        .collect();
+    json!({
+        "type": "table",
+        "data": {
+            "columns": df.get_column_names(),
+            "rows": rows
+        },
+        "options": {}
+    })
+}
+
+/// Heatmap of a pivot table: the first column labels the rows, every other column
+/// is one column of cells
+fn heatmap_chart(df: &DataFrame) -> Option<Value> {
+    let (index, columns) = df.get_columns().split_first()?;
+
+    let y_labels: Vec<Value> = (0..df.height()).map(|row| cell(index, row)).collect();
+    let values: Vec<Vec<Value>> = (0..df.height())
+        .map(|row| columns.iter().map(|series| cell(series, row)).collect())
+        .collect();
+    let x_labels: Vec<&str> = columns.iter().map(|series| series.name()).collect();
+
+    Some(json!({
+        "type": "heatmap",
+        "data": {
+            "x_labels": x_labels,
+            "y_labels": y_labels,
+            "values": values
+        },
+        "options": {}
+    }))
+}
+
+/// A value of a result column as JSON, null when it can't be read
+fn cell(series: &Series, index: usize) -> Value {
+    series
+        .get(index)
+        .ok()
+        .and_then(|value| serde_json::to_value(Cell(value)).ok())
+        .unwrap_or(Value::Null)
+}
+
+/// Convert a DataFrame into a JSON array of row objects
+fn dataframe_to_json(df: &DataFrame) -> Result<Value> {
+    serde_json::to_value(FrameRows(df)).context("Failed to convert DataFrame to JSON")
 }
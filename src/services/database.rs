@@ -1,10 +1,38 @@
 #[cfg(feature = "external-services")]
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
+#[cfg(feature = "external-services")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "external-services")]
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+#[cfg(feature = "external-services")]
+use sqlx::Row;
+#[cfg(feature = "external-services")]
+use std::time::{Duration, SystemTime};
 #[cfg(feature = "external-services")]
-use sqlx::postgres::PgPool;
 use uuid::Uuid;
 
-use crate::models::job::{Job, JobStatus, NewJob};
+#[cfg(feature = "external-services")]
+use crate::config::Config;
+#[cfg(feature = "external-services")]
+use crate::models::batch::{Batch, NewBatch};
+#[cfg(feature = "external-services")]
+use crate::models::conversation::{metadata_from_json, DatasetMetadata};
+#[cfg(feature = "external-services")]
+use crate::models::job::{InvalidTransition, Job, JobFilter, JobStatus, NewJob};
+#[cfg(feature = "external-services")]
+use crate::models::response::Insights;
+#[cfg(feature = "external-services")]
+use crate::models::schema::{overrides_from_json, SchemaOverrides};
+#[cfg(feature = "external-services")]
+use crate::models::usage::{TokenUsage, UsageEntry, UsageQuery};
+
+/// How long startup waits for a Postgres connection before giving up
+#[cfg(feature = "external-services")]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[cfg(feature = "external-services")]
+const JOB_COLUMNS: &str =
+    "id, user_id, tenant_id, request_id, bucket, file_key, content_hash, batch_id, schema_overrides::text AS schema_overrides, status, error_message, attempts, low_memory, dataset_metadata::text AS dataset_metadata, started_at, finished_at, created_at, updated_at";
 
 #[cfg(feature = "external-services")]
 #[derive(Clone, Debug)]
@@ -14,48 +42,378 @@ pub struct DatabaseService {
 
 #[cfg(feature = "external-services")]
 impl DatabaseService {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    /// Open a pool to `DATABASE_URL` and check the server answers, so a wrong URL
+    /// or an unreachable database fails startup instead of the first upload
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.database_max_connections)
+            .acquire_timeout(CONNECT_TIMEOUT)
+            .connect(&config.database_url)
+            .await
+            .context("Failed to connect to Postgres at DATABASE_URL")?;
+        sqlx::query("SELECT 1")
+            .execute(&pool)
+            .await
+            .context("Postgres did not answer the connectivity check")?;
+        log::info!("🐘 Connected to Postgres (pool of up to {} connections)", config.database_max_connections);
+        Ok(Self { pool })
     }
-    
+
+    /// Apply the migrations under `migrations/postgres` that haven't run yet.
+    /// sqlx holds an advisory lock meanwhile, so instances starting together
+    /// don't apply them twice.
+    pub async fn migrate(&self) -> Result<()> {
+        sqlx::migrate!("./migrations/postgres")
+            .run(&self.pool)
+            .await
+            .context("Failed to migrate the Postgres job database")
+    }
+
     /// Create a new job in the database
     pub async fn create_job(&self, new_job: NewJob) -> Result<Uuid> {
         let job_id = Uuid::new_v4();
         let status = JobStatus::Queued.to_string();
-        
-        sqlx::query!("INSERT INTO jobs (id, user_id, file_key, status) VALUES ($1, $2, $3, $4) RETURNING id",
-            job_id,
-            new_job.user_id,
-            new_job.file_key,
-            status
+
+        sqlx::query(
+            "INSERT INTO jobs (id, user_id, tenant_id, request_id, bucket, file_key, content_hash, batch_id, status)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
         )
-        .fetch_one(&self.pool)
-        .await?;
-        
+            .bind(job_id)
+            .bind(&new_job.user_id)
+            .bind(&new_job.tenant_id)
+            .bind(&new_job.request_id)
+            .bind(&new_job.bucket)
+            .bind(&new_job.file_key)
+            .bind(&new_job.content_hash)
+            .bind(new_job.batch_id)
+            .bind(status)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert job")?;
+
         Ok(job_id)
     }
-    
+
+    /// Record a new batch of uploads
+    pub async fn create_batch(&self, new_batch: NewBatch) -> Result<Uuid> {
+        let batch_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO batches (id, user_id, tenant_id) VALUES ($1, $2, $3)")
+            .bind(batch_id)
+            .bind(&new_batch.user_id)
+            .bind(&new_batch.tenant_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to insert batch")?;
+
+        Ok(batch_id)
+    }
+
+    /// Remove a batch no job was created for
+    pub async fn delete_batch(&self, batch_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM batches WHERE id = $1")
+            .bind(batch_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to delete batch")?;
+
+        Ok(())
+    }
+
+    /// Get a batch by ID
+    pub async fn get_batch(&self, batch_id: Uuid) -> Result<Option<Batch>> {
+        let row = sqlx::query("SELECT id, user_id, tenant_id, created_at FROM batches WHERE id = $1")
+            .bind(batch_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to load batch")?;
+
+        row.map(|row| -> Result<Batch> {
+            Ok(Batch {
+                id: row.try_get("id")?,
+                user_id: row.try_get("user_id")?,
+                tenant_id: row.try_get("tenant_id")?,
+                created_at: row.try_get::<Option<DateTime<Utc>>, _>("created_at")?.map(SystemTime::from),
+            })
+        })
+        .transpose()
+    }
+
+    /// The jobs of a batch, oldest first
+    pub async fn list_batch_jobs(&self, batch_id: Uuid) -> Result<Vec<Job>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM jobs WHERE batch_id = $1 ORDER BY created_at, id", JOB_COLUMNS))
+            .bind(batch_id)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to list batch jobs")?;
+
+        rows.iter().map(job_from_row).collect()
+    }
+
     /// Get a job by ID
     pub async fn get_job(&self, job_id: Uuid) -> Result<Option<Job>> {
-        let job = sqlx::query_as!(Job,
-            "SELECT id, user_id, file_key, status as \"status: JobStatus\", created_at, updated_at FROM jobs WHERE id = $1",
-            job_id
-        )
-        .fetch_optional(&self.pool)
-        .await?;
-        
-        Ok(job)
+        let row = sqlx::query(&format!("SELECT {} FROM jobs WHERE id = $1", JOB_COLUMNS))
+            .bind(job_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to load job")?;
+
+        row.as_ref().map(job_from_row).transpose()
+    }
+
+    /// Up to `limit` of the jobs `filter` matches ordered by id, starting after `after`
+    pub async fn list_jobs(&self, filter: &JobFilter, after: Option<Uuid>, limit: usize) -> Result<Vec<Job>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM jobs \
+             WHERE ($1::text IS NULL OR tenant_id = $1) AND ($2::text IS NULL OR user_id = $2) \
+             AND ($3::uuid IS NULL OR id > $3) ORDER BY id LIMIT $4",
+            JOB_COLUMNS
+        ))
+        .bind(filter.tenant_id.as_deref())
+        .bind(filter.user_id.as_deref())
+        .bind(after)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list jobs")?;
+
+        rows.iter().map(job_from_row).collect()
     }
-    
-    /// Update job status
+
+    /// Jobs a tenant created since `since`
+    pub async fn count_jobs_since(&self, tenant_id: &str, since: DateTime<Utc>) -> Result<u64> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM jobs WHERE tenant_id = $1 AND created_at >= $2")
+            .bind(tenant_id)
+            .bind(since)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to count jobs")?;
+        Ok(count as u64)
+    }
+
+    /// Update job status; starting processing counts an attempt and finishing
+    /// records when it ended. The update only matches jobs in a status the new one
+    /// may follow, so a concurrent change can't be overwritten by an illegal one.
     pub async fn update_job_status(&self, job_id: Uuid, status: JobStatus) -> Result<()> {
-        sqlx::query!("UPDATE jobs SET status = $1, updated_at = NOW() WHERE id = $2",
-            status.to_string(),
-            job_id
+        let set = match status {
+            JobStatus::Processing => {
+                "status = $1, attempts = attempts + 1, started_at = NOW(), finished_at = NULL,
+                 error_message = NULL, updated_at = NOW()"
+            }
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled => {
+                "status = $1, finished_at = NOW(), updated_at = NOW()"
+            }
+            JobStatus::Queued => "status = $1, updated_at = NOW()",
+        };
+        let result = sqlx::query(&format!(
+            "UPDATE jobs SET {} WHERE id = $2 AND status IN ({})",
+            set,
+            status_list(status.allowed_from())
+        ))
+            .bind(status.to_string())
+            .bind(job_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update job status")?;
+
+        if result.rows_affected() == 0 {
+            return Err(self.rejected_transition(job_id, status).await);
+        }
+        Ok(())
+    }
+
+    /// Mark a job failed with the reason
+    pub async fn fail_job(&self, job_id: Uuid, error_message: &str) -> Result<()> {
+        let result = sqlx::query(&format!(
+            "UPDATE jobs SET status = $1, error_message = $2, finished_at = NOW(), updated_at = NOW()
+             WHERE id = $3 AND status IN ({})",
+            status_list(JobStatus::Failed.allowed_from())
+        ))
+            .bind(JobStatus::Failed.to_string())
+            .bind(error_message)
+            .bind(job_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to record job failure")?;
+
+        if result.rows_affected() == 0 {
+            return Err(self.rejected_transition(job_id, JobStatus::Failed).await);
+        }
+        Ok(())
+    }
+
+    /// Why a status update matched no row: the job is missing or its status
+    /// doesn't allow the change
+    async fn rejected_transition(&self, job_id: Uuid, to: JobStatus) -> anyhow::Error {
+        match self.get_job(job_id).await {
+            Ok(Some(job)) => InvalidTransition { job_id, from: job.status, to }.into(),
+            Ok(None) => anyhow!("Job not found"),
+            Err(e) => e,
+        }
+    }
+
+    /// Record whether the job is being processed in low-memory mode
+    pub async fn set_job_low_memory(&self, job_id: Uuid, low_memory: bool) -> Result<()> {
+        sqlx::query("UPDATE jobs SET low_memory = $1 WHERE id = $2")
+            .bind(low_memory)
+            .bind(job_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to record the processing mode")?;
+
+        Ok(())
+    }
+
+    /// Record the shape of a job's dataset
+    pub async fn set_job_dataset_metadata(&self, job_id: Uuid, metadata: Option<&DatasetMetadata>) -> Result<()> {
+        sqlx::query("UPDATE jobs SET dataset_metadata = $1::jsonb WHERE id = $2")
+            .bind(metadata.map(serde_json::to_string).transpose()?)
+            .bind(job_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to record the dataset metadata")?;
+
+        Ok(())
+    }
+
+    /// Replace the types a job's columns are read as
+    pub async fn update_job_schema(&self, job_id: Uuid, overrides: &SchemaOverrides) -> Result<()> {
+        sqlx::query("UPDATE jobs SET schema_overrides = $1::jsonb, updated_at = NOW() WHERE id = $2")
+            .bind(serde_json::to_string(overrides)?)
+            .bind(job_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update job schema")?;
+
+        Ok(())
+    }
+
+    /// Point a job at a new source file
+    pub async fn update_job_file(&self, job_id: Uuid, file_key: &str, content_hash: &str) -> Result<()> {
+        sqlx::query("UPDATE jobs SET file_key = $1, content_hash = $2, updated_at = NOW() WHERE id = $3")
+            .bind(file_key)
+            .bind(content_hash)
+            .bind(job_id)
+            .execute(&self.pool)
+            .await
+            .context("Failed to update job file")?;
+
+        Ok(())
+    }
+
+    /// Store a job's insights, replacing earlier ones
+    pub async fn save_insights(&self, job_id: Uuid, insights: &Insights) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO insights (job_id, insights) VALUES ($1, $2::jsonb)
+             ON CONFLICT (job_id) DO UPDATE SET insights = EXCLUDED.insights, updated_at = NOW()",
         )
+        .bind(job_id)
+        .bind(serde_json::to_string(insights)?)
         .execute(&self.pool)
-        .await?;
-        
+        .await
+        .context("Failed to save insights")?;
+
         Ok(())
     }
+
+    /// A job's stored insights as JSON
+    pub async fn get_insights(&self, job_id: Uuid) -> Result<Option<String>> {
+        sqlx::query_scalar("SELECT insights::text FROM insights WHERE job_id = $1")
+            .bind(job_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("Failed to load insights")
+    }
+
+    /// Add an entry's usage to the row for its day, job, tenant, user and conversation
+    pub async fn record_usage(&self, entry: &UsageEntry) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO usage (date, job_id, tenant_id, user_id, conversation_id, prompt_tokens, completion_tokens, estimated_cost_usd)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+             ON CONFLICT (date, job_id, COALESCE(tenant_id, ''), COALESCE(user_id, ''), COALESCE(conversation_id, ''))
+             DO UPDATE SET prompt_tokens = usage.prompt_tokens + EXCLUDED.prompt_tokens,
+                 completion_tokens = usage.completion_tokens + EXCLUDED.completion_tokens,
+                 estimated_cost_usd = usage.estimated_cost_usd + EXCLUDED.estimated_cost_usd",
+        )
+        .bind(entry.date)
+        .bind(&entry.job_id)
+        .bind(entry.tenant_id.as_deref())
+        .bind(entry.user_id.as_deref())
+        .bind(entry.conversation_id.as_deref())
+        .bind(entry.usage.prompt_tokens as i64)
+        .bind(entry.usage.completion_tokens as i64)
+        .bind(entry.usage.estimated_cost_usd)
+        .execute(&self.pool)
+        .await
+        .context("Failed to record usage")?;
+
+        Ok(())
+    }
+
+    /// Usage rows matching the query
+    pub async fn list_usage(&self, query: &UsageQuery) -> Result<Vec<UsageEntry>> {
+        let rows = sqlx::query(
+            "SELECT date, job_id, tenant_id, user_id, conversation_id, prompt_tokens, completion_tokens, estimated_cost_usd
+             FROM usage
+             WHERE ($1::text IS NULL OR job_id = $1) AND ($2::text IS NULL OR tenant_id = $2)
+             AND ($3::text IS NULL OR user_id = $3) AND ($4::text IS NULL OR conversation_id = $4)
+             AND ($5::date IS NULL OR date >= $5) AND ($6::date IS NULL OR date <= $6)",
+        )
+        .bind(query.job_id.as_deref())
+        .bind(query.tenant_id.as_deref())
+        .bind(query.user_id.as_deref())
+        .bind(query.conversation_id.as_deref())
+        .bind(query.from)
+        .bind(query.to)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to list usage")?;
+
+        rows.iter().map(usage_from_row).collect()
+    }
+}
+
+/// Statuses as a quoted SQL list for `IN (...)`; they come from the enum, never from input
+#[cfg(feature = "external-services")]
+fn status_list(statuses: &[JobStatus]) -> String {
+    statuses.iter().map(|status| format!("'{}'", status)).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(feature = "external-services")]
+fn usage_from_row(row: &PgRow) -> Result<UsageEntry> {
+    Ok(UsageEntry {
+        date: row.try_get("date")?,
+        job_id: row.try_get("job_id")?,
+        tenant_id: row.try_get("tenant_id")?,
+        user_id: row.try_get("user_id")?,
+        conversation_id: row.try_get("conversation_id")?,
+        usage: TokenUsage {
+            prompt_tokens: row.try_get::<i64, _>("prompt_tokens")? as u64,
+            completion_tokens: row.try_get::<i64, _>("completion_tokens")? as u64,
+            estimated_cost_usd: row.try_get("estimated_cost_usd")?,
+        },
+    })
+}
+
+#[cfg(feature = "external-services")]
+fn job_from_row(row: &PgRow) -> Result<Job> {
+    Ok(Job {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        tenant_id: row.try_get("tenant_id")?,
+        request_id: row.try_get("request_id")?,
+        bucket: row.try_get("bucket")?,
+        file_key: row.try_get("file_key")?,
+        content_hash: row.try_get("content_hash")?,
+        batch_id: row.try_get("batch_id")?,
+        schema_overrides: overrides_from_json(row.try_get::<Option<String>, _>("schema_overrides")?.as_deref())?,
+        status: row.try_get("status")?,
+        error_message: row.try_get("error_message")?,
+        attempts: row.try_get("attempts")?,
+        low_memory: row.try_get("low_memory")?,
+        dataset_metadata: metadata_from_json(row.try_get::<Option<String>, _>("dataset_metadata")?.as_deref())?,
+        started_at: row.try_get::<Option<DateTime<Utc>>, _>("started_at")?.map(SystemTime::from),
+        finished_at: row.try_get::<Option<DateTime<Utc>>, _>("finished_at")?.map(SystemTime::from),
+        created_at: row.try_get::<Option<DateTime<Utc>>, _>("created_at")?.map(SystemTime::from),
+        updated_at: row.try_get::<Option<DateTime<Utc>>, _>("updated_at")?.map(SystemTime::from),
+    })
 }
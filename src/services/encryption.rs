@@ -0,0 +1,169 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+
+/// Leads every encrypted object; the last byte is the format version
+const MAGIC: &[u8; 8] = b"GDPENC\x00\x01";
+const NONCE_LEN: usize = 12;
+/// A 256-bit data key plus its 16-byte GCM tag
+const WRAPPED_KEY_LEN: usize = 32 + 16;
+const HEADER_LEN: usize = MAGIC.len() + NONCE_LEN + WRAPPED_KEY_LEN + NONCE_LEN;
+
+/// Envelope encryption for stored objects. Each object is sealed with its own
+/// random AES-256-GCM data key, and that key is sealed with the master key and
+/// kept in the object's header:
+///
+/// `MAGIC | key nonce | wrapped data key | data nonce | ciphertext + tag`
+///
+/// Objects without the header were written before encryption was enabled and
+/// are read back as they are.
+#[derive(Clone)]
+pub struct DatasetCipher {
+    master: Aes256Gcm,
+}
+
+impl std::fmt::Debug for DatasetCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DatasetCipher")
+    }
+}
+
+impl DatasetCipher {
+    /// Build from a base64-encoded 32-byte master key
+    pub fn from_base64_key(encoded: &str) -> Result<Self> {
+        let key = STANDARD
+            .decode(encoded.trim())
+            .context("STORAGE_ENCRYPTION_KEY is not valid base64")?;
+        let master = Aes256Gcm::new_from_slice(&key)
+            .map_err(|_| anyhow!("STORAGE_ENCRYPTION_KEY must decode to 32 bytes, got {}", key.len()))?;
+        Ok(Self { master })
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let key_nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let wrapped_key = self
+            .master
+            .encrypt(&key_nonce, Payload { msg: data_key.as_slice(), aad: MAGIC })
+            .map_err(|_| anyhow!("Failed to wrap the data key"))?;
+
+        let data_nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&data_key)
+            .encrypt(&data_nonce, Payload { msg: plaintext, aad: MAGIC })
+            .map_err(|_| anyhow!("Failed to encrypt object"))?;
+
+        let mut sealed = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        sealed.extend_from_slice(MAGIC);
+        sealed.extend_from_slice(&key_nonce);
+        sealed.extend_from_slice(&wrapped_key);
+        sealed.extend_from_slice(&data_nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Open an object written by `encrypt`; objects without the header pass through
+    pub fn decrypt(&self, data: Bytes) -> Result<Bytes> {
+        if !is_encrypted(&data) {
+            return Ok(data);
+        }
+        if data.len() < HEADER_LEN {
+            bail!("Encrypted object is truncated");
+        }
+        let (key_nonce, rest) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        let (wrapped_key, rest) = rest.split_at(WRAPPED_KEY_LEN);
+        let (data_nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let data_key = self
+            .master
+            .decrypt(Nonce::from_slice(key_nonce), Payload { msg: wrapped_key, aad: MAGIC })
+            .map_err(|_| anyhow!("Failed to unwrap the data key; STORAGE_ENCRYPTION_KEY does not match the one the object was written with"))?;
+        let cipher = Aes256Gcm::new_from_slice(&data_key).map_err(|_| anyhow!("Encrypted object has a malformed data key"))?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(data_nonce), Payload { msg: ciphertext, aad: MAGIC })
+            .map_err(|_| anyhow!("Encrypted object failed authentication; it is corrupt or was modified"))?;
+        Ok(plaintext.into())
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(byte: u8) -> DatasetCipher {
+        DatasetCipher::from_base64_key(&STANDARD.encode([byte; 32])).unwrap()
+    }
+
+    const CSV: &[u8] = b"id,revenue\n1,120.5\n2,98\n";
+
+    #[test]
+    fn objects_round_trip() {
+        let cipher = cipher(7);
+        let sealed = cipher.encrypt(CSV).unwrap();
+        assert!(is_encrypted(&sealed));
+        assert_eq!(sealed.len(), HEADER_LEN + CSV.len() + 16);
+        assert!(!sealed.windows(CSV.len()).any(|w| w == CSV));
+        assert_eq!(cipher.decrypt(sealed.into()).unwrap(), Bytes::from_static(CSV));
+
+        // Each object gets its own data key and nonces
+        assert_ne!(cipher.encrypt(CSV).unwrap(), cipher.encrypt(CSV).unwrap());
+        assert_eq!(cipher.decrypt(cipher.encrypt(b"").unwrap().into()).unwrap(), Bytes::new());
+    }
+
+    #[test]
+    fn another_master_key_is_rejected() {
+        let sealed = cipher(7).encrypt(CSV).unwrap();
+        let error = cipher(8).decrypt(sealed.into()).unwrap_err();
+        assert!(error.to_string().contains("does not match"), "{}", error);
+    }
+
+    #[test]
+    fn tampered_objects_are_rejected() {
+        let cipher = cipher(7);
+        let sealed = cipher.encrypt(CSV).unwrap();
+
+        let mut body = sealed.clone();
+        *body.last_mut().unwrap() ^= 1;
+        assert!(cipher.decrypt(body.into()).is_err());
+
+        // Every byte after the magic, from the key nonce to the data nonce, is checked
+        for at in MAGIC.len()..HEADER_LEN {
+            let mut header = sealed.clone();
+            header[at] ^= 1;
+            assert!(cipher.decrypt(header.into()).is_err(), "byte {} of the header was not checked", at);
+        }
+    }
+
+    #[test]
+    fn truncated_objects_are_rejected() {
+        let cipher = cipher(7);
+        let sealed = cipher.encrypt(CSV).unwrap();
+        for len in [MAGIC.len(), HEADER_LEN - 1] {
+            let error = cipher.decrypt(Bytes::copy_from_slice(&sealed[..len])).unwrap_err();
+            assert!(error.to_string().contains("truncated"), "{}", error);
+        }
+        // A header without its ciphertext tag fails authentication
+        assert!(cipher.decrypt(Bytes::copy_from_slice(&sealed[..HEADER_LEN])).is_err());
+    }
+
+    #[test]
+    fn objects_written_before_encryption_pass_through() {
+        let cipher = cipher(7);
+        assert!(!is_encrypted(CSV));
+        assert_eq!(cipher.decrypt(Bytes::from_static(CSV)).unwrap(), Bytes::from_static(CSV));
+        assert_eq!(cipher.decrypt(Bytes::new()).unwrap(), Bytes::new());
+    }
+
+    #[test]
+    fn master_keys_must_be_32_bytes_of_base64() {
+        assert!(DatasetCipher::from_base64_key("not base64!").is_err());
+        assert!(DatasetCipher::from_base64_key(&STANDARD.encode([1u8; 16])).is_err());
+        assert!(DatasetCipher::from_base64_key(&format!(" {}\n", STANDARD.encode([1u8; 32]))).is_ok());
+    }
+}
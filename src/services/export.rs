@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Context, Result};
+use polars::prelude::*;
+use rust_xlsxwriter::{Format, Workbook};
+use serde_json::Value;
+use std::fmt::Write;
+
+use crate::models::conversation::ConversationExport;
+use crate::models::query::ExportFormat;
+
+/// Rows an Excel worksheet holds, including the header row
+const XLSX_MAX_ROWS: usize = 1_048_576;
+
+/// Serialize a query result into a downloadable file
+pub fn export_dataframe(df: &mut DataFrame, format: ExportFormat) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    match format {
+        ExportFormat::Csv => {
+            CsvWriter::new(&mut buf)
+                .has_header(true)
+                .finish(df)
+                .context("Failed to write CSV")?;
+        }
+        ExportFormat::Arrow => {
+            IpcWriter::new(&mut buf).finish(df).context("Failed to write Arrow IPC")?;
+        }
+        ExportFormat::Xlsx => buf = write_xlsx(df)?,
+    }
+    Ok(buf)
+}
+
+/// One worksheet with a bold header row. Numbers and booleans keep their cell type,
+/// everything else (dates included) is written as text.
+fn write_xlsx(df: &DataFrame) -> Result<Vec<u8>> {
+    if df.height() >= XLSX_MAX_ROWS {
+        return Err(anyhow!(
+            "Result has {} rows, more than an Excel worksheet holds; download it as CSV instead",
+            df.height()
+        ));
+    }
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    let header = Format::new().set_bold();
+
+    for (col, series) in df.get_columns().iter().enumerate() {
+        let col = u16::try_from(col).context("Result has too many columns for Excel")?;
+        worksheet.write_string_with_format(0, col, series.name(), &header)?;
+
+        if series.dtype().is_numeric() {
+            let values = series.cast(&DataType::Float64)?;
+            for (row, value) in values.f64()?.into_iter().enumerate() {
+                if let Some(value) = value.filter(|v| v.is_finite()) {
+                    worksheet.write_number(row as u32 + 1, col, value)?;
+                }
+            }
+        } else if series.dtype() == &DataType::Boolean {
+            for (row, value) in series.bool()?.into_iter().enumerate() {
+                if let Some(value) = value {
+                    worksheet.write_boolean(row as u32 + 1, col, value)?;
+                }
+            }
+        } else {
+            let values = series.cast(&DataType::Utf8)?;
+            for (row, value) in values.utf8()?.into_iter().enumerate() {
+                if let Some(value) = value {
+                    worksheet.write_string(row as u32 + 1, col, value)?;
+                }
+            }
+        }
+    }
+
+    workbook.save_to_buffer().context("Failed to write Excel workbook")
+}
+
+/// Render an exported conversation as Markdown: each question as a heading followed
+/// by the answer, a table of the result's first rows, and the chart config and
+/// structured query as JSON blocks
+pub fn conversation_markdown(export: &ConversationExport) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "# Conversation {}\n", export.conversation_id)?;
+    writeln!(
+        out,
+        "Dataset `{}` · started {} · exported {}\n",
+        export.job_id,
+        export.created_at.format("%Y-%m-%d %H:%M UTC"),
+        export.exported_at.format("%Y-%m-%d %H:%M UTC")
+    )?;
+    if let Some(summary) = &export.summary {
+        writeln!(out, "> **Earlier in the conversation:** {}\n", summary.replace('\n', " "))?;
+    }
+
+    for (index, turn) in export.turns.iter().enumerate() {
+        writeln!(out, "## {}. {}\n", index + 1, turn.query.trim())?;
+        writeln!(out, "{}\n", turn.response.trim())?;
+
+        if let Some(rows) = turn.data.as_ref().and_then(Value::as_array).filter(|rows| !rows.is_empty()) {
+            let header: Vec<String> = turn.columns.iter().map(|c| markdown_cell(c)).collect();
+            writeln!(out, "| {} |", header.join(" | "))?;
+            writeln!(out, "|{}", " --- |".repeat(header.len()))?;
+            for row in rows {
+                let cells: Vec<String> = turn
+                    .columns
+                    .iter()
+                    .map(|c| match row.get(c) {
+                        None | Some(Value::Null) => String::new(),
+                        Some(Value::String(s)) => markdown_cell(s),
+                        Some(other) => markdown_cell(&other.to_string()),
+                    })
+                    .collect();
+                writeln!(out, "| {} |", cells.join(" | "))?;
+            }
+            if let Some(total) = turn.total_rows.filter(|total| *total > rows.len()) {
+                writeln!(out, "\n_Showing {} of {} rows._", rows.len(), total)?;
+            }
+            writeln!(out)?;
+        }
+        if let Some(error) = &turn.error {
+            writeln!(out, "_The result could not be reproduced: {}_\n", error)?;
+        }
+        if let Some(chart) = &turn.visualization_data {
+            writeln!(out, "Chart:\n\n```json\n{}\n```\n", serde_json::to_string_pretty(chart)?)?;
+        }
+        if let Some(query) = &turn.structured_query {
+            writeln!(out, "Query:\n\n```json\n{}\n```\n", serde_json::to_string_pretty(query)?)?;
+        }
+    }
+    Ok(out)
+}
+
+/// A value made safe for a Markdown table cell
+fn markdown_cell(value: &str) -> String {
+    value.trim().replace('|', "\\|").replace(['\r', '\n'], " ")
+}
@@ -0,0 +1,164 @@
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::{redirect, Url};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// How long fetching one file may take, connection included
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A file fetched for a batch upload
+#[derive(Debug)]
+pub struct FetchedFile {
+    /// Last segment of the URL's path, ending in `.csv` when the server sent CSV
+    pub filename: String,
+    pub content: Vec<u8>,
+}
+
+/// Download a CSV named in a batch manifest. Only http(s) URLs of public hosts
+/// are fetched: the host is resolved once, every address must be public, and the
+/// connection is pinned to the checked address so a second lookup can't point it
+/// at an internal service. Redirects are not followed and the body is cut off
+/// past `max_bytes`.
+pub async fn fetch_csv(url: &str, max_bytes: u64) -> Result<FetchedFile> {
+    let url = Url::parse(url).with_context(|| format!("'{}' is not a URL", url))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("only http and https URLs can be fetched");
+    }
+    let host = url.host_str().ok_or_else(|| anyhow!("the URL has no host"))?.to_string();
+    let port = url.port_or_known_default().ok_or_else(|| anyhow!("the URL has no port"))?;
+
+    let address = public_address(&host, port).await?;
+    let client = reqwest::Client::builder()
+        .redirect(redirect::Policy::none())
+        .timeout(FETCH_TIMEOUT)
+        .resolve(host.trim_start_matches('[').trim_end_matches(']'), address)
+        .build()
+        .context("Failed to build the fetch client")?;
+
+    let mut response = client.get(url.clone()).send().await.context("the request failed")?;
+    if !response.status().is_success() {
+        bail!("the server answered {}", response.status());
+    }
+    if response.content_length().is_some_and(|length| length > max_bytes) {
+        bail!("the file is larger than {} bytes", max_bytes);
+    }
+    let is_csv = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/csv"));
+
+    let mut content = Vec::new();
+    while let Some(chunk) = response.chunk().await.context("reading the file failed")? {
+        if (content.len() + chunk.len()) as u64 > max_bytes {
+            bail!("the file is larger than {} bytes", max_bytes);
+        }
+        content.extend_from_slice(&chunk);
+    }
+
+    let mut filename = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or("download")
+        .to_string();
+    if is_csv && !filename.to_lowercase().ends_with(".csv") {
+        filename.push_str(".csv");
+    }
+    Ok(FetchedFile { filename, content })
+}
+
+/// The first address of `host`, refusing hosts with any address that isn't public
+async fn public_address(host: &str, port: u16) -> Result<SocketAddr> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .with_context(|| format!("{} could not be resolved", host))?
+        .collect();
+    if let Some(address) = addresses.iter().find(|address| !is_public(address.ip())) {
+        bail!("{} resolves to {}, which is not a public address", host, address.ip());
+    }
+    addresses.first().copied().ok_or_else(|| anyhow!("{} has no addresses", host))
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        // "This network", carrier-grade NAT, IETF protocol assignments, benchmarking and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, link-local and documentation ranges
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_and_reserved_addresses_are_refused() {
+        for ip in [
+            "127.0.0.1",
+            "127.255.0.9",
+            "10.0.0.1",
+            "10.255.255.255",
+            "172.16.0.1",
+            "172.31.255.254",
+            "192.168.1.10",
+            "169.254.169.254",
+            "100.64.0.1",
+            "100.127.255.254",
+            "0.0.0.0",
+            "255.255.255.255",
+            "224.0.0.1",
+            "::1",
+            "::",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "febf::1",
+            "ff02::1",
+            "2001:db8::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} counted as public", ip);
+        }
+    }
+
+    #[test]
+    fn public_addresses_are_allowed() {
+        for ip in ["8.8.8.8", "172.32.0.1", "100.128.0.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{} counted as private", ip);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+
+use crate::models::storage::ObjectInfo;
+
+/// Suffix of the temporary files writes go through before they are renamed into
+/// place; never listed or read as objects
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Stores objects as files under a root directory, one file per key, for
+/// single-node deployments that want durability without S3. Writes land in a
+/// temporary file that is synced and renamed over the object, so readers see the
+/// old or the new content and never a partial file.
+#[derive(Clone, Debug)]
+pub struct FsStorageService {
+    root: PathBuf,
+    /// Sync each object and its directory before a write returns
+    fsync: bool,
+}
+
+impl FsStorageService {
+    pub fn new(root: impl AsRef<Path>, fsync: bool) -> Result<Self> {
+        let root = root.as_ref();
+        fs::create_dir_all(root).with_context(|| format!("Failed to create storage directory {}", root.display()))?;
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve storage directory {}", root.display()))?;
+        info!("🗄️ Filesystem storage initialized at {}", root.display());
+        Ok(Self { root, fsync })
+    }
+
+    /// Write an object, replacing any previous content atomically. The whole write
+    /// runs as one blocking task, so large objects never stall the async runtime.
+    pub async fn upload_file(&self, key: &str, data: Bytes) -> Result<()> {
+        let path = self.path_for(key)?;
+        let size = data.len();
+        let fsync = self.fsync;
+        let written = {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || write_atomically(&path, &data, fsync))
+                .await
+                .map_err(|e| anyhow!("Task join error while writing {}: {}", key, e))?
+        };
+        if let Err(e) = written {
+            error!("Failed to write {}: {:#}", path.display(), e);
+            return Err(anyhow!("Failed to write object {}: {:#}", key, e));
+        }
+
+        info!("✅ File saved to disk at: {} ({} bytes)", path.display(), size);
+        Ok(())
+    }
+
+    pub async fn download_file(&self, key: &str) -> Result<Bytes> {
+        let path = self.path_for(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(data.into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(anyhow!("Object not found: {}", key)),
+            Err(e) => Err(anyhow!("Failed to read object {}: {}", key, e)),
+        }
+    }
+
+    /// Objects live under a single root, so the bucket only names them in errors
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        self.download_file(key)
+            .await
+            .map_err(|e| anyhow!("{} (bucket {})", e, bucket))
+    }
+
+    /// Remove an object; a missing object is already removed
+    pub async fn delete_object(&self, key: &str) -> Result<()> {
+        let path = self.path_for(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {
+                info!("🗑️ Deleted {}", path.display());
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(anyhow!("Failed to delete object {}: {}", key, e)),
+        }
+    }
+
+    /// Objects whose key starts with `prefix`, skipping in-flight temporary files
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let root = self.root.clone();
+        let prefix = prefix.to_string();
+        tokio::task::spawn_blocking(move || list_dir(&root, &prefix))
+            .await
+            .map_err(|e| anyhow!("Task join error while listing storage: {}", e))?
+    }
+
+    pub async fn head_object(&self, key: &str) -> Result<Option<ObjectInfo>> {
+        let path = self.path_for(key)?;
+        match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(object_info(key.to_string(), &metadata))),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow!("Failed to read metadata of {}: {}", key, e)),
+        }
+    }
+
+    /// Path of an object under the root. Keys are relative `/`-separated paths;
+    /// absolute keys, `..` segments and anything else that could resolve outside
+    /// the root are rejected.
+    fn path_for(&self, key: &str) -> Result<PathBuf> {
+        if key.is_empty() || key.contains('\0') || key.contains('\\') {
+            bail!("Invalid storage key '{}'", key);
+        }
+        let mut path = self.root.clone();
+        for component in Path::new(key).components() {
+            match component {
+                Component::Normal(part) if !part.to_string_lossy().ends_with(TEMP_SUFFIX) => path.push(part),
+                Component::CurDir => {}
+                _ => bail!("Invalid storage key '{}'", key),
+            }
+        }
+        if path == self.root {
+            bail!("Invalid storage key '{}'", key);
+        }
+        Ok(path)
+    }
+}
+
+/// Write `data` to a temporary file beside `path` and rename it over `path`. With
+/// `fsync` the file is synced before the rename and its directory after it, so
+/// the object survives a power failure once this returns.
+fn write_atomically(path: &Path, data: &[u8], fsync: bool) -> Result<()> {
+    let dir = path.parent().ok_or_else(|| anyhow!("Invalid storage path {}", path.display()))?;
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create directory {}", dir.display()))?;
+
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("object");
+    let temp_path = dir.join(format!(".{}.{}{}", file_name, Uuid::new_v4(), TEMP_SUFFIX));
+    let written = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        if fsync {
+            file.sync_all()?;
+        }
+        fs::rename(&temp_path, path)
+    })();
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    if fsync {
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Failed to sync directory {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Walk the root for files whose `/`-separated key starts with `prefix`
+fn list_dir(root: &Path, prefix: &str) -> Result<Vec<ObjectInfo>> {
+    let mut objects = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir).with_context(|| format!("Failed to list {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("Failed to list {}", dir.display()))?;
+            let path = entry.path();
+            // Objects deleted while the walk runs are skipped
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            if path.to_string_lossy().ends_with(TEMP_SUFFIX) {
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            let key = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if key.starts_with(prefix) {
+                objects.push(object_info(key, &metadata));
+            }
+        }
+    }
+    objects.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(objects)
+}
+
+fn object_info(key: String, metadata: &fs::Metadata) -> ObjectInfo {
+    ObjectInfo {
+        key,
+        size: metadata.len(),
+        last_modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage() -> FsStorageService {
+        let root = std::env::temp_dir().join(format!("fs-storage-test-{}", Uuid::new_v4()));
+        FsStorageService::new(root, false).unwrap()
+    }
+
+    #[test]
+    fn keys_that_leave_the_root_are_rejected() {
+        let storage = storage();
+        for key in ["../x", "/etc/passwd", "a/../../x", "a/../x", "a\\b", "x.tmp", "a/b.tmp", "", ".", "./", "a\0b"] {
+            assert!(storage.path_for(key).is_err(), "{:?} was accepted", key);
+        }
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[test]
+    fn relative_keys_resolve_under_the_root() {
+        let storage = storage();
+        assert_eq!(
+            storage.path_for("tenants/t/uploads/a.csv").unwrap(),
+            storage.root.join("tenants").join("t").join("uploads").join("a.csv")
+        );
+        assert_eq!(storage.path_for("./a.csv").unwrap(), storage.root.join("a.csv"));
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn objects_are_written_listed_and_deleted() {
+        let storage = storage();
+        let key = "tenants/t/uploads/a.csv";
+        storage.upload_file(key, Bytes::from_static(b"a,b\n1,2\n")).await.unwrap();
+        storage.upload_file("tenants/u/uploads/b.csv", Bytes::from_static(b"x\n")).await.unwrap();
+        assert_eq!(storage.download_file(key).await.unwrap(), Bytes::from_static(b"a,b\n1,2\n"));
+
+        // Overwrites replace the content and leave no temporary file behind
+        storage.upload_file(key, Bytes::from_static(b"a\n")).await.unwrap();
+        let listed = storage.list_objects("tenants/t/").await.unwrap();
+        assert_eq!(listed.iter().map(|o| (o.key.as_str(), o.size)).collect::<Vec<_>>(), vec![(key, 2)]);
+        assert_eq!(storage.head_object(key).await.unwrap().map(|o| o.size), Some(2));
+
+        storage.delete_object(key).await.unwrap();
+        assert!(storage.download_file(key).await.is_err());
+        assert!(storage.head_object(key).await.unwrap().is_none());
+        assert!(storage.list_objects("tenants/t/").await.unwrap().is_empty());
+        // Deleting a missing object succeeds
+        storage.delete_object(key).await.unwrap();
+        fs::remove_dir_all(&storage.root).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+use regex::Regex;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+use crate::models::conversation::GroundingCheck;
+
+/// Whole numbers up to this are counts and ordinals ("the top 3 regions") and are
+/// not checked against the result
+const SMALL_INTEGER: f64 = 10.0;
+
+/// Relative difference tolerated between a figure and a result value, for prose
+/// that rounds ("about 12.3k")
+const RELATIVE_TOLERANCE: f64 = 0.005;
+
+/// A number written in prose, with how it was written
+#[derive(Debug)]
+struct Figure {
+    text: String,
+    value: f64,
+    /// Digits after the decimal point, which bound how far it may be rounded
+    decimals: i32,
+    multiplier: f64,
+    percent: bool,
+}
+
+/// Check that every figure stated in a narration appears in the query result,
+/// allowing for rounding, thousands separators, k/M/B suffixes and percentages
+/// written for fractions. Figures from `context`, such as the user's question,
+/// are grounded too, as are the result's row counts in `known`.
+pub fn check_grounding(narration: &str, result: &Value, context: &str, known: &[f64]) -> GroundingCheck {
+    let mut values: Vec<f64> = known.to_vec();
+    collect_values(result, &mut values);
+    values.extend(figures(context).iter().map(|f| f.value * f.multiplier));
+
+    let unverified_figures: Vec<String> = figures(narration)
+        .into_iter()
+        .filter(|figure| !is_grounded(figure, &values))
+        .map(|figure| figure.text)
+        .collect();
+    GroundingCheck {
+        grounded: unverified_figures.is_empty(),
+        unverified_figures,
+    }
+}
+
+fn is_grounded(figure: &Figure, values: &[f64]) -> bool {
+    if figure.decimals == 0 && figure.multiplier == 1.0 && !figure.percent && figure.value.abs() <= SMALL_INTEGER {
+        return true;
+    }
+    let amount = figure.value * figure.multiplier;
+    let rounding = 0.5 * 10f64.powi(-figure.decimals) * figure.multiplier;
+    let mut candidates = vec![(amount, rounding)];
+    if figure.percent {
+        // 15% may be stored as 0.15
+        candidates.push((amount / 100.0, rounding / 100.0));
+    }
+    candidates.iter().any(|(candidate, rounding)| {
+        let tolerance = rounding.max(candidate.abs() * RELATIVE_TOLERANCE);
+        values.iter().any(|v| (v.abs() - candidate.abs()).abs() <= tolerance)
+    })
+}
+
+/// Every number in a JSON value, including those inside strings such as dates
+fn collect_values(value: &Value, out: &mut Vec<f64>) {
+    match value {
+        Value::Number(n) => out.extend(n.as_f64()),
+        Value::String(s) => out.extend(figures(s).iter().map(|f| f.value * f.multiplier)),
+        Value::Array(items) => items.iter().for_each(|item| collect_values(item, out)),
+        Value::Object(fields) => fields.values().for_each(|field| collect_values(field, out)),
+        _ => {}
+    }
+}
+
+/// Numbers written in text. Digits glued to letters or underscores, as in `Q1`
+/// or `p90_revenue`, are names rather than figures.
+fn figures(text: &str) -> Vec<Figure> {
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+    let number = NUMBER.get_or_init(|| {
+        Regex::new(r"(?i)(\d{1,3}(?:,\d{3})+|\d+)(\.\d+)?(?:\s*(%|percent\b|thousand\b|million\b|billion\b|k\b|m\b|mn\b|bn\b|b\b))?")
+            .expect("valid figure pattern")
+    });
+
+    number
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let whole = caps.get(0)?;
+            let glued = |c: char| c.is_alphanumeric() || c == '_';
+            if text[..whole.start()].chars().next_back().is_some_and(glued) {
+                return None;
+            }
+            if text[caps.get(2).unwrap_or(caps.get(1)?).end()..].chars().next().is_some_and(glued)
+                && caps.get(3).is_none()
+            {
+                return None;
+            }
+            let fraction = caps.get(2).map_or("", |m| m.as_str());
+            let value: f64 = format!("{}{}", caps[1].replace(',', ""), fraction).parse().ok()?;
+            let suffix = caps.get(3).map(|m| m.as_str().to_lowercase());
+            let multiplier = match suffix.as_deref() {
+                Some("k" | "thousand") => 1e3,
+                Some("m" | "mn" | "million") => 1e6,
+                Some("b" | "bn" | "billion") => 1e9,
+                _ => 1.0,
+            };
+            Some(Figure {
+                text: whole.as_str().trim().to_string(),
+                value,
+                decimals: fraction.len().saturating_sub(1) as i32,
+                multiplier,
+                percent: matches!(suffix.as_deref(), Some("%" | "percent")),
+            })
+        })
+        .collect()
+}
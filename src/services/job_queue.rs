@@ -0,0 +1,79 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Why a job could not be handed to the worker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueError {
+    /// Every slot is taken; the caller should try again after `retry_after`
+    Full { depth: usize, capacity: usize, retry_after: Duration },
+    /// The worker has stopped
+    Closed,
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::Full { depth, .. } => write!(f, "the job queue is full ({} jobs waiting)", depth),
+            QueueError::Closed => write!(f, "the job queue is closed"),
+        }
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+/// Sending side of the worker's job queue. Submissions never wait for room: a
+/// full queue is reported at once, so requests are answered `503` during load
+/// spikes instead of hanging until the worker catches up.
+#[derive(Debug)]
+pub struct JobQueue {
+    sender: mpsc::Sender<Uuid>,
+    retry_after: Duration,
+    /// Submissions turned away because the queue was full
+    rejected: AtomicU64,
+}
+
+impl JobQueue {
+    pub fn new(sender: mpsc::Sender<Uuid>, retry_after: Duration) -> Self {
+        Self { sender, retry_after, rejected: AtomicU64::new(0) }
+    }
+
+    /// Hold a slot for a job that is about to be created, so it is only created
+    /// when the worker can take it; dropping the permit frees the slot
+    pub fn reserve(&self) -> Result<mpsc::Permit<'_, Uuid>, QueueError> {
+        self.sender.try_reserve().map_err(|e| self.refused(e))
+    }
+
+    /// Hold `n` slots at once, for a batch whose jobs must all be queued or none
+    /// created; a batch needing more slots than are free is turned away whole
+    pub fn reserve_many(&self, n: usize) -> Result<mpsc::PermitIterator<'_, Uuid>, QueueError> {
+        self.sender.try_reserve_many(n).map_err(|e| self.refused(e))
+    }
+
+    fn refused<T>(&self, error: mpsc::error::TrySendError<T>) -> QueueError {
+        match error {
+            mpsc::error::TrySendError::Full(_) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                log::warn!("🚦 Job queue full ({} of {} slots taken), turning a job away", self.depth(), self.capacity());
+                QueueError::Full { depth: self.depth(), capacity: self.capacity(), retry_after: self.retry_after }
+            }
+            mpsc::error::TrySendError::Closed(_) => QueueError::Closed,
+        }
+    }
+
+    /// Jobs sent to the worker and not yet picked up, plus reserved slots
+    pub fn depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.sender.max_capacity()
+    }
+
+    /// Submissions turned away since startup
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
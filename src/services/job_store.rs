@@ -0,0 +1,249 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::batch::{Batch, NewBatch};
+use crate::models::conversation::DatasetMetadata;
+use crate::models::database::DatabaseBackendKind;
+use crate::models::job::{Job, JobFilter, JobStatus, NewJob};
+use crate::models::response::Insights;
+use crate::models::schema::SchemaOverrides;
+use crate::models::usage::{UsageEntry, UsageQuery};
+#[cfg(feature = "external-services")]
+use crate::services::database::DatabaseService;
+use crate::services::memory_db::MemoryDatabaseService;
+#[cfg(feature = "sqlite")]
+use crate::services::sqlite_db::SqliteDatabaseService;
+use crate::services::DatabaseServiceTrait;
+
+/// The job database selected by `DATABASE_BACKEND`, so the routes are built once
+/// whichever database the deployment uses
+#[derive(Clone, Debug)]
+pub enum JobStore {
+    Memory(MemoryDatabaseService),
+    #[cfg(feature = "external-services")]
+    Postgres(DatabaseService),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteDatabaseService),
+}
+
+impl JobStore {
+    pub async fn from_config(config: &Config) -> Result<Self> {
+        match config.database_backend {
+            DatabaseBackendKind::Memory => Ok(JobStore::Memory(MemoryDatabaseService::new())),
+            #[cfg(feature = "external-services")]
+            DatabaseBackendKind::Postgres => Ok(JobStore::Postgres(DatabaseService::connect(config).await?)),
+            #[cfg(not(feature = "external-services"))]
+            DatabaseBackendKind::Postgres => anyhow::bail!("DATABASE_BACKEND=postgres needs a build with the external-services feature"),
+            #[cfg(feature = "sqlite")]
+            DatabaseBackendKind::Sqlite => Ok(JobStore::Sqlite(SqliteDatabaseService::connect(config).await?)),
+            #[cfg(not(feature = "sqlite"))]
+            DatabaseBackendKind::Sqlite => anyhow::bail!("DATABASE_BACKEND=sqlite needs a build with the sqlite feature"),
+        }
+    }
+
+    /// Bring the database schema up to date; the memory backend has none
+    pub async fn migrate(&self) -> Result<()> {
+        match self {
+            JobStore::Memory(_) => Ok(()),
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.migrate().await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.migrate().await,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl DatabaseServiceTrait for JobStore {
+    async fn create_job(&self, new_job: NewJob) -> Result<Uuid> {
+        match self {
+            JobStore::Memory(service) => service.create_job(new_job).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.create_job(new_job).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.create_job(new_job).await,
+        }
+    }
+
+    async fn create_batch(&self, new_batch: NewBatch) -> Result<Uuid> {
+        match self {
+            JobStore::Memory(service) => service.create_batch(new_batch).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.create_batch(new_batch).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.create_batch(new_batch).await,
+        }
+    }
+
+    async fn delete_batch(&self, batch_id: Uuid) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.delete_batch(batch_id).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.delete_batch(batch_id).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.delete_batch(batch_id).await,
+        }
+    }
+
+    async fn get_batch(&self, batch_id: Uuid) -> Result<Option<Batch>> {
+        match self {
+            JobStore::Memory(service) => service.get_batch(batch_id).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.get_batch(batch_id).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.get_batch(batch_id).await,
+        }
+    }
+
+    async fn list_batch_jobs(&self, batch_id: Uuid) -> Result<Vec<Job>> {
+        match self {
+            JobStore::Memory(service) => service.list_batch_jobs(batch_id).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.list_batch_jobs(batch_id).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.list_batch_jobs(batch_id).await,
+        }
+    }
+
+    async fn get_job(&self, job_id: Uuid) -> Result<Option<Job>> {
+        match self {
+            JobStore::Memory(service) => service.get_job(job_id).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.get_job(job_id).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.get_job(job_id).await,
+        }
+    }
+
+    async fn list_jobs(&self, filter: &JobFilter, after: Option<Uuid>, limit: usize) -> Result<Vec<Job>> {
+        match self {
+            JobStore::Memory(service) => service.list_jobs(filter, after, limit).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.list_jobs(filter, after, limit).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.list_jobs(filter, after, limit).await,
+        }
+    }
+
+    async fn count_jobs_since(&self, tenant_id: &str, since: DateTime<Utc>) -> Result<u64> {
+        match self {
+            JobStore::Memory(service) => service.count_jobs_since(tenant_id, since).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.count_jobs_since(tenant_id, since).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.count_jobs_since(tenant_id, since).await,
+        }
+    }
+
+    async fn update_job_status(&self, job_id: Uuid, status: JobStatus) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.update_job_status(job_id, status).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.update_job_status(job_id, status).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.update_job_status(job_id, status).await,
+        }
+    }
+
+    async fn fail_job(&self, job_id: Uuid, error_message: &str) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.fail_job(job_id, error_message).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.fail_job(job_id, error_message).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.fail_job(job_id, error_message).await,
+        }
+    }
+
+    async fn set_job_low_memory(&self, job_id: Uuid, low_memory: bool) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.set_job_low_memory(job_id, low_memory).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.set_job_low_memory(job_id, low_memory).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.set_job_low_memory(job_id, low_memory).await,
+        }
+    }
+
+    async fn set_job_dataset_metadata(&self, job_id: Uuid, metadata: Option<&DatasetMetadata>) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.set_job_dataset_metadata(job_id, metadata).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.set_job_dataset_metadata(job_id, metadata).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.set_job_dataset_metadata(job_id, metadata).await,
+        }
+    }
+
+    async fn update_job_schema(&self, job_id: Uuid, overrides: &SchemaOverrides) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.update_job_schema(job_id, overrides).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.update_job_schema(job_id, overrides).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.update_job_schema(job_id, overrides).await,
+        }
+    }
+
+    async fn update_job_file(&self, job_id: Uuid, file_key: &str, content_hash: &str) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.update_job_file(job_id, file_key, content_hash).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.update_job_file(job_id, file_key, content_hash).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.update_job_file(job_id, file_key, content_hash).await,
+        }
+    }
+
+    async fn save_insights(&self, job_id: Uuid, insights: &Insights) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.save_insights(job_id, insights).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.save_insights(job_id, insights).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.save_insights(job_id, insights).await,
+        }
+    }
+
+    async fn get_insights(&self, job_id: Uuid) -> Result<Option<String>> {
+        match self {
+            JobStore::Memory(service) => service.get_insights(job_id).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.get_insights(job_id).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.get_insights(job_id).await,
+        }
+    }
+
+    async fn record_usage(&self, entry: &UsageEntry) -> Result<()> {
+        match self {
+            JobStore::Memory(service) => service.record_usage(entry).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.record_usage(entry).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.record_usage(entry).await,
+        }
+    }
+
+    async fn list_usage(&self, query: &UsageQuery) -> Result<Vec<UsageEntry>> {
+        match self {
+            JobStore::Memory(service) => service.list_usage(query).await,
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => service.list_usage(query).await,
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => service.list_usage(query).await,
+        }
+    }
+
+    fn backend_name(&self) -> &'static str {
+        match self {
+            JobStore::Memory(service) => DatabaseServiceTrait::backend_name(service),
+            #[cfg(feature = "external-services")]
+            JobStore::Postgres(service) => DatabaseServiceTrait::backend_name(service),
+            #[cfg(feature = "sqlite")]
+            JobStore::Sqlite(service) => DatabaseServiceTrait::backend_name(service),
+        }
+    }
+}
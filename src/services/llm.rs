@@ -0,0 +1,468 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use log::{debug, error};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::models::llm::{LlmProviderKind, ModelSettings};
+use crate::models::usage::TokenUsage;
+use crate::services::LlmProviderTrait;
+
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const ANTHROPIC_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Completion tokens an Anthropic reply may use unless the settings say otherwise;
+/// the Messages API requires a cap
+const ANTHROPIC_MAX_TOKENS: u32 = 4096;
+
+/// A provider call that failed in a way worth retrying: rate limited, overloaded,
+/// timed out or unreachable
+#[derive(Debug)]
+pub struct TransientLlmError {
+    pub message: String,
+    /// How long the provider asked callers to wait before trying again
+    pub retry_after: Option<Duration>,
+    /// The provider did not answer within the request timeout
+    pub timed_out: bool,
+}
+
+impl fmt::Display for TransientLlmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TransientLlmError {}
+
+fn transient(message: String, retry_after: Option<Duration>) -> anyhow::Error {
+    TransientLlmError { message, retry_after, timed_out: false }.into()
+}
+
+/// One system + user exchange with a chat model
+#[derive(Debug, Clone)]
+pub struct ChatRequest {
+    pub system: String,
+    pub user: String,
+    /// Ask for a single JSON object as the reply
+    pub json_response: bool,
+    /// Function the model must call; its arguments become the reply
+    pub tool: Option<ToolSpec>,
+    pub settings: ModelSettings,
+}
+
+/// A function offered to the model, described by a JSON Schema of its arguments
+#[derive(Debug, Clone)]
+pub struct ToolSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub parameters: Value,
+}
+
+impl ChatRequest {
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.settings.timeout_secs)
+    }
+
+    /// Model the request goes to: its own setting, or the provider's model
+    fn model<'a>(&'a self, provider_model: &'a str) -> &'a str {
+        self.settings.model.as_deref().unwrap_or(provider_model)
+    }
+
+    /// Add the temperature and token cap, when set, to a request body
+    fn apply_sampling(&self, body: &mut Value) {
+        if let Some(temperature) = self.settings.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = self.settings.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+    }
+}
+
+/// Build the provider selected by `LLM_PROVIDER`. `None` when the provider needs a
+/// key and none is configured; an error when its other settings are incomplete.
+pub fn provider_from_config(config: &Config) -> Result<Option<Arc<dyn LlmProviderTrait>>> {
+    let model = |kind: LlmProviderKind| {
+        config
+            .llm_model
+            .clone()
+            .or_else(|| kind.default_model().map(String::from))
+            .ok_or_else(|| anyhow!("LLM_MODEL must be set for the {} provider", kind))
+    };
+    let openai_key = config.llm_api_key.clone().or_else(|| config.open_ai_key.clone());
+    let client = http_client(config)?;
+
+    let provider: Arc<dyn LlmProviderTrait> = match config.llm_provider {
+        LlmProviderKind::OpenAi => {
+            let Some(api_key) = openai_key else { return Ok(None) };
+            Arc::new(OpenAiProvider {
+                client,
+                base_url: base_url(config.llm_base_url.as_deref().unwrap_or(OPENAI_BASE_URL)),
+                api_key: Some(api_key),
+                model: model(LlmProviderKind::OpenAi)?,
+                self_hosted: false,
+            })
+        }
+        LlmProviderKind::Local => Arc::new(OpenAiProvider {
+            client,
+            base_url: base_url(config.llm_base_url.as_deref().ok_or_else(|| {
+                anyhow!("LLM_BASE_URL must be set for the local provider, e.g. http://localhost:11434/v1")
+            })?),
+            api_key: config.llm_api_key.clone(),
+            model: model(LlmProviderKind::Local)?,
+            self_hosted: true,
+        }),
+        LlmProviderKind::Azure => {
+            let Some(api_key) = openai_key else { return Ok(None) };
+            let deployment = config
+                .azure_openai_deployment
+                .clone()
+                .ok_or_else(|| anyhow!("AZURE_OPENAI_DEPLOYMENT must be set for the azure provider"))?;
+            Arc::new(AzureOpenAiProvider {
+                client,
+                endpoint: base_url(config.llm_base_url.as_deref().ok_or_else(|| {
+                    anyhow!("LLM_BASE_URL must be set to the Azure OpenAI resource endpoint")
+                })?),
+                api_key,
+                api_version: config.azure_openai_api_version.clone(),
+                // Deployments are named by the operator; price by the model when given
+                model: config.llm_model.clone().unwrap_or_else(|| deployment.clone()),
+                deployment,
+            })
+        }
+        LlmProviderKind::Anthropic => {
+            let Some(api_key) = config.llm_api_key.clone() else { return Ok(None) };
+            Arc::new(AnthropicProvider {
+                client,
+                base_url: base_url(config.llm_base_url.as_deref().unwrap_or(ANTHROPIC_BASE_URL)),
+                api_key,
+                model: model(LlmProviderKind::Anthropic)?,
+            })
+        }
+    };
+    Ok(Some(provider))
+}
+
+/// The one HTTP client every call to the provider goes through. Connections are
+/// pooled and kept alive, so calls after the first skip the TCP and TLS handshakes;
+/// HTTPS endpoints that offer HTTP/2 get it through ALPN, and `HTTPS_PROXY`,
+/// `HTTP_PROXY` and `NO_PROXY` are honoured.
+fn http_client(config: &Config) -> Result<Client> {
+    Client::builder()
+        .pool_max_idle_per_host(config.llm_pool_max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(config.llm_pool_idle_timeout_secs))
+        .tcp_keepalive(Duration::from_secs(60))
+        .http2_keep_alive_interval(Duration::from_secs(30))
+        .http2_keep_alive_while_idle(true)
+        .build()
+        .context("Failed to build the LLM HTTP client")
+}
+
+fn base_url(url: &str) -> String {
+    url.trim().trim_end_matches('/').to_string()
+}
+
+/// OpenAI chat messages for a request
+fn openai_messages(request: &ChatRequest) -> Value {
+    json!([
+        {"role": "system", "content": request.system},
+        {"role": "user", "content": request.user}
+    ])
+}
+
+/// Send a request and return its JSON body, turning transport failures and error
+/// statuses into errors that name the provider
+async fn send(provider: &str, request: RequestBuilder, timeout: Duration) -> Result<Value> {
+    let response = match request.timeout(timeout).send().await {
+        Ok(response) => response,
+        Err(e) if e.is_timeout() => {
+            error!("{} request timed out after {} seconds", provider, timeout.as_secs());
+            let message = format!("{} API request timed out after {} seconds", provider, timeout.as_secs());
+            return Err(TransientLlmError { message, retry_after: None, timed_out: true }.into());
+        }
+        Err(e) if e.is_connect() => {
+            error!("Connection error: {}", e);
+            return Err(transient(format!("Failed to connect to {} API: {}", provider, e), None));
+        }
+        Err(e) => return Err(anyhow!("Failed to send request to {} API: {}", provider, e)),
+    };
+
+    let status = response.status();
+    if !status.is_success() {
+        let retry_after = retry_after(response.headers());
+        let error_text = response.text().await
+            .unwrap_or_else(|_| "Could not read error response".to_string());
+        error!("{} API error: Status {}, Details: {}", provider, status, error_text);
+        let message = format!("{} API error: Status {}, Details: {}", provider, status, error_text);
+        // Rate limits and server-side failures pass; bad requests and auth errors do not
+        return Err(if status.as_u16() == 429 || status.is_server_error() {
+            transient(message, retry_after)
+        } else {
+            anyhow!(message)
+        });
+    }
+    debug!("{} API response received", provider);
+    response.json().await.map_err(|e| anyhow!("Failed to parse {} API response: {}", provider, e))
+}
+
+/// Wait requested by a `Retry-After` header (seconds or an HTTP date), or by the
+/// `retry-after-ms` header OpenAI and Azure send alongside it
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+    // Waits too long to represent, infinite or not a number are ignored
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        return Duration::try_from_secs_f64(ms.max(0.0) / 1000.0).ok();
+    }
+    let value = header(RETRY_AFTER.as_str())?;
+    if let Ok(secs) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs.max(0.0)).ok();
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default())
+}
+
+/// Ask an OpenAI-style API for the request's function call, or for a JSON object
+fn apply_response_format(request: &ChatRequest, body: &mut Value) {
+    if let Some(tool) = &request.tool {
+        body["tools"] = json!([{
+            "type": "function",
+            "function": {"name": tool.name, "description": tool.description, "parameters": tool.parameters},
+        }]);
+        body["tool_choice"] = json!({"type": "function", "function": {"name": tool.name}});
+    } else if request.json_response {
+        body["response_format"] = json!({"type": "json_object"});
+    }
+}
+
+/// Arguments of the requested function call in the first choice of a chat
+/// completion, or its content. Servers without function calling answer in the
+/// content instead.
+fn completion_content(provider: &str, request: &ChatRequest, response: &Value) -> Result<String> {
+    let message = &response["choices"][0]["message"];
+    if let Some(tool) = &request.tool {
+        let arguments = message["tool_calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|call| call["function"]["name"] == tool.name)
+            .and_then(|call| call["function"]["arguments"].as_str());
+        if let Some(arguments) = arguments {
+            return Ok(arguments.trim().to_string());
+        }
+    }
+    match message["content"].as_str() {
+        Some(content) if !content.trim().is_empty() => Ok(content.trim().to_string()),
+        _ => {
+            error!("Could not extract content from {} response: {:?}", provider, response);
+            Err(anyhow!("Could not extract content from {} response", provider))
+        }
+    }
+}
+
+/// api.openai.com, or any server that implements its chat completions API
+#[derive(Debug)]
+pub struct OpenAiProvider {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    /// Self-hosted models are not priced
+    self_hosted: bool,
+}
+
+#[async_trait::async_trait]
+impl LlmProviderTrait for OpenAiProvider {
+    async fn chat(&self, request: &ChatRequest) -> Result<(String, TokenUsage)> {
+        let model = request.model(&self.model);
+        let mut body = json!({
+            "model": model,
+            "messages": openai_messages(request),
+        });
+        request.apply_sampling(&mut body);
+        apply_response_format(request, &mut body);
+        let mut http = self.client.post(format!("{}/chat/completions", self.base_url)).json(&body);
+        if let Some(api_key) = &self.api_key {
+            http = http.bearer_auth(api_key);
+        }
+
+        let provider = if self.self_hosted { "Local LLM" } else { "OpenAI" };
+        let response = send(provider, http, request.timeout()).await?;
+        let usage = if self.self_hosted {
+            let usage = &response["usage"];
+            TokenUsage::unpriced(
+                usage["prompt_tokens"].as_u64().unwrap_or(0),
+                usage["completion_tokens"].as_u64().unwrap_or(0),
+            )
+        } else {
+            TokenUsage::from_completion(&response, model)
+        };
+        Ok((completion_content(provider, request, &response)?, usage))
+    }
+
+    fn backend_name(&self) -> &'static str {
+        if self.self_hosted { "local" } else { "openai" }
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// An Azure OpenAI deployment
+#[derive(Debug)]
+pub struct AzureOpenAiProvider {
+    client: Client,
+    endpoint: String,
+    api_key: String,
+    api_version: String,
+    deployment: String,
+    /// Model behind the deployment, used for pricing
+    model: String,
+}
+
+#[async_trait::async_trait]
+impl LlmProviderTrait for AzureOpenAiProvider {
+    async fn chat(&self, request: &ChatRequest) -> Result<(String, TokenUsage)> {
+        // A model setting names another deployment of the same resource
+        let deployment = request.settings.model.as_deref().unwrap_or(&self.deployment);
+        let mut body = json!({"messages": openai_messages(request)});
+        request.apply_sampling(&mut body);
+        apply_response_format(request, &mut body);
+        let http = self
+            .client
+            .post(format!("{}/openai/deployments/{}/chat/completions", self.endpoint, deployment))
+            .query(&[("api-version", &self.api_version)])
+            .header("api-key", &self.api_key)
+            .json(&body);
+
+        let response = send("Azure OpenAI", http, request.timeout()).await?;
+        let usage = TokenUsage::from_completion(&response, request.model(&self.model));
+        Ok((completion_content("Azure OpenAI", request, &response)?, usage))
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "azure"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+/// The Anthropic Messages API
+#[derive(Debug)]
+pub struct AnthropicProvider {
+    client: Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+#[async_trait::async_trait]
+impl LlmProviderTrait for AnthropicProvider {
+    async fn chat(&self, request: &ChatRequest) -> Result<(String, TokenUsage)> {
+        // The Messages API has no JSON mode, so without a tool the reply is asked for
+        // and cut out of the text
+        let system = if request.json_response && request.tool.is_none() {
+            format!("{}\n\nReply with a single JSON object and nothing else.", request.system)
+        } else {
+            request.system.clone()
+        };
+        let model = request.model(&self.model);
+        let mut body = json!({
+            "model": model,
+            "max_tokens": ANTHROPIC_MAX_TOKENS,
+            "system": system,
+            "messages": [{"role": "user", "content": request.user}],
+        });
+        request.apply_sampling(&mut body);
+        if let Some(tool) = &request.tool {
+            body["tools"] = json!([{"name": tool.name, "description": tool.description, "input_schema": tool.parameters}]);
+            body["tool_choice"] = json!({"type": "tool", "name": tool.name});
+        }
+        let http = self
+            .client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&body);
+
+        let response = send("Anthropic", http, request.timeout()).await?;
+        let usage = TokenUsage::priced(
+            response["usage"]["input_tokens"].as_u64().unwrap_or(0),
+            response["usage"]["output_tokens"].as_u64().unwrap_or(0),
+            model,
+        );
+        if let Some(tool) = &request.tool {
+            let input = response["content"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|block| block["type"] == "tool_use" && block["name"] == tool.name)
+                .map(|block| &block["input"]);
+            if let Some(input) = input {
+                return Ok((input.to_string(), usage));
+            }
+        }
+        let text: String = response["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|block| block["type"] == "text")
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        let text = text.trim();
+        let content = match (request.json_response, text.find('{'), text.rfind('}')) {
+            (true, Some(start), Some(end)) if start < end => &text[start..=end],
+            _ => text,
+        };
+        if content.is_empty() {
+            error!("Could not extract content from Anthropic response: {:?}", response);
+            return Err(anyhow!("Could not extract content from Anthropic response"));
+        }
+        Ok((content.to_string(), usage))
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn retry_after_reads_seconds_and_milliseconds() {
+        assert_eq!(retry_after(&headers("retry-after", "2")), Some(Duration::from_secs(2)));
+        assert_eq!(retry_after(&headers("retry-after", "0.5")), Some(Duration::from_millis(500)));
+        assert_eq!(retry_after(&headers("retry-after-ms", "1500")), Some(Duration::from_millis(1500)));
+        assert_eq!(retry_after(&headers("retry-after", "-3")), Some(Duration::ZERO));
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn retry_after_ignores_waits_too_long_to_represent() {
+        for value in ["inf", "1e400", "1e300"] {
+            assert_eq!(retry_after(&headers("retry-after", value)), None, "{}", value);
+        }
+        assert_eq!(retry_after(&headers("retry-after-ms", "inf")), None);
+    }
+}
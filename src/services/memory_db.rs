@@ -1,20 +1,36 @@
 use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDate, Utc};
 use uuid::Uuid;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
-use crate::models::job::{Job, JobStatus, NewJob};
+use crate::models::batch::{Batch, NewBatch};
+use crate::models::conversation::DatasetMetadata;
+use crate::models::job::{Job, JobFilter, JobStatus, NewJob};
+use crate::models::response::Insights;
+use crate::models::schema::SchemaOverrides;
+use crate::models::usage::{TokenUsage, UsageEntry, UsageQuery};
 
-#[derive(Clone, Debug)]
+/// Day, job, tenant, user and conversation an AI call is attributed to
+type UsageKey = (NaiveDate, String, Option<String>, Option<String>, Option<String>);
+
+#[derive(Clone, Debug, Default)]
 pub struct MemoryDatabaseService {
     jobs: Arc<Mutex<HashMap<Uuid, Job>>>,
+    batches: Arc<Mutex<HashMap<Uuid, Batch>>>,
+    /// Serialized insights by job
+    insights: Arc<Mutex<HashMap<Uuid, String>>>,
+    usage: Arc<Mutex<HashMap<UsageKey, TokenUsage>>>,
 }
 
 impl MemoryDatabaseService {
     pub fn new() -> Self {
         Self {
             jobs: Arc::new(Mutex::new(HashMap::new())),
+            batches: Arc::new(Mutex::new(HashMap::new())),
+            insights: Arc::new(Mutex::new(HashMap::new())),
+            usage: Arc::new(Mutex::new(HashMap::new())),
         }
     }
     
@@ -27,8 +43,20 @@ impl MemoryDatabaseService {
         let job = Job {
             id: job_id,
             user_id: new_job.user_id,
+            tenant_id: new_job.tenant_id,
+            request_id: new_job.request_id,
+            bucket: new_job.bucket,
             file_key: new_job.file_key,
+            content_hash: Some(new_job.content_hash),
+            batch_id: new_job.batch_id,
+            schema_overrides: SchemaOverrides::new(),
             status,
+            error_message: None,
+            attempts: 0,
+            low_memory: false,
+            dataset_metadata: None,
+            started_at: None,
+            finished_at: None,
             created_at: now,
             updated_at: now,
         };
@@ -39,22 +67,213 @@ impl MemoryDatabaseService {
         Ok(job_id)
     }
     
+    /// Record a new batch of uploads
+    pub async fn create_batch(&self, new_batch: NewBatch) -> Result<Uuid> {
+        let batch = Batch {
+            id: Uuid::new_v4(),
+            user_id: new_batch.user_id,
+            tenant_id: new_batch.tenant_id,
+            created_at: Some(SystemTime::now()),
+        };
+        let batch_id = batch.id;
+        let mut batches = self.batches.lock().map_err(|_| anyhow!("Failed to lock batches"))?;
+        batches.insert(batch_id, batch);
+        Ok(batch_id)
+    }
+
+    /// Remove a batch no job was created for
+    pub async fn delete_batch(&self, batch_id: Uuid) -> Result<()> {
+        let mut batches = self.batches.lock().map_err(|_| anyhow!("Failed to lock batches"))?;
+        batches.remove(&batch_id);
+        Ok(())
+    }
+
+    /// Get a batch by ID
+    pub async fn get_batch(&self, batch_id: Uuid) -> Result<Option<Batch>> {
+        let batches = self.batches.lock().map_err(|_| anyhow!("Failed to lock batches"))?;
+        Ok(batches.get(&batch_id).cloned())
+    }
+
+    /// The jobs of a batch, oldest first
+    pub async fn list_batch_jobs(&self, batch_id: Uuid) -> Result<Vec<Job>> {
+        let jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
+        let mut batch_jobs: Vec<Job> = jobs.values().filter(|job| job.batch_id == Some(batch_id)).cloned().collect();
+        batch_jobs.sort_by_key(|job| (job.created_at, job.id));
+        Ok(batch_jobs)
+    }
+
     /// Get a job by ID
     pub async fn get_job(&self, job_id: Uuid) -> Result<Option<Job>> {
         let jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
         Ok(jobs.get(&job_id).cloned())
     }
     
-    /// Update job status
+    /// Up to `limit` of the jobs `filter` matches ordered by id, starting after `after`
+    pub async fn list_jobs(&self, filter: &JobFilter, after: Option<Uuid>, limit: usize) -> Result<Vec<Job>> {
+        let jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
+        let mut page: Vec<Job> = jobs
+            .values()
+            .filter(|job| filter.matches(job) && after.is_none_or(|after| job.id > after))
+            .cloned()
+            .collect();
+        page.sort_by_key(|job| job.id);
+        page.truncate(limit);
+        Ok(page)
+    }
+
+    /// Jobs a tenant created since `since`
+    pub async fn count_jobs_since(&self, tenant_id: &str, since: DateTime<Utc>) -> Result<u64> {
+        let jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
+        let since = SystemTime::from(since);
+        Ok(jobs
+            .values()
+            .filter(|job| job.tenant_id == tenant_id && job.created_at.is_some_and(|at| at >= since))
+            .count() as u64)
+    }
+
+    /// Update job status; starting processing counts an attempt and finishing
+    /// records when it ended. Transitions the current status doesn't allow are rejected.
     pub async fn update_job_status(&self, job_id: Uuid, status: JobStatus) -> Result<()> {
         let mut jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
         
         if let Some(job) = jobs.get_mut(&job_id) {
+            job.check_transition(status)?;
+            let now = Some(SystemTime::now());
+            match status {
+                JobStatus::Processing => {
+                    job.attempts += 1;
+                    job.started_at = now;
+                    job.finished_at = None;
+                    job.error_message = None;
+                }
+                JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled => job.finished_at = now,
+                JobStatus::Queued => {}
+            }
             job.status = status.to_string();
+            job.updated_at = now;
+            Ok(())
+        } else {
+            Err(anyhow!("Job not found"))
+        }
+    }
+
+    /// Mark a job failed with the reason
+    pub async fn fail_job(&self, job_id: Uuid, error_message: &str) -> Result<()> {
+        let mut jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
+
+        if let Some(job) = jobs.get_mut(&job_id) {
+            job.check_transition(JobStatus::Failed)?;
+            let now = Some(SystemTime::now());
+            job.status = JobStatus::Failed.to_string();
+            job.error_message = Some(error_message.to_string());
+            job.finished_at = now;
+            job.updated_at = now;
+            Ok(())
+        } else {
+            Err(anyhow!("Job not found"))
+        }
+    }
+
+    /// Record whether the job is being processed in low-memory mode
+    pub async fn set_job_low_memory(&self, job_id: Uuid, low_memory: bool) -> Result<()> {
+        let mut jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
+
+        if let Some(job) = jobs.get_mut(&job_id) {
+            job.low_memory = low_memory;
+            Ok(())
+        } else {
+            Err(anyhow!("Job not found"))
+        }
+    }
+
+    /// Record the shape of a job's dataset
+    pub async fn set_job_dataset_metadata(&self, job_id: Uuid, metadata: Option<&DatasetMetadata>) -> Result<()> {
+        let mut jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
+
+        if let Some(job) = jobs.get_mut(&job_id) {
+            job.dataset_metadata = metadata.cloned();
+            Ok(())
+        } else {
+            Err(anyhow!("Job not found"))
+        }
+    }
+
+    /// Replace the types a job's columns are read as
+    pub async fn update_job_schema(&self, job_id: Uuid, overrides: &SchemaOverrides) -> Result<()> {
+        let mut jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
+
+        if let Some(job) = jobs.get_mut(&job_id) {
+            job.schema_overrides = overrides.clone();
             job.updated_at = Some(SystemTime::now());
             Ok(())
         } else {
             Err(anyhow!("Job not found"))
         }
     }
+
+    /// Point a job at a new source file
+    pub async fn update_job_file(&self, job_id: Uuid, file_key: &str, content_hash: &str) -> Result<()> {
+        let mut jobs = self.jobs.lock().map_err(|_| anyhow!("Failed to lock jobs"))?;
+
+        if let Some(job) = jobs.get_mut(&job_id) {
+            job.file_key = file_key.to_string();
+            job.content_hash = Some(content_hash.to_string());
+            job.updated_at = Some(SystemTime::now());
+            Ok(())
+        } else {
+            Err(anyhow!("Job not found"))
+        }
+    }
+
+    /// Store a job's insights, replacing earlier ones
+    pub async fn save_insights(&self, job_id: Uuid, insights: &Insights) -> Result<()> {
+        let json = serde_json::to_string(insights)?;
+        let mut stored = self.insights.lock().map_err(|_| anyhow!("Failed to lock insights"))?;
+        stored.insert(job_id, json);
+        Ok(())
+    }
+
+    /// A job's stored insights as JSON
+    pub async fn get_insights(&self, job_id: Uuid) -> Result<Option<String>> {
+        let stored = self.insights.lock().map_err(|_| anyhow!("Failed to lock insights"))?;
+        Ok(stored.get(&job_id).cloned())
+    }
+
+    /// Add an entry's usage to the row for its day, job, tenant, user and conversation
+    pub async fn record_usage(&self, entry: &UsageEntry) -> Result<()> {
+        let key = (
+            entry.date,
+            entry.job_id.clone(),
+            entry.tenant_id.clone(),
+            entry.user_id.clone(),
+            entry.conversation_id.clone(),
+        );
+        let mut usage = self.usage.lock().map_err(|_| anyhow!("Failed to lock usage"))?;
+        *usage.entry(key).or_default() += entry.usage;
+        Ok(())
+    }
+
+    /// Usage rows matching the query
+    pub async fn list_usage(&self, query: &UsageQuery) -> Result<Vec<UsageEntry>> {
+        let usage = self.usage.lock().map_err(|_| anyhow!("Failed to lock usage"))?;
+        Ok(usage
+            .iter()
+            .filter(|((date, job_id, tenant_id, user_id, conversation_id), _)| {
+                query.job_id.as_ref().is_none_or(|id| id == job_id)
+                    && query.tenant_id.as_ref().is_none_or(|id| tenant_id.as_ref() == Some(id))
+                    && query.user_id.as_ref().is_none_or(|id| user_id.as_ref() == Some(id))
+                    && query.conversation_id.as_ref().is_none_or(|id| conversation_id.as_ref() == Some(id))
+                    && query.from.is_none_or(|from| *date >= from)
+                    && query.to.is_none_or(|to| *date <= to)
+            })
+            .map(|((date, job_id, tenant_id, user_id, conversation_id), usage)| UsageEntry {
+                date: *date,
+                job_id: job_id.clone(),
+                tenant_id: tenant_id.clone(),
+                user_id: user_id.clone(),
+                conversation_id: conversation_id.clone(),
+                usage: *usage,
+            })
+            .collect())
+    }
 }
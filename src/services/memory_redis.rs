@@ -1,25 +1,186 @@
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use log::debug;
+use tokio::sync::broadcast;
+
+use crate::models::cache::CacheStats;
+use crate::models::usage::UsageReservation;
+
+/// Messages a slow in-process subscriber may fall behind by before missing some
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Cached values up to an entry count and a byte budget. Writing past either
+/// evicts the least recently used entries; expired entries are dropped when
+/// read or by the periodic sweep, whichever comes first.
+#[derive(Debug)]
+struct CacheEntries {
+    entries: HashMap<String, CacheEntry>,
+    /// Keys by the tick they were last used at, oldest first
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: usize,
+    max_entries: usize,
+    max_bytes: usize,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    value: String,
+    expires_at: Option<Instant>,
+    last_used: u64,
+}
+
+impl CacheEntry {
+    /// Bytes the entry counts against the budget: its key and value
+    fn size(&self, key: &str) -> usize {
+        key.len() + self.value.len()
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+impl CacheEntries {
+    fn new(max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            max_entries,
+            max_bytes,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<String> {
+        match self.entries.get(key) {
+            Some(entry) if entry.is_expired(Instant::now()) => {
+                self.remove(key);
+                self.misses += 1;
+                return None;
+            }
+            Some(_) => self.hits += 1,
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        }
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        entry.last_used = self.tick;
+        self.recency.insert(self.tick, key.to_string());
+        Some(entry.value.clone())
+    }
+
+    /// Store a value, evicting the least recently used entries to stay within both
+    /// caps. A value larger than the whole byte budget is not cached.
+    fn insert(&mut self, key: &str, value: &str, expires_at: Option<Instant>) {
+        self.remove(key);
+        let size = key.len() + value.len();
+        if size > self.max_bytes || self.max_entries == 0 {
+            debug!("Cache entry {} ({} bytes) exceeds the cache budget, not caching it", key, size);
+            return;
+        }
+        while self.entries.len() >= self.max_entries || self.bytes + size > self.max_bytes {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.bytes -= evicted.size(&oldest);
+                self.evictions += 1;
+                debug!("Evicted cache entry {} ({} bytes)", oldest, evicted.size(&oldest));
+            }
+        }
+        self.tick += 1;
+        self.bytes += size;
+        self.recency.insert(self.tick, key.to_string());
+        self.entries.insert(key.to_string(), CacheEntry { value: value.to_string(), expires_at, last_used: self.tick });
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+            self.bytes -= entry.size(key);
+        }
+    }
+
+    /// Drop every expired entry, returning how many were dropped
+    fn sweep(&mut self) -> usize {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.is_expired(now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            self.remove(key);
+        }
+        expired.len()
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct MemoryRedisService {
-    data: Arc<Mutex<HashMap<String, (String, Option<Instant>)>>>,
+    data: Arc<Mutex<CacheEntries>>,
+    /// Pub/sub channels, reaching subscribers in this process only
+    channels: Arc<Mutex<HashMap<String, broadcast::Sender<String>>>>,
+    /// Held locks with their holder's token and expiry, kept apart from the
+    /// cached entries so eviction can never release one
+    locks: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    /// Usage counters with their expiry, kept apart from the cached entries for
+    /// the same reason
+    counters: Arc<Mutex<HashMap<String, (u64, Instant)>>>,
 }
 
 impl MemoryRedisService {
-    pub fn new() -> Self {
+    /// A cache holding at most `max_entries` entries and `max_bytes` of keys and values
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
         Self {
-            data: Arc::new(Mutex::new(HashMap::new())),
+            data: Arc::new(Mutex::new(CacheEntries::new(max_entries, max_bytes))),
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            locks: Arc::new(Mutex::new(HashMap::new())),
+            counters: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    /// Get a connection (no-op in memory implementation)
-    fn get_connection(&self) -> Result<()> {
-        Ok(())
+    fn lock_data(&self) -> Result<std::sync::MutexGuard<'_, CacheEntries>> {
+        self.data.lock().map_err(|_| anyhow!("Failed to lock data"))
     }
-    
+
+    /// Drop expired entries every `interval` for as long as the cache is in use,
+    /// so entries that are never read again don't hold memory until evicted
+    pub fn spawn_sweeper(&self, interval: Duration) {
+        let data = Arc::downgrade(&self.data);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(data) = data.upgrade() else {
+                    break;
+                };
+                let swept = match data.lock() {
+                    Ok(mut entries) => entries.sweep(),
+                    Err(_) => break,
+                };
+                if swept > 0 {
+                    debug!("🧹 Swept {} expired cache entries", swept);
+                }
+            }
+        });
+    }
+
     pub fn set_with_expiry(&self, key: &str, value: &str, expiry_secs: u64) -> Result<()> {
         let expiry = if expiry_secs > 0 {
             Some(Instant::now() + Duration::from_secs(expiry_secs))
@@ -27,39 +188,22 @@ impl MemoryRedisService {
             None
         };
         
-        let mut data = self.data.lock().map_err(|_| anyhow!("Failed to lock data"))?;
-        data.insert(key.to_string(), (value.to_string(), expiry));
+        self.lock_data()?.insert(key, value, expiry);
         Ok(())
     }
     
     pub fn get(&self, key: &str) -> Result<Option<String>> {
-        let mut data = self.data.lock().map_err(|_| anyhow!("Failed to lock data"))?;
-        
-        // Check if key exists and is not expired
-        if let Some((value, expiry)) = data.get(key) {
-            if let Some(expiry_time) = expiry {
-                if expiry_time < &Instant::now() {
-                    // Key has expired, remove it
-                    data.remove(key);
-                    return Ok(None);
-                }
-            }
-            return Ok(Some(value.clone()));
-        }
-        
-        Ok(None)
+        Ok(self.lock_data()?.get(key))
     }
     
     pub fn delete(&self, key: &str) -> Result<()> {
-        let mut data = self.data.lock().map_err(|_| anyhow!("Failed to lock data"))?;
-        data.remove(key);
+        self.lock_data()?.remove(key);
         Ok(())
     }
 
-    /// Set a value with an optional expiry
+    /// Set a value that doesn't expire
     pub fn set_value(&self, key: &str, value: &str) -> Result<()> {
-        let mut data = self.data.lock().map_err(|_| anyhow!("Failed to lock data"))?;
-        data.insert(key.to_string(), (value.to_string(), None));
+        self.lock_data()?.insert(key, value, None);
         Ok(())
     }
     
@@ -68,7 +212,103 @@ impl MemoryRedisService {
         self.get(key)
     }
 
+    /// Entries, bytes and counters since the cache was created
+    pub fn stats(&self) -> Result<CacheStats> {
+        let data = self.lock_data()?;
+        Ok(CacheStats {
+            backend: "memory".to_string(),
+            entries: data.entries.len() as u64,
+            bytes: data.bytes as u64,
+            max_entries: Some(data.max_entries as u64),
+            max_bytes: Some(data.max_bytes as u64),
+            hits: data.hits,
+            misses: data.misses,
+            evictions: data.evictions,
+            hit_ratio: CacheStats::ratio(data.hits, data.misses),
+        })
+    }
+
+    /// Send `message` to the subscribers of `channel`; with none listening it is dropped
+    pub fn publish(&self, channel: &str, message: &str) -> Result<()> {
+        let channels = self.channels.lock().map_err(|_| anyhow!("Failed to lock channels"))?;
+        if let Some(sender) = channels.get(channel) {
+            // Fails only when every subscriber has gone away
+            let _ = sender.send(message.to_string());
+        }
+        Ok(())
+    }
 
+    /// Receive the messages published on `channel` from now on
+    #[allow(dead_code)]
+    pub fn subscribe(&self, channel: &str) -> Result<broadcast::Receiver<String>> {
+        let mut channels = self.channels.lock().map_err(|_| anyhow!("Failed to lock channels"))?;
+        let sender = channels
+            .entry(channel.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0);
+        Ok(sender.subscribe())
+    }
 
+    /// Take the lock `name` for `ttl` unless an unexpired holder has it
+    pub fn try_lock(&self, name: &str, token: &str, ttl: Duration) -> Result<bool> {
+        let mut locks = self.locks.lock().map_err(|_| anyhow!("Failed to lock locks"))?;
+        let now = Instant::now();
+        if locks.get(name).is_some_and(|(_, expires_at)| *expires_at > now) {
+            return Ok(false);
+        }
+        locks.insert(name.to_string(), (token.to_string(), now + ttl));
+        Ok(true)
+    }
 
+    /// Release the lock `name` if `token` holds it
+    pub fn unlock(&self, name: &str, token: &str) -> Result<()> {
+        let mut locks = self.locks.lock().map_err(|_| anyhow!("Failed to lock locks"))?;
+        if locks.get(name).is_some_and(|(holder, _)| holder == token) {
+            locks.remove(name);
+        }
+        Ok(())
+    }
+
+    /// Extend the lock `name` to `ttl` from now if `token` holds it
+    pub fn relock(&self, name: &str, token: &str, ttl: Duration) -> Result<bool> {
+        let mut locks = self.locks.lock().map_err(|_| anyhow!("Failed to lock locks"))?;
+        match locks.get_mut(name) {
+            Some((holder, expires_at)) if holder == token => {
+                *expires_at = Instant::now() + ttl;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Add `amount` to the unexpired counter `name` unless that takes it past `limit`
+    pub fn reserve_counter(&self, name: &str, amount: u64, limit: u64) -> Result<UsageReservation> {
+        let mut counters = self.counters.lock().map_err(|_| anyhow!("Failed to lock counters"))?;
+        let Some((used, _)) = counters.get_mut(name).filter(|(_, expires_at)| *expires_at > Instant::now()) else {
+            return Ok(UsageReservation::Unset);
+        };
+        if *used + amount > limit {
+            return Ok(UsageReservation::Exceeded { used: *used });
+        }
+        *used += amount;
+        Ok(UsageReservation::Reserved { used: *used })
+    }
+
+    /// Set the counter `name` to `used` for `ttl` unless an unexpired one is set
+    pub fn seed_counter(&self, name: &str, used: u64, ttl: Duration) -> Result<()> {
+        let mut counters = self.counters.lock().map_err(|_| anyhow!("Failed to lock counters"))?;
+        let now = Instant::now();
+        if counters.get(name).is_none_or(|(_, expires_at)| *expires_at <= now) {
+            counters.insert(name.to_string(), (used, now + ttl));
+        }
+        Ok(())
+    }
+
+    /// Take `amount` off the counter `name`, never below zero
+    pub fn release_counter(&self, name: &str, amount: u64) -> Result<()> {
+        let mut counters = self.counters.lock().map_err(|_| anyhow!("Failed to lock counters"))?;
+        if let Some((used, _)) = counters.get_mut(name) {
+            *used = used.saturating_sub(amount);
+        }
+        Ok(())
+    }
 }
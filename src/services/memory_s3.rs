@@ -1,168 +1,165 @@
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, Mutex};
-use std::fs::{self, File};
-use std::io::{Write, Read};
-use std::path::Path;
-use log::{info, error};
+use log::{debug, info, error};
+
+use crate::models::storage::ObjectInfo;
+use crate::services::fs_storage::FsStorageService;
+
+/// Directory the in-memory objects are mirrored to, so they survive a restart
+const STORAGE_DIR: &str = "./storage";
+
+/// Objects held in memory up to a byte budget. Every object is also on disk, so
+/// the least recently used ones are dropped when the budget is exceeded and read
+/// back from disk on the next miss.
+#[derive(Debug)]
+struct ObjectCache {
+    entries: HashMap<String, CachedObject>,
+    /// Keys by the tick they were last used at, oldest first
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+#[derive(Debug)]
+struct CachedObject {
+    data: Bytes,
+    last_used: u64,
+}
+
+impl ObjectCache {
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// A cached object, shared with the cache rather than copied
+    fn get(&mut self, key: &str) -> Option<Bytes> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        entry.last_used = self.tick;
+        self.recency.insert(self.tick, key.to_string());
+        Some(entry.data.clone())
+    }
+
+    /// Cache an object, evicting the least recently used ones to stay within the
+    /// budget. Objects larger than the whole budget are left on disk only.
+    fn insert(&mut self, key: &str, data: Bytes) {
+        self.remove(key);
+        if data.len() > self.max_bytes {
+            debug!("Object {} ({} bytes) exceeds the memory budget, serving it from disk", key, data.len());
+            return;
+        }
+        while self.bytes + data.len() > self.max_bytes {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.bytes -= evicted.data.len();
+                debug!("Evicted {} ({} bytes) from memory", oldest, evicted.data.len());
+            }
+        }
+        self.tick += 1;
+        self.bytes += data.len();
+        self.recency.insert(self.tick, key.to_string());
+        self.entries.insert(key.to_string(), CachedObject { data, last_used: self.tick });
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+            self.bytes -= entry.data.len();
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct MemoryS3Service {
-    data: Arc<Mutex<HashMap<String, Vec<u8>>>>,
-    storage_dir: String,
+    cache: Arc<Mutex<ObjectCache>>,
+    disk: FsStorageService,
 }
 
 impl MemoryS3Service {
-    pub fn new() -> Self {
-        // Create storage directory if it doesn't exist
-        let storage_dir = "./storage";
-        if !Path::new(storage_dir).exists() {
-            fs::create_dir_all(storage_dir).unwrap_or_else(|e| {
-                error!("Failed to create storage directory: {}", e);
-            });
-        }
-        
-        info!("🗄️ Memory S3 service initialized with storage directory: {}", storage_dir);
-        
-        Self {
-            data: Arc::new(Mutex::new(HashMap::new())),
-            storage_dir: storage_dir.to_string(),
-        }
+    /// `max_bytes` bounds the objects kept in memory; the rest are read from disk.
+    /// `fsync` makes every write durable on disk before it returns.
+    pub fn new(max_bytes: usize, fsync: bool) -> Result<Self> {
+        let disk = FsStorageService::new(STORAGE_DIR, fsync)?;
+        info!("🗄️ Memory S3 service initialized with storage directory: {} (memory budget {} bytes)", STORAGE_DIR, max_bytes);
+
+        Ok(Self {
+            cache: Arc::new(Mutex::new(ObjectCache::new(max_bytes))),
+            disk,
+        })
     }
 
-    /// Upload data to in-memory storage and save to disk
-    pub async fn upload_file(&self, key: &str, data: Vec<u8>) -> Result<()> {
-        info!("📤 Uploading file to key: {} (size: {} bytes)", key, data.len());
-        
-        // Store in memory
-        let mut storage = self.data.lock().map_err(|e| {
+    fn lock_cache(&self) -> Result<std::sync::MutexGuard<'_, ObjectCache>> {
+        self.cache.lock().map_err(|e| {
             error!("Failed to lock storage: {}", e);
             anyhow!("Failed to lock storage")
-        })?;
-        storage.insert(key.to_string(), data.clone());
-        
-        // Also save to disk for debugging/verification
-        let file_path = self.get_file_path(key);
-        let dir_path = Path::new(&file_path).parent().unwrap();
-        
-        // Create directory if it doesn't exist
-        if !dir_path.exists() {
-            fs::create_dir_all(dir_path).map_err(|e| {
-                error!("Failed to create directory {}: {}", dir_path.display(), e);
-                anyhow!("Failed to create directory: {}", e)
-            })?;
-        }
-        
-        // Write file to disk
-        let mut file = File::create(&file_path).map_err(|e| {
-            error!("Failed to create file {}: {}", file_path, e);
-            anyhow!("Failed to create file: {}", e)
-        })?;
-        
-        file.write_all(&data).map_err(|e| {
-            error!("Failed to write to file {}: {}", file_path, e);
-            anyhow!("Failed to write to file: {}", e)
-        })?;
-        
-        info!("✅ File saved to disk at: {}", file_path);
+        })
+    }
+
+    /// Upload data to in-memory storage and save to disk
+    pub async fn upload_file(&self, key: &str, data: Bytes) -> Result<()> {
+        info!("📤 Uploading file to key: {} (size: {} bytes)", key, data.len());
+
+        // Write to disk first so an evicted object can always be read back; the
+        // cache keeps the same buffer the disk copy was written from
+        self.disk.upload_file(key, data.clone()).await?;
+        self.lock_cache()?.insert(key, data);
         Ok(())
     }
 
     /// Download data from in-memory storage
-    pub async fn download_file(&self, key: &str) -> Result<Vec<u8>> {
+    pub async fn download_file(&self, key: &str) -> Result<Bytes> {
         self.get_object("default-bucket", key).await
     }
-    
+
     /// Get object from in-memory storage or disk if not in memory
-    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Vec<u8>> {
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Bytes> {
         info!("🔍 Retrieving object: {}/{}", bucket, key);
-        
-        // Try to get from memory first. Lock is scoped to release before any .await.
-        let maybe_data_from_memory: Option<Vec<u8>> = {
-            let storage = self.data.lock().map_err(|e| {
-                error!("Failed to lock storage for memory check: {}", e);
-                anyhow!("Failed to lock storage for memory check")
-            })?;
-            let full_key = format!("{}/{}", bucket, key);
-            if let Some(data) = storage.get(&full_key) {
-                info!("✅ Found data in memory at full key: {} (size: {} bytes)", full_key, data.len());
-                Some(data.clone())
-            } else if let Some(data) = storage.get(key) {
-                info!("✅ Found data in memory at key: {} (size: {} bytes)", key, data.len());
-                Some(data.clone())
-            } else {
-                None
-            }
-            // MutexGuard `storage` is dropped here
-        };
 
-        if let Some(data) = maybe_data_from_memory {
-            return Ok(data); // Return if found in memory
+        // The guard is a temporary, released before any .await
+        let cached = self.lock_cache()?.get(key);
+        if let Some(data) = cached {
+            info!("✅ Found data in memory at key: {} (size: {} bytes)", key, data.len());
+            return Ok(data);
         }
 
-        // If not in memory, proceed to disk read. No MutexGuard held here.
-        let file_path = self.get_file_path(key);
-        let file_path_for_blocking = file_path.clone();
-        if Path::new(&file_path).exists() {
-            let data_from_disk = tokio::task::spawn_blocking(move || {
-                info!("[BLOCKING_TASK] Attempting to open file: {}", file_path_for_blocking);
-                let mut file = File::open(&file_path_for_blocking).map_err(|e| {
-                    error!("[BLOCKING_TASK] Failed to open file {}: {}", file_path_for_blocking, e);
-                    std::io::Error::new(e.kind(), format!("Failed to open file {}: {}", file_path_for_blocking, e))
-                })?;
-                info!("[BLOCKING_TASK] Successfully opened file. Attempting to read: {}", file_path_for_blocking);
-                let mut buffer = Vec::new();
-                file.read_to_end(&mut buffer).map_err(|e| {
-                    error!("[BLOCKING_TASK] Failed to read file {}: {}", file_path_for_blocking, e);
-                    std::io::Error::new(e.kind(), format!("Failed to read file {}: {}", file_path_for_blocking, e))
-                })?;
-                info!("[BLOCKING_TASK] Successfully read file (size: {} bytes): {}", buffer.len(), file_path_for_blocking);
-                Ok::<_, std::io::Error>(buffer)
-            })
-            .await // Async thread awaits completion
-            .inspect(|res| { // Log immediately after await returns
-                match res {
-                    Ok(_) => info!("[ASYNC_TASK] spawn_blocking for file read completed successfully."),
-                    Err(join_error) => error!("[ASYNC_TASK] spawn_blocking for file read failed with JoinError: {}", join_error),
-                }
-            })
-            .map_err(|e| anyhow!("Task join error during file reading: {}", e))? // Handle JoinError
-            .map_err(|e| { // Handle std::io::Error from file operations
-                error!("I/O error during spawned file read (spawn_blocking task): {}", e);
-                anyhow!("I/O error during spawned file read (spawn_blocking task): {}", e)
-            })?;
-            let mut storage = self.data.lock().map_err(|e| {
-                error!("Failed to lock storage: {}", e);
-                anyhow!("Failed to lock storage")
-            })?;
-            storage.insert(key.to_string(), data_from_disk.clone());
-            
-            info!("✅ Read file from disk: {} (size: {} bytes)", file_path, data_from_disk.len());
-            return Ok(data_from_disk);
+        // If not in memory, fall back to the copy on disk
+        match self.disk.download_file(key).await {
+            Ok(data_from_disk) => {
+                info!("✅ Read file from disk: {} (size: {} bytes)", key, data_from_disk.len());
+                self.lock_cache()?.insert(key, data_from_disk.clone());
+                Ok(data_from_disk)
+            }
+            Err(e) => {
+                error!("❌ Object not found: {}/{} ({})", bucket, key, e);
+                Err(anyhow!("Object not found: {}/{}", bucket, key))
+            }
         }
-        
-        // Not found anywhere
-        error!("❌ Object not found: {}/{}", bucket, key);
-        Err(anyhow!("Object not found: {}/{}", bucket, key))
     }
-    
-    // Helper method to get file path on disk
-    fn get_file_path(&self, key: &str) -> String {
-        format!("{}/{}", self.storage_dir, key)
+
+    /// Remove an object from memory and disk
+    pub async fn delete_object(&self, key: &str) -> Result<()> {
+        self.lock_cache()?.remove(key);
+        self.disk.delete_object(key).await
+    }
+
+    /// Every object is written through to disk, so the disk copy is the full listing
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        self.disk.list_objects(prefix).await
     }
-    
-    // List all files in storage (both memory and disk)
-    pub fn list_files(&self) -> Vec<String> {
-        let mut files = Vec::new();
-        
-        // Get files from memory
-        if let Ok(storage) = self.data.lock() {
-            for key in storage.keys() {
-                files.push(key.clone());
-            }
-        }
-        
-        info!("📋 Files in storage: {:?}", files);
-        files
+
+    pub async fn head_object(&self, key: &str) -> Result<Option<ObjectInfo>> {
+        self.disk.head_object(key).await
     }
 }
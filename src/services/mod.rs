@@ -3,65 +3,261 @@ pub mod database;
 pub mod redis;
 pub mod processor;
 pub mod memory_db;
+pub mod sqlite_db;
 pub mod memory_redis;
 pub mod memory_s3;
+pub mod fs_storage;
 pub mod ai;
+pub mod llm;
 pub mod conversation;
 pub mod query_translator;
+pub mod sql_guard;
+pub mod column_resolver;
+pub mod rule_translator;
+pub mod analysis;
+pub mod export;
+pub mod suggestions;
+pub mod grounding;
+pub mod narration;
+pub mod usage;
+pub mod storage;
+pub mod job_store;
+pub mod cache;
+pub mod registry;
+pub mod auth;
+pub mod quota;
+pub mod encryption;
+pub mod admin;
+pub mod fetch;
+pub mod spill;
+pub mod job_queue;
 
 use anyhow::Result;
 
 // Define traits for service functionality
 #[async_trait::async_trait]
 pub trait S3ServiceTrait: Send + Sync + 'static {
-    async fn upload_file(&self, key: &str, data: Vec<u8>) -> Result<()>;
-    async fn download_file(&self, key: &str) -> Result<Vec<u8>>;
-    async fn get_object(&self, bucket: &str, key: &str) -> Result<Vec<u8>>;
+    /// Objects are passed as `Bytes` so the body of an upload reaches the backend,
+    /// and a cached object reaches its reader, without being copied
+    async fn upload_file(&self, key: &str, data: bytes::Bytes) -> Result<()>;
+    #[allow(dead_code)]
+    async fn download_file(&self, key: &str) -> Result<bytes::Bytes>;
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<bytes::Bytes>;
+    /// Remove an object; removing one that does not exist is not an error
+    async fn delete_object(&self, key: &str) -> Result<()>;
+    /// Objects whose key starts with `prefix`, sorted by key
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<crate::models::storage::ObjectInfo>>;
+    /// Size and modification time of an object, or `None` when it does not exist
+    async fn head_object(&self, key: &str) -> Result<Option<crate::models::storage::ObjectInfo>>;
+    /// A time-limited URL a client can fetch the object from directly, or `None`
+    /// when the backend cannot sign one and the object must be served by the API
+    async fn presigned_url(&self, bucket: &str, key: &str, expires_in: std::time::Duration) -> Result<Option<String>>;
+    /// Short name of the storage backend, reported by `/capabilities`
+    fn backend_name(&self) -> &'static str;
 }
 
 #[async_trait::async_trait]
 pub trait DatabaseServiceTrait: Send + Sync + 'static {
     async fn create_job(&self, new_job: crate::models::job::NewJob) -> Result<uuid::Uuid>;
     async fn get_job(&self, job_id: uuid::Uuid) -> Result<Option<crate::models::job::Job>>;
+    async fn create_batch(&self, new_batch: crate::models::batch::NewBatch) -> Result<uuid::Uuid>;
+    /// Remove a batch no job was created for
+    async fn delete_batch(&self, batch_id: uuid::Uuid) -> Result<()>;
+    async fn get_batch(&self, batch_id: uuid::Uuid) -> Result<Option<crate::models::batch::Batch>>;
+    /// Jobs created for a batch's files, oldest first
+    async fn list_batch_jobs(&self, batch_id: uuid::Uuid) -> Result<Vec<crate::models::job::Job>>;
+    /// Up to `limit` of the jobs `filter` matches ordered by id, starting after `after`,
+    /// for walking them in pages
+    async fn list_jobs(
+        &self,
+        filter: &crate::models::job::JobFilter,
+        after: Option<uuid::Uuid>,
+        limit: usize,
+    ) -> Result<Vec<crate::models::job::Job>>;
+    /// Jobs a tenant created since `since`, for the daily job quota
+    async fn count_jobs_since(&self, tenant_id: &str, since: chrono::DateTime<chrono::Utc>) -> Result<u64>;
     async fn update_job_status(&self, job_id: uuid::Uuid, status: crate::models::job::JobStatus) -> Result<()>;
+    /// Mark a job failed, recording why
+    async fn fail_job(&self, job_id: uuid::Uuid, error_message: &str) -> Result<()>;
+    /// Record whether the job's current processing runs in low-memory mode
+    async fn set_job_low_memory(&self, job_id: uuid::Uuid, low_memory: bool) -> Result<()>;
+    /// Record the shape of a job's dataset, or clear it while it is out of date
+    async fn set_job_dataset_metadata(&self, job_id: uuid::Uuid, metadata: Option<&crate::models::conversation::DatasetMetadata>) -> Result<()>;
+    /// Replace the types a job's columns are read as
+    async fn update_job_schema(&self, job_id: uuid::Uuid, overrides: &crate::models::schema::SchemaOverrides) -> Result<()>;
+    /// Point a job at a new source file, recording its content hash
+    async fn update_job_file(&self, job_id: uuid::Uuid, file_key: &str, content_hash: &str) -> Result<()>;
+    /// Store a job's insights, replacing earlier ones; the cache is refilled from here
+    async fn save_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()>;
+    /// A job's stored insights as JSON
+    async fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>>;
+    /// Add an entry's usage to the row for its day, job, tenant, user and conversation
+    async fn record_usage(&self, entry: &crate::models::usage::UsageEntry) -> Result<()>;
+    /// Usage rows matching the query, in no particular order
+    async fn list_usage(&self, query: &crate::models::usage::UsageQuery) -> Result<Vec<crate::models::usage::UsageEntry>>;
+    /// Short name of the job database backend, reported by `/capabilities`
+    fn backend_name(&self) -> &'static str;
 }
 
 #[async_trait::async_trait]
-pub trait RedisServiceTrait: Send + Sync + 'static {
-    fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>>;
-    fn cache_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()>;
+pub trait RedisServiceTrait: Send + Sync + std::fmt::Debug + 'static {
+    /// Cached values are `Ok(None)` when missing; errors mean the cache itself failed
+    async fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>>;
+    async fn cache_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()>;
+    async fn get_aggregate_state(&self, job_id: uuid::Uuid) -> Result<Option<String>>;
+    async fn cache_aggregate_state(&self, job_id: uuid::Uuid, state: &analysis::incremental::AggregateState) -> Result<()>;
+    /// Per-column statistics of a job's last processing, reused for columns whose content is unchanged
+    async fn get_column_stats(&self, job_id: uuid::Uuid) -> Result<Option<String>>;
+    async fn cache_column_stats(&self, job_id: uuid::Uuid, stats: &analysis::column_cache::ColumnStatsCache) -> Result<()>;
+    async fn get_profile_settings(&self, job_id: uuid::Uuid) -> Result<Option<String>>;
+    async fn cache_profile_settings(&self, job_id: uuid::Uuid, settings: &crate::models::profile::ProfileSettings) -> Result<()>;
+    async fn get_ai_response(&self, key: &str) -> Result<Option<String>>;
+    async fn cache_ai_response(&self, key: &str, response: &str, expiry_secs: u64) -> Result<()>;
+    /// Drop a job's cached insights and aggregate state, so the next read loads the stored copy
+    async fn invalidate_insights(&self, job_id: uuid::Uuid) -> Result<()>;
+    /// Announce a job status change on `JOB_EVENTS_CHANNEL`
+    async fn publish_job_event(&self, event: &crate::models::job::JobEvent) -> Result<()>;
+    /// Whether a job's insights are being recomputed, per a marker set with `mark_insights_pending`
+    async fn insights_pending(&self, job_id: uuid::Uuid) -> Result<bool>;
+    /// Note that a job's insights are being recomputed, for `ttl_secs` at most
+    async fn mark_insights_pending(&self, job_id: uuid::Uuid, ttl_secs: u64) -> Result<()>;
+    async fn clear_insights_pending(&self, job_id: uuid::Uuid) -> Result<()>;
+    /// Take the lock `name` for `ttl` unless someone else holds it, returning whether
+    /// it was taken. `token` identifies the holder so only it can release the lock.
+    async fn acquire_lock(&self, name: &str, token: &str, ttl: std::time::Duration) -> Result<bool>;
+    /// Release the lock `name` if `token` still holds it
+    async fn release_lock(&self, name: &str, token: &str) -> Result<()>;
+    /// Extend the lock `name` to `ttl` from now if `token` still holds it, returning
+    /// whether it does
+    async fn renew_lock(&self, name: &str, token: &str, ttl: std::time::Duration) -> Result<bool>;
+    /// Atomically add `amount` to the usage counter `name` unless that takes it past `limit`
+    async fn reserve_usage(&self, name: &str, amount: u64, limit: u64) -> Result<crate::models::usage::UsageReservation>;
+    /// Set the usage counter `name` to `used` for `ttl`, unless it is already set
+    async fn seed_usage(&self, name: &str, used: u64, ttl: std::time::Duration) -> Result<()>;
+    /// Give back `amount` of the usage counter `name`, if it is set
+    async fn release_usage(&self, name: &str, amount: u64) -> Result<()>;
+    /// Entry count, size, hit ratio and evictions, for the admin API
+    async fn stats(&self) -> Result<crate::models::cache::CacheStats>;
+    /// Short name of the cache backend, reported by `/capabilities`
+    fn backend_name(&self) -> &'static str;
+}
+
+/// A chat model the AI features send their prompts to
+#[async_trait::async_trait]
+pub trait LlmProviderTrait: Send + Sync + std::fmt::Debug + 'static {
+    /// Send a system and user prompt, returning the reply with the tokens it consumed
+    async fn chat(&self, request: &llm::ChatRequest) -> Result<(String, crate::models::usage::TokenUsage)>;
+    /// Short name of the provider, reported by `/capabilities`
+    fn backend_name(&self) -> &'static str;
+    /// Model (or Azure deployment) the requests go to
+    fn model(&self) -> &str;
 }
 
 // Implement the traits for both real and memory services
 #[cfg(feature = "external-services")]
 #[async_trait::async_trait]
 impl S3ServiceTrait for s3::S3Service {
-    async fn upload_file(&self, key: &str, data: Vec<u8>) -> Result<()> {
+    async fn upload_file(&self, key: &str, data: bytes::Bytes) -> Result<()> {
         self.upload_file(key, data).await
     }
     
-    async fn download_file(&self, key: &str) -> Result<Vec<u8>> {
+    async fn download_file(&self, key: &str) -> Result<bytes::Bytes> {
         self.download_file(key).await
     }
     
-    async fn get_object(&self, bucket: &str, key: &str) -> Result<Vec<u8>> {
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<bytes::Bytes> {
         self.get_object(bucket, key).await
     }
+
+    async fn delete_object(&self, key: &str) -> Result<()> {
+        self.delete_object(key).await
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<crate::models::storage::ObjectInfo>> {
+        self.list_objects(prefix).await
+    }
+
+    async fn head_object(&self, key: &str) -> Result<Option<crate::models::storage::ObjectInfo>> {
+        self.head_object(key).await
+    }
+
+    async fn presigned_url(&self, bucket: &str, key: &str, expires_in: std::time::Duration) -> Result<Option<String>> {
+        self.presigned_url(bucket, key, expires_in).await
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "s3"
+    }
 }
 
 #[async_trait::async_trait]
 impl S3ServiceTrait for memory_s3::MemoryS3Service {
-    async fn upload_file(&self, key: &str, data: Vec<u8>) -> Result<()> {
+    async fn upload_file(&self, key: &str, data: bytes::Bytes) -> Result<()> {
+        self.upload_file(key, data).await
+    }
+    
+    async fn download_file(&self, key: &str) -> Result<bytes::Bytes> {
+        self.download_file(key).await
+    }
+    
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<bytes::Bytes> {
+        self.get_object(bucket, key).await
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<()> {
+        self.delete_object(key).await
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<crate::models::storage::ObjectInfo>> {
+        self.list_objects(prefix).await
+    }
+
+    async fn head_object(&self, key: &str) -> Result<Option<crate::models::storage::ObjectInfo>> {
+        self.head_object(key).await
+    }
+
+    async fn presigned_url(&self, _bucket: &str, _key: &str, _expires_in: std::time::Duration) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "memory"
+    }
+}
+
+#[async_trait::async_trait]
+impl S3ServiceTrait for fs_storage::FsStorageService {
+    async fn upload_file(&self, key: &str, data: bytes::Bytes) -> Result<()> {
         self.upload_file(key, data).await
     }
     
-    async fn download_file(&self, key: &str) -> Result<Vec<u8>> {
+    async fn download_file(&self, key: &str) -> Result<bytes::Bytes> {
         self.download_file(key).await
     }
     
-    async fn get_object(&self, bucket: &str, key: &str) -> Result<Vec<u8>> {
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<bytes::Bytes> {
         self.get_object(bucket, key).await
     }
+
+    async fn delete_object(&self, key: &str) -> Result<()> {
+        self.delete_object(key).await
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<crate::models::storage::ObjectInfo>> {
+        self.list_objects(prefix).await
+    }
+
+    async fn head_object(&self, key: &str) -> Result<Option<crate::models::storage::ObjectInfo>> {
+        self.head_object(key).await
+    }
+
+    async fn presigned_url(&self, _bucket: &str, _key: &str, _expires_in: std::time::Duration) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "fs"
+    }
 }
 
 #[cfg(feature = "external-services")]
@@ -74,10 +270,164 @@ impl DatabaseServiceTrait for database::DatabaseService {
     async fn get_job(&self, job_id: uuid::Uuid) -> Result<Option<crate::models::job::Job>> {
         self.get_job(job_id).await
     }
+
+    async fn create_batch(&self, new_batch: crate::models::batch::NewBatch) -> Result<uuid::Uuid> {
+        self.create_batch(new_batch).await
+    }
+
+    async fn delete_batch(&self, batch_id: uuid::Uuid) -> Result<()> {
+        self.delete_batch(batch_id).await
+    }
+
+    async fn get_batch(&self, batch_id: uuid::Uuid) -> Result<Option<crate::models::batch::Batch>> {
+        self.get_batch(batch_id).await
+    }
+
+    async fn list_batch_jobs(&self, batch_id: uuid::Uuid) -> Result<Vec<crate::models::job::Job>> {
+        self.list_batch_jobs(batch_id).await
+    }
+
+    async fn list_jobs(
+        &self,
+        filter: &crate::models::job::JobFilter,
+        after: Option<uuid::Uuid>,
+        limit: usize,
+    ) -> Result<Vec<crate::models::job::Job>> {
+        self.list_jobs(filter, after, limit).await
+    }
+
+    async fn count_jobs_since(&self, tenant_id: &str, since: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        self.count_jobs_since(tenant_id, since).await
+    }
     
     async fn update_job_status(&self, job_id: uuid::Uuid, status: crate::models::job::JobStatus) -> Result<()> {
         self.update_job_status(job_id, status).await
     }
+
+    async fn fail_job(&self, job_id: uuid::Uuid, error_message: &str) -> Result<()> {
+        self.fail_job(job_id, error_message).await
+    }
+
+    async fn set_job_low_memory(&self, job_id: uuid::Uuid, low_memory: bool) -> Result<()> {
+        self.set_job_low_memory(job_id, low_memory).await
+    }
+
+    async fn set_job_dataset_metadata(&self, job_id: uuid::Uuid, metadata: Option<&crate::models::conversation::DatasetMetadata>) -> Result<()> {
+        self.set_job_dataset_metadata(job_id, metadata).await
+    }
+
+    async fn update_job_schema(&self, job_id: uuid::Uuid, overrides: &crate::models::schema::SchemaOverrides) -> Result<()> {
+        self.update_job_schema(job_id, overrides).await
+    }
+
+    async fn update_job_file(&self, job_id: uuid::Uuid, file_key: &str, content_hash: &str) -> Result<()> {
+        self.update_job_file(job_id, file_key, content_hash).await
+    }
+
+    async fn save_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()> {
+        self.save_insights(job_id, insights).await
+    }
+
+    async fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_insights(job_id).await
+    }
+
+    async fn record_usage(&self, entry: &crate::models::usage::UsageEntry) -> Result<()> {
+        self.record_usage(entry).await
+    }
+
+    async fn list_usage(&self, query: &crate::models::usage::UsageQuery) -> Result<Vec<crate::models::usage::UsageEntry>> {
+        self.list_usage(query).await
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "postgres"
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait::async_trait]
+impl DatabaseServiceTrait for sqlite_db::SqliteDatabaseService {
+    async fn create_job(&self, new_job: crate::models::job::NewJob) -> Result<uuid::Uuid> {
+        self.create_job(new_job).await
+    }
+    
+    async fn get_job(&self, job_id: uuid::Uuid) -> Result<Option<crate::models::job::Job>> {
+        self.get_job(job_id).await
+    }
+
+    async fn create_batch(&self, new_batch: crate::models::batch::NewBatch) -> Result<uuid::Uuid> {
+        self.create_batch(new_batch).await
+    }
+
+    async fn delete_batch(&self, batch_id: uuid::Uuid) -> Result<()> {
+        self.delete_batch(batch_id).await
+    }
+
+    async fn get_batch(&self, batch_id: uuid::Uuid) -> Result<Option<crate::models::batch::Batch>> {
+        self.get_batch(batch_id).await
+    }
+
+    async fn list_batch_jobs(&self, batch_id: uuid::Uuid) -> Result<Vec<crate::models::job::Job>> {
+        self.list_batch_jobs(batch_id).await
+    }
+
+    async fn list_jobs(
+        &self,
+        filter: &crate::models::job::JobFilter,
+        after: Option<uuid::Uuid>,
+        limit: usize,
+    ) -> Result<Vec<crate::models::job::Job>> {
+        self.list_jobs(filter, after, limit).await
+    }
+
+    async fn count_jobs_since(&self, tenant_id: &str, since: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        self.count_jobs_since(tenant_id, since).await
+    }
+    
+    async fn update_job_status(&self, job_id: uuid::Uuid, status: crate::models::job::JobStatus) -> Result<()> {
+        self.update_job_status(job_id, status).await
+    }
+
+    async fn fail_job(&self, job_id: uuid::Uuid, error_message: &str) -> Result<()> {
+        self.fail_job(job_id, error_message).await
+    }
+
+    async fn set_job_low_memory(&self, job_id: uuid::Uuid, low_memory: bool) -> Result<()> {
+        self.set_job_low_memory(job_id, low_memory).await
+    }
+
+    async fn set_job_dataset_metadata(&self, job_id: uuid::Uuid, metadata: Option<&crate::models::conversation::DatasetMetadata>) -> Result<()> {
+        self.set_job_dataset_metadata(job_id, metadata).await
+    }
+
+    async fn update_job_schema(&self, job_id: uuid::Uuid, overrides: &crate::models::schema::SchemaOverrides) -> Result<()> {
+        self.update_job_schema(job_id, overrides).await
+    }
+
+    async fn update_job_file(&self, job_id: uuid::Uuid, file_key: &str, content_hash: &str) -> Result<()> {
+        self.update_job_file(job_id, file_key, content_hash).await
+    }
+
+    async fn save_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()> {
+        self.save_insights(job_id, insights).await
+    }
+
+    async fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_insights(job_id).await
+    }
+
+    async fn record_usage(&self, entry: &crate::models::usage::UsageEntry) -> Result<()> {
+        self.record_usage(entry).await
+    }
+
+    async fn list_usage(&self, query: &crate::models::usage::UsageQuery) -> Result<Vec<crate::models::usage::UsageEntry>> {
+        self.list_usage(query).await
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "sqlite"
+    }
 }
 
 #[async_trait::async_trait]
@@ -89,35 +439,281 @@ impl DatabaseServiceTrait for memory_db::MemoryDatabaseService {
     async fn get_job(&self, job_id: uuid::Uuid) -> Result<Option<crate::models::job::Job>> {
         self.get_job(job_id).await
     }
+
+    async fn create_batch(&self, new_batch: crate::models::batch::NewBatch) -> Result<uuid::Uuid> {
+        self.create_batch(new_batch).await
+    }
+
+    async fn delete_batch(&self, batch_id: uuid::Uuid) -> Result<()> {
+        self.delete_batch(batch_id).await
+    }
+
+    async fn get_batch(&self, batch_id: uuid::Uuid) -> Result<Option<crate::models::batch::Batch>> {
+        self.get_batch(batch_id).await
+    }
+
+    async fn list_batch_jobs(&self, batch_id: uuid::Uuid) -> Result<Vec<crate::models::job::Job>> {
+        self.list_batch_jobs(batch_id).await
+    }
+
+    async fn list_jobs(
+        &self,
+        filter: &crate::models::job::JobFilter,
+        after: Option<uuid::Uuid>,
+        limit: usize,
+    ) -> Result<Vec<crate::models::job::Job>> {
+        self.list_jobs(filter, after, limit).await
+    }
+
+    async fn count_jobs_since(&self, tenant_id: &str, since: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        self.count_jobs_since(tenant_id, since).await
+    }
     
     async fn update_job_status(&self, job_id: uuid::Uuid, status: crate::models::job::JobStatus) -> Result<()> {
         self.update_job_status(job_id, status).await
     }
+
+    async fn fail_job(&self, job_id: uuid::Uuid, error_message: &str) -> Result<()> {
+        self.fail_job(job_id, error_message).await
+    }
+
+    async fn set_job_low_memory(&self, job_id: uuid::Uuid, low_memory: bool) -> Result<()> {
+        self.set_job_low_memory(job_id, low_memory).await
+    }
+
+    async fn set_job_dataset_metadata(&self, job_id: uuid::Uuid, metadata: Option<&crate::models::conversation::DatasetMetadata>) -> Result<()> {
+        self.set_job_dataset_metadata(job_id, metadata).await
+    }
+
+    async fn update_job_schema(&self, job_id: uuid::Uuid, overrides: &crate::models::schema::SchemaOverrides) -> Result<()> {
+        self.update_job_schema(job_id, overrides).await
+    }
+
+    async fn update_job_file(&self, job_id: uuid::Uuid, file_key: &str, content_hash: &str) -> Result<()> {
+        self.update_job_file(job_id, file_key, content_hash).await
+    }
+
+    async fn save_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()> {
+        self.save_insights(job_id, insights).await
+    }
+
+    async fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_insights(job_id).await
+    }
+
+    async fn record_usage(&self, entry: &crate::models::usage::UsageEntry) -> Result<()> {
+        self.record_usage(entry).await
+    }
+
+    async fn list_usage(&self, query: &crate::models::usage::UsageQuery) -> Result<Vec<crate::models::usage::UsageEntry>> {
+        self.list_usage(query).await
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "memory"
+    }
 }
 
 #[cfg(feature = "external-services")]
 #[async_trait::async_trait]
 impl RedisServiceTrait for redis::RedisService {
-    fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
-        self.get_value(&format!("insights:{}", job_id))
+    async fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_value(&format!("insights:{}", job_id)).await
     }
     
-    fn cache_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()> {
+    async fn cache_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()> {
         let insights_json = serde_json::to_string(insights)?;
-        self.set_with_expiry(&format!("insights:{}", job_id), &insights_json, 3600 * 24)
+        self.set_with_expiry(&format!("insights:{}", job_id), &insights_json, 3600 * 24).await
+    }
+
+    async fn get_aggregate_state(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_value(&format!("insights_state:{}", job_id)).await
+    }
+
+    async fn cache_aggregate_state(&self, job_id: uuid::Uuid, state: &analysis::incremental::AggregateState) -> Result<()> {
+        let state_json = serde_json::to_string(state)?;
+        self.set_with_expiry(&format!("insights_state:{}", job_id), &state_json, 3600 * 24).await
+    }
+
+    async fn get_column_stats(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_value(&format!("column_stats:{}", job_id)).await
+    }
+
+    async fn cache_column_stats(&self, job_id: uuid::Uuid, stats: &analysis::column_cache::ColumnStatsCache) -> Result<()> {
+        let stats_json = serde_json::to_string(stats)?;
+        self.set_with_expiry(&format!("column_stats:{}", job_id), &stats_json, 3600 * 24).await
+    }
+
+    async fn get_profile_settings(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_value(&format!("insights_profile:{}", job_id)).await
+    }
+
+    async fn cache_profile_settings(&self, job_id: uuid::Uuid, settings: &crate::models::profile::ProfileSettings) -> Result<()> {
+        let settings_json = serde_json::to_string(settings)?;
+        self.set_with_expiry(&format!("insights_profile:{}", job_id), &settings_json, 3600 * 24).await
+    }
+
+    async fn get_ai_response(&self, key: &str) -> Result<Option<String>> {
+        self.get_value(&format!("ai_response:{}", key)).await
+    }
+
+    async fn cache_ai_response(&self, key: &str, response: &str, expiry_secs: u64) -> Result<()> {
+        self.set_with_expiry(&format!("ai_response:{}", key), response, expiry_secs).await
+    }
+
+    async fn invalidate_insights(&self, job_id: uuid::Uuid) -> Result<()> {
+        self.delete(&[format!("insights:{}", job_id), format!("insights_state:{}", job_id)]).await
+    }
+
+    async fn publish_job_event(&self, event: &crate::models::job::JobEvent) -> Result<()> {
+        let event_json = serde_json::to_string(event)?;
+        self.publish(crate::models::job::JOB_EVENTS_CHANNEL, &event_json).await
+    }
+
+    async fn insights_pending(&self, job_id: uuid::Uuid) -> Result<bool> {
+        Ok(self.get_value(&format!("insights_pending:{}", job_id)).await?.is_some())
+    }
+
+    async fn mark_insights_pending(&self, job_id: uuid::Uuid, ttl_secs: u64) -> Result<()> {
+        self.set_with_expiry(&format!("insights_pending:{}", job_id), "1", ttl_secs).await
+    }
+
+    async fn clear_insights_pending(&self, job_id: uuid::Uuid) -> Result<()> {
+        self.delete(&[format!("insights_pending:{}", job_id)]).await
+    }
+
+    async fn acquire_lock(&self, name: &str, token: &str, ttl: std::time::Duration) -> Result<bool> {
+        self.set_if_absent(&format!("lock:{}", name), token, ttl).await
+    }
+
+    async fn release_lock(&self, name: &str, token: &str) -> Result<()> {
+        self.delete_if_equal(&format!("lock:{}", name), token).await
+    }
+
+    async fn renew_lock(&self, name: &str, token: &str, ttl: std::time::Duration) -> Result<bool> {
+        self.expire_if_equal(&format!("lock:{}", name), token, ttl).await
+    }
+
+    async fn reserve_usage(&self, name: &str, amount: u64, limit: u64) -> Result<crate::models::usage::UsageReservation> {
+        self.reserve_counter(&format!("usage:{}", name), amount, limit).await
+    }
+
+    async fn seed_usage(&self, name: &str, used: u64, ttl: std::time::Duration) -> Result<()> {
+        self.set_if_absent(&format!("usage:{}", name), &used.to_string(), ttl).await.map(|_| ())
+    }
+
+    async fn release_usage(&self, name: &str, amount: u64) -> Result<()> {
+        self.release_counter(&format!("usage:{}", name), amount).await
+    }
+
+    async fn stats(&self) -> Result<crate::models::cache::CacheStats> {
+        self.stats().await
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "redis"
     }
 }
 
 #[async_trait::async_trait]
 impl RedisServiceTrait for memory_redis::MemoryRedisService {
-    fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+    async fn get_insights(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
         self.get_value(&format!("insights:{}", job_id))
     }
     
-    fn cache_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()> {
+    async fn cache_insights(&self, job_id: uuid::Uuid, insights: &crate::models::response::Insights) -> Result<()> {
         let insights_json = serde_json::to_string(insights)?;
         self.set_value(&format!("insights:{}", job_id), &insights_json)
     }
+
+    async fn get_aggregate_state(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_value(&format!("insights_state:{}", job_id))
+    }
+
+    async fn cache_aggregate_state(&self, job_id: uuid::Uuid, state: &analysis::incremental::AggregateState) -> Result<()> {
+        let state_json = serde_json::to_string(state)?;
+        self.set_value(&format!("insights_state:{}", job_id), &state_json)
+    }
+
+    async fn get_column_stats(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_value(&format!("column_stats:{}", job_id))
+    }
+
+    async fn cache_column_stats(&self, job_id: uuid::Uuid, stats: &analysis::column_cache::ColumnStatsCache) -> Result<()> {
+        let stats_json = serde_json::to_string(stats)?;
+        self.set_value(&format!("column_stats:{}", job_id), &stats_json)
+    }
+
+    async fn get_profile_settings(&self, job_id: uuid::Uuid) -> Result<Option<String>> {
+        self.get_value(&format!("insights_profile:{}", job_id))
+    }
+
+    async fn cache_profile_settings(&self, job_id: uuid::Uuid, settings: &crate::models::profile::ProfileSettings) -> Result<()> {
+        let settings_json = serde_json::to_string(settings)?;
+        self.set_value(&format!("insights_profile:{}", job_id), &settings_json)
+    }
+
+    async fn get_ai_response(&self, key: &str) -> Result<Option<String>> {
+        self.get_value(&format!("ai_response:{}", key))
+    }
+
+    async fn cache_ai_response(&self, key: &str, response: &str, expiry_secs: u64) -> Result<()> {
+        self.set_with_expiry(&format!("ai_response:{}", key), response, expiry_secs)
+    }
+
+    async fn invalidate_insights(&self, job_id: uuid::Uuid) -> Result<()> {
+        self.delete(&format!("insights:{}", job_id))?;
+        self.delete(&format!("insights_state:{}", job_id))
+    }
+
+    async fn publish_job_event(&self, event: &crate::models::job::JobEvent) -> Result<()> {
+        let event_json = serde_json::to_string(event)?;
+        self.publish(crate::models::job::JOB_EVENTS_CHANNEL, &event_json)
+    }
+
+    async fn insights_pending(&self, job_id: uuid::Uuid) -> Result<bool> {
+        Ok(self.get_value(&format!("insights_pending:{}", job_id))?.is_some())
+    }
+
+    async fn mark_insights_pending(&self, job_id: uuid::Uuid, ttl_secs: u64) -> Result<()> {
+        self.set_with_expiry(&format!("insights_pending:{}", job_id), "1", ttl_secs)
+    }
+
+    async fn clear_insights_pending(&self, job_id: uuid::Uuid) -> Result<()> {
+        self.delete(&format!("insights_pending:{}", job_id))
+    }
+
+    async fn acquire_lock(&self, name: &str, token: &str, ttl: std::time::Duration) -> Result<bool> {
+        self.try_lock(name, token, ttl)
+    }
+
+    async fn release_lock(&self, name: &str, token: &str) -> Result<()> {
+        self.unlock(name, token)
+    }
+
+    async fn renew_lock(&self, name: &str, token: &str, ttl: std::time::Duration) -> Result<bool> {
+        self.relock(name, token, ttl)
+    }
+
+    async fn reserve_usage(&self, name: &str, amount: u64, limit: u64) -> Result<crate::models::usage::UsageReservation> {
+        self.reserve_counter(name, amount, limit)
+    }
+
+    async fn seed_usage(&self, name: &str, used: u64, ttl: std::time::Duration) -> Result<()> {
+        self.seed_counter(name, used, ttl)
+    }
+
+    async fn release_usage(&self, name: &str, amount: u64) -> Result<()> {
+        self.release_counter(name, amount)
+    }
+
+    async fn stats(&self) -> Result<crate::models::cache::CacheStats> {
+        self.stats()
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "memory"
+    }
 }
 
 // Re-export the services
@@ -0,0 +1,172 @@
+use polars::prelude::*;
+
+use crate::i18n::{Locale, Message};
+use crate::services::query_translator::{aggregation_name, ColumnOperation, QueryIntent, StructuredQuery};
+
+/// Answer a query in plain words from its result, without the AI service: the
+/// aggregate values, the highest and lowest groups, the rows matched or the sort
+/// order, depending on what the query did. `dataset_rows` is the size of the
+/// dataset the query ran over.
+pub fn describe_result(query: &StructuredQuery, df: &DataFrame, dataset_rows: usize, locale: Locale) -> String {
+    let rows = df.height();
+    if rows == 0 {
+        return Message::NoDataFound.localize(locale);
+    }
+
+    let groups: Vec<&str> = query
+        .operations
+        .iter()
+        .filter_map(|op| match op {
+            ColumnOperation::GroupBy(c) => Some(c.as_str()),
+            _ => None,
+        })
+        .collect();
+    // (label, result column) of every aggregation the result holds
+    let aggregates: Vec<(String, String)> = query
+        .operations
+        .iter()
+        .filter_map(|op| {
+            let name = aggregation_name(op)?;
+            let column = aggregated_column(op)?;
+            let aggregation = name.strip_suffix(&format!("_{}", column))?;
+            df.column(&name).ok()?;
+            Some((Message::AggregationOf(aggregation, column).localize(locale), name))
+        })
+        .collect();
+    let conditions: Vec<String> = query
+        .operations
+        .iter()
+        .filter_map(|op| match op {
+            ColumnOperation::Filter(c, operator, value) | ColumnOperation::Having(c, operator, value) => {
+                Some(format!("{} {} {}", c, operator, value))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut sentences = Vec::new();
+    if let Some(ColumnOperation::Pivot(index, pivot, _, _)) =
+        query.operations.iter().find(|op| matches!(op, ColumnOperation::Pivot(..)))
+    {
+        sentences.push(Message::PivotTable(index, pivot, rows, df.width().saturating_sub(1)).localize(locale));
+    } else if matches!(query.intent, QueryIntent::Describe) {
+        sentences.push(Message::FirstRows(rows, dataset_rows).localize(locale));
+    } else if !aggregates.is_empty() && groups.is_empty() {
+        let values: Vec<String> = aggregates
+            .iter()
+            .map(|(label, name)| Message::AggregateValue(label, &cell(df, name, 0, locale)).localize(locale))
+            .collect();
+        let mut sentence = values.join("; ");
+        if conditions.is_empty() {
+            sentence.push_str(&Message::AcrossRows(dataset_rows).localize(locale));
+        }
+        sentences.push(sentence);
+    } else if !groups.is_empty() {
+        let group_label = groups.join(", ");
+        sentences.push(Message::GroupCount(rows, &group_label).localize(locale));
+        // Groups without aggregations are counted
+        let count_column = format!("count_{}", groups[0]);
+        let count_label = Message::AggregationOf("count", groups[0]).localize(locale);
+        let measure = aggregates
+            .first()
+            .map(|(label, name)| (label.as_str(), name.as_str()))
+            .or_else(|| df.column(&count_column).ok().map(|_| (count_label.as_str(), count_column.as_str())));
+        if let (true, Some((label, name))) = (rows > 1, measure) {
+            if let Some((top, bottom)) = extremes(df, name) {
+                let group = |row| groups.iter().map(|g| cell(df, g, row, locale)).collect::<Vec<_>>().join(" / ");
+                sentences.push(
+                    Message::GroupExtremes(
+                        label,
+                        &group(top),
+                        &cell(df, name, top, locale),
+                        &group(bottom),
+                        &cell(df, name, bottom, locale),
+                    )
+                    .localize(locale),
+                );
+            }
+        }
+    } else if let Some(column) = sort_column(query, df) {
+        sentences.push(Message::SortedRows(rows, &column, &cell(df, &column, 0, locale)).localize(locale));
+    } else if !conditions.is_empty() {
+        sentences.push(Message::MatchingRows(rows, &conditions.join(&Message::And.localize(locale))).localize(locale));
+    } else {
+        sentences.push(Message::ResultRows(rows).localize(locale));
+    }
+
+    sentences.iter().map(|sentence| format!("{}.", capitalize(sentence))).collect::<Vec<_>>().join(" ")
+}
+
+/// Dataset column an aggregation is computed over
+fn aggregated_column(op: &ColumnOperation) -> Option<&str> {
+    match op {
+        ColumnOperation::Mean(c)
+        | ColumnOperation::Sum(c)
+        | ColumnOperation::Count(c)
+        | ColumnOperation::Median(c)
+        | ColumnOperation::Quantile(c, _)
+        | ColumnOperation::Std(c)
+        | ColumnOperation::Min(c)
+        | ColumnOperation::Max(c)
+        | ColumnOperation::DistinctCount(c) => Some(c),
+        _ => None,
+    }
+}
+
+/// Column the result is ordered by, as named in the result
+fn sort_column(query: &StructuredQuery, df: &DataFrame) -> Option<String> {
+    let column = query.operations.iter().find_map(|op| match op {
+        ColumnOperation::SortBy(keys, _) => keys.first().map(|(c, _)| c.as_str()),
+        ColumnOperation::TopN(c, _, _) => Some(c.as_str()),
+        _ => None,
+    })?;
+    df.column(column).ok().map(|_| column.to_string())
+}
+
+/// Rows with the highest and lowest value of a numeric column
+fn extremes(df: &DataFrame, column: &str) -> Option<(usize, usize)> {
+    let series = df.column(column).ok()?;
+    if !series.dtype().is_numeric() {
+        return None;
+    }
+    let values = series.cast(&DataType::Float64).ok()?;
+    let values: Vec<(usize, f64)> = values
+        .f64()
+        .ok()?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(row, value)| Some((row, value?)))
+        .collect();
+    let top = values.iter().max_by(|a, b| a.1.total_cmp(&b.1))?.0;
+    let bottom = values.iter().min_by(|a, b| a.1.total_cmp(&b.1))?.0;
+    Some((top, bottom))
+}
+
+/// A result cell as it reads in a sentence, numbers in the locale's format
+fn cell(df: &DataFrame, column: &str, row: usize, locale: Locale) -> String {
+    let Ok(series) = df.column(column) else {
+        return String::new();
+    };
+    if series.dtype().is_numeric() {
+        if let Some(value) = series
+            .cast(&DataType::Float64)
+            .ok()
+            .and_then(|values| values.f64().ok().and_then(|values| values.get(row)))
+        {
+            return locale.format_number(value);
+        }
+    }
+    match series.get(row) {
+        Ok(AnyValue::Null) | Err(_) => "—".to_string(),
+        Ok(AnyValue::Utf8(value)) => value.to_string(),
+        Ok(value) => value.to_string(),
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
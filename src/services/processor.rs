@@ -1,14 +1,87 @@
-use anyhow::{Result, anyhow, Context};
+use anyhow::{Result, anyhow, bail, Context};
+use bytes::Bytes;
 use polars::prelude::*;
+use rayon::prelude::*;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
-use crate::models::job::{Job, JobStatus};
-use crate::models::response::{Insights, DataSummary, ColumnStatistics, AISummary, ActionableRecommendation};
+use crate::models::conversation::DatasetMetadata;
+use crate::models::job::{InvalidTransition, Job, JobEvent, JobFilter, JobStatus};
+use crate::models::profile::{InsightProfile, ProfileSettings};
+use crate::models::query::ExportFormat;
+use crate::models::storage::{content_hash, tenant_key};
+use crate::models::usage::TokenUsage;
+use crate::models::schema::{DatasetSchema, SchemaOverrides};
+use crate::models::response::{DriftReport, Insights, DataSummary, SamplingInfo, ColumnStatistics, ColumnFlag, AISummary, SummaryPart, TextProfile, TypeSuggestion, LongTail};
 use crate::services::{S3ServiceTrait, DatabaseServiceTrait, RedisServiceTrait};
 use crate::services::ai::AIService;
+use crate::services::analysis::column_cache::{self, CachedColumn, ColumnStatsCache};
+use crate::services::analysis::incremental::AggregateState;
+use crate::services::analysis::{anomaly, association, cardinality, dependency, drift, extremes, incremental, pii, plausibility, sampling, schema, text};
+use crate::services::export::export_dataframe;
+use crate::services::quota::{QuotaExceeded, TenantQuotas};
+use crate::services::spill::{self, FrameEstimate, SpillFile};
+use crate::services::usage::UsageLedger;
 use crate::config::Config;
 
+/// Fewest rows a low-memory chunk holds, however small the budget
+const MIN_CHUNK_ROWS: usize = 1000;
+
+/// Processing or appending to a job another worker or instance already holds
+#[derive(Debug)]
+pub struct JobLocked(pub Uuid);
+
+impl std::fmt::Display for JobLocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Job {} is already being processed", self.0)
+    }
+}
+
+impl std::error::Error for JobLocked {}
+
+/// Appended rows the dataset can't take: a malformed CSV, columns that differ from
+/// the dataset's, or values that don't fit its column types
+#[derive(Debug)]
+pub struct InvalidAppend(pub String);
+
+impl std::fmt::Display for InvalidAppend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidAppend {}
+
+/// The service an append failed on, attached as context so the failure is
+/// answered as that service's error rather than the caller's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendFault {
+    Storage,
+    Database,
+    Cache,
+}
+
+impl std::fmt::Display for AppendFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppendFault::Storage => write!(f, "Reading or writing the dataset's files failed"),
+            AppendFault::Database => write!(f, "The job database failed"),
+            AppendFault::Cache => write!(f, "The cache failed"),
+        }
+    }
+}
+
+/// Seconds the insights-pending marker outlives a recompute that never clears it
+const INSIGHTS_PENDING_TTL_SECS: u64 = 30;
+
+/// A job's insights, or word that they are being computed
+#[derive(Debug)]
+pub enum InsightsLookup {
+    Ready(String),
+    Pending,
+}
+
 #[derive(Clone, Debug)]
 pub struct DataProcessor<S, D, R>
 where
@@ -20,7 +93,27 @@ where
     db_service: D,
     redis_service: R,
     ai_service: Option<AIService>,
+    ai_analysis_enabled: bool,
+    sample_threshold_rows: usize,
+    sample_size: usize,
+    insight_profile: InsightProfile,
     s3_bucket: String,
+    download_url_expiry: std::time::Duration,
+    dedup_uploads: bool,
+    job_lock_ttl: std::time::Duration,
+    batch_max_files: usize,
+    batch_fetch_max_bytes: u64,
+    /// Largest file `/upload` and appends accept; 0 for no limit
+    max_upload_bytes: u64,
+    /// Bytes a parsed dataset may take before it is processed in low-memory mode; 0 for no limit
+    memory_budget: u64,
+    /// Directory large uploads are spilled to in low-memory mode
+    spill_dir: std::path::PathBuf,
+    /// Insights recomputes in flight in this process, so concurrent requests for
+    /// the same job wait on one recompute instead of each starting their own
+    recomputes: Arc<Mutex<HashMap<Uuid, Arc<tokio::sync::Mutex<()>>>>>,
+    usage: UsageLedger<D>,
+    quotas: TenantQuotas,
 }
 
 impl<S, D, R> DataProcessor<S, D, R>
@@ -29,46 +122,381 @@ where
     D: DatabaseServiceTrait + Clone + std::fmt::Debug,
     R: RedisServiceTrait + Clone + std::fmt::Debug,
 {
-    pub fn new(s3_service: S, db_service: D, redis_service: R, s3_bucket: String) -> Self {
-        // Load config from environment
-        let config = Config::from_env();
-        
-        // Try to initialize the AI service, but don't fail if it can't be created
-        let ai_service = match AIService::new(&config) {
-            Ok(service_option) => service_option,
-            Err(e) => {
-                log::warn!("Failed to initialize AI service: {}", e);
-                None
-            }
-        };
-        
-        if ai_service.is_some() {
-            log::info!("AI service initialized successfully");
-        } else {
+    /// Create a processor; `ai_service` is shared with the conversation service so
+    /// limits on AI calls hold across both
+    pub fn new(s3_service: S, db_service: D, redis_service: R, config: &Config, ai_service: Option<AIService>) -> Self {
+        if ai_service.is_none() {
             log::info!("AI service not available - AI analysis features will be disabled");
+        } else if !config.ai_analysis_enabled {
+            log::info!("AI analysis during job processing disabled by AI_ANALYSIS_ENABLED");
         }
         
+        let usage = UsageLedger::new(db_service.clone());
         Self {
             s3_service,
             db_service,
             redis_service,
             ai_service,
-            s3_bucket,
+            ai_analysis_enabled: config.ai_analysis_enabled,
+            sample_threshold_rows: config.sample_threshold_rows,
+            sample_size: config.sample_size,
+            insight_profile: config.insight_profile,
+            s3_bucket: config.s3_bucket.clone(),
+            download_url_expiry: std::time::Duration::from_secs(config.download_url_expiry_secs),
+            dedup_uploads: config.storage_dedup,
+            job_lock_ttl: std::time::Duration::from_secs(config.job_lock_ttl_secs),
+            batch_max_files: config.batch_max_files,
+            batch_fetch_max_bytes: config.batch_fetch_max_bytes,
+            max_upload_bytes: config.max_upload_bytes,
+            memory_budget: config.processing_memory_budget_bytes,
+            spill_dir: config.spill_dir.clone(),
+            recomputes: Arc::new(Mutex::new(HashMap::new())),
+            usage,
+            quotas: TenantQuotas::from_config(config),
         }
     }
 
-    /// Process a job with the given ID
-    ///  - parse CSV 
-    ///  - generate insights (no chart rendering here)
-    ///  - cache the JSON(insights) in Redis
+    /// Profile requested for a job at upload time, or the configured default
+    async fn profile_settings(&self, job_id: Uuid) -> ProfileSettings {
+        match self.redis_service.get_profile_settings(job_id).await {
+            Ok(Some(settings_json)) => serde_json::from_str(&settings_json).unwrap_or_else(|e| {
+                log::warn!("⚠️ [Job-{}] Ignoring unreadable profile settings: {}", job_id, e);
+                self.default_profile()
+            }),
+            Ok(None) => self.default_profile(),
+            Err(e) => {
+                log::warn!("⚠️ [Job-{}] Failed to load profile settings: {}", job_id, e);
+                self.default_profile()
+            }
+        }
+    }
+
+    /// Profile used when an upload does not ask for one
+    pub fn default_profile(&self) -> ProfileSettings {
+        ProfileSettings {
+            profile: self.insight_profile,
+            ..Default::default()
+        }
+    }
+
+    /// Row count above which insights are computed on a sample, and the sample size
+    pub fn sampling_limits(&self) -> (usize, usize) {
+        (self.sample_threshold_rows, self.sample_size)
+    }
+
+    /// Whether completed jobs get an AI summary attached to their insights
+    pub fn ai_analysis_enabled(&self) -> bool {
+        self.ai_analysis_enabled && self.ai_service.is_some()
+    }
+
+    /// Ledger of the AI spend of every job
+    pub fn usage_ledger(&self) -> &UsageLedger<D> {
+        &self.usage
+    }
+
+    /// How long a processing job holds its lock, after which it counts as stuck
+    pub fn job_lock_ttl(&self) -> std::time::Duration {
+        self.job_lock_ttl
+    }
+
+    /// Most files a batch upload may hold, and the largest file it fetches from a URL
+    pub fn batch_limits(&self) -> (usize, u64) {
+        (self.batch_max_files, self.batch_fetch_max_bytes)
+    }
+
+    /// Largest file `/upload` and appends accept, `None` when uploads are not capped
+    pub fn max_upload_bytes(&self) -> Option<u64> {
+        Some(self.max_upload_bytes).filter(|max| *max > 0)
+    }
+
+    /// Storage, job and AI limits of each tenant
+    pub fn quotas(&self) -> TenantQuotas {
+        self.quotas
+    }
+
+    /// Reserve a job and `new_bytes` of storage for an upload, failing with
+    /// `QuotaExceeded` when the tenant has no room for them
+    pub async fn reserve_upload(&self, tenant_id: &str, new_bytes: u64) -> Result<()> {
+        self.quotas.reserve_job(&self.db_service, &self.redis_service, tenant_id).await?;
+        if let Err(e) = self.quotas.reserve_storage(&self.s3_service, &self.redis_service, tenant_id, new_bytes).await {
+            self.quotas.release_job(&self.redis_service, tenant_id).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Give back what `reserve_upload` took for an upload that created no job
+    pub async fn release_upload(&self, tenant_id: &str, new_bytes: u64) {
+        self.quotas.release_job(&self.redis_service, tenant_id).await;
+        self.quotas.release_storage(&self.redis_service, tenant_id, new_bytes).await;
+    }
+
+    /// The AI service, scoped to a tenant and holding tokens of its daily AI quota
+    /// until the service drops, unless the tenant spent its AI tokens for today
+    pub async fn tenant_ai(&self, ai_service: Option<&AIService>, tenant_id: &str) -> Option<AIService> {
+        let ai_service = ai_service?;
+        let cache: Arc<dyn RedisServiceTrait> = Arc::new(self.redis_service.clone());
+        match self.quotas.reserve_ai(&self.usage, cache, tenant_id).await {
+            Ok(reservation) => Some(ai_service.for_tenant(tenant_id, reservation)),
+            Err(e) if e.downcast_ref::<QuotaExceeded>().is_some() => None,
+            Err(e) => {
+                log::warn!("⚠️ Failed to read the AI usage of tenant {}: {:#}", tenant_id, e);
+                Some(ai_service.for_tenant(tenant_id, None))
+            }
+        }
+    }
+
+    /// Bucket new uploads are stored in
+    pub fn bucket(&self) -> &str {
+        &self.s3_bucket
+    }
+
+    /// How long presigned dataset download URLs stay valid
+    pub fn download_url_expiry(&self) -> std::time::Duration {
+        self.download_url_expiry
+    }
+
+    /// Whether uploads are stored once per content hash
+    pub fn dedup_uploads(&self) -> bool {
+        self.dedup_uploads
+    }
+
+    /// A job's insights as JSON, read through the cache: a miss falls back to the
+    /// database and refills the cache, so expired entries don't force reprocessing.
+    /// An unavailable cache is read past the same way.
+    pub async fn insights(&self, job_id: Uuid) -> Result<Option<String>> {
+        match self.redis_service.get_insights(job_id).await {
+            Ok(Some(json)) => return Ok(Some(json)),
+            Ok(None) => {}
+            Err(e) => log::warn!("⚠️ [Job-{}] Failed to read the insights cache: {:#}", job_id, e),
+        }
+        let Some(json) = self.db_service.get_insights(job_id).await? else {
+            return Ok(None);
+        };
+        match serde_json::from_str::<Insights>(&json) {
+            Ok(insights) => {
+                if let Err(e) = self.redis_service.cache_insights(job_id, &insights).await {
+                    log::warn!("⚠️ [Job-{}] Failed to refill the insights cache: {}", job_id, e);
+                }
+            }
+            Err(e) => log::warn!("⚠️ [Job-{}] Stored insights could not be parsed: {}", job_id, e),
+        }
+        Ok(Some(json))
+    }
+
+    /// A completed job's insights, recomputing them when none are stored. Requests in
+    /// this process that miss together share one recompute; while one runs anywhere,
+    /// a short-lived marker in the cache answers other requests with `Pending`.
+    pub async fn insights_or_recompute(&self, job_id: Uuid) -> Result<InsightsLookup> {
+        if let Some(json) = self.insights(job_id).await? {
+            return Ok(InsightsLookup::Ready(json));
+        }
+        match self.redis_service.insights_pending(job_id).await {
+            Ok(true) => return Ok(InsightsLookup::Pending),
+            Ok(false) => {}
+            Err(e) => log::warn!("⚠️ [Job-{}] Failed to read the insights-pending marker: {:#}", job_id, e),
+        }
+
+        let recompute = {
+            let mut recomputes = self.recomputes.lock().map_err(|_| anyhow!("Failed to lock recomputes"))?;
+            recomputes.entry(job_id).or_default().clone()
+        };
+        let result = {
+            let _guard = recompute.lock().await;
+            self.recompute_insights(job_id).await
+        };
+        let mut recomputes = self.recomputes.lock().map_err(|_| anyhow!("Failed to lock recomputes"))?;
+        // The map and this request hold the only references once nobody else waits
+        if Arc::strong_count(&recompute) == 2 {
+            recomputes.remove(&job_id);
+        }
+        result
+    }
+
+    /// Recompute a job's insights unless a request that held the recompute first
+    /// already stored them
+    async fn recompute_insights(&self, job_id: Uuid) -> Result<InsightsLookup> {
+        if let Some(json) = self.insights(job_id).await? {
+            return Ok(InsightsLookup::Ready(json));
+        }
+        if let Err(e) = self.redis_service.mark_insights_pending(job_id, INSIGHTS_PENDING_TTL_SECS).await {
+            log::warn!("⚠️ [Job-{}] Failed to set the insights-pending marker: {:#}", job_id, e);
+        }
+        let processed = self.reprocess_job(job_id).await;
+        if let Err(e) = self.redis_service.clear_insights_pending(job_id).await {
+            log::warn!("⚠️ [Job-{}] Failed to clear the insights-pending marker: {:#}", job_id, e);
+        }
+        match processed {
+            Ok(()) => self
+                .insights(job_id)
+                .await?
+                .map(InsightsLookup::Ready)
+                .ok_or_else(|| anyhow!("Job {} finished processing without stored insights", job_id)),
+            Err(e) if e.downcast_ref::<JobLocked>().is_some() => Ok(InsightsLookup::Pending),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Save a job's insights to the database, then cache them. The database is the
+    /// record, so a cache failure only costs the next read a database lookup.
+    async fn store_insights(&self, job_id: Uuid, insights: &Insights) -> Result<()> {
+        self.db_service.save_insights(job_id, insights).await?;
+        if let Err(e) = self.redis_service.cache_insights(job_id, insights).await {
+            log::warn!("⚠️ [Job-{}] Failed to cache insights: {}", job_id, e);
+        }
+        Ok(())
+    }
+
+    /// Announce a job status change. Subscribers only follow along, so a failed
+    /// publish is logged rather than failing the job.
+    pub async fn publish_status(&self, job_id: Uuid, status: JobStatus, error_message: Option<&str>) {
+        let event = JobEvent::new(job_id, status, error_message);
+        if let Err(e) = self.redis_service.publish_job_event(&event).await {
+            log::warn!("⚠️ [Job-{}] Failed to publish the {} event: {:#}", job_id, status, e);
+        }
+    }
+
+    /// Mark a job failed with the reason and announce it
+    pub async fn fail_job(&self, job_id: Uuid, reason: &str) -> Result<()> {
+        self.db_service.fail_job(job_id, reason).await?;
+        self.publish_status(job_id, JobStatus::Failed, Some(reason)).await;
+        Ok(())
+    }
+
+    /// Drop a job's cached insights and aggregate state; reads fall back to the
+    /// stored insights and appends to a full recompute until they are cached again
+    pub async fn invalidate_cache(&self, job_id: Uuid) -> Result<()> {
+        self.redis_service.invalidate_insights(job_id).await?;
+        log::info!("🧽 [Job-{}] Cleared cached insights", job_id);
+        Ok(())
+    }
+
     /// Get a reference to the S3 service
     pub fn get_s3_service(&self) -> &S {
         &self.s3_service
     }
-    
+
+    /// Get a reference to the database service
+    pub fn get_db_service(&self) -> &D {
+        &self.db_service
+    }
+
+    /// Get a reference to the cache service
+    pub fn get_redis_service(&self) -> &R {
+        &self.redis_service
+    }
+
+    /// Attribute the AI spend of processing a job to its tenant and owner
+    async fn record_usage(&self, job: &Job, usage: TokenUsage) {
+        if let Err(e) = self.usage.record(&job.id.to_string(), Some(&job.tenant_id), Some(&job.user_id), None, usage).await {
+            log::warn!("⚠️ [Job-{}] Failed to record AI usage: {}", job.id, e);
+        }
+    }
+
+    /// Process a job, marking it failed with the error when processing fails. A job
+    /// whose status moved on meanwhile (e.g. it was cancelled) is left as it is.
+    /// Processing holds the job's lock in the cache, so instances sharing it never
+    /// process the same job at once; a held lock fails with `JobLocked`.
     pub async fn process_job(&self, job_id: Uuid) -> Result<()> {
+        let token = match self.acquire_job_lock(job_id).await {
+            Ok(token) => Some(token),
+            Err(e) if e.downcast_ref::<JobLocked>().is_some() => return Err(e),
+            Err(e) => {
+                // Processing twice only wastes work, so an unavailable cache doesn't stop the job
+                log::warn!("⚠️ [Job-{}] Processing without a lock, the cache could not take it: {:#}", job_id, e);
+                None
+            }
+        };
+
+        let result = match &token {
+            Some(token) => self.holding_job_lock(job_id, token, self.process_locked(job_id)).await,
+            None => self.process_locked(job_id).await,
+        };
+        if let Some(token) = token {
+            self.release_job_lock(job_id, &token).await;
+        }
+        result
+    }
+
+    /// Process a completed job again, e.g. one whose insights were never stored,
+    /// under its lock like `process_job`
+    async fn reprocess_job(&self, job_id: Uuid) -> Result<()> {
+        let token = self.acquire_job_lock(job_id).await?;
+        let result = self.holding_job_lock(job_id, &token, self.reprocess_locked(job_id)).await;
+        self.release_job_lock(job_id, &token).await;
+        result
+    }
+
+    /// Queue a completed job whose lock the caller holds again and process it; only
+    /// queued jobs may start processing
+    async fn reprocess_locked(&self, job_id: Uuid) -> Result<()> {
+        self.db_service.update_job_status(job_id, JobStatus::Queued).await?;
+        self.publish_status(job_id, JobStatus::Queued, None).await;
+        self.process_locked(job_id).await
+    }
+
+    /// Process a job whose lock the caller holds, recording a failure on the job
+    async fn process_locked(&self, job_id: Uuid) -> Result<()> {
+        let result = self.run_job(job_id).await;
+        if let Err(e) = &result {
+            if e.downcast_ref::<InvalidTransition>().is_some() {
+                log::warn!("⚠️ [Job-{}] Stopped processing: {}", job_id, e);
+            } else if let Err(record_error) = self.fail_job(job_id, &format!("{:#}", e)).await {
+                log::error!("❌ [Job-{}] Failed to record the failure: {}", job_id, record_error);
+            }
+        }
+        result
+    }
+
+    /// Take the job's lock in the cache, returning the token that releases it; a
+    /// lock someone else holds fails with `JobLocked`
+    async fn acquire_job_lock(&self, job_id: Uuid) -> Result<String> {
+        let token = Uuid::new_v4().to_string();
+        if self.redis_service.acquire_lock(&format!("job:{}", job_id), &token, self.job_lock_ttl).await? {
+            Ok(token)
+        } else {
+            Err(JobLocked(job_id).into())
+        }
+    }
+
+    /// Run `work` while renewing the job's lock every third of `JOB_LOCK_TTL_SECS`, so
+    /// a job that runs longer than the TTL keeps its lock, while one whose instance
+    /// died still loses it after the TTL. A lock lost anyway, e.g. when the cache
+    /// was unreachable for longer than the TTL, is logged and the work carries on.
+    async fn holding_job_lock<T>(&self, job_id: Uuid, token: &str, work: impl std::future::Future<Output = T>) -> T {
+        let name = format!("job:{}", job_id);
+        let heartbeat = async {
+            loop {
+                tokio::time::sleep(self.job_lock_ttl / 3).await;
+                match self.redis_service.renew_lock(&name, token, self.job_lock_ttl).await {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        log::warn!("⚠️ [Job-{}] Lost the job lock, another instance may take the job over", job_id);
+                        break;
+                    }
+                    Err(e) => log::warn!("⚠️ [Job-{}] Failed to renew the job lock: {:#}", job_id, e),
+                }
+            }
+            std::future::pending::<()>().await
+        };
+        tokio::select! {
+            result = work => result,
+            _ = heartbeat => unreachable!("the heartbeat never ends"),
+        }
+    }
+
+    async fn release_job_lock(&self, job_id: Uuid, token: &str) {
+        if let Err(e) = self.redis_service.release_lock(&format!("job:{}", job_id), token).await {
+            log::warn!("⚠️ [Job-{}] Failed to release the job lock, it expires on its own: {:#}", job_id, e);
+        }
+    }
+
+    /// Process a job with the given ID
+    ///  - parse CSV
+    ///  - generate insights (no chart rendering here)
+    ///  - store the insights and cache them
+    async fn run_job(&self, job_id: Uuid) -> Result<()> {
         log::info!("🔍 [Job-{}] Starting job processing", job_id);
-        log::info!("📃 [Job-{}] Processing details: bucket={}", job_id, self.s3_bucket);
         
         // 1) Mark job as "Processing"
         log::info!("⏳ [Job-{}] Updating status to Processing", job_id);
@@ -79,6 +507,7 @@ where
                 log::error!("❌ [Job-{}] Failed to update status to Processing: {}", job_id, e);
                 e
             })?;
+        self.publish_status(job_id, JobStatus::Processing, None).await;
     
         // 2) Fetch job details
         log::info!("📋 [Job-{}] Fetching job details from database", job_id);
@@ -90,35 +519,29 @@ where
                 return Err(err);
             }
         };
+        // Tie the worker's log lines to the request that created the job
+        if let Some(request_id) = &job.request_id {
+            tracing::Span::current().record("request_id", tracing::field::display(request_id));
+        }
     
-        log::info!("📥 [Job-{}] Downloading file: {} from bucket: {}", job_id, job.file_key, self.s3_bucket);
-        log::info!("🔎 [Job-{}] Attempting to retrieve file with key: {}", job_id, job.file_key);
+        let source = job.source_key();
+        log::info!("📥 [Job-{}] Downloading file: {} from bucket: {}", job_id, source.key, source.bucket);
         
-        match self.s3_service.get_object(&self.s3_bucket, &job.file_key).await {
+        let csv_data = match self.s3_service.get_object(&source.bucket, &source.key).await {
             Ok(data) => {
                 log::info!("✅ [Job-{}] Successfully downloaded file: {} (size: {} bytes)", job_id, job.file_key, data.len());
-                let csv_data = data;
-        
-                log::info!("📊 [Job-{}] Parsing CSV data (size: {} bytes)", job_id, csv_data.len());
-                let parse_start = std::time::Instant::now();
-                match self.parse_csv_data(&csv_data) {
-                    Ok(dataframe) => {
-                        let parse_duration = parse_start.elapsed();
-                        log::info!("✅ [Job-{}] Successfully parsed CSV in {:.2?}: {} rows, {} columns", 
-                            job_id, parse_duration, dataframe.height(), dataframe.width());
-                        let df = dataframe;
-        
-                        log::info!("🧠 [Job-{}] Generating insights for dataframe", job_id);
-                        let insights_start = std::time::Instant::now();
-                        match self.generate_insights(&df) {
-                            Ok(result) => {
-                                let insights_duration = insights_start.elapsed();
-                                log::info!("✅ [Job-{}] Successfully generated insights in {:.2?}", job_id, insights_duration);
-                                // Store the initial insights result
-                                let mut insights = result;
-                                
-                                // If AI service is available, generate AI summary with timeout
-                                if let Some(ai_service) = &self.ai_service {
+                data
+            },
+            Err(e) => {
+                log::error!("❌ [Job-{}] Failed to download file: {}", job_id, e);
+                return Err(e);
+            }
+        };
+        let mut insights = self.compute_insights(&job, csv_data).await?;
+
+        // If AI analysis is enabled and available, generate AI summary with timeout
+        let ai_service = self.ai_service.as_ref().filter(|_| self.ai_analysis_enabled);
+        if let Some(ai_service) = self.tenant_ai(ai_service, &job.tenant_id).await {
     log::info!("🤖 [Job-{}] Generating AI summary and visualization recommendations", job_id);
     let insights_json = serde_json::to_value(&insights).unwrap_or_default();
     use tokio::time::{timeout, Duration};
@@ -128,8 +551,9 @@ where
     match timeout(Duration::from_secs(15), ai_service.generate_data_summary(&insights_json)).await {
         Ok(result) => {
             match result {
-                Ok(ai_summary) => {
+                Ok((ai_summary, usage)) => {
                     log::info!("✅ [Job-{}] Successfully generated AI summary (attempt 1)", job_id);
+                    self.record_usage(&job, usage).await;
                     ai_summary_result = Some(ai_summary);
                 },
                 Err(e) => {
@@ -144,7 +568,7 @@ where
         }
     }
     // Retry if failed or empty
-    let mut needs_retry = ai_summary_result.as_ref().map_or(true, |ai_summary| {
+    let needs_retry = ai_summary_result.as_ref().is_none_or(|ai_summary| {
         ai_summary.summary.trim().is_empty()
             && ai_summary.key_insights.is_empty()
             && ai_summary.actionable_recommendations.is_empty()
@@ -155,8 +579,9 @@ where
         match timeout(Duration::from_secs(15), ai_service.generate_data_summary(&insights_json)).await {
             Ok(result) => {
                 match result {
-                    Ok(ai_summary) => {
+                    Ok((ai_summary, usage)) => {
                         log::info!("✅ [Job-{}] Successfully generated AI summary (attempt 2)", job_id);
+                        self.record_usage(&job, usage).await;
                         ai_summary_result = Some(ai_summary);
                         last_error = None;
                     },
@@ -180,39 +605,23 @@ where
             && ai_summary.visualization_recommendations.is_empty();
         if is_empty {
             log::warn!("⚠️ [Job-{}] AI summary is empty after retry, setting fallback summary", job_id);
-            insights.ai_analysis = Some(AISummary {
-                summary: format!("AI analysis could not be generated at this time. Last error: {}", last_error.clone().unwrap_or_else(|| "Unknown".to_string())),
-                key_insights: vec!["No insights could be generated from the data.".to_string()],
-                actionable_recommendations: vec![ActionableRecommendation {
-                    recommendation: "Review your dataset for completeness and try again.".to_string(),
-                    rationale: "The AI was unable to extract meaningful patterns or recommendations from the current data.".to_string(),
-                }],
-                visualization_recommendations: vec![],
-            });
+            insights.ai_analysis = Some(AISummary::unavailable(last_error.clone()));
         } else {
             insights.ai_analysis = Some(ai_summary);
         }
     } else {
         log::warn!("⚠️ [Job-{}] AI summary call failed after retry, setting fallback summary", job_id);
-        insights.ai_analysis = Some(AISummary {
-            summary: format!("AI analysis could not be generated at this time. Last error: {}", last_error.unwrap_or_else(|| "Unknown".to_string())),
-            key_insights: vec!["No insights could be generated from the data.".to_string()],
-            actionable_recommendations: vec![ActionableRecommendation {
-                recommendation: "Review your dataset for completeness and try again.".to_string(),
-                rationale: "The AI was unable to extract meaningful patterns or recommendations from the current data.".to_string(),
-            }],
-            visualization_recommendations: vec![],
-        });
+        insights.ai_analysis = Some(AISummary::unavailable(last_error));
     }
 }
-log::info!(" [Job-{}] Caching insights in Redis", job_id);
-match self.redis_service.cache_insights(job_id, &insights) {
+log::info!(" [Job-{}] Storing insights", job_id);
+match self.store_insights(job_id, &insights).await {
     Ok(_) => {
-        log::info!(" [Job-{}] Successfully cached insights in Redis", job_id);
+        log::info!(" [Job-{}] Successfully stored insights", job_id);
     },
     Err(e) => {
-        log::error!(" [Job-{}] Failed to cache insights: {}", job_id, e);
-        return Err(e.into());
+        log::error!(" [Job-{}] Failed to store insights: {}", job_id, e);
+        return Err(e);
     }
 };
 
@@ -220,242 +629,937 @@ log::info!(" [Job-{}] Updating status to Completed", job_id);
 match self.db_service.update_job_status(job_id, JobStatus::Completed).await {
     Ok(_) => {
         log::info!(" [Job-{}] Successfully updated status to Completed", job_id);
+        self.publish_status(job_id, JobStatus::Completed, None).await;
     },
     Err(e) => {
         log::error!(" [Job-{}] Failed to update status to Completed: {}", job_id, e);
-        return Err(e.into());
+        return Err(e);
     }
 };
 
 log::info!(" [Job-{}] Successfully completed processing", job_id);
-return Ok(());
-                            },
-                            Err(e) => {
-                                log::error!("❌ [Job-{}] Failed to generate insights: {}", job_id, e);
-                                return Err(e);
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        log::error!("❌ [Job-{}] Failed to parse CSV: {}", job_id, e);
-                        return Err(e);
-                    }
+Ok(())
+    }
+
+    /// Parse a job's CSV and compute its insights, caching the aggregates appends
+    /// are merged into. A file whose parsed frame would exceed the memory budget is
+    /// processed in low-memory mode instead, which the job records.
+    async fn compute_insights(&self, job: &Job, csv_data: Bytes) -> Result<Insights> {
+        let job_id = job.id;
+        let profile = self.profile_settings(job_id).await;
+        let cached_columns = self.cached_column_stats(job_id).await;
+        let estimate = spill::estimate_frame(&csv_data).map_err(|e| {
+            log::error!("❌ [Job-{}] Failed to parse CSV: {}", job_id, e);
+            e
+        })?;
+        let low_memory = self.memory_budget > 0 && estimate.frame_bytes() > self.memory_budget;
+        self.db_service.set_job_low_memory(job_id, low_memory).await?;
+
+        let processed = if low_memory {
+            log::warn!(
+                "🪫 [Job-{}] Parsed data would take about {} bytes, over the {} byte budget; processing in low-memory mode",
+                job_id, estimate.frame_bytes(), self.memory_budget
+            );
+            let (job, profile) = (job.clone(), profile.clone());
+            self.run_blocking(move |processor| {
+                processor.low_memory_insights(&job, csv_data, &estimate, &profile, &cached_columns)
+            })
+            .await?
+        } else {
+            log::info!("📊 [Job-{}] Parsing CSV data (size: {} bytes)", job_id, csv_data.len());
+            let parse_start = std::time::Instant::now();
+            let overrides = job.schema_overrides.clone();
+            let df = self
+                .run_blocking(move |processor| processor.parse_csv_data(&csv_data, &overrides))
+                .await
+                .map_err(|e| {
+                    log::error!("❌ [Job-{}] Failed to parse CSV: {}", job_id, e);
+                    e
+                })?;
+            log::info!("✅ [Job-{}] Successfully parsed CSV in {:.2?}: {} rows, {} columns",
+                job_id, parse_start.elapsed(), df.height(), df.width());
+            self.store_processed_copy(job, &df).await;
+            let metadata = schema::dataset_metadata(&df);
+
+            log::info!("🧠 [Job-{}] Generating insights for dataframe", job_id);
+            let insights_start = std::time::Instant::now();
+            let processed = self
+                .run_blocking(move |processor| {
+                    let (insights, column_stats) = processor.generate_insights(&df, &profile, &cached_columns)?;
+                    // Keep mergeable aggregates of the full dataset so appended rows can be folded in later
+                    let state = AggregateState::from_frame(&df, &insights.data_summary.numeric_columns);
+                    Ok(Processed { insights, state, metadata, column_stats })
+                })
+                .await
+                .map_err(|e| {
+                    log::error!("❌ [Job-{}] Failed to generate insights: {}", job_id, e);
+                    e
+                })?;
+            log::info!("✅ [Job-{}] Successfully generated insights in {:.2?}", job_id, insights_start.elapsed());
+            processed
+        };
+        let Processed { insights, state, metadata, column_stats } = processed;
+
+        // Conversations read the dataset's shape from the job instead of parsing the file
+        if let Err(e) = self.db_service.set_job_dataset_metadata(job_id, Some(&metadata)).await {
+            log::warn!("⚠️ [Job-{}] Failed to record dataset metadata: {}", job_id, e);
+        }
+
+        match state {
+            Ok(state) => {
+                if let Err(e) = self.redis_service.cache_aggregate_state(job_id, &state).await {
+                    log::warn!("⚠️ [Job-{}] Failed to cache aggregate state: {}", job_id, e);
                 }
             },
+            Err(e) => log::warn!("⚠️ [Job-{}] Failed to build aggregate state: {}", job_id, e),
+        }
+        if let Err(e) = self.redis_service.cache_column_stats(job_id, &column_stats).await {
+            log::warn!("⚠️ [Job-{}] Failed to cache column statistics: {}", job_id, e);
+        }
+        Ok(insights)
+    }
+
+    /// Column statistics kept from a job's last processing; an unreadable cache
+    /// only means every column is computed again
+    async fn cached_column_stats(&self, job_id: Uuid) -> ColumnStatsCache {
+        let cached = match self.redis_service.get_column_stats(job_id).await {
+            Ok(cached) => cached,
             Err(e) => {
-                log::error!("❌ [Job-{}] Failed to download file: {}", job_id, e);
-                return Err(e);
+                log::warn!("⚠️ [Job-{}] Failed to read cached column statistics: {}", job_id, e);
+                None
             }
+        };
+        cached
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| {
+                log::warn!("⚠️ [Job-{}] Ignoring unreadable cached column statistics: {}", job_id, e);
+            }).ok())
+            .unwrap_or_default()
+    }
+
+    /// Run CPU-bound work on the blocking pool with its own handle on the processor,
+    /// so parsing and statistics of wide datasets don't stall the async runtime
+    async fn run_blocking<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(Self) -> Result<T> + Send + 'static,
+    {
+        let processor = self.clone();
+        tokio::task::spawn_blocking(move || work(processor))
+            .await
+            .context("Processing panicked")?
+    }
+
+    /// Insights of a file too large to parse at once. The file is spilled to disk
+    /// and read in chunks: the aggregates of every chunk are merged, so counts, null
+    /// counts, min/max, mean, standard deviation and correlations stay exact, while
+    /// the other statistics are computed on a random sample drawn from every chunk.
+    /// No processed copy is stored, since the whole frame never exists.
+    fn low_memory_insights(
+        &self,
+        job: &Job,
+        csv_data: Bytes,
+        estimate: &FrameEstimate,
+        profile: &ProfileSettings,
+        cached_columns: &ColumnStatsCache,
+    ) -> Result<Processed> {
+        let spill = SpillFile::write(&self.spill_dir, job.id, &csv_data)?;
+        drop(csv_data);
+
+        // Chunks take an eighth of the budget and the sample a quarter
+        let bytes_per_row = estimate.bytes_per_row.max(1) as u64;
+        let chunk_rows = ((self.memory_budget / 8 / bytes_per_row) as usize).max(MIN_CHUNK_ROWS);
+        let mut sample_rows = ((self.memory_budget / 4 / bytes_per_row) as usize).max(1);
+        if self.sample_size > 0 {
+            sample_rows = sample_rows.min(self.sample_size);
+        }
+        let fraction = (sample_rows as f64 / estimate.rows.max(1) as f64).min(1.0);
+
+        let mut numeric_columns: Option<Vec<String>> = None;
+        let mut state: Option<AggregateState> = None;
+        let mut sample: Option<DataFrame> = None;
+        spill.read_chunks(estimate.schema.clone(), chunk_rows, |chunk| {
+            let chunk = schema::apply_overrides(chunk, &job.schema_overrides)?;
+            let numeric_columns = numeric_columns.get_or_insert_with(|| {
+                chunk
+                    .get_columns()
+                    .iter()
+                    .filter(|s| s.dtype().is_numeric())
+                    .map(|s| s.name().to_string())
+                    .collect()
+            });
+            let chunk_state = AggregateState::from_frame(&chunk, numeric_columns)?;
+            match state.as_mut() {
+                Some(state) => state.merge(&chunk_state),
+                None => state = Some(chunk_state),
+            }
+
+            let picked_rows = ((chunk.height() as f64 * fraction).round() as usize).min(chunk.height());
+            let picked = chunk.sample_n_literal(picked_rows, false, false, Some(sampling::SAMPLE_SEED))?;
+            match sample.as_mut() {
+                Some(sample) => {
+                    sample.vstack_mut(&picked)?;
+                }
+                None => sample = Some(picked),
+            }
+            Ok(())
+        })?;
+
+        let (Some(state), Some(mut sample)) = (state, sample) else {
+            return Err(anyhow!("The CSV has no rows"));
+        };
+        sample.as_single_chunk_par();
+        log::info!("🪫 [Job-{}] Read {} rows in chunks of {}, sampled {}", job.id, state.row_count, chunk_rows, sample.height());
+
+        let sampling = SamplingInfo {
+            method: "random".to_string(),
+            sample_rows: sample.height(),
+            total_rows: state.row_count,
+            seed: sampling::SAMPLE_SEED,
+        };
+        let (mut insights, column_stats) =
+            insights_from_sample(&sample, state.row_count, Some(sampling), profile, cached_columns)?;
+        incremental::apply_exact(&mut insights, &state);
+        insights.data_summary.push_summary(SummaryPart::LowMemory);
+        let mut metadata = schema::dataset_metadata(&sample);
+        metadata.row_count = state.row_count;
+        Ok(Processed { insights, state: Ok(state), metadata, column_stats })
+    }
+
+    /// Mark jobs whose uploaded file is gone from storage as failed, walking every job
+    /// in pages; jobs that already failed or were cancelled are skipped. Returns how
+    /// many were marked.
+    pub async fn reconcile_missing_files(&self) -> Result<usize> {
+        const PAGE_SIZE: usize = 500;
+        let mut marked = 0;
+        let mut after = None;
+        loop {
+            let page = self.db_service.list_jobs(&JobFilter::all(), after, PAGE_SIZE).await?;
+            for job in page.iter().filter(|job| job.check_transition(JobStatus::Failed).is_ok()) {
+                let source = job.source_key();
+                if self.s3_service.head_object(&source.key).await?.is_none() {
+                    log::warn!("🧹 [Job-{}] Dataset file {} is missing, marking the job failed", job.id, source);
+                    let reason = format!("Dataset file {} is missing from storage", source);
+                    match self.fail_job(job.id, &reason).await {
+                        Ok(()) => marked += 1,
+                        // Cancelled since the page was read
+                        Err(e) if e.downcast_ref::<InvalidTransition>().is_some() => log::debug!("🧹 {}", e),
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            if page.len() < PAGE_SIZE {
+                return Ok(marked);
+            }
+            after = page.last().map(|job| job.id);
+        }
+    }
+
+    /// Append CSV rows (with a header matching the dataset) to a completed job's file and
+    /// fold them into its cached insights. Falls back to a full recompute when no
+    /// aggregate state is cached for the job. Returns the number of rows appended.
+    /// The job's lock is held throughout, so concurrent appends can't overwrite each
+    /// other's rows; an append while it is held fails with `JobLocked`.
+    pub async fn append_data(&self, job_id: Uuid, csv_chunk: &[u8]) -> Result<usize> {
+        let token = match self.acquire_job_lock(job_id).await {
+            Ok(token) => token,
+            Err(e) if e.downcast_ref::<JobLocked>().is_some() => return Err(e),
+            Err(e) => return Err(e.context(AppendFault::Cache)),
+        };
+        let result = self.holding_job_lock(job_id, &token, self.append_locked(job_id, csv_chunk)).await;
+        self.release_job_lock(job_id, &token).await;
+        result
+    }
+
+    async fn append_locked(&self, job_id: Uuid, csv_chunk: &[u8]) -> Result<usize> {
+        let job = self.db_service.get_job(job_id).await.context(AppendFault::Database)?
+            .ok_or_else(|| anyhow!("Job not found"))?;
+
+        let chunk = Bytes::copy_from_slice(csv_chunk);
+        let overrides = job.schema_overrides.clone();
+        let chunk_df = self
+            .run_blocking(move |processor| processor.parse_csv_data(&chunk, &overrides))
+            .await
+            .map_err(|e| InvalidAppend(format!("The appended rows are not a readable CSV: {:#}", e)))?;
+        let appended_rows = chunk_df.height();
+
+        let cached = match (
+            self.insights(job_id).await.context(AppendFault::Database)?,
+            self.redis_service.get_aggregate_state(job_id).await.context(AppendFault::Cache)?,
+        ) {
+            (Some(insights_json), Some(state_json)) => Some((
+                serde_json::from_str::<Insights>(&insights_json).context("Failed to parse cached insights")?,
+                serde_json::from_str::<AggregateState>(&state_json).context("Failed to parse cached aggregate state")?,
+            )),
+            _ => None,
+        };
+
+        if let Some((_, state)) = &cached {
+            let expected: Vec<&str> = state.null_counts.iter().map(|(name, _)| name.as_str()).collect();
+            if chunk_df.get_column_names() != expected {
+                bail!(InvalidAppend(format!(
+                    "Appended columns {:?} do not match dataset columns {:?}",
+                    chunk_df.get_column_names(),
+                    expected
+                )));
+            }
+        }
+
+        // The processed copy grows with the rows before anything is written, which
+        // also refuses rows whose values don't fit the dataset's column types
+        let extended = self.extend_processed_copy(&job, chunk_df.clone()).await?;
+
+        // Append the rows, minus their header, to the stored file
+        let source = job.source_key();
+        let mut data = Vec::from(self.s3_service.get_object(&source.bucket, &source.key).await
+            .with_context(|| format!("Dataset file {} of job {} could not be read", source, job_id))
+            .context(AppendFault::Storage)?);
+        if !data.is_empty() && !data.ends_with(b"\n") {
+            data.push(b'\n');
+        }
+        let body_start = csv_chunk.iter().position(|b| *b == b'\n').map_or(csv_chunk.len(), |i| i + 1);
+        data.extend_from_slice(&csv_chunk[body_start..]);
+        // A shared blob belongs to every job that uploaded it, so the grown file
+        // gets a key of its own
+        let file_key = if job.shares_source() {
+            tenant_key(&job.tenant_id, &format!("uploads/{}.csv", job_id))
+        } else {
+            job.file_key.clone()
+        };
+        // Charge what is written: the appended rows, or the whole file when it
+        // becomes a copy of its own
+        let new_bytes = if job.shares_source() { data.len() } else { csv_chunk.len() - body_start } as u64;
+        self.quotas.reserve_storage(&self.s3_service, &self.redis_service, &job.tenant_id, new_bytes).await?;
+        let hash = content_hash(&data);
+        if let Err(e) = self.s3_service.upload_file(&file_key, data.into()).await {
+            self.quotas.release_storage(&self.redis_service, &job.tenant_id, new_bytes).await;
+            return Err(e.context(AppendFault::Storage));
+        }
+        self.db_service.update_job_file(job_id, &file_key, &hash).await.context(AppendFault::Database)?;
+        // The cached insights describe the dataset before the append
+        if let Err(e) = self.invalidate_cache(job_id).await {
+            log::warn!("⚠️ [Job-{}] Failed to clear cached insights after append: {:#}", job_id, e);
+        }
+        let processed = job.processed_key();
+        let metadata = match extended {
+            Some((copy, metadata)) => {
+                match self.s3_service.upload_file(&processed.key, copy.into()).await {
+                    Ok(()) => log::info!("💾 [Job-{}] Added {} rows to the processed copy at {}", job_id, appended_rows, processed),
+                    Err(e) => {
+                        log::warn!("⚠️ [Job-{}] Failed to store the grown processed copy: {}", job_id, e);
+                        self.remove_processed_copy(&job).await;
+                    }
+                }
+                Some(metadata)
+            }
+            None => {
+                // Whatever copy there is lacks the appended rows
+                self.remove_processed_copy(&job).await;
+                job.dataset_metadata.clone().map(|mut metadata| {
+                    metadata.row_count += appended_rows;
+                    metadata
+                })
+            }
+        };
+        if let Some(metadata) = metadata {
+            if let Err(e) = self.db_service.set_job_dataset_metadata(job_id, Some(&metadata)).await {
+                log::warn!("⚠️ [Job-{}] Failed to record dataset metadata after append: {}", job_id, e);
+            }
+        }
+
+        match cached {
+            Some((mut insights, mut state)) => {
+                let chunk_state = AggregateState::from_frame(&chunk_df, &insights.data_summary.numeric_columns)?;
+                state.merge(&chunk_state);
+                incremental::apply_append(&mut insights, &state, &chunk_state);
+                self.redis_service.cache_aggregate_state(job_id, &state).await.context(AppendFault::Cache)?;
+                self.store_insights(job_id, &insights).await.context(AppendFault::Database)?;
+                log::info!("➕ [Job-{}] Merged {} appended rows into insights", job_id, appended_rows);
+            }
+            None => {
+                log::info!("🔁 [Job-{}] No aggregate state cached, recomputing insights after append", job_id);
+                self.reprocess_locked(job_id).await?;
+            }
+        }
+
+        Ok(appended_rows)
+    }
+
+    /// Compare the datasets of two jobs: schema changes, row-count delta and per-column
+    /// distribution shift (PSI for every shared column, KS for numeric ones)
+    pub async fn compare_jobs(&self, base: &Job, target: &Job) -> Result<DriftReport> {
+        let base_df = self.parse_csv_data(&self.read_source(base).await?, &base.schema_overrides)?;
+        let target_df = self.parse_csv_data(&self.read_source(target).await?, &target.schema_overrides)?;
+
+        let schema = drift::schema_diff(&base_df, &target_df);
+        let (base_sample, base_sampling) =
+            sampling::sample_for_insights(&base_df, self.sample_threshold_rows, self.sample_size)?;
+        let (target_sample, target_sampling) =
+            sampling::sample_for_insights(&target_df, self.sample_threshold_rows, self.sample_size)?;
+        let columns = drift::column_drift(&base_sample, &target_sample)?;
+
+        let base_row_count = base_df.height();
+        let target_row_count = target_df.height();
+        Ok(DriftReport {
+            base_job_id: base.id,
+            target_job_id: target.id,
+            base_row_count,
+            target_row_count,
+            row_count_delta: target_row_count as i64 - base_row_count as i64,
+            row_count_change_pct: (base_row_count > 0)
+                .then(|| (target_row_count as f64 - base_row_count as f64) / base_row_count as f64 * 100.0),
+            schema,
+            columns,
+            base_sampling,
+            target_sampling,
+        })
+    }
+
+    /// Write the typed copy of a job's dataset that the query path prefers over the CSV.
+    /// Failures only cost query speed, so they are logged rather than failing the job.
+    async fn store_processed_copy(&self, job: &Job, df: &DataFrame) {
+        let processed = job.processed_key();
+        let stored = match export_dataframe(&mut df.clone(), ExportFormat::Arrow) {
+            Ok(bytes) => self.s3_service.upload_file(&processed.key, bytes.into()).await,
+            Err(e) => Err(e),
+        };
+        match stored {
+            Ok(()) => log::info!("💾 [Job-{}] Stored processed copy at {}", job.id, processed),
+            Err(e) => log::warn!("⚠️ [Job-{}] Failed to store processed copy: {}", job.id, e),
+        }
+    }
+
+    /// A job's processed copy with appended rows added, and the grown dataset's
+    /// metadata, built without parsing the whole file again. `None` when there is no
+    /// readable copy: low-memory jobs never store one. Rows whose values don't fit
+    /// the copy's column types are refused.
+    async fn extend_processed_copy(&self, job: &Job, chunk_df: DataFrame) -> Result<Option<(Vec<u8>, DatasetMetadata)>> {
+        if job.low_memory {
+            return Ok(None);
         }
+        let processed = job.processed_key();
+        let Ok(data) = self.s3_service.get_object(&processed.bucket, &processed.key).await else {
+            return Ok(None);
+        };
+        let job_id = job.id;
+        self.run_blocking(move |_| {
+            let base = match IpcReader::new(std::io::Cursor::new(data)).finish() {
+                Ok(base) => base,
+                Err(e) => {
+                    log::warn!("⚠️ [Job-{}] Processed copy is unreadable, leaving it out of the append: {}", job_id, e);
+                    return Ok(None);
+                }
+            };
+            let mut combined = stack_rows(base, &chunk_df).map_err(|e| {
+                InvalidAppend(format!("The appended rows do not fit the dataset's column types: {:#}", e))
+            })?;
+            let metadata = schema::dataset_metadata(&combined);
+            Ok(Some((export_dataframe(&mut combined, ExportFormat::Arrow)?, metadata)))
+        })
+        .await
+    }
+
+    /// Remove a processed copy that no longer matches the CSV, so queries read the
+    /// CSV until the job is processed again
+    async fn remove_processed_copy(&self, job: &Job) {
+        let processed = job.processed_key();
+        if let Err(e) = self.s3_service.delete_object(&processed.key).await {
+            log::warn!("⚠️ [Job-{}] Failed to remove the stale processed copy: {}", job.id, e);
+        }
+    }
+
+    /// The uploaded file of a job
+    async fn read_source(&self, job: &Job) -> Result<Bytes> {
+        let source = job.source_key();
+        self.s3_service
+            .get_object(&source.bucket, &source.key)
+            .await
+            .with_context(|| format!("Dataset file {} of job {} could not be read", source, job.id))
     }
 
-    /// Parse raw CSV bytes into a `DataFrame`
-    fn parse_csv_data(&self, csv_data: &[u8]) -> Result<DataFrame> {
+    /// Parse raw CSV bytes into a `DataFrame`, reading overridden columns as their override type
+    pub fn parse_csv_data(&self, csv_data: &[u8], overrides: &SchemaOverrides) -> Result<DataFrame> {
         let cursor = std::io::Cursor::new(csv_data);
         let df = CsvReader::new(cursor)
             .infer_schema(Some(100))
             .has_header(true)
+            .with_try_parse_dates(true)
             .finish()
             .context("Failed to parse CSV data")?;
-        Ok(df)
-    }
-
-    /// Generate summary statistics + per‐column stats + correlations
-    fn generate_insights(&self, df: &DataFrame) -> Result<Insights> {
-        // 1) Basic counts
-        let row_count = df.height();
-        let col_count = df.width();
-
-        // 2) Bucket column names by dtype
-        let mut numeric_columns = Vec::new();
-        let mut categorical_columns = Vec::new();
-        let mut date_columns = Vec::new();
-
-        for s in df.get_columns() {
-            let name = s.name().to_string();
-            match s.dtype() {
-                DataType::Int8
-                | DataType::Int16
-                | DataType::Int32
-                | DataType::Int64
-                | DataType::UInt8
-                | DataType::UInt16
-                | DataType::UInt32
-                | DataType::UInt64
-                | DataType::Float32
-                | DataType::Float64 => {
-                    numeric_columns.push(name);
-                }
-                DataType::Date | DataType::Datetime(_, _) => {
-                    date_columns.push(name);
-                }
-                _ => {
-                    categorical_columns.push(name);
-                }
+        schema::apply_overrides(df, overrides)
+    }
+
+    /// A job's columns as processing reads them, with suggested type fixes
+    pub async fn dataset_schema(&self, job: &Job) -> Result<DatasetSchema> {
+        let df = self.parse_csv_data(&self.read_source(job).await?, &job.schema_overrides)?;
+        Ok(DatasetSchema {
+            job_id: job.id,
+            row_count: df.height(),
+            columns: schema::describe_columns(&df, &job.schema_overrides)?,
+        })
+    }
+
+    /// Replace a job's schema overrides and mark it queued so it is processed again
+    /// with them; its stale insights are dropped from the cache. Every overridden
+    /// column must exist and convert to its new type.
+    pub async fn update_schema(&self, job: &Job, overrides: &SchemaOverrides) -> Result<()> {
+        // Columns are checked against the file rather than trusted, so a typo fails here
+        let df = self.parse_csv_data(&self.read_source(job).await?, &SchemaOverrides::new())?;
+        let unknown: Vec<&str> = overrides
+            .keys()
+            .map(String::as_str)
+            .filter(|name| df.column(name).is_err())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(anyhow!("Unknown columns: {}", unknown.join(", ")));
+        }
+        schema::apply_overrides(df, overrides)?;
+
+        self.db_service.update_job_schema(job.id, overrides).await?;
+        // Column types change with the overrides; processing records them again
+        self.db_service.set_job_dataset_metadata(job.id, None).await?;
+        self.db_service.update_job_status(job.id, JobStatus::Queued).await?;
+        self.publish_status(job.id, JobStatus::Queued, None).await;
+        if let Err(e) = self.invalidate_cache(job.id).await {
+            log::warn!("⚠️ [Job-{}] Failed to clear cached insights after a schema change: {:#}", job.id, e);
+        }
+        log::info!("🧬 [Job-{}] Schema overrides changed: {:?}", job.id, overrides);
+        Ok(())
+    }
+
+    /// Generate summary statistics + per‐column stats + correlations. Columns whose
+    /// statistics are in `cached_columns` for their current content reuse them; the
+    /// statistics of every column are returned for the next run.
+    pub fn generate_insights(
+        &self,
+        df: &DataFrame,
+        profile: &ProfileSettings,
+        cached_columns: &ColumnStatsCache,
+    ) -> Result<(Insights, ColumnStatsCache)> {
+        // Large datasets get their statistics computed on a random sample
+        let (sample, sampling) = sampling::sample_for_insights(df, self.sample_threshold_rows, self.sample_size)?;
+        insights_from_sample(&sample, df.height(), sampling, profile, cached_columns)
+    }
+}
+
+/// What processing a dataset produces besides its insights
+struct Processed {
+    insights: Insights,
+    state: Result<AggregateState>,
+    metadata: DatasetMetadata,
+    column_stats: ColumnStatsCache,
+}
+
+/// Insights of a dataset of `row_count` rows, computed on `df`: the whole
+/// dataset, or the sample `sampling` describes. Also returns the statistics of
+/// every column, fingerprinted for `cached_columns` of a later run.
+fn insights_from_sample(
+    df: &DataFrame,
+    row_count: usize,
+    sampling: Option<SamplingInfo>,
+    profile: &ProfileSettings,
+    cached_columns: &ColumnStatsCache,
+) -> Result<(Insights, ColumnStatsCache)> {
+    // 1) Basic counts (exact, even when the statistics below run on a sample)
+    let col_count = df.width();
+    let analysed_rows = df.height();
+
+    // 2) Bucket column names by dtype
+    let mut numeric_columns = Vec::new();
+    let mut categorical_columns = Vec::new();
+    let mut date_columns = Vec::new();
+
+    for s in df.get_columns() {
+        let name = s.name().to_string();
+        match s.dtype() {
+            DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Float32
+            | DataType::Float64 => {
+                numeric_columns.push(name);
+            }
+            DataType::Date | DataType::Datetime(_, _) => {
+                date_columns.push(name);
+            }
+            _ => {
+                categorical_columns.push(name);
             }
         }
+    }
 
-        // 3) Top‐level summary text
-        let summary_text = format!(
-            "Dataset has {} rows and {} columns ({} numeric, {} categorical, {} date).",
-            row_count,
-            col_count,
-            numeric_columns.len(),
-            categorical_columns.len(),
-            date_columns.len()
-        );
-        let data_summary = DataSummary {
-            row_count,
-            column_count: col_count,
-            numeric_columns: numeric_columns.clone(),
-            categorical_columns: categorical_columns.clone(),
-            date_columns: date_columns.clone(),
-            summary_text,
-        };
+    // 3) Top‐level summary text
+    let mut data_summary = DataSummary {
+        row_count,
+        column_count: col_count,
+        numeric_columns: numeric_columns.clone(),
+        categorical_columns: categorical_columns.clone(),
+        date_columns: date_columns.clone(),
+        profile: Some(profile.profile),
+        ..Default::default()
+    };
+    data_summary.push_summary(SummaryPart::Shape);
+    if let Some(info) = sampling {
+        log::info!("Computing statistics on a random sample of {} of {} rows", info.sample_rows, info.total_rows);
+        data_summary.sampling = Some(info);
+        data_summary.push_summary(SummaryPart::Sampled);
+    }
 
-        // 4) Per‐column statistics
-        let mut column_stats: Vec<ColumnStatistics> = Vec::new();
-
-        for s in df.get_columns() {
-            let name = s.name().to_string();
-            let dtype = format!("{:?}", s.dtype());
-            let null_count = s.null_count();
-
-            // Unique count as usize
-            let unique_count = s.n_unique().unwrap_or(0) as usize;
-
-            // Initialize placeholders
-            let mut min_str: Option<String> = None;
-            let mut max_str: Option<String> = None;
-            let mut mean_str: Option<String> = None;
-            let mut median_str: Option<String> = None;
-            let mut std_str: Option<String> = None;
-            let mut percentile_25_str: Option<String> = None;
-            let mut percentile_75_str: Option<String> = None;
-            let mut freq_vals: Option<HashMap<String, u32>> = None;
-
-            match s.dtype() {
-                // ─────────── Numeric branch ───────────
-                DataType::Int8
-                | DataType::Int16
-                | DataType::Int32
-                | DataType::Int64
-                | DataType::UInt8
-                | DataType::UInt16
-                | DataType::UInt32
-                | DataType::UInt64
-                | DataType::Float32
-                | DataType::Float64 => {
-                    // Cast to Float64 → ChunkedArray<Float64Type>
-                    if let Ok(ca_f64) = s.cast(&DataType::Float64)?.f64() {
-                        min_str = ca_f64.min().map(|v| v.to_string());
-                        max_str = ca_f64.max().map(|v| v.to_string());
-                        mean_str = ca_f64.mean().map(|v| format!("{:.2}", v));
-                        median_str = ca_f64.median().map(|v| format!("{:.2}", v));
-                        std_str = ca_f64.std(1).map(|v| format!("{:.2}", v));
-                        
-                        // Calculate 25th percentile
-                        if let Ok(s_f64) = s.cast(&DataType::Float64) {
-                            // Using the correct Polars API for percentile calculation
-                            if let Ok(p25) = s_f64.quantile_as_series(0.25, QuantileInterpolOptions::Linear) {
-                                if let Some(p25_val) = p25.f64()?.get(0) {
-                                    percentile_25_str = Some(format!("{:.2}", p25_val));
-                                }
-                            }
-                            
-                            // Calculate 75th percentile
-                            if let Ok(p75) = s_f64.quantile_as_series(0.75, QuantileInterpolOptions::Linear) {
-                                if let Some(p75_val) = p75.f64()?.get(0) {
-                                    percentile_75_str = Some(format!("{:.2}", p75_val));
-                                }
-                            }
+    // 4) Per‐column statistics, one column per thread; collecting keeps column order.
+    //    Columns unchanged since the last run take their statistics from the cache.
+    let computed: Vec<(String, ColumnStatistics, bool)> = df
+        .get_columns()
+        .par_iter()
+        .map(|s| {
+            let fingerprint = column_cache::fingerprint(s, profile.for_column(s.name()), profile.top_values())?;
+            match cached_columns.get(s.name(), &fingerprint) {
+                Some(stats) => Ok((fingerprint, stats.clone(), true)),
+                None => Ok((fingerprint, column_statistics(s, analysed_rows, profile)?, false)),
+            }
+        })
+        .collect::<Result<_>>()?;
+    let reused = computed.iter().filter(|(_, _, reused)| *reused).count();
+    if reused > 0 {
+        log::info!("♻️ Reused the cached statistics of {} of {} columns", reused, computed.len());
+    }
+    let mut column_cache = ColumnStatsCache::default();
+    let column_stats: Vec<ColumnStatistics> = computed
+        .into_iter()
+        .map(|(fingerprint, stats, _)| {
+            column_cache.columns.insert(stats.name.clone(), CachedColumn { fingerprint, stats: stats.clone() });
+            stats
+        })
+        .collect();
+    for stats in &column_stats {
+        for flag in &stats.flags {
+            match flag {
+                ColumnFlag::LikelyIdentifier => data_summary.identifier_columns.push(stats.name.clone()),
+                ColumnFlag::Constant => data_summary.constant_columns.push(stats.name.clone()),
+                ColumnFlag::HighCardinality => data_summary.high_cardinality_columns.push(stats.name.clone()),
+            }
+        }
+        if !stats.pii_types.is_empty() {
+            data_summary.pii_columns.push(stats.name.clone());
+        }
+        if let Some(suggested_type) = stats.text_profile.as_ref().and_then(|t| t.suggested_type.clone()) {
+            data_summary.type_suggestions.push(TypeSuggestion {
+                column: stats.name.clone(),
+                suggested_type,
+            });
+        }
+    }
+
+    // Mention flagged columns in the summary and leave them out of the pairwise analyses
+    for (part, flagged) in [
+        (SummaryPart::IdentifierColumns, !data_summary.identifier_columns.is_empty()),
+        (SummaryPart::ConstantColumns, !data_summary.constant_columns.is_empty()),
+        (SummaryPart::HighCardinalityColumns, !data_summary.high_cardinality_columns.is_empty()),
+        (SummaryPart::PersonalDataColumns, !data_summary.pii_columns.is_empty()),
+        (SummaryPart::TypeSuggestions, !data_summary.type_suggestions.is_empty()),
+    ] {
+        if flagged {
+            data_summary.push_summary(part);
+        }
+    }
+    let is_analysable = |name: &String| {
+        !data_summary.identifier_columns.contains(name)
+            && !data_summary.constant_columns.contains(name)
+            && !data_summary.high_cardinality_columns.contains(name)
+    };
+    let analysable_numeric: Vec<String> = numeric_columns.iter().filter(|c| is_analysable(c)).cloned().collect();
+    let analysable_categorical: Vec<String> = categorical_columns.iter().filter(|c| is_analysable(c)).cloned().collect();
+
+    // 5) Pairwise correlations (only if ≥2 numeric columns)
+    let correlations = if profile.profile.correlations() && analysable_numeric.len() >= 2 {
+        // 1) Cast each column to Float64 once, rather than once per pair
+        let values: Vec<Option<Series>> = analysable_numeric
+            .par_iter()
+            .map(|c| df.column(c).and_then(|s| s.cast(&DataType::Float64)).ok())
+            .collect();
+
+        // 2) Calculate the correlation of every pair, one pair per thread
+        let pairs: Vec<(usize, usize)> = (0..values.len())
+            .flat_map(|i| ((i + 1)..values.len()).map(move |j| (i, j)))
+            .collect();
+        let corr_map = pairs
+            .into_par_iter()
+            .filter_map(|(i, j)| {
+                let (s1, s2) = (values[i].as_ref()?, values[j].as_ref()?);
+                let corr_val = calculate_correlation(s1, s2).ok()?;
+                Some((format!("{}-{}", analysable_numeric[i], analysable_numeric[j]), corr_val))
+            })
+            .collect();
+        Some(corr_map)
+    } else {
+        None
+    };
+
+    // 6) Categorical–numeric associations (group-wise stats + correlation ratio)
+    let categorical_associations = if profile.profile.associations()
+        && !analysable_categorical.is_empty()
+        && !analysable_numeric.is_empty()
+    {
+        let associations = association::categorical_numeric_associations(
+            df,
+            &analysable_categorical,
+            &analysable_numeric,
+        )?;
+
+        // Surface the strongest driver in the summary when the effect is large
+        if let Some(strongest) = associations.first() {
+            let explained = strongest.correlation_ratio.powi(2);
+            if explained >= 0.14 {
+                data_summary.push_summary(SummaryPart::VarianceExplained {
+                    categorical_column: strongest.categorical_column.clone(),
+                    numeric_column: strongest.numeric_column.clone(),
+                    percent: explained * 100.0,
+                });
+            }
+        }
+        Some(associations)
+    } else {
+        None
+    };
+
+    // 7) Time-series anomalies (rolling z-score per period of each date column)
+    let anomalies = if profile.profile.anomalies() && !date_columns.is_empty() && !analysable_numeric.is_empty() {
+        let anomalies = anomaly::detect_anomalies(df, &date_columns, &analysable_numeric)?;
+        if let Some(largest) = anomalies.first() {
+            data_summary.push_summary(SummaryPart::Anomalies {
+                count: anomalies.len(),
+                measure_column: largest.measure_column.clone(),
+                period: largest.period.to_string(),
+                z_score: largest.z_score,
+            });
+        }
+        Some(anomalies)
+    } else {
+        None
+    };
+
+    // 8) Extreme records, labelled with identifying columns that hold no personal data
+    let extreme_records = if profile.profile.extremes() && !analysable_numeric.is_empty() {
+        let context_columns: Vec<String> = data_summary
+            .identifier_columns
+            .iter()
+            .chain(&date_columns)
+            .chain(&analysable_categorical)
+            .filter(|c| !data_summary.pii_columns.contains(c))
+            .cloned()
+            .collect();
+        Some(extremes::extreme_records(df, &analysable_numeric, &context_columns)?)
+    } else {
+        None
+    };
+
+    // 9) Functional dependencies between categorical and integer columns
+    let functional_dependencies = if profile.profile.dependencies() {
+        let candidates: Vec<String> = analysable_categorical
+            .iter()
+            .chain(analysable_numeric.iter().filter(|c| {
+                df.column(c).map(|s| s.dtype().is_integer()).unwrap_or(false)
+            }))
+            .cloned()
+            .collect();
+        let dependencies = dependency::functional_dependencies(df, &candidates)?;
+        if !dependencies.is_empty() {
+            let listed: Vec<String> = dependencies
+                .iter()
+                .take(3)
+                .map(|d| format!("{} → {}", d.determinant, d.dependent))
+                .collect();
+            data_summary.push_summary(SummaryPart::FunctionalDependencies { dependencies: listed });
+        }
+        Some(dependencies)
+    } else {
+        None
+    };
+
+    // 10) Benford and plausibility checks, only on the numeric columns the upload selected
+    let plausibility_targets = profile.plausibility_targets(&numeric_columns);
+    let plausibility = if plausibility_targets.is_empty() {
+        None
+    } else {
+        let checks = plausibility::plausibility_checks(df, &plausibility_targets)?;
+        let suspicious: Vec<String> = checks
+            .iter()
+            .filter(|c| !c.flags.is_empty())
+            .map(|c| c.column.clone())
+            .collect();
+        if !suspicious.is_empty() {
+            data_summary.push_summary(SummaryPart::ImplausibleColumns { columns: suspicious });
+        }
+        Some(checks)
+    };
+
+    let insights = Insights {
+        data_summary,
+        column_statistics: column_stats,
+        correlations,
+        categorical_associations,
+        anomalies,
+        extreme_records,
+        functional_dependencies,
+        plausibility,
+        ai_analysis: None,
+    };
+    Ok((insights, column_cache))
+}
+
+/// Statistics of one column, computed on `analysed_rows` rows
+fn column_statistics(s: &Series, analysed_rows: usize, profile: &ProfileSettings) -> Result<ColumnStatistics> {
+    let name = s.name().to_string();
+    let dtype = format!("{:?}", s.dtype());
+    let null_count = s.null_count();
+
+    // Unique count as usize
+    let unique_count = s.n_unique().unwrap_or(0);
+
+    // Flag identifier, constant and high-cardinality columns
+    let flags = cardinality::column_flags(&name, s.dtype(), analysed_rows, null_count, unique_count);
+
+    // Scan a sample of the values for personal data
+    let pii_types = pii::detect_pii(s)?;
+
+    // Initialize placeholders
+    let mut min_str: Option<String> = None;
+    let mut max_str: Option<String> = None;
+    let mut mean_str: Option<String> = None;
+    let mut median_str: Option<String> = None;
+    let mut std_str: Option<String> = None;
+    let mut percentile_25_str: Option<String> = None;
+    let mut percentile_75_str: Option<String> = None;
+    let mut freq_vals: Option<HashMap<String, u32>> = None;
+    let mut text_profile: Option<TextProfile> = None;
+    let mut long_tail: Option<LongTail> = None;
+    let column_profile = profile.for_column(&name);
+
+    match s.dtype() {
+        // ─────────── Numeric branch ───────────
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Float32
+        | DataType::Float64 => {
+            // Cast to Float64 → ChunkedArray<Float64Type>
+            if let Ok(ca_f64) = s.cast(&DataType::Float64)?.f64() {
+                min_str = ca_f64.min().map(|v| v.to_string());
+                max_str = ca_f64.max().map(|v| v.to_string());
+                if column_profile.moments() {
+                    mean_str = ca_f64.mean().map(|v| format!("{:.2}", v));
+                    std_str = ca_f64.std(1).map(|v| format!("{:.2}", v));
+                }
+                if column_profile.percentiles() {
+                    median_str = ca_f64.median().map(|v| format!("{:.2}", v));
+                }
+                
+                // Calculate 25th percentile
+                if let (true, Ok(s_f64)) = (column_profile.percentiles(), s.cast(&DataType::Float64)) {
+                    // Using the correct Polars API for percentile calculation
+                    if let Ok(p25) = s_f64.quantile_as_series(0.25, QuantileInterpolOptions::Linear) {
+                        if let Some(p25_val) = p25.f64()?.get(0) {
+                            percentile_25_str = Some(format!("{:.2}", p25_val));
                         }
                     }
-                }
-
-                // ───────── Non‐numeric (e.g. Utf8, Boolean) ─────────
-                _ => {
-                    // No numeric stats for non‐numeric
-                    min_str = None;
-                    max_str = None;
-                    mean_str = None;
-                    median_str = None;
-                    std_str = None;
-
-                    // If it’s a categorical column, compute top‐10 frequent values
-                    if categorical_columns.contains(&name) {
-                        if let Ok(vc_df) = s.value_counts(false, false) {
-                            // vc_df: [ { col_name }, "counts" ]
-                            if let (Ok(vals), Ok(cnts)) = (
-                                vc_df.column(&name)?.utf8(),
-                                vc_df.column("counts")?.u32(),
-                            ) {
-                                let mut map = HashMap::new();
-                                for i in 0..vals.len().min(10) {
-                                    if let (Some(val_str), Some(cnt)) =
-                                        (vals.get(i), cnts.get(i))
-                                    {
-                                        map.insert(val_str.to_string(), cnt);
-                                    }
-                                }
-                                freq_vals = Some(map);
-                            }
+                    
+                    // Calculate 75th percentile
+                    if let Ok(p75) = s_f64.quantile_as_series(0.75, QuantileInterpolOptions::Linear) {
+                        if let Some(p75_val) = p75.f64()?.get(0) {
+                            percentile_75_str = Some(format!("{:.2}", p75_val));
                         }
                     }
                 }
             }
-
-            column_stats.push(ColumnStatistics {
-                name: name.clone(),
-                data_type: dtype.clone(),
-                null_count,
-                unique_count,
-                min: min_str,
-                max: max_str,
-                mean: mean_str,
-                median: median_str,
-                std_dev: std_str,
-                percentile_25: percentile_25_str,
-                percentile_75: percentile_75_str,
-                frequent_values: freq_vals,
-            });
         }
 
-        // 5) Pairwise correlations (only if ≥2 numeric columns)
-        let correlations = if numeric_columns.len() >= 2 {
-            let mut corr_map = HashMap::new();
-            for i in 0..numeric_columns.len() {
-                for j in (i + 1)..numeric_columns.len() {
-                    let c1 = &numeric_columns[i];
-                    let c2 = &numeric_columns[j];
-        
-                    // 1) Cast each Series to Float64
-                    if let (Ok(s1), Ok(s2)) = (
-                        df.column(c1)?.cast(&DataType::Float64),
-                        df.column(c2)?.cast(&DataType::Float64)
+        // ───────── Non‐numeric (e.g. Utf8, Boolean) ─────────
+        _ => {
+            // No numeric stats for non‐numeric
+            min_str = None;
+            max_str = None;
+            mean_str = None;
+            median_str = None;
+            std_str = None;
+
+            // If it’s a categorical column, compute the top frequent values and
+            // summarise the tail beyond them
+            let is_categorical = !matches!(s.dtype(), DataType::Date | DataType::Datetime(_, _));
+            if is_categorical && column_profile.frequent_values() {
+                if let Ok(vc_df) = s.value_counts(true, false) {
+                    // vc_df: [ { col_name }, "counts" ]
+                    if let (Ok(vals), Ok(cnts)) = (
+                        vc_df.column(&name)?.utf8(),
+                        vc_df.column("counts")?.u32(),
                     ) {
-                        // 2) Calculate correlation manually
-                        if let Ok(corr_val) = calculate_correlation(&s1, &s2) {
-                            corr_map.insert(format!("{}-{}", c1, c2), corr_val);
+                        let top_values = profile.top_values();
+                        let mut map = HashMap::new();
+                        for i in 0..vals.len().min(top_values) {
+                            if let (Some(val_str), Some(cnt)) =
+                                (vals.get(i), cnts.get(i))
+                            {
+                                map.insert(val_str.to_string(), cnt);
+                            }
                         }
+                        freq_vals = Some(map);
+
+                        let counts: Vec<u32> = vals
+                            .into_iter()
+                            .zip(cnts)
+                            .filter_map(|(val, cnt)| val.and(cnt))
+                            .collect();
+                        long_tail = cardinality::long_tail(&counts, top_values);
                     }
                 }
             }
-            Some(corr_map)
-        } else {
-            None
-        };
-        
 
-        Ok(Insights {
-            data_summary,
-            column_statistics: column_stats,
-            correlations,
-            ai_analysis: None,
-        })
+            // Profile string columns and note the ones that hold numbers, dates or booleans
+            if column_profile.text_profile() {
+                text_profile = text::profile_text(s)?;
+            }
+        }
     }
+
+    Ok(ColumnStatistics {
+        name,
+        data_type: dtype,
+        null_count,
+        unique_count,
+        min: min_str,
+        max: max_str,
+        mean: mean_str,
+        median: median_str,
+        std_dev: std_str,
+        percentile_25: percentile_25_str,
+        percentile_75: percentile_75_str,
+        frequent_values: freq_vals,
+        long_tail,
+        flags,
+        pii_types,
+        text_profile,
+    })
 }
+
 /// Calculate the Pearson correlation coefficient between two Series
+/// `rows` below `base`, each column cast to the type `base` has for it. A value
+/// that doesn't fit the type fails rather than becoming null.
+fn stack_rows(base: DataFrame, rows: &DataFrame) -> Result<DataFrame> {
+    let columns = base
+        .get_columns()
+        .iter()
+        .map(|column| Ok(rows.column(column.name())?.strict_cast(column.dtype())?))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(base.vstack(&DataFrame::new(columns)?)?)
+}
+
 /// Both Series should already be cast to Float64 type
 fn calculate_correlation(s1: &Series, s2: &Series) -> Result<f64> {
     // Get float arrays from the Series
@@ -478,7 +1582,7 @@ fn calculate_correlation(s1: &Series, s2: &Series) -> Result<f64> {
     let mut valid_count = 0.0;
     
     // Iterate through both arrays simultaneously
-    for (v1, v2) in ca1.into_iter().zip(ca2.into_iter()) {
+    for (v1, v2) in ca1.into_iter().zip(ca2) {
         // Only use pairs where both values are not null
         if let (Some(x), Some(y)) = (v1, v2) {
             let dx = x - mean1;
@@ -505,7 +1609,7 @@ fn calculate_correlation(s1: &Series, s2: &Series) -> Result<f64> {
     let correlation = cov_sum / (var1_sum.sqrt() * var2_sum.sqrt());
     
     // Ensure the result is between -1 and 1
-    if correlation < -1.0 || correlation > 1.0 {
+    if !(-1.0..=1.0).contains(&correlation) {
         // Handle floating point precision issues
         if (correlation + 1.0).abs() < f64::EPSILON {
             return Ok(-1.0);
@@ -519,3 +1623,41 @@ fn calculate_correlation(s1: &Series, s2: &Series) -> Result<f64> {
     Ok(correlation)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigSources;
+    use crate::services::memory_db::MemoryDatabaseService;
+    use crate::services::memory_redis::MemoryRedisService;
+    use crate::services::memory_s3::MemoryS3Service;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn the_job_lock_is_renewed_while_work_runs() {
+        let mut config = Config::load(&ConfigSources::default()).unwrap();
+        config.job_lock_ttl_secs = 1;
+        let cache = MemoryRedisService::new(100, 1 << 20);
+        let processor = DataProcessor::new(
+            MemoryS3Service::new(1 << 20, false).unwrap(),
+            MemoryDatabaseService::new(),
+            cache.clone(),
+            &config,
+            None,
+        );
+        let job_id = Uuid::new_v4();
+        let token = processor.acquire_job_lock(job_id).await.unwrap();
+
+        let work = async {
+            tokio::time::sleep(Duration::from_millis(2500)).await;
+            // Past its one-second TTL, the lock is still ours
+            processor.acquire_job_lock(job_id).await
+        };
+        let taken = processor.holding_job_lock(job_id, &token, work).await;
+        assert!(taken.unwrap_err().downcast_ref::<JobLocked>().is_some());
+
+        // Without renewal it expires
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert!(processor.acquire_job_lock(job_id).await.is_ok());
+    }
+}
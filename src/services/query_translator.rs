@@ -1,12 +1,21 @@
 use anyhow::{Result, anyhow, Context};
+use chrono::{NaiveDate, NaiveDateTime};
 use log::{info, warn, error};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use std::sync::OnceLock;
 use polars::prelude::*;
-use uuid::Uuid;
+use polars::sql::SQLContext;
 
-use crate::models::conversation::ConversationContext;
+use crate::models::conversation::{ConversationContext, DatasetMetadata};
+use crate::models::job::Job;
+use crate::models::schema::SchemaOverrides;
+use crate::models::usage::TokenUsage;
 use crate::services::ai::AIService;
+use crate::services::analysis::schema;
+use crate::services::column_resolver::resolve_column;
+use crate::services::rule_translator;
+use crate::services::sql_guard;
 use crate::services::S3ServiceTrait;
 
 /// Represents the intent of a query
@@ -22,6 +31,13 @@ pub enum QueryIntent {
     Describe,
     /// Visualize the data
     Visualize,
+    /// Reshape into a wide table, one column per value of another column
+    Pivot,
+}
+
+impl QueryIntent {
+    /// Every intent the query engine can execute
+    pub const SUPPORTED: &'static [&'static str] = &["Aggregate", "Filter", "Sort", "Describe", "Visualize", "Pivot"];
 }
 
 /// Represents a column operation
@@ -33,14 +49,91 @@ pub enum ColumnOperation {
     Sum(String),
     /// Count values in a column
     Count(String),
+    /// Calculate the median of a column
+    Median(String),
+    /// Calculate a quantile of a column, e.g. 0.9 for the 90th percentile
+    Quantile(String, f64), // (column name, quantile in 0..=1)
+    /// Calculate the sample standard deviation of a column
+    Std(String),
+    /// Smallest value of a column
+    Min(String),
+    /// Largest value of a column
+    Max(String),
+    /// Count the distinct values of a column
+    DistinctCount(String),
     /// Group by a column
     GroupBy(String),
-    /// Sort by a column
-    SortBy(String, bool), // (column name, ascending)
+    /// Sort by one or more columns, each ascending or descending
+    SortBy(Vec<(String, bool)>, bool), // ((column name, ascending) in priority order, nulls last)
     /// Filter by a condition
     Filter(String, String, String), // (column, operator, value)
+    /// Filter aggregated rows by a condition on a result column, e.g. `sum_revenue > 1000000`
+    Having(String, String, String), // (result column, operator, value)
+    /// Keep only the first n rows
+    Limit(usize),
+    /// Keep the n rows with the highest (or, ascending, lowest) values of a column
+    TopN(String, usize, bool), // (column name, n, ascending)
+    /// Truncate a date column to the start of its day, week, month, quarter or year
+    DateTrunc(String, String), // (column name, unit)
+    /// Cross-tabulate a value column: one row per index value, one column per pivot value
+    Pivot(String, String, String, String), // (index column, pivot column, value column, aggregation)
+    /// Mean over a sliding window of rows ("7") or of time ("7d") ending at each row
+    RollingMean(String, String, Option<String>), // (column, window, date column ordering the rows)
+    /// Sum over a sliding window of rows or of time ending at each row
+    RollingSum(String, String, Option<String>), // (column, window, date column ordering the rows)
+    /// Running total
+    CumSum(String, Option<String>), // (column, date column ordering the rows)
+    /// Value n rows earlier
+    Lag(String, usize, Option<String>), // (column, n, date column ordering the rows)
+    /// Change from the value n rows earlier
+    Diff(String, usize, Option<String>), // (column, n, date column ordering the rows)
 }
 
+impl ColumnOperation {
+    /// Every operation the query engine can execute
+    pub const SUPPORTED: &'static [&'static str] = &[
+        "Mean", "Sum", "Count", "Median", "Quantile", "Std", "Min", "Max", "DistinctCount", "GroupBy", "SortBy", "Filter", "Having", "Limit", "TopN", "DateTrunc", "Pivot",
+        "RollingMean", "RollingSum", "CumSum", "Lag", "Diff",
+    ];
+}
+
+/// Comparison operators accepted by `ColumnOperation::Filter`. The string
+/// predicates are case-insensitive; `in` takes a comma-separated list.
+pub const FILTER_OPERATORS: &[&str] = &[
+    "=", "==", "!=", "<>", ">", "<", ">=", "<=", "contains", "starts_with", "ends_with", "regex", "in",
+];
+
+/// Comparison operators accepted by `ColumnOperation::Having`
+pub const HAVING_OPERATORS: &[&str] = &["=", "==", "!=", "<>", ">", "<", ">=", "<="];
+
+/// Aggregations a `Having` can name instead of a result column; `sum` with
+/// column `revenue` refers to `sum_revenue`
+pub const HAVING_AGGREGATIONS: &[&str] = &["mean", "sum", "count", "median", "std", "min", "max", "distinct_count"];
+
+/// Units accepted by `ColumnOperation::DateTrunc`
+pub const DATE_TRUNC_UNITS: &[&str] = &["day", "week", "month", "quarter", "year"];
+
+/// Aggregations accepted by `ColumnOperation::Pivot`
+pub const PIVOT_AGGREGATIONS: &[&str] = &["sum", "mean", "count", "min", "max", "median", "first"];
+
+/// Time units a rolling window can span, e.g. `7d`, `2w`, `1mo`
+pub const WINDOW_UNITS: &[&str] = &["h", "d", "w", "mo", "q", "y"];
+
+/// Distinct pivot values allowed, since each becomes a column
+pub const MAX_PIVOT_COLUMNS: usize = 50;
+
+/// Table name a dataset is registered under for SQL queries
+pub const SQL_TABLE_NAME: &str = "data";
+
+/// Rows returned by a SQL query at most
+pub const SQL_ROW_LIMIT: usize = 10_000;
+
+/// Rows returned by a `Describe` query without a `Limit`
+pub const DESCRIBE_ROW_LIMIT: usize = 10;
+
+/// Rows returned by a `Visualize` query without a `Limit`
+pub const VISUALIZE_ROW_LIMIT: usize = 100;
+
 /// Represents a structured query
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StructuredQuery {
@@ -52,49 +145,238 @@ pub struct StructuredQuery {
     pub operations: Vec<ColumnOperation>,
 }
 
-/// Translates natural language queries into structured queries
-#[derive(Clone, Debug)]
-pub struct QueryTranslator {
-    ai_service: Option<AIService>,
-    s3_bucket: String,
+impl StructuredQuery {
+    /// The query in the documented JSON shape accepted by `parse_structured_query`,
+    /// so an executed query can be read and replayed as-is
+    pub fn to_json(&self) -> Value {
+        let operations: Vec<Value> = self
+            .operations
+            .iter()
+            .map(|op| match op {
+                ColumnOperation::Mean(c) => json!({"type": "Mean", "column": c}),
+                ColumnOperation::Sum(c) => json!({"type": "Sum", "column": c}),
+                ColumnOperation::Count(c) => json!({"type": "Count", "column": c}),
+                ColumnOperation::Median(c) => json!({"type": "Median", "column": c}),
+                ColumnOperation::Quantile(c, q) => json!({"type": "Quantile", "column": c, "q": q}),
+                ColumnOperation::Std(c) => json!({"type": "Std", "column": c}),
+                ColumnOperation::Min(c) => json!({"type": "Min", "column": c}),
+                ColumnOperation::Max(c) => json!({"type": "Max", "column": c}),
+                ColumnOperation::DistinctCount(c) => json!({"type": "DistinctCount", "column": c}),
+                ColumnOperation::GroupBy(c) => json!({"type": "GroupBy", "column": c}),
+                ColumnOperation::SortBy(keys, nulls_last) => json!({
+                    "type": "SortBy",
+                    "by": keys
+                        .iter()
+                        .map(|(c, ascending)| json!({"column": c, "ascending": ascending}))
+                        .collect::<Vec<_>>(),
+                    "nulls": if *nulls_last { "last" } else { "first" },
+                }),
+                ColumnOperation::Filter(c, operator, value) => {
+                    json!({"type": "Filter", "column": c, "operator": operator, "value": value})
+                }
+                ColumnOperation::Having(c, operator, value) => {
+                    json!({"type": "Having", "column": c, "operator": operator, "value": value})
+                }
+                ColumnOperation::Limit(n) => json!({"type": "Limit", "n": n}),
+                ColumnOperation::TopN(c, n, ascending) => {
+                    json!({"type": "TopN", "column": c, "n": n, "ascending": ascending})
+                }
+                ColumnOperation::DateTrunc(c, unit) => json!({"type": "DateTrunc", "column": c, "unit": unit}),
+                ColumnOperation::Pivot(index, pivot, values, aggregation) => json!({
+                    "type": "Pivot",
+                    "index": index,
+                    "pivot": pivot,
+                    "column": values,
+                    "aggregation": aggregation,
+                }),
+                ColumnOperation::RollingMean(c, window, date_column) => {
+                    json!({"type": "RollingMean", "column": c, "window": window, "date_column": date_column})
+                }
+                ColumnOperation::RollingSum(c, window, date_column) => {
+                    json!({"type": "RollingSum", "column": c, "window": window, "date_column": date_column})
+                }
+                ColumnOperation::CumSum(c, date_column) => {
+                    json!({"type": "CumSum", "column": c, "date_column": date_column})
+                }
+                ColumnOperation::Lag(c, n, date_column) => {
+                    json!({"type": "Lag", "column": c, "n": n, "date_column": date_column})
+                }
+                ColumnOperation::Diff(c, n, date_column) => {
+                    json!({"type": "Diff", "column": c, "n": n, "date_column": date_column})
+                }
+            })
+            .collect();
+
+        json!({
+            "intent": format!("{:?}", self.intent),
+            "columns": self.columns,
+            "operations": operations,
+        })
+    }
 }
 
-impl QueryTranslator {
-    /// Create a new query translator
-    pub fn new(ai_service: Option<AIService>) -> Self {
-        // Get bucket name from environment or use a default
-        let s3_bucket = std::env::var("S3_BUCKET")
-            .unwrap_or_else(|_| {
-                log::warn!("S3_BUCKET environment variable not set, using default bucket name");
-                "data-pipeline-bucket".to_string()
-            });
+/// Structured query as written by the model or a query builder, before validation
+#[derive(Debug, Deserialize)]
+struct RawStructuredQuery {
+    intent: String,
+    #[serde(default)]
+    columns: Vec<String>,
+    #[serde(default)]
+    operations: Vec<RawOperation>,
+}
 
-        log::info!("QueryTranslator initialized with bucket: {}", s3_bucket);
+/// One entry of the `operations` array, e.g.
+/// `{"type": "Filter", "column": "price", "operator": ">", "value": "10"}`
+#[derive(Debug, Deserialize)]
+struct RawOperation {
+    #[serde(rename = "type")]
+    kind: String,
+    column: Option<String>,
+    operator: Option<String>,
+    value: Option<Value>,
+    /// Row count for `Limit` and `TopN`
+    n: Option<usize>,
+    /// Quantile for `Quantile`, between 0 and 1 (percentiles up to 100 are also accepted)
+    q: Option<f64>,
+    /// Truncation unit for `DateTrunc`
+    unit: Option<String>,
+    /// Row and column keys of a `Pivot`; `column` holds the values
+    index: Option<String>,
+    pivot: Option<String>,
+    /// Pivot aggregation, `sum` when absent; for `Having`, the aggregation of `column`
+    aggregation: Option<String>,
+    ascending: Option<bool>,
+    /// Accepted as an alternative to `ascending`: "asc" or "desc"
+    direction: Option<String>,
+    /// Sort keys of a multi-column `SortBy`, highest priority first
+    by: Option<Vec<RawSortKey>>,
+    /// Where a `SortBy` puts missing values: "first" (default) or "last"
+    nulls: Option<String>,
+    /// Rolling window: a row count (`7`) or a time span (`"7d"`, `"2w"`, `"1mo"`)
+    window: Option<Value>,
+    /// Date column that orders the rows of a window operation and measures time windows
+    date_column: Option<String>,
+}
 
-        Self {
-            ai_service,
-            s3_bucket,
-        }
+/// One key of a multi-column `SortBy`, e.g. `{"column": "revenue", "direction": "desc"}`
+#[derive(Debug, Deserialize)]
+struct RawSortKey {
+    column: String,
+    ascending: Option<bool>,
+    direction: Option<String>,
+}
+
+/// Sort direction from either `ascending` or `direction`, ascending when neither is given
+fn sort_ascending(ascending: Option<bool>, direction: Option<&str>) -> bool {
+    match (ascending, direction) {
+        (Some(ascending), _) => ascending,
+        (None, Some(direction)) => !direction.trim().eq_ignore_ascii_case("desc"),
+        (None, None) => true,
+    }
+}
+
+/// JSON Schema of the structured query format, given to the model as the
+/// parameters of the function it answers with, so intents, operation types and
+/// operators can only be ones the engine executes
+pub fn structured_query_schema() -> Value {
+    let mut aggregations: Vec<&str> = PIVOT_AGGREGATIONS.to_vec();
+    aggregations.extend(HAVING_AGGREGATIONS.iter().filter(|a| !PIVOT_AGGREGATIONS.contains(a)));
+    let direction = json!({"type": "string", "enum": ["asc", "desc"]});
+
+    json!({
+        "type": "object",
+        "properties": {
+            "intent": {"type": "string", "enum": QueryIntent::SUPPORTED},
+            "columns": {
+                "type": "array",
+                "items": {"type": "string"},
+                "description": "Dataset columns the query uses"
+            },
+            "operations": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "type": {"type": "string", "enum": ColumnOperation::SUPPORTED},
+                        "column": {"type": "string"},
+                        "operator": {"type": "string", "enum": FILTER_OPERATORS},
+                        "value": {
+                            "anyOf": [
+                                {"type": "string"},
+                                {"type": "number"},
+                                {"type": "array", "items": {"type": "string"}}
+                            ],
+                            "description": "Filter or Having value; a list for the in operator"
+                        },
+                        "n": {"type": "integer", "minimum": 1},
+                        "q": {"type": "number", "minimum": 0, "maximum": 1},
+                        "unit": {"type": "string", "enum": DATE_TRUNC_UNITS},
+                        "index": {"type": "string", "description": "Row key of a Pivot"},
+                        "pivot": {"type": "string", "description": "Column of a Pivot whose values become columns"},
+                        "aggregation": {"type": "string", "enum": aggregations},
+                        "ascending": {"type": "boolean"},
+                        "direction": direction,
+                        "by": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "column": {"type": "string"},
+                                    "direction": direction
+                                },
+                                "required": ["column"]
+                            },
+                            "description": "Sort keys of a multi-column SortBy, highest priority first"
+                        },
+                        "nulls": {"type": "string", "enum": ["first", "last"]},
+                        "window": {
+                            "anyOf": [{"type": "integer", "minimum": 1}, {"type": "string"}],
+                            "description": "Row count, or time span such as 7d, 2w or 1mo"
+                        },
+                        "date_column": {"type": "string", "description": "Date column ordering the rows of a window operation"}
+                    },
+                    "required": ["type"]
+                }
+            }
+        },
+        "required": ["intent", "columns", "operations"]
+    })
+}
+
+/// Translates natural language queries into structured queries
+#[derive(Clone, Debug, Default)]
+pub struct QueryTranslator;
+
+impl QueryTranslator {
+    /// Create a new query translator
+    pub fn new() -> Self {
+        Self
     }
 
-    /// Translate a natural language query into a structured query
+    /// Translate a natural language query into a structured query with the AI
+    /// service when one is given, reporting the AI tokens spent (zero for
+    /// rule-based translation)
     pub async fn translate_query(
         &self,
         query: &str,
         context: &ConversationContext,
-    ) -> Result<StructuredQuery> {
+        ai_service: Option<&AIService>,
+    ) -> Result<(StructuredQuery, TokenUsage)> {
         // If AI service is available, use it for translation
-        if let Some(ai_service) = &self.ai_service {
+        if let Some(ai_service) = ai_service {
             info!("Using AI service to translate query: {}", query);
 
             // Build the prompt
             let prompt = self.build_translation_prompt(query, context);
 
             // Send to AI service
-            let response = ai_service.generate_query_translation(&prompt).await?;
+            let (response, usage) = ai_service.generate_query_translation(&prompt).await?;
 
             // Parse the response
-            return self.parse_ai_response(response);
+            let structured_query = self
+                .parse_structured_query(response, &context.dataset_metadata)
+                .context("AI translation was not a valid structured query")?;
+            return Ok((structured_query, usage));
         }
 
         // If no AI service is available, use a simple rule-based approach
@@ -102,7 +384,7 @@ impl QueryTranslator {
             "No AI service available, using rule-based translation for query: {}",
             query
         );
-        self.rule_based_translation(query, context)
+        Ok((self.rule_based_translation(query, context)?, TokenUsage::default()))
     }
 
     /// Build a prompt for the AI service
@@ -113,12 +395,15 @@ impl QueryTranslator {
                 "data_types": context.dataset_metadata.data_types,
                 "row_count": context.dataset_metadata.row_count
             },
-            "conversation_history": context.history.iter().map(|turn| {
+            "conversation_summary": context.summary,
+            "conversation_history": context.recent_turns().iter().map(|turn| {
                 json!({
                     "query": turn.query,
                     "response": turn.response
                 })
             }).collect::<Vec<_>>(),
+            "previous_query": context.last_query,
+            "previous_result_columns": context.last_result_columns,
             "current_query": query,
             "examples": [
                 {
@@ -136,331 +421,1149 @@ impl QueryTranslator {
                         "columns": ["column1", "column2"],
                         "operations": [{"type": "Filter", "column": "column1", "operator": ">", "value": "10"}]
                     }
+                },
+                {
+                    "query": "Show the 5 rows with the highest column1",
+                    "structured_query": {
+                        "intent": "Sort",
+                        "columns": [],
+                        "operations": [{"type": "TopN", "column": "column1", "n": 5, "ascending": false}]
+                    }
+                },
+                {
+                    "query": "Total column1 in Q1 2024 by month",
+                    "structured_query": {
+                        "intent": "Aggregate",
+                        "columns": ["date", "column1"],
+                        "operations": [
+                            {"type": "Filter", "column": "date", "operator": ">=", "value": "2024-01-01"},
+                            {"type": "Filter", "column": "date", "operator": "<", "value": "2024-04-01"},
+                            {"type": "DateTrunc", "column": "date", "unit": "month"},
+                            {"type": "GroupBy", "column": "date"},
+                            {"type": "Sum", "column": "column1"}
+                        ]
+                    }
+                },
+                {
+                    "query": "Which categories have total column1 over 1M?",
+                    "structured_query": {
+                        "intent": "Aggregate",
+                        "columns": ["category", "column1"],
+                        "operations": [
+                            {"type": "GroupBy", "column": "category"},
+                            {"type": "Sum", "column": "column1"},
+                            {"type": "Having", "column": "column1", "aggregation": "sum", "operator": ">", "value": "1000000"}
+                        ]
+                    }
                 }
             ]
         })
     }
 
-    /// Parse the AI service response into a structured query
-    fn parse_ai_response(&self, _response: Value) -> Result<StructuredQuery> {
-        // In a real implementation, parse `response` (JSON) into `StructuredQuery`.
-        // For now, return a placeholder.
+    /// Parse a structured query in the documented JSON shape (the AI translation
+    /// format), checking every column it mentions against the dataset
+    pub fn parse_structured_query(&self, response: Value, metadata: &DatasetMetadata) -> Result<StructuredQuery> {
+        let raw: RawStructuredQuery = serde_json::from_value(response.clone())
+            .with_context(|| format!("Query did not match the structured query format: {}", response))?;
+
+        let intent = match raw.intent.trim().to_lowercase().as_str() {
+            "aggregate" => QueryIntent::Aggregate,
+            "filter" => QueryIntent::Filter,
+            "sort" => QueryIntent::Sort,
+            "describe" => QueryIntent::Describe,
+            "visualize" | "visualise" => QueryIntent::Visualize,
+            "pivot" => QueryIntent::Pivot,
+            _ => {
+                return Err(anyhow!(
+                    "Query used unknown intent '{}' (expected one of {})",
+                    raw.intent,
+                    QueryIntent::SUPPORTED.join(", ")
+                ))
+            }
+        };
+
+        let columns = raw
+            .columns
+            .iter()
+            .map(|c| resolve_column(c, &metadata.columns))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Aggregations are ranked by their results (`sum_revenue`), which are not
+        // dataset columns; they are checked once every operation is parsed
+        let aggregating = matches!(intent, QueryIntent::Aggregate);
+        let ranking_column = |name: &str| match resolve_column(name, &metadata.columns) {
+            Err(_) if aggregating => Ok(name.trim().to_string()),
+            resolved => resolved,
+        };
+
+        let mut operations = Vec::with_capacity(raw.operations.len());
+        for op in &raw.operations {
+            let kind = op.kind.trim().to_lowercase();
+            if kind == "limit" {
+                let n = op.n.ok_or_else(|| anyhow!("Query has a Limit without n"))?;
+                operations.push(ColumnOperation::Limit(n));
+                continue;
+            }
+            if matches!(kind.as_str(), "sortby" | "sort_by" | "sort") {
+                let keys = match (&op.by, &op.column) {
+                    (Some(by), _) if !by.is_empty() => by
+                        .iter()
+                        .map(|key| {
+                            let column = ranking_column(&key.column)?;
+                            Ok((column, sort_ascending(key.ascending, key.direction.as_deref())))
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                    (_, Some(column)) => vec![(
+                        ranking_column(column)?,
+                        sort_ascending(op.ascending, op.direction.as_deref()),
+                    )],
+                    _ => return Err(anyhow!("Query has a SortBy without a column")),
+                };
+                let nulls_last = match op.nulls.as_deref().map(|n| n.trim().to_lowercase()) {
+                    None => false,
+                    Some(nulls) if nulls == "first" => false,
+                    Some(nulls) if nulls == "last" => true,
+                    Some(nulls) => return Err(anyhow!("Query used unsupported nulls placement '{}' (expected first or last)", nulls)),
+                };
+                operations.push(ColumnOperation::SortBy(keys, nulls_last));
+                continue;
+            }
+            if kind == "having" {
+                operations.push(self.parse_having(op, &intent, metadata)?);
+                continue;
+            }
+            if matches!(kind.as_str(), "topn" | "top_n") {
+                let column = op
+                    .column
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("Query has a TopN without a column"))?;
+                let column = ranking_column(column)?;
+                let n = op
+                    .n
+                    .ok_or_else(|| anyhow!("Query has a TopN on '{}' without n", column))?;
+                operations.push(ColumnOperation::TopN(column, n, op.ascending.unwrap_or(false)));
+                continue;
+            }
+            if let Some(operation) = self.parse_window(op, &kind, &intent, &operations, metadata)? {
+                operations.push(operation);
+                continue;
+            }
+            let column = match &op.column {
+                Some(column) => resolve_column(column, &metadata.columns)?,
+                None => return Err(anyhow!("Query has a {} operation without a column", op.kind)),
+            };
+            let operation = match kind.as_str() {
+                "mean" | "average" | "avg" => ColumnOperation::Mean(column),
+                "sum" => ColumnOperation::Sum(column),
+                "count" => ColumnOperation::Count(column),
+                "median" => ColumnOperation::Median(column),
+                "quantile" | "percentile" => {
+                    let q = op
+                        .q
+                        .ok_or_else(|| anyhow!("Query has a Quantile of '{}' without q", column))?;
+                    // "the 90th percentile" often arrives as 90 rather than 0.9
+                    let q = if q > 1.0 && q <= 100.0 { q / 100.0 } else { q };
+                    if !(0.0..=1.0).contains(&q) {
+                        return Err(anyhow!("Query has a Quantile of '{}' with q outside 0..1", column));
+                    }
+                    ColumnOperation::Quantile(column, q)
+                }
+                "std" | "stddev" | "std_dev" => ColumnOperation::Std(column),
+                "min" => ColumnOperation::Min(column),
+                "max" => ColumnOperation::Max(column),
+                "distinctcount" | "distinct_count" | "count_distinct" | "n_unique" => ColumnOperation::DistinctCount(column),
+                "groupby" | "group_by" => ColumnOperation::GroupBy(column),
+                "datetrunc" | "date_trunc" => {
+                    let unit = op
+                        .unit
+                        .as_deref()
+                        .map(|u| u.trim().to_lowercase())
+                        .ok_or_else(|| anyhow!("Query has a DateTrunc on '{}' without a unit", column))?;
+                    if !DATE_TRUNC_UNITS.contains(&unit.as_str()) {
+                        return Err(anyhow!(
+                            "Query used unsupported date unit '{}' (expected one of {})",
+                            unit,
+                            DATE_TRUNC_UNITS.join(", ")
+                        ));
+                    }
+                    ColumnOperation::DateTrunc(column, unit)
+                }
+                "pivot" => {
+                    let index = op
+                        .index
+                        .as_deref()
+                        .ok_or_else(|| anyhow!("Query has a Pivot of '{}' without an index column", column))?;
+                    let pivot = op
+                        .pivot
+                        .as_deref()
+                        .ok_or_else(|| anyhow!("Query has a Pivot of '{}' without a pivot column", column))?;
+                    let aggregation = op.aggregation.as_deref().unwrap_or("sum").trim().to_lowercase();
+                    if !PIVOT_AGGREGATIONS.contains(&aggregation.as_str()) {
+                        return Err(anyhow!(
+                            "Query used unsupported pivot aggregation '{}' (expected one of {})",
+                            aggregation,
+                            PIVOT_AGGREGATIONS.join(", ")
+                        ));
+                    }
+                    ColumnOperation::Pivot(
+                        resolve_column(index, &metadata.columns)?,
+                        resolve_column(pivot, &metadata.columns)?,
+                        column,
+                        aggregation,
+                    )
+                }
+                "filter" => {
+                    let operator = op
+                        .operator
+                        .as_deref()
+                        .map(|o| o.trim().to_lowercase())
+                        .ok_or_else(|| anyhow!("Query has a Filter on '{}' without an operator", column))?;
+                    if !FILTER_OPERATORS.contains(&operator.as_str()) {
+                        return Err(anyhow!(
+                            "Query used unsupported filter operator '{}' (expected one of {})",
+                            operator,
+                            FILTER_OPERATORS.join(" ")
+                        ));
+                    }
+                    let value = filter_value(op.value.as_ref())
+                        .ok_or_else(|| anyhow!("Query has a Filter on '{}' without a value", column))?;
+                    ColumnOperation::Filter(column, operator, value)
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "Query used unknown operation '{}' (expected one of {})",
+                        op.kind,
+                        ColumnOperation::SUPPORTED.join(", ")
+                    ))
+                }
+            };
+            operations.push(operation);
+        }
+        if aggregating {
+            resolve_ranking_columns(&mut operations)?;
+        }
+
         Ok(StructuredQuery {
-            intent: QueryIntent::Describe,
-            columns: vec!["column1".to_string(), "column2".to_string()],
-            operations: vec![],
+            intent,
+            columns,
+            operations,
         })
     }
 
-    /// Use a simple rule-based approach to translate a query
-    fn rule_based_translation(
+    /// Parse a window operation (`RollingMean`, `RollingSum`, `CumSum`, `Lag`, `Diff`);
+    /// `None` for other operations. In `Aggregate` queries windows run over the
+    /// aggregated rows, so `column` may name an aggregation result (`sum_revenue`),
+    /// and a dataset column aggregated exactly once stands for that result.
+    fn parse_window(
         &self,
-        query: &str,
-        context: &ConversationContext,
-    ) -> Result<StructuredQuery> {
-        let query = query.to_lowercase();
-
-        // Check for common patterns
-        if query.contains("average") || query.contains("mean") {
-            // Pick the first column from metadata (fallback to "column1")
-            let columns = context.dataset_metadata.columns.clone();
-            let column = columns
-                .first()
-                .cloned()
-                .unwrap_or_else(|| "column1".to_string());
-
-            return Ok(StructuredQuery {
-                intent: QueryIntent::Aggregate,
-                columns: vec![column.clone()],
-                operations: vec![ColumnOperation::Mean(column)],
-            });
+        op: &RawOperation,
+        kind: &str,
+        intent: &QueryIntent,
+        parsed: &[ColumnOperation],
+        metadata: &DatasetMetadata,
+    ) -> Result<Option<ColumnOperation>> {
+        let kind = match kind {
+            "rollingmean" | "rolling_mean" | "rollingavg" | "rolling_avg" | "rolling_average" => "RollingMean",
+            "rollingsum" | "rolling_sum" => "RollingSum",
+            "cumsum" | "cum_sum" | "cumulative_sum" | "running_total" => "CumSum",
+            "lag" | "shift" => "Lag",
+            "diff" | "difference" | "change" => "Diff",
+            _ => return Ok(None),
+        };
+        if matches!(intent, QueryIntent::Pivot) {
+            return Err(anyhow!("{} cannot be combined with a Pivot", kind));
         }
 
-        if query.contains("sum") {
-            let columns = context.dataset_metadata.columns.clone();
-            let column = columns
-                .first()
-                .cloned()
-                .unwrap_or_else(|| "column1".to_string());
-
-            return Ok(StructuredQuery {
-                intent: QueryIntent::Aggregate,
-                columns: vec![column.clone()],
-                operations: vec![ColumnOperation::Sum(column)],
-            });
-        }
+        let name = op
+            .column
+            .as_deref()
+            .map(str::trim)
+            .ok_or_else(|| anyhow!("Query has a {} operation without a column", kind))?;
+        let results: Vec<String> = parsed.iter().filter_map(aggregation_name).collect();
+        let column = if results.iter().any(|r| r == name) {
+            name.to_string()
+        } else {
+            let column = resolve_column(name, &metadata.columns)?;
+            let mut aggregated = results.iter().filter(|r| r.ends_with(&format!("_{}", column)));
+            match (intent, aggregated.next(), aggregated.next()) {
+                (QueryIntent::Aggregate, Some(result), None) => result.clone(),
+                _ => column,
+            }
+        };
+        let date_column = op
+            .date_column
+            .as_deref()
+            .map(|c| resolve_column(c, &metadata.columns))
+            .transpose()?;
 
-        if query.contains("count") {
-            let columns = context.dataset_metadata.columns.clone();
-            let column = columns
-                .first()
-                .cloned()
-                .unwrap_or_else(|| "column1".to_string());
-
-            return Ok(StructuredQuery {
-                intent: QueryIntent::Aggregate,
-                columns: vec![column.clone()],
-                operations: vec![ColumnOperation::Count(column)],
-            });
+        let operation = match kind {
+            "RollingMean" | "RollingSum" => {
+                let window = match &op.window {
+                    Some(Value::Number(n)) => n.to_string(),
+                    Some(Value::String(w)) => w.trim().to_lowercase(),
+                    _ => return Err(anyhow!("Query has a {} of '{}' without a window", kind, column)),
+                };
+                let date_column = if window.chars().all(|c| c.is_ascii_digit()) {
+                    if window.parse::<usize>().unwrap_or(0) == 0 {
+                        return Err(anyhow!("Query has a {} of '{}' with an empty window", kind, column));
+                    }
+                    date_column
+                } else {
+                    let unit = window.trim_start_matches(|c: char| c.is_ascii_digit());
+                    if unit.len() == window.len() || !WINDOW_UNITS.contains(&unit) {
+                        return Err(anyhow!(
+                            "Query used unsupported window '{}' (expected a row count or a span in {})",
+                            window,
+                            WINDOW_UNITS.join(", ")
+                        ));
+                    }
+                    // Time windows need a date column; the dataset's only one is assumed
+                    let dates: Vec<&String> = metadata
+                        .columns
+                        .iter()
+                        .filter(|c| metadata.data_types.get(*c).is_some_and(|t| t == "date" || t == "datetime"))
+                        .collect();
+                    match (date_column, dates.as_slice()) {
+                        (Some(date_column), _) => Some(date_column),
+                        (None, [only]) => Some((*only).clone()),
+                        _ => return Err(anyhow!("Query has a {} over '{}' without a date_column", kind, window)),
+                    }
+                };
+                if kind == "RollingMean" {
+                    ColumnOperation::RollingMean(column, window, date_column)
+                } else {
+                    ColumnOperation::RollingSum(column, window, date_column)
+                }
+            }
+            "CumSum" => ColumnOperation::CumSum(column, date_column),
+            "Lag" => ColumnOperation::Lag(column, op.n.unwrap_or(1), date_column),
+            _ => ColumnOperation::Diff(column, op.n.unwrap_or(1), date_column),
+        };
+        Ok(Some(operation))
+    }
+
+    /// Parse a `Having` operation. The condition names either a result column
+    /// (`{"column": "sum_revenue"}`) or a dataset column and its aggregation
+    /// (`{"column": "revenue", "aggregation": "sum"}`).
+    fn parse_having(&self, op: &RawOperation, intent: &QueryIntent, metadata: &DatasetMetadata) -> Result<ColumnOperation> {
+        if !matches!(intent, QueryIntent::Aggregate) {
+            return Err(anyhow!("Having only applies to Aggregate queries"));
+        }
+        let column = op
+            .column
+            .as_deref()
+            .map(str::trim)
+            .ok_or_else(|| anyhow!("Query has a Having without a column"))?;
+        let column = match op.aggregation.as_deref().map(|a| a.trim().to_lowercase()) {
+            Some(aggregation) => {
+                if !HAVING_AGGREGATIONS.contains(&aggregation.as_str()) {
+                    return Err(anyhow!(
+                        "Query used unsupported having aggregation '{}' (expected one of {})",
+                        aggregation,
+                        HAVING_AGGREGATIONS.join(", ")
+                    ));
+                }
+                format!("{}_{}", aggregation, resolve_column(column, &metadata.columns)?)
+            }
+            None => column.to_string(),
+        };
+        let operator = op
+            .operator
+            .as_deref()
+            .map(|o| o.trim().to_lowercase())
+            .ok_or_else(|| anyhow!("Query has a Having on '{}' without an operator", column))?;
+        if !HAVING_OPERATORS.contains(&operator.as_str()) {
+            return Err(anyhow!(
+                "Query used unsupported having operator '{}' (expected one of {})",
+                operator,
+                HAVING_OPERATORS.join(" ")
+            ));
         }
+        let value = filter_value(op.value.as_ref())
+            .ok_or_else(|| anyhow!("Query has a Having on '{}' without a value", column))?;
+        Ok(ColumnOperation::Having(column, operator, value))
+    }
 
-        // Default to describing the dataset
-        Ok(StructuredQuery {
-            intent: QueryIntent::Describe,
-            columns: context.dataset_metadata.columns.clone(),
-            operations: vec![],
-        })
+    /// Translate a query from the column names, comparison phrases, groups and
+    /// sorting words it mentions, for when no AI service is configured
+    fn rule_based_translation(
+        &self,
+        query: &str,
+        context: &ConversationContext,
+    ) -> Result<StructuredQuery> {
+        // A follow-up refines the previous query instead of starting over
+        let previous = context
+            .last_query
+            .clone()
+            .and_then(|previous| self.parse_structured_query(previous, &context.dataset_metadata).ok());
+        if let Some(refined) = previous.and_then(|previous| rule_translator::refine(query, &previous, &context.dataset_metadata)) {
+            return Ok(refined);
+        }
+        Ok(rule_translator::translate(query, &context.dataset_metadata))
     }
 
     /// Execute a structured query on a dataset
     pub async fn execute_query(
         &self,
         structured_query: &StructuredQuery,
-        job_id: &str,
+        job: &Job,
         s3_service: &dyn S3ServiceTrait,
     ) -> Result<DataFrame> {
-        // 1. Load the CSV from S3
-        let uuid = match Uuid::parse_str(job_id) {
-            Ok(id) => id,
-            Err(e) => return Err(anyhow!("Invalid job ID: {}", e)),
-        };
+        let df = self.load_dataset(job, s3_service).await?;
 
-        // Files are stored under "uploads/{job_id}.csv"
-        let file_key = format!("uploads/{}.csv", uuid);
+        // Apply the structured query operations
+        self.apply_operations(df, structured_query)
+    }
 
-        info!("Loading CSV data for job {} with key {}", job_id, file_key);
-        let csv_data = match s3_service.get_object("", &file_key).await {
-            Ok(data) => {
-                info!("Loaded CSV data with direct key ({} bytes)", data.len());
-                data
-            }
-            Err(_) => {
-                // Fallback #1: default bucket
-                match s3_service.get_object("default-bucket", &file_key).await {
-                    Ok(data) => {
-                        info!("Loaded CSV data from default bucket ({} bytes)", data.len());
-                        data
-                    }
-                    Err(_) => {
-                        // Fallback #2: configured bucket
-                        match s3_service.get_object(&self.s3_bucket, &file_key).await {
-                            Ok(data) => {
-                                info!(
-                                    "Loaded CSV data from configured bucket ({} bytes)",
-                                    data.len()
-                                );
-                                data
-                            }
-                            Err(_) => {
-                                // Fallback #3: key = "{job_id}.csv"
-                                let simple_key = format!("{}.csv", uuid);
-                                match s3_service.get_object("", &simple_key).await {
-                                    Ok(data) => {
-                                        info!(
-                                            "Loaded CSV data with simple key ({} bytes)",
-                                            data.len()
-                                        );
-                                        data
-                                    }
-                                    Err(e) => {
-                                        error!(
-                                            "Failed to load CSV data after all fallbacks: {}",
-                                            e
-                                        );
-                                        return Err(anyhow!("Failed to load CSV data: {}", e));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        };
+    /// Run a SQL query against a dataset registered as the table `data`,
+    /// returning at most `SQL_ROW_LIMIT` rows and whether more were cut off.
+    /// Statements that do anything but read `data` are refused; see `sql_guard::check`.
+    pub fn execute_sql(&self, df: DataFrame, sql: &str) -> Result<(DataFrame, bool)> {
+        sql_guard::check(sql)?;
+        let mut context = SQLContext::new();
+        context.register(SQL_TABLE_NAME, df.lazy());
+        let result = context
+            .execute(sql)
+            .context("Failed to plan SQL query")?
+            .limit(SQL_ROW_LIMIT as IdxSize + 1)
+            .collect()
+            .context("Failed to execute SQL query")?;
+        let truncated = result.height() > SQL_ROW_LIMIT;
+        Ok((result.head(Some(SQL_ROW_LIMIT)), truncated))
+    }
 
-        // 2. Parse CSV into a DataFrame
-        let df = match self.parse_csv_data(&csv_data) {
-            Ok(df) => {
-                info!("Parsed CSV: {} rows, {} columns", df.height(), df.width());
-                df
-            }
-            Err(e) => {
-                error!("Failed to parse CSV data: {}", e);
-                return Err(anyhow!("Failed to parse CSV data: {}", e));
+    /// Load a job's dataset from the storage keys recorded with the job: the typed
+    /// processed copy when it is readable, otherwise the uploaded CSV
+    pub async fn load_dataset(&self, job: &Job, s3_service: &dyn S3ServiceTrait) -> Result<DataFrame> {
+        // Prefer the typed copy written after processing; it needs no parsing or inference
+        let processed = job.processed_key();
+        if let Ok(data) = s3_service.get_object(&processed.bucket, &processed.key).await {
+            match IpcReader::new(std::io::Cursor::new(data)).finish() {
+                Ok(df) => {
+                    info!("Loaded processed copy for job {}: {} rows, {} columns", job.id, df.height(), df.width());
+                    return Ok(df);
+                }
+                Err(e) => warn!("Processed copy for job {} is unreadable, falling back to CSV: {}", job.id, e),
             }
-        };
+        }
 
-        // 3. Apply the structured query operations
-        let result_df = self.apply_operations(df, structured_query)?;
+        let source = job.source_key();
+        info!("Loading CSV data for job {} from {}", job.id, source);
+        let csv_data = s3_service
+            .get_object(&source.bucket, &source.key)
+            .await
+            .map_err(|e| {
+                error!("Failed to load dataset file {} of job {}: {}", source, job.id, e);
+                anyhow!("Dataset file {} of job {} could not be read: {}", source, job.id, e)
+            })?;
 
-        Ok(result_df)
+        let df = self.parse_csv_data(&csv_data, &job.schema_overrides).map_err(|e| {
+            error!("Failed to parse CSV data: {}", e);
+            anyhow!("Failed to parse CSV data: {}", e)
+        })?;
+        info!("Parsed CSV: {} rows, {} columns", df.height(), df.width());
+        Ok(df)
     }
 
-    /// Parse CSV data into a DataFrame
-    fn parse_csv_data(&self, csv_data: &[u8]) -> Result<DataFrame> {
+    /// Parse CSV data into a DataFrame, applying the job's schema overrides
+    fn parse_csv_data(&self, csv_data: &[u8], overrides: &SchemaOverrides) -> Result<DataFrame> {
         let df = CsvReader::new(std::io::Cursor::new(csv_data))
             .infer_schema(Some(100))
             .has_header(true)
+            .with_try_parse_dates(true)
             .finish()
             .context("Failed to parse CSV data")?;
-        Ok(df)
+        schema::apply_overrides(df, overrides)
     }
 
-    /// Apply operations from a structured query to a DataFrame
-    fn apply_operations(&self, df: DataFrame, query: &StructuredQuery) -> Result<DataFrame> {
-        let mut result = df;
+    /// Apply operations from a structured query to a DataFrame. The operations are
+    /// compiled into one lazy plan and collected once with the streaming engine, so
+    /// filters and projections are pushed down and no intermediate copy of the
+    /// dataset is materialized per operation. Sorts always carry a null flag key
+    /// next to each column and the streaming sort only handles one key, so plans
+    /// with `SortBy` run on the default engine, as do aggregations over several
+    /// groups, whose results are sorted by every key, and window operations,
+    /// which need every row in order.
+    pub fn apply_operations(&self, df: DataFrame, query: &StructuredQuery) -> Result<DataFrame> {
+        let schema = df.schema();
+        let plan = self.query_plan(df.lazy(), &schema, query)?;
+        let group_keys = query.operations.iter().filter(|op| matches!(op, ColumnOperation::GroupBy(_))).count();
+        let streaming = group_keys < 2
+            && !query.operations.iter().any(|op| {
+                matches!(op, ColumnOperation::SortBy(..)) || window_expr(op).is_some()
+            });
+        Ok(plan.with_streaming(streaming).collect()?)
+    }
+
+    /// Optimized Polars plan a structured query runs as on a dataset with this
+    /// schema. The plan is built over an empty frame, so nothing is computed; a
+    /// pivot shows up as the scan of its (empty) wide table.
+    pub fn explain(&self, schema: &Schema, query: &StructuredQuery) -> Result<String> {
+        let plan = self.query_plan(DataFrame::from(schema).lazy(), schema, query)?;
+        Ok(plan.describe_optimized_plan()?)
+    }
+
+    /// Lazy plan for a structured query: filter → date truncation → the intent's
+    /// aggregation, sort or pivot → projection → row limits
+    fn query_plan(&self, lf: LazyFrame, schema: &Schema, query: &StructuredQuery) -> Result<LazyFrame> {
+        // Filters and date truncation narrow and reshape the rows for every intent,
+        // e.g. "sales in Q1 2024 by month" filters on the date before aggregating
+        let mut result = self.apply_filters(lf, schema, &query.operations)?;
+        result = self.apply_date_truncation(result, schema, &query.operations)?;
+        // Windows see the filtered rows; in aggregations they see the groups instead
+        if !matches!(query.intent, QueryIntent::Aggregate) {
+            result = self.apply_windows(result, &query.operations)?;
+        }
 
         match query.intent {
             QueryIntent::Describe => {
-                // Return the first 10 rows
-                result = result.head(Some(10));
+                // Return the first few rows
+                result = self.apply_row_limits(result, &query.operations, Some(DESCRIBE_ROW_LIMIT));
             }
 
             QueryIntent::Aggregate => {
-                // Apply each aggregation operation
-                for op in &query.operations {
-                    match op {
-                        ColumnOperation::Mean(col_name) => {
-                            // First compute the mean expression
-                            let mean_expr = col(col_name).mean();
-                            // Create a new dataframe with just this expression
-                            result = result.lazy().select([mean_expr.alias(&format!("mean_{}", col_name))])
-                                .collect()?;
-                        }
-                        ColumnOperation::Sum(col_name) => {
-                            // First compute the sum expression
-                            let sum_expr = col(col_name).sum();
-                            // Create a new dataframe with just this expression
-                            result = result.lazy().select([sum_expr.alias(&format!("sum_{}", col_name))])
-                                .collect()?;
-                        }
-                        ColumnOperation::Count(col_name) => {
-                            // First compute the count expression
-                            let count_expr = col(col_name).count();
-                            // Create a new dataframe with just this expression
-                            result = result.lazy().select([count_expr.alias(&format!("count_{}", col_name))])
-                                .collect()?;
-                        }
-                        ColumnOperation::GroupBy(col_name) => {
-                            // Group by `col_name` and count rows in each group
-                            // Use lazy API for groupby and aggregation
-                            let count_expr = col(col_name).count().alias(&format!("count_{}", col_name));
-                            result = result.lazy()
-                                .group_by([col(col_name)])
-                                .agg([count_expr])
-                                .collect()?;
-                        }
-                        _ => {
-                            // Other ops (e.g., SortBy or Filter) are not handled under Aggregate
+                // Aggregations are computed per group when the query groups, otherwise
+                // over the whole (filtered) dataset
+                let keys: Vec<Expr> = query
+                    .operations
+                    .iter()
+                    .filter_map(|op| match op {
+                        ColumnOperation::GroupBy(col_name) => Some(col(col_name)),
+                        _ => None,
+                    })
+                    .collect();
+                let mut aggregations: Vec<Expr> = query.operations.iter().filter_map(aggregation_expr).collect();
+
+                if keys.is_empty() {
+                    if !aggregations.is_empty() {
+                        result = result.select(aggregations);
+                    }
+                } else {
+                    if aggregations.is_empty() {
+                        // Group by `col_name` and count rows in each group
+                        for op in &query.operations {
+                            if let ColumnOperation::GroupBy(col_name) = op {
+                                aggregations.push(col(col_name).count().alias(&format!("count_{}", col_name)));
+                                break;
+                            }
                         }
                     }
+                    let n_keys = keys.len();
+                    result = result
+                        .group_by(keys.clone())
+                        .agg(aggregations)
+                        .sort_by_exprs(keys, vec![false; n_keys], true, false);
                 }
+
+                // e.g. "regions with total sales over 1M"
+                result = self.apply_having(result, &query.operations)?;
+
+                // e.g. "7-day rolling average of daily signups"
+                result = self.apply_windows(result, &query.operations)?;
+
+                // e.g. the five largest groups
+                result = self.apply_row_limits(result, &query.operations, None);
             }
 
             QueryIntent::Filter => {
-                // Apply each filter operation
-                for op in &query.operations {
-                    if let ColumnOperation::Filter(col_name, operator, value) = op {
-                        let filter_expr = match operator.as_str() {
-                            "=" | "==" => col(col_name).eq(lit(value.clone())),
-                            ">" => match value.parse::<f64>() {
-                                Ok(num) => col(col_name).gt(lit(num)),
-                                Err(_) => {
-                                    warn!(
-                                        "Failed to parse '{}' as number for '>' comparison",
-                                        value
-                                    );
-                                    continue;
-                                }
-                            },
-                            "<" => match value.parse::<f64>() {
-                                Ok(num) => col(col_name).lt(lit(num)),
-                                Err(_) => {
-                                    warn!(
-                                        "Failed to parse '{}' as number for '<' comparison",
-                                        value
-                                    );
-                                    continue;
-                                }
-                            },
-                            ">=" => match value.parse::<f64>() {
-                                Ok(num) => col(col_name).gt_eq(lit(num)),
-                                Err(_) => {
-                                    warn!(
-                                        "Failed to parse '{}' as number for '>=' comparison",
-                                        value
-                                    );
-                                    continue;
-                                }
-                            },
-                            "<=" => match value.parse::<f64>() {
-                                Ok(num) => col(col_name).lt_eq(lit(num)),
-                                Err(_) => {
-                                    warn!(
-                                        "Failed to parse '{}' as number for '<=' comparison",
-                                        value
-                                    );
-                                    continue;
-                                }
-                            },
-                            "!=" | "<>" => col(col_name).neq(lit(value.clone())),
-                            _ => {
-                                warn!("Unsupported operator: {}", operator);
-                                continue;
-                            }
-                        };
-
-                        // Convert expression to lazy dataframe and collect
-                        result = result.lazy().filter(filter_expr).collect()?;
-                    }
-                }
+                result = self.apply_row_limits(result, &query.operations, None);
 
                 // After filtering, select only requested columns (if any)
-                if !query.columns.is_empty() {
-                    let cols: Vec<&str> = query
-                        .columns
-                        .iter()
-                        .map(|c| c.as_str())
-                        .collect();
-                    result = result.select(cols)?;
-                }
+                result = select_columns(result, &output_columns(query));
             }
 
             QueryIntent::Sort => {
-                // Apply each sort operation
+                // Every SortBy contributes its keys in order, so two SortBy operations
+                // sort by the first and break ties with the second
+                let nulls_last = query
+                    .operations
+                    .iter()
+                    .any(|op| matches!(op, ColumnOperation::SortBy(_, true)));
+                let mut keys = Vec::new();
+                let mut descending = Vec::new();
                 for op in &query.operations {
-                    if let ColumnOperation::SortBy(col_name, ascending) = op {
-                        // Sort by column with specified options
-                        result = result.sort([col_name], vec![!ascending], false)?;
+                    if let ColumnOperation::SortBy(columns, _) = op {
+                        for (col_name, ascending) in columns {
+                            // Multi-key sorts flip null placement on descending keys,
+                            // so each key's nulls are placed by an explicit null flag
+                            keys.push(col(col_name).is_null());
+                            descending.push(!nulls_last);
+                            keys.push(col(col_name));
+                            descending.push(!ascending);
+                        }
                     }
                 }
+                if !keys.is_empty() {
+                    result = result.sort_by_exprs(keys, descending, false, false);
+                }
+                result = self.apply_row_limits(result, &query.operations, None);
 
                 // After sorting, select only requested columns (if any)
-                if !query.columns.is_empty() {
-                    let cols: Vec<&str> = query
-                        .columns
-                        .iter()
-                        .map(|c| c.as_str())
-                        .collect();
-                    result = result.select(cols)?;
-                }
+                result = select_columns(result, &output_columns(query));
             }
 
             QueryIntent::Visualize => {
+                // Limit the number of rows to avoid sending too much data
+                result = self.apply_row_limits(result, &query.operations, Some(VISUALIZE_ROW_LIMIT));
+
                 // Select requested columns (if any)
-                if !query.columns.is_empty() {
-                    let cols: Vec<&str> = query
-                        .columns
-                        .iter()
-                        .map(|c| c.as_str())
-                        .collect();
-                    result = result.select(cols)?;
+                result = select_columns(result, &output_columns(query));
+            }
+
+            QueryIntent::Pivot => {
+                let (index, pivot_column, values, aggregation) = query
+                    .operations
+                    .iter()
+                    .find_map(|op| match op {
+                        ColumnOperation::Pivot(index, pivot_column, values, aggregation) => {
+                            Some((index, pivot_column, values, aggregation))
+                        }
+                        _ => None,
+                    })
+                    .ok_or_else(|| anyhow!("Pivot queries need a Pivot operation"))?;
+                // Pivoting is eager in Polars; only the columns it reads are collected
+                let long = result
+                    .select([col(index), col(pivot_column), col(values)])
+                    .with_streaming(true)
+                    .collect()?;
+                result = self.pivot_table(long, index, pivot_column, values, aggregation)?.lazy();
+                result = self.apply_row_limits(result, &query.operations, None);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Wide table with one row per `index` value and one column per `pivot_column`
+    /// value, each cell aggregating `values`
+    fn pivot_table(
+        &self,
+        df: DataFrame,
+        index: &str,
+        pivot_column: &str,
+        values: &str,
+        aggregation: &str,
+    ) -> Result<DataFrame> {
+        let distinct = df.column(pivot_column)?.n_unique()?;
+        if distinct > MAX_PIVOT_COLUMNS {
+            return Err(anyhow!(
+                "Column '{}' has {} distinct values; pivots are limited to {} columns",
+                pivot_column,
+                distinct,
+                MAX_PIVOT_COLUMNS
+            ));
+        }
+
+        let value_column = col(values);
+        let agg = match aggregation {
+            "sum" => value_column.sum(),
+            "mean" => value_column.mean(),
+            "count" => value_column.count(),
+            "min" => value_column.min(),
+            "max" => value_column.max(),
+            "median" => value_column.median(),
+            "first" => value_column.first(),
+            other => return Err(anyhow!("Unsupported pivot aggregation: {}", other)),
+        };
+
+        let wide = pivot::pivot_stable(&df, [values], [index], [pivot_column], true, Some(agg), None)?;
+        Ok(wide.sort([index], false, true)?)
+    }
+
+    /// Apply every `Filter` operation. Comparisons against date columns parse the
+    /// value as a date; comparisons against numeric columns, and ordering comparisons
+    /// on other columns, parse it as a number (see `parse_number`). A value that
+    /// does not parse fails the query rather than dropping the filter.
+    fn apply_filters(&self, lf: LazyFrame, schema: &Schema, operations: &[ColumnOperation]) -> Result<LazyFrame> {
+        let mut result = lf;
+
+        for op in operations {
+            if let ColumnOperation::Filter(col_name, operator, value) = op {
+                let dtype = column_type(schema, col_name)?;
+
+                // String predicates and `in` lists build their own expressions
+                let predicate = if operator.eq_ignore_ascii_case("in") {
+                    Some(in_list(col_name, value, &dtype))
+                } else {
+                    string_predicate(col_name, operator, value)?
+                };
+                if let Some(filter_expr) = predicate {
+                    result = result.filter(filter_expr);
+                    continue;
                 }
 
-                // Limit the number of rows to avoid sending too much data
-                result = result.head(Some(100));
+                let literal = match &dtype {
+                    DataType::Date | DataType::Datetime(_, _) => match parse_date_value(value) {
+                        Some(dt) if dtype == DataType::Date => lit(dt.date()),
+                        Some(dt) => lit(dt).cast(dtype.clone()),
+                        None => {
+                            warn!("Failed to parse '{}' as a date for column '{}'", value, col_name);
+                            continue;
+                        }
+                    },
+                    _ if !dtype.is_numeric() && matches!(operator.as_str(), "=" | "==" | "!=" | "<>") => {
+                        lit(value.clone())
+                    }
+                    _ => match parse_number(value) {
+                        Some(num) => lit(num),
+                        None => {
+                            return Err(anyhow!(
+                                "Filter on '{}' compares against '{}', which is not a number",
+                                col_name,
+                                value
+                            ))
+                        }
+                    },
+                };
+
+                match comparison(col_name, operator, literal) {
+                    Some(filter_expr) => result = result.filter(filter_expr),
+                    None => warn!("Unsupported operator: {}", operator),
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Add a column for each window operation, named after the operation
+    /// (`rolling_mean_7d_signups`, `cumsum_revenue`, `diff1_price`). Rows are put in
+    /// date order first when an operation names a date column.
+    fn apply_windows(&self, lf: LazyFrame, operations: &[ColumnOperation]) -> Result<LazyFrame> {
+        let windows: Vec<&ColumnOperation> = operations.iter().filter(|op| window_expr(op).is_some()).collect();
+        if windows.is_empty() {
+            return Ok(lf);
+        }
+        let schema = lf.schema()?;
+        for op in &windows {
+            let (column, date_column) = match op {
+                ColumnOperation::RollingMean(c, _, d) | ColumnOperation::RollingSum(c, _, d) => (c, d),
+                ColumnOperation::CumSum(c, d) | ColumnOperation::Lag(c, _, d) | ColumnOperation::Diff(c, _, d) => (c, d),
+                _ => continue,
+            };
+            let numeric_only = !matches!(op, ColumnOperation::Lag(..));
+            if let Some(dtype) = schema.get(column).filter(|dtype| numeric_only && !dtype.is_numeric()) {
+                return Err(anyhow!("Window operation on '{}' needs a numeric column, not {}", column, dtype));
+            }
+            for name in std::iter::once(column).chain(date_column) {
+                if schema.get(name).is_none() {
+                    return Err(anyhow!(
+                        "Window operation refers to '{}', which is not a column at that point (available: {})",
+                        name,
+                        schema.iter_names().map(|n| n.as_str()).collect::<Vec<_>>().join(", ")
+                    ));
+                }
+            }
+        }
+
+        let mut result = lf;
+        let date_column = windows.iter().find_map(|op| match op {
+            ColumnOperation::RollingMean(_, _, d)
+            | ColumnOperation::RollingSum(_, _, d)
+            | ColumnOperation::CumSum(_, d)
+            | ColumnOperation::Lag(_, _, d)
+            | ColumnOperation::Diff(_, _, d) => d.clone(),
+            _ => None,
+        });
+        if let Some(date_column) = date_column {
+            result = result.sort(&date_column, SortOptions::default());
+        }
+        let exprs: Vec<Expr> = windows.iter().filter_map(|op| window_expr(op)).collect();
+        Ok(result.with_columns(exprs))
+    }
+
+    /// Apply every `Having` operation to the aggregated rows. The condition's
+    /// column must be one of the aggregation's result columns.
+    fn apply_having(&self, lf: LazyFrame, operations: &[ColumnOperation]) -> Result<LazyFrame> {
+        if !operations.iter().any(|op| matches!(op, ColumnOperation::Having(..))) {
+            return Ok(lf);
+        }
+        let schema = lf.schema()?;
+        let mut result = lf;
+
+        for op in operations {
+            if let ColumnOperation::Having(col_name, operator, value) = op {
+                let dtype = schema.get(col_name).ok_or_else(|| {
+                    anyhow!(
+                        "Having refers to '{}', which is not an aggregation result (available: {})",
+                        col_name,
+                        schema.iter_names().map(|n| n.as_str()).collect::<Vec<_>>().join(", ")
+                    )
+                })?;
+                let literal = if dtype.is_numeric() {
+                    let num = parse_number(value)
+                        .ok_or_else(|| anyhow!("Having on '{}' compares against '{}', which is not a number", col_name, value))?;
+                    lit(num)
+                } else {
+                    lit(value.clone())
+                };
+                let condition = comparison(col_name, operator, literal)
+                    .ok_or_else(|| anyhow!("Unsupported having operator: {}", operator))?;
+                result = result.filter(condition);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Replace each `DateTrunc` column with the start of its day/week/month/quarter/year
+    /// so it can be grouped by
+    fn apply_date_truncation(&self, lf: LazyFrame, schema: &Schema, operations: &[ColumnOperation]) -> Result<LazyFrame> {
+        let mut result = lf;
+
+        for op in operations {
+            if let ColumnOperation::DateTrunc(col_name, unit) = op {
+                let every = match unit.as_str() {
+                    "day" => "1d",
+                    "week" => "1w",
+                    "month" => "1mo",
+                    "quarter" => "3mo",
+                    "year" => "1y",
+                    _ => {
+                        warn!("Unsupported date unit: {}", unit);
+                        continue;
+                    }
+                };
+                let dtype = column_type(schema, col_name)?;
+                if !matches!(dtype, DataType::Date | DataType::Datetime(_, _)) {
+                    return Err(anyhow!("Column '{}' is not a date column and cannot be truncated by {}", col_name, unit));
+                }
+                result = result.with_column(
+                    col(col_name)
+                        .dt()
+                        .truncate(lit(every), "0ns".to_string(), lit("raise"))
+                        .alias(col_name),
+                );
             }
         }
 
         Ok(result)
     }
+
+    /// Apply `TopN` and `Limit` operations in order. `default_limit` caps the rows
+    /// when the query has no explicit `Limit` or `TopN`.
+    fn apply_row_limits(
+        &self,
+        lf: LazyFrame,
+        operations: &[ColumnOperation],
+        default_limit: Option<usize>,
+    ) -> LazyFrame {
+        let mut result = lf;
+        let mut limited = false;
+
+        for op in operations {
+            match op {
+                // A sort followed by a limit is planned as a top-k selection
+                ColumnOperation::TopN(col_name, n, ascending) => {
+                    result = result
+                        .sort_by_exprs([col(col_name)], [!ascending], true, false)
+                        .limit(*n as IdxSize);
+                    limited = true;
+                }
+                ColumnOperation::Limit(n) => {
+                    result = result.limit(*n as IdxSize);
+                    limited = true;
+                }
+                _ => {}
+            }
+        }
+
+        match default_limit {
+            Some(n) if !limited => result.limit(n as IdxSize),
+            _ => result,
+        }
+    }
+}
+
+/// Expression computing an aggregation operation, named `{aggregation}_{column}`
+/// (`p90_{column}` for the 90th percentile); `None` for other operations
+fn aggregation_expr(op: &ColumnOperation) -> Option<Expr> {
+    let (expr, name) = match op {
+        ColumnOperation::Mean(c) => (col(c).mean(), format!("mean_{}", c)),
+        ColumnOperation::Sum(c) => (col(c).sum(), format!("sum_{}", c)),
+        ColumnOperation::Count(c) => (col(c).count(), format!("count_{}", c)),
+        ColumnOperation::Median(c) => (col(c).median(), format!("median_{}", c)),
+        ColumnOperation::Quantile(c, q) => (
+            col(c).quantile(lit(*q), QuantileInterpolOptions::Linear),
+            format!("p{}_{}", (q * 100.0 * 100.0).round() / 100.0, c),
+        ),
+        ColumnOperation::Std(c) => (col(c).std(1), format!("std_{}", c)),
+        ColumnOperation::Min(c) => (col(c).min(), format!("min_{}", c)),
+        ColumnOperation::Max(c) => (col(c).max(), format!("max_{}", c)),
+        ColumnOperation::DistinctCount(c) => (col(c).n_unique(), format!("distinct_count_{}", c)),
+        _ => return None,
+    };
+    Some(expr.alias(&name))
+}
+
+/// `column <operator> literal` for the comparison operators; `None` for any other operator
+fn comparison(col_name: &str, operator: &str, literal: Expr) -> Option<Expr> {
+    let expr = match operator {
+        "=" | "==" => col(col_name).eq(literal),
+        "!=" | "<>" => col(col_name).neq(literal),
+        ">" => col(col_name).gt(literal),
+        "<" => col(col_name).lt(literal),
+        ">=" => col(col_name).gt_eq(literal),
+        "<=" => col(col_name).lt_eq(literal),
+        _ => return None,
+    };
+    Some(expr)
+}
+
+/// Filter value as text; `in` lists may come as a JSON array and are joined with commas
+fn filter_value(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Array(items) => Some(
+            items
+                .iter()
+                .map(|item| match item {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+        _ => None,
+    }
+}
+
+/// Point the `SortBy`/`TopN` keys of an aggregation at columns it produces: a
+/// dataset column aggregated exactly once ranks by that result (`revenue` →
+/// `sum_revenue`). A `TopN` on anything but a group or a result is an error.
+fn resolve_ranking_columns(operations: &mut [ColumnOperation]) -> Result<()> {
+    let groups: Vec<String> = operations
+        .iter()
+        .filter_map(|op| match op {
+            ColumnOperation::GroupBy(c) => Some(c.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut results: Vec<String> = operations.iter().filter_map(aggregation_name).collect();
+    // Groups without aggregations are counted
+    if let (true, Some(group)) = (results.is_empty(), groups.first()) {
+        results.push(format!("count_{}", group));
+    }
+    let resolve = |column: &str| -> Option<String> {
+        if groups.iter().chain(&results).any(|c| c == column) {
+            return Some(column.to_string());
+        }
+        let suffix = format!("_{}", column);
+        let mut aggregated = results.iter().filter(|r| r.ends_with(&suffix));
+        match (aggregated.next(), aggregated.next()) {
+            (Some(result), None) => Some(result.clone()),
+            _ => None,
+        }
+    };
+    for op in operations.iter_mut() {
+        match op {
+            ColumnOperation::TopN(column, ..) => {
+                *column = resolve(column).ok_or_else(|| {
+                    anyhow!(
+                        "Query ranks the aggregation by '{}', which it does not produce (available: {})",
+                        column,
+                        groups.iter().chain(&results).cloned().collect::<Vec<_>>().join(", ")
+                    )
+                })?;
+            }
+            ColumnOperation::SortBy(keys, _) => {
+                for (column, _) in keys.iter_mut() {
+                    if let Some(resolved) = resolve(column) {
+                        *column = resolved;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Result column an aggregation operation produces; `None` for other operations
+pub fn aggregation_name(op: &ColumnOperation) -> Option<String> {
+    aggregation_expr(op).and_then(|expr| match expr {
+        Expr::Alias(_, name) => Some(name.to_string()),
+        _ => None,
+    })
+}
+
+/// Expression computing a window operation, named `{operation}_{column}`; `None`
+/// for other operations. Row windows count rows; time windows (`7d`) span the
+/// rows whose date falls within the window ending at each row.
+fn window_expr(op: &ColumnOperation) -> Option<Expr> {
+    let rolling = |window: &str, date_column: &Option<String>| {
+        let time_based = !window.chars().all(|c| c.is_ascii_digit());
+        RollingOptions {
+            window_size: Duration::parse(&if time_based { window.to_string() } else { format!("{}i", window) }),
+            min_periods: 1,
+            by: if time_based { date_column.clone() } else { None },
+            closed_window: time_based.then_some(ClosedWindow::Right),
+            ..Default::default()
+        }
+    };
+    let (expr, name) = match op {
+        ColumnOperation::RollingMean(c, window, date_column) => (
+            col(c).cast(DataType::Float64).rolling_mean(rolling(window, date_column)),
+            format!("rolling_mean_{}_{}", window, c),
+        ),
+        ColumnOperation::RollingSum(c, window, date_column) => (
+            col(c).cast(DataType::Float64).rolling_sum(rolling(window, date_column)),
+            format!("rolling_sum_{}_{}", window, c),
+        ),
+        ColumnOperation::CumSum(c, _) => (col(c).cumsum(false), format!("cumsum_{}", c)),
+        ColumnOperation::Lag(c, n, _) => (col(c).shift(*n as i64), format!("lag{}_{}", n, c)),
+        ColumnOperation::Diff(c, n, _) => (col(c).diff(*n as i64, polars::series::ops::NullBehavior::Ignore), format!("diff{}_{}", n, c)),
+        _ => return None,
+    };
+    Some(expr.alias(&name))
+}
+
+/// Requested columns plus the columns window operations add, so selecting
+/// columns keeps the windows; an empty list still means every column
+fn output_columns(query: &StructuredQuery) -> Vec<String> {
+    if query.columns.is_empty() {
+        return Vec::new();
+    }
+    let mut columns = query.columns.clone();
+    for expr in query.operations.iter().filter_map(window_expr) {
+        if let Expr::Alias(_, name) = expr {
+            columns.push(name.to_string());
+        }
+    }
+    columns
+}
+
+/// Keep only the requested columns; an empty list keeps them all
+fn select_columns(lf: LazyFrame, columns: &[String]) -> LazyFrame {
+    if columns.is_empty() {
+        return lf;
+    }
+    lf.select(columns.iter().map(|c| col(c)).collect::<Vec<_>>())
+}
+
+/// Type of a column in the dataset being queried
+fn column_type(schema: &Schema, col_name: &str) -> Result<DataType> {
+    schema
+        .get(col_name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Column '{}' not found in the dataset", col_name))
+}
+
+/// Case-insensitive `contains`, `starts_with`, `ends_with` and `regex` filters;
+/// `None` for any other operator. Non-string columns are compared as text.
+fn string_predicate(col_name: &str, operator: &str, value: &str) -> Result<Option<Expr>> {
+    let text = || col(col_name).cast(DataType::Utf8).str().to_lowercase().str();
+    let needle = value.to_lowercase();
+
+    let expr = match operator.to_lowercase().as_str() {
+        "contains" => text().contains_literal(lit(needle)),
+        "starts_with" => text().starts_with(lit(needle)),
+        "ends_with" => text().ends_with(lit(needle)),
+        "regex" => {
+            regex::Regex::new(value).map_err(|e| anyhow!("Invalid regex '{}' in filter on '{}': {}", value, col_name, e))?;
+            col(col_name)
+                .cast(DataType::Utf8)
+                .str()
+                .contains(lit(format!("(?i){}", value)), true)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(expr))
+}
+
+/// `column IN (a, b, c)`: matches any value of a comma-separated list, optionally
+/// wrapped in parentheses and with quoted items. Numeric columns compare numerically.
+fn in_list(col_name: &str, value: &str, dtype: &DataType) -> Expr {
+    let items: Vec<&str> = value
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(|item| item.trim().trim_matches(|c| c == '\'' || c == '"'))
+        .filter(|item| !item.is_empty())
+        .collect();
+
+    items
+        .iter()
+        .map(|item| match parse_number(item) {
+            Some(num) if dtype.is_numeric() => col(col_name).eq(lit(num)),
+            _ => col(col_name).cast(DataType::Utf8).eq(lit(item.to_string())),
+        })
+        .reduce(|a, b| a.or(b))
+        .unwrap_or_else(|| lit(false))
+}
+
+/// Parse a number the way people write it in questions: currency symbols, thousands
+/// separators, `k`/`M`/`B` (or thousand/million/billion) suffixes and percentages,
+/// e.g. `$1,200` → 1200, `2.5M` → 2500000, `-€3k` → -3000, `15%` → 0.15.
+/// Percentages compare as fractions, the usual storage for rates and shares.
+pub fn parse_number(value: &str) -> Option<f64> {
+    let mut text = value.trim().to_lowercase();
+    let mut sign = 1.0;
+    // Accounting notation for negatives: (1,200)
+    if text.starts_with('(') && text.ends_with(')') {
+        sign = -sign;
+        text = text[1..text.len() - 1].trim().to_string();
+    }
+    if let Some(rest) = text.strip_prefix('-') {
+        sign = -sign;
+        text = rest.trim().to_string();
+    }
+    let mut text = text.trim_start_matches(['$', '€', '£', '¥']).trim();
+    if let Some(rest) = text.strip_prefix('-') {
+        sign = -sign;
+        text = rest.trim();
+    }
+
+    let mut scale = 1.0;
+    for (suffix, factor) in [
+        ("billion", 1e9),
+        ("million", 1e6),
+        ("thousand", 1e3),
+        ("bn", 1e9),
+        ("%", 0.01),
+        ("k", 1e3),
+        ("m", 1e6),
+        ("b", 1e9),
+    ] {
+        if let Some(rest) = text.strip_suffix(suffix) {
+            text = rest.trim();
+            scale = factor;
+            break;
+        }
+    }
+
+    // Commas only count as thousands separators in groups of three: 1,200 but not 1,2
+    static GROUPED: OnceLock<regex::Regex> = OnceLock::new();
+    let grouped = GROUPED.get_or_init(|| regex::Regex::new(r"^\d{1,3}(,\d{3})+(\.\d+)?$").unwrap());
+    let digits = if text.contains(',') {
+        if !grouped.is_match(text) {
+            return None;
+        }
+        text.replace(',', "")
+    } else {
+        text.to_string()
+    };
+    // f64 parsing also accepts "inf" and "nan", which are not amounts
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    digits.parse::<f64>().ok().map(|n| sign * n * scale)
+}
+
+/// Parse a filter value as a date or date-time. `2024-03` means the first day of the month.
+fn parse_date_value(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(dt);
+        }
+    }
+    ["%Y-%m-%d", "%Y/%m/%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d").ok())
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
 }
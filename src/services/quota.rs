@@ -0,0 +1,333 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::models::storage::{tenant_prefix, DEFAULT_TENANT, TENANT_PREFIX};
+use crate::models::usage::UsageReservation;
+use crate::services::usage::UsageLedger;
+use crate::services::{DatabaseServiceTrait, RedisServiceTrait, S3ServiceTrait};
+
+/// How long a tenant's storage counter is trusted before it is listed again, so
+/// objects removed outside uploads and appends are eventually given back
+const STORAGE_COUNTER_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// AI tokens held for a tenant each time the AI service is taken up for it, before
+/// what its calls spend is known; at most the tenant's daily quota
+pub const AI_TOKENS_PER_HOLD: u64 = 4_000;
+
+/// A tenant reached one of its quotas; answered with 429
+#[derive(Debug)]
+pub struct QuotaExceeded(pub String);
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// What each tenant may use, so one tenant cannot starve the others; 0 means no limit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TenantQuotas {
+    pub max_storage_bytes: u64,
+    pub max_jobs_per_day: u64,
+    pub max_ai_tokens_per_day: u64,
+}
+
+impl TenantQuotas {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_storage_bytes: config.tenant_max_storage_bytes,
+            max_jobs_per_day: config.tenant_max_jobs_per_day,
+            max_ai_tokens_per_day: config.tenant_max_ai_tokens_per_day,
+        }
+    }
+
+    /// Reserve `new_bytes` of the tenant's storage quota, failing with
+    /// `QuotaExceeded` when they don't fit. Concurrent uploads reserve against one
+    /// counter in the cache, so they can't all pass the same check; give the bytes
+    /// back with `release_storage` when they end up not stored.
+    pub async fn reserve_storage<S, R>(&self, storage: &S, cache: &R, tenant_id: &str, new_bytes: u64) -> Result<()>
+    where
+        S: S3ServiceTrait,
+        R: RedisServiceTrait,
+    {
+        if self.max_storage_bytes == 0 || new_bytes == 0 {
+            return Ok(());
+        }
+        let counter = storage_counter(tenant_id);
+        let used = || stored_bytes(storage, tenant_id);
+        if let Some(used) = reserve(cache, &counter, new_bytes, self.max_storage_bytes, STORAGE_COUNTER_TTL, used).await? {
+            bail!(QuotaExceeded(format!(
+                "storing {} more bytes would exceed the tenant's {} byte storage quota ({} bytes used)",
+                new_bytes, self.max_storage_bytes, used
+            )));
+        }
+        Ok(())
+    }
+
+    /// Give back storage reserved for bytes that were not stored
+    pub async fn release_storage<R: RedisServiceTrait>(&self, cache: &R, tenant_id: &str, bytes: u64) {
+        if self.max_storage_bytes > 0 && bytes > 0 {
+            release(cache, &storage_counter(tenant_id), bytes).await;
+        }
+    }
+
+    /// Reserve one of the tenant's jobs for today, failing with `QuotaExceeded`
+    /// when it already created them all; give it back with `release_job` when no
+    /// job is created
+    pub async fn reserve_job<D, R>(&self, jobs: &D, cache: &R, tenant_id: &str) -> Result<()>
+    where
+        D: DatabaseServiceTrait,
+        R: RedisServiceTrait,
+    {
+        if self.max_jobs_per_day == 0 {
+            return Ok(());
+        }
+        let now = Utc::now();
+        let (midnight, ttl) = today(now);
+        let counter = jobs_counter(tenant_id, now);
+        let created = || jobs.count_jobs_since(tenant_id, midnight);
+        if reserve(cache, &counter, 1, self.max_jobs_per_day, ttl, created).await?.is_some() {
+            bail!(QuotaExceeded(format!("the tenant reached its limit of {} jobs per day", self.max_jobs_per_day)));
+        }
+        Ok(())
+    }
+
+    /// Give back a job reserved for an upload that created none
+    pub async fn release_job<R: RedisServiceTrait>(&self, cache: &R, tenant_id: &str) {
+        if self.max_jobs_per_day > 0 {
+            release(cache, &jobs_counter(tenant_id, Utc::now()), 1).await;
+        }
+    }
+
+    /// Hold AI tokens of the tenant's daily quota for the AI calls about to be made
+    /// for it, failing with `QuotaExceeded` when it has none left; a tenant that is
+    /// out is answered by the rule-based fallbacks, as if no LLM were configured.
+    /// Concurrent holds reserve against one counter in the cache, so replicas and
+    /// concurrent turns can't all pass the same check. `None` when AI use is unlimited.
+    pub async fn reserve_ai<D>(
+        &self,
+        usage: &UsageLedger<D>,
+        cache: Arc<dyn RedisServiceTrait>,
+        tenant_id: &str,
+    ) -> Result<Option<AiReservation>>
+    where
+        D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+    {
+        if self.max_ai_tokens_per_day == 0 {
+            return Ok(None);
+        }
+        let now = Utc::now();
+        let (_, ttl) = today(now);
+        let counter = ai_counter(tenant_id, now);
+        let held = AI_TOKENS_PER_HOLD.min(self.max_ai_tokens_per_day);
+        let spent = || usage.tenant_tokens_today(tenant_id);
+        if let Some(used) = reserve(&*cache, &counter, held, self.max_ai_tokens_per_day, ttl, spent).await? {
+            log::info!("🪫 Tenant {} spent its {} AI tokens for today", tenant_id, self.max_ai_tokens_per_day);
+            bail!(QuotaExceeded(format!(
+                "the tenant spent {} of its {} AI tokens for today",
+                used, self.max_ai_tokens_per_day
+            )));
+        }
+        Ok(Some(AiReservation { cache, counter, held, spent: AtomicU64::new(0) }))
+    }
+}
+
+/// AI tokens held against a tenant's daily quota while AI calls are made for it.
+/// The calls count what they spend with `spend`; when the hold drops it is settled
+/// against that: tokens not spent are given back and any spent past it are charged.
+#[derive(Debug)]
+pub struct AiReservation {
+    cache: Arc<dyn RedisServiceTrait>,
+    counter: String,
+    held: u64,
+    spent: AtomicU64,
+}
+
+impl AiReservation {
+    /// Count tokens an AI call spent
+    pub fn spend(&self, tokens: u64) {
+        self.spent.fetch_add(tokens, Ordering::Relaxed);
+    }
+}
+
+impl Drop for AiReservation {
+    fn drop(&mut self) {
+        let (held, spent) = (self.held, *self.spent.get_mut());
+        if held == spent {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            log::warn!("⚠️ No runtime to settle AI usage counter {}; it is settled when it expires", self.counter);
+            return;
+        };
+        let (cache, counter) = (self.cache.clone(), std::mem::take(&mut self.counter));
+        runtime.spawn(async move {
+            if spent < held {
+                release(&*cache, &counter, held - spent).await;
+            } else if let Err(e) = cache.reserve_usage(&counter, spent - held, u64::MAX).await {
+                // An expired counter is seeded again from the recorded usage, which holds these tokens
+                log::warn!("⚠️ Failed to charge {} AI tokens to usage counter {}: {:#}", spent - held, counter, e);
+            }
+        });
+    }
+}
+
+/// Midnight today and how long until the next one, which a day's counters live for
+fn today(now: DateTime<Utc>) -> (DateTime<Utc>, Duration) {
+    let midnight = now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+    let ttl = (midnight + chrono::Duration::days(1) - now).to_std().unwrap_or_default().max(Duration::from_secs(1));
+    (midnight, ttl)
+}
+
+fn storage_counter(tenant_id: &str) -> String {
+    format!("quota_storage:{}", tenant_id)
+}
+
+fn jobs_counter(tenant_id: &str, now: DateTime<Utc>) -> String {
+    format!("quota_jobs:{}:{}", tenant_id, now.date_naive())
+}
+
+fn ai_counter(tenant_id: &str, now: DateTime<Utc>) -> String {
+    format!("quota_ai:{}:{}", tenant_id, now.date_naive())
+}
+
+/// Add `amount` to the usage counter `counter`, seeding it from `used` when it
+/// isn't set. Returns the usage that refused the amount, if it was refused. When
+/// the cache fails the quota is checked against `used` alone, as before counters.
+async fn reserve<R, F, Fut>(cache: &R, counter: &str, amount: u64, limit: u64, ttl: Duration, used: F) -> Result<Option<u64>>
+where
+    R: RedisServiceTrait + ?Sized,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<u64>>,
+{
+    // A counter that expires between seeding and reserving is seeded once more
+    for _ in 0..2 {
+        let reservation = match cache.reserve_usage(counter, amount, limit).await {
+            Ok(reservation) => reservation,
+            Err(e) => {
+                log::warn!("⚠️ Failed to reserve against usage counter {}, checking without it: {:#}", counter, e);
+                let used = used().await?;
+                return Ok((used + amount > limit).then_some(used));
+            }
+        };
+        match reservation {
+            UsageReservation::Reserved { .. } => return Ok(None),
+            UsageReservation::Exceeded { used } => return Ok(Some(used)),
+            UsageReservation::Unset => {
+                let used = used().await?;
+                if let Err(e) = cache.seed_usage(counter, used, ttl).await {
+                    log::warn!("⚠️ Failed to seed usage counter {}, checking without it: {:#}", counter, e);
+                    return Ok((used + amount > limit).then_some(used));
+                }
+            }
+        }
+    }
+    bail!("usage counter {} could not be seeded", counter)
+}
+
+async fn release<R: RedisServiceTrait + ?Sized>(cache: &R, counter: &str, amount: u64) {
+    if let Err(e) = cache.release_usage(counter, amount).await {
+        log::warn!("⚠️ Failed to release {} of usage counter {}: {:#}", amount, counter, e);
+    }
+}
+
+/// Bytes of the objects stored under a tenant's prefix. The default tenant's
+/// objects are unprefixed, so every other tenant's are left out of its total.
+async fn stored_bytes<S: S3ServiceTrait>(storage: &S, tenant_id: &str) -> Result<u64> {
+    let objects = storage.list_objects(&tenant_prefix(tenant_id)).await?;
+    Ok(objects
+        .iter()
+        .filter(|object| tenant_id != DEFAULT_TENANT || !object.key.starts_with(TENANT_PREFIX))
+        .map(|object| object.size)
+        .sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::usage::TokenUsage;
+    use crate::services::memory_db::MemoryDatabaseService;
+    use crate::services::memory_redis::MemoryRedisService;
+
+    fn quotas(max_ai_tokens_per_day: u64) -> TenantQuotas {
+        TenantQuotas { max_ai_tokens_per_day, ..Default::default() }
+    }
+
+    fn cache() -> Arc<dyn RedisServiceTrait> {
+        Arc::new(MemoryRedisService::new(100, 1 << 20))
+    }
+
+    fn ledger() -> UsageLedger<MemoryDatabaseService> {
+        UsageLedger::new(MemoryDatabaseService::new())
+    }
+
+    /// Tokens counted against the tenant today, once dropped holds are settled
+    async fn counted(cache: &Arc<dyn RedisServiceTrait>, tenant_id: &str) -> u64 {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        match cache.reserve_usage(&ai_counter(tenant_id, Utc::now()), 0, u64::MAX).await.unwrap() {
+            UsageReservation::Reserved { used } => used,
+            other => panic!("counter is not set: {:?}", other),
+        }
+    }
+
+    fn exceeded(result: Result<Option<AiReservation>>) -> bool {
+        result.err().is_some_and(|e| e.downcast_ref::<QuotaExceeded>().is_some())
+    }
+
+    #[tokio::test]
+    async fn concurrent_holds_cannot_overshoot_the_quota() {
+        let (quotas, usage, cache) = (quotas(10_000), ledger(), cache());
+        let first = quotas.reserve_ai(&usage, cache.clone(), "acme").await.unwrap();
+        let second = quotas.reserve_ai(&usage, cache.clone(), "acme").await.unwrap();
+        assert!(first.is_some() && second.is_some());
+        assert!(exceeded(quotas.reserve_ai(&usage, cache.clone(), "acme").await));
+        // Other tenants have their own counter
+        assert!(quotas.reserve_ai(&usage, cache.clone(), "globex").await.unwrap().is_some());
+
+        // Unspent tokens are given back once a hold drops
+        first.unwrap().spend(1_000);
+        assert_eq!(counted(&cache, "acme").await, 5_000);
+        assert!(quotas.reserve_ai(&usage, cache.clone(), "acme").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn spend_past_the_hold_is_charged() {
+        let (quotas, usage, cache) = (quotas(10_000), ledger(), cache());
+        let hold = quotas.reserve_ai(&usage, cache.clone(), "acme").await.unwrap().unwrap();
+        hold.spend(AI_TOKENS_PER_HOLD);
+        hold.spend(5_000);
+        drop(hold);
+        assert_eq!(counted(&cache, "acme").await, AI_TOKENS_PER_HOLD + 5_000);
+        assert!(exceeded(quotas.reserve_ai(&usage, cache.clone(), "acme").await));
+    }
+
+    #[tokio::test]
+    async fn counters_start_from_recorded_usage() {
+        let (quotas, usage, cache) = (quotas(10_000), ledger(), cache());
+        usage.record("job", Some("acme"), Some("alice"), None, TokenUsage::priced(7_000, 0, "gpt-4o-mini")).await.unwrap();
+        assert!(exceeded(quotas.reserve_ai(&usage, cache.clone(), "acme").await));
+        assert_eq!(counted(&cache, "acme").await, 7_000);
+    }
+
+    #[tokio::test]
+    async fn small_quotas_hold_at_most_the_quota() {
+        let (quotas, usage, cache) = (quotas(500), ledger(), cache());
+        let hold = quotas.reserve_ai(&usage, cache.clone(), "acme").await.unwrap().unwrap();
+        assert!(exceeded(quotas.reserve_ai(&usage, cache.clone(), "acme").await));
+        drop(hold);
+        assert_eq!(counted(&cache, "acme").await, 0);
+        assert!(quotas.reserve_ai(&usage, cache.clone(), "acme").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn unlimited_tenants_hold_nothing() {
+        assert!(quotas(0).reserve_ai(&ledger(), cache(), "acme").await.unwrap().is_none());
+    }
+}
@@ -1,89 +1,213 @@
-use anyhow::{Result, Context};
 #[cfg(feature = "external-services")]
-use redis::{Client, Commands, Connection};
-use uuid::Uuid;
-use crate::models::response::Insights;
+use anyhow::{Context, Result};
+#[cfg(feature = "external-services")]
+use deadpool_redis::{Config as PoolConfig, Connection, Pool, PoolConfig as PoolSize, Runtime};
+#[cfg(feature = "external-services")]
+use redis::AsyncCommands;
+#[cfg(feature = "external-services")]
+use std::time::Duration;
+
+#[cfg(feature = "external-services")]
+use crate::config::Config;
+#[cfg(feature = "external-services")]
+use crate::models::cache::CacheStats;
+#[cfg(feature = "external-services")]
+use crate::models::usage::UsageReservation;
 
+/// How long a cache call waits for a pooled connection before failing
 #[cfg(feature = "external-services")]
-#[derive(Clone, Debug)]
+const CONNECTION_WAIT: Duration = Duration::from_secs(5);
+
+/// Cache entries in Redis, over a pool of connections shared by every request.
+/// A missing key is `Ok(None)`; an unreachable server or a failed command is an
+/// error, so callers can tell a cache miss from a broken cache.
+#[cfg(feature = "external-services")]
+#[derive(Clone)]
 pub struct RedisService {
-    client: Client,
+    pool: Pool,
+}
+
+#[cfg(feature = "external-services")]
+impl std::fmt::Debug for RedisService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisService").field("pool", &self.pool.status()).finish()
+    }
 }
 
 #[cfg(feature = "external-services")]
 impl RedisService {
-    pub fn new(redis_url: &str) -> Result<Self> {
-        let client = Client::open(redis_url)?;
-        Ok(Self { client })
+    /// Open a pool to `REDIS_URL` and check the server answers, so a wrong URL
+    /// or an unreachable server fails startup instead of the first cache read
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let mut pool_config = PoolConfig::from_url(&config.redis_url);
+        let mut size = PoolSize::new(config.redis_pool_size);
+        size.timeouts.wait = Some(CONNECTION_WAIT);
+        size.timeouts.create = Some(CONNECTION_WAIT);
+        pool_config.pool = Some(size);
+        let pool = pool_config
+            .create_pool(Some(Runtime::Tokio1))
+            .context("Invalid REDIS_URL")?;
+
+        let service = Self { pool };
+        let mut conn = service.connection().await?;
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut conn)
+            .await
+            .context("Redis did not answer PING")?;
+        log::info!("🧰 Connected to Redis (pool of up to {} connections)", config.redis_pool_size);
+        Ok(service)
     }
 
-    /// Get a connection to Redis
-    fn get_connection(&self) -> Result<Connection> {
-        let conn = self.client.get_connection()?;
-        Ok(conn)
+    /// A connection from the pool
+    async fn connection(&self) -> Result<Connection> {
+        self.pool.get().await.context("Failed to get a Redis connection")
     }
-    
-    pub fn set_with_expiry(&self, key: &str, value: &str, expiry_secs: u64) -> Result<()> {
-        let mut conn = self.get_connection()?;
-        conn.set_ex::<_, _, ()>(key, value, expiry_secs as usize)?;
-        Ok(())
+
+    pub async fn set_with_expiry(&self, key: &str, value: &str, expiry_secs: u64) -> Result<()> {
+        let mut conn = self.connection().await?;
+        conn.set_ex::<_, _, ()>(key, value, expiry_secs as usize)
+            .await
+            .with_context(|| format!("Failed to set Redis key {}", key))
     }
-    
-    pub fn get(&self, key: &str) -> Result<Option<String>> {
-        let mut conn = self.get_connection()?;
-        let result: redis::RedisResult<String> = conn.get(key);
-        match result {
-            Ok(value) => Ok(Some(value)),
-            Err(_) => Ok(None),
-        }
+
+    /// The value at `key`, or `None` when it is not set or has expired
+    pub async fn get_value(&self, key: &str) -> Result<Option<String>> {
+        let mut conn = self.connection().await?;
+        conn.get::<_, Option<String>>(key)
+            .await
+            .with_context(|| format!("Failed to get Redis key {}", key))
     }
-    
-    pub fn get_value(&self, key: &str) -> Result<Option<String>> {
-        self.get(key)
+
+    /// Remove keys; removing ones that are not set is not an error
+    pub async fn delete(&self, keys: &[String]) -> Result<()> {
+        let mut conn = self.connection().await?;
+        conn.del::<_, ()>(keys)
+            .await
+            .with_context(|| format!("Failed to delete Redis keys {}", keys.join(", ")))
     }
-    
-    pub fn delete(&self, key: &str) -> Result<()> {
-        let mut conn = self.get_connection()?;
-        let _: () = conn.del(key)?;
-        Ok(())
+
+    /// Send `message` to the subscribers of `channel`; with none listening it is dropped
+    pub async fn publish(&self, channel: &str, message: &str) -> Result<()> {
+        let mut conn = self.connection().await?;
+        conn.publish::<_, _, ()>(channel, message)
+            .await
+            .with_context(|| format!("Failed to publish to Redis channel {}", channel))
     }
 
-    /// Cache insights for a job
-    pub fn cache_insights(&self, job_id: Uuid, insights: &Insights) -> Result<()> {
-        let key = format!("insights:{}", job_id);
-        let serialized = serde_json::to_string(insights)
-            .context("Failed to serialize insights")?;
-        
-        self.set_with_expiry(&key, &serialized, 3600 * 24)?;
-        
-        Ok(())
+    /// Set `key` to `value` for `ttl` unless it is already set, returning whether it was set
+    pub async fn set_if_absent(&self, key: &str, value: &str, ttl: Duration) -> Result<bool> {
+        let mut conn = self.connection().await?;
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await
+            .with_context(|| format!("Failed to set Redis key {}", key))?;
+        Ok(reply.is_some())
     }
 
-    /// Get cached insights for a job
-    pub fn get_insights(&self, job_id: Uuid) -> Result<Option<Insights>> {
-        let key = format!("insights:{}", job_id);
-        
-        if let Some(serialized) = self.get(&key)? {
-            let insights: Insights = serde_json::from_str(&serialized)
-                .context("Failed to deserialize insights")?;
-            Ok(Some(insights))
-        } else {
-            Ok(None)
-        }
+    /// Key count from `DBSIZE` and the server's counters from `INFO`
+    pub async fn stats(&self) -> Result<CacheStats> {
+        let mut conn = self.connection().await?;
+        let entries: u64 = redis::cmd("DBSIZE")
+            .query_async(&mut conn)
+            .await
+            .context("Failed to count Redis keys")?;
+        let info: String = redis::cmd("INFO")
+            .query_async(&mut conn)
+            .await
+            .context("Failed to read Redis INFO")?;
+        let field = |name: &str| {
+            info.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+        let (hits, misses) = (field("keyspace_hits"), field("keyspace_misses"));
+        Ok(CacheStats {
+            backend: "redis".to_string(),
+            entries,
+            bytes: field("used_memory"),
+            max_entries: None,
+            max_bytes: None,
+            hits,
+            misses,
+            evictions: field("evicted_keys"),
+            hit_ratio: CacheStats::ratio(hits, misses),
+        })
     }
 
-    /// Cache chart URL for a job
-    pub fn cache_chart_url(&self, job_id: Uuid, chart_url: &str) -> Result<()> {
-        let key = format!("chart_url:{}", job_id);
-        
-        self.set_with_expiry(&key, chart_url, 3600 * 24)?;
-        
+    /// Remove `key` only while it still holds `value`, checked and deleted in one
+    /// step so a key that expired and was set by someone else is left alone
+    pub async fn delete_if_equal(&self, key: &str, value: &str) -> Result<()> {
+        let mut conn = self.connection().await?;
+        redis::Script::new(
+            "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end",
+        )
+        .key(key)
+        .arg(value)
+        .invoke_async::<_, i64>(&mut conn)
+        .await
+        .with_context(|| format!("Failed to delete Redis key {}", key))?;
         Ok(())
     }
 
-    /// Get cached chart URL for a job
-    pub fn get_chart_url(&self, job_id: Uuid) -> Result<Option<String>> {
-        let key = format!("chart_url:{}", job_id);
-        self.get(&key)
+    /// Set `key` to expire `ttl` from now if it still holds `value`, returning whether it does
+    pub async fn expire_if_equal(&self, key: &str, value: &str, ttl: Duration) -> Result<bool> {
+        let mut conn = self.connection().await?;
+        let renewed: i64 = redis::Script::new(
+            "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('PEXPIRE', KEYS[1], ARGV[2]) else return 0 end",
+        )
+        .key(key)
+        .arg(value)
+        .arg(ttl.as_millis() as u64)
+        .invoke_async(&mut conn)
+        .await
+        .with_context(|| format!("Failed to extend Redis key {}", key))?;
+        Ok(renewed == 1)
+    }
+
+    /// Add `amount` to the counter at `key` unless that takes it past `limit`, in one
+    /// script so concurrent reservations can't both pass; the key keeps its expiry
+    pub async fn reserve_counter(&self, key: &str, amount: u64, limit: u64) -> Result<UsageReservation> {
+        let mut conn = self.connection().await?;
+        let (reserved, used): (i64, u64) = redis::Script::new(
+            "local used = redis.call('GET', KEYS[1])
+             if not used then return {-1, 0} end
+             used = tonumber(used)
+             if used + tonumber(ARGV[1]) > tonumber(ARGV[2]) then return {0, used} end
+             return {1, redis.call('INCRBY', KEYS[1], ARGV[1])}",
+        )
+        .key(key)
+        .arg(amount)
+        .arg(limit)
+        .invoke_async(&mut conn)
+        .await
+        .with_context(|| format!("Failed to reserve against Redis counter {}", key))?;
+        Ok(match reserved {
+            1 => UsageReservation::Reserved { used },
+            0 => UsageReservation::Exceeded { used },
+            _ => UsageReservation::Unset,
+        })
+    }
+
+    /// Take `amount` off the counter at `key` if it is set, never below zero
+    pub async fn release_counter(&self, key: &str, amount: u64) -> Result<()> {
+        let mut conn = self.connection().await?;
+        redis::Script::new(
+            "if redis.call('EXISTS', KEYS[1]) == 0 then return 0 end
+             local used = redis.call('DECRBY', KEYS[1], ARGV[1])
+             if used < 0 then redis.call('INCRBY', KEYS[1], -used) end
+             return 0",
+        )
+        .key(key)
+        .arg(amount)
+        .invoke_async::<_, i64>(&mut conn)
+        .await
+        .with_context(|| format!("Failed to release Redis counter {}", key))?;
+        Ok(())
     }
 }
@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+
+use crate::config::Config;
+use crate::services::cache::CacheStore;
+use crate::services::job_store::JobStore;
+use crate::services::storage::StorageService;
+
+/// The storage, job database and cache backends chosen by `STORAGE_BACKEND`,
+/// `DATABASE_BACKEND` and `CACHE_BACKEND`
+#[derive(Clone, Debug)]
+pub struct ServiceRegistry {
+    pub storage: StorageService,
+    pub jobs: JobStore,
+    pub cache: CacheStore,
+}
+
+impl ServiceRegistry {
+    pub fn builder(config: &Config) -> ServiceRegistryBuilder<'_> {
+        ServiceRegistryBuilder {
+            config,
+            migrate: config.database_auto_migrate,
+        }
+    }
+}
+
+/// Connects each backend selected in the config
+pub struct ServiceRegistryBuilder<'a> {
+    config: &'a Config,
+    migrate: bool,
+}
+
+impl ServiceRegistryBuilder<'_> {
+    /// Whether to bring the job database schema up to date; `DATABASE_AUTO_MIGRATE` by default
+    pub fn migrate(mut self, migrate: bool) -> Self {
+        self.migrate = migrate;
+        self
+    }
+
+    pub async fn build(self) -> Result<ServiceRegistry> {
+        let config = self.config;
+
+        let storage = StorageService::from_config(config).context("Failed to initialize storage")?;
+        log::info!("🗄️ Using the {} storage backend", config.storage_backend);
+
+        let jobs = JobStore::from_config(config)
+            .await
+            .context("Failed to initialize the job database")?;
+        log::info!("💾 Using the {} job database", config.database_backend);
+        if self.migrate {
+            jobs.migrate().await?;
+            log::info!("📜 Job database schema is up to date");
+        }
+
+        let cache = CacheStore::from_config(config).await.context("Failed to initialize the cache")?;
+        log::info!("🧰 Using the {} cache", config.cache_backend);
+
+        Ok(ServiceRegistry { storage, jobs, cache })
+    }
+}
@@ -0,0 +1,648 @@
+use log::info;
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::models::conversation::DatasetMetadata;
+use crate::services::query_translator::{
+    aggregation_name, parse_number, ColumnOperation, QueryIntent, StructuredQuery,
+};
+
+/// A column named in the question, by byte range in the lowercased text
+#[derive(Debug, Clone)]
+struct Mention {
+    column: String,
+    start: usize,
+    end: usize,
+}
+
+/// An aggregation keyword and where it ends, e.g. "average" or "how many"
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Aggregation {
+    Mean,
+    Sum,
+    Count,
+    DistinctCount,
+    Median,
+    Std,
+    Min,
+    Max,
+}
+
+/// A window phrase: "rolling average", "moving sum", "running total"
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WindowKind {
+    Mean,
+    Sum,
+    CumSum,
+}
+
+/// Rows a rolling window spans when the question gives no size
+const DEFAULT_ROLLING_ROWS: usize = 7;
+
+struct RulePatterns {
+    aggregation: Regex,
+    group_by: Regex,
+    ranked_by: Regex,
+    top_n: Regex,
+    sort_by: Regex,
+    limit: Regex,
+    descending: Regex,
+    visualize: Regex,
+    comparison: Regex,
+    date_comparison: Regex,
+    rolling: Regex,
+    cumulative: Regex,
+    follow_up: Regex,
+    filter_by: Regex,
+    bare_value: Regex,
+}
+
+fn patterns() -> &'static RulePatterns {
+    static PATTERNS: OnceLock<RulePatterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| RulePatterns {
+        aggregation: Regex::new(
+            r"\b(average|mean|avg|total|sum|how many distinct|how many unique|number of distinct|number of unique|distinct|unique|count|how many|number of|median|standard deviation|std|stddev|minimum|min|maximum|max)\b",
+        )
+        .unwrap(),
+        group_by: Regex::new(r"\b(by|per|for each|each|across)\s+(?:the\s+)?$").unwrap(),
+        ranked_by: Regex::new(r"\bby\s+(?:the\s+)?$").unwrap(),
+        top_n: Regex::new(r"\b(top|highest|largest|biggest|best|bottom|lowest|smallest|worst)\s+(\d+)\b").unwrap(),
+        sort_by: Regex::new(r"\b(?:sort|sorted|order|ordered|rank|ranked)\s+(?:them\s+|it\s+)?by\s+(?:the\s+)?$").unwrap(),
+        limit: Regex::new(r"\b(?:first|limit(?:\s+to)?|only)\s+(\d+)\b").unwrap(),
+        descending: Regex::new(r"\b(desc|descending|highest first|largest first|biggest first|decreasing)\b").unwrap(),
+        visualize: Regex::new(r"\b(chart|plot|graph|visuali[sz]e|histogram)\b").unwrap(),
+        // Matched right after a column mention: "revenue is over $1,000", "date after 2024-01-01"
+        comparison: Regex::new(
+            r#"^\s*(?:is\s+|was\s+|are\s+|were\s+)?(greater than or equal to|less than or equal to|greater than|more than|higher than|larger than|bigger than|exceeding|exceeds|exceed|over|above|at least|no less than|>=|less than|lower than|smaller than|fewer than|under|below|at most|no more than|<=|>|<|not equal to|!=|<>|equal to|equals|==|=|after|since|before|until|containing|contains|starts with|ends with|is)\s+([$€£]?-?\d[\d,]*(?:\.\d+)?(?:\s?(?:thousand|million|billion|bn|k|m|b)\b|%)?|\d{4}-\d{2}(?:-\d{2})?|'[^']*'|"[^"]*"|[\p{L}\d_][\p{L}\d_.@-]*)"#,
+        )
+        .unwrap(),
+        // "orders after 2024-02-01" without naming the date column
+        date_comparison: Regex::new(r"\b(after|since|before|until)\s+(\d{4}-\d{2}(?:-\d{2})?)\b").unwrap(),
+        // "7-day rolling average", "moving sum", "3 month moving average"
+        rolling: Regex::new(
+            r"\b(?:(\d+)[- ]?(hour|day|week|month|quarter|year|row|period)s?\s+)?(?:rolling|moving)\s+(average|mean|avg|sum|total)\b",
+        )
+        .unwrap(),
+        cumulative: Regex::new(r"\b(?:cumulative|running)\s+(?:sum|total)\b").unwrap(),
+        // "and only for the north", "filter that by region", "same but by month"
+        follow_up: Regex::new(
+            r"^\s*(?:and|now|also|then|but|ok|okay|what about|how about|instead|just|only|same)\b|\b(?:filter|narrow|limit|restrict|sort|order|rank|group|break|split|show|plot|chart)\s+(?:that|those|these|them|it)\b|\b(?:of|for|from|in)\s+(?:that|those|these|them)\b|\b(?:the same|instead|as well)\b",
+        )
+        .unwrap(),
+        // "filter that by region north": the column and value follow
+        filter_by: Regex::new(r"\b(?:filter|narrow|restrict)(?:\s+(?:that|those|these|them|it|down|results?))?\s+(?:by|to|on|for)\s+(?:the\s+)?$").unwrap(),
+        bare_value: Regex::new(r"^\s*(?:=|:)?\s*([\p{L}\d_][\p{L}\d_.@-]*)").unwrap(),
+    })
+}
+
+/// Translate a question into a structured query without a language model. Column
+/// names mentioned in the text are matched against the dataset, then comparison
+/// phrases ("revenue over 100") become filters, "by region"/"per region" become
+/// groups, aggregation words pick the aggregation and "top 5"/"sorted by" pick the
+/// ordering. A question naming nothing recognizable describes the dataset.
+pub fn translate(query: &str, metadata: &DatasetMetadata) -> StructuredQuery {
+    let text = query.to_lowercase();
+    let p = patterns();
+    let mentions = find_mentions(&text, &metadata.columns);
+    let is_numeric = |column: &str| {
+        metadata
+            .data_types
+            .get(column)
+            .is_some_and(|t| matches!(t.as_str(), "integer" | "unsigned integer" | "float"))
+    };
+    let is_date = |column: &str| {
+        metadata
+            .data_types
+            .get(column)
+            .is_some_and(|t| matches!(t.as_str(), "date" | "datetime"))
+    };
+
+    // Filters: a mention followed by a comparison phrase and a value
+    let mut filters = Vec::new();
+    let mut filtered_mentions = Vec::new();
+    for (i, mention) in mentions.iter().enumerate() {
+        if let Some(captures) = p.comparison.captures(&text[mention.end..]) {
+            let phrase = captures[1].trim();
+            let value = captures[2].trim_end_matches('.').trim_matches(|c| c == '\'' || c == '"');
+            if let Some((operator, value)) = comparison(phrase, value, is_numeric(&mention.column), is_date(&mention.column)) {
+                filters.push(ColumnOperation::Filter(mention.column.clone(), operator.to_string(), value));
+                filtered_mentions.push(i);
+            }
+        }
+    }
+    let date_column = metadata.columns.iter().find(|c| is_date(c));
+    if let Some(date_column) = date_column {
+        for captures in p.date_comparison.captures_iter(&text) {
+            let named = filtered_mentions.iter().any(|i| {
+                let mention = &mentions[*i];
+                mention.column == *date_column && mention.end <= captures.get(0).unwrap().start()
+            });
+            if !named {
+                if let Some((operator, value)) = comparison(&captures[1], &captures[2], false, true) {
+                    filters.push(ColumnOperation::Filter(date_column.clone(), operator.to_string(), value));
+                }
+            }
+        }
+    }
+
+    // "top 5 ..." / "lowest 3 ..."
+    let top_n = p.top_n.captures(&text).map(|captures| {
+        let whole = captures.get(0).unwrap();
+        let ascending = matches!(&captures[1], "bottom" | "lowest" | "smallest" | "worst");
+        let n: usize = captures[2].parse().unwrap_or(10);
+        (whole.end(), n, ascending)
+    });
+
+    // "sorted by X", which also keeps "by X" from being read as a group
+    let mut sort_mentions = Vec::new();
+    for (i, mention) in mentions.iter().enumerate() {
+        if p.sort_by.is_match(&text[..mention.start]) {
+            sort_mentions.push(i);
+        }
+    }
+
+    // "by X" after "top N" names the ranking column rather than a group
+    let ranked_by = top_n.and_then(|(top_end, _, _)| {
+        mentions
+            .iter()
+            .position(|m| m.start > top_end && p.ranked_by.is_match(&text[..m.start]))
+    });
+
+    // "7-day rolling average of signups" / "running total of revenue": the
+    // window's own "average"/"total" is not an aggregation
+    let windows: Vec<(WindowKind, Option<String>, usize, usize)> = p
+        .rolling
+        .captures_iter(&text)
+        .map(|captures| {
+            let whole = captures.get(0).unwrap();
+            let kind = if matches!(&captures[3], "sum" | "total") { WindowKind::Sum } else { WindowKind::Mean };
+            let unit = captures.get(2).map(|u| match u.as_str() {
+                "hour" => "h",
+                "day" => "d",
+                "week" => "w",
+                "month" => "mo",
+                "quarter" => "q",
+                "year" => "y",
+                _ => "",
+            });
+            let window = captures.get(1).map(|n| format!("{}{}", n.as_str(), unit.unwrap_or("")));
+            (kind, window, whole.start(), whole.end())
+        })
+        .chain(p.cumulative.find_iter(&text).map(|m| (WindowKind::CumSum, None, m.start(), m.end())))
+        .collect();
+
+    // Aggregations, each applied to the closest column after its keyword
+    let mut aggregations: Vec<(Aggregation, usize)> = Vec::new();
+    for m in p.aggregation.find_iter(&text) {
+        if windows.iter().any(|(_, _, start, end)| m.start() >= *start && m.end() <= *end) {
+            continue;
+        }
+        let aggregation = match m.as_str() {
+            "average" | "mean" | "avg" => Aggregation::Mean,
+            "total" | "sum" => Aggregation::Sum,
+            "count" | "how many" | "number of" => Aggregation::Count,
+            "distinct" | "unique" | "how many distinct" | "how many unique" | "number of distinct" | "number of unique" => {
+                Aggregation::DistinctCount
+            }
+            "median" => Aggregation::Median,
+            "standard deviation" | "std" | "stddev" => Aggregation::Std,
+            "minimum" | "min" => Aggregation::Min,
+            _ => Aggregation::Max,
+        };
+        aggregations.push((aggregation, m.end()));
+    }
+
+    // Groups: a mention directly after "by", "per", "for each", "each" or "across"
+    let mut groups: Vec<String> = Vec::new();
+    for (i, mention) in mentions.iter().enumerate() {
+        if sort_mentions.contains(&i) || filtered_mentions.contains(&i) || ranked_by == Some(i) {
+            continue;
+        }
+        if p.group_by.is_match(&text[..mention.start]) && !groups.contains(&mention.column) {
+            groups.push(mention.column.clone());
+        }
+    }
+    // "top 5 regions by total revenue": the ranked things are the groups
+    if let (Some((top_end, _, _)), false) = (top_n, aggregations.is_empty()) {
+        if groups.is_empty() {
+            if let Some(mention) = mentions.iter().find(|m| m.start >= top_end && !is_numeric(&m.column)) {
+                groups.push(mention.column.clone());
+            }
+        }
+    }
+
+    let mut operations: Vec<ColumnOperation> = filters;
+    let mut columns: Vec<String> = Vec::new();
+    let first_numeric = metadata.columns.iter().find(|c| is_numeric(c)).cloned();
+
+    let mut aggregated_columns = Vec::new();
+    for (aggregation, keyword_end) in &aggregations {
+        let wants_number = !matches!(aggregation, Aggregation::Count | Aggregation::DistinctCount);
+        let candidate = |m: &&Mention| !groups.contains(&m.column) && (!wants_number || is_numeric(&m.column));
+        let column = mentions
+            .iter()
+            .filter(|m| m.start >= *keyword_end)
+            .find(candidate)
+            .or_else(|| mentions.iter().rev().filter(|m| m.end <= *keyword_end).find(candidate))
+            .map(|m| m.column.clone())
+            .or_else(|| if wants_number { first_numeric.clone() } else { metadata.columns.first().cloned() });
+        let Some(column) = column else { continue };
+        let (operation, result_name) = match aggregation {
+            Aggregation::Mean => (ColumnOperation::Mean(column.clone()), format!("mean_{}", column)),
+            Aggregation::Sum => (ColumnOperation::Sum(column.clone()), format!("sum_{}", column)),
+            Aggregation::Count => (ColumnOperation::Count(column.clone()), format!("count_{}", column)),
+            Aggregation::DistinctCount => {
+                (ColumnOperation::DistinctCount(column.clone()), format!("distinct_count_{}", column))
+            }
+            Aggregation::Median => (ColumnOperation::Median(column.clone()), format!("median_{}", column)),
+            Aggregation::Std => (ColumnOperation::Std(column.clone()), format!("std_{}", column)),
+            Aggregation::Min => (ColumnOperation::Min(column.clone()), format!("min_{}", column)),
+            Aggregation::Max => (ColumnOperation::Max(column.clone()), format!("max_{}", column)),
+        };
+        // "how many distinct" also matches "distinct"; keep one operation per result
+        if aggregated_columns.contains(&result_name) {
+            continue;
+        }
+        // "how many unique customers" is a distinct count, not a count
+        if *aggregation == Aggregation::DistinctCount {
+            operations.retain(|op| !matches!(op, ColumnOperation::Count(c) if *c == column));
+            aggregated_columns.retain(|name| *name != format!("count_{}", column));
+        } else if *aggregation == Aggregation::Count && aggregated_columns.contains(&format!("distinct_count_{}", column)) {
+            continue;
+        }
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+        aggregated_columns.push(result_name);
+        operations.push(operation);
+    }
+
+    for group in &groups {
+        operations.push(ColumnOperation::GroupBy(group.clone()));
+    }
+
+    let descending = p.descending.is_match(&text);
+    for i in &sort_mentions {
+        operations.push(ColumnOperation::SortBy(vec![(mentions[*i].column.clone(), !descending)], false));
+    }
+
+    // Windows over the closest numeric column after the phrase, in date order;
+    // a time window needs a date column, so without one it counts rows
+    let date_column = date_column.cloned();
+    for (kind, window, _, end) in &windows {
+        let Some(column) = mentions
+            .iter()
+            .find(|m| m.start >= *end && is_numeric(&m.column))
+            .map(|m| m.column.clone())
+            .or_else(|| first_numeric.clone())
+        else {
+            continue;
+        };
+        // Over aggregated rows the window runs on the column's aggregate
+        let column = aggregated_columns
+            .iter()
+            .find(|name| name.ends_with(&format!("_{}", column)))
+            .cloned()
+            .unwrap_or(column);
+        let window = match window {
+            Some(w) if date_column.is_none() => w.trim_end_matches(|c: char| c.is_ascii_alphabetic()).to_string(),
+            Some(w) => w.clone(),
+            None => DEFAULT_ROLLING_ROWS.to_string(),
+        };
+        operations.push(match kind {
+            WindowKind::Mean => ColumnOperation::RollingMean(column, window, date_column.clone()),
+            WindowKind::Sum => ColumnOperation::RollingSum(column, window, date_column.clone()),
+            WindowKind::CumSum => ColumnOperation::CumSum(column, date_column.clone()),
+        });
+    }
+
+    // "plot revenue by date" charts the rows rather than aggregating them
+    let visualize = p.visualize.is_match(&text) && aggregated_columns.is_empty();
+    if visualize {
+        operations.retain(|op| !matches!(op, ColumnOperation::GroupBy(_)));
+    }
+    let aggregating = !visualize && (!aggregated_columns.is_empty() || !groups.is_empty());
+    if let Some((top_end, n, ascending)) = top_n {
+        let column = if aggregating {
+            // Rank the groups by their first aggregate, or by their size
+            aggregated_columns.first().cloned().or_else(|| groups.first().map(|g| format!("count_{}", g)))
+        } else {
+            ranked_by
+                .map(|i| mentions[i].column.clone())
+                .or_else(|| {
+                    mentions
+                        .iter()
+                        .find(|m| m.start >= top_end && is_numeric(&m.column))
+                        .or_else(|| mentions.iter().find(|m| is_numeric(&m.column)))
+                        .map(|m| m.column.clone())
+                })
+        };
+        if let Some(column) = column {
+            operations.push(ColumnOperation::TopN(column, n, ascending));
+        }
+    } else if let Some(captures) = p.limit.captures(&text) {
+        if let Ok(n) = captures[1].parse() {
+            operations.push(ColumnOperation::Limit(n));
+        }
+    }
+
+    let has = |f: fn(&ColumnOperation) -> bool| operations.iter().any(f);
+    let intent = if visualize {
+        QueryIntent::Visualize
+    } else if aggregating {
+        QueryIntent::Aggregate
+    } else if has(|op| matches!(op, ColumnOperation::SortBy(..) | ColumnOperation::TopN(..))) {
+        QueryIntent::Sort
+    } else if has(|op| matches!(op, ColumnOperation::Filter(..))) || !windows.is_empty() {
+        QueryIntent::Filter
+    } else {
+        QueryIntent::Describe
+    };
+
+    let columns = match intent {
+        QueryIntent::Aggregate => {
+            let mut all = groups.clone();
+            all.extend(columns.into_iter().filter(|c| !groups.contains(c)));
+            all
+        }
+        // Charts plot the columns the question names, "by" columns first as the x axis
+        QueryIntent::Visualize => {
+            let mut named: Vec<String> = groups.clone();
+            for mention in &mentions {
+                if !named.contains(&mention.column) {
+                    named.push(mention.column.clone());
+                }
+            }
+            named
+        }
+        QueryIntent::Describe => metadata.columns.clone(),
+        // Filtered and sorted rows keep every column
+        _ => Vec::new(),
+    };
+
+    let structured_query = StructuredQuery {
+        intent,
+        columns,
+        operations,
+    };
+    info!("Rule-based translation of '{}': {:?}", query, structured_query);
+    structured_query
+}
+
+/// Refine the previous query with a follow-up question ("only for the north",
+/// "now by month", "show the median instead"); `None` when the question does not
+/// refer back to it and should be translated on its own. Filters on a column
+/// replace earlier ones on it, groups and windows are added, new ordering replaces
+/// the old, and an aggregation word without a column changes the aggregation of
+/// every previously aggregated column.
+pub fn refine(query: &str, previous: &StructuredQuery, metadata: &DatasetMetadata) -> Option<StructuredQuery> {
+    let text = query.to_lowercase();
+    let p = patterns();
+    if matches!(previous.intent, QueryIntent::Describe) || !p.follow_up.is_match(&text) {
+        return None;
+    }
+    let mut update = translate(query, metadata);
+    let mentions = find_mentions(&text, &metadata.columns);
+
+    // "filter that by region north" filters rather than groups
+    for mention in &mentions {
+        if !p.filter_by.is_match(&text[..mention.start]) {
+            continue;
+        }
+        let Some(value) = p.bare_value.captures(&text[mention.end..]).map(|c| c[1].to_string()) else {
+            continue;
+        };
+        if NOT_VALUES.contains(&value.as_str()) || mentions.iter().any(|m| m.start > mention.end && m.column == value) {
+            continue;
+        }
+        update.operations.retain(|op| {
+            !matches!(op, ColumnOperation::GroupBy(c) | ColumnOperation::Filter(c, ..) if *c == mention.column)
+        });
+        update.operations.push(ColumnOperation::Filter(mention.column.clone(), "=".to_string(), value));
+    }
+    let names_numeric = mentions.iter().any(|m| {
+        metadata
+            .data_types
+            .get(&m.column)
+            .is_some_and(|t| matches!(t.as_str(), "integer" | "unsigned integer" | "float"))
+    });
+
+    // "now the top 3": rank by what the previous query aggregated or ranked by
+    let ranks = update.operations.iter().any(|op| matches!(op, ColumnOperation::TopN(..)));
+    if let (false, Some(captures)) = (ranks, p.top_n.captures(&text)) {
+        let ranked_by = previous
+            .operations
+            .iter()
+            .find_map(|op| match op {
+                ColumnOperation::TopN(column, ..) => Some(column.clone()),
+                ColumnOperation::SortBy(keys, _) => keys.first().map(|(column, _)| column.clone()),
+                _ => None,
+            })
+            .or_else(|| previous.operations.iter().find_map(aggregation_name));
+        if let (Some(column), Ok(n)) = (ranked_by, captures[2].parse()) {
+            let ascending = matches!(&captures[1], "bottom" | "lowest" | "smallest" | "worst");
+            update.operations.push(ColumnOperation::TopN(column, n, ascending));
+        }
+    }
+
+    let mut operations = previous.operations.clone();
+    let mut columns = previous.columns.clone();
+    let mut changed = false;
+    for op in &update.operations {
+        match op {
+            ColumnOperation::Filter(column, ..) => {
+                operations.retain(|existing| !matches!(existing, ColumnOperation::Filter(c, ..) if c == column));
+                operations.push(op.clone());
+            }
+            ColumnOperation::GroupBy(column) => {
+                if !operations.iter().any(|existing| matches!(existing, ColumnOperation::GroupBy(c) if c == column)) {
+                    operations.push(op.clone());
+                }
+                // Groups lead the columns, in the order they were added
+                if !columns.contains(column) {
+                    let groups = columns
+                        .iter()
+                        .take_while(|c| operations.iter().any(|op| matches!(op, ColumnOperation::GroupBy(g) if g == *c)))
+                        .count();
+                    columns.insert(groups, column.clone());
+                }
+            }
+            ColumnOperation::SortBy(..) | ColumnOperation::TopN(..) | ColumnOperation::Limit(_) => {
+                operations.retain(|existing| {
+                    !matches!(existing, ColumnOperation::SortBy(..) | ColumnOperation::TopN(..) | ColumnOperation::Limit(_))
+                });
+                operations.push(op.clone());
+            }
+            op if aggregation_name(op).is_some() => {
+                let column = aggregated_column(op)?;
+                if names_numeric || !operations.iter().any(|existing| aggregation_name(existing).is_some()) {
+                    operations.retain(|existing| aggregated_column(existing) != Some(column));
+                    operations.push(op.clone());
+                    if !columns.contains(column) {
+                        columns.push(column.clone());
+                    }
+                } else {
+                    // "the median instead": same columns, new aggregation
+                    operations = operations
+                        .into_iter()
+                        .map(|existing| match aggregated_column(&existing) {
+                            Some(c) => with_column(op, c.clone()),
+                            None => existing,
+                        })
+                        .collect();
+                }
+            }
+            _ => operations.push(op.clone()),
+        }
+        changed = true;
+    }
+    let aggregating = operations
+        .iter()
+        .any(|op| aggregation_name(op).is_some() || matches!(op, ColumnOperation::GroupBy(_)));
+    if !changed && !matches!(update.intent, QueryIntent::Visualize) {
+        return None;
+    }
+
+    // Rankings over aggregated rows rank by an aggregate, not a raw column
+    let results: Vec<String> = operations.iter().filter_map(aggregation_name).collect();
+    if let (true, Some(first)) = (aggregating, results.first()) {
+        for op in operations.iter_mut() {
+            if let ColumnOperation::TopN(column, ..) = op {
+                if !results.contains(column) {
+                    *column = results
+                        .iter()
+                        .find(|r| r.ends_with(&format!("_{}", column)))
+                        .unwrap_or(first)
+                        .clone();
+                }
+            }
+        }
+    }
+
+    let intent = match (&update.intent, &previous.intent) {
+        (_, QueryIntent::Pivot) => QueryIntent::Pivot,
+        _ if aggregating => QueryIntent::Aggregate,
+        (QueryIntent::Visualize, _) => QueryIntent::Visualize,
+        (_, QueryIntent::Describe) => update.intent.clone(),
+        (_, intent) => intent.clone(),
+    };
+    let structured_query = StructuredQuery {
+        intent,
+        columns,
+        operations,
+    };
+    info!("Rule-based refinement of '{}': {:?}", query, structured_query);
+    Some(structured_query)
+}
+
+/// Column an aggregation operation aggregates
+fn aggregated_column(op: &ColumnOperation) -> Option<&String> {
+    match op {
+        ColumnOperation::Mean(c)
+        | ColumnOperation::Sum(c)
+        | ColumnOperation::Count(c)
+        | ColumnOperation::DistinctCount(c)
+        | ColumnOperation::Median(c)
+        | ColumnOperation::Std(c)
+        | ColumnOperation::Min(c)
+        | ColumnOperation::Max(c)
+        | ColumnOperation::Quantile(c, _) => Some(c),
+        _ => None,
+    }
+}
+
+/// The aggregation `op` performs, applied to `column`
+fn with_column(op: &ColumnOperation, column: String) -> ColumnOperation {
+    match op {
+        ColumnOperation::Mean(_) => ColumnOperation::Mean(column),
+        ColumnOperation::Sum(_) => ColumnOperation::Sum(column),
+        ColumnOperation::Count(_) => ColumnOperation::Count(column),
+        ColumnOperation::DistinctCount(_) => ColumnOperation::DistinctCount(column),
+        ColumnOperation::Median(_) => ColumnOperation::Median(column),
+        ColumnOperation::Std(_) => ColumnOperation::Std(column),
+        ColumnOperation::Min(_) => ColumnOperation::Min(column),
+        ColumnOperation::Max(_) => ColumnOperation::Max(column),
+        ColumnOperation::Quantile(_, q) => ColumnOperation::Quantile(column, *q),
+        other => other.clone(),
+    }
+}
+
+/// Words after "is" that describe rather than compare: "which region is best"
+const NOT_VALUES: &[&str] = &[
+    "the", "a", "an", "best", "worst", "highest", "lowest", "most", "least", "largest", "smallest", "biggest", "top",
+    "missing", "null", "empty", "used", "there",
+];
+
+/// Operator and value of a comparison phrase, `None` when the phrase does not
+/// compare this kind of column (e.g. "is north" against a numeric column)
+fn comparison(phrase: &str, value: &str, numeric: bool, date: bool) -> Option<(&'static str, String)> {
+    let number = parse_number(value).map(|_| value.to_string());
+    let is_date_value = value.len() >= 7 && value.as_bytes()[4] == b'-' && value[..4].chars().all(|c| c.is_ascii_digit());
+    let operator = match phrase {
+        "greater than" | "more than" | "higher than" | "larger than" | "bigger than" | "exceeding" | "exceeds"
+        | "exceed" | "over" | "above" | ">" => ">",
+        "greater than or equal to" | "at least" | "no less than" | ">=" => ">=",
+        "less than" | "lower than" | "smaller than" | "fewer than" | "under" | "below" | "<" => "<",
+        "less than or equal to" | "at most" | "no more than" | "<=" => "<=",
+        "not equal to" | "!=" | "<>" => "!=",
+        "is" if NOT_VALUES.contains(&value) => return None,
+        "equal to" | "equals" | "==" | "=" | "is" => "=",
+        "after" => ">",
+        "since" => ">=",
+        "before" | "until" => "<",
+        "containing" | "contains" => return (!numeric && !date).then(|| ("contains", value.to_string())),
+        "starts with" => return (!numeric && !date).then(|| ("starts_with", value.to_string())),
+        "ends with" => return (!numeric && !date).then(|| ("ends_with", value.to_string())),
+        _ => return None,
+    };
+
+    if date {
+        return is_date_value.then(|| (operator, value.to_string()));
+    }
+    if matches!(phrase, "after" | "since" | "before" | "until") {
+        return None;
+    }
+    match number {
+        Some(number) => Some((operator, number)),
+        // Text only compares for (in)equality: "region is north"
+        None if !numeric && matches!(operator, "=" | "!=") => Some((operator, value.to_string())),
+        None => None,
+    }
+}
+
+/// Columns mentioned in the text, longest names first so `unit price` wins over
+/// `unit`; spaces in the text may stand for underscores or hyphens in the name,
+/// and a trailing plural `s` is allowed ("regions" → `region`)
+fn find_mentions(text: &str, columns: &[String]) -> Vec<Mention> {
+    let mut candidates: Vec<Mention> = Vec::new();
+    for column in columns {
+        let words: Vec<String> = column
+            .to_lowercase()
+            .split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+            .filter(|w| !w.is_empty())
+            .map(regex::escape)
+            .collect();
+        if words.is_empty() {
+            continue;
+        }
+        let pattern = format!(r"\b{}(?:s|es)?\b", words.join(r"[\s_-]+"));
+        let Ok(re) = Regex::new(&pattern) else { continue };
+        for m in re.find_iter(text) {
+            candidates.push(Mention {
+                column: column.clone(),
+                start: m.start(),
+                end: m.end(),
+            });
+        }
+    }
+
+    candidates.sort_by(|a, b| (b.end - b.start).cmp(&(a.end - a.start)).then(a.start.cmp(&b.start)));
+    let mut mentions: Vec<Mention> = Vec::new();
+    for candidate in candidates {
+        if mentions.iter().all(|m| candidate.end <= m.start || candidate.start >= m.end) {
+            mentions.push(candidate);
+        }
+    }
+    mentions.sort_by_key(|m| m.start);
+    mentions
+}
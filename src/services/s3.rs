@@ -1,20 +1,86 @@
-use anyhow::{Result, Context};
 #[cfg(feature = "external-services")]
-use rusoto_core::Region;
+use anyhow::{anyhow, bail, Context, Result};
+#[cfg(feature = "external-services")]
+use bytes::Bytes;
+#[cfg(feature = "external-services")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "external-services")]
+use rand::Rng;
+#[cfg(feature = "external-services")]
+use rusoto_core::credential::{
+    AwsCredentials, CredentialsError, DefaultCredentialsProvider, ProvideAwsCredentials, StaticProvider,
+};
+#[cfg(feature = "external-services")]
+use rusoto_core::{ByteStream, HttpClient, Region, RusotoError};
 #[cfg(feature = "external-services")]
 use rusoto_s3::{
-    GetObjectRequest, PutObjectRequest, S3Client, S3,
+    AbortMultipartUploadRequest, CompleteMultipartUploadRequest, CompletedMultipartUpload, CompletedPart,
+    CreateMultipartUploadRequest, DeleteObjectRequest, GetObjectRequest, HeadObjectError, HeadObjectRequest,
+    ListObjectsV2Request, PutObjectRequest, S3Client, UploadPartRequest, S3,
 };
 #[cfg(feature = "external-services")]
-use std::io::Read;
+use rusoto_s3::util::{PreSignedRequest, PreSignedRequestOption};
+#[cfg(feature = "external-services")]
+use std::future::Future;
 #[cfg(feature = "external-services")]
 use std::str::FromStr;
+#[cfg(feature = "external-services")]
+use std::time::Duration;
+
+#[cfg(feature = "external-services")]
+use crate::config::Config;
+#[cfg(feature = "external-services")]
+use crate::models::storage::ObjectInfo;
+#[cfg(feature = "external-services")]
+use tokio::io::AsyncReadExt;
+
+/// Objects larger than this are uploaded in parts
+#[cfg(feature = "external-services")]
+const MULTIPART_THRESHOLD: usize = 16 * 1024 * 1024;
+
+/// Size of each part of a multipart upload (S3 requires at least 5 MiB)
+#[cfg(feature = "external-services")]
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// Attempts made for each request before a throttling or server error is returned
+#[cfg(feature = "external-services")]
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, doubled on each attempt after it
+#[cfg(feature = "external-services")]
+const BASE_DELAY: Duration = Duration::from_millis(200);
+
+#[cfg(feature = "external-services")]
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Static credentials when configured, otherwise the standard AWS chain
+/// (environment, profile, instance role); kept beside the client to sign URLs
+#[cfg(feature = "external-services")]
+#[derive(Clone)]
+enum S3Credentials {
+    Static(StaticProvider),
+    // Boxed: the chain's providers are several times the size of static keys
+    Chain(Box<DefaultCredentialsProvider>),
+}
+
+#[cfg(feature = "external-services")]
+#[async_trait::async_trait]
+impl ProvideAwsCredentials for S3Credentials {
+    async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
+        match self {
+            S3Credentials::Static(provider) => provider.credentials().await,
+            S3Credentials::Chain(provider) => provider.credentials().await,
+        }
+    }
+}
 
 #[cfg(feature = "external-services")]
 #[derive(Clone)]
 pub struct S3Service {
     client: S3Client,
     bucket: String,
+    region: Region,
+    credentials: S3Credentials,
 }
 
 // Manual Debug implementation since S3Client doesn't implement Debug
@@ -23,6 +89,7 @@ impl std::fmt::Debug for S3Service {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("S3Service")
             .field("bucket", &self.bucket)
+            .field("region", &self.region)
             .field("client", &"S3Client")
             .finish()
     }
@@ -30,46 +97,298 @@ impl std::fmt::Debug for S3Service {
 
 #[cfg(feature = "external-services")]
 impl S3Service {
-    pub fn new(region: String, bucket: String) -> Self {
-        let region = Region::from_str(&region).unwrap_or(Region::UsEast1);
-        let client = S3Client::new(region);
-        Self { client, bucket }
+    /// Client for `S3_BUCKET`, on AWS or on the S3-compatible store at
+    /// `S3_ENDPOINT`. Requests are addressed path-style (`{endpoint}/{bucket}/{key}`),
+    /// which MinIO and R2 both accept.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let region = match &config.s3_endpoint {
+            Some(endpoint) => Region::Custom {
+                name: config.aws_region.clone(),
+                endpoint: endpoint.trim_end_matches('/').to_string(),
+            },
+            None => Region::from_str(&config.aws_region).unwrap_or(Region::UsEast1),
+        };
+        let http = HttpClient::new().context("Failed to create the S3 HTTP client")?;
+        let credentials = match (&config.s3_access_key_id, &config.s3_secret_access_key) {
+            (Some(key), Some(secret)) => S3Credentials::Static(StaticProvider::new_minimal(key.clone(), secret.clone())),
+            (None, None) => S3Credentials::Chain(Box::new(
+                DefaultCredentialsProvider::new().context("Failed to load AWS credentials")?,
+            )),
+            _ => bail!("S3_ACCESS_KEY_ID and S3_SECRET_ACCESS_KEY must be set together"),
+        };
+        Ok(Self {
+            client: S3Client::new_with(http, credentials.clone(), region.clone()),
+            bucket: config.s3_bucket.clone(),
+            region,
+            credentials,
+        })
     }
 
-    /// Upload data to S3 bucket
-    pub async fn upload_file(&self, key: &str, data: Vec<u8>) -> Result<()> {
-        let req = PutObjectRequest {
-            bucket: self.bucket.clone(),
-            key: key.to_string(),
-            body: Some(data.into()),
-            ..Default::default()
-        };
+    /// Upload data to S3 bucket, in parts when it is larger than
+    /// `MULTIPART_THRESHOLD`
+    pub async fn upload_file(&self, key: &str, data: Bytes) -> Result<()> {
+        if data.len() > MULTIPART_THRESHOLD {
+            return self.upload_multipart(key, data).await;
+        }
 
-        self.client.put_object(req).await?;
+        with_retries(&format!("put object {}", key), || {
+            let req = PutObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                content_length: Some(data.len() as i64),
+                body: Some(byte_stream(data.clone())),
+                ..Default::default()
+            };
+            self.client.put_object(req)
+        })
+        .await?;
         Ok(())
     }
 
+    /// Upload data as a multipart upload of `PART_SIZE` parts, aborting the upload
+    /// if any part fails so S3 does not keep the parts already sent
+    async fn upload_multipart(&self, key: &str, data: Bytes) -> Result<()> {
+        let created = with_retries(&format!("start multipart upload of {}", key), || {
+            self.client.create_multipart_upload(CreateMultipartUploadRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                ..Default::default()
+            })
+        })
+        .await?;
+        let upload_id = created
+            .upload_id
+            .ok_or_else(|| anyhow!("S3 returned no upload id for {}", key))?;
+
+        match self.upload_parts(key, &upload_id, &data).await {
+            Ok(parts) => {
+                with_retries(&format!("complete multipart upload of {}", key), || {
+                    self.client.complete_multipart_upload(CompleteMultipartUploadRequest {
+                        bucket: self.bucket.clone(),
+                        key: key.to_string(),
+                        upload_id: upload_id.clone(),
+                        multipart_upload: Some(CompletedMultipartUpload { parts: Some(parts.clone()) }),
+                        ..Default::default()
+                    })
+                })
+                .await?;
+                log::info!("📤 Uploaded {} to S3 in {} parts", key, parts.len());
+                Ok(())
+            }
+            Err(e) => {
+                let abort = self
+                    .client
+                    .abort_multipart_upload(AbortMultipartUploadRequest {
+                        bucket: self.bucket.clone(),
+                        key: key.to_string(),
+                        upload_id: upload_id.clone(),
+                        ..Default::default()
+                    })
+                    .await;
+                if let Err(abort_error) = abort {
+                    log::warn!("⚠️ Failed to abort multipart upload {} of {}: {}", upload_id, key, abort_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn upload_parts(&self, key: &str, upload_id: &str, data: &Bytes) -> Result<Vec<CompletedPart>> {
+        let mut parts = Vec::new();
+        for (index, start) in (0..data.len()).step_by(PART_SIZE).enumerate() {
+            let part_number = index as i64 + 1;
+            let chunk = data.slice(start..(start + PART_SIZE).min(data.len()));
+            let uploaded = with_retries(&format!("upload part {} of {}", part_number, key), || {
+                self.client.upload_part(UploadPartRequest {
+                    bucket: self.bucket.clone(),
+                    key: key.to_string(),
+                    upload_id: upload_id.to_string(),
+                    part_number,
+                    content_length: Some(chunk.len() as i64),
+                    body: Some(byte_stream(chunk.clone())),
+                    ..Default::default()
+                })
+            })
+            .await?;
+            parts.push(CompletedPart {
+                e_tag: uploaded.e_tag,
+                part_number: Some(part_number),
+            });
+        }
+        Ok(parts)
+    }
+
     /// Download data from S3 bucket
-    pub async fn download_file(&self, key: &str) -> Result<Vec<u8>> {
+    pub async fn download_file(&self, key: &str) -> Result<Bytes> {
         self.get_object(&self.bucket, key).await
     }
-    
+
     /// Get object from any S3 bucket
-    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Vec<u8>> {
-        let req = GetObjectRequest {
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        let result = with_retries(&format!("get object {}/{}", bucket, key), || {
+            self.client.get_object(GetObjectRequest {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                ..Default::default()
+            })
+        })
+        .await?;
+
+        let body = result
+            .body
+            .ok_or_else(|| anyhow!("Object {}/{} has no body", bucket, key))?;
+        let mut data = Vec::with_capacity(result.content_length.unwrap_or(0).max(0) as usize);
+        body.into_async_read()
+            .read_to_end(&mut data)
+            .await
+            .context(format!("Failed to read object {}/{}", bucket, key))?;
+
+        Ok(data.into())
+    }
+
+    /// Remove an object; S3 reports success for keys that do not exist
+    pub async fn delete_object(&self, key: &str) -> Result<()> {
+        with_retries(&format!("delete object {}", key), || {
+            self.client.delete_object(DeleteObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                ..Default::default()
+            })
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Objects whose key starts with `prefix`, following continuation tokens
+    /// until the listing is complete
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let mut objects = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let page = with_retries(&format!("list objects under '{}'", prefix), || {
+                self.client.list_objects_v2(ListObjectsV2Request {
+                    bucket: self.bucket.clone(),
+                    prefix: Some(prefix.to_string()).filter(|p| !p.is_empty()),
+                    continuation_token: continuation_token.clone(),
+                    ..Default::default()
+                })
+            })
+            .await?;
+            objects.extend(page.contents.unwrap_or_default().into_iter().filter_map(|object| {
+                Some(ObjectInfo {
+                    key: object.key?,
+                    size: object.size.unwrap_or(0).max(0) as u64,
+                    last_modified: object.last_modified.as_deref().and_then(parse_timestamp),
+                })
+            }));
+            match page.next_continuation_token {
+                Some(token) if page.is_truncated == Some(true) => continuation_token = Some(token),
+                _ => break,
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    pub async fn head_object(&self, key: &str) -> Result<Option<ObjectInfo>> {
+        let result = with_retries(&format!("head object {}", key), || {
+            self.client.head_object(HeadObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_string(),
+                ..Default::default()
+            })
+        })
+        .await;
+        match result {
+            Ok(head) => Ok(Some(ObjectInfo {
+                key: key.to_string(),
+                size: head.content_length.unwrap_or(0).max(0) as u64,
+                last_modified: head.last_modified.as_deref().and_then(parse_timestamp),
+            })),
+            Err(e) if is_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A GET URL for an object signed with the service's credentials, valid for
+    /// `expires_in`; signing happens locally, without a request to S3
+    pub async fn presigned_url(&self, bucket: &str, key: &str, expires_in: Duration) -> Result<Option<String>> {
+        let credentials = self
+            .credentials
+            .credentials()
+            .await
+            .context("Failed to load credentials to sign a download URL")?;
+        let request = GetObjectRequest {
             bucket: bucket.to_string(),
             key: key.to_string(),
             ..Default::default()
         };
+        Ok(Some(request.get_presigned_url(&self.region, &credentials, &PreSignedRequestOption { expires_in })))
+    }
+}
 
-        let result = self.client.get_object(req).await
-            .context(format!("Failed to get object {}/{}", bucket, key))?;
-            
-        let mut body = result.body.unwrap().into_blocking_read();
-        let mut data = Vec::new();
-        body.read_to_end(&mut data)
-            .context("Failed to read object body")?;
-        
-        Ok(data)
+/// Run an S3 request, retrying throttling (429, `SlowDown`), server errors and
+/// dropped connections with jittered exponential backoff
+#[cfg(feature = "external-services")]
+async fn with_retries<T, E, F, Fut>(action: &str, mut request: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RusotoError<E>>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < MAX_ATTEMPTS && is_transient(&e) => {
+                let backoff = BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt - 1)).min(MAX_DELAY);
+                let delay = backoff.mul_f64(rand::thread_rng().gen_range(0.5..1.0));
+                log::warn!("⏳ S3 {} failed (attempt {}/{}), retrying in {:.2?}: {}", action, attempt, MAX_ATTEMPTS, delay, e);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(anyhow::Error::new(e).context(format!("Failed to {}", action))),
+        }
     }
 }
+
+#[cfg(feature = "external-services")]
+fn is_transient<E>(error: &RusotoError<E>) -> bool {
+    match error {
+        RusotoError::HttpDispatch(_) => true,
+        RusotoError::Unknown(response) => {
+            response.status.as_u16() == 429
+                || response.status.is_server_error()
+                || response.body_as_str().contains("SlowDown")
+        }
+        _ => false,
+    }
+}
+
+/// HEAD responses carry no body, so a missing key surfaces as a bare 404 rather
+/// than `NoSuchKey`
+#[cfg(feature = "external-services")]
+fn is_not_found(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<RusotoError<HeadObjectError>>() {
+        Some(RusotoError::Service(HeadObjectError::NoSuchKey(_))) => true,
+        Some(RusotoError::Unknown(response)) => response.status.as_u16() == 404,
+        _ => false,
+    }
+}
+
+/// S3 timestamps: RFC 3339 in listings, RFC 2822 in object headers
+#[cfg(feature = "external-services")]
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_rfc2822(value))
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+/// Wraps a buffer as a request body; cloning `Bytes` for a retry shares the
+/// buffer rather than copying it
+#[cfg(feature = "external-services")]
+fn byte_stream(data: Bytes) -> ByteStream {
+    let size = data.len();
+    ByteStream::new_with_size(futures::stream::once(async move { Ok(data) }), size)
+}
@@ -0,0 +1,183 @@
+use anyhow::{bail, Context, Result};
+use polars::io::mmap::MmapBytesReader;
+use polars::prelude::*;
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io::{Cursor, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Rows parsed to measure how much memory a row takes once parsed
+const ESTIMATE_ROWS: usize = 1000;
+
+/// Directory under `SPILL_DIR` spill files are written in
+const SPILL_SUBDIR: &str = "g-data-pipeline-spill";
+
+/// How large a CSV's `DataFrame` would be, measured on its first rows
+#[derive(Debug, Clone)]
+pub struct FrameEstimate {
+    /// Data rows, counted as line breaks after the header; quoted line breaks
+    /// make it an overestimate
+    pub rows: usize,
+    pub bytes_per_row: usize,
+    /// Columns and types inferred from the first rows, as processing reads them
+    pub schema: SchemaRef,
+}
+
+impl FrameEstimate {
+    pub fn frame_bytes(&self) -> u64 {
+        self.rows as u64 * self.bytes_per_row as u64
+    }
+}
+
+/// Estimate the parsed size of `csv` from its first rows and its line count
+pub fn estimate_frame(csv: &[u8]) -> Result<FrameEstimate> {
+    let head = CsvReader::new(Cursor::new(csv))
+        .infer_schema(Some(100))
+        .has_header(true)
+        .with_try_parse_dates(true)
+        .with_n_rows(Some(ESTIMATE_ROWS))
+        .finish()
+        .context("Failed to parse CSV data")?;
+
+    let line_breaks = csv.iter().filter(|&&byte| byte == b'\n').count();
+    let ends_with_break = csv.last() == Some(&b'\n');
+    // The header's line break, or the last row's missing one, offsets the count by one
+    let rows = if ends_with_break { line_breaks.saturating_sub(1) } else { line_breaks };
+    Ok(FrameEstimate {
+        rows: rows.max(head.height()),
+        bytes_per_row: head.estimated_size().div_ceil(head.height().max(1)),
+        schema: Arc::new(head.schema()),
+    })
+}
+
+/// An uploaded file written to local disk so it can be read in chunks instead
+/// of held in memory; removed when dropped
+#[derive(Debug)]
+pub struct SpillFile {
+    path: PathBuf,
+}
+
+impl SpillFile {
+    /// Write `data` to a file only this user can read, in a directory only this
+    /// user can enter: the spilled copy is plaintext even when storage is encrypted,
+    /// and `dir` is usually the shared temp directory
+    pub fn write(dir: &Path, job_id: Uuid, data: &[u8]) -> Result<Self> {
+        let dir = private_dir(dir)?;
+        let path = dir.join(format!("{}.csv", job_id));
+        let mut options = OpenOptions::new();
+        // Never reuse or follow a file planted under this name
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+        // Removed from here on, even when writing fails part way
+        let spill = Self { path };
+        file.write_all(data).with_context(|| format!("Failed to write {}", spill.path.display()))?;
+        Ok(spill)
+    }
+
+    /// Parse the file `chunk_rows` rows at a time as `schema`, handing each chunk to `f`
+    pub fn read_chunks(&self, schema: SchemaRef, chunk_rows: usize, mut f: impl FnMut(DataFrame) -> Result<()>) -> Result<()> {
+        let file = File::open(&self.path).with_context(|| format!("Failed to open {}", self.path.display()))?;
+        let mut reader = CsvReader::new(Box::new(file) as Box<dyn MmapBytesReader>)
+            .has_header(true)
+            .with_try_parse_dates(true)
+            .with_chunk_size(chunk_rows)
+            .batched_mmap(Some(schema))
+            .context("Failed to read the spilled CSV")?;
+        while let Some(chunks) = reader.next_batches(1).context("Failed to parse CSV data")? {
+            for chunk in chunks {
+                f(chunk)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `SPILL_SUBDIR` under `dir`, created if needed and restricted to this user. Only
+/// its owner can change its mode, so one planted by another user is refused.
+fn private_dir(dir: &Path) -> Result<PathBuf> {
+    let private = dir.join(SPILL_SUBDIR);
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder
+        .create(&private)
+        .with_context(|| format!("Failed to create spill directory {}", private.display()))?;
+
+    let metadata = std::fs::symlink_metadata(&private)
+        .with_context(|| format!("Failed to inspect spill directory {}", private.display()))?;
+    if !metadata.is_dir() {
+        bail!("Spill directory {} is not a directory", private.display());
+    }
+    #[cfg(unix)]
+    std::fs::set_permissions(&private, std::fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("Spill directory {} belongs to another user", private.display()))?;
+    Ok(private)
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("⚠️ Failed to remove spill file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn spill_files_are_private_and_removed() {
+        let root = std::env::temp_dir().join(format!("spill-test-{}", Uuid::new_v4()));
+        let job_id = Uuid::new_v4();
+        let spill = SpillFile::write(&root, job_id, b"a,b\n1,2\n").unwrap();
+
+        let dir = root.join(SPILL_SUBDIR);
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&spill.path), 0o600);
+        assert_eq!(std::fs::read(&spill.path).unwrap(), b"a,b\n1,2\n");
+
+        // A second spill under the same name does not overwrite the first
+        assert!(SpillFile::write(&root, job_id, b"x").is_err());
+        assert_eq!(std::fs::read(&spill.path).unwrap(), b"a,b\n1,2\n");
+
+        let path = spill.path.clone();
+        drop(spill);
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_loosened_spill_directory_is_tightened() {
+        let root = std::env::temp_dir().join(format!("spill-test-{}", Uuid::new_v4()));
+        let dir = root.join(SPILL_SUBDIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+
+        let spill = SpillFile::write(&root, Uuid::new_v4(), b"a\n1\n").unwrap();
+        assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        drop(spill);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_symlinked_spill_directory_is_refused() {
+        let root = std::env::temp_dir().join(format!("spill-test-{}", Uuid::new_v4()));
+        let elsewhere = root.join("elsewhere");
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, root.join(SPILL_SUBDIR)).unwrap();
+
+        assert!(SpillFile::write(&root, Uuid::new_v4(), b"a\n1\n").is_err());
+        assert_eq!(std::fs::read_dir(&elsewhere).unwrap().count(), 0);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::{bail, Context, Result};
+use sqlparser::ast::{
+    Distinct, Expr, FunctionArg, FunctionArgExpr, GroupByExpr, JoinConstraint, JoinOperator, OrderByExpr, Query,
+    Select, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, WindowType,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+
+use crate::services::query_translator::SQL_TABLE_NAME;
+
+/// Fail unless `sql` is a single query that only reads the table `data` and the
+/// CTEs it defines. Polars' SQL engine also runs `CREATE TABLE`, `DROP`, `SHOW`,
+/// `EXPLAIN` and the table functions `read_csv`, `read_ipc`, `read_json` and
+/// `read_parquet`, which read any file the server can: its environment, other
+/// tenants' uploads. The statement is parsed with the dialect Polars uses, and
+/// every relation it names is checked before the engine sees it.
+pub fn check(sql: &str) -> Result<()> {
+    let statements = Parser::parse_sql(&GenericDialect, sql).context("Failed to parse SQL query")?;
+    let [statement] = statements.as_slice() else {
+        bail!("SQL must be a single SELECT statement, got {}", statements.len());
+    };
+    match statement {
+        Statement::Query(query) => check_query(query, &[]),
+        _ => bail!("Only SELECT statements are allowed"),
+    }
+}
+
+/// `ctes` are the names defined by enclosing `WITH` clauses
+fn check_query(query: &Query, ctes: &[String]) -> Result<()> {
+    let mut scope = ctes.to_vec();
+    if let Some(with) = &query.with {
+        for cte in &with.cte_tables {
+            check_query(&cte.query, &scope)?;
+            scope.push(cte.alias.name.value.clone());
+        }
+    }
+    check_set_expr(&query.body, &scope)?;
+    check_order_by(&query.order_by, &scope)?;
+    if let Some(limit) = &query.limit {
+        check_expr(limit, &scope)?;
+    }
+    if let Some(offset) = &query.offset {
+        check_expr(&offset.value, &scope)?;
+    }
+    Ok(())
+}
+
+fn check_set_expr(body: &SetExpr, scope: &[String]) -> Result<()> {
+    match body {
+        SetExpr::Select(select) => check_select(select, scope),
+        SetExpr::Query(query) => check_query(query, scope),
+        SetExpr::SetOperation { left, right, .. } => {
+            check_set_expr(left, scope)?;
+            check_set_expr(right, scope)
+        }
+        other => bail!("Unsupported SQL: {}", other),
+    }
+}
+
+fn check_select(select: &Select, scope: &[String]) -> Result<()> {
+    if select.into.is_some() {
+        bail!("SELECT INTO is not allowed");
+    }
+    for table in &select.from {
+        check_from(table, scope)?;
+    }
+    for item in &select.projection {
+        match item {
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => check_expr(expr, scope)?,
+            SelectItem::QualifiedWildcard(..) | SelectItem::Wildcard(_) => {}
+        }
+    }
+    if let Some(Distinct::On(exprs)) = &select.distinct {
+        check_exprs(exprs, scope)?;
+    }
+    if let GroupByExpr::Expressions(exprs) = &select.group_by {
+        check_exprs(exprs, scope)?;
+    }
+    for expr in select.selection.iter().chain(&select.having).chain(&select.qualify) {
+        check_expr(expr, scope)?;
+    }
+    check_exprs(&select.sort_by, scope)
+}
+
+fn check_from(table: &TableWithJoins, scope: &[String]) -> Result<()> {
+    check_relation(&table.relation, scope)?;
+    for join in &table.joins {
+        check_relation(&join.relation, scope)?;
+        let constraint = match &join.join_operator {
+            JoinOperator::Inner(constraint)
+            | JoinOperator::LeftOuter(constraint)
+            | JoinOperator::RightOuter(constraint)
+            | JoinOperator::FullOuter(constraint)
+            | JoinOperator::LeftSemi(constraint)
+            | JoinOperator::RightSemi(constraint)
+            | JoinOperator::LeftAnti(constraint)
+            | JoinOperator::RightAnti(constraint) => Some(constraint),
+            JoinOperator::CrossJoin | JoinOperator::CrossApply | JoinOperator::OuterApply => None,
+        };
+        if let Some(JoinConstraint::On(expr)) = constraint {
+            check_expr(expr, scope)?;
+        }
+    }
+    Ok(())
+}
+
+/// Only the dataset and CTEs may be read; a table with arguments is a table function
+fn check_relation(relation: &TableFactor, scope: &[String]) -> Result<()> {
+    match relation {
+        TableFactor::Table { name, args: Some(_), .. } => bail!("Table function {} is not allowed", name),
+        TableFactor::Table { name, .. } => match name.0.as_slice() {
+            [table] if table.value == SQL_TABLE_NAME || scope.contains(&table.value) => Ok(()),
+            _ => bail!("Unknown table {}; query the table {}", name, SQL_TABLE_NAME),
+        },
+        TableFactor::Derived { subquery, .. } => check_query(subquery, scope),
+        TableFactor::NestedJoin { table_with_joins, .. } => check_from(table_with_joins, scope),
+        other => bail!("Unsupported relation: {}", other),
+    }
+}
+
+/// Expressions Polars evaluates, walked for the subqueries they may hold; any
+/// other expression is one Polars would refuse anyway
+fn check_expr(expr: &Expr, scope: &[String]) -> Result<()> {
+    match expr {
+        Expr::Identifier(_) | Expr::CompoundIdentifier(_) | Expr::Value(_) | Expr::TypedString { .. } => Ok(()),
+        Expr::Nested(expr)
+        | Expr::UnaryOp { expr, .. }
+        | Expr::Cast { expr, .. }
+        | Expr::TryCast { expr, .. }
+        | Expr::Ceil { expr, .. }
+        | Expr::Floor { expr, .. }
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::IsTrue(expr)
+        | Expr::IsNotTrue(expr)
+        | Expr::IsFalse(expr)
+        | Expr::IsNotFalse(expr) => check_expr(expr, scope),
+        Expr::BinaryOp { left, right, .. }
+        | Expr::AnyOp { left, right, .. }
+        | Expr::AllOp { left, right, .. }
+        | Expr::IsDistinctFrom(left, right)
+        | Expr::IsNotDistinctFrom(left, right) => {
+            check_expr(left, scope)?;
+            check_expr(right, scope)
+        }
+        Expr::Between { expr, low, high, .. } => check_exprs([expr, low, high].map(|expr| &**expr), scope),
+        Expr::Like { expr, pattern, .. } | Expr::ILike { expr, pattern, .. } => {
+            check_expr(expr, scope)?;
+            check_expr(pattern, scope)
+        }
+        Expr::InList { expr, list, .. } => {
+            check_expr(expr, scope)?;
+            check_exprs(list, scope)
+        }
+        Expr::InSubquery { expr, subquery, .. } => {
+            check_expr(expr, scope)?;
+            check_query(subquery, scope)
+        }
+        Expr::Subquery(query) | Expr::Exists { subquery: query, .. } => check_query(query, scope),
+        Expr::Trim { expr, trim_what, .. } => {
+            check_expr(expr, scope)?;
+            trim_what.iter().try_for_each(|what| check_expr(what, scope))
+        }
+        Expr::Case { operand, conditions, results, else_result } => {
+            operand.iter().chain(else_result).try_for_each(|expr| check_expr(expr, scope))?;
+            check_exprs(conditions, scope)?;
+            check_exprs(results, scope)
+        }
+        Expr::Function(function) => {
+            for arg in &function.args {
+                if let FunctionArg::Named { arg: FunctionArgExpr::Expr(expr), .. }
+                | FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) = arg
+                {
+                    check_expr(expr, scope)?;
+                }
+            }
+            check_order_by(&function.order_by, scope)?;
+            if let Some(WindowType::WindowSpec(window)) = &function.over {
+                check_exprs(&window.partition_by, scope)?;
+                check_order_by(&window.order_by, scope)?;
+            }
+            Ok(())
+        }
+        Expr::ArrayAgg(agg) => {
+            check_expr(&agg.expr, scope)?;
+            check_order_by(agg.order_by.iter().flatten(), scope)?;
+            agg.limit.iter().try_for_each(|limit| check_expr(limit, scope))
+        }
+        other => bail!("Unsupported SQL expression: {}", other),
+    }
+}
+
+fn check_exprs<'a>(exprs: impl IntoIterator<Item = &'a Expr>, scope: &[String]) -> Result<()> {
+    exprs.into_iter().try_for_each(|expr| check_expr(expr, scope))
+}
+
+fn check_order_by<'a>(order_by: impl IntoIterator<Item = &'a OrderByExpr>, scope: &[String]) -> Result<()> {
+    order_by.into_iter().try_for_each(|order| check_expr(&order.expr, scope))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use polars::prelude::*;
+
+    use crate::services::query_translator::QueryTranslator;
+
+    fn refused(sql: &str) -> String {
+        format!("{:#}", check(sql).expect_err(sql))
+    }
+
+    fn dataset() -> DataFrame {
+        df!("region" => ["north", "south", "north"], "sales" => [10i64, 20, 30]).unwrap()
+    }
+
+    #[test]
+    fn refuses_table_functions() {
+        for function in ["read_csv", "read_ipc", "read_json", "read_parquet", "READ_CSV"] {
+            let sql = format!("SELECT * FROM {}('/proc/self/environ')", function);
+            assert!(refused(&sql).contains("not allowed"), "{}", sql);
+        }
+        let hidden = [
+            "SELECT * FROM data JOIN read_csv('storage/tenants/other/uploads/a.csv') AS o ON data.region = o.region",
+            "SELECT * FROM data WHERE region IN (SELECT region FROM read_ipc('storage/tenants/other/processed/a.arrow'))",
+            "WITH leaked AS (SELECT * FROM read_json('/etc/passwd')) SELECT * FROM leaked",
+            "SELECT * FROM data UNION ALL SELECT * FROM read_csv('/proc/self/environ')",
+            "SELECT * FROM (SELECT * FROM read_csv('/proc/self/environ')) AS t",
+            "SELECT CASE WHEN EXISTS (SELECT 1 FROM read_csv('/etc/passwd')) THEN 1 END FROM data",
+        ];
+        for sql in hidden {
+            assert!(refused(sql).contains("not allowed"), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn refuses_other_tables() {
+        for sql in ["SELECT * FROM jobs", "SELECT * FROM public.data", "SELECT * FROM data JOIN other ON data.a = other.a"] {
+            assert!(refused(sql).contains("Unknown table"), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn refuses_statements_other_than_one_select() {
+        for sql in [
+            "CREATE TABLE copy AS SELECT * FROM data",
+            "DROP TABLE data",
+            "SHOW TABLES",
+            "EXPLAIN SELECT * FROM data",
+            "SELECT * FROM data; SELECT * FROM data",
+            "",
+        ] {
+            check(sql).expect_err(sql);
+        }
+    }
+
+    #[test]
+    fn accepts_queries_over_data_and_ctes() {
+        for sql in [
+            "SELECT * FROM data",
+            "SELECT region, SUM(sales) AS total FROM data WHERE sales > 5 GROUP BY region HAVING SUM(sales) > 1 ORDER BY total DESC LIMIT 10",
+            "WITH big AS (SELECT * FROM data WHERE sales > 15) SELECT COUNT(*) FROM big",
+            "SELECT * FROM data WHERE region IN (SELECT region FROM data WHERE sales > 25)",
+            "SELECT d.region, CASE WHEN d.sales > 15 THEN 'high' ELSE 'low' END FROM data AS d",
+            "SELECT region FROM data UNION SELECT region FROM data",
+        ] {
+            check(sql).unwrap_or_else(|e| panic!("{}: {:#}", sql, e));
+        }
+    }
+
+    #[test]
+    fn execute_sql_refuses_before_reading_files() {
+        let path = std::env::temp_dir().join(format!("sql-guard-{}.csv", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "secret\nhunter2\n").unwrap();
+        let translator = QueryTranslator::new();
+        for function in ["read_csv", "read_ipc", "read_json"] {
+            let sql = format!("SELECT * FROM {}('{}')", function, path.display());
+            let error = translator.execute_sql(dataset(), &sql).expect_err(&sql);
+            assert!(format!("{:#}", error).contains("not allowed"), "{}", sql);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let (df, truncated) = translator.execute_sql(dataset(), "SELECT SUM(sales) AS total FROM data").unwrap();
+        assert_eq!(df.column("total").unwrap().i64().unwrap().get(0), Some(60));
+        assert!(!truncated);
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use log::info;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::models::storage::{ObjectInfo, StorageBackendKind};
+use crate::services::encryption::{is_encrypted, DatasetCipher};
+use crate::services::fs_storage::FsStorageService;
+use crate::services::memory_s3::MemoryS3Service;
+#[cfg(feature = "external-services")]
+use crate::services::s3::S3Service;
+use crate::services::S3ServiceTrait;
+
+/// The storage backend selected by `STORAGE_BACKEND`, so the routes are built once
+/// whichever backend the deployment uses. With `STORAGE_ENCRYPTION_KEY` set,
+/// objects are encrypted here before any backend sees them and decrypted on
+/// read, so neither `./storage` nor the bucket holds plaintext datasets.
+#[derive(Clone, Debug)]
+pub struct StorageService {
+    backend: Backend,
+    cipher: Option<DatasetCipher>,
+}
+
+#[derive(Clone, Debug)]
+enum Backend {
+    Memory(MemoryS3Service),
+    Fs(FsStorageService),
+    // Boxed: the S3 client and its credentials dwarf the local backends
+    #[cfg(feature = "external-services")]
+    S3(Box<S3Service>),
+}
+
+impl StorageService {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let backend = match config.storage_backend {
+            StorageBackendKind::Memory => Backend::Memory(MemoryS3Service::new(config.memory_storage_max_bytes, config.storage_fsync)?),
+            StorageBackendKind::Fs => Backend::Fs(FsStorageService::new(&config.storage_root, config.storage_fsync)?),
+            #[cfg(feature = "external-services")]
+            StorageBackendKind::S3 => Backend::S3(Box::new(S3Service::from_config(config)?)),
+            #[cfg(not(feature = "external-services"))]
+            StorageBackendKind::S3 => bail!("STORAGE_BACKEND=s3 needs a build with the external-services feature"),
+        };
+        let cipher = config
+            .storage_encryption_key
+            .as_deref()
+            .map(DatasetCipher::from_base64_key)
+            .transpose()?;
+        if cipher.is_some() {
+            info!("🔐 Stored objects are encrypted at rest");
+        }
+        Ok(Self { backend, cipher })
+    }
+
+    /// Decrypt an object read from the backend
+    fn open(&self, key: &str, data: Bytes) -> Result<Bytes> {
+        match &self.cipher {
+            Some(cipher) => cipher.decrypt(data).map_err(|e| e.context(format!("Failed to decrypt object {}", key))),
+            None if is_encrypted(&data) => bail!("Object {} is encrypted; set STORAGE_ENCRYPTION_KEY to read it", key),
+            None => Ok(data),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl S3ServiceTrait for StorageService {
+    async fn upload_file(&self, key: &str, data: Bytes) -> Result<()> {
+        let data = match &self.cipher {
+            Some(cipher) => cipher.encrypt(&data)?.into(),
+            None => data,
+        };
+        match &self.backend {
+            Backend::Memory(service) => service.upload_file(key, data).await,
+            Backend::Fs(service) => service.upload_file(key, data).await,
+            #[cfg(feature = "external-services")]
+            Backend::S3(service) => service.upload_file(key, data).await,
+        }
+    }
+
+    async fn download_file(&self, key: &str) -> Result<Bytes> {
+        let data = match &self.backend {
+            Backend::Memory(service) => service.download_file(key).await,
+            Backend::Fs(service) => service.download_file(key).await,
+            #[cfg(feature = "external-services")]
+            Backend::S3(service) => service.download_file(key).await,
+        }?;
+        self.open(key, data)
+    }
+
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        let data = match &self.backend {
+            Backend::Memory(service) => service.get_object(bucket, key).await,
+            Backend::Fs(service) => service.get_object(bucket, key).await,
+            #[cfg(feature = "external-services")]
+            Backend::S3(service) => service.get_object(bucket, key).await,
+        }?;
+        self.open(key, data)
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<()> {
+        match &self.backend {
+            Backend::Memory(service) => service.delete_object(key).await,
+            Backend::Fs(service) => service.delete_object(key).await,
+            #[cfg(feature = "external-services")]
+            Backend::S3(service) => service.delete_object(key).await,
+        }
+    }
+
+    async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        match &self.backend {
+            Backend::Memory(service) => service.list_objects(prefix).await,
+            Backend::Fs(service) => service.list_objects(prefix).await,
+            #[cfg(feature = "external-services")]
+            Backend::S3(service) => service.list_objects(prefix).await,
+        }
+    }
+
+    async fn head_object(&self, key: &str) -> Result<Option<ObjectInfo>> {
+        match &self.backend {
+            Backend::Memory(service) => service.head_object(key).await,
+            Backend::Fs(service) => service.head_object(key).await,
+            #[cfg(feature = "external-services")]
+            Backend::S3(service) => service.head_object(key).await,
+        }
+    }
+
+    /// A presigned URL would hand out the ciphertext, so encrypted deployments
+    /// serve downloads through the API instead
+    async fn presigned_url(&self, bucket: &str, key: &str, expires_in: Duration) -> Result<Option<String>> {
+        if self.cipher.is_some() {
+            return Ok(None);
+        }
+        match &self.backend {
+            Backend::Memory(service) => service.presigned_url(bucket, key, expires_in).await,
+            Backend::Fs(service) => service.presigned_url(bucket, key, expires_in).await,
+            #[cfg(feature = "external-services")]
+            Backend::S3(service) => service.presigned_url(bucket, key, expires_in).await,
+        }
+    }
+
+    fn backend_name(&self) -> &'static str {
+        match &self.backend {
+            Backend::Memory(service) => S3ServiceTrait::backend_name(service),
+            Backend::Fs(service) => S3ServiceTrait::backend_name(service),
+            #[cfg(feature = "external-services")]
+            Backend::S3(service) => S3ServiceTrait::backend_name(service.as_ref()),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::models::conversation::DatasetMetadata;
+use crate::models::response::Insights;
+
+/// Fewest and most questions suggested per dataset
+pub const MIN_SUGGESTIONS: usize = 5;
+pub const MAX_SUGGESTIONS: usize = 8;
+
+/// Categorical columns with more distinct values than this make poor groups
+const MAX_GROUP_VALUES: usize = 50;
+
+/// Questions built from the dataset's schema and, when cached, its insights. They
+/// are phrased the way the rules translator reads them, so each one can be asked
+/// as-is without the AI service.
+pub fn rule_based_suggestions(metadata: &DatasetMetadata, insights: Option<&Insights>) -> Vec<String> {
+    let has_type = |column: &str, types: &[&str]| {
+        metadata
+            .data_types
+            .get(column)
+            .is_some_and(|t| types.contains(&t.as_str()))
+    };
+    let stats = |column: &str| insights.and_then(|i| i.column_statistics.iter().find(|s| s.name == column));
+
+    let numeric: Vec<&String> = metadata
+        .columns
+        .iter()
+        .filter(|c| has_type(c, &["integer", "unsigned integer", "float"]))
+        // Identifiers are numbers nobody averages
+        .filter(|c| stats(c).is_none_or(|s| s.unique_count < metadata.row_count || metadata.row_count <= 1))
+        .collect();
+    let dates: Vec<&String> = metadata.columns.iter().filter(|c| has_type(c, &["date", "datetime"])).collect();
+    let categories: Vec<&String> = metadata
+        .columns
+        .iter()
+        .filter(|c| has_type(c, &["string", "boolean"]))
+        .filter(|c| match stats(c) {
+            Some(s) => s.pii_types.is_empty() && s.unique_count > 1 && s.unique_count <= MAX_GROUP_VALUES,
+            None => !looks_like_identifier(c),
+        })
+        .collect();
+
+    // The strongest category–measure pair from the insights leads
+    let (category, measure) = insights
+        .and_then(|i| i.categorical_associations.as_ref())
+        .and_then(|associations| {
+            associations
+                .iter()
+                .filter(|a| categories.contains(&&a.categorical_column) && numeric.contains(&&a.numeric_column))
+                .max_by(|a, b| a.correlation_ratio.total_cmp(&b.correlation_ratio))
+                .map(|a| (Some(&a.categorical_column), Some(&a.numeric_column)))
+        })
+        .unwrap_or((categories.first().copied(), numeric.first().copied()));
+
+    let mut questions = Vec::new();
+    if let (Some(category), Some(measure)) = (category, measure) {
+        questions.push(format!("What is the average {} by {}?", human(measure), human(category)));
+        questions.push(format!("What are the top 5 {} by total {}?", plural(category), human(measure)));
+    }
+    if let (Some(date), Some(measure)) = (dates.first(), measure) {
+        questions.push(format!("Plot {} by {}", human(measure), human(date)));
+        questions.push(format!("What is the 7-day rolling average of {}?", human(measure)));
+    }
+    if let Some(category) = category {
+        questions.push(format!("How many unique {} are there?", plural(category)));
+    }
+    if let Some(measure) = measure {
+        match stats(measure).and_then(|s| s.percentile_75.clone()) {
+            Some(p75) => questions.push(format!("Show rows where {} is over {}", human(measure), p75)),
+            None => questions.push(format!("What is the median {}?", human(measure))),
+        }
+    }
+    // Two strongly correlated measures are worth charting against each other
+    if let Some((pair, _)) = insights.and_then(|i| i.correlations.as_ref()).and_then(|correlations| {
+        correlations
+            .iter()
+            .filter(|(_, r)| r.abs() >= 0.5)
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+    }) {
+        if let Some((a, b)) = correlation_columns(pair, &numeric) {
+            questions.push(format!("Plot {} by {}", human(a), human(b)));
+        }
+    }
+    if let Some(anomaly) = insights
+        .and_then(|i| i.anomalies.as_ref())
+        .and_then(|anomalies| anomalies.first())
+    {
+        questions.push(format!(
+            "What is the total {} for {} after {}?",
+            human(&anomaly.measure_column),
+            human(&anomaly.date_column),
+            anomaly.period
+        ));
+    }
+    for (category, measure) in categories.iter().skip(1).zip(numeric.iter().skip(1)) {
+        questions.push(format!("What is the total {} by {}?", human(measure), human(category)));
+    }
+    if let Some(measure) = numeric.get(1) {
+        questions.push(format!("What is the maximum {}?", human(measure)));
+    }
+    questions.push("Show the first 10 rows".to_string());
+    questions.push("Describe the dataset".to_string());
+
+    let mut unique = Vec::new();
+    for question in questions {
+        if !unique.contains(&question) {
+            unique.push(question);
+        }
+    }
+    unique.truncate(MAX_SUGGESTIONS);
+    unique
+}
+
+/// The two columns of a correlation key such as `price_units`, which joins names
+/// that may themselves contain underscores
+fn correlation_columns<'a>(pair: &str, numeric: &[&'a String]) -> Option<(&'a String, &'a String)> {
+    numeric.iter().find_map(|a| {
+        let rest = pair.strip_prefix(a.as_str())?.strip_prefix('_')?;
+        numeric.iter().find(|b| b.as_str() == rest).map(|b| (*a, *b))
+    })
+}
+
+fn looks_like_identifier(column: &str) -> bool {
+    let column = column.to_lowercase();
+    column == "id" || column.ends_with("_id") || column.contains("email") || column.contains("phone") || column.contains("name")
+}
+
+/// Column name as it would be written in a question
+fn human(column: &str) -> String {
+    column.replace(['_', '-'], " ")
+}
+
+fn plural(column: &str) -> String {
+    let name = human(column);
+    if name.ends_with('s') {
+        name
+    } else {
+        format!("{}s", name)
+    }
+}
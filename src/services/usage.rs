@@ -0,0 +1,95 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::HashMap;
+
+use crate::models::usage::{TokenUsage, UsageEntry, UsageQuery, UsageReport, UserUsage};
+use crate::services::DatabaseServiceTrait;
+
+/// Running totals of the tokens and estimated cost of AI calls, kept in the `usage`
+/// table so spend can be attributed to jobs, tenants, users and days across restarts
+/// and replicas
+#[derive(Clone, Debug)]
+pub struct UsageLedger<D> {
+    db_service: D,
+}
+
+impl<D> UsageLedger<D>
+where
+    D: DatabaseServiceTrait + Clone + std::fmt::Debug,
+{
+    pub fn new(db_service: D) -> Self {
+        Self { db_service }
+    }
+
+    /// Add the usage of AI calls made today for a job; calls answered without the
+    /// AI service consume nothing and are not recorded
+    pub async fn record(
+        &self,
+        job_id: &str,
+        tenant_id: Option<&str>,
+        user_id: Option<&str>,
+        conversation_id: Option<&str>,
+        usage: TokenUsage,
+    ) -> Result<()> {
+        if usage.total_tokens() == 0 {
+            return Ok(());
+        }
+        self.db_service
+            .record_usage(&UsageEntry {
+                date: Utc::now().date_naive(),
+                job_id: job_id.to_string(),
+                tenant_id: tenant_id.map(str::to_string),
+                user_id: user_id.map(str::to_string),
+                conversation_id: conversation_id.map(str::to_string),
+                usage,
+            })
+            .await
+    }
+
+    /// Tokens a tenant's AI calls consumed today, for its daily quota
+    pub async fn tenant_tokens_today(&self, tenant_id: &str) -> Result<u64> {
+        let today = Utc::now().date_naive();
+        let entries = self
+            .db_service
+            .list_usage(&UsageQuery {
+                tenant_id: Some(tenant_id.to_string()),
+                from: Some(today),
+                to: Some(today),
+                ..Default::default()
+            })
+            .await?;
+        Ok(entries.iter().map(|entry| entry.usage.total_tokens()).sum())
+    }
+
+    /// Usage matching the query, newest day first
+    pub async fn report(&self, query: &UsageQuery) -> Result<UsageReport> {
+        let mut matching = self.db_service.list_usage(query).await?;
+
+        matching.sort_by(|a, b| {
+            b.date
+                .cmp(&a.date)
+                .then_with(|| a.job_id.cmp(&b.job_id))
+                .then_with(|| a.conversation_id.cmp(&b.conversation_id))
+        });
+
+        let mut total = TokenUsage::default();
+        let mut by_user: HashMap<Option<String>, TokenUsage> = HashMap::new();
+        for entry in &matching {
+            total += entry.usage;
+            *by_user.entry(entry.user_id.clone()).or_default() += entry.usage;
+        }
+        let mut by_user: Vec<UserUsage> = by_user
+            .into_iter()
+            .map(|(user_id, usage)| UserUsage { user_id, usage })
+            .collect();
+        by_user.sort_by(|a, b| b.usage.estimated_cost_usd.total_cmp(&a.usage.estimated_cost_usd));
+
+        Ok(UsageReport {
+            from: query.from,
+            to: query.to,
+            total,
+            by_user,
+            entries: matching,
+        })
+    }
+}